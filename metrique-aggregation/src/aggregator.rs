@@ -62,25 +62,26 @@ where
             merge_config,
         }
     }
+}
 
-    fn get_or_create_accum<'a>(
-        storage: &'a mut hashbrown::HashMap<KeyTy<'static, T>, AggregateTy<T>>,
-        merge_config: &<T::Source as Merge>::MergeConfig,
-        entry: &T::Source,
-    ) -> &'a mut AggregateTy<T> {
-        let borrowed_key = T::Key::from_source(entry);
-        let hash = storage.hasher().hash_one(&borrowed_key);
-
-        match storage
-            .raw_entry_mut()
-            .from_hash(hash, |k| T::Key::static_key_matches(k, &borrowed_key))
-        {
-            RawEntryMut::Occupied(occupied) => occupied.into_mut(),
-            RawEntryMut::Vacant(vacant) => {
-                let static_key = T::Key::static_key(&borrowed_key);
-                let new_value = T::Source::new_merged(merge_config);
-                vacant.insert_hashed_nocheck(hash, static_key, new_value).1
-            }
+/// Return the accumulator of the key of `entry` in `storage`, creating it if needed
+pub(crate) fn get_or_create_accum<'a, T: AggregateStrategy>(
+    storage: &'a mut hashbrown::HashMap<KeyTy<'static, T>, AggregateTy<T>>,
+    merge_config: &<T::Source as Merge>::MergeConfig,
+    entry: &T::Source,
+) -> &'a mut AggregateTy<T> {
+    let borrowed_key = T::Key::from_source(entry);
+    let hash = storage.hasher().hash_one(&borrowed_key);
+
+    match storage
+        .raw_entry_mut()
+        .from_hash(hash, |k| T::Key::static_key_matches(k, &borrowed_key))
+    {
+        RawEntryMut::Occupied(occupied) => occupied.into_mut(),
+        RawEntryMut::Vacant(vacant) => {
+            let static_key = T::Key::static_key(&borrowed_key);
+            let new_value = T::Source::new_merged(merge_config);
+            vacant.insert_hashed_nocheck(hash, static_key, new_value).1
         }
     }
 }
@@ -91,7 +92,7 @@ where
     Sink: metrique_writer::EntrySink<AggregatedEntry<T>>,
 {
     fn merge(&mut self, entry: T::Source) {
-        let accum = Self::get_or_create_accum(&mut self.storage, &self.merge_config, &entry);
+        let accum = get_or_create_accum::<T>(&mut self.storage, &self.merge_config, &entry);
         T::Source::merge(accum, entry);
     }
 }
@@ -103,7 +104,7 @@ where
     Sink: metrique_writer::EntrySink<AggregatedEntry<T>>,
 {
     fn merge_ref(&mut self, entry: &T::Source) {
-        let accum = Self::get_or_create_accum(&mut self.storage, &self.merge_config, entry);
+        let accum = get_or_create_accum::<T>(&mut self.storage, &self.merge_config, entry);
         T::Source::merge_ref(accum, entry);
     }
 }
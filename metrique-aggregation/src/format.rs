@@ -0,0 +1,251 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Measuring how long [`Format`]s take to format entries.
+//!
+//! Wrap a format in a [`TimingFormat`] to record the wall-clock time of every
+//! [`Format::format`] call into a [`SharedHistogram`]. Since the format is usually moved into an
+//! [`EntryIoStream`](metrique_writer::EntryIoStream), the timings are read through a
+//! [`FormatTimingsHandle`], which can produce a [`FormatTimings`] summary entry at any point:
+//!
+//! ```
+//! use metrique_aggregation::format::TimingFormat;
+//! use metrique_writer::{Entry, EntryIoStream, EntryWriter, FormatExt};
+//! use metrique::emf::Emf;
+//!
+//! struct Request;
+//! impl Entry for Request {
+//!     fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+//!         writer.value("Operation", "Get");
+//!     }
+//! }
+//!
+//! let format = TimingFormat::new(Emf::all_validations("MyApp".into(), vec![vec![]]));
+//! let timings = format.timings();
+//! let mut stream = format.output_to(std::io::sink());
+//! for _ in 0..100 {
+//!     stream.next(&Request).unwrap();
+//! }
+//!
+//! // `summary` is an `Entry`, e.g. to append to a sink for capacity planning
+//! let summary = timings.take_summary();
+//! assert_eq!(summary.entries(), 100);
+//! ```
+
+use std::{
+    io,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use metrique_timesource::TimeSource;
+use metrique_writer::{
+    Entry, EntryWriter, IoStreamError,
+    format::{Format, FormatOutcome},
+};
+
+use crate::histogram::{HistogramClosed, SharedHistogram};
+
+/// A [`Format`] that records how long each call to the inner format's [`Format::format`]
+/// takes.
+///
+/// Time is measured using the [`TimeSource`] that was current when the format was created, see
+/// [`TimingFormat::with_time_source`] to override it. Failed calls are measured too.
+pub struct TimingFormat<F> {
+    inner: F,
+    time_source: TimeSource,
+    timings: FormatTimingsHandle,
+}
+
+impl<F> TimingFormat<F> {
+    /// Wrap `inner`, measuring its format calls
+    pub fn new(inner: F) -> Self {
+        Self {
+            inner,
+            time_source: metrique_timesource::time_source(),
+            timings: FormatTimingsHandle::default(),
+        }
+    }
+
+    /// Measure format calls using `time_source`
+    pub fn with_time_source(mut self, time_source: TimeSource) -> Self {
+        self.time_source = time_source;
+        self
+    }
+
+    /// Return a handle to the recorded timings. The handle remains usable after the format is
+    /// moved into a stream.
+    pub fn timings(&self) -> FormatTimingsHandle {
+        self.timings.clone()
+    }
+
+    /// Return the inner format
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+}
+
+impl<F: Format> Format for TimingFormat<F> {
+    fn format(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<(), IoStreamError> {
+        let start = self.time_source.instant();
+        let result = self.inner.format(entry, output);
+        self.timings.record(start.elapsed());
+        result
+    }
+
+    fn format_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<usize, IoStreamError> {
+        let start = self.time_source.instant();
+        let result = self.inner.format_counted(entry, output);
+        self.timings.record(start.elapsed());
+        result
+    }
+
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        let start = self.time_source.instant();
+        let result = self.inner.format_outcome(entry, output);
+        self.timings.record(start.elapsed());
+        result
+    }
+}
+
+/// A handle to the timings recorded by a [`TimingFormat`].
+#[derive(Clone, Default)]
+pub struct FormatTimingsHandle(Arc<FormatTimingsState>);
+
+#[derive(Default)]
+struct FormatTimingsState {
+    format_time: SharedHistogram<Duration>,
+    entries: AtomicU64,
+}
+
+impl FormatTimingsHandle {
+    fn record(&self, elapsed: Duration) {
+        self.0.format_time.add_value(elapsed);
+        self.0.entries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Return a summary of the timings recorded since the last call, and reset them.
+    pub fn take_summary(&self) -> FormatTimings {
+        FormatTimings {
+            format_time: self.0.format_time.drain(),
+            entries: self.0.entries.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+/// A summary of the timings recorded by a [`TimingFormat`], returned by
+/// [`FormatTimingsHandle::take_summary`].
+///
+/// This is an [`Entry`] that writes the histogram of format times, in milliseconds, as
+/// `FormatTime` and the number of formatted entries as `FormattedEntries`.
+pub struct FormatTimings {
+    format_time: HistogramClosed<Duration>,
+    entries: u64,
+}
+
+impl FormatTimings {
+    /// The number of entries formatted
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+}
+
+impl Entry for FormatTimings {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        writer.value("FormatTime", &self.format_time);
+        writer.value("FormattedEntries", &self.entries);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Duration};
+
+    use metrique_timesource::{TimeSource, fakes::ManuallyAdvancedTimeSource};
+    use metrique_writer::{
+        Entry, EntryWriter, IoStreamError, Observation, format::Format, test_util,
+    };
+
+    use super::TimingFormat;
+
+    /// A format that takes 5ms per entry
+    struct SlowFormat(ManuallyAdvancedTimeSource);
+
+    impl Format for SlowFormat {
+        fn format(
+            &mut self,
+            _entry: &impl Entry,
+            _output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            self.0.update_instant(Duration::from_millis(5));
+            Ok(())
+        }
+    }
+
+    struct Request;
+    impl Entry for Request {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value("Operation", "Get");
+        }
+    }
+
+    #[test]
+    fn accumulates_samples() {
+        let time = ManuallyAdvancedTimeSource::at_time(std::time::UNIX_EPOCH);
+        let mut format =
+            TimingFormat::new(SlowFormat(time.clone())).with_time_source(TimeSource::custom(time));
+        let timings = format.timings();
+
+        for _ in 0..3 {
+            format.format(&Request, &mut io::sink()).unwrap();
+        }
+
+        let summary = timings.take_summary();
+        assert_eq!(summary.entries(), 3);
+        let entry = test_util::to_test_entry(&summary);
+        assert_eq!(entry.metrics["FormattedEntries"], 3);
+        let format_time = &entry.metrics["FormatTime"];
+        assert_eq!(
+            format_time.unit,
+            metrique_writer::Unit::Second(metrique_writer::unit::NegativeScale::Milli)
+        );
+        let (total, occurrences) =
+            format_time
+                .distribution
+                .iter()
+                .fold(
+                    (0.0, 0),
+                    |(total, occurrences), observation| match *observation {
+                        Observation::Repeated {
+                            total: t,
+                            occurrences: o,
+                        } => (total + t, occurrences + o),
+                        _ => panic!("unexpected observation {observation:?}"),
+                    },
+                );
+        assert_eq!(occurrences, 3);
+        // the histogram buckets have ~6.25% error
+        assert!((total / 3.0 - 5.0).abs() < 0.5, "{total}");
+
+        // taking the summary resets the timings
+        let summary = timings.take_summary();
+        assert_eq!(summary.entries(), 0);
+        let entry = test_util::to_test_entry(&summary);
+        assert!(entry.metrics["FormatTime"].distribution.is_empty());
+    }
+}
@@ -94,6 +94,13 @@
 //! - Slower drain operation due to sorting
 //!
 //! Use this when you need exact values and have a bounded number of observations (typically < 1000).
+//!
+//! # Persisting histograms
+//!
+//! With the `serde` feature, histograms whose strategy implements `serde`'s `Serialize` and
+//! `Deserialize`, like [`ExponentialAggregationStrategy`], implement them too. Histograms that
+//! cover a long window can be written to a file on shutdown with `Histogram::persist` and read
+//! back on startup with `Histogram::restore`, so that their percentiles survive process restarts.
 
 use histogram::Config;
 use metrique_core::CloseValue;
@@ -102,7 +109,10 @@ use ordered_float::OrderedFloat;
 use smallvec::SmallVec;
 use std::{borrow::Borrow, marker::PhantomData};
 
-use crate::traits::AggregateValue;
+use crate::traits::{AggregateValue, CombineValue};
+
+#[cfg(feature = "serde")]
+mod persist;
 
 /// Strategy for aggregating observations in a histogram.
 ///
@@ -123,6 +133,14 @@ pub trait AggregationStrategy {
     fn drain(&mut self) -> Vec<Observation>;
 }
 
+/// An [`AggregationStrategy`] that can add the observations of another instance to its own.
+///
+/// This lets histograms that use it implement [`CombineValue`].
+pub trait CombineStrategy: AggregationStrategy {
+    /// Record every observation recorded in `other`, leaving `other` unchanged.
+    fn combine(&mut self, other: &Self);
+}
+
 /// Thread-safe strategy for aggregating observations in a histogram.
 ///
 /// Like [`AggregationStrategy`] but allows recording values through a shared reference.
@@ -169,11 +187,33 @@ impl<T, S: AggregationStrategy> Histogram<T, S> {
     /// The value is converted to observations using the metric value's implementation,
     /// then recorded in the aggregation strategy.
     pub fn add_value(&mut self, value: impl Borrow<T>)
+    where
+        T: MetricValue,
+    {
+        self.add_weighted_value(value, 1);
+    }
+
+    /// Add a value to the histogram, counting it as `weight` observations.
+    ///
+    /// Use this for pre-sampled data, passing the inverse of the sample rate as the weight
+    /// (e.g. 10 for a value that was sampled with a rate of 10%), so that percentiles estimate the
+    /// full population rather than the sample. Fractional weights should be rounded to the
+    /// nearest integer. A weight of 0 records nothing.
+    ///
+    /// ```
+    /// use metrique_aggregation::histogram::Histogram;
+    /// use std::time::Duration;
+    ///
+    /// let mut histogram: Histogram<Duration> = Histogram::default();
+    /// // this value was sampled at 1%
+    /// histogram.add_weighted_value(Duration::from_millis(45), 100);
+    /// ```
+    pub fn add_weighted_value(&mut self, value: impl Borrow<T>, weight: u64)
     where
         T: MetricValue,
     {
         let value = value.borrow();
-        struct Capturer<'a, S>(&'a mut S);
+        struct Capturer<'a, S>(&'a mut S, u64);
         impl<'b, S: AggregationStrategy> ValueWriter for Capturer<'b, S> {
             fn string(self, _value: &str) {}
             fn metric<'a>(
@@ -183,13 +223,14 @@ impl<T, S: AggregationStrategy> Histogram<T, S> {
                 _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
                 _flags: MetricFlags<'_>,
             ) {
+                let weight = self.1;
                 for obs in distribution {
                     match obs {
-                        Observation::Unsigned(v) => self.0.record(v as f64),
-                        Observation::Floating(v) => self.0.record(v),
+                        Observation::Unsigned(v) => self.0.record_many(v as f64, weight),
+                        Observation::Floating(v) => self.0.record_many(v, weight),
                         Observation::Repeated { total, occurrences } if occurrences > 0 => {
                             let avg = total / occurrences as f64;
-                            self.0.record_many(avg, occurrences);
+                            self.0.record_many(avg, occurrences.saturating_mul(weight));
                         }
                         _ => {}
                     }
@@ -198,11 +239,21 @@ impl<T, S: AggregationStrategy> Histogram<T, S> {
             fn error(self, _error: metrique_writer::ValidationError) {}
         }
 
-        let capturer = Capturer(&mut self.strategy);
+        if weight == 0 {
+            return;
+        }
+        let capturer = Capturer(&mut self.strategy, weight);
         value.write(capturer);
     }
 }
 
+impl<T, S: CombineStrategy> Histogram<T, S> {
+    /// Add the observations of `other` to this histogram
+    pub(crate) fn combine(&mut self, other: &Self) {
+        self.strategy.combine(&other.strategy);
+    }
+}
+
 impl<T, S: Default + AggregationStrategy> Default for Histogram<T, S> {
     fn default() -> Self {
         Self::new(S::default())
@@ -255,7 +306,18 @@ impl<T, S: SharedAggregationStrategy> SharedHistogram<T, S> {
     where
         T: MetricValue,
     {
-        struct Capturer<'a, S>(&'a S);
+        self.add_weighted_value(value, 1);
+    }
+
+    /// Add a value to the histogram through a shared reference, counting it as `weight`
+    /// observations.
+    ///
+    /// See [`Histogram::add_weighted_value`].
+    pub fn add_weighted_value(&self, value: T, weight: u64)
+    where
+        T: MetricValue,
+    {
+        struct Capturer<'a, S>(&'a S, u64);
         impl<'b, S: SharedAggregationStrategy> ValueWriter for Capturer<'b, S> {
             fn string(self, _value: &str) {}
             fn metric<'a>(
@@ -265,13 +327,14 @@ impl<T, S: SharedAggregationStrategy> SharedHistogram<T, S> {
                 _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
                 _flags: MetricFlags<'_>,
             ) {
+                let weight = self.1;
                 for obs in distribution {
                     match obs {
-                        Observation::Unsigned(v) => self.0.record(v as f64),
-                        Observation::Floating(v) => self.0.record(v),
+                        Observation::Unsigned(v) => self.0.record_many(v as f64, weight),
+                        Observation::Floating(v) => self.0.record_many(v, weight),
                         Observation::Repeated { total, occurrences } if occurrences > 0 => {
                             let avg = total / occurrences as f64;
-                            self.0.record_many(avg, occurrences);
+                            self.0.record_many(avg, occurrences.saturating_mul(weight));
                         }
                         _ => {}
                     }
@@ -280,11 +343,24 @@ impl<T, S: SharedAggregationStrategy> SharedHistogram<T, S> {
             fn error(self, _error: metrique_writer::ValidationError) {}
         }
 
-        let capturer = Capturer(&self.strategy);
+        if weight == 0 {
+            return;
+        }
+        let capturer = Capturer(&self.strategy, weight);
         value.write(capturer);
     }
 }
 
+impl<T, S: SharedAggregationStrategy> SharedHistogram<T, S> {
+    /// Drain the observations recorded so far, leaving the histogram empty.
+    pub(crate) fn drain(&self) -> HistogramClosed<T> {
+        HistogramClosed {
+            observations: self.strategy.drain(),
+            _value: PhantomData,
+        }
+    }
+}
+
 impl<T: MetricValue, S: SharedAggregationStrategy> CloseValue for SharedHistogram<T, S> {
     type Closed = HistogramClosed<T>;
 
@@ -398,15 +474,33 @@ impl AggregationStrategy for ExponentialAggregationStrategy {
     }
 }
 
+impl CombineStrategy for ExponentialAggregationStrategy {
+    fn combine(&mut self, other: &Self) {
+        // both histograms use the default config, so their buckets line up
+        for (bucket, count) in self
+            .inner
+            .as_mut_slice()
+            .iter_mut()
+            .zip(other.inner.as_slice())
+        {
+            *bucket = bucket.saturating_add(*count);
+        }
+    }
+}
+
 /// Strategy that stores all observations and sorts them on emission.
 ///
 /// This preserves all observations exactly but uses more memory than bucketing strategies.
-/// This uses a `SmallVec` (default size 32, memory usage of 256 bytes) to avoid allocations for small numbers of observations.
+/// This uses a `SmallVec` (default size 32, memory usage of 512 bytes) to avoid allocations for small numbers of observations.
+///
+/// Observations of the same value recorded at once (e.g. a [weighted](Histogram::add_weighted_value)
+/// value) are stored as a single value and count, so memory usage grows with the number of
+/// recordings rather than with their weight.
 ///
 /// The const generic `N` controls the inline capacity before heap allocation.
 #[derive(Default)]
 pub struct SortAndMerge<const N: usize = 32> {
-    values: SmallVec<[f64; N]>,
+    values: SmallVec<[(f64, u64); N]>,
 }
 
 impl<const N: usize> SortAndMerge<N> {
@@ -420,29 +514,30 @@ impl<const N: usize> SortAndMerge<N> {
 
 impl<const N: usize> AggregationStrategy for SortAndMerge<N> {
     fn record_many(&mut self, value: f64, count: u64) {
-        self.values
-            .extend(std::iter::repeat_n(value, count as usize));
+        if count > 0 {
+            self.values.push((value, count));
+        }
     }
 
     fn drain(&mut self) -> Vec<Observation> {
-        self.values.sort_by_key(|v| OrderedFloat(*v));
+        self.values.sort_by_key(|(v, _)| OrderedFloat(*v));
         let mut observations = Vec::new();
-        let mut iter = self.values.iter().copied().filter(|v| !v.is_nan());
+        let mut iter = self.values.iter().copied().filter(|(v, _)| !v.is_nan());
 
-        if let Some(first) = iter.next() {
+        if let Some((first, first_count)) = iter.next() {
             let mut current_value = first;
-            let mut current_count: u64 = 1;
+            let mut current_count = first_count;
 
-            for value in iter {
+            for (value, count) in iter {
                 if value == current_value {
-                    current_count = current_count.saturating_add(1);
+                    current_count = current_count.saturating_add(count);
                 } else {
                     observations.push(Observation::Repeated {
                         total: current_value * current_count as f64,
                         occurrences: current_count,
                     });
                     current_value = value;
-                    current_count = 1;
+                    current_count = count;
                 }
             }
 
@@ -457,6 +552,12 @@ impl<const N: usize> AggregationStrategy for SortAndMerge<N> {
     }
 }
 
+impl<const N: usize> CombineStrategy for SortAndMerge<N> {
+    fn combine(&mut self, other: &Self) {
+        self.values.extend_from_slice(&other.values);
+    }
+}
+
 /// Thread-safe exponential bucketing strategy using atomic counters.
 ///
 /// This uses 976 buckets and supports values from 0 to u64::MAX. Values greater than u64::MAX are truncated to u64::MAX.
@@ -549,14 +650,300 @@ where
     }
 }
 
+/// Histograms can be combined whatever the type of value they aggregate.
+impl<V, T, S> CombineValue<V> for Histogram<T, S>
+where
+    Histogram<T, S>: AggregateValue<V, Aggregated = Histogram<T, S>>,
+    S: CombineStrategy,
+{
+    fn combine(accum: &mut Self::Aggregated, other: &Self::Aggregated) {
+        accum.combine(other);
+    }
+}
+
+/// The quantiles kept by a [`QuantileHistogram`].
+///
+/// This is normally implemented by the `#[aggregate]` macro for fields with
+/// `#[aggregate(histogram(quantiles = [...]))]`, but it can be implemented by hand to use
+/// [`QuantileHistogram`] directly.
+///
+/// ```
+/// use metrique_aggregation::histogram::{QuantileHistogram, Quantiles};
+/// use std::time::Duration;
+///
+/// struct Tail;
+///
+/// impl Quantiles for Tail {
+///     const QUANTILES: &'static [f64] = &[0.9, 0.99];
+/// }
+///
+/// let mut histogram: QuantileHistogram<Duration, Tail> = QuantileHistogram::default();
+/// histogram.add_value(Duration::from_millis(45));
+/// ```
+pub trait Quantiles: 'static {
+    /// The quantiles to keep, each between 0 and 1, in the order they are emitted.
+    const QUANTILES: &'static [f64];
+}
+
+/// A histogram that only emits the configured [`Quantiles`] of its observations.
+///
+/// Observations are recorded with the aggregation strategy `S` like in [`Histogram`], but when
+/// the histogram is written, the distribution only contains one observation per quantile (the
+/// value at that quantile) rather than every bucket. This keeps the emitted metric small when
+/// only a few statistics are of interest.
+///
+/// Closed quantile histograms can be merged into another quantile histogram (e.g. when
+/// aggregating a struct that already contains one), which replays the full distribution. This
+/// requires both histograms to keep exactly the same quantiles. Merging histograms with different
+/// quantiles, even overlapping ones, is an error at build time:
+///
+/// ```compile_fail
+/// use metrique_aggregation::histogram::{QuantileHistogram, Quantiles};
+/// use metrique_aggregation::traits::AggregateValue;
+/// use metrique_core::CloseValue;
+/// use std::time::Duration;
+///
+/// struct Median;
+/// impl Quantiles for Median {
+///     const QUANTILES: &'static [f64] = &[0.5, 0.9];
+/// }
+///
+/// struct Tail;
+/// impl Quantiles for Tail {
+///     const QUANTILES: &'static [f64] = &[0.9, 0.99];
+/// }
+///
+/// let partial: QuantileHistogram<Duration, Median> = QuantileHistogram::default();
+/// let mut accum: QuantileHistogram<Duration, Tail> = QuantileHistogram::default();
+/// <QuantileHistogram<Duration, Tail> as AggregateValue<_>>::insert(&mut accum, partial.close());
+/// ```
+pub struct QuantileHistogram<T, Q, S = ExponentialAggregationStrategy> {
+    histogram: Histogram<T, S>,
+    _quantiles: PhantomData<Q>,
+}
+
+impl<T, Q: Quantiles, S: AggregationStrategy> QuantileHistogram<T, Q, S> {
+    /// Create a new quantile histogram with the given aggregation strategy.
+    pub fn new(strategy: S) -> Self {
+        Self {
+            histogram: Histogram::new(strategy),
+            _quantiles: PhantomData,
+        }
+    }
+
+    /// Add a value to the histogram.
+    ///
+    /// See [`Histogram::add_value`].
+    pub fn add_value(&mut self, value: impl Borrow<T>)
+    where
+        T: MetricValue,
+    {
+        self.histogram.add_value(value);
+    }
+
+    /// Add a value to the histogram, counting it as `weight` observations.
+    ///
+    /// See [`Histogram::add_weighted_value`].
+    pub fn add_weighted_value(&mut self, value: impl Borrow<T>, weight: u64)
+    where
+        T: MetricValue,
+    {
+        self.histogram.add_weighted_value(value, weight);
+    }
+}
+
+impl<T, Q: Quantiles, S: Default + AggregationStrategy> Default for QuantileHistogram<T, Q, S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<T: MetricValue, Q: Quantiles, S: AggregationStrategy> CloseValue
+    for QuantileHistogram<T, Q, S>
+{
+    type Closed = QuantileHistogramClosed<T, Q>;
+
+    fn close(mut self) -> Self::Closed {
+        QuantileHistogramClosed {
+            observations: self.histogram.strategy.drain(),
+            _value: PhantomData,
+        }
+    }
+}
+
+/// Closed [`QuantileHistogram`], emitted as a distribution of the configured quantiles.
+///
+/// This keeps the full distribution so that it can still be merged into another
+/// [`QuantileHistogram`], the quantiles are computed when it is written.
+pub struct QuantileHistogramClosed<T, Q> {
+    observations: Vec<Observation>,
+    _value: PhantomData<(T, Q)>,
+}
+
+impl<T: MetricValue, Q: Quantiles> Value for QuantileHistogramClosed<T, Q> {
+    fn write(&self, writer: impl ValueWriter) {
+        use metrique_writer::unit::UnitTag;
+        writer.metric(
+            quantiles(&self.observations, Q::QUANTILES),
+            T::Unit::UNIT,
+            [],
+            MetricFlags::upcast(&Distribution),
+        )
+    }
+}
+
+impl<T: MetricValue, Q: Quantiles> MetricValue for QuantileHistogramClosed<T, Q> {
+    type Unit = T::Unit;
+}
+
+/// Return the value at each of `quantiles` in `observations`, using the nearest-rank method.
+///
+/// Returns no observations if `observations` is empty.
+fn quantiles(observations: &[Observation], quantiles: &[f64]) -> Vec<Observation> {
+    let mut values: Vec<(f64, u64)> = observations
+        .iter()
+        .filter_map(|obs| match *obs {
+            Observation::Unsigned(v) => Some((v as f64, 1)),
+            Observation::Floating(v) => Some((v, 1)),
+            Observation::Repeated { total, occurrences } if occurrences > 0 => {
+                Some((total / occurrences as f64, occurrences))
+            }
+            _ => None,
+        })
+        .filter(|(v, _)| !v.is_nan())
+        .collect();
+    values.sort_by_key(|(v, _)| OrderedFloat(*v));
+    let count = values.iter().fold(0u64, |count, (_, occurrences)| {
+        count.saturating_add(*occurrences)
+    });
+    if count == 0 {
+        return vec![];
+    }
+
+    quantiles
+        .iter()
+        .map(|q| {
+            // the smallest value with at least `rank` observations at or below it
+            let rank = ((q.clamp(0.0, 1.0) * count as f64).ceil() as u64).max(1);
+            let mut seen = 0u64;
+            let value = values
+                .iter()
+                .find(|(_, occurrences)| {
+                    seen = seen.saturating_add(*occurrences);
+                    seen >= rank
+                })
+                .or(values.last())
+                .map_or(0.0, |(v, _)| *v);
+            Observation::Floating(value)
+        })
+        .collect()
+}
+
+const fn same_quantiles(a: &[f64], b: &[f64]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i].to_bits() != b[i].to_bits() {
+            return false;
+        }
+        i = i.saturating_add(1);
+    }
+    true
+}
+
+/// Passes the quantile configuration of `#[aggregate(histogram(quantiles = [...]))]` to the
+/// histogram strategy of a field.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` does not support `#[aggregate(histogram(quantiles = ...))]`",
+    note = "quantiles can only be configured for `Histogram` strategies"
+)]
+pub trait WithQuantiles<Q: Quantiles> {
+    /// The strategy that keeps the quantiles `Q`
+    type Strategy;
+}
+
+impl<T, S, Q: Quantiles> WithQuantiles<Q> for Histogram<T, S> {
+    type Strategy = QuantileHistogram<T, Q, S>;
+}
+
+impl<T, Q, S> AggregateValue<T> for QuantileHistogram<T, Q, S>
+where
+    T: MetricValue,
+    Q: Quantiles,
+    S: AggregationStrategy + Default,
+{
+    type Aggregated = QuantileHistogram<T, Q, S>;
+
+    fn insert(accum: &mut Self::Aggregated, value: T) {
+        accum.add_value(value);
+    }
+}
+
+/// Merge closed histograms into a quantile histogram, see the impl for [`Histogram`].
+impl<T, Q, S> AggregateValue<HistogramClosed<T>> for QuantileHistogram<T, Q, S>
+where
+    T: MetricValue,
+    Q: Quantiles,
+    S: AggregationStrategy + Default,
+{
+    type Aggregated = QuantileHistogram<T, Q, S>;
+
+    fn insert(accum: &mut Self::Aggregated, value: HistogramClosed<T>) {
+        <Histogram<T, S> as AggregateValue<HistogramClosed<T>>>::insert(
+            &mut accum.histogram,
+            value,
+        );
+    }
+}
+
+/// Merge closed quantile histograms into a quantile histogram. Both must keep the same quantiles.
+impl<T, Q, Q2, S> AggregateValue<QuantileHistogramClosed<T, Q2>> for QuantileHistogram<T, Q, S>
+where
+    T: MetricValue,
+    Q: Quantiles,
+    Q2: Quantiles,
+    S: AggregationStrategy + Default,
+{
+    type Aggregated = QuantileHistogram<T, Q, S>;
+
+    fn insert(accum: &mut Self::Aggregated, value: QuantileHistogramClosed<T, Q2>) {
+        const {
+            assert!(
+                same_quantiles(Q::QUANTILES, Q2::QUANTILES),
+                "cannot merge histograms that keep different quantiles"
+            )
+        };
+        <Histogram<T, S> as AggregateValue<HistogramClosed<T>>>::insert(
+            &mut accum.histogram,
+            HistogramClosed {
+                observations: value.observations,
+                _value: PhantomData,
+            },
+        );
+    }
+}
+
+impl<V, T, Q, S> CombineValue<V> for QuantileHistogram<T, Q, S>
+where
+    QuantileHistogram<T, Q, S>: AggregateValue<V, Aggregated = QuantileHistogram<T, Q, S>>,
+    S: CombineStrategy,
+{
+    fn combine(accum: &mut Self::Aggregated, other: &Self::Aggregated) {
+        accum.histogram.combine(&other.histogram);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use assert2::check;
     use metrique_writer::Observation;
 
     use crate::histogram::{
-        AggregationStrategy, AtomicExponentialAggregationStrategy, ExponentialAggregationStrategy,
-        SharedAggregationStrategy, default_histogram_config, scale_down, scale_up,
+        AggregationStrategy, AtomicExponentialAggregationStrategy, CombineStrategy,
+        ExponentialAggregationStrategy, SharedAggregationStrategy, SortAndMerge,
+        default_histogram_config, quantiles, same_quantiles, scale_down, scale_up,
     };
 
     #[test]
@@ -589,6 +976,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn combine_records_the_observations_of_both_strategies() {
+        let mut all = ExponentialAggregationStrategy::new();
+        let mut accum = ExponentialAggregationStrategy::new();
+        let mut other = ExponentialAggregationStrategy::new();
+        for (value, count) in [(1.0, 2), (100.0, 1)] {
+            accum.record_many(value, count);
+            all.record_many(value, count);
+        }
+        for (value, count) in [(100.0, 3), (5000.0, 1)] {
+            other.record_many(value, count);
+            all.record_many(value, count);
+        }
+        accum.combine(&other);
+        check!(accum.drain() == all.drain());
+        // other is left unchanged
+        check!(other.drain().len() == 2);
+
+        let mut accum = SortAndMerge::<4>::new();
+        let mut other = SortAndMerge::<4>::new();
+        accum.record_many(2.0, 1);
+        other.record_many(2.0, 2);
+        other.record(1.0);
+        accum.combine(&other);
+        check!(
+            accum.drain()
+                == vec![
+                    Observation::Repeated {
+                        total: 1.0,
+                        occurrences: 1
+                    },
+                    Observation::Repeated {
+                        total: 6.0,
+                        occurrences: 3
+                    },
+                ]
+        );
+    }
+
     #[test]
     fn num_buckets() {
         check!(default_histogram_config().total_buckets() == 976);
@@ -599,4 +1025,40 @@ mod tests {
         let x = 0.001;
         check!(scale_down(scale_up(x)) == x);
     }
+
+    #[test]
+    fn test_quantiles() {
+        let observations = [
+            Observation::Repeated {
+                total: 30.0,
+                occurrences: 3,
+            },
+            Observation::Unsigned(1),
+            Observation::Floating(100.0),
+            Observation::Repeated {
+                total: 250.0,
+                occurrences: 5,
+            },
+        ];
+        // sorted: 1, 10, 10, 10, 50, 50, 50, 50, 50, 100
+        check!(
+            quantiles(&observations, &[0.0, 0.1, 0.5, 0.9, 0.99, 1.0])
+                == vec![
+                    Observation::Floating(1.0),
+                    Observation::Floating(1.0),
+                    Observation::Floating(50.0),
+                    Observation::Floating(50.0),
+                    Observation::Floating(100.0),
+                    Observation::Floating(100.0),
+                ]
+        );
+        check!(quantiles(&[], &[0.5]) == vec![]);
+    }
+
+    #[test]
+    fn test_same_quantiles() {
+        check!(same_quantiles(&[0.5, 0.9], &[0.5, 0.9]));
+        check!(!same_quantiles(&[0.5, 0.9], &[0.9, 0.99]));
+        check!(!same_quantiles(&[0.5, 0.9], &[0.5]));
+    }
 }
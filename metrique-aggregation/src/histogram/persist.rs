@@ -0,0 +1,271 @@
+//! Serialization of histogram sketches, so that long-window histograms survive process restarts.
+
+use std::{
+    fs,
+    io::{self, BufReader, BufWriter},
+    marker::PhantomData,
+    path::Path,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::DeserializeOwned, de::Error};
+
+use super::{
+    AggregationStrategy, ExponentialAggregationStrategy, Histogram, QuantileHistogram,
+    default_histogram_config,
+};
+
+/// The serialized form of an [`ExponentialAggregationStrategy`]: the configuration of the
+/// bucketing and the counts of its non-empty buckets.
+#[derive(Serialize, Deserialize)]
+struct ExponentialSketch {
+    grouping_power: u8,
+    max_value_power: u8,
+    index: Vec<usize>,
+    count: Vec<u64>,
+}
+
+impl Serialize for ExponentialAggregationStrategy {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let config = self.inner.config();
+        let (index, count) = self
+            .inner
+            .as_slice()
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .unzip();
+        ExponentialSketch {
+            grouping_power: config.grouping_power(),
+            max_value_power: config.max_value_power(),
+            index,
+            count,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ExponentialAggregationStrategy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let sketch = ExponentialSketch::deserialize(deserializer)?;
+        let config = default_histogram_config();
+        if (sketch.grouping_power, sketch.max_value_power)
+            != (config.grouping_power(), config.max_value_power())
+        {
+            return Err(D::Error::custom(format_args!(
+                "sketch has grouping power {} and max value power {}, expected {} and {}",
+                sketch.grouping_power,
+                sketch.max_value_power,
+                config.grouping_power(),
+                config.max_value_power()
+            )));
+        }
+        if sketch.index.len() != sketch.count.len() {
+            return Err(D::Error::custom(
+                "sketch has a different number of bucket indexes and counts",
+            ));
+        }
+
+        let mut strategy = Self::new();
+        let buckets = strategy.inner.as_mut_slice();
+        for (index, count) in sketch.index.into_iter().zip(sketch.count) {
+            let Some(bucket) = buckets.get_mut(index) else {
+                return Err(D::Error::custom(format_args!(
+                    "sketch bucket index {index} is out of range"
+                )));
+            };
+            *bucket = count;
+        }
+        Ok(strategy)
+    }
+}
+
+impl<T, S: Serialize> Serialize for Histogram<T, S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        self.strategy.serialize(serializer)
+    }
+}
+
+impl<'de, T, S: Deserialize<'de>> Deserialize<'de> for Histogram<T, S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self {
+            strategy: S::deserialize(deserializer)?,
+            _value: PhantomData,
+        })
+    }
+}
+
+impl<T, Q, S: Serialize> Serialize for QuantileHistogram<T, Q, S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        self.histogram.serialize(serializer)
+    }
+}
+
+impl<'de, T, Q, S: Deserialize<'de>> Deserialize<'de> for QuantileHistogram<T, Q, S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self {
+            histogram: Histogram::deserialize(deserializer)?,
+            _quantiles: PhantomData,
+        })
+    }
+}
+
+impl<T, S> Histogram<T, S>
+where
+    S: AggregationStrategy + Serialize + DeserializeOwned,
+{
+    /// Write the observations recorded so far to the file at `path`, replacing it if it exists.
+    ///
+    /// This is intended to be called on shutdown, to later [restore](Self::restore) the histogram
+    /// on startup so that long-window percentiles don't lose the observations recorded before
+    /// a restart. The histogram is written to a temporary file next to `path` first, so a crash
+    /// while persisting leaves the previous file intact.
+    ///
+    /// The file contains the sketch of the aggregation strategy (e.g. the bucket counts of
+    /// [`ExponentialAggregationStrategy`]) as JSON. Its format is stable across versions of this
+    /// crate that use the same bucketing.
+    ///
+    /// ```
+    /// use metrique_aggregation::histogram::Histogram;
+    /// use std::{io, time::Duration};
+    /// # let dir = tempfile::tempdir().unwrap();
+    /// # let path = dir.path().join("latency.json");
+    ///
+    /// // on startup, restore the histogram if it was persisted
+    /// let mut latency: Histogram<Duration> = match Histogram::restore(&path) {
+    ///     Ok(histogram) => histogram,
+    ///     Err(e) if e.kind() == io::ErrorKind::NotFound => Histogram::default(),
+    ///     Err(e) => panic!("failed to restore histogram: {e}"),
+    /// };
+    ///
+    /// latency.add_value(Duration::from_millis(45));
+    ///
+    /// // on shutdown
+    /// latency.persist(&path).unwrap();
+    /// ```
+    pub fn persist(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        let mut writer = BufWriter::new(fs::File::create(&tmp_path)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer.into_inner()?.sync_all()?;
+        fs::rename(&tmp_path, path)
+    }
+
+    /// Read a histogram written by [`persist`](Self::persist) from the file at `path`.
+    ///
+    /// Returns an error of kind [`io::ErrorKind::NotFound`] if there is no file at `path`, and an
+    /// error if the file does not contain a valid sketch for the aggregation strategy (e.g.
+    /// because it was persisted with a different bucketing).
+    pub fn restore(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(fs::File::open(path)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Duration};
+
+    use assert2::check;
+    use metrique_core::CloseValue;
+    use metrique_writer::Observation;
+
+    use crate::histogram::{
+        AggregationStrategy, ExponentialAggregationStrategy, Histogram, quantiles,
+    };
+
+    fn p50_p90_p99(histogram: Histogram<Duration>) -> Vec<f64> {
+        let closed = histogram.close();
+        quantiles(&closed.observations, &[0.5, 0.9, 0.99])
+            .into_iter()
+            .map(|obs| match obs {
+                Observation::Floating(v) => v,
+                other => panic!("unexpected observation {other:?}"),
+            })
+            .collect()
+    }
+
+    fn sample_histogram() -> Histogram<Duration> {
+        let mut histogram = Histogram::default();
+        for millis in 1..=1000 {
+            histogram.add_value(Duration::from_millis(millis));
+        }
+        histogram
+    }
+
+    #[test]
+    fn round_trip_preserves_quantiles() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latency.json");
+
+        sample_histogram().persist(&path).unwrap();
+        let restored: Histogram<Duration> = Histogram::restore(&path).unwrap();
+
+        let restored = p50_p90_p99(restored);
+        check!(restored == p50_p90_p99(sample_histogram()));
+        // bucketing keeps quantiles within the error of the exponential strategy
+        for (actual, expected) in restored.iter().zip([500.0, 900.0, 990.0]) {
+            check!((actual - expected).abs() / expected < 0.0625);
+        }
+    }
+
+    #[test]
+    fn restored_histogram_keeps_recording() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("latency.json");
+
+        sample_histogram().persist(&path).unwrap();
+        let mut restored: Histogram<Duration> = Histogram::restore(&path).unwrap();
+        for millis in 1..=1000 {
+            restored.add_value(Duration::from_millis(millis));
+        }
+
+        let mut expected = sample_histogram();
+        for millis in 1..=1000 {
+            expected.add_value(Duration::from_millis(millis));
+        }
+        check!(restored.close().observations == expected.close().observations);
+    }
+
+    #[test]
+    fn restore_missing_file_is_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let err = Histogram::<Duration>::restore(dir.path().join("missing.json"))
+            .err()
+            .unwrap();
+        check!(err.kind() == io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn invalid_sketches_are_rejected() {
+        let parse = |json: &str| serde_json::from_str::<ExponentialAggregationStrategy>(json);
+
+        let err = parse(r#"{"grouping_power":7,"max_value_power":64,"index":[],"count":[]}"#)
+            .err()
+            .unwrap();
+        check!(err.to_string().contains("expected 4 and 64"));
+        let err = parse(r#"{"grouping_power":4,"max_value_power":64,"index":[1],"count":[]}"#)
+            .err()
+            .unwrap();
+        check!(
+            err.to_string()
+                .contains("different number of bucket indexes and counts")
+        );
+        let err = parse(r#"{"grouping_power":4,"max_value_power":64,"index":[976],"count":[1]}"#)
+            .err()
+            .unwrap();
+        check!(err.to_string().contains("out of range"));
+
+        let mut strategy =
+            parse(r#"{"grouping_power":4,"max_value_power":64,"index":[0],"count":[3]}"#).unwrap();
+        check!(
+            strategy.drain()
+                == vec![Observation::Repeated {
+                    total: 0.0,
+                    occurrences: 3
+                }]
+        );
+    }
+}
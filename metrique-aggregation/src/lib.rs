@@ -5,6 +5,7 @@
 //! Histogram implementations for aggregating metrique metrics.
 
 pub mod aggregator;
+pub mod format;
 pub mod histogram;
 pub mod sink;
 pub mod traits;
@@ -12,7 +13,10 @@ pub mod value;
 
 #[doc(hidden)]
 pub mod __macro_plumbing {
-    pub use crate::traits::{AggregateStrategy, AggregateValue, Key, Merge, MergeRef};
+    pub use crate::histogram::{Quantiles, WithQuantiles};
+    pub use crate::traits::{
+        AggregateStrategy, AggregateValue, Combine, CombineValue, Key, Merge, MergeRef,
+    };
     pub use crate::value::{CopyWrapper, NoKey};
 }
 
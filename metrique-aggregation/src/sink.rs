@@ -9,9 +9,11 @@ use metrique_writer::EntrySink;
 use crate::traits::{AggregateSink, AggregateSinkRef, AggregateStrategy, FlushableSink, RootSink};
 
 pub mod mutex;
+pub mod scheduled;
 pub mod worker;
 
 pub use mutex::MutexSink;
+pub use scheduled::{ScheduledSink, ScheduledSinkBuilder};
 pub use worker::WorkerSink;
 
 /// Handle for metric that will be automatically merged into the target when dropped (for `#[aggregate(direct)]`)
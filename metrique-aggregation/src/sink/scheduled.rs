@@ -0,0 +1,488 @@
+//! Background worker sink that flushes multiple targets at independent cadences
+
+use std::{sync::Arc, thread, time::Duration};
+
+use metrique_core::CloseValue;
+use metrique_timesource::{BoxFuture, Instant, TimeSource};
+use metrique_writer::EntrySink;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::aggregator::{AggregatedEntry, get_or_create_accum};
+use crate::traits::{
+    AggregateStrategy, AggregateTy, AggregationResult, Combine, KeyTy, Merge, RootSink,
+};
+
+/// The most messages the worker handles before checking whether a target is due
+const MAX_BATCH: usize = 1024;
+
+enum QueueMessage<T> {
+    Entry(T),
+    Flush(oneshot::Sender<()>),
+}
+
+type Storage<T> = hashbrown::HashMap<KeyTy<'static, T>, AggregateTy<T>>;
+
+type TargetSink<T> = Box<dyn EntrySink<AggregatedEntry<T>> + Send>;
+
+struct Target<T: AggregateStrategy> {
+    sink: TargetSink<T>,
+    interval: Duration,
+    /// When this target is next flushed, as the time elapsed since the schedule started
+    next_flush: Duration,
+    /// What was merged since this target was last flushed, apart from what is still in the
+    /// shared accumulator
+    pending: Storage<T>,
+}
+
+impl<T: AggregateStrategy> Target<T> {
+    fn flush(&mut self, now: Duration) {
+        for (key, aggregated) in self.pending.drain() {
+            self.sink.append(AggregationResult {
+                key: key.close(),
+                aggregated: aggregated.close(),
+            });
+        }
+        self.next_flush = now + self.interval;
+    }
+}
+
+/// The state owned by the worker thread.
+///
+/// Every entry is merged once, into the accumulator shared by all targets. When a target is due,
+/// the shared accumulator is combined into the pending aggregate of each target, which holds what
+/// that target has not emitted yet, and the targets that are due emit theirs.
+struct Schedule<T: AggregateStrategy> {
+    shared: Storage<T>,
+    merge_config: <T::Source as Merge>::MergeConfig,
+    targets: Vec<Target<T>>,
+}
+
+impl<T> Schedule<T>
+where
+    T: AggregateStrategy,
+    T::Source: Combine,
+    KeyTy<'static, T>: Clone,
+{
+    fn new(
+        targets: Vec<(TargetSink<T>, Duration)>,
+        merge_config: <T::Source as Merge>::MergeConfig,
+    ) -> Self {
+        Self {
+            shared: Default::default(),
+            merge_config,
+            targets: targets
+                .into_iter()
+                .map(|(sink, interval)| Target {
+                    sink,
+                    interval,
+                    next_flush: interval,
+                    pending: Default::default(),
+                })
+                .collect(),
+        }
+    }
+
+    fn merge(&mut self, entry: T::Source) {
+        if self.targets.is_empty() {
+            return;
+        }
+        let accum = get_or_create_accum::<T>(&mut self.shared, &self.merge_config, &entry);
+        T::Source::merge(accum, entry);
+    }
+
+    /// Move the contents of the shared accumulator to the pending aggregate of every target
+    fn distribute(&mut self) {
+        for (key, aggregated) in self.shared.drain() {
+            for target in &mut self.targets {
+                let pending = target
+                    .pending
+                    .entry(key.clone())
+                    .or_insert_with(|| T::Source::new_merged(&self.merge_config));
+                T::Source::combine(pending, &aggregated);
+            }
+        }
+    }
+
+    /// Flush every target whose deadline has passed, and schedule its next flush.
+    ///
+    /// Returns whether any target was flushed.
+    fn flush_due(&mut self, now: Duration) -> bool {
+        if !self.targets.iter().any(|target| target.next_flush <= now) {
+            return false;
+        }
+        self.distribute();
+        for target in &mut self.targets {
+            if target.next_flush <= now {
+                target.flush(now);
+            }
+        }
+        true
+    }
+
+    fn flush_all(&mut self, now: Duration) {
+        self.distribute();
+        for target in &mut self.targets {
+            target.flush(now);
+        }
+    }
+
+    fn time_until_next_flush(&self, now: Duration) -> Option<Duration> {
+        self.targets
+            .iter()
+            .map(|target| target.next_flush.saturating_sub(now))
+            .min()
+    }
+
+    fn sleep_until_next_flush(&self, time_source: &TimeSource, now: Duration) -> BoxFuture<()> {
+        match self.time_until_next_flush(now) {
+            Some(timeout) => time_source.sleep(timeout),
+            None => Box::pin(std::future::pending()),
+        }
+    }
+
+    async fn run(
+        mut self,
+        mut receiver: mpsc::UnboundedReceiver<QueueMessage<T::Source>>,
+        start: Instant,
+        time_source: TimeSource,
+    ) {
+        let mut messages = Vec::with_capacity(MAX_BATCH);
+        let mut sleep = self.sleep_until_next_flush(&time_source, start.elapsed());
+        loop {
+            // entries are merged before a deadline is handled, so that the entries sent before
+            // the deadline are included in the flush
+            let mut flushed = tokio::select! {
+                biased;
+                received = receiver.recv_many(&mut messages, MAX_BATCH) => {
+                    if received == 0 {
+                        // every sender was dropped
+                        self.flush_all(start.elapsed());
+                        return;
+                    }
+                    let mut flushed = false;
+                    for message in messages.drain(..) {
+                        match message {
+                            QueueMessage::Entry(entry) => self.merge(entry),
+                            QueueMessage::Flush(sender) => {
+                                self.flush_all(start.elapsed());
+                                flushed = true;
+                                let _ = sender.send(());
+                            }
+                        }
+                    }
+                    flushed
+                }
+                () = &mut sleep => true,
+            };
+            flushed |= self.flush_due(start.elapsed());
+            if flushed {
+                sleep = self.sleep_until_next_flush(&time_source, start.elapsed());
+            }
+        }
+    }
+}
+
+/// Builder for [`ScheduledSink`]
+///
+/// See [`ScheduledSink::builder`].
+pub struct ScheduledSinkBuilder<T: AggregateStrategy> {
+    targets: Vec<(TargetSink<T>, Duration)>,
+    time_source: TimeSource,
+}
+
+impl<T> ScheduledSinkBuilder<T>
+where
+    T: AggregateStrategy,
+    T::Source: Combine + Send,
+    KeyTy<'static, T>: Clone,
+    AggregateTy<T>: Send,
+    <T::Source as Merge>::MergeConfig: Default + Send,
+{
+    /// Add a target that the aggregated entries are appended to every `interval`
+    ///
+    /// # Panics
+    /// Panics if `interval` is zero.
+    pub fn target(
+        mut self,
+        sink: impl EntrySink<AggregatedEntry<T>> + Send + 'static,
+        interval: Duration,
+    ) -> Self {
+        assert!(
+            interval > Duration::ZERO,
+            "ScheduledSink: flush interval must be greater than zero"
+        );
+        self.targets.push((Box::new(sink), interval));
+        self
+    }
+
+    /// Schedule flushes using `time_source`
+    ///
+    /// By default, the [`TimeSource`] that was current when the builder was created is used.
+    /// The time source must be able to sleep, see [`TimeSource::sleep`].
+    pub fn with_time_source(mut self, time_source: TimeSource) -> Self {
+        self.time_source = time_source;
+        self
+    }
+
+    /// Spawn the background thread and return the sink
+    pub fn build(self) -> ScheduledSink<T> {
+        ScheduledSink::new(
+            Schedule::new(self.targets, Default::default()),
+            self.time_source,
+        )
+    }
+}
+
+/// Background worker sink that aggregates entries once, and emits the aggregate to several
+/// targets, each at its own cadence
+///
+/// This is useful to, for example, emit per-minute rollups to CloudWatch and per-second rollups
+/// to a local debug sink from the same stream of entries. Each target emits everything that was
+/// merged since its own previous flush, so flushing one target does not reset the others.
+///
+/// Every entry is merged once, into an accumulator shared by all targets. When a target is due,
+/// the shared accumulator is [combined](crate::traits::Combine) into what each target has not
+/// emitted yet, so the cost of a flush grows with the number of keys and targets rather than with
+/// the number of entries. The `#[aggregate]` macro implements [`Combine`] for entries whose
+/// strategies all support it, as the built-in ones do.
+///
+/// Flushes are scheduled with a [`TimeSource`], see [`ScheduledSinkBuilder::with_time_source`].
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use metrique::unit_of_work::metrics;
+/// use metrique_aggregation::{aggregate, value::Sum};
+/// use metrique_aggregation::sink::ScheduledSink;
+/// # use metrique::test_util::test_entry_sink;
+///
+/// #[aggregate]
+/// #[metrics]
+/// struct ApiCall {
+///     #[aggregate(key)]
+///     endpoint: String,
+///     #[aggregate(strategy = Sum)]
+///     count: u64,
+/// }
+///
+/// # let cloudwatch_sink = test_entry_sink().sink;
+/// # let debug_sink = test_entry_sink().sink;
+/// let sink = ScheduledSink::<ApiCall>::builder()
+///     .target(cloudwatch_sink, Duration::from_secs(60))
+///     .target(debug_sink, Duration::from_secs(1))
+///     .build();
+///
+/// ApiCall { endpoint: "Foo".into(), count: 1 }.close_and_merge(sink.clone());
+/// ```
+pub struct ScheduledSink<T: AggregateStrategy> {
+    sender: mpsc::UnboundedSender<QueueMessage<T::Source>>,
+    _handle: Arc<thread::JoinHandle<()>>,
+}
+
+impl<T: AggregateStrategy> Clone for ScheduledSink<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            _handle: self._handle.clone(),
+        }
+    }
+}
+
+impl<T> ScheduledSink<T>
+where
+    T: AggregateStrategy,
+    T::Source: Combine + Send,
+    KeyTy<'static, T>: Clone,
+    AggregateTy<T>: Send,
+    <T::Source as Merge>::MergeConfig: Default + Send,
+{
+    /// Create a builder to register targets and their flush intervals
+    pub fn builder() -> ScheduledSinkBuilder<T> {
+        ScheduledSinkBuilder {
+            targets: Vec::new(),
+            time_source: metrique_timesource::time_source(),
+        }
+    }
+
+    fn new(schedule: Schedule<T>, time_source: TimeSource) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        // deadlines are relative to the creation of the sink, not the start of the thread
+        let start = time_source.instant();
+
+        let handle = thread::spawn(move || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .expect("failed to start the ScheduledSink runtime")
+                .block_on(schedule.run(receiver, start, time_source))
+        });
+
+        Self {
+            sender,
+            _handle: Arc::new(handle),
+        }
+    }
+
+    /// Send an entry to be aggregated
+    pub fn send(&self, entry: T::Source) {
+        let _ = self.sender.send(QueueMessage::Entry(entry));
+    }
+
+    /// Flush all targets, regardless of their schedule
+    pub async fn flush(&self) {
+        let (tx, rx) = oneshot::channel();
+        let _ = self.sender.send(QueueMessage::Flush(tx));
+        rx.await.unwrap()
+    }
+}
+
+impl<T> RootSink<T::Source> for ScheduledSink<T>
+where
+    T: AggregateStrategy,
+    T::Source: Combine + Send,
+    KeyTy<'static, T>: Clone,
+    AggregateTy<T>: Send,
+    <T::Source as Merge>::MergeConfig: Default + Send,
+{
+    fn merge(&self, entry: T::Source) {
+        self.send(entry);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::NoKey;
+    use metrique::test_util::{Inspector, test_entry_sink};
+    use metrique::unit_of_work::metrics;
+
+    struct Calls(u64);
+
+    #[metrics]
+    #[derive(Default)]
+    struct AggregatedCalls {
+        calls: u64,
+    }
+
+    impl Merge for Calls {
+        type Merged = AggregatedCalls;
+        type MergeConfig = ();
+
+        fn new_merged(_conf: &()) -> AggregatedCalls {
+            AggregatedCalls::default()
+        }
+
+        fn merge(accum: &mut AggregatedCalls, input: Self) {
+            accum.calls += input.0;
+        }
+    }
+
+    impl Combine for Calls {
+        fn combine(accum: &mut AggregatedCalls, other: &AggregatedCalls) {
+            accum.calls += other.calls;
+        }
+    }
+
+    struct CallsStrategy;
+
+    impl AggregateStrategy for CallsStrategy {
+        type Source = Calls;
+        type Key = NoKey;
+    }
+
+    fn schedule(intervals: &[u64]) -> (Schedule<CallsStrategy>, Vec<Inspector>) {
+        let (targets, inspectors) = intervals
+            .iter()
+            .map(|secs| {
+                let sink = test_entry_sink();
+                let target: TargetSink<CallsStrategy> = Box::new(sink.sink);
+                ((target, Duration::from_secs(*secs)), sink.inspector)
+            })
+            .unzip();
+        (Schedule::new(targets, ()), inspectors)
+    }
+
+    fn flushed(inspector: &Inspector) -> Vec<u64> {
+        inspector
+            .entries()
+            .iter()
+            .map(|entry| entry.metrics["calls"].as_u64())
+            .collect()
+    }
+
+    #[test]
+    fn targets_flush_at_independent_cadences() {
+        let (mut schedule, inspectors) = schedule(&[1, 3]);
+
+        for second in 1..=6 {
+            schedule.merge(Calls(second));
+            schedule.flush_due(Duration::from_secs(second));
+        }
+
+        // the fast target flushes every second, the slow one every 3 seconds, and each
+        // flush only contains what was merged since that target's own previous flush
+        assert_eq!(flushed(&inspectors[0]), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(flushed(&inspectors[1]), [1 + 2 + 3, 4 + 5 + 6]);
+    }
+
+    #[test]
+    fn entries_are_merged_once_into_the_shared_accumulator() {
+        let (mut schedule, _inspectors) = schedule(&[1, 3]);
+        schedule.merge(Calls(1));
+        schedule.merge(Calls(2));
+
+        assert_eq!(schedule.shared.len(), 1);
+        assert!(
+            schedule
+                .targets
+                .iter()
+                .all(|target| target.pending.is_empty())
+        );
+
+        // only targets that have not flushed keep a pending aggregate
+        schedule.flush_due(Duration::from_secs(1));
+        assert!(schedule.shared.is_empty());
+        assert!(schedule.targets[0].pending.is_empty());
+        assert_eq!(schedule.targets[1].pending[&NoKey].calls, 3);
+    }
+
+    #[test]
+    fn time_until_next_flush_is_earliest_deadline() {
+        let (mut schedule, _inspectors) = schedule(&[60, 1]);
+
+        assert_eq!(
+            schedule.time_until_next_flush(Duration::ZERO),
+            Some(Duration::from_secs(1))
+        );
+        schedule.flush_due(Duration::from_secs(1));
+        assert_eq!(
+            schedule.time_until_next_flush(Duration::from_secs(1)),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(
+            schedule.time_until_next_flush(Duration::from_secs(120)),
+            Some(Duration::ZERO)
+        );
+        let (empty, _) = self::schedule(&[]);
+        assert_eq!(empty.time_until_next_flush(Duration::ZERO), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "flush interval must be greater than zero")]
+    fn rejects_zero_interval() {
+        let _ = ScheduledSink::<CallsStrategy>::builder()
+            .target(test_entry_sink().sink, Duration::ZERO);
+    }
+
+    #[test]
+    fn flush_all_resets_every_deadline() {
+        let (mut schedule, inspectors) = schedule(&[1, 3]);
+
+        schedule.merge(Calls(5));
+        schedule.flush_all(Duration::from_millis(500));
+        schedule.flush_due(Duration::from_secs(1));
+
+        assert_eq!(flushed(&inspectors[0]), [5]);
+        assert_eq!(flushed(&inspectors[1]), [5]);
+    }
+}
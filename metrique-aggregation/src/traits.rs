@@ -26,6 +26,11 @@
 //! The [`AggregateStrategy`] trait ties together a source type with its merge behavior and
 //! key extraction strategy. The `#[aggregate]` macro generates these implementations automatically.
 //!
+//! [`CombineValue`] and [`Combine`] are the field-level and entry-level counterparts for merging
+//! two accumulators together, e.g. to build the aggregate of a long window out of the aggregates
+//! of shorter ones. The `#[aggregate]` macro implements [`Combine`] when every field's strategy
+//! implements [`CombineValue`].
+//!
 //! ## Key extraction: [`Key`]
 //!
 //! The [`Key`] trait extracts grouping keys from source entries, enabling keyed aggregation
@@ -96,6 +101,15 @@ pub trait AggregateValue<T> {
     fn insert(accum: &mut Self::Aggregated, value: T);
 }
 
+/// Merges one accumulator of an [`AggregateValue`] strategy into another.
+///
+/// This is implemented by the built-in strategies. Merging `other` into `accum` must give the same
+/// result as inserting the values of `other` into `accum`.
+pub trait CombineValue<T>: AggregateValue<T> {
+    /// Merge the values aggregated in `other` into `accum`
+    fn combine(accum: &mut Self::Aggregated, other: &Self::Aggregated);
+}
+
 /// Key extraction trait for aggregation strategies.
 ///
 /// Extracts grouping keys from source entries to enable keyed aggregation. Entries with
@@ -245,6 +259,16 @@ pub trait MergeRef: Merge {
     fn merge_ref(accum: &mut Self::Merged, input: &Self);
 }
 
+/// Merges one accumulator of a [`Merge`] type into another.
+///
+/// This lets several accumulators share the work of merging entries, see
+/// [`ScheduledSink`](crate::sink::ScheduledSink). The `#[aggregate]` macro implements it when
+/// the strategy of every field implements [`CombineValue`].
+pub trait Combine: Merge {
+    /// Merge the entries aggregated in `other` into `accum`
+    fn combine(accum: &mut Self::Merged, other: &Self::Merged);
+}
+
 /// Ties together source type, merge behavior, and key extraction.
 ///
 /// This trait combines all the pieces needed for aggregation into a single strategy type.
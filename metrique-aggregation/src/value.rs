@@ -4,7 +4,7 @@ use metrique_writer::MetricValue;
 
 use crate::{
     histogram::{Histogram, SortAndMerge},
-    traits::AggregateValue,
+    traits::{AggregateValue, Combine, CombineValue},
 };
 use std::{marker::PhantomData, ops::AddAssign};
 
@@ -25,6 +25,15 @@ where
     }
 }
 
+impl<T> CombineValue<T> for Sum
+where
+    T: Default + AddAssign + Clone,
+{
+    fn combine(accum: &mut T, other: &T) {
+        *accum += other.clone();
+    }
+}
+
 /// Aggregation strategy that preserves the most recently set value
 pub struct KeepLast;
 
@@ -36,6 +45,14 @@ impl<T: Clone> AggregateValue<T> for KeepLast {
     }
 }
 
+impl<T: Clone> CombineValue<T> for KeepLast {
+    fn combine(accum: &mut Self::Aggregated, other: &Self::Aggregated) {
+        if other.is_some() {
+            accum.clone_from(other);
+        }
+    }
+}
+
 /// Wrap a given strategy to support optional values by ignoring `None`
 pub struct MergeOptions<Inner> {
     _data: PhantomData<Inner>,
@@ -54,6 +71,15 @@ where
     }
 }
 
+impl<T, S> CombineValue<Option<T>> for MergeOptions<S>
+where
+    S: CombineValue<T>,
+{
+    fn combine(accum: &mut Self::Aggregated, other: &Self::Aggregated) {
+        S::combine(accum, other);
+    }
+}
+
 /// Helper wrapper used by the aggregate macro to automatically copy Copy types in MergeRef
 pub struct CopyWrapper<Inner> {
     data: PhantomData<Inner>,
@@ -87,6 +113,15 @@ where
     }
 }
 
+impl<T> CombineValue<T> for Flatten
+where
+    T: Combine,
+{
+    fn combine(accum: &mut Self::Aggregated, other: &Self::Aggregated) {
+        T::combine(accum, other);
+    }
+}
+
 /// Distribution preserves all values while compressing duplicates
 ///
 /// This is effectively a type alias for `Histogram<T, SortAndMerge>`, however,
@@ -100,6 +135,12 @@ impl<T: MetricValue> AggregateValue<T> for Distribution {
     }
 }
 
+impl<T: MetricValue> CombineValue<T> for Distribution {
+    fn combine(accum: &mut Self::Aggregated, other: &Self::Aggregated) {
+        accum.combine(other);
+    }
+}
+
 /// Key type for aggregations with no key fields
 #[derive(Clone, Hash, PartialEq, Eq)]
 pub struct NoKey;
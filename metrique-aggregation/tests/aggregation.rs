@@ -372,3 +372,161 @@ fn last_value_wins() {
         value: Option<String>,
     }
 }
+
+#[test]
+fn test_aggregate_histogram_quantiles() {
+    #[aggregate]
+    #[metrics]
+    pub struct Request {
+        #[aggregate(histogram(quantiles = [0.5, 0.9, 0.99]))]
+        #[metrics(unit = Millisecond)]
+        latency: Duration,
+
+        #[aggregate(strategy = Histogram<u64, SortAndMerge>, histogram(quantiles = [0, 1]))]
+        size: u64,
+    }
+
+    #[metrics(rename_all = "PascalCase")]
+    struct Metrics {
+        #[metrics(flatten)]
+        requests: Aggregate<Request>,
+    }
+
+    let mut metrics = Metrics {
+        requests: Aggregate::default(),
+    };
+    for i in 1..=100 {
+        metrics.requests.insert(Request {
+            latency: Duration::from_millis(i),
+            size: i,
+        });
+    }
+
+    let entry = test_metric(metrics);
+    let latency = &entry.metrics["Latency"];
+    check!(latency.unit == Unit::Second(NegativeScale::Milli));
+    check!(latency.num_observations() == 3);
+    let latency: Vec<f64> = latency
+        .distribution
+        .iter()
+        .map(|obs| match obs {
+            Observation::Floating(v) => *v,
+            other => panic!("expected a quantile, got {other:?}"),
+        })
+        .collect();
+    // the default strategy is bucketed, so quantiles are approximate
+    for (actual, expected) in latency.iter().zip([50.0, 90.0, 99.0]) {
+        check!((actual - expected).abs() / expected < 0.07);
+    }
+    check!(
+        entry.metrics["Size"].distribution
+            == vec![Observation::Floating(1.0), Observation::Floating(100.0)]
+    );
+}
+
+#[test]
+fn test_aggregate_histogram_quantiles_fields() {
+    use metrique_aggregation::histogram::{QuantileHistogram, Quantiles};
+
+    struct Extremes;
+    impl Quantiles for Extremes {
+        const QUANTILES: &'static [f64] = &[0.0, 1.0];
+    }
+
+    #[aggregate]
+    #[metrics]
+    pub struct ShardResult {
+        // merging partial quantile histograms keeps the full distribution, so this is exact
+        #[aggregate(
+            strategy = Histogram<Duration, SortAndMerge>,
+            histogram(quantiles = [0.0, 1.0])
+        )]
+        #[metrics(unit = Millisecond)]
+        latency: QuantileHistogram<Duration, Extremes, SortAndMerge>,
+    }
+
+    #[metrics(rename_all = "PascalCase")]
+    struct QueryMetrics {
+        #[metrics(flatten)]
+        shards: Aggregate<ShardResult>,
+    }
+
+    let mut query = QueryMetrics {
+        shards: Aggregate::default(),
+    };
+    for latencies in [[20, 40], [10, 30]] {
+        let mut shard = ShardResult {
+            latency: QuantileHistogram::default(),
+        };
+        for latency in latencies {
+            shard.latency.add_value(Duration::from_millis(latency));
+        }
+        query.shards.insert(shard);
+    }
+
+    let entry = test_metric(query);
+    check!(
+        entry.metrics["Latency"].distribution
+            == vec![Observation::Floating(10.0), Observation::Floating(40.0)]
+    );
+}
+
+#[test]
+fn test_combine_aggregated_entries() {
+    use metrique_aggregation::traits::{Combine, Merge};
+
+    #[aggregate(direct)]
+    struct Upload {
+        #[aggregate(strategy = Sum)]
+        bytes: u64,
+        #[aggregate(strategy = Histogram<u64, SortAndMerge>)]
+        parts: u64,
+        #[aggregate(strategy = KeepLast)]
+        bucket: String,
+    }
+
+    let upload = |bytes, parts, bucket: &str| Upload {
+        bytes,
+        parts,
+        bucket: bucket.to_string(),
+    };
+    let mut first = Upload::new_default_merged();
+    <Upload as Merge>::merge(&mut first, upload(10, 1, "a"));
+    let mut second = Upload::new_default_merged();
+    <Upload as Merge>::merge(&mut second, upload(20, 2, "b"));
+    <Upload as Merge>::merge(&mut second, upload(30, 2, "c"));
+
+    <Upload as Combine>::combine(&mut first, &second);
+    let entry = test_metric(first);
+    check!(entry.metrics["bytes"].as_u64() == 60);
+    check!(entry.metrics["parts"].flatten_and_sort() == vec![1.0, 2.0, 2.0]);
+    check!(entry.values["bucket"] == "c");
+}
+
+/// Strategies that can't combine accumulators can still be used, the aggregated entry just
+/// doesn't implement `Combine`
+#[test]
+fn test_aggregate_with_strategy_that_does_not_combine() {
+    use metrique_aggregation::traits::AggregateValue;
+
+    struct Max;
+
+    impl AggregateValue<u64> for Max {
+        type Aggregated = u64;
+
+        fn insert(accum: &mut u64, value: u64) {
+            *accum = (*accum).max(value);
+        }
+    }
+
+    #[aggregate(direct)]
+    struct Request {
+        #[aggregate(strategy = Max)]
+        latency: u64,
+    }
+
+    let mut aggregate = Aggregate::<Request>::default();
+    aggregate.insert_direct(Request { latency: 3 });
+    aggregate.insert_direct(Request { latency: 1 });
+    check!(test_metric(aggregate).metrics["latency"].as_u64() == 3);
+}
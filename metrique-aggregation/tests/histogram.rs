@@ -584,3 +584,148 @@ fn test_histogram_microsecond_accuracy() {
     check_accuracy(0.005, buckets, 50.0, 6.25);
     check_accuracy(1.0, buckets, 100.0, 6.25);
 }
+
+#[metrics]
+struct WeightedMetrics {
+    #[metrics(unit = Millisecond)]
+    exponential: Histogram<Duration, ExponentialAggregationStrategy>,
+    #[metrics(unit = Millisecond)]
+    sorted: Histogram<Duration, SortAndMerge>,
+    #[metrics(unit = Millisecond)]
+    shared: SharedHistogram<Duration, AtomicExponentialAggregationStrategy>,
+}
+
+impl WeightedMetrics {
+    fn new() -> Self {
+        Self {
+            exponential: Histogram::default(),
+            sorted: Histogram::default(),
+            shared: SharedHistogram::default(),
+        }
+    }
+
+    fn add(&mut self, value: Duration, weight: u64) {
+        self.exponential.add_weighted_value(value, weight);
+        self.sorted.add_weighted_value(value, weight);
+        self.shared.add_weighted_value(value, weight);
+    }
+}
+
+#[test]
+fn weighted_matches_unweighted_population() {
+    // the population, as (value, number of occurrences)
+    let population = [(1, 50), (5, 30), (20, 15), (100, 4), (1000, 1)];
+
+    let mut unweighted = WeightedMetrics::new();
+    let mut weighted = WeightedMetrics::new();
+    for (millis, count) in population {
+        let value = Duration::from_millis(millis);
+        for _ in 0..count {
+            unweighted.add(value, 1);
+        }
+        weighted.add(value, count);
+    }
+
+    let unweighted = test_metric(unweighted);
+    let weighted = test_metric(weighted);
+    for name in ["exponential", "sorted", "shared"] {
+        check!(
+            weighted.metrics[name].distribution == unweighted.metrics[name].distribution,
+            "{name}"
+        );
+    }
+    check!(
+        weighted.metrics["sorted"].distribution
+            == population
+                .map(|(millis, count)| Observation::Repeated {
+                    total: (millis * count) as f64,
+                    occurrences: count,
+                })
+                .to_vec()
+    );
+}
+
+#[test]
+fn weighted_sample_estimates_population_percentiles() {
+    let mut rng = ChaCha8Rng::seed_from_u64(7);
+    let mut population: Vec<f64> = (0..100_000)
+        .map(|_| rng.random_range(1.0..1000.0))
+        .collect();
+
+    // keep 1 in 10 values, and weight each kept value by the inverse sample rate
+    let mut weighted = Histogram::<f64, SortAndMerge>::default();
+    let mut unweighted = Histogram::<f64, SortAndMerge>::default();
+    for value in population.iter().step_by(10) {
+        weighted.add_weighted_value(value, 10);
+        unweighted.add_value(value);
+    }
+
+    #[metrics]
+    struct Sampled {
+        weighted: Histogram<f64, SortAndMerge>,
+        unweighted: Histogram<f64, SortAndMerge>,
+    }
+    let entry = test_metric(Sampled {
+        weighted,
+        unweighted,
+    });
+    let weighted = &entry.metrics["weighted"].distribution;
+    let unweighted = &entry.metrics["unweighted"].distribution;
+
+    let count = |observations: &[Observation]| {
+        observations
+            .iter()
+            .map(|obs| match obs {
+                Observation::Repeated { occurrences, .. } => *occurrences,
+                _ => 1,
+            })
+            .sum::<u64>()
+    };
+    check!(count(weighted) == 100_000);
+    check!(count(unweighted) == 10_000);
+
+    population.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    for percentile in [50.0, 90.0, 99.0] {
+        let expected = calculate_percentile(&population, percentile);
+        check_accuracy(expected, weighted, percentile, 2.0);
+    }
+}
+
+#[test]
+fn zero_weight_records_nothing() {
+    let mut metrics = WeightedMetrics::new();
+    metrics.add(Duration::from_millis(5), 0);
+    let entry = test_metric(metrics);
+    check!(entry.metrics["exponential"].distribution.is_empty());
+    check!(entry.metrics["sorted"].distribution.is_empty());
+    check!(entry.metrics["shared"].distribution.is_empty());
+}
+
+#[test]
+fn sort_and_merge_stores_large_weights_as_counts() {
+    #[metrics]
+    struct Weighted {
+        sorted: Histogram<Duration, SortAndMerge>,
+    }
+
+    let mut sorted = Histogram::<Duration, SortAndMerge>::default();
+    // storing every weighted observation would not fit in memory
+    sorted.add_weighted_value(Duration::from_millis(5), u64::MAX);
+    sorted.add_weighted_value(Duration::from_millis(5), 1000);
+    sorted.add_weighted_value(Duration::from_millis(1), 1000);
+
+    let entry = test_metric(Weighted { sorted });
+    check!(
+        entry.metrics["sorted"].distribution
+            == [
+                Observation::Repeated {
+                    total: 1000.0,
+                    occurrences: 1000,
+                },
+                Observation::Repeated {
+                    total: 5.0 * u64::MAX as f64,
+                    occurrences: u64::MAX,
+                },
+            ]
+    );
+}
@@ -0,0 +1,118 @@
+//! Tests for ScheduledSink, which flushes several targets at their own cadence
+
+use assert2::check;
+use metrique::unit_of_work::metrics;
+use metrique_aggregation::aggregate;
+use metrique_aggregation::histogram::Histogram;
+use metrique_aggregation::sink::ScheduledSink;
+use metrique_aggregation::value::Sum;
+use metrique_timesource::{TimeSource, fakes::ManualTimeSource};
+use metrique_writer::test_util::{Inspector, test_entry_sink};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+#[aggregate]
+#[metrics]
+pub struct ApiCall {
+    #[aggregate(key)]
+    endpoint: String,
+
+    #[aggregate(strategy = Sum)]
+    count: u64,
+
+    #[aggregate(strategy = Histogram<u64>)]
+    size: u64,
+}
+
+fn call(count: u64) -> ApiCall {
+    ApiCall {
+        endpoint: "api1".to_string(),
+        count,
+        size: 10,
+    }
+}
+
+async fn wait_for_entries(inspector: &Inspector, count: usize) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while inspector.entries().len() < count {
+        assert!(
+            Instant::now() < deadline,
+            "timed out waiting for {count} entries"
+        );
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+fn total_count(inspector: &Inspector) -> u64 {
+    inspector
+        .entries()
+        .iter()
+        .map(|entry| entry.metrics["count"].as_u64())
+        .sum()
+}
+
+async fn wait_for_total_count(inspector: &Inspector, count: u64) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while total_count(inspector) < count {
+        assert!(
+            Instant::now() < deadline,
+            "timed out waiting for a total count of {count}"
+        );
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+}
+
+#[tokio::test]
+async fn targets_emit_independently() {
+    let fast = test_entry_sink();
+    let slow = test_entry_sink();
+    let clock = ManualTimeSource::at_time(UNIX_EPOCH);
+
+    let sink = ScheduledSink::<ApiCall>::builder()
+        .with_time_source(TimeSource::custom(clock.clone()))
+        .target(fast.sink, Duration::from_secs(1))
+        .target(slow.sink, Duration::from_secs(3600))
+        .build();
+
+    call(1).close_and_merge(sink.clone());
+    call(2).close_and_merge(sink.clone());
+
+    // the fast target emits on its own, without waiting for the slow one
+    clock.advance(Duration::from_secs(1));
+    wait_for_total_count(&fast.inspector, 3).await;
+    check!(fast.inspector.entries().len() == 1);
+    check!(slow.inspector.entries().is_empty());
+
+    call(4).close_and_merge(sink.clone());
+    clock.advance(Duration::from_secs(1));
+    wait_for_total_count(&fast.inspector, 7).await;
+    check!(fast.inspector.entries().len() == 2);
+    check!(fast.inspector.get(1).metrics["count"].as_u64() == 4);
+    check!(slow.inspector.entries().is_empty());
+
+    // flushing the fast target did not reset the slow target's state
+    clock.advance(Duration::from_secs(3598));
+    wait_for_entries(&slow.inspector, 1).await;
+    let slow_entries = slow.inspector.entries();
+    check!(slow_entries.len() == 1);
+    check!(slow_entries[0].metrics["count"].as_u64() == 7);
+    check!(slow_entries[0].metrics["size"].num_observations() == 3);
+}
+
+#[tokio::test]
+async fn dropping_sink_flushes_all_targets() {
+    let a = test_entry_sink();
+    let b = test_entry_sink();
+
+    let sink = ScheduledSink::<ApiCall>::builder()
+        .target(a.sink, Duration::from_secs(3600))
+        .target(b.sink, Duration::from_secs(7200))
+        .build();
+
+    call(5).close_and_merge(sink.clone());
+    drop(sink);
+
+    wait_for_entries(&a.inspector, 1).await;
+    wait_for_entries(&b.inspector, 1).await;
+    check!(a.inspector.get(0).metrics["count"].as_u64() == 5);
+    check!(b.inspector.get(0).metrics["count"].as_u64() == 5);
+}
@@ -152,8 +152,102 @@ impl CloseValue for Counter {
     }
 }
 
+/// An atomic value that can go up and down, like the number of in-flight requests.
+///
+/// Unlike [`Counter`], `Gauge` can be decremented, and all updates saturate: the value never
+/// goes below 0 or wraps past `u64::MAX`. Updates take `&self`, so a `Gauge` can be shared (e.g.
+/// behind an `Arc`, or in a `SharedChild`). Closing reads the instantaneous value with
+/// [`Ordering::Relaxed`], without resetting it.
+///
+/// ```
+/// use metrique_core::{CloseValue, Gauge};
+///
+/// let in_flight = Gauge::new(0);
+/// in_flight.increment();
+/// in_flight.add(2);
+/// in_flight.decrement();
+/// assert_eq!((&in_flight).close(), 2);
+///
+/// // decrementing saturates at zero
+/// in_flight.sub(5);
+/// assert_eq!(in_flight.close(), 0);
+/// ```
+///
+/// [`Ordering::Relaxed`]: std::sync::atomic::Ordering::Relaxed
+#[derive(Default, Debug)]
+pub struct Gauge(AtomicU64);
+
+impl Gauge {
+    /// Create a new [`Gauge`], initialized to a specific value
+    pub const fn new(value: u64) -> Self {
+        Self(AtomicU64::new(value))
+    }
+
+    /// Add 1 to this gauge, saturating at `u64::MAX`
+    pub fn increment(&self) {
+        self.add(1);
+    }
+
+    /// Subtract 1 from this gauge, saturating at 0
+    pub fn decrement(&self) {
+        self.sub(1);
+    }
+
+    /// Increase the value of this gauge by `i`, saturating at `u64::MAX`
+    pub fn add(&self, i: u64) {
+        self.update(|v| v.saturating_add(i));
+    }
+
+    /// Decrease the value of this gauge by `i`, saturating at 0
+    pub fn sub(&self, i: u64) {
+        self.update(|v| v.saturating_sub(i));
+    }
+
+    /// Set this gauge to `i`, discarding the previous value
+    pub fn set(&self, i: u64) {
+        self.0.store(i, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    /// Returns the current value of this gauge
+    pub fn get(&self) -> u64 {
+        self.0.load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    fn update(&self, f: impl Fn(u64) -> u64) {
+        self.0
+            .fetch_update(
+                std::sync::atomic::Ordering::Relaxed,
+                std::sync::atomic::Ordering::Relaxed,
+                |v| Some(f(v)),
+            )
+            .ok();
+    }
+}
+
+impl CloseValue for &'_ Gauge {
+    type Closed = u64;
+
+    fn close(self) -> Self::Closed {
+        self.get()
+    }
+}
+
+impl CloseValue for Gauge {
+    type Closed = u64;
+
+    fn close(self) -> Self::Closed {
+        self.get()
+    }
+}
+
+// The unsigned integer atomics and `AtomicBool` close to their plain value, so they can be used
+// directly as `#[metrics]` fields (including behind an `Arc`).
 macro_rules! close_value_atomic {
     (atomic: $atomic: ty, inner: $inner: ty) => {
+        /// Reads the current value with `Ordering::Relaxed`, without resetting it.
+        ///
+        /// An atomic shared between several entries reports its running value in each of them.
+        /// Since the load is relaxed, closing does not synchronize with other memory accesses.
         impl $crate::CloseValue for &'_ $atomic {
             type Closed = $inner;
 
@@ -162,6 +256,7 @@ macro_rules! close_value_atomic {
             }
         }
 
+        /// Reads the current value with `Ordering::Relaxed`.
         impl $crate::CloseValue for $atomic {
             type Closed = $inner;
 
@@ -186,6 +281,26 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn atomics_close_reads_without_reset() {
+        use std::sync::atomic::Ordering;
+
+        let count = AtomicU64::new(3);
+        let flag = AtomicBool::new(true);
+        assert_eq!((&count).close(), 3);
+        assert!((&flag).close());
+        // closing a reference does not reset the value
+        count.fetch_add(2, Ordering::Relaxed);
+        assert_eq!((&count).close(), 5);
+        assert_eq!(count.load(Ordering::Relaxed), 5);
+
+        let shared = Arc::new(AtomicUsize::new(7));
+        assert_eq!((&shared).close(), 7);
+        assert_eq!(AtomicU8::new(u8::MAX).close(), u8::MAX);
+        assert_eq!(AtomicU16::new(16).close(), 16);
+        assert_eq!(AtomicU32::new(32).close(), 32);
+    }
+
     #[test]
     fn increment_scoped() {
         let counter = Counter::new(0);
@@ -274,4 +389,42 @@ mod tests {
         drop(g2);
         assert_eq!(counter.0.load(std::sync::atomic::Ordering::Relaxed), 0);
     }
+
+    #[test]
+    fn gauge_saturates() {
+        let gauge = Gauge::new(1);
+        gauge.decrement();
+        gauge.decrement();
+        assert_eq!(gauge.get(), 0);
+        gauge.add(3);
+        gauge.sub(1);
+        assert_eq!((&gauge).close(), 2);
+
+        gauge.set(u64::MAX - 1);
+        gauge.add(5);
+        gauge.increment();
+        assert_eq!(gauge.close(), u64::MAX);
+    }
+
+    #[test]
+    fn gauge_shared_across_threads() {
+        let gauge = Arc::new(Gauge::new(0));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let gauge = Arc::clone(&gauge);
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        gauge.increment();
+                    }
+                    for _ in 0..500 {
+                        gauge.decrement();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!((&gauge).close(), 2000);
+    }
 }
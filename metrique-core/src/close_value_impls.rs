@@ -5,12 +5,16 @@
 
 use core::time::Duration;
 use std::marker::PhantomData;
+use std::num::{NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroUsize};
 use std::sync::{Arc, MutexGuard};
 use std::time::SystemTime;
 use std::{borrow::Cow, sync::Mutex};
 
 use metrique_writer_core::EntryWriter;
-use metrique_writer_core::value::WithDimensions;
+use metrique_writer_core::config::HighPriority;
+use metrique_writer_core::entry::{DefaultUnitEntryWriter, WithEntryUnit};
+use metrique_writer_core::unit::UnitTag;
+use metrique_writer_core::value::{AsEpochMillis, WithDimensions};
 use metrique_writer_core::value::{FlagConstructor, ForceFlag};
 
 use crate::{CloseValue, CloseValueRef, InflectableEntry};
@@ -53,6 +57,8 @@ macro_rules! close_value {
 close_value_ref!(
     bool, Duration, f32, f64, u16, u32, u64, u8, usize, SystemTime
 );
+close_value_ref!(NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroUsize);
+close_value_ref!(HighPriority, AsEpochMillis);
 
 close_value!(String);
 
@@ -240,6 +246,15 @@ impl<V: CloseValue> CloseValue for Vec<V> {
     }
 }
 
+#[diagnostic::do_not_recommend]
+impl<V: CloseValue, const N: usize> CloseValue for [V; N] {
+    type Closed = [V::Closed; N];
+
+    fn close(self) -> Self::Closed {
+        self.map(CloseValue::close)
+    }
+}
+
 #[diagnostic::do_not_recommend]
 impl<T: CloseValue, const N: usize> CloseValue for WithDimensions<T, N> {
     type Closed = WithDimensions<T::Closed, N>;
@@ -318,6 +333,30 @@ impl<NS: crate::NameStyle, T: InflectableEntry<NS>, const N: usize> InflectableE
     }
 }
 
+#[diagnostic::do_not_recommend]
+impl<T: CloseValue, U> CloseValue for WithEntryUnit<T, U> {
+    type Closed = WithEntryUnit<T::Closed, U>;
+
+    fn close(self) -> Self::Closed {
+        self.map_value(|v| v.close())
+    }
+}
+
+#[diagnostic::do_not_recommend]
+impl<NS: crate::NameStyle, T: InflectableEntry<NS>, U: UnitTag> InflectableEntry<NS>
+    for WithEntryUnit<T, U>
+{
+    fn write<'a>(&'a self, writer: &mut impl metrique_writer_core::EntryWriter<'a>) {
+        <T as InflectableEntry<NS>>::write(self, &mut DefaultUnitEntryWriter::<_, U>::new(writer))
+    }
+
+    fn sample_group(
+        &self,
+    ) -> impl Iterator<Item = metrique_writer_core::entry::SampleGroupElement> {
+        <T as InflectableEntry<NS>>::sample_group(self)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::sync::{Arc, Mutex};
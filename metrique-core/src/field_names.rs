@@ -0,0 +1,146 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Support for the `FIELD_NAMES` constant generated by `#[metrics]`. This is used by the
+//! `#[metrics]` macro and is not a stable API.
+
+use std::{borrow::Cow, sync::Arc};
+
+use crate::{NameStyle, concat::MaybeConstStr, namestyle::Identity};
+
+/// The maximum number of names a single [`FieldNameList`] can hold
+pub const MAX_FIELD_NAMES: usize = 256;
+
+/// A list of metric names, built at compile time
+#[derive(Clone, Copy)]
+pub struct FieldNameList {
+    names: [&'static str; MAX_FIELD_NAMES],
+    len: usize,
+}
+
+impl FieldNameList {
+    /// Create an empty list
+    pub const fn new() -> Self {
+        Self {
+            names: [""; MAX_FIELD_NAMES],
+            len: 0,
+        }
+    }
+
+    /// Append the value of the constant string `S`
+    pub const fn push<S: MaybeConstStr>(&mut self) {
+        // names over the `Concatenated` length limit are only available at runtime
+        assert!(
+            S::HAVE_VAL,
+            "metric name is too long to be computed at compile time"
+        );
+        self.push_str(S::MAYBE_VAL);
+    }
+
+    /// Append every name in `names`
+    pub const fn extend(&mut self, names: &[&'static str]) {
+        let mut i = 0;
+        while i < names.len() {
+            self.push_str(names[i]);
+            i += 1;
+        }
+    }
+
+    const fn push_str(&mut self, name: &'static str) {
+        assert!(self.len < MAX_FIELD_NAMES, "too many metric names");
+        self.names[self.len] = name;
+        self.len += 1;
+    }
+
+    /// Return the names in the list
+    pub const fn as_slice(&'static self) -> &'static [&'static str] {
+        self.names.split_at(self.len).0
+    }
+}
+
+impl Default for FieldNameList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Return whether `a` and `b` have no name in common.
+///
+/// This is evaluated at compile time when subfields are merged into an entry, so that merging
+/// entries that write the same name fails to compile.
+pub const fn is_disjoint(a: &[&str], b: &[&str]) -> bool {
+    let mut i = 0;
+    while i < a.len() {
+        let mut j = 0;
+        while j < b.len() {
+            if str_eq(a[i], b[j]) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// The names an entry writes when written with the name style `NS`
+pub trait InflectableFieldNames<NS: NameStyle = Identity> {
+    /// The list backing [`Self::FIELD_NAMES`]
+    const FIELD_NAME_LIST: FieldNameList;
+
+    /// The names, in the order they are written
+    const FIELD_NAMES: &'static [&'static str] = {
+        let list: &'static FieldNameList = &Self::FIELD_NAME_LIST;
+        list.as_slice()
+    };
+}
+
+/// The names written by the `I`-th flattened field of an entry, used to scope the prefix
+/// types of each field
+pub trait FlattenedFieldNames<NS: NameStyle, const I: usize> {
+    /// The names, in the order they are written
+    const FIELD_NAMES: &'static [&'static str];
+}
+
+macro_rules! delegate_field_names {
+    ($($ty:ty),*) => {
+        $(
+            impl<NS: NameStyle, T: InflectableFieldNames<NS> + ?Sized> InflectableFieldNames<NS>
+                for $ty
+            {
+                const FIELD_NAME_LIST: FieldNameList = T::FIELD_NAME_LIST;
+                const FIELD_NAMES: &'static [&'static str] = T::FIELD_NAMES;
+            }
+        )*
+    };
+}
+
+delegate_field_names!(&T, Box<T>, Arc<T>);
+
+// `Option` writes the same names when it is `Some`, and nothing when it is `None`
+impl<NS: NameStyle, T: InflectableFieldNames<NS>> InflectableFieldNames<NS> for Option<T> {
+    const FIELD_NAME_LIST: FieldNameList = T::FIELD_NAME_LIST;
+    const FIELD_NAMES: &'static [&'static str] = T::FIELD_NAMES;
+}
+
+impl<NS: NameStyle, T: InflectableFieldNames<NS> + ToOwned + ?Sized> InflectableFieldNames<NS>
+    for Cow<'_, T>
+{
+    const FIELD_NAME_LIST: FieldNameList = T::FIELD_NAME_LIST;
+    const FIELD_NAMES: &'static [&'static str] = T::FIELD_NAMES;
+}
@@ -0,0 +1,142 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt;
+
+use crate::CloseValue;
+
+/// A field wrapper that only closes (and emits) its inner value if a gate returns `true`.
+///
+/// This is useful for metrics that are expensive to compute when closing, e.g. a percentile
+/// over a buffer of values, and that should be skipped entirely when a feature flag is off.
+/// The gate is called once, when the value is closed. If it returns `false`, the inner value is
+/// dropped without being closed and nothing is emitted, like a `None`.
+///
+/// `Gated` closes to an `Option`, so it can wrap both plain values and entries used with
+/// `#[metrics(flatten)]`.
+///
+/// ```
+/// use metrique::Gated;
+/// use metrique::unit_of_work::metrics;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+///
+/// static DETAILED_METRICS: AtomicBool = AtomicBool::new(false);
+///
+/// fn detailed_metrics_enabled() -> bool {
+///     DETAILED_METRICS.load(Ordering::Relaxed)
+/// }
+///
+/// #[metrics]
+/// struct RequestMetrics {
+///     // only emitted if detailed metrics are enabled
+///     payload_size: Gated<usize, fn() -> bool>,
+/// }
+///
+/// let metrics = RequestMetrics {
+///     payload_size: Gated::new(1024, detailed_metrics_enabled),
+/// };
+/// ```
+#[derive(Clone)]
+pub struct Gated<T, F: Fn() -> bool> {
+    value: T,
+    gate: F,
+}
+
+impl<T, F: Fn() -> bool> Gated<T, F> {
+    /// Wrap `value`, only closing it if `gate` returns `true`
+    pub fn new(value: T, gate: F) -> Self {
+        Self { value, gate }
+    }
+
+    /// Return a reference to the inner value
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Return a mutable reference to the inner value
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Return the inner value
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: fmt::Debug, F: Fn() -> bool> fmt::Debug for Gated<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Gated")
+            .field("value", &self.value)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T: CloseValue, F: Fn() -> bool> CloseValue for Gated<T, F> {
+    type Closed = Option<T::Closed>;
+
+    fn close(self) -> Self::Closed {
+        (self.gate)().then(|| self.value.close())
+    }
+}
+
+impl<'a, T, F: Fn() -> bool> CloseValue for &'a Gated<T, F>
+where
+    &'a T: CloseValue,
+{
+    type Closed = Option<<&'a T as CloseValue>::Closed>;
+
+    fn close(self) -> Self::Closed {
+        (self.gate)().then(|| self.value.close())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{CloseValue, CloseValueRef};
+
+    use super::Gated;
+
+    /// Closes to its value, counting how often it was closed
+    struct Expensive<'a>(u64, &'a Cell<usize>);
+
+    impl CloseValue for &Expensive<'_> {
+        type Closed = u64;
+
+        fn close(self) -> u64 {
+            self.1.set(self.1.get() + 1);
+            self.0
+        }
+    }
+
+    impl CloseValue for Expensive<'_> {
+        type Closed = u64;
+
+        fn close(self) -> u64 {
+            self.close_ref()
+        }
+    }
+
+    #[test]
+    fn enabled_closes_value() {
+        let closed = Cell::new(0);
+        assert_eq!(Gated::new(Expensive(5, &closed), || true).close(), Some(5));
+        assert_eq!(closed.get(), 1);
+
+        let gated = Gated::new(Expensive(6, &closed), || true);
+        assert_eq!(gated.close_ref(), Some(6));
+        assert_eq!(closed.get(), 2);
+    }
+
+    #[test]
+    fn disabled_skips_value() {
+        let closed = Cell::new(0);
+        assert_eq!(Gated::new(Expensive(5, &closed), || false).close(), None);
+
+        let gated = Gated::new(Expensive(6, &closed), || false);
+        assert_eq!(gated.close_ref(), None);
+        assert_eq!(closed.get(), 0);
+    }
+}
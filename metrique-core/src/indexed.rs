@@ -0,0 +1,130 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Support for `#[metrics(flatten, index)]` fields. This is used by the `#[metrics]` macro and
+//! is not a stable API.
+
+use std::borrow::Cow;
+
+use metrique_writer_core::{EntryConfig, EntryWriter, Value};
+
+use crate::{InflectableEntry, NameStyle};
+
+/// Write every entry in `entries` with its zero-based index inserted into its names, right after
+/// `prefix`.
+///
+/// `prefix` is the fully inflected prefix the entries are written with. If it ends with a
+/// delimiter (`_` or `-`), the delimiter is repeated after the index, so that e.g. `retry_` and
+/// `latency` make `retry_0_latency` while `Retry` and `Latency` make `Retry0Latency`. Names that
+/// don't start with `prefix` (e.g. from `flatten_entry` fields, which are not inflected) get
+/// the prefix and index prepended.
+pub fn write_indexed<'a, NS: NameStyle, E: InflectableEntry<NS> + 'a>(
+    entries: impl IntoIterator<Item = &'a E>,
+    prefix: &str,
+    writer: &mut impl EntryWriter<'a>,
+) {
+    let separator = match prefix.chars().last() {
+        Some(c @ ('_' | '-')) => Some(c),
+        _ => None,
+    };
+    for (index, entry) in entries.into_iter().enumerate() {
+        entry.write(&mut IndexedEntryWriter {
+            writer,
+            prefix,
+            separator,
+            index,
+        });
+    }
+}
+
+struct IndexedEntryWriter<'w, W> {
+    writer: &'w mut W,
+    prefix: &'w str,
+    separator: Option<char>,
+    index: usize,
+}
+
+impl<'a, W: EntryWriter<'a>> EntryWriter<'a> for IndexedEntryWriter<'_, W> {
+    fn timestamp(&mut self, timestamp: std::time::SystemTime) {
+        self.writer.timestamp(timestamp)
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        let name = name.into();
+        let rest = name.strip_prefix(self.prefix).unwrap_or(&name);
+        let mut indexed = format!("{}{}", self.prefix, self.index);
+        indexed.extend(self.separator);
+        indexed.push_str(rest);
+        self.writer.value(indexed, value)
+    }
+
+    fn config(&mut self, config: &'a dyn EntryConfig) {
+        self.writer.config(config);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrique_writer_core::{EntryWriter, test_stream::DummyEntryWriter};
+
+    use crate::{
+        InflectableEntry, NameStyle,
+        concat::{ConstStr, EmptyConstStr, const_str_value},
+    };
+
+    struct Latency(u64);
+
+    struct LatencyPreserve;
+    impl ConstStr for LatencyPreserve {
+        const VAL: &'static str = "latency";
+    }
+    struct LatencyPascal;
+    impl ConstStr for LatencyPascal {
+        const VAL: &'static str = "Latency";
+    }
+
+    impl<NS: NameStyle> InflectableEntry<NS> for Latency {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            let name = const_str_value::<
+                NS::Inflect<LatencyPreserve, LatencyPascal, LatencyPreserve, LatencyPreserve>,
+            >();
+            writer.value(name, &self.0);
+        }
+    }
+
+    struct RetryPascal;
+    impl ConstStr for RetryPascal {
+        const VAL: &'static str = "Retry";
+    }
+    struct RetrySnake;
+    impl ConstStr for RetrySnake {
+        const VAL: &'static str = "retry_";
+    }
+
+    fn write<NS: NameStyle>(entries: &[Latency]) -> Vec<String> {
+        let prefix = const_str_value::<
+            NS::Inflect<EmptyConstStr, EmptyConstStr, EmptyConstStr, EmptyConstStr>,
+        >();
+        let mut writer = DummyEntryWriter::default();
+        super::write_indexed::<NS, _>(entries, &prefix, &mut writer);
+        writer.0.into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn indexes_names_in_order() {
+        let entries = [Latency(1), Latency(2), Latency(3)];
+        assert_eq!(
+            write::<crate::PascalCase<RetryPascal>>(&entries),
+            ["Retry0Latency", "Retry1Latency", "Retry2Latency"]
+        );
+        assert_eq!(
+            write::<crate::SnakeCase<RetrySnake>>(&entries),
+            ["retry_0_latency", "retry_1_latency", "retry_2_latency"]
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert!(write::<crate::PascalCase<RetryPascal>>(&[]).is_empty());
+    }
+}
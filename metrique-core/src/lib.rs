@@ -10,10 +10,17 @@ use metrique_writer_core::{EntryWriter, entry::SampleGroupElement};
 mod atomics;
 mod close_value_impls;
 pub mod concat;
+#[doc(hidden)]
+pub mod field_names;
+mod gated;
+#[doc(hidden)]
+pub mod indexed;
 mod inflectable_entry_impls;
 mod namestyle;
+mod reset;
 
-pub use atomics::{Counter, CounterGuard, OwnedCounterGuard};
+pub use atomics::{Counter, CounterGuard, Gauge, OwnedCounterGuard};
+pub use gated::Gated;
 pub use namestyle::{DynamicNameStyle, Identity, KebabCase, NameStyle, PascalCase, SnakeCase};
 
 /// Close a given value
@@ -146,6 +153,33 @@ pub trait CloseValue {
     fn close(self) -> Self::Closed;
 }
 
+/// Reset a value to the state it would have if it had just been created
+///
+/// Counters go back to zero, timers restart and timestamps are set to the current time. This
+/// is used by the `reset` method generated by `#[metrics(reset)]`, which allows reusing a
+/// metrics struct across iterations of a loop instead of creating a new one each time.
+///
+/// ```
+/// use metrique::Reset;
+///
+/// struct Attempts(u32);
+///
+/// impl Reset for Attempts {
+///     fn reset(&mut self) {
+///         self.0 = 0;
+///     }
+/// }
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "Reset is not implemented for {Self}",
+    note = "You may need to add `#[metrics(reset)]` to `{Self}` or implement `Reset` directly.",
+    note = "Fields that should keep their value across resets can be marked `#[metrics(no_reset)]`."
+)]
+pub trait Reset {
+    /// Reset the value in place
+    fn reset(&mut self);
+}
+
 mod private {
     pub trait Sealed {}
 }
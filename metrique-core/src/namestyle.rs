@@ -3,9 +3,11 @@
 
 //! Contains various name styles
 
-use std::marker::PhantomData;
+use std::{borrow::Cow, marker::PhantomData};
 
-use crate::concat::{Concatenated, EmptyConstStr, MaybeConstStr};
+use inflector::Inflector;
+
+use crate::concat::{Concatenated, EmptyConstStr, MaybeConstStr, const_str_value};
 
 pub(crate) mod private {
     /// Helper trait to make `NameStyle` sealed
@@ -37,6 +39,19 @@ pub trait NameStyle: private::NameStyleInternal {
     /// Inflect an affix (just inflect, without adding prefixes)
     #[doc(hidden)]
     type InflectAffix<ID: MaybeConstStr, PASCAL: MaybeConstStr, SNAKE: MaybeConstStr, KEBAB: MaybeConstStr>: MaybeConstStr;
+
+    /// Inflect a name that is only known at runtime, adding prefixes
+    #[doc(hidden)]
+    fn inflect_name(name: &str) -> Cow<'_, str>;
+}
+
+fn with_prefix<PREFIX: MaybeConstStr>(name: Cow<'_, str>) -> Cow<'_, str> {
+    if PREFIX::LEN == 0 {
+        return name;
+    }
+    let mut res = const_str_value::<PREFIX>().into_owned();
+    res.push_str(&name);
+    Cow::Owned(res)
 }
 
 /// Inflects names to the identity case
@@ -59,6 +74,10 @@ impl<PREFIX: MaybeConstStr> NameStyle for Identity<PREFIX> {
         SNAKE: MaybeConstStr,
         KEBAB: MaybeConstStr,
     > = ID;
+
+    fn inflect_name(name: &str) -> Cow<'_, str> {
+        with_prefix::<PREFIX>(Cow::Borrowed(name))
+    }
 }
 
 /// inflects names to `PascalCase`
@@ -81,6 +100,10 @@ impl<PREFIX: MaybeConstStr> NameStyle for PascalCase<PREFIX> {
         SNAKE: MaybeConstStr,
         KEBAB: MaybeConstStr,
     > = PASCAL;
+
+    fn inflect_name(name: &str) -> Cow<'_, str> {
+        with_prefix::<PREFIX>(Cow::Owned(name.to_pascal_case()))
+    }
 }
 
 /// Inflects names to `snake_case`
@@ -103,6 +126,10 @@ impl<PREFIX: MaybeConstStr> NameStyle for SnakeCase<PREFIX> {
         SNAKE: MaybeConstStr,
         KEBAB: MaybeConstStr,
     > = SNAKE;
+
+    fn inflect_name(name: &str) -> Cow<'_, str> {
+        with_prefix::<PREFIX>(Cow::Owned(name.to_snake_case()))
+    }
 }
 
 /// Inflects names to `kebab-case`
@@ -125,6 +152,10 @@ impl<PREFIX: MaybeConstStr> NameStyle for KebabCase<PREFIX> {
         SNAKE: MaybeConstStr,
         KEBAB: MaybeConstStr,
     > = KEBAB;
+
+    fn inflect_name(name: &str) -> Cow<'_, str> {
+        with_prefix::<PREFIX>(Cow::Owned(name.to_kebab_case()))
+    }
 }
 
 /// Runtime-selectable name style for metric field names.
@@ -144,3 +175,29 @@ pub enum DynamicNameStyle {
     /// Convert to kebab-case (e.g. `workers-count`).
     KebabCase,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        NameStyle,
+        concat::ConstStr,
+        namestyle::{Identity, KebabCase, PascalCase, SnakeCase},
+    };
+
+    struct Prefix;
+    impl ConstStr for Prefix {
+        const VAL: &'static str = "Retry";
+    }
+
+    #[test]
+    fn inflect_name_applies_style_and_prefix() {
+        assert_eq!(<Identity>::inflect_name("total_time"), "total_time");
+        assert_eq!(<PascalCase>::inflect_name("total_time"), "TotalTime");
+        assert_eq!(<SnakeCase>::inflect_name("TotalTime"), "total_time");
+        assert_eq!(<KebabCase>::inflect_name("total_time"), "total-time");
+        assert_eq!(
+            <PascalCase<Prefix>>::inflect_name("total_time"),
+            "RetryTotalTime"
+        );
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Default implementations of [`Reset`]
+
+use core::time::Duration;
+use std::time::SystemTime;
+
+use crate::{Counter, Gauge, Reset};
+
+macro_rules! reset_to_zero {
+    ($($type:ty),+) => {
+        $(
+            impl Reset for $type {
+                fn reset(&mut self) {
+                    *self = 0 as $type;
+                }
+            }
+        )+
+    };
+}
+
+reset_to_zero!(u8, u16, u32, u64, usize, f32, f64);
+
+impl Reset for bool {
+    fn reset(&mut self) {
+        *self = false;
+    }
+}
+
+impl Reset for Duration {
+    fn reset(&mut self) {
+        *self = Duration::ZERO;
+    }
+}
+
+/// Resets to [`SystemTime::now`]. Use `metrique::timers::Timestamp` to read the time from
+/// the configured time source instead.
+impl Reset for SystemTime {
+    fn reset(&mut self) {
+        *self = SystemTime::now();
+    }
+}
+
+impl Reset for String {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T> Reset for Vec<T> {
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T> Reset for Option<T> {
+    fn reset(&mut self) {
+        *self = None;
+    }
+}
+
+impl<T: Reset + ?Sized> Reset for Box<T> {
+    fn reset(&mut self) {
+        (**self).reset();
+    }
+}
+
+impl Reset for Counter {
+    fn reset(&mut self) {
+        self.set(0);
+    }
+}
+
+impl Reset for Gauge {
+    fn reset(&mut self) {
+        self.set(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use crate::{Counter, Gauge, Reset};
+
+    #[test]
+    fn resets_to_fresh_values() {
+        let mut count = 5u64;
+        let mut ratio = 0.5f64;
+        let mut flag = true;
+        let mut latency = Duration::from_secs(1);
+        let mut time = UNIX_EPOCH;
+        let mut name = String::from("name");
+        let mut values = vec![1, 2];
+        let mut maybe = Some(Box::new(3usize));
+        let mut counter = Counter::new(7);
+        let mut gauge = Gauge::new(7);
+
+        count.reset();
+        ratio.reset();
+        flag.reset();
+        latency.reset();
+        time.reset();
+        name.reset();
+        values.reset();
+        maybe.reset();
+        counter.reset();
+        gauge.reset();
+
+        assert_eq!(count, 0);
+        assert_eq!(ratio, 0.0);
+        assert!(!flag);
+        assert_eq!(latency, Duration::ZERO);
+        assert!(time > SystemTime::UNIX_EPOCH);
+        assert!(name.is_empty());
+        assert!(values.is_empty());
+        assert_eq!(maybe, None);
+        assert_eq!(counter.0.load(Ordering::Relaxed), 0);
+        assert_eq!(gauge.get(), 0);
+    }
+}
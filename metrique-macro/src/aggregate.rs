@@ -12,9 +12,35 @@ struct AggregateField {
     is_key: bool,
     is_ignored: bool,
     use_clone: bool,
+    quantiles: Option<Vec<f64>>,
     metrics_attrs: Vec<Attribute>,
 }
 
+impl AggregateField {
+    /// The strategy of the field, configured with its quantiles if it has any.
+    ///
+    /// `histogram(quantiles = ...)` without a `strategy` defaults to a `Histogram` of `value_ty`.
+    fn strategy(&self, original_name: &Ident, value_ty: &Ts2) -> Ts2 {
+        let strategy = match &self.strategy {
+            Some(strategy) => quote! { #strategy },
+            None => quote! { ::metrique_aggregation::histogram::Histogram<#value_ty> },
+        };
+        if self.quantiles.is_some() {
+            let marker = self.quantiles_marker(original_name);
+            quote! {
+                <#strategy as ::metrique_aggregation::__macro_plumbing::WithQuantiles<#marker>>::Strategy
+            }
+        } else {
+            strategy
+        }
+    }
+
+    /// The type implementing `Quantiles` for the field's `histogram(quantiles = ...)`
+    fn quantiles_marker(&self, original_name: &Ident) -> Ident {
+        format_ident!("__{}_{}_Quantiles", original_name, self.name)
+    }
+}
+
 #[derive(Debug)]
 struct ParsedAggregate {
     fields: Vec<AggregateField>,
@@ -57,6 +83,7 @@ fn parse_aggregate_fields(input: &DeriveInput) -> Result<ParsedAggregate> {
         let mut is_key = false;
         let mut is_ignored = false;
         let mut use_clone = false;
+        let mut quantiles = None;
 
         for attr in &field.attrs {
             if attr.path().is_ident("aggregate") {
@@ -83,12 +110,26 @@ fn parse_aggregate_fields(input: &DeriveInput) -> Result<ParsedAggregate> {
                         }
                         use_clone = true;
                         Ok(())
+                    } else if meta.path.is_ident("histogram") {
+                        meta.parse_nested_meta(|meta| {
+                            if meta.path.is_ident("quantiles") {
+                                if quantiles.is_some() {
+                                    return Err(meta.error("duplicate 'quantiles' attribute"));
+                                }
+                                quantiles = Some(parse_quantiles(&meta.value()?.parse()?)?);
+                                Ok(())
+                            } else {
+                                Err(meta.error(
+                                    "unknown histogram attribute. Valid attributes are: quantiles",
+                                ))
+                            }
+                        })
                     } else {
                         let path_str = meta.path.get_ident()
                             .map(|i| i.to_string())
                             .unwrap_or_else(|| meta.path.to_token_stream().to_string());
                         Err(meta.error(format!(
-                            "unknown aggregate attribute '{}'. Valid attributes are: strategy, key, clone",
+                            "unknown aggregate attribute '{}'. Valid attributes are: strategy, key, clone, histogram",
                             path_str
                         )))
                     }
@@ -107,7 +148,17 @@ fn parse_aggregate_fields(input: &DeriveInput) -> Result<ParsedAggregate> {
             ));
         }
 
-        if !is_key && !is_ignored && strategy.is_none() {
+        if is_key && quantiles.is_some() {
+            return Err(Error::new(
+                name.span(),
+                format!(
+                    "field '{}' cannot have both 'key' and 'histogram' attributes",
+                    name
+                ),
+            ));
+        }
+
+        if !is_key && !is_ignored && strategy.is_none() && quantiles.is_none() {
             return Err(Error::new(
                 name.span(),
                 format!(
@@ -131,6 +182,7 @@ fn parse_aggregate_fields(input: &DeriveInput) -> Result<ParsedAggregate> {
             is_key,
             is_ignored,
             use_clone,
+            quantiles,
             metrics_attrs,
         });
     }
@@ -140,6 +192,36 @@ fn parse_aggregate_fields(input: &DeriveInput) -> Result<ParsedAggregate> {
     })
 }
 
+/// Parse the quantiles of `histogram(quantiles = [...])`, which must be distinct numbers between
+/// 0 and 1
+fn parse_quantiles(array: &syn::ExprArray) -> Result<Vec<f64>> {
+    if array.elems.is_empty() {
+        return Err(Error::new(array.span(), "quantiles cannot be empty"));
+    }
+    let mut quantiles: Vec<f64> = Vec::with_capacity(array.elems.len());
+    for elem in &array.elems {
+        let quantile = match elem {
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Float(lit),
+                ..
+            }) => lit.base10_parse()?,
+            syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Int(lit),
+                ..
+            }) => lit.base10_parse::<u8>()? as f64,
+            _ => return Err(Error::new(elem.span(), "quantiles must be number literals")),
+        };
+        if !(0.0..=1.0).contains(&quantile) {
+            return Err(Error::new(elem.span(), "quantiles must be between 0 and 1"));
+        }
+        if quantiles.contains(&quantile) {
+            return Err(Error::new(elem.span(), "duplicate quantile"));
+        }
+        quantiles.push(quantile);
+    }
+    Ok(quantiles)
+}
+
 pub(crate) fn generate_aggregated_struct(input: &DeriveInput, entry_mode: bool) -> Result<Ts2> {
     let parsed = parse_aggregate_fields(input)?;
     let original_name = &input.ident;
@@ -148,13 +230,13 @@ pub(crate) fn generate_aggregated_struct(input: &DeriveInput, entry_mode: bool)
     let aggregated_fields = parsed.fields.iter().filter(|f| !f.is_key && !f.is_ignored).map(|f| {
         let name = &f.name;
         let metrics_attrs = &f.metrics_attrs;
-        let strategy = f.strategy.as_ref().unwrap();
         let source_ty = &f.ty;
         let value_ty = if entry_mode {
             quote! { <#source_ty as metrique::CloseValue>::Closed }
         } else {
             quote! { #source_ty }
         };
+        let strategy = f.strategy(original_name, &value_ty);
         quote! {
             #(#metrics_attrs)*
             #name: <#strategy as ::metrique_aggregation::__macro_plumbing::AggregateValue<#value_ty>>::Aggregated
@@ -209,7 +291,6 @@ pub(crate) fn generate_aggregate_strategy_impl(
     // Generate Merge impl
     let merge_calls = parsed.fields.iter().filter(|f| !f.is_key && !f.is_ignored).map(|f| {
         let name = &f.name;
-        let strategy = f.strategy.as_ref().unwrap();
         let field_ty = &f.ty;
 
         let value_ty = if entry_mode {
@@ -217,6 +298,7 @@ pub(crate) fn generate_aggregate_strategy_impl(
         } else {
             quote! { #field_ty }
         };
+        let strategy = f.strategy(original_name, &value_ty);
 
         // Check if field has a unit attribute by parsing metrics attributes
         // Only dereference in entry mode, where the field is wrapped in WithUnit
@@ -269,6 +351,45 @@ pub(crate) fn generate_aggregate_strategy_impl(
         }
     };
 
+    // Generate Combine impl, which only applies if every strategy can combine its accumulators.
+    // The `for<'_>` bounds defer that check to the users of the impl, so that strategies that
+    // can't be combined don't break the macro.
+    let combined_fields = parsed
+        .fields
+        .iter()
+        .filter(|f| !f.is_key && !f.is_ignored)
+        .map(|f| {
+            let field_ty = &f.ty;
+            let value_ty = if entry_mode {
+                quote! { <#field_ty as metrique::CloseValue>::Closed }
+            } else {
+                quote! { #field_ty }
+            };
+            let strategy = f.strategy(original_name, &value_ty);
+            (&f.name, strategy, value_ty)
+        })
+        .collect::<Vec<_>>();
+    let combine_bounds = combined_fields.iter().map(|(_, strategy, value_ty)| {
+        quote! {
+            for<'__metrique> #strategy: ::metrique_aggregation::__macro_plumbing::CombineValue<#value_ty>
+        }
+    });
+    let combine_calls = combined_fields.iter().map(|(name, strategy, value_ty)| {
+        quote_spanned! { name.span()=>
+            <#strategy as ::metrique_aggregation::__macro_plumbing::CombineValue<#value_ty>>::combine(&mut accum.#name, &other.#name);
+        }
+    });
+    let combine_impl = quote! {
+        impl ::metrique_aggregation::__macro_plumbing::Combine for #source_ty
+        where
+            #(#combine_bounds),*
+        {
+            fn combine(accum: &mut Self::Merged, other: &Self::Merged) {
+                #(#combine_calls)*
+            }
+        }
+    };
+
     // Generate Key struct and impl if there are key fields
     let (key_struct, key_impl, strategy_key_type) = if key_fields.is_empty() {
         (
@@ -338,6 +459,28 @@ pub(crate) fn generate_aggregate_strategy_impl(
         (key_struct, key_impl, quote! { #key_extractor_name })
     };
 
+    // Generate the quantile configuration of `histogram(quantiles = ...)` fields
+    let quantiles_impls = parsed
+        .fields
+        .iter()
+        .filter(|f| !f.is_ignored)
+        .filter_map(|f| {
+            let quantiles = f.quantiles.as_ref()?;
+            let marker = f.quantiles_marker(original_name);
+            let quantiles = quantiles
+                .iter()
+                .map(|q| proc_macro2::Literal::f64_suffixed(*q));
+            Some(quote! {
+                #[doc(hidden)]
+                #[allow(non_camel_case_types)]
+                #vis struct #marker;
+
+                impl ::metrique_aggregation::__macro_plumbing::Quantiles for #marker {
+                    const QUANTILES: &'static [f64] = &[#(#quantiles),*];
+                }
+            })
+        });
+
     // Generate AggregateStrategy impl
     let strategy_impl = quote! {
         impl ::metrique_aggregation::__macro_plumbing::AggregateStrategy for #original_name {
@@ -347,7 +490,9 @@ pub(crate) fn generate_aggregate_strategy_impl(
     };
 
     Ok(quote! {
+        #(#quantiles_impls)*
         #merge_impl
+        #combine_impl
         #key_struct
         #key_impl
         #strategy_impl
@@ -378,7 +523,6 @@ pub(crate) fn generate_merge_ref_impl(
     // Generate merge_ref calls for non-key fields
     let merge_ref_calls = parsed.fields.iter().filter(|f| !f.is_key && !f.is_ignored).map(|f| {
         let name = &f.name;
-        let strategy = f.strategy.as_ref().unwrap();
         let field_ty = &f.ty;
 
         let value_ty = if entry_mode {
@@ -386,6 +530,7 @@ pub(crate) fn generate_merge_ref_impl(
         } else {
             quote! { #field_ty }
         };
+        let strategy = f.strategy(original_name, &value_ty);
 
         let field_span = name.span();
 
@@ -633,6 +778,63 @@ mod tests {
         insta::assert_snapshot!("aggregate_with_ignore", parsed_file);
     }
 
+    #[test]
+    fn test_aggregate_with_quantiles() {
+        let input = quote! {
+            #[metrics]
+            pub struct ApiCall {
+                #[aggregate(histogram(quantiles = [0.5, 0.9, 0.99]))]
+                #[metrics(unit = Millisecond)]
+                latency: Duration,
+                #[aggregate(strategy = Histogram<usize, SortAndMerge>, histogram(quantiles = [0.5]))]
+                response_size: usize,
+            }
+        };
+
+        let parsed_file = aggregate_impl_string(input);
+        insta::assert_snapshot!("aggregate_with_quantiles", parsed_file);
+    }
+
+    #[test]
+    fn test_invalid_quantiles() {
+        use assert2::check;
+
+        let error = |attr: Ts2| {
+            let input = quote! {
+                struct ApiCall {
+                    #attr
+                    latency: Duration,
+                }
+            };
+            parse_aggregate_fields(&syn::parse2(input).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+
+        check!(
+            error(quote!(#[aggregate(histogram(quantiles = [0.5, 1.5]))]))
+                == "quantiles must be between 0 and 1"
+        );
+        check!(
+            error(quote!(#[aggregate(histogram(quantiles = []))])) == "quantiles cannot be empty"
+        );
+        check!(
+            error(quote!(#[aggregate(histogram(quantiles = [0.5, 0.5]))])) == "duplicate quantile"
+        );
+        check!(
+            error(quote!(#[aggregate(histogram(quantiles = [P50]))]))
+                == "quantiles must be number literals"
+        );
+        check!(
+            error(quote!(#[aggregate(histogram(buckets = 10))]))
+                == "unknown histogram attribute. Valid attributes are: quantiles"
+        );
+        check!(
+            error(quote!(#[aggregate(key, histogram(quantiles = [0.5]))]))
+                == "field 'latency' cannot have both 'key' and 'histogram' attributes"
+        );
+    }
+
     #[test]
     fn test_unknown_attribute() {
         use assert2::check;
@@ -34,3 +34,8 @@ pub(crate) fn value_string_auto_derives() -> Vec<Attribute> {
         .collect();
     vec![syn::parse_quote!(#[derive(#(#derives),*)])]
 }
+
+/// Returns `#[derive(PartialEq, Eq)]`, applied to the generated Value type when `derive_eq` is set
+pub(crate) fn eq_derives() -> Attribute {
+    syn::parse_quote!(#[derive(PartialEq, Eq)])
+}
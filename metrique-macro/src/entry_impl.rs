@@ -6,15 +6,18 @@
 
 use proc_macro2::TokenStream as Ts2;
 use quote::{format_ident, quote, quote_spanned};
-use syn::Ident;
+use syn::{Ident, spanned::Spanned};
 
-use crate::{MetricsField, MetricsFieldKind, NameStyle, RootAttributes, inflect::metric_name};
+use crate::{
+    FieldFormat, MetricsField, MetricsFieldKind, NameStyle, RootAttributes,
+    inflect::{HasInflectableName, metric_name},
+};
 
 mod enum_impl;
 mod struct_impl;
 
 pub(crate) use enum_impl::generate_enum_entry_impl;
-pub(crate) use struct_impl::generate_struct_entry_impl;
+pub(crate) use struct_impl::{generate_struct_entry_impl, generate_struct_field_names_impl};
 
 /// Hygiene helper for generated method-local identifiers.
 ///
@@ -35,12 +38,26 @@ pub(crate) fn mixed_site_self() -> Ident {
     format_ident!("__metrique_self", span = proc_macro2::Span::mixed_site())
 }
 
+/// Generate a `Serialize` impl for the entry type. This expands to nothing unless
+/// `metrique` is built with the `serde` feature.
+pub(crate) fn generate_serialize_impl(entry_name: &Ident, generics: &syn::Generics) -> Ts2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        ::metrique::__plumbing_serialize_entry!(
+            [#impl_generics] [#entry_name #ty_generics] [#where_clause]
+        );
+    }
+}
+
 fn make_ns(ns: NameStyle, span: proc_macro2::Span) -> Ts2 {
     match ns {
         NameStyle::PascalCase => quote_spanned! {span=> NS::PascalCase },
         NameStyle::SnakeCase => quote_spanned! {span=> NS::SnakeCase },
         NameStyle::KebabCase => quote_spanned! {span=> NS::KebabCase },
         NameStyle::Preserve => quote_spanned! {span=> NS },
+        NameStyle::ScreamingSnakeCase => {
+            unreachable!("SCREAMING_SNAKE_CASE is only allowed on value(string) enums")
+        }
     }
 }
 
@@ -58,10 +75,12 @@ fn const_str(ident: &syn::Ident, value: &str) -> Ts2 {
 /// Generate 4 ConstStr structs (one per naming style) and build an Inflect namespace type.
 /// The `name_fn` callback computes the string value for each style.
 /// Returns (extra_code, inflected_type).
+/// If `const_prefix` is set, it names a `ConstStr` that is prepended as-is to every style.
 fn make_inflect_base(
     ns: &Ts2,
     inflect_method: syn::Ident,
     span: proc_macro2::Span,
+    const_prefix: Option<&syn::Ident>,
     mut name_fn: impl FnMut(NameStyle) -> String,
 ) -> (Ts2, Ts2) {
     let preserve_val = name_fn(NameStyle::Preserve);
@@ -114,6 +133,11 @@ fn make_inflect_base(
         #extra_snake
     );
 
+    let [name_ident, name_pascal, name_snake, name_kebab] =
+        [name_ident, name_pascal, name_snake, name_kebab].map(|name| match const_prefix {
+            None => quote!(#name),
+            Some(prefix) => quote!(::metrique::concat::Concatenated<#prefix, #name>),
+        });
     let inflected_type = quote!(
         <#ns as ::metrique::NameStyle>::#inflect_method<#name_ident, #name_pascal, #name_snake, #name_kebab>
     );
@@ -128,7 +152,70 @@ fn make_inflect(
     span: proc_macro2::Span,
     name_fn: impl FnMut(NameStyle) -> String,
 ) -> (Ts2, Ts2) {
-    make_inflect_base(ns, format_ident!("Inflect", span = span), span, name_fn)
+    make_inflect_base(
+        ns,
+        format_ident!("Inflect", span = span),
+        span,
+        None,
+        name_fn,
+    )
+}
+
+/// Like [`make_inflect`], but with a root `prefix` that is a `const`. Its value is only known
+/// when compiling the generated code, so it is prepended to the inflected names as-is.
+fn make_inflect_const_prefixed(
+    ns: &Ts2,
+    span: proc_macro2::Span,
+    prefix: &syn::Path,
+    name_fn: impl FnMut(NameStyle) -> String,
+) -> (Ts2, Ts2) {
+    let prefix_ident = const_prefix_ident(prefix, span);
+    let (extra, inflected) = make_inflect_base(
+        ns,
+        format_ident!("Inflect", span = span),
+        span,
+        Some(&prefix_ident),
+        name_fn,
+    );
+    let extra = quote! {
+        #extra
+        struct #prefix_ident;
+        impl ::metrique::concat::ConstStr for #prefix_ident {
+            const VAL: &'static str = #prefix;
+        }
+    };
+    (extra, inflected)
+}
+
+/// Generate inflectable name using the `Inflect` method, with the root prefix if the name takes
+/// it (see [`make_inflect_const_prefixed`]). `name_fn` applies any other root prefix itself.
+pub(crate) fn make_inflect_root_prefixed(
+    root_attrs: &RootAttributes,
+    prefixed: bool,
+    span: proc_macro2::Span,
+    name_fn: impl FnMut(NameStyle) -> String,
+) -> (Ts2, Ts2) {
+    let ns = make_ns(root_attrs.rename_all, span);
+    match &root_attrs.prefix {
+        Some(crate::Prefix::Const(prefix)) if prefixed => {
+            make_inflect_const_prefixed(&ns, span, prefix, name_fn)
+        }
+        _ => make_inflect(&ns, span, name_fn),
+    }
+}
+
+fn const_prefix_ident(prefix: &syn::Path, span: proc_macro2::Span) -> syn::Ident {
+    let last = prefix
+        .segments
+        .last()
+        .map(|segment| segment.ident.to_string())
+        .unwrap_or_default();
+    let ident_base: String = NameStyle::PascalCase
+        .apply(&last)
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+    format_ident!("{}ConstPrefix", ident_base, span = span)
 }
 
 /// Generate inflectable affix using the `InflectAffix` method.
@@ -143,6 +230,7 @@ fn make_inflect_affix(
         ns,
         format_ident!("InflectAffix", span = span),
         span,
+        None,
         name_fn,
     )
 }
@@ -180,6 +268,26 @@ pub(crate) fn make_exact_prefix(
     (extra, ns_with_prefix)
 }
 
+/// Generate a prefix from a path to a `const &str`, which is not inflected.
+/// Returns (extra_code, namespace_with_prefix).
+pub(crate) fn make_const_prefix(
+    ns: &Ts2,
+    prefix: &syn::Path,
+    span: proc_macro2::Span,
+) -> (Ts2, Ts2) {
+    let prefix_ident = const_prefix_ident(prefix, span);
+    let extra = quote_spanned! {span=>
+        struct #prefix_ident;
+        impl ::metrique::concat::ConstStr for #prefix_ident {
+            const VAL: &'static str = #prefix;
+        }
+    };
+    let ns_with_prefix = quote!(
+        <#ns as ::metrique::NameStyle>::AppendPrefix<#prefix_ident>
+    );
+    (extra, ns_with_prefix)
+}
+
 fn generate_field_writes(
     fields: &[MetricsField],
     root_attrs: &RootAttributes,
@@ -196,10 +304,23 @@ fn generate_field_writes(
         let write = match &field.attrs.kind {
             MetricsFieldKind::Timestamp(span) => {
                 let field_access = field_access(&field.ident);
+                // route the call through a named function spanned at the field's type, so that a
+                // type that can't be a timestamp is reported at the field (with the function name
+                // explaining the requirement) rather than deep inside the generated code.
+                let check = quote_spanned! {field.ty.span()=>
+                    fn timestamp_field_must_implement_entry_timestamp<T: ::metrique::writer::EntryTimestamp + ?::std::marker::Sized>(
+                        timestamp: &T,
+                    ) -> ::std::option::Option<::std::time::SystemTime> {
+                        ::metrique::writer::EntryTimestamp::entry_timestamp(timestamp)
+                    }
+                    let timestamp = timestamp_field_must_implement_entry_timestamp(#field_access);
+                };
                 quote_spanned! {*span=>
-                    #[allow(clippy::useless_conversion)]
                     {
-                        ::metrique::writer::EntryWriter::timestamp(#writer_ident, (*#field_access).into());
+                        #check
+                        if let ::std::option::Option::Some(timestamp) = timestamp {
+                            ::metrique::writer::EntryWriter::timestamp(#writer_ident, timestamp);
+                        }
                     }
                 }
             }
@@ -209,21 +330,27 @@ fn generate_field_writes(
                     ::metrique::writer::Entry::write(#field_access, #writer_ident);
                 }
             }
-            MetricsFieldKind::Flatten { span, prefix } => {
-                let (extra, ns) = match prefix {
-                    None => (quote!(), ns),
-                    Some(prefix) => prefix.append_to(&ns, field_span),
-                };
+            MetricsFieldKind::Flatten {
+                span,
+                prefix,
+                index,
+                unit,
+            } => {
                 let field_access = field_access(&field.ident);
-                quote_spanned! {*span=>
-                    #extra
-                    ::metrique::InflectableEntry::<#ns>::write(#field_access, #writer_ident);
-                }
+                generate_flatten_write(
+                    &ns,
+                    prefix.as_ref(),
+                    *index,
+                    unit.as_ref(),
+                    *span,
+                    field_span,
+                    field_access,
+                )
             }
             MetricsFieldKind::Ignore(_) => {
                 continue;
             }
-            MetricsFieldKind::Field { format, .. } => {
+            MetricsFieldKind::Field { format, alias, .. } => {
                 let (extra, name) = make_inflect_metric_name(root_attrs, field);
                 let field_access = field_access(&field.ident);
                 let value = crate::value_impl::format_value(format, field_span, field_access);
@@ -241,6 +368,19 @@ fn generate_field_writes(
                     quote! { &#expr }
                 };
 
+                let alias_write = alias.as_ref().map(|alias| {
+                    // like `name`, the alias is emitted as-is and is not inflected
+                    let (extra, name) = make_inflect(&ns, field_span, |_| alias.clone());
+                    quote_spanned! {field_span=>
+                        ::metrique::writer::EntryWriter::value(#writer_ident,
+                            {
+                                #extra
+                                ::metrique::concat::const_str_value::<#name>()
+                            }
+                            , #wrapped_value);
+                    }
+                });
+
                 quote_spanned! {field_span=>
                     ::metrique::writer::EntryWriter::value(#writer_ident,
                         {
@@ -248,6 +388,7 @@ fn generate_field_writes(
                             ::metrique::concat::const_str_value::<#name>()
                         }
                         , #wrapped_value);
+                    #alias_write
                 }
             }
         };
@@ -261,6 +402,57 @@ fn generate_field_writes(
     writes
 }
 
+/// Write a flattened field (or tuple variant field) with its prefix applied.
+///
+/// With `index`, the field is a collection and each element is written with its index
+/// appended to the prefix. With `unit`, metrics without a unit are written with `unit`.
+pub(crate) fn generate_flatten_write(
+    ns: &Ts2,
+    prefix: Option<&crate::Prefix>,
+    index: Option<proc_macro2::Span>,
+    unit: Option<&syn::Path>,
+    span: proc_macro2::Span,
+    prefix_span: proc_macro2::Span,
+    access: Ts2,
+) -> Ts2 {
+    let writer_ident = mixed_site_writer();
+    let (extra, ns) = match prefix {
+        None => (quote!(), ns.clone()),
+        Some(prefix) => prefix.append_to(ns, prefix_span),
+    };
+    let write = match index {
+        None => quote_spanned! {span=>
+            #extra
+            ::metrique::InflectableEntry::<#ns>::write(#access, #writer_ident);
+        },
+        Some(index_span) => quote_spanned! {index_span=>
+            #extra
+            ::metrique::indexed::write_indexed::<#ns, _>(
+                #access,
+                &::metrique::concat::const_str_value::<
+                    <#ns as ::metrique::NameStyle>::Inflect<
+                        ::metrique::concat::EmptyConstStr,
+                        ::metrique::concat::EmptyConstStr,
+                        ::metrique::concat::EmptyConstStr,
+                        ::metrique::concat::EmptyConstStr,
+                    >,
+                >(),
+                #writer_ident,
+            );
+        },
+    };
+    match unit {
+        None => write,
+        // shadow the writer, so the write above goes through the unit writer
+        Some(unit) => quote_spanned! {unit.span()=>
+            {
+                let #writer_ident = &mut ::metrique::unit::DefaultUnitEntryWriter::<_, #unit>::new(#writer_ident);
+                #write
+            }
+        },
+    }
+}
+
 /// Return an iterator that chains the iterators in `iterators`.
 ///
 /// This calls `chain` in a binary tree fashion to avoid problems with the recursion limit,
@@ -285,8 +477,10 @@ fn make_binary_tree_chain(iterators: Vec<Ts2>) -> Ts2 {
 }
 
 fn make_inflect_metric_name(root_attrs: &RootAttributes, field: &MetricsField) -> (Ts2, Ts2) {
-    make_inflect(
-        &make_ns(root_attrs.rename_all, field.span),
+    // fields with a `name` don't get the root prefix
+    make_inflect_root_prefixed(
+        root_attrs,
+        field.name_override().is_none(),
         field.span,
         |style| metric_name(root_attrs, style, field),
     )
@@ -307,6 +501,9 @@ fn collect_field_sample_group<'a>(
     let field_ident = &field.ident;
     let cfg_attrs: Vec<_> = field.cfg_attrs().collect();
     let inner = match &field.attrs.kind {
+        // indexed elements would need their sample group names indexed too, which is not
+        // supported
+        MetricsFieldKind::Flatten { index: Some(_), .. } => return None,
         MetricsFieldKind::Flatten { span, .. } => {
             let ns = make_ns(root_attrs.rename_all, field.span);
             let access = field_access(field_ident);
@@ -322,10 +519,14 @@ fn collect_field_sample_group<'a>(
         }
         MetricsFieldKind::Field {
             sample_group: Some(span),
+            format,
             ..
         } => {
             let (extra, name) = make_inflect_metric_name(root_attrs, field);
-            let access = field_access(field_ident);
+            let mut access = field_access(field_ident);
+            if let Some(FieldFormat::Value { .. }) = format {
+                access = quote_spanned!(*span=> ::metrique::format::WithFormatter::value(#access));
+            }
             quote_spanned!(*span=>
                 {
                     #extra
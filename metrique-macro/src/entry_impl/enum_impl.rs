@@ -86,6 +86,7 @@ fn generate_write_arms(
         .tag
         .as_ref()
         .map(|tag| tag.field_name(root_attrs));
+    let tag_prefixed = root_attrs.tag.as_ref().is_some_and(|tag| tag.prefixed());
     let writer_ident = mixed_site_writer();
 
     variants
@@ -94,8 +95,9 @@ fn generate_write_arms(
             let variant_ident = &variant.ident;
 
             let tag_write = tag_name.as_ref().map(|tag_name| {
-                let (extra, name) = make_inflect(
-                    &make_ns(root_attrs.rename_all, variant.ident.span()),
+                let (extra, name) = make_inflect_root_prefixed(
+                    root_attrs,
+                    tag_prefixed,
                     variant.ident.span(),
                     |style| style.apply(tag_name),
                 );
@@ -162,15 +164,21 @@ fn generate_tuple_writes(
         .map(|(idx, td)| {
             let binding = quote::format_ident!("v{}", idx);
             let write = match &td.kind {
-                MetricsFieldKind::Flatten { span, prefix } => {
+                MetricsFieldKind::Flatten {
+                    span,
+                    prefix,
+                    index,
+                    unit,
+                } => {
                     let base_ns = make_ns(root_attrs.rename_all, *span);
-                    let (extra, ns) = match prefix {
-                        None => (quote!(), base_ns),
-                        Some(prefix) => prefix.append_to(&base_ns, variant_span),
-                    };
-                    quote::quote_spanned!(*span=>
-                        #extra
-                        ::metrique::InflectableEntry::<#ns>::write(#binding, #writer_ident);
+                    super::generate_flatten_write(
+                        &base_ns,
+                        prefix.as_ref(),
+                        *index,
+                        unit.as_ref(),
+                        *span,
+                        variant_span,
+                        quote!(#binding),
                     )
                 }
                 MetricsFieldKind::FlattenEntry(span) => {
@@ -212,6 +220,7 @@ fn generate_sample_group_arms(
         .tag
         .as_ref()
         .map(|tag| tag.field_name(root_attrs));
+    let tag_prefixed = root_attrs.tag.as_ref().is_some_and(|tag| tag.prefixed());
     let include_tag_in_sample_group = root_attrs.tag.as_ref().is_some_and(|t| t.sample_group());
 
     variants.iter().enumerate().map(|(idx, variant)| {
@@ -219,8 +228,9 @@ fn generate_sample_group_arms(
         let iter_variant_name = quote::format_ident!("V{}", idx);
 
         let tag_sample_group = if let Some(tag_name) = tag_name.as_ref().filter(|_| include_tag_in_sample_group) {
-            let (extra, name) = make_inflect(
-                &make_ns(root_attrs.rename_all, variant.ident.span()),
+            let (extra, name) = make_inflect_root_prefixed(
+                root_attrs,
+                tag_prefixed,
                 variant.ident.span(),
                 |style| style.apply(tag_name),
             );
@@ -303,6 +313,7 @@ fn collect_tuple_sample_group(
     binding: &Ident,
 ) -> Option<Ts2> {
     match kind {
+        MetricsFieldKind::Flatten { index: Some(_), .. } => None,
         MetricsFieldKind::Flatten { span, .. } => {
             let ns = make_ns(root_attrs.rename_all, *span);
             Some(quote_spanned!(*span=>
@@ -81,3 +81,115 @@ fn generate_sample_group_statements(fields: &[MetricsField], root_attrs: &RootAt
 
     make_binary_tree_chain(sample_group_fields)
 }
+
+/// Generate the `InflectableFieldNames` impl backing the `FIELD_NAMES` constant.
+///
+/// Flattened fields contribute the names of their entry, if it implements
+/// `InflectableFieldNames`. Each flattened field gets its own `FlattenedFieldNames` impl, so that
+/// the `ConstStr` types of its prefix don't clash with those of other fields.
+pub(crate) fn generate_struct_field_names_impl(
+    entry_name: &Ident,
+    generics: &syn::Generics,
+    fields: &[MetricsField],
+    root_attrs: &RootAttributes,
+) -> Ts2 {
+    // indexed names and the names of `flatten_entry` fields are only known at runtime
+    if fields.iter().any(|f| {
+        matches!(
+            f.attrs.kind,
+            MetricsFieldKind::FlattenEntry(_) | MetricsFieldKind::Flatten { index: Some(_), .. }
+        )
+    }) {
+        return quote! {};
+    }
+
+    let mut impl_generics = generics.clone();
+    impl_generics
+        .params
+        .push(syn::parse_quote!(NS: ::metrique::NameStyle));
+    let (impl_generics, _, _) = impl_generics.split_for_impl();
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
+    let predicates: Vec<_> = where_clause
+        .map(|w| w.predicates.iter().collect())
+        .unwrap_or_default();
+
+    // see `mixed_site_writer`
+    let names = format_ident!("__metrique_names", span = proc_macro2::Span::mixed_site());
+    let mut flattened_impls = vec![];
+    let mut flattened_bounds = vec![];
+    let mut pushes = vec![];
+    for field in fields {
+        let field_span = field.span;
+        let ns = make_ns(root_attrs.rename_all, field_span);
+        let cfg_attrs: Vec<_> = field.cfg_attrs().collect();
+        match &field.attrs.kind {
+            MetricsFieldKind::Field { alias, .. } => {
+                let (extra, name) = make_inflect_metric_name(root_attrs, field);
+                let alias_push = alias.as_ref().map(|alias| {
+                    let (extra, name) = make_inflect(&ns, field_span, |_| alias.clone());
+                    quote! { { #extra #names.push::<#name>(); } }
+                });
+                pushes.push(quote_spanned! {field_span=>
+                    #(#cfg_attrs)*
+                    {
+                        #extra
+                        #names.push::<#name>();
+                        #alias_push
+                    }
+                });
+            }
+            MetricsFieldKind::Flatten { prefix, .. } => {
+                let index = flattened_impls.len();
+                let ty = field.entry_type();
+                let (extra, ns) = match prefix {
+                    None => (quote!(), ns),
+                    Some(prefix) => prefix.append_to(&ns, field_span),
+                };
+                flattened_impls.push(quote_spanned! {field_span=>
+                    #(#cfg_attrs)*
+                    const _: () = {
+                        #extra
+                        impl #impl_generics ::metrique::field_names::FlattenedFieldNames<NS, #index>
+                            for #entry_name #ty_generics
+                        where
+                            #(#predicates,)*
+                            #ty: ::metrique::field_names::InflectableFieldNames<#ns>,
+                        {
+                            const FIELD_NAMES: &'static [&'static str] =
+                                <#ty as ::metrique::field_names::InflectableFieldNames<#ns>>::FIELD_NAMES;
+                        }
+                    };
+                });
+                flattened_bounds.push(quote! {
+                    #entry_name #ty_generics: ::metrique::field_names::FlattenedFieldNames<NS, #index>
+                });
+                pushes.push(quote_spanned! {field_span=>
+                    #(#cfg_attrs)*
+                    #names.extend(
+                        <#entry_name #ty_generics as ::metrique::field_names::FlattenedFieldNames<NS, #index>>::FIELD_NAMES
+                    );
+                });
+            }
+            MetricsFieldKind::Ignore(_)
+            | MetricsFieldKind::Timestamp(_)
+            | MetricsFieldKind::FlattenEntry(_) => {}
+        }
+    }
+
+    quote! {
+        const _: () = {
+            #(#flattened_impls)*
+            impl #impl_generics ::metrique::field_names::InflectableFieldNames<NS> for #entry_name #ty_generics
+            where
+                #(#predicates,)*
+                #(#flattened_bounds,)*
+            {
+                const FIELD_NAME_LIST: ::metrique::field_names::FieldNameList = {
+                    let mut #names = ::metrique::field_names::FieldNameList::new();
+                    #(#pushes)*
+                    #names
+                };
+            }
+        };
+    }
+}
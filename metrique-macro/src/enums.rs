@@ -113,6 +113,10 @@ impl MetricsVariant {
     }
 }
 
+fn default_on_enum_error(span: proc_macro2::Span) -> syn::Error {
+    syn::Error::new(span, "`default` can only be used on fields of structs")
+}
+
 fn parse_variant_data(fields: &syn::Fields) -> Result<Option<VariantData>> {
     match fields {
         syn::Fields::Unit => Ok(None),
@@ -123,6 +127,15 @@ fn parse_variant_data(fields: &syn::Fields) -> Result<Option<VariantData>> {
                 .map(|field| {
                     let raw_attrs = RawMetricsFieldAttrs::from_field(field)?;
                     let attrs = raw_attrs.validate()?;
+                    if let Some(span) = attrs.default {
+                        return Err(default_on_enum_error(span));
+                    }
+                    if let Some(skip_if) = &attrs.skip_if {
+                        return Err(syn::Error::new_spanned(
+                            skip_if,
+                            "`skip_if` is not supported on tuple variant fields",
+                        ));
+                    }
 
                     match &attrs.kind {
                         MetricsFieldKind::Flatten { .. }
@@ -148,6 +161,9 @@ fn parse_variant_data(fields: &syn::Fields) -> Result<Option<VariantData>> {
         }
         syn::Fields::Named(fields) => {
             let parsed_fields = parse_metric_fields(&fields.named)?;
+            if let Some(span) = parsed_fields.iter().find_map(|f| f.attrs.default) {
+                return Err(default_on_enum_error(span));
+            }
             Ok(Some(VariantData::Struct(parsed_fields)))
         }
     }
@@ -229,6 +245,9 @@ pub(crate) fn generate_metrics_for_enum(
     variants: &[MetricsVariant],
 ) -> Result<Ts2> {
     let enum_name = &input.ident;
+    if let Some(span) = root_attrs.reset {
+        return Err(syn::Error::new(span, "`reset` can only be used on structs"));
+    }
     let is_value_string = root_attrs.mode == MetricMode::ValueString;
     let entry_name = if is_value_string {
         quote::format_ident!("{}Value", enum_name)
@@ -241,7 +260,10 @@ pub(crate) fn generate_metrics_for_enum(
     // For value(string) enums, auto-derive Debug, Clone, Copy on the generated Value enum only.
     // The base enum keeps whatever the user provides — no stripping, no injection.
     let (base_attrs, entry_attrs) = if is_value_string {
-        let auto_derives = crate::derive_utils::value_string_auto_derives();
+        let mut auto_derives = crate::derive_utils::value_string_auto_derives();
+        if root_attrs.derive_eq {
+            auto_derives.push(crate::derive_utils::eq_derives());
+        }
         (clean_attrs(&input.attrs), auto_derives)
     } else {
         let entry_attrs = crate::derive_utils::extract_allowed_derives(&input.attrs);
@@ -266,12 +288,20 @@ pub(crate) fn generate_metrics_for_enum(
             &input.generics,
             variants,
         )?,
-        _ => crate::entry_impl::generate_enum_entry_impl(
-            &entry_name,
-            &input.generics,
-            variants,
-            &root_attrs,
-        ),
+        _ => {
+            let entry_impl = crate::entry_impl::generate_enum_entry_impl(
+                &entry_name,
+                &input.generics,
+                variants,
+                &root_attrs,
+            );
+            let serialize_impl =
+                crate::entry_impl::generate_serialize_impl(&entry_name, &input.generics);
+            quote! {
+                #entry_impl
+                #serialize_impl
+            }
+        }
     };
 
     let close_value_impl = match root_attrs.mode {
@@ -313,6 +343,7 @@ pub(crate) fn generate_metrics_for_enum(
                 &entry_name,
                 &handle_name,
                 &input.generics,
+                root_attrs.default_sink.as_ref(),
             );
             quote! {
                 #on_drop_wrapper
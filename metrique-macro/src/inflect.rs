@@ -26,6 +26,10 @@ pub(crate) enum NameStyle {
     SnakeCase,
     #[darling(rename = "kebab-case")]
     KebabCase,
+    /// Only supported for `#[metrics(value(string))]` variants, which are inflected at compile
+    /// time. Field names are inflected through `metrique::NameStyle`, which has no such style.
+    #[darling(rename = "SCREAMING_SNAKE_CASE")]
+    ScreamingSnakeCase,
     #[default]
     Preserve,
 }
@@ -38,6 +42,7 @@ impl NameStyle {
             NameStyle::SnakeCase => name.to_snake_case(),
             NameStyle::Preserve => name.to_string(),
             NameStyle::KebabCase => name.to_kebab_case(),
+            NameStyle::ScreamingSnakeCase => name.to_screaming_snake_case(),
         }
     }
 
@@ -60,6 +65,13 @@ impl NameStyle {
                 }
                 res
             }
+            NameStyle::ScreamingSnakeCase => {
+                let mut res = name.to_screaming_snake_case();
+                if !res.ends_with("_") {
+                    res.push('_');
+                }
+                res
+            }
         }
     }
 
@@ -69,6 +81,9 @@ impl NameStyle {
             NameStyle::SnakeCase => "Snake",
             NameStyle::Preserve => "Preserve",
             NameStyle::KebabCase => "Kebab",
+            NameStyle::ScreamingSnakeCase => {
+                unreachable!("SCREAMING_SNAKE_CASE is only allowed on value(string) enums")
+            }
         }
     }
 }
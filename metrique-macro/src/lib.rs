@@ -37,9 +37,10 @@ use crate::inflect::{name_contains_dot, name_contains_uninflectables, name_ends_
 ///
 /// | Attribute | Type | Description | Example |
 /// |-----------|------|-------------|---------|
-/// | `rename_all` | String | Changes the case style of all field names | `#[metrics(rename_all = "PascalCase")]` |
-/// | `prefix` | String | Adds a prefix to all field names (prefix gets inflected) | `#[metrics(prefix = "api_")]` |
+/// | `rename_all` | String | Changes the case style of all field names, or of variant names for `value(string)` enums (which also accept `"SCREAMING_SNAKE_CASE"`) | `#[metrics(rename_all = "PascalCase")]` |
+/// | `prefix` | String or Path | Adds a prefix to all field names (prefix gets inflected). A path to a `const &str` is not inflected, see [Prefixes](#prefixes) | `#[metrics(prefix = "api_")]` |
 /// | `exact_prefix` | String | Adds a prefix to all field names without inflection | `#[metrics(exact_prefix = "API_")]` |
+/// | `default_sink` | Path | Replaces `DefaultSink` as the default sink type of the generated `Guard` and `Handle` aliases, e.g. to avoid boxing with a concrete `BackgroundQueue` sink. Generic sink types need a type alias | `#[metrics(default_sink = MyQueue)]` |
 /// | `emf::dimension_sets` | Array | Defines dimension sets for CloudWatch metrics | `#[metrics(emf::dimension_sets = [["Status", "Operation"]])]` |
 /// | `tag` | Nested | On entry enums, adds a tag field with the variant name. Tag value respects `rename_all` and variant `name`, but not `prefix`. | |
 /// | - `name` | String | Name of the tag field (inflectable, respects `prefix` and `rename_all`) | `#[metrics(tag(name = "operation"))]` |
@@ -50,22 +51,30 @@ use crate::inflect::{name_contains_dot, name_contains_uninflectables, name_ends_
 /// | `value` | Flag | Used for *structs*. Makes the struct a value newtype | `#[metrics(value)]` |
 /// | `value(string)` | Flag | Used for *enums*. Transforms the enum into a string value. Automatically derives `Debug`, `Clone`, and `Copy` on the generated Value enum. The base enum is left untouched — derive what you need on it yourself. | `#[metrics(value(string))]` |
 /// | `sample_group` | Flag | On `#[metrics(value)]`, forwards `sample_group` to the inner field | `#[metrics(value, sample_group)]` |
+/// | `derive_eq` | Flag | On `#[metrics(value)]` and `#[metrics(value(string))]`, derives `PartialEq` and `Eq` on the generated Value type, e.g. to compare closed values in tests. The closed field types must implement `Eq`. | `#[metrics(value, derive_eq)]` |
+/// | `reset` | Flag | On structs, generates a `reset` method and a `Reset` impl that put the fields back into their fresh state, see [Reset](#reset) | `#[metrics(reset)]` |
 ///
 /// # Field Attributes
 ///
 /// | Attribute | Type | Description | Example |
 /// |-----------|------|-------------|---------|
 /// | `name` | String | Overrides the field name in metrics | `#[metrics(name = "CustomName")]` |
-/// | `unit` | Path | Specifies the unit for the metric value | `#[metrics(unit = Millisecond)]` |
-/// | `format` | Path | Specifies the formatter (`ValueFormatter`) for the metric value | `#[metrics(format=EpochSeconds)]` |
-/// | `timestamp` | Flag | Marks a field as the canonical timestamp | `#[metrics(timestamp)]` |
+/// | `alias` | String | Additionally emits the field under this name (not inflected), e.g. to keep an old name during a rename | `#[metrics(name = "NewName", alias = "OldName")]` |
+/// | `unit` | Path | Specifies the unit for the metric value. On `flatten` fields, applies to every metric of the flattened entry that doesn't have its own unit | `#[metrics(unit = Millisecond)]` |
+/// | `format` | Path or Expression | Specifies the formatter for the metric value. A path names a `ValueFormatter` type. A constructor call or struct expression creates a `ConfiguredValueFormatter`, which is evaluated when the field is closed and stored in the entry | `#[metrics(format=EpochSeconds)]`, `#[metrics(format=Precision::new(3))]` |
+/// | `timestamp` | Flag | Marks a field as the canonical timestamp. The closed field must implement `EntryTimestamp`, e.g. `SystemTime` | `#[metrics(timestamp)]` |
 /// | `sample_group` | Flag | Marks a field as a sample group - it will still be emitted as a value | `#[metrics(sample_group)]` |
-/// | `prefix` | String | Adds a prefix to flattened entries. Prefix will get inflected to the right case style | `#[metrics(flatten, prefix="prefix-")]` |
+/// | `prefix` | String or Path | Adds a prefix to flattened entries. Prefix will get inflected to the right case style, unless it is a path to a `const &str` | `#[metrics(flatten, prefix="prefix-")]` |
 /// | `exact_prefix` | String | Adds a prefix to flattened entries without inflection | `#[metrics(flatten, exact_prefix="API_")]` |
-/// | `flatten` | Flag | Flattens nested `CloseEntry` metric structs | `#[metrics(flatten)]` |
-/// | `flatten_entry` | Flag | Flattens nested `CloseValue<Closed: Entry>` metric structs, with no prefix or inflection | `#[metrics(flatten_entry)]` |
-/// | `no_close` | Flag | Use the entry directly instead of closing it | `#[metrics(no_close)]` |
-/// | `ignore` | Flag | Excludes the field from metrics | `#[metrics(ignore)]` |
+/// | `flatten` | Flag | Flattens nested `CloseEntry` metric structs. An `Option` field writes nothing (and has no sample group) when `None` | `#[metrics(flatten)]` |
+/// | `index` | Flag | With `flatten` and a prefix, on a `Vec` or array field: flattens every element with its zero-based index appended to the prefix (`Retry0Latency`, `retry_0_latency`). Indexed elements do not contribute sample groups | `#[metrics(flatten, index, prefix = "retry")]` |
+/// | `flatten_entry` | Flag | Flattens nested `CloseValue<Closed: Entry>` metric structs, with no prefix or inflection. Combine with `no_close` for types that implement `Entry` directly | `#[metrics(flatten_entry, no_close)]` |
+/// | `no_close` | Flag | Use the entry directly instead of closing it. Not supported in `subfield` (use `subfield_owned`) or `value` structs | `#[metrics(no_close)]` |
+/// | `ignore` | Flag | Excludes the field from metrics. `PhantomData` fields without `#[metrics]` attributes are excluded automatically | `#[metrics(ignore)]` |
+/// | `default` | Flag | Makes the field optional in the generated builder, see [Builders](#builders). With `reset`, the field is reset to `Default::default()` | `#[metrics(default)]` |
+/// | `no_reset` | Flag | With `reset` on the struct, keeps the value of the field when the struct is reset | `#[metrics(no_reset)]` |
+/// | `emf::high_storage_resolution` | Flag | Wraps the closed value in `HighStorageResolution`, so EMF reports the metric with 1-second storage resolution. Composes with `unit`, cannot be combined with `format`. Without the `emf` feature of `metrique`, this compiles but sets no flag | `#[metrics(emf::high_storage_resolution)]` |
+/// | `skip_if` | Path | A `fn(&FieldType) -> bool` called when the field is closed. If it returns `true`, the field is not emitted. Cannot be combined with `timestamp`, `ignore`, `index` or `sample_group` | `#[metrics(skip_if = is_zero)]` |
 ///
 /// # Variant Attributes
 ///
@@ -159,6 +168,26 @@ use crate::inflect::{name_contains_dot, name_contains_uninflectables, name_ends_
 /// Prefixes can either be inflectable (with the `prefix` attribute) or non-inflectable
 /// (with the `exact_prefix` attribute).
 ///
+/// `prefix` also accepts a path to a `const &str`, e.g. to share prefixes between structs.
+/// The value of the constant is not known when the macro expands, so it can't be inflected:
+/// it is used as-is, like `exact_prefix`, in every name style. The rest of the name is still
+/// inflected by `rename_all` and the name style of the parent. Root-level constant prefixes
+/// don't need to end with a delimiter.
+///
+/// ```rust
+/// # use metrique::unit_of_work::metrics;
+/// mod prefixes {
+///     pub const API: &str = "Api:";
+/// }
+///
+/// #[metrics(prefix = prefixes::API, rename_all = "PascalCase")]
+/// struct Base {
+///     request_count: u32,
+/// }
+///
+/// assert_eq!(Base::FIELD_NAMES, ["Api:RequestCount"]);
+/// ```
+///
 /// ## Inflection
 ///
 /// Metric names are inflected to allow them to fit into the name style used by the
@@ -196,6 +225,87 @@ use crate::inflect::{name_contains_dot, name_contains_uninflectables, name_ends_
 /// assert_eq!(entry.metrics["waterfowl_NDucks"], 0);
 /// ```
 ///
+/// ## Field Names
+///
+/// Every struct gets a `FIELD_NAMES` constant with the names of the metrics it writes, in
+/// order, e.g. to build dashboards and alarms from the metric definitions. Flattened fields
+/// are expanded, with their prefix and name style applied.
+///
+/// ```rust
+/// # use metrique::unit_of_work::metrics;
+/// # use std::time::Duration;
+/// #[metrics(subfield)]
+/// struct Subfield {
+///     #[metrics(name = "NDucks")]
+///     number_of_ducks: u32,
+/// }
+///
+/// #[metrics(rename_all = "PascalCase")]
+/// struct Base {
+///     request_latency: Duration,
+///     #[metrics(flatten, prefix = "waterfowl_")]
+///     waterfowl: Subfield,
+/// }
+///
+/// assert_eq!(Base::FIELD_NAMES, ["RequestLatency", "WaterfowlNDucks"]);
+/// ```
+///
+/// The names of `flatten_entry` and `index` fields are only known at runtime, so `FIELD_NAMES`
+/// can't be used on structs that have them, or that flatten a type without `FIELD_NAMES`.
+/// `#[metrics(value)]` structs don't write names of their own, and their `FIELD_NAMES` is empty.
+///
+/// ## Merging Subfields
+///
+/// `subfield` and `subfield_owned` structs get a `merge_into` method that closes them and
+/// merges their metrics into an open entry, for metrics that are collected separately from the
+/// entry they end up in. It returns a [`Merged`](https://docs.rs/metrique/latest/metrique/struct.Merged.html)
+/// that derefs to the entry. The merged metrics are written after the entry's own metrics, in the
+/// subfield's name style, when the entry is dropped.
+///
+/// ```rust
+/// # use metrique::unit_of_work::metrics;
+/// #[metrics(subfield)]
+/// struct Downstream {
+///     downstream_calls: usize,
+/// }
+///
+/// #[metrics]
+/// struct Request {
+///     status: usize,
+/// }
+///
+/// let vec_sink = metrique::writer::sink::VecEntrySink::new();
+/// let request = Request { status: 200 }.append_on_drop(vec_sink.clone());
+/// let request = Downstream { downstream_calls: 3 }.merge_into(request);
+/// drop(request);
+/// let entries = vec_sink.drain();
+/// let entry = metrique::test_util::to_test_entry(&entries[0]);
+/// assert_eq!(entry.metrics["downstream_calls"], 3);
+/// ```
+///
+/// Names are compared at compile time, so structs whose names are only known at runtime (with
+/// `flatten_entry` or `index` fields) can't be merged. Merging fails to compile if the subfield
+/// writes a name that the entry, or any subfield already merged into it, also writes:
+///
+/// ```rust,compile_fail
+/// # use metrique::unit_of_work::metrics;
+/// #[metrics(subfield)]
+/// struct Downstream {
+///     status: usize,
+/// }
+///
+/// #[metrics]
+/// struct Request {
+///     status: usize,
+/// }
+///
+/// let vec_sink = metrique::writer::sink::VecEntrySink::new();
+/// let request = Request { status: 200 }.append_on_drop(vec_sink.clone());
+/// Downstream { status: 500 }.merge_into(request);
+/// ```
+///
+/// This includes merging two subfields that share a name, or merging the same subfield twice.
+///
 /// # Example
 ///
 /// ```rust
@@ -249,6 +359,84 @@ use crate::inflect::{name_contains_dot, name_contains_uninflectables, name_ends_
 ///     }
 /// }
 /// ```
+///
+/// # Builders
+///
+/// If any field of a struct with named fields is marked `#[metrics(default)]`, the macro
+/// generates a `builder` function taking the other fields, in declaration order. Fields marked
+/// `default` start out as `Default::default()` and can be set on the builder, which has a setter
+/// named after each of them:
+///
+/// ```rust
+/// # use metrique::unit_of_work::metrics;
+/// # use metrique::timers::{Timer, Timestamp};
+/// # use metrique::unit::Millisecond;
+/// # use metrique::ServiceMetrics;
+/// # use metrique::writer::GlobalEntrySink;
+/// #[metrics]
+/// struct RequestMetrics {
+///     #[metrics(timestamp)]
+///     timestamp: Timestamp,
+///     #[metrics(unit = Millisecond)]
+///     operation_time: Timer,
+///     #[metrics(default)]
+///     retries: usize,
+///     #[metrics(default)]
+///     cache_hits: usize,
+/// }
+///
+/// impl RequestMetrics {
+///     fn init() -> RequestMetricsGuard {
+///         // `cache_hits` starts out as 0
+///         RequestMetrics::builder(Timestamp::now(), Timer::start_now())
+///             .retries(1)
+///             .build()
+///             .append_on_drop(ServiceMetrics::sink())
+///     }
+/// }
+/// ```
+///
+/// # Reset
+///
+/// `#[metrics(reset)]` generates `reset(&mut self)`, which puts the fields of a struct back into
+/// the state of a freshly created struct, e.g. to reuse it across the iterations of a tight loop.
+/// Root entries are consumed when they are closed, so a struct that is closed once per iteration
+/// should be a `subfield`, which is closed by reference.
+/// Fields are reset through the [`Reset`](https://docs.rs/metrique/latest/metrique/trait.Reset.html)
+/// trait: counters go back to zero, timers restart and timestamps are set to the current time.
+/// `flatten` fields are reset as well, which requires the nested struct to have `#[metrics(reset)]`
+/// too. `default` fields are reset to `Default::default()`, `ignore` fields are left untouched,
+/// and fields that should keep their value can be marked `#[metrics(no_reset)]`:
+///
+/// ```rust
+/// # use metrique::unit_of_work::metrics;
+/// # use metrique::timers::{Timer, Timestamp};
+/// # use metrique::unit::Millisecond;
+/// #[metrics(reset)]
+/// struct BatchMetrics {
+///     #[metrics(timestamp)]
+///     timestamp: Timestamp,
+///     #[metrics(no_reset)]
+///     operation: &'static str,
+///     #[metrics(unit = Millisecond)]
+///     latency: Timer,
+///     items: usize,
+/// }
+///
+/// let mut metrics = BatchMetrics {
+///     timestamp: Timestamp::now(),
+///     operation: "Process",
+///     latency: Timer::start_now(),
+///     items: 0,
+/// };
+/// for batch in [[1, 2], [3, 4]] {
+///     metrics.reset();
+///     metrics.items += batch.len();
+///     // `operation` is still "Process", `items` counts only this batch
+///     assert_eq!(metrics.items, 2);
+/// }
+/// ```
+///
 /// # Enums
 ///
 /// Enums can be used in two ways: as value enums or entry enums.
@@ -257,6 +445,8 @@ use crate::inflect::{name_contains_dot, name_contains_uninflectables, name_ends_
 ///
 /// Value enums with `#[metrics(value(string))]` convert enum variants to string values.
 /// Only unit variants are allowed. Variant names respect `#[metrics(name = "...")]` and `rename_all`.
+/// Variant names are inflected at compile time, and `rename_all` additionally supports
+/// `"SCREAMING_SNAKE_CASE"` on value enums (e.g. `ReadData` becomes `"READ_DATA"`).
 ///
 /// `Debug`, `Clone`, and `Copy` are automatically derived on the generated Value enum.
 /// The base enum is not modified — add your own derives as needed:
@@ -375,6 +565,8 @@ use crate::inflect::{name_contains_dot, name_contains_uninflectables, name_ends_
 ///   A type alias to ``AppendAndCloseOnDrop`.
 /// - `MyMetricsHandle`: A shareable handle for concurrent access to the metrics.
 ///   A type alias to ``AppendAndCloseOnDropHandle`.
+/// - `MyMetricsBuilder`: For structs with `#[metrics(default)]` fields, the builder returned by
+///   `MyMetrics::builder`. See [Builders](#builders).
 ///
 /// Value enums do not have new types generated, only trait implementations (`From<&MyEnum> for &'static str`, `SampleGroup`, `Value`).
 #[proc_macro_attribute]
@@ -514,11 +706,37 @@ pub fn metrics(attr: TokenStream, input: proc_macro::TokenStream) -> proc_macro:
 /// }
 /// ```
 ///
+/// ## Histogram Quantiles
+///
+/// `#[aggregate(histogram(quantiles = [...]))]` makes a histogram field only emit the value at each
+/// of the given quantiles instead of its full distribution. Without a `strategy`, the field is
+/// aggregated into a `Histogram` of its type, and with one, the strategy must be a `Histogram`:
+///
+/// ```
+/// use metrique::unit_of_work::metrics;
+/// use metrique_aggregation::{aggregate, histogram::{Histogram, SortAndMerge}};
+/// use std::time::Duration;
+///
+/// #[aggregate]
+/// #[metrics]
+/// struct ApiCall {
+///     #[aggregate(histogram(quantiles = [0.5, 0.9, 0.99]))]
+///     latency: Duration,
+///
+///     #[aggregate(strategy = Histogram<usize, SortAndMerge>, histogram(quantiles = [0.5]))]
+///     bytes_sent: usize,
+/// }
+/// ```
+///
+/// See `QuantileHistogram` for how quantile histograms are merged.
+///
 /// # Generated Types
 ///
 /// For a struct with `#[aggregate]`, the macro generates:
 /// - `AggregatedMyMetrics`: The aggregated struct where each field is replaced with its aggregated type
 /// - `impl AggregateEntry for MyMetrics`: Trait implementation for merging observations
+/// - `impl Combine for MyMetrics`: Trait implementation for merging aggregated structs together,
+///   when every field's strategy supports it (the built-in strategies do)
 ///
 /// For more details on the aggregation trait system, see the
 /// [traits module documentation](https://docs.rs/metrique-aggregation/latest/metrique_aggregation/traits/index.html).
@@ -626,6 +844,11 @@ impl Tag {
         }
     }
 
+    /// Whether the tag field name gets the root prefix
+    pub(crate) fn prefixed(&self) -> bool {
+        matches!(self, Tag::Inflectable { .. })
+    }
+
     pub(crate) fn sample_group(&self) -> bool {
         match self {
             Tag::Inflectable { sample_group, .. } => *sample_group,
@@ -716,7 +939,7 @@ impl FromMeta for FlagsList {
 
 #[derive(Debug, Default, FromMeta)]
 struct RawRootAttributes {
-    prefix: Option<SpannedKv<String>>,
+    prefix: Option<SpannedKv<PrefixValue>>,
     exact_prefix: Option<SpannedKv<String>>,
 
     #[darling(default)]
@@ -732,7 +955,10 @@ struct RawRootAttributes {
     subfield_owned: Flag,
     #[darling(rename = "sample_group")]
     sample_group: Flag,
+    derive_eq: Flag,
+    default_sink: Option<SpannedKv<syn::Path>>,
     value: Option<ValueAttributes>,
+    reset: Flag,
 }
 
 #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
@@ -757,6 +983,13 @@ struct RootAttributes {
 
     sample_group: bool,
 
+    derive_eq: bool,
+
+    default_sink: Option<syn::Path>,
+
+    /// Set by `#[metrics(reset)]`, a `reset` method is generated for the struct
+    reset: Option<Span>,
+
     mode: MetricMode,
 }
 
@@ -795,6 +1028,33 @@ impl RawRootAttributes {
         } else {
             false
         };
+        let derive_eq = if self.derive_eq.is_present() {
+            if let MetricMode::Value | MetricMode::ValueString = mode {
+                true
+            } else {
+                return Err(darling::Error::custom(
+                    "`derive_eq` can only be used with #[metrics(value)] or #[metrics(value(string))]",
+                )
+                .with_span(&self.derive_eq.span()));
+            }
+        } else {
+            false
+        };
+        if self.rename_all == NameStyle::ScreamingSnakeCase && mode != MetricMode::ValueString {
+            return Err(darling::Error::custom(
+                "`rename_all = \"SCREAMING_SNAKE_CASE\"` can only be used with #[metrics(value(string))]",
+            ));
+        }
+        let default_sink = match (self.default_sink, mode) {
+            (None, _) => None,
+            (Some(default_sink), MetricMode::RootEntry) => Some(default_sink.value),
+            (Some(default_sink), _) => {
+                return Err(darling::Error::custom(
+                    "`default_sink` can only be used on root entries, not with subfield or value",
+                )
+                .with_span(&default_sink.key_span));
+            }
+        };
         if let (MetricMode::ValueString, Some(ds)) = (mode, &self.emf_dimensions) {
             return Err(
                 darling::Error::custom("value does not make sense with dimension-sets")
@@ -825,6 +1085,9 @@ impl RawRootAttributes {
             emf_dimensions: self.emf_dimensions,
             tag,
             sample_group,
+            derive_eq,
+            default_sink,
+            reset: self.reset.is_present().then(|| self.reset.span()),
             mode,
         })
     }
@@ -877,6 +1140,8 @@ impl RootAttributes {
 struct RawMetricsFieldAttrs {
     flatten: Flag,
 
+    index: Flag,
+
     flatten_entry: Flag,
 
     no_close: Flag,
@@ -887,23 +1152,36 @@ struct RawMetricsFieldAttrs {
 
     ignore: Flag,
 
+    default: Flag,
+
+    no_reset: Flag,
+
     #[darling(default)]
     unit: Option<SpannedKv<syn::Path>>,
 
     #[darling(default)]
-    format: Option<SpannedKv<syn::Path>>,
+    format: Option<SpannedKv<syn::Expr>>,
+
+    #[darling(default)]
+    skip_if: Option<SpannedKv<syn::Path>>,
 
     #[darling(default)]
     name: Option<SpannedKv<String>>,
 
     #[darling(default)]
-    prefix: Option<SpannedKv<String>>,
+    alias: Option<SpannedKv<String>>,
+
+    #[darling(default)]
+    prefix: Option<SpannedKv<PrefixValue>>,
 
     #[darling(default)]
     exact_prefix: Option<SpannedKv<String>>,
 
     #[darling(default)]
     flags: FlagsList,
+
+    #[darling(rename = "emf::high_storage_resolution")]
+    emf_high_storage_resolution: Flag,
 }
 
 /// Wrapper type to allow recovering both the key and value span when parsing an attribute
@@ -944,7 +1222,7 @@ pub(crate) fn parse_metric_fields(
             None => (quote! { #i }, None, field.ty.span()),
         };
 
-        let attrs = match errors
+        let mut attrs = match errors
             .handle(RawMetricsFieldAttrs::from_field(field).and_then(|attr| attr.validate()))
         {
             Some(attrs) => attrs,
@@ -952,6 +1230,11 @@ pub(crate) fn parse_metric_fields(
                 continue;
             }
         };
+        // `PhantomData` markers (e.g. for type states) never write anything, so they are
+        // ignored unless the field says otherwise
+        if is_phantom_data(&field.ty) && !field.attrs.iter().any(|a| a.path().is_ident("metrics")) {
+            attrs.kind = MetricsFieldKind::Ignore(field.ty.span());
+        }
 
         parsed_fields.push(MetricsField {
             ident,
@@ -969,6 +1252,17 @@ pub(crate) fn parse_metric_fields(
     Ok(parsed_fields)
 }
 
+/// Whether `ty` is `PhantomData<_>`, under any path
+fn is_phantom_data(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "PhantomData"),
+        _ => false,
+    }
+}
+
 fn cannot_combine_error(existing: &str, new: &str, new_span: Span) -> darling::Error {
     darling::Error::custom(format!("Cannot combine `{existing}` with `{new}`")).with_span(&new_span)
 }
@@ -1019,7 +1313,12 @@ impl RawMetricsFieldAttrs {
     fn validate(self) -> darling::Result<MetricsFieldAttrs> {
         let mut out: Option<(MetricsFieldKind, &'static str)> = None;
         out = set_exclusive(
-            |span| MetricsFieldKind::Flatten { span, prefix: None },
+            |span| MetricsFieldKind::Flatten {
+                span,
+                prefix: None,
+                index: None,
+                unit: None,
+            },
             "flatten",
             out,
             &self.flatten,
@@ -1040,14 +1339,64 @@ impl RawMetricsFieldAttrs {
 
         let name = self.name.map(validate_name).transpose()?;
         let name = get_field_option("name", &out, &name)?;
-        let unit = get_field_option("unit", &out, &self.unit)?;
-        let format = get_field_option("format", &out, &self.format)?;
+        let alias = self.alias.map(validate_name).transpose()?;
+        if let (Some(alias), Some(name)) = (&alias, name)
+            && alias.value == *name
+        {
+            return Err(darling::Error::custom(
+                "`alias` must be different from `name`, the field would be emitted twice under the same name",
+            )
+            .with_span(&alias.value_span));
+        }
+        let alias = get_field_option("alias", &out, &alias)?;
+        // on flattened fields, `unit` is the default unit for the values of the flattened entry
+        let mut unit_attr = self.unit;
+        if let Some((MetricsFieldKind::Flatten { unit, .. }, _)) = &mut out {
+            *unit = unit_attr.take().map(|unit| unit.value);
+        }
+        let unit = get_field_option("unit", &out, &unit_attr)?;
+        let format = get_field_option("format", &out, &self.format)?
+            .map(FieldFormat::from_expr)
+            .transpose()?;
         let sample_group = get_field_flag("sample_group", &out, &self.sample_group)?;
+        let high_storage_resolution = get_field_flag(
+            "emf::high_storage_resolution",
+            &out,
+            &self.emf_high_storage_resolution,
+        )?;
+        // the formatter formats the closed value, which would be wrapped by the flag
+        if let (Some(span), Some(_)) = (high_storage_resolution, &format) {
+            return Err(cannot_combine_error(
+                "format",
+                "emf::high_storage_resolution",
+                span,
+            ));
+        }
+        // `no_close` stores the field in the entry as-is. Combined with `flatten_entry`, this
+        // flattens types that implement `Entry` directly rather than through `CloseValue`.
         let close = !self.no_close.is_present();
         if let (false, Some((MetricsFieldKind::Ignore(span), _))) = (close, &out) {
             return Err(cannot_combine_error("no_close", "ignore", *span));
         }
 
+        // ignored fields are never reset, and `default` fields are reset to their default
+        if self.no_reset.is_present() {
+            if let Some((MetricsFieldKind::Ignore(_), _)) = &out {
+                return Err(cannot_combine_error(
+                    "ignore",
+                    "no_reset",
+                    self.no_reset.span(),
+                ));
+            }
+            if self.default.is_present() {
+                return Err(cannot_combine_error(
+                    "default",
+                    "no_reset",
+                    self.no_reset.span(),
+                ));
+            }
+        }
+
         let prefix = Prefix::from_inflectable_and_exact(
             &self.prefix,
             &self.exact_prefix,
@@ -1066,6 +1415,48 @@ impl RawMetricsFieldAttrs {
                 }
             }
         }
+        if self.index.is_present() {
+            match &mut out {
+                Some((MetricsFieldKind::Flatten { prefix: None, .. }, _)) => {
+                    return Err(darling::Error::custom(
+                        "`index` requires `prefix` or `exact_prefix`, the index is appended to the prefix",
+                    )
+                    .with_span(&self.index.span()));
+                }
+                Some((MetricsFieldKind::Flatten { index, .. }, _)) => {
+                    *index = Some(self.index.span());
+                }
+                _ => {
+                    return Err(
+                        darling::Error::custom("`index` can only be used with `flatten`")
+                            .with_span(&self.index.span()),
+                    );
+                }
+            }
+        }
+
+        let skip_if = match (self.skip_if, &out) {
+            (None, _) => None,
+            (
+                Some(skip_if),
+                Some((MetricsFieldKind::Timestamp(_) | MetricsFieldKind::Ignore(_), other)),
+            ) => {
+                return Err(cannot_combine_error(other, "skip_if", skip_if.key_span));
+            }
+            // the closed field is an `Option`, which can't be indexed
+            (Some(skip_if), Some((MetricsFieldKind::Flatten { index: Some(_), .. }, _))) => {
+                return Err(cannot_combine_error("index", "skip_if", skip_if.key_span));
+            }
+            // a skipped field has no value to use as the sample group
+            (Some(skip_if), None) if sample_group.is_some() => {
+                return Err(cannot_combine_error(
+                    "sample_group",
+                    "skip_if",
+                    skip_if.key_span,
+                ));
+            }
+            (Some(skip_if), _) => Some(skip_if.value),
+        };
 
         // flags(...) on flatten/flatten_entry/timestamp/ignore is not yet supported.
         if !self.flags.0.is_empty()
@@ -1090,11 +1481,16 @@ impl RawMetricsFieldAttrs {
                 None => MetricsFieldKind::Field {
                     sample_group,
                     name: name.cloned(),
+                    alias: alias.cloned(),
                     unit: unit.cloned(),
-                    format: format.cloned(),
+                    format,
                 },
             },
             flags: self.flags.0,
+            default: self.default.is_present().then(|| self.default.span()),
+            no_reset: self.no_reset.is_present(),
+            skip_if,
+            high_storage_resolution,
         })
     }
 }
@@ -1122,6 +1518,16 @@ struct MetricsFieldAttrs {
     close: bool,
     kind: MetricsFieldKind,
     flags: Vec<syn::Path>,
+    /// Set by `#[metrics(default)]`, the field is optional in the generated builder
+    default: Option<Span>,
+    /// Set by `#[metrics(no_reset)]`, the field keeps its value when the struct is reset
+    no_reset: bool,
+    /// Set by `#[metrics(skip_if = PREDICATE)]`, the field is closed to `None` if the predicate
+    /// returns `true`
+    skip_if: Option<syn::Path>,
+    /// Set by `#[metrics(emf::high_storage_resolution)]`, the closed value is wrapped in
+    /// `HighStorageResolution`
+    high_storage_resolution: Option<Span>,
 }
 
 pub(crate) struct MetricsField {
@@ -1166,19 +1572,8 @@ impl MetricsField {
         if let MetricsFieldKind::Ignore(_span) = self.attrs.kind {
             return None;
         }
-        let MetricsField {
-            ident, ty, span, ..
-        } = self;
-        let mut base_type = if self.attrs.close {
-            quote_spanned! { *span=> <#ty as metrique::CloseValue>::Closed }
-        } else {
-            quote_spanned! { *span=>#ty }
-        };
-        if let Some(expr) = self.unit() {
-            base_type = quote_spanned! { expr.span()=>
-                <#base_type as ::metrique::unit::AttachUnit>::Output<#expr>
-            }
-        }
+        let MetricsField { ident, span, .. } = self;
+        let base_type = self.entry_type();
         let inner = if named {
             quote! { #ident: #base_type }
         } else {
@@ -1193,6 +1588,35 @@ impl MetricsField {
         })
     }
 
+    /// The type of this field in the entry struct
+    pub(crate) fn entry_type(&self) -> Ts2 {
+        let MetricsField { ty, span, .. } = self;
+        let mut base_type = if self.attrs.close {
+            quote_spanned! { *span=> <#ty as metrique::CloseValue>::Closed }
+        } else {
+            quote_spanned! { *span=>#ty }
+        };
+        if let Some(expr) = self.unit() {
+            base_type = quote_spanned! { expr.span()=>
+                <#base_type as ::metrique::unit::AttachUnit>::Output<#expr>
+            }
+        }
+        if let Some(span) = self.attrs.high_storage_resolution {
+            base_type = quote_spanned! { span=>
+                ::metrique::emf::__HighStorageResolution<#base_type>
+            }
+        }
+        if let Some(skip_if) = &self.attrs.skip_if {
+            base_type = quote_spanned! { skip_if.span()=> ::std::option::Option<#base_type> }
+        }
+        if let Some((format_ty, _)) = self.stored_format() {
+            base_type = quote_spanned! { format_ty.span()=>
+                ::metrique::format::WithFormatter<#base_type, #format_ty>
+            }
+        }
+        base_type
+    }
+
     fn unit(&self) -> Option<&syn::Path> {
         match &self.attrs.kind {
             MetricsFieldKind::Field { unit, .. } => unit.as_ref(),
@@ -1200,6 +1624,16 @@ impl MetricsField {
         }
     }
 
+    fn stored_format(&self) -> Option<(&syn::Path, &syn::Expr)> {
+        match &self.attrs.kind {
+            MetricsFieldKind::Field {
+                format: Some(format),
+                ..
+            } => format.stored(),
+            _ => None,
+        }
+    }
+
     pub(crate) fn close_value(&self, ownership_kind: OwnershipKind) -> Ts2 {
         let ident = &self.ident;
         let span = self.span;
@@ -1216,12 +1650,40 @@ impl MetricsField {
         let base = if self.attrs.close {
             quote_spanned! {span=> metrique::CloseValue::close(#field_expr) }
         } else {
-            field_expr
+            field_expr.clone()
         };
 
         let base = if let Some(unit) = self.unit() {
             quote_spanned! { unit.span() =>
-                #base.into()
+                ::metrique::unit::AttachUnit::make::<#unit>(#base)
+            }
+        } else {
+            base
+        };
+
+        let base = if let Some(span) = self.attrs.high_storage_resolution {
+            quote_spanned! { span=>
+                ::metrique::emf::__HighStorageResolution::from(#base)
+            }
+        } else {
+            base
+        };
+
+        let base = if let Some(skip_if) = &self.attrs.skip_if {
+            quote_spanned! { skip_if.span() =>
+                if #skip_if(&#field_expr) {
+                    ::std::option::Option::None
+                } else {
+                    ::std::option::Option::Some(#base)
+                }
+            }
+        } else {
+            base
+        };
+
+        let base = if let Some((_, format_expr)) = self.stored_format() {
+            quote_spanned! { format_expr.span() =>
+                ::metrique::format::WithFormatter::new(#base, #format_expr)
             }
         } else {
             base
@@ -1252,14 +1714,50 @@ pub(crate) enum PrefixLevel {
     Field,
 }
 
+/// The value of a `prefix` attribute: a string literal, or a path to a `const &str`
+#[derive(Debug, Clone)]
+pub(crate) enum PrefixValue {
+    Literal(String),
+    Const(syn::Path),
+}
+
+impl FromMeta for PrefixValue {
+    fn from_string(value: &str) -> darling::Result<Self> {
+        Ok(Self::Literal(value.to_owned()))
+    }
+
+    fn from_expr(expr: &syn::Expr) -> darling::Result<Self> {
+        match expr {
+            syn::Expr::Lit(lit) => Self::from_value(&lit.lit),
+            syn::Expr::Path(syn::ExprPath {
+                qself: None, path, ..
+            }) => Ok(Self::Const(path.clone())),
+            // paths passed through `macro_rules!` are wrapped in a group
+            syn::Expr::Group(group) => Self::from_expr(&group.expr),
+            _ => Err(darling::Error::custom(
+                "`prefix` must be a string literal or a path to a `const &str`",
+            )
+            .with_span(expr)),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) enum Prefix {
-    Inflectable { prefix: String },
+    Inflectable {
+        prefix: String,
+    },
     Exact(String),
+    /// A path to a `const &str`. Its value is not known when the macro expands, so it is not
+    /// inflected and is prepended as-is, like `Exact`.
+    Const(syn::Path),
 }
 
 impl Prefix {
     /// Apply prefix to base name and inflect according to name_style
+    ///
+    /// `Const` prefixes can't be applied here, so the name is only inflected. They are added
+    /// when generating the name, see `entry_impl::make_inflect_const_prefixed`.
     pub(crate) fn apply(&self, base: &str, name_style: NameStyle) -> String {
         match self {
             Prefix::Exact(exact_prefix) => {
@@ -1269,6 +1767,7 @@ impl Prefix {
                 let prefixed = format!("{}{}", prefix, base);
                 name_style.apply(&prefixed)
             }
+            Prefix::Const(_) => name_style.apply(base),
         }
     }
 
@@ -1299,32 +1798,50 @@ impl Prefix {
     }
 
     fn from_inflectable_and_exact(
-        inflectable: &Option<SpannedKv<String>>,
+        inflectable: &Option<SpannedKv<PrefixValue>>,
         exact: &Option<SpannedKv<String>>,
         level: PrefixLevel,
     ) -> darling::Result<Option<SpannedValue<Self>>> {
         match (inflectable, exact) {
-            (Some(prefix), None) => {
-                if let Some(c) = name_contains_uninflectables(&prefix.value) {
+            (
+                Some(SpannedKv {
+                    key_span,
+                    value: PrefixValue::Const(path),
+                    ..
+                }),
+                None,
+            ) => Ok(Some(SpannedValue::new(
+                Self::Const(path.clone()),
+                *key_span,
+            ))),
+            (
+                Some(SpannedKv {
+                    key_span,
+                    value: PrefixValue::Literal(prefix),
+                    ..
+                }),
+                None,
+            ) => {
+                if let Some(c) = name_contains_uninflectables(prefix) {
                     Err(
-                        darling::Error::custom(Self::inflected_prefix_message(&prefix.value, c))
-                            .with_span(&prefix.key_span),
+                        darling::Error::custom(Self::inflected_prefix_message(prefix, c))
+                            .with_span(key_span),
                     )
                 } else if let PrefixLevel::Root = level
-                    && !name_ends_with_delimiter(&prefix.value)
+                    && !name_ends_with_delimiter(prefix)
                 {
                     Err(
                         darling::Error::custom(Self::prefix_should_end_with_delimiter_message(
-                            &prefix.value,
+                            prefix,
                         ))
-                        .with_span(&prefix.key_span),
+                        .with_span(key_span),
                     )
                 } else {
                     Ok(Some(SpannedValue::new(
                         Self::Inflectable {
-                            prefix: prefix.value.clone(),
+                            prefix: prefix.clone(),
                         },
-                        prefix.key_span,
+                        *key_span,
                     )))
                 }
             }
@@ -1356,6 +1873,7 @@ impl Prefix {
             Prefix::Exact(exact_prefix) => {
                 crate::entry_impl::make_exact_prefix(ns, exact_prefix, span)
             }
+            Prefix::Const(path) => crate::entry_impl::make_const_prefix(ns, path, span),
         }
     }
 }
@@ -1366,17 +1884,77 @@ enum MetricsFieldKind {
     Flatten {
         span: Span,
         prefix: Option<Prefix>,
+        /// Set by `index`: the field is a collection, whose elements are written with their
+        /// index appended to the prefix
+        index: Option<Span>,
+        /// Unit applied to the values of the flattened entry that have no unit
+        unit: Option<syn::Path>,
     },
     FlattenEntry(Span),
     Timestamp(Span),
     Field {
         unit: Option<syn::Path>,
         name: Option<String>,
-        format: Option<syn::Path>,
+        alias: Option<String>,
+        format: Option<FieldFormat>,
         sample_group: Option<Span>,
     },
 }
 
+/// The formatter of a field, set with `format`
+#[derive(Debug, Clone)]
+enum FieldFormat {
+    /// A `ValueFormatter` type, e.g. `format = EpochSeconds`
+    Type(syn::Path),
+    /// A `ConfiguredValueFormatter` of type `ty`, evaluated when the field is closed, e.g.
+    /// `format = Precision::new(3)`
+    Value { ty: syn::Path, expr: Box<syn::Expr> },
+}
+
+impl FieldFormat {
+    fn from_expr(expr: &syn::Expr) -> darling::Result<Self> {
+        let ty = match expr {
+            syn::Expr::Path(syn::ExprPath {
+                qself: None, path, ..
+            }) => return Ok(FieldFormat::Type(path.clone())),
+            // `Ty::constructor(..)`, the formatter is assumed to be of type `Ty`
+            syn::Expr::Call(syn::ExprCall { func, .. }) => match &**func {
+                syn::Expr::Path(syn::ExprPath {
+                    qself: None, path, ..
+                }) if path.segments.len() > 1 => {
+                    let mut ty = path.clone();
+                    ty.segments.pop();
+                    ty.segments.pop_punct();
+                    Some(ty)
+                }
+                _ => None,
+            },
+            syn::Expr::Struct(syn::ExprStruct {
+                qself: None, path, ..
+            }) => Some(path.clone()),
+            _ => None,
+        };
+        match ty {
+            Some(ty) => Ok(FieldFormat::Value {
+                ty,
+                expr: Box::new(expr.clone()),
+            }),
+            None => Err(darling::Error::custom(
+                "`format` must be a formatter type, a constructor call such as `Formatter::new(..)`, or a struct expression",
+            )
+            .with_span(expr)),
+        }
+    }
+
+    /// The formatter that is stored in the entry, if any
+    fn stored(&self) -> Option<(&syn::Path, &syn::Expr)> {
+        match self {
+            FieldFormat::Type(_) => None,
+            FieldFormat::Value { ty, expr } => Some((ty, expr)),
+        }
+    }
+}
+
 // produce a warning that the user can see
 //
 // currently, we do not have any logic that produces warnings, but leave this
@@ -1516,24 +2094,46 @@ pub(crate) fn generate_on_drop_wrapper(
     target: &Ident,
     handle: &Ident,
     generics: &Generics,
+    default_sink: Option<&syn::Path>,
 ) -> Ts2 {
     let inner_str = inner.to_string();
+    let default_sink = match default_sink {
+        Some(default_sink) => quote! { #default_sink },
+        None => quote! { ::metrique::DefaultSink },
+    };
     let guard_str = guard.to_string();
 
     let (_impl_generics, _, where_clause) = generics.split_for_impl();
     let inner_static = with_static_lifetimes(inner, generics);
     let target_static = with_static_lifetimes(target, generics);
+    // lifetimes are always `'static`, but type and const parameters are kept
+    let params: Vec<_> = generics
+        .params
+        .iter()
+        .filter(|param| !matches!(param, GenericParam::Lifetime(_)))
+        .collect();
+    // with type parameters, whether the struct can be sent depends on them
+    let send_bound = if generics.type_params().next().is_some() {
+        quote! { where Self: Send + Sync + 'static }
+    } else {
+        quote! {}
+    };
+    let params_idents = params.iter().map(|param| match param {
+        GenericParam::Type(ty) => &ty.ident,
+        GenericParam::Const(c) => &c.ident,
+        GenericParam::Lifetime(_) => unreachable!(),
+    });
 
     quote! {
         #[doc = concat!("Metrics guard returned from [`", #inner_str, "::append_on_drop`], closes the entry and appends the metrics to a sink when dropped.")]
-        #vis type #guard<Q = ::metrique::DefaultSink> = ::metrique::AppendAndCloseOnDrop<#inner_static, Q>;
+        #vis type #guard<#(#params,)* Q = #default_sink> = ::metrique::AppendAndCloseOnDrop<#inner_static, Q>;
 
         #[doc = concat!("Metrics handle returned from [`", #guard_str, "::handle`], similar to an `Arc<", #guard_str, ">`.")]
-        #vis type #handle<Q = ::metrique::DefaultSink> = ::metrique::AppendAndCloseOnDropHandle<#inner_static, Q>;
+        #vis type #handle<#(#params,)* Q = #default_sink> = ::metrique::AppendAndCloseOnDropHandle<#inner_static, Q>;
 
-        impl #inner_static #where_clause {
+        impl<#(#params),*> #inner_static #where_clause {
             #[doc = "Creates an AppendAndCloseOnDrop that will be automatically appended to `sink` on drop."]
-            #vis fn append_on_drop<Q: ::metrique::writer::EntrySink<::metrique::RootEntry<#target_static>> + Send + Sync + 'static>(self, sink: Q) -> #guard<Q> {
+            #vis fn append_on_drop<Q: ::metrique::writer::EntrySink<::metrique::RootEntry<#target_static>> + Send + Sync + 'static>(self, sink: Q) -> #guard<#(#params_idents,)* Q> #send_bound {
                 ::metrique::append_and_close(self, sink)
             }
         }
@@ -1648,7 +2248,7 @@ mod tests {
     use quote::quote;
     use syn::{parse_quote, parse2};
 
-    use crate::RawRootAttributes;
+    use crate::{RawMetricsFieldAttrs, RawRootAttributes};
 
     // Helper function to convert proc_macro::TokenStream to proc_macro2::TokenStream
     // This allows us to test the macro without needing to use the proc_macro API directly
@@ -1703,17 +2303,164 @@ mod tests {
     }
 
     #[test]
-    fn test_sample_group_metrics_struct() {
+    fn test_default_sink_metrics_struct() {
         let input = quote! {
             struct RequestMetrics {
-                #[metrics(sample_group)]
                 operation: &'static str,
-                number_of_ducks: usize
             }
         };
 
-        let parsed_file = metrics_impl_string(input, quote!(metrics()));
-        assert_snapshot!("sample_group_metrics_struct", parsed_file);
+        let parsed_file = metrics_impl_string(
+            input,
+            quote!(metrics(default_sink = crate::sinks::MetricsQueue)),
+        );
+        assert_snapshot!("default_sink_metrics_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_default_sink_requires_root_entry() {
+        let err = RawRootAttributes::from_meta(&parse_quote!(metrics(
+            subfield,
+            default_sink = MetricsQueue
+        )))
+        .unwrap()
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("`default_sink` can only be used"));
+    }
+
+    #[test]
+    fn test_default_field_builder_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                #[metrics(timestamp)]
+                timestamp: Timestamp,
+                operation: &'static str,
+                #[metrics(default)]
+                retries: usize,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics()));
+        assert_snapshot!("default_field_builder_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_reset_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                #[metrics(timestamp)]
+                timestamp: Timestamp,
+                #[metrics(no_reset)]
+                operation: &'static str,
+                latency: Timer,
+                #[metrics(flatten)]
+                nested: NestedMetrics,
+                #[metrics(default)]
+                retries: usize,
+                #[metrics(ignore)]
+                scratch: Vec<u8>,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics(reset)));
+        assert_snapshot!("reset_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_reset_value_unnamed_struct() {
+        let input = quote! {
+            struct RequestCount(usize);
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics(value, reset)));
+        assert_snapshot!("reset_value_unnamed_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_reset_errors() {
+        let root_attrs = |meta: syn::Meta| {
+            RawRootAttributes::from_meta(&meta)
+                .unwrap()
+                .validate()
+                .unwrap()
+        };
+
+        let input = syn::parse2(quote! {
+            enum Operation {
+                Read,
+            }
+        })
+        .unwrap();
+        let err = super::generate_metrics(
+            root_attrs(parse_quote!(metrics(value(string), reset))),
+            input,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`reset` can only be used on structs")
+        );
+
+        for field in [
+            quote!(#[metrics(ignore, no_reset)] scratch: usize),
+            quote!(#[metrics(default, no_reset)] retries: usize),
+        ] {
+            let input = syn::parse2(quote! {
+                struct RequestMetrics { #field }
+            })
+            .unwrap();
+            let err = super::generate_metrics(root_attrs(parse_quote!(metrics(reset))), input)
+                .unwrap_err();
+            assert!(err.to_string().contains("Cannot combine"), "{err}");
+        }
+    }
+
+    #[test]
+    fn test_default_field_errors() {
+        let root_attrs = |meta: syn::Meta| {
+            RawRootAttributes::from_meta(&meta)
+                .unwrap()
+                .validate()
+                .unwrap()
+        };
+
+        let input = syn::parse2(quote! {
+            struct RequestCount(#[metrics(default)] usize);
+        })
+        .unwrap();
+        let err =
+            super::generate_metrics(root_attrs(parse_quote!(metrics(value))), input).unwrap_err();
+        assert!(err.to_string().contains("structs with named fields"));
+
+        let input = syn::parse2(quote! {
+            enum RequestMetrics {
+                Read {
+                    #[metrics(default)]
+                    bytes: usize,
+                },
+            }
+        })
+        .unwrap();
+        let err = super::generate_metrics(root_attrs(parse_quote!(metrics())), input).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("can only be used on fields of structs")
+        );
+    }
+
+    #[test]
+    fn test_sample_group_metrics_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                #[metrics(sample_group)]
+                operation: &'static str,
+                number_of_ducks: usize
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics()));
+        assert_snapshot!("sample_group_metrics_struct", parsed_file);
     }
 
     #[test]
@@ -1757,6 +2504,21 @@ mod tests {
         assert_snapshot!("simple_metrics_value_unnamed_struct", parsed_file);
     }
 
+    #[test]
+    fn test_derive_eq_metrics_value_struct() {
+        let input = quote! {
+            #[derive(Debug)]
+            struct RequestValue {
+                #[metrics(ignore)]
+                ignore: u32,
+                value: u32,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics(value, derive_eq)));
+        assert_snapshot!("derive_eq_metrics_value_struct", parsed_file);
+    }
+
     #[test]
     fn test_simple_metrics_enum() {
         let input = quote! {
@@ -1769,6 +2531,96 @@ mod tests {
         assert_snapshot!("simple_metrics_enum", parsed_file);
     }
 
+    #[test]
+    fn test_derive_eq_metrics_enum() {
+        let input = quote! {
+            enum Foo {
+                Bar,
+                Baz,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics(value(string), derive_eq)));
+        assert_snapshot!("derive_eq_metrics_enum", parsed_file);
+    }
+
+    #[test]
+    fn test_derive_eq_requires_value() {
+        use darling::FromMeta;
+        let err = RawRootAttributes::from_meta(&parse_quote!(metrics(derive_eq)))
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("`derive_eq` can only be used"));
+    }
+
+    #[test]
+    fn test_screaming_snake_case_value_string_enum() {
+        let input = quote! {
+            enum Operation {
+                ReadData,
+                #[metrics(name = "write")]
+                WriteData,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(
+            input,
+            quote!(metrics(value(string), rename_all = "SCREAMING_SNAKE_CASE")),
+        );
+        assert_snapshot!("screaming_snake_case_value_string_enum", parsed_file);
+    }
+
+    #[test]
+    fn test_screaming_snake_case_requires_value_string() {
+        use darling::FromMeta;
+        let err = RawRootAttributes::from_meta(&parse_quote!(metrics(
+            rename_all = "SCREAMING_SNAKE_CASE"
+        )))
+        .unwrap()
+        .validate()
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`rename_all = \"SCREAMING_SNAKE_CASE\"` can only be used")
+        );
+    }
+
+    #[test]
+    fn test_alias_metrics_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                #[metrics(name = "ItemCount", alias = "NumItems")]
+                items: usize,
+                #[metrics(alias = "Op")]
+                operation: &'static str,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics(rename_all = "PascalCase")));
+        assert_snapshot!("alias_metrics_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_alias_same_as_name_error() {
+        let input = syn::parse2(quote! {
+            struct RequestMetrics {
+                #[metrics(name = "Same", alias = "Same")]
+                items: usize,
+            }
+        })
+        .unwrap();
+        let root_attrs = RawRootAttributes::from_meta(&parse_quote!(metrics()))
+            .unwrap()
+            .validate()
+            .unwrap();
+        let err = super::generate_metrics(root_attrs, input).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`alias` must be different from `name`")
+        );
+    }
+
     #[test]
     fn test_exact_prefix_struct() {
         let input = quote! {
@@ -1782,6 +2634,30 @@ mod tests {
         assert_snapshot!("exact_prefix_struct", parsed_file);
     }
 
+    #[test]
+    fn test_const_prefix_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                #[metrics(flatten, prefix = prefixes::NESTED)]
+                nested: NestedMetrics,
+                operation: &'static str
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics(prefix = prefixes::API)));
+        assert_snapshot!("const_prefix_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_const_prefix_errors() {
+        let err = RawRootAttributes::from_meta(&parse_quote!(metrics(prefix = API.to_string())))
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`prefix` must be a string literal or a path to a `const &str` at prefix"
+        );
+    }
+
     #[test]
     fn test_field_exact_prefix_struct() {
         let input = quote! {
@@ -1860,6 +2736,300 @@ mod tests {
         assert_snapshot!("field_inflectable_prefix_struct", parsed_file);
     }
 
+    #[test]
+    fn test_field_indexed_flatten_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                #[metrics(flatten, index, prefix = "retry")]
+                retries: Vec<Attempt>,
+                operation: &'static str
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics(rename_all = "PascalCase")));
+        assert_snapshot!("field_indexed_flatten_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_field_flatten_unit_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                #[metrics(flatten, unit = Millisecond)]
+                timings: Timings,
+                operation: &'static str
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics()));
+        assert_snapshot!("field_flatten_unit_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_format_expression_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                #[metrics(format = EpochSeconds)]
+                start: Timestamp,
+                #[metrics(format = fmt::Precision::new(3))]
+                ratio: f64,
+                #[metrics(format = Precision { digits: 1 })]
+                load: f64,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics()));
+        assert_snapshot!("format_expression_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_format_expression_errors() {
+        let input = syn::parse2(quote! {
+            struct RequestMetrics {
+                #[metrics(format = precision(3))]
+                ratio: f64,
+            }
+        })
+        .unwrap();
+        let err = super::generate_metrics(
+            RawRootAttributes::from_meta(&parse_quote!(metrics()))
+                .unwrap()
+                .validate()
+                .unwrap(),
+            input,
+        )
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`format` must be a formatter type")
+        );
+    }
+
+    #[test]
+    fn test_skip_if_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                #[metrics(skip_if = is_zero)]
+                cache_miss_latency: Duration,
+                #[metrics(flatten, skip_if = Option::is_none)]
+                retry: Option<RetryMetrics>,
+                #[metrics(unit = Millisecond, skip_if = is_zero)]
+                backoff: Duration,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics()));
+        assert_snapshot!("skip_if_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_phantom_data_struct() {
+        let input = quote! {
+            struct RequestMetrics<S> {
+                operation: &'static str,
+                state: PhantomData<S>,
+                marker: std::marker::PhantomData<Sent>,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics()));
+        assert_snapshot!("phantom_data_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_generic_value_struct() {
+        let input = quote! {
+            struct Latency<'a, S: Stage>(Duration, PhantomData<&'a S>)
+            where
+                S: Debug;
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics(value)));
+        assert_snapshot!("generic_value_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_high_storage_resolution_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                #[metrics(emf::high_storage_resolution)]
+                queue_depth: usize,
+                #[metrics(emf::high_storage_resolution, unit = Millisecond)]
+                latency: Duration,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics()));
+        assert_snapshot!("high_storage_resolution_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_high_storage_resolution_errors() {
+        let error = |input: Ts2| {
+            let input = syn::parse2(input).unwrap();
+            super::generate_metrics(
+                RawRootAttributes::from_meta(&parse_quote!(metrics()))
+                    .unwrap()
+                    .validate()
+                    .unwrap(),
+                input,
+            )
+            .unwrap_err()
+            .to_string()
+        };
+
+        assert_eq!(
+            error(quote! {
+                struct RequestMetrics {
+                    #[metrics(flatten, emf::high_storage_resolution)]
+                    nested: Nested,
+                }
+            }),
+            "Cannot combine `flatten` with `emf::high_storage_resolution`"
+        );
+        assert_eq!(
+            error(quote! {
+                struct RequestMetrics {
+                    #[metrics(format = EpochSeconds, emf::high_storage_resolution)]
+                    start: Timestamp,
+                }
+            }),
+            "Cannot combine `format` with `emf::high_storage_resolution`"
+        );
+    }
+
+    #[test]
+    fn test_flatten_entry_no_close_struct() {
+        let input = quote! {
+            struct RequestMetrics {
+                operation: &'static str,
+                #[metrics(flatten_entry, no_close)]
+                downstream: DownstreamEntry,
+            }
+        };
+
+        let parsed_file = metrics_impl_string(input, quote!(metrics()));
+        assert_snapshot!("flatten_entry_no_close_struct", parsed_file);
+    }
+
+    #[test]
+    fn test_no_close_by_ref_errors() {
+        let error = |input: Ts2, attrs: Ts2| {
+            let input = syn::parse2(input).unwrap();
+            super::generate_metrics(
+                RawRootAttributes::from_meta(&parse_quote!(#attrs))
+                    .unwrap()
+                    .validate()
+                    .unwrap(),
+                input,
+            )
+            .unwrap_err()
+            .to_string()
+        };
+        let input = quote! {
+            struct Downstream {
+                #[metrics(flatten_entry, no_close)]
+                entry: DownstreamEntry,
+            }
+        };
+
+        assert_eq!(
+            error(input.clone(), quote!(metrics(subfield))),
+            "`no_close` fields are moved into the entry, which requires `#[metrics(subfield_owned)]` instead of `#[metrics(subfield)]`"
+        );
+        assert!(
+            metrics_impl_string(input, quote!(metrics(subfield_owned)))
+                .contains("entry: DownstreamEntry,")
+        );
+        assert_eq!(
+            error(
+                quote! {
+                    struct Wrapper(#[metrics(no_close)] Histogram);
+                },
+                quote!(metrics(value))
+            ),
+            "`no_close` is not supported in `#[metrics(value)]` structs, which are closed by reference"
+        );
+    }
+
+    #[test]
+    fn test_skip_if_errors() {
+        let error = |input: Ts2| {
+            let input = syn::parse2(input).unwrap();
+            super::generate_metrics(
+                RawRootAttributes::from_meta(&parse_quote!(metrics()))
+                    .unwrap()
+                    .validate()
+                    .unwrap(),
+                input,
+            )
+            .unwrap_err()
+            .to_string()
+        };
+
+        assert_eq!(
+            error(quote! {
+                struct RequestMetrics {
+                    #[metrics(timestamp, skip_if = is_epoch)]
+                    start: Timestamp,
+                }
+            }),
+            "Cannot combine `timestamp` with `skip_if`"
+        );
+        assert_eq!(
+            error(quote! {
+                struct RequestMetrics {
+                    #[metrics(ignore, skip_if = is_empty)]
+                    notes: Vec<String>,
+                }
+            }),
+            "Cannot combine `ignore` with `skip_if`"
+        );
+        assert_eq!(
+            error(quote! {
+                struct RequestMetrics {
+                    #[metrics(sample_group, skip_if = is_empty)]
+                    operation: &'static str,
+                }
+            }),
+            "Cannot combine `sample_group` with `skip_if`"
+        );
+        assert_eq!(
+            error(quote! {
+                enum RequestMetrics {
+                    Read(#[metrics(flatten, skip_if = is_empty)] ReadMetrics),
+                }
+            }),
+            "`skip_if` is not supported on tuple variant fields"
+        );
+    }
+
+    #[test]
+    fn test_index_requires_flatten_and_prefix() {
+        use darling::FromField;
+        let no_prefix: syn::Field = parse_quote! {
+            #[metrics(flatten, index)]
+            retries: Vec<Attempt>
+        };
+        let err = RawMetricsFieldAttrs::from_field(&no_prefix)
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("`index` requires `prefix`"));
+
+        let no_flatten: syn::Field = parse_quote! {
+            #[metrics(index)]
+            retries: Vec<u64>
+        };
+        let err = RawMetricsFieldAttrs::from_field(&no_flatten)
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`index` can only be used with `flatten`")
+        );
+    }
+
     #[test]
     fn test_entry_enum() {
         let nested = metrics_impl_string(
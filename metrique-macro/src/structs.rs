@@ -2,14 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use proc_macro2::TokenStream as Ts2;
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, quote_spanned};
 use syn::{
     Attribute, DeriveInput, FieldsNamed, FieldsUnnamed, Generics, Ident, Result, Visibility,
 };
 
 use crate::{
-    MetricMode, MetricsField, MetricsFieldKind, RootAttributes, clean_attrs, entry_impl,
-    generate_on_drop_wrapper, parse_metric_fields, value_impl,
+    MetricMode, MetricsField, MetricsFieldKind, OwnershipKind, RootAttributes, clean_attrs,
+    entry_impl, generate_on_drop_wrapper, parse_metric_fields, value_impl,
 };
 
 pub(crate) fn generate_metrics_for_struct(
@@ -27,6 +27,7 @@ pub(crate) fn generate_metrics_for_struct(
     let handle_name = format_ident!("{}Handle", struct_name);
 
     let parsed_fields = parse_metric_fields(fields)?;
+    validate_no_close_fields(&root_attributes, &parsed_fields)?;
 
     let base_struct = generate_base_struct(
         struct_name,
@@ -35,6 +36,14 @@ pub(crate) fn generate_metrics_for_struct(
         &clean_attrs(&input.attrs),
         &parsed_fields,
     )?;
+    let builder = generate_builder(struct_name, &input.vis, &input.generics, &parsed_fields)?;
+    let reset = generate_reset(
+        struct_name,
+        &input.vis,
+        &input.generics,
+        &parsed_fields,
+        &root_attributes,
+    );
     let warnings = root_attributes.warnings();
 
     let entry_struct = generate_entry_struct(
@@ -59,17 +68,39 @@ pub(crate) fn generate_metrics_for_struct(
                 &parsed_fields,
             )?
         }
-        _ => entry_impl::generate_struct_entry_impl(
-            &entry_name,
-            &input.generics,
-            &parsed_fields,
-            &root_attributes,
-        ),
+        _ => {
+            let entry_impl = entry_impl::generate_struct_entry_impl(
+                &entry_name,
+                &input.generics,
+                &parsed_fields,
+                &root_attributes,
+            );
+            let serialize_impl = entry_impl::generate_serialize_impl(&entry_name, &input.generics);
+            let field_names_impl = entry_impl::generate_struct_field_names_impl(
+                &entry_name,
+                &input.generics,
+                &parsed_fields,
+                &root_attributes,
+            );
+            quote! {
+                #entry_impl
+                #serialize_impl
+                #field_names_impl
+            }
+        }
     };
+    let field_names = generate_field_names_const(
+        struct_name,
+        &entry_name,
+        &input.vis,
+        &input.generics,
+        &root_attributes,
+    );
 
     let close_value_impl = generate_close_value_impls_for_struct(
         struct_name,
         &entry_name,
+        &input.vis,
         &input.generics,
         &parsed_fields,
         &root_attributes,
@@ -85,6 +116,7 @@ pub(crate) fn generate_metrics_for_struct(
                 &entry_name,
                 &handle_name,
                 &input.generics,
+                root_attributes.default_sink.as_ref(),
             );
             quote! {
                 #on_drop_wrapper
@@ -100,6 +132,9 @@ pub(crate) fn generate_metrics_for_struct(
 
     Ok(quote! {
         #base_struct
+        #builder
+        #reset
+        #field_names
         #warnings
         #entry_struct
         #inner_impl
@@ -108,6 +143,29 @@ pub(crate) fn generate_metrics_for_struct(
     })
 }
 
+/// `no_close` fields are moved into the entry as-is (e.g. a `flatten_entry` field whose type
+/// implements `Entry` directly), which isn't possible for structs that are closed by reference.
+fn validate_no_close_fields(root_attrs: &RootAttributes, fields: &[MetricsField]) -> Result<()> {
+    if matches!(root_attrs.ownership_kind(), OwnershipKind::ByValue) {
+        return Ok(());
+    }
+    let Some(field) = fields
+        .iter()
+        .find(|f| !f.attrs.close && !matches!(f.attrs.kind, MetricsFieldKind::Ignore(_)))
+    else {
+        return Ok(());
+    };
+    let message = match root_attrs.mode {
+        MetricMode::Subfield => {
+            "`no_close` fields are moved into the entry, which requires `#[metrics(subfield_owned)]` instead of `#[metrics(subfield)]`"
+        }
+        _ => {
+            "`no_close` is not supported in `#[metrics(value)]` structs, which are closed by reference"
+        }
+    };
+    Err(syn::Error::new(field.span, message))
+}
+
 fn generate_base_struct(
     name: &Ident,
     vis: &Visibility,
@@ -117,7 +175,7 @@ fn generate_base_struct(
 ) -> Result<Ts2> {
     let has_named_fields = fields.iter().any(|f| f.name.is_some());
     let fields = fields.iter().map(|f| f.core_field(has_named_fields));
-    let body = wrap_fields_into_struct_decl(has_named_fields, fields);
+    let body = wrap_fields_into_struct_decl(has_named_fields, generics, fields);
 
     Ok(quote! {
         #(#attrs)*
@@ -125,11 +183,192 @@ fn generate_base_struct(
     })
 }
 
-fn wrap_fields_into_struct_decl(has_named_fields: bool, fields: impl Iterator<Item = Ts2>) -> Ts2 {
+/// Generate `<Name>::builder` and `<Name>Builder` if any field is marked `#[metrics(default)]`.
+///
+/// The builder wraps the base struct, which `builder` constructs from the required fields and
+/// `Default::default()` for the others.
+fn generate_builder(
+    name: &Ident,
+    vis: &Visibility,
+    generics: &Generics,
+    fields: &[MetricsField],
+) -> Result<Ts2> {
+    let Some(default_span) = fields.iter().find_map(|f| f.attrs.default) else {
+        return Ok(quote! {});
+    };
+    if fields.iter().any(|f| f.name.is_none()) {
+        return Err(syn::Error::new(
+            default_span,
+            "`default` can only be used on structs with named fields",
+        ));
+    }
+
+    let builder_name = format_ident!("{}Builder", name);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let (default_fields, required_fields): (Vec<_>, Vec<_>) =
+        fields.iter().partition(|f| f.attrs.default.is_some());
+    let params = required_fields.iter().map(|f| {
+        let (ident, ty) = (&f.ident, &f.ty);
+        let cfg_attrs = f.cfg_attrs();
+        quote! { #(#cfg_attrs)* #ident: #ty }
+    });
+    let inits = fields.iter().map(|f| {
+        let ident = &f.ident;
+        let cfg_attrs = f.cfg_attrs();
+        if f.attrs.default.is_some() {
+            let default = quote_spanned! {f.span=> ::std::default::Default::default() };
+            quote! { #(#cfg_attrs)* #ident: #default }
+        } else {
+            quote! { #(#cfg_attrs)* #ident }
+        }
+    });
+    let setters = default_fields.iter().map(|f| {
+        let (ident, ty) = (&f.ident, &f.ty);
+        let cfg_attrs = f.cfg_attrs();
+        let doc = format!("Set `{ident}`, which otherwise defaults to `Default::default()`");
+        quote! {
+            #(#cfg_attrs)*
+            #[doc = #doc]
+            #vis fn #ident(mut self, #ident: #ty) -> Self {
+                self.0.#ident = #ident;
+                self
+            }
+        }
+    });
+
+    let builder_doc = format!("Builder for [`{name}`], returned by [`{name}::builder`]");
+    let build_doc = format!("Build the [`{name}`]");
+    Ok(quote! {
+        #[doc = #builder_doc]
+        #[must_use]
+        #vis struct #builder_name #generics (#name #ty_generics) #where_clause;
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Create a builder from the fields that don't have `#[metrics(default)]`, in
+            /// declaration order. The other fields start out as `Default::default()`.
+            #[allow(clippy::too_many_arguments)]
+            #vis fn builder(#(#params),*) -> #builder_name #ty_generics {
+                #builder_name(#name { #(#inits,)* })
+            }
+        }
+
+        impl #impl_generics #builder_name #ty_generics #where_clause {
+            #(#setters)*
+
+            #[doc = #build_doc]
+            #vis fn build(self) -> #name #ty_generics {
+                self.0
+            }
+        }
+    })
+}
+
+/// Generate `<Name>::reset` and the matching `Reset` impl if the struct has `#[metrics(reset)]`.
+///
+/// `default` fields go back to `Default::default()`, `ignore` and `no_reset` fields are left
+/// as-is, and every other field is reset through its own `Reset` impl.
+fn generate_reset(
+    name: &Ident,
+    vis: &Visibility,
+    generics: &Generics,
+    fields: &[MetricsField],
+    root_attrs: &RootAttributes,
+) -> Ts2 {
+    if root_attrs.reset.is_none() {
+        return quote! {};
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    let resets = fields.iter().filter_map(|f| {
+        if f.attrs.no_reset || matches!(f.attrs.kind, MetricsFieldKind::Ignore(_)) {
+            return None;
+        }
+        let ident = &f.ident;
+        let cfg_attrs = f.cfg_attrs();
+        let reset = if f.attrs.default.is_some() {
+            quote_spanned! {f.span=> self.#ident = ::std::default::Default::default(); }
+        } else {
+            quote_spanned! {f.span=> ::metrique::Reset::reset(&mut self.#ident); }
+        };
+        Some(quote! { #(#cfg_attrs)* #reset })
+    });
+
+    let reset_doc = format!(
+        "Reset the fields of this [`{name}`] to their fresh state, e.g. to reuse it for another unit of work"
+    );
+    quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            #[doc = #reset_doc]
+            #vis fn reset(&mut self) {
+                #(#resets)*
+            }
+        }
+
+        impl #impl_generics ::metrique::Reset for #name #ty_generics #where_clause {
+            fn reset(&mut self) {
+                #name::reset(self)
+            }
+        }
+    }
+}
+
+/// Generate `<Name>::FIELD_NAMES`, the names of the metrics the struct writes.
+///
+/// The constant is only usable if the entry implements `InflectableFieldNames`, which it doesn't
+/// if it has fields whose names are only known at runtime. The `for<'_>` bound defers that check
+/// to the uses of the constant, rather than failing the whole struct.
+fn generate_field_names_const(
+    name: &Ident,
+    entry_name: &Ident,
+    vis: &Visibility,
+    generics: &Generics,
+    root_attrs: &RootAttributes,
+) -> Ts2 {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    if root_attrs.mode == MetricMode::Value {
+        return quote! {
+            impl #impl_generics #name #ty_generics #where_clause {
+                /// The names of the metrics this struct writes. Values have no names of their
+                /// own, so this is always empty.
+                #vis const FIELD_NAMES: &'static [&'static str] = &[];
+            }
+        };
+    }
+
+    let predicates = where_clause
+        .map(|w| w.predicates.iter())
+        .into_iter()
+        .flatten();
+    quote! {
+        impl #impl_generics #name #ty_generics
+        where
+            #(#predicates,)*
+            for<'__metrique> #entry_name #ty_generics: ::metrique::field_names::InflectableFieldNames,
+        {
+            /// The names of the metrics this struct writes, in the order they are written, after
+            /// `rename_all`, `prefix` and `name` are applied. Flattened fields contribute the names
+            /// of their own `FIELD_NAMES`, inflected and prefixed the same way.
+            ///
+            /// This is not available if the struct has `flatten_entry` or `index` fields, or
+            /// flattens a type that doesn't have `FIELD_NAMES`, since their names are only known at
+            /// runtime.
+            #vis const FIELD_NAMES: &'static [&'static str] =
+                <#entry_name #ty_generics as ::metrique::field_names::InflectableFieldNames>::FIELD_NAMES;
+        }
+    }
+}
+
+fn wrap_fields_into_struct_decl(
+    has_named_fields: bool,
+    generics: &Generics,
+    fields: impl Iterator<Item = Ts2>,
+) -> Ts2 {
+    let where_clause = &generics.where_clause;
     if has_named_fields {
-        quote! { { #(#fields,)* } }
+        quote! { #where_clause { #(#fields,)* } }
     } else {
-        quote! { ( #(#fields,)* ); }
+        quote! { ( #(#fields,)* ) #where_clause; }
     }
 }
 
@@ -143,10 +382,24 @@ fn generate_entry_struct(
     let has_named_fields = fields.iter().any(|f| f.name.is_some());
     let config = root_attrs.configuration_fields();
 
+    let marker = entry_marker_type(generics).map(|marker| {
+        if has_named_fields {
+            quote! { #[doc(hidden)] __metrique_marker: #marker }
+        } else {
+            quote! { #[doc(hidden)] #marker }
+        }
+    });
     let fields = fields.iter().flat_map(|f| f.entry_field(has_named_fields));
-    let body = wrap_fields_into_struct_decl(has_named_fields, config.into_iter().chain(fields));
+    let body = wrap_fields_into_struct_decl(
+        has_named_fields,
+        generics,
+        config.into_iter().chain(fields).chain(marker),
+    );
 
-    let allowed_derives = crate::derive_utils::extract_allowed_derives(base_attrs);
+    let mut allowed_derives = crate::derive_utils::extract_allowed_derives(base_attrs);
+    if root_attrs.derive_eq {
+        allowed_derives.push(crate::derive_utils::eq_derives());
+    }
 
     Ok(quote!(
         #[doc(hidden)]
@@ -156,28 +409,101 @@ fn generate_entry_struct(
     ))
 }
 
+/// The type of the marker field the entry struct gets if the base struct has type parameters.
+///
+/// Type and lifetime parameters are often only used by fields that are not part of the entry,
+/// like the `PhantomData` of a type state, so the entry marks them as used itself.
+fn entry_marker_type(generics: &Generics) -> Option<Ts2> {
+    let lifetimes = generics.lifetimes().map(|param| {
+        let lifetime = &param.lifetime;
+        quote! { &#lifetime () }
+    });
+    let params = generics.type_params().map(|param| {
+        let ident = &param.ident;
+        quote! { #ident }
+    });
+    let params: Vec<_> = lifetimes.chain(params).collect();
+    if params.is_empty() {
+        return None;
+    }
+    Some(quote! { ::std::marker::PhantomData<fn() -> (#(#params,)*)> })
+}
+
 fn generate_close_value_impls_for_struct(
     metrics_struct: &Ident,
     entry: &Ident,
+    vis: &Visibility,
     generics: &Generics,
     fields: &[MetricsField],
     root_attrs: &RootAttributes,
 ) -> Ts2 {
-    let fields = fields
+    let has_named_fields = fields.iter().any(|f| f.name.is_some());
+    let fields: Vec<_> = fields
         .iter()
         .filter(|f| !matches!(f.attrs.kind, MetricsFieldKind::Ignore(_)))
-        .map(|f| f.close_value(root_attrs.ownership_kind()));
+        .map(|f| f.close_value(root_attrs.ownership_kind()))
+        .collect();
     let config: Vec<Ts2> = root_attrs.create_configuration();
+    let marker = entry_marker_type(generics).map(|_| {
+        let ident = if has_named_fields {
+            quote! { __metrique_marker }
+        } else {
+            let index = syn::Index::from(fields.len());
+            quote! { #index }
+        };
+        quote! { #ident: ::std::marker::PhantomData }
+    });
 
     let impl_body = quote! {
         #[allow(deprecated)]
         #entry {
             #(#config,)*
             #(#fields,)*
+            #marker
         }
     };
 
-    crate::generate_close_value_impls(root_attrs, metrics_struct, entry, generics, impl_body)
+    let close_value_impls =
+        crate::generate_close_value_impls(root_attrs, metrics_struct, entry, generics, impl_body);
+    let merge_into = generate_merge_into(metrics_struct, entry, vis, generics, root_attrs);
+    quote! {
+        #close_value_impls
+        #merge_into
+    }
+}
+
+/// Generate `merge_into` for subfields, which closes the subfield and merges it into an open
+/// entry, see `metrique::Merged`.
+fn generate_merge_into(
+    metrics_struct: &Ident,
+    entry: &Ident,
+    vis: &Visibility,
+    generics: &Generics,
+    root_attrs: &RootAttributes,
+) -> Ts2 {
+    if !matches!(
+        root_attrs.mode,
+        MetricMode::Subfield | MetricMode::SubfieldOwned
+    ) {
+        return quote! {};
+    }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    quote! {
+        impl #impl_generics #metrics_struct #ty_generics #where_clause {
+            /// Close this struct, and write its metrics alongside those of `other` when `other`
+            /// is flushed.
+            ///
+            /// This fails to compile if this struct writes any name that `other`, or another
+            /// entry merged into it, also writes. See [`Merged`](::metrique::Merged) for details.
+            #vis fn merge_into<__G>(self, other: __G) -> ::metrique::Merged<__G, #entry #ty_generics>
+            where
+                __G: ::metrique::MergeTarget<#entry #ty_generics>,
+            {
+                ::metrique::Merged::new(other, ::metrique::CloseValue::close(self))
+            }
+        }
+    }
 }
 
 pub(crate) fn clean_base_struct(
@@ -202,9 +528,10 @@ pub(crate) fn clean_base_struct(
         }
     });
 
+    let where_clause = &generics.where_clause;
     let expanded = quote! {
         #(#filtered_attrs)*
-        #vis struct #struct_name #generics {
+        #vis struct #struct_name #generics #where_clause {
             #(#clean_fields),*
         }
     };
@@ -233,11 +560,12 @@ pub(crate) fn clean_base_unnamed_struct(
         }
     });
 
+    let where_clause = &generics.where_clause;
     let expanded = quote! {
         #(#filtered_attrs)*
         #vis struct #struct_name #generics (
             #(#clean_fields),*
-        );
+        ) #where_clause;
     };
 
     expanded
@@ -1,4 +1,6 @@
-use crate::{MetricsField, MetricsFieldKind, NameStyle, RootAttributes, enums::MetricsVariant};
+use crate::{
+    FieldFormat, MetricsField, MetricsFieldKind, NameStyle, RootAttributes, enums::MetricsVariant,
+};
 
 use proc_macro2::{Span, TokenStream as Ts2};
 use quote::{quote, quote_spanned};
@@ -54,6 +56,7 @@ pub fn validate_value_impl_for_struct(
             unit: _,
             sample_group,
             name,
+            alias,
             format: _,
         } = &field.attrs.kind
         {
@@ -69,6 +72,12 @@ pub fn validate_value_impl_for_struct(
                     "`name` does not make sense with #[metrics(value)]",
                 ));
             }
+            if alias.is_some() {
+                return Err(syn::Error::new(
+                    field.span,
+                    "`alias` does not make sense with #[metrics(value)]",
+                ));
+            }
         }
     }
     if root_attrs.sample_group && non_ignore_fields.is_empty() {
@@ -99,11 +108,16 @@ pub fn validate_value_impl_for_struct(
     Ok(())
 }
 
-pub(crate) fn format_value(format: &Option<syn::Path>, span: Span, field: Ts2) -> Ts2 {
-    if let Some(format) = format {
-        quote_spanned! { span=> &::metrique::format::FormattedValue::<_, #format, _>::new(#field)}
-    } else {
-        field
+pub(crate) fn format_value(format: &Option<FieldFormat>, span: Span, field: Ts2) -> Ts2 {
+    match format {
+        Some(FieldFormat::Type(format)) => {
+            quote_spanned! { span=> &::metrique::format::FormattedValue::<_, #format, _>::new(#field)}
+        }
+        // the formatter is stored next to the value, see `MetricsField::entry_field`
+        Some(FieldFormat::Value { .. }) => {
+            quote_spanned! { span=> &::metrique::format::ConfiguredFormattedValue::<_, _, _>::new(#field)}
+        }
+        None => field,
     }
 }
 
@@ -128,6 +142,7 @@ pub(crate) fn generate_value_impl_for_struct(
                 unit: _,
                 sample_group: _,
                 name: _,
+                alias: _,
                 format,
             } => {
                 let ident = &field.ident;
@@ -139,11 +154,17 @@ pub(crate) fn generate_value_impl_for_struct(
                 let sample_group_impl = if root_attrs.sample_group {
                     // SampleGroup impl is only valid if there is a field
                     let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+                    let access = match format {
+                        Some(FieldFormat::Value { .. }) => {
+                            quote_spanned! {field.span=> ::metrique::format::WithFormatter::value(&self.#ident) }
+                        }
+                        _ => quote_spanned! {field.span=> &self.#ident },
+                    };
                     quote_spanned! {field.span=>
                         impl #impl_generics ::metrique::writer::core::SampleGroup for #value_name #ty_generics #where_clause {
                             fn as_sample_group(&self) -> ::std::borrow::Cow<'static, str> {
                                 #[allow(deprecated)] {
-                                    ::metrique::writer::core::SampleGroup::as_sample_group(&self.#ident)
+                                    ::metrique::writer::core::SampleGroup::as_sample_group(#access)
                                 }
                             }
                         }
@@ -164,6 +164,11 @@ impl<V: MetricsRsVersion + ?Sized> MetricRecorder<V> {
     pub fn readout(&self) -> MetricAccumulatorEntry<V> {
         self.0.readout()
     }
+
+    #[cfg_attr(not(feature = "metrics-rs-024"), allow(unused))]
+    pub(crate) fn registry(&self) -> &V::AtomicStorageWithHistogramRegistry {
+        &self.0.registry
+    }
 }
 
 impl<V: MetricsRsVersion + ?Sized> Default for MetricRecorder<V> {
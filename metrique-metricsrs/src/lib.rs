@@ -11,6 +11,8 @@ mod generic;
 pub mod lambda_reporter;
 pub mod metrics_histogram;
 mod reporter;
+#[cfg(feature = "metrics-rs-024")]
+pub mod snapshot;
 mod unit;
 
 pub use accumulator::{MetricAccumulatorEntry, MetricRecorder, SharedRecorder};
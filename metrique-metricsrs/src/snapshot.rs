@@ -0,0 +1,225 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Fields that read the value of a `metrics`-rs counter or gauge when a metric entry is closed.
+//!
+//! This bridges values recorded via the `metrics` facade into `#[metrics]` unit-of-work entries.
+//! Obtain a [`CounterSnapshot`] or [`GaugeSnapshot`] from the [`MetricRecorder`] that the
+//! facade is recording into. The snapshot shares storage with every handle the recorder
+//! returns for the same key (e.g. via `metrics::counter!`), and closes to the value at the
+//! time the entry is closed.
+//!
+//! ```
+//! # use metrics_024 as metrics;
+//! use metrique::unit_of_work::metrics;
+//! use metrique_metricsrs::MetricRecorder;
+//! use metrique_metricsrs::snapshot::CounterSnapshot;
+//!
+//! #[metrics]
+//! struct RequestMetrics {
+//!     cache_evictions: CounterSnapshot,
+//! }
+//!
+//! let recorder = MetricRecorder::<dyn metrics::Recorder>::new();
+//! let metrics = RequestMetrics {
+//!     cache_evictions: recorder.counter_snapshot("cache_evictions"),
+//! };
+//!
+//! metrics::with_local_recorder(&recorder, || {
+//!     metrics::counter!("cache_evictions").increment(2);
+//! });
+//!
+//! // when `metrics` is closed, `cache_evictions` is written as 2
+//! assert_eq!(metrics.cache_evictions.value(), 2);
+//! ```
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+use metrics_024::{Counter, Gauge, Key};
+use metrique_core::CloseValue;
+
+use crate::MetricRecorder;
+
+/// A `metrics`-rs counter registered on a [`MetricRecorder`] that closes to its current value.
+///
+/// Note that [`MetricRecorder::readout`] resets counters, so the closed value only includes
+/// increments made since the last readout.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct CounterSnapshot {
+    handle: Counter,
+    value: Arc<AtomicU64>,
+}
+
+impl CounterSnapshot {
+    /// The `metrics` handle for this counter, which can be used to increment it.
+    pub fn handle(&self) -> &Counter {
+        &self.handle
+    }
+
+    /// The current value of the counter
+    pub fn value(&self) -> u64 {
+        self.value.load(Ordering::Relaxed)
+    }
+}
+
+impl std::fmt::Debug for CounterSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CounterSnapshot")
+            .field(&self.value())
+            .finish()
+    }
+}
+
+impl CloseValue for CounterSnapshot {
+    type Closed = u64;
+
+    fn close(self) -> Self::Closed {
+        self.value()
+    }
+}
+
+impl CloseValue for &CounterSnapshot {
+    type Closed = u64;
+
+    fn close(self) -> Self::Closed {
+        self.value()
+    }
+}
+
+/// A `metrics`-rs gauge registered on a [`MetricRecorder`] that closes to its current value.
+///
+/// See the [module documentation](self) for an example.
+#[derive(Clone)]
+pub struct GaugeSnapshot {
+    handle: Gauge,
+    value: Arc<AtomicU64>,
+}
+
+impl GaugeSnapshot {
+    /// The `metrics` handle for this gauge, which can be used to update it.
+    pub fn handle(&self) -> &Gauge {
+        &self.handle
+    }
+
+    /// The current value of the gauge
+    pub fn value(&self) -> f64 {
+        f64::from_bits(self.value.load(Ordering::Relaxed))
+    }
+}
+
+impl std::fmt::Debug for GaugeSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("GaugeSnapshot").field(&self.value()).finish()
+    }
+}
+
+impl CloseValue for GaugeSnapshot {
+    type Closed = f64;
+
+    fn close(self) -> Self::Closed {
+        self.value()
+    }
+}
+
+impl CloseValue for &GaugeSnapshot {
+    type Closed = f64;
+
+    fn close(self) -> Self::Closed {
+        self.value()
+    }
+}
+
+impl MetricRecorder<dyn metrics_024::Recorder> {
+    /// Register (or look up) the counter `key` on this recorder, returning a field that
+    /// closes to its current value.
+    pub fn counter_snapshot(&self, key: impl Into<Key>) -> CounterSnapshot {
+        let value = self
+            .registry()
+            .get_or_create_counter(&key.into(), Clone::clone);
+        CounterSnapshot {
+            handle: Counter::from_arc(value.clone()),
+            value,
+        }
+    }
+
+    /// Register (or look up) the gauge `key` on this recorder, returning a field that
+    /// closes to its current value.
+    pub fn gauge_snapshot(&self, key: impl Into<Key>) -> GaugeSnapshot {
+        let value = self
+            .registry()
+            .get_or_create_gauge(&key.into(), Clone::clone);
+        GaugeSnapshot {
+            handle: Gauge::from_arc(value.clone()),
+            value,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics_024::{counter, gauge, with_local_recorder};
+    use metrique::unit_of_work::metrics;
+    use metrique_writer::test_util::test_metric;
+
+    use super::{CounterSnapshot, GaugeSnapshot};
+    use crate::MetricRecorder;
+
+    #[metrics(rename_all = "PascalCase")]
+    struct RequestMetrics {
+        operation: &'static str,
+        cache_evictions: CounterSnapshot,
+        queue_depth: GaugeSnapshot,
+    }
+
+    #[test]
+    fn facade_values_are_read_at_close() {
+        let recorder = MetricRecorder::<dyn metrics_024::Recorder>::new();
+        let metrics = RequestMetrics {
+            operation: "Foo",
+            cache_evictions: recorder.counter_snapshot("cache_evictions"),
+            queue_depth: recorder.gauge_snapshot("queue_depth"),
+        };
+
+        with_local_recorder(&recorder, || {
+            counter!("cache_evictions").increment(2);
+            counter!("cache_evictions").increment(3);
+            counter!("unrelated").increment(100);
+            gauge!("queue_depth").set(7.5);
+        });
+        metrics.cache_evictions.handle().increment(1);
+
+        let entry = test_metric(metrics);
+        assert_eq!(entry.values["Operation"], "Foo");
+        assert_eq!(entry.metrics["CacheEvictions"].as_u64(), 6);
+        assert_eq!(entry.metrics["QueueDepth"].as_f64(), 7.5);
+    }
+
+    #[test]
+    fn counter_reflects_readout_reset() {
+        let recorder = MetricRecorder::<dyn metrics_024::Recorder>::new();
+        let snapshot = recorder.counter_snapshot("requests");
+        snapshot.handle().increment(4);
+        assert_eq!(snapshot.value(), 4);
+
+        assert_eq!(recorder.readout().counter_value("requests"), Some(4));
+        assert_eq!(snapshot.value(), 0);
+
+        with_local_recorder(&recorder, || counter!("requests").increment(1));
+        assert_eq!(snapshot.value(), 1);
+    }
+
+    #[test]
+    fn snapshots_of_the_same_key_share_storage() {
+        let recorder = MetricRecorder::<dyn metrics_024::Recorder>::new();
+        let a = recorder.gauge_snapshot("depth");
+        let b = recorder.gauge_snapshot("depth");
+        a.handle().set(3.0);
+        assert_eq!(b.value(), 3.0);
+        assert_eq!(format!("{b:?}"), "GaugeSnapshot(3.0)");
+    }
+}
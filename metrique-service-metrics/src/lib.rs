@@ -55,6 +55,9 @@ global_entry_sink! {
     ///     )
     /// }
     ///
+    /// # // write the logs to a temporary directory rather than the source tree
+    /// # let dir = tempfile::tempdir().unwrap();
+    /// # let initialize_metrics = |_: PathBuf| initialize_metrics(dir.path().to_path_buf());
     /// let _join = initialize_metrics("my/metrics/dir".into());
     /// let mut metrics = RequestMetrics::init();
     /// metrics.number_of_ducks = 5;
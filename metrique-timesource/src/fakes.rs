@@ -2,11 +2,14 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::{
+    future::Future,
+    pin::Pin,
     sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
     time::{Duration, Instant, SystemTime},
 };
 
-use crate::Time;
+use crate::{AsyncTime, BoxFuture, Time};
 
 /// Simple static timesource that will always return the same time
 #[derive(Debug)]
@@ -143,3 +146,268 @@ impl Time for ManuallyAdvancedTimeSource {
         self.0.lock().unwrap().now_instant
     }
 }
+
+/// Timesource that only moves when it is told to, without needing tokio
+///
+/// Unlike [`ManuallyAdvancedTimeSource`], [`Self::advance`] moves both the [`SystemTime`] and the
+/// [`Instant`] forward together, so [`Instant::elapsed`] and system time differences always agree.
+/// Clones share the same clock.
+///
+/// It implements [`AsyncTime`]: sleeps resolve once the clock is advanced past their deadline.
+///
+/// [`Instant::elapsed`]: crate::Instant::elapsed
+#[derive(Debug, Clone)]
+pub struct ManualTimeSource(Arc<Mutex<ManualClock>>);
+
+#[derive(Debug)]
+struct ManualClock {
+    now: SystemTime,
+    start_instant: Instant,
+    // total of all advances, added to `start_instant`
+    elapsed: Duration,
+    // pending sleeps, as (id, deadline in terms of `elapsed`, waker)
+    sleepers: Vec<(u64, Duration, Waker)>,
+    next_sleeper_id: u64,
+}
+
+impl ManualTimeSource {
+    /// Create a new ManualTimeSource that is started with the given time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use metrique_timesource::{TimeSource, fakes::ManualTimeSource};
+    /// use std::time::UNIX_EPOCH;
+    ///
+    /// let clock = ManualTimeSource::at_time(UNIX_EPOCH);
+    /// let ts = TimeSource::custom(clock);
+    /// assert_eq!(ts.system_time(), UNIX_EPOCH);
+    /// ```
+    pub fn at_time(time: impl Into<SystemTime>) -> Self {
+        Self(Arc::new(Mutex::new(ManualClock {
+            now: time.into(),
+            start_instant: Instant::now(),
+            elapsed: Duration::ZERO,
+            sleepers: vec![],
+            next_sleeper_id: 0,
+        })))
+    }
+
+    /// Move the clock forward by `duration`. This advances both the system time and the instant,
+    /// and wakes the sleeps whose deadline has been reached.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use metrique_timesource::{TimeSource, fakes::ManualTimeSource};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let clock = ManualTimeSource::at_time(UNIX_EPOCH);
+    /// let ts = TimeSource::custom(clock.clone());
+    /// let start = ts.instant();
+    ///
+    /// clock.advance(Duration::from_secs(5));
+    /// assert_eq!(start.elapsed(), Duration::from_secs(5));
+    /// assert_eq!(ts.system_time(), UNIX_EPOCH + Duration::from_secs(5));
+    /// ```
+    pub fn advance(&self, duration: Duration) {
+        let mut woken = vec![];
+        {
+            let mut clock = self.0.lock().unwrap();
+            clock.now += duration;
+            clock.elapsed += duration;
+            let elapsed = clock.elapsed;
+            clock.sleepers.retain(|(_, deadline, waker)| {
+                if *deadline <= elapsed {
+                    woken.push(waker.clone());
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+        // wake outside the lock, since wakers may poll synchronously
+        for waker in woken {
+            waker.wake();
+        }
+    }
+
+    /// Set the system time to `time`, which may be earlier than the current time.
+    ///
+    /// The instant is not affected, so [`Instant`]s from this time source stay monotonic.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use metrique_timesource::{TimeSource, fakes::ManualTimeSource};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let clock = ManualTimeSource::at_time(UNIX_EPOCH + Duration::from_secs(100));
+    /// let ts = TimeSource::custom(clock.clone());
+    ///
+    /// clock.set(UNIX_EPOCH);
+    /// assert_eq!(ts.system_time(), UNIX_EPOCH);
+    /// ```
+    pub fn set(&self, time: impl Into<SystemTime>) {
+        self.0.lock().unwrap().now = time.into();
+    }
+}
+
+impl Time for ManualTimeSource {
+    fn now(&self) -> SystemTime {
+        self.0.lock().unwrap().now
+    }
+
+    fn instant(&self) -> Instant {
+        let clock = self.0.lock().unwrap();
+        clock.start_instant + clock.elapsed
+    }
+
+    fn as_async_time(&self) -> Option<&dyn AsyncTime> {
+        Some(self)
+    }
+}
+
+impl AsyncTime for ManualTimeSource {
+    /// Sleep until the clock has been [advanced](Self::advance) by `duration`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() {
+    /// use metrique_timesource::{AsyncTime, fakes::ManualTimeSource};
+    /// use std::time::{Duration, UNIX_EPOCH};
+    ///
+    /// let clock = ManualTimeSource::at_time(UNIX_EPOCH);
+    /// let sleep = tokio::spawn(clock.sleep(Duration::from_secs(60)));
+    /// clock.advance(Duration::from_secs(60));
+    /// sleep.await.unwrap();
+    /// # }
+    /// ```
+    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+        let mut clock = self.0.lock().unwrap();
+        let id = clock.next_sleeper_id;
+        clock.next_sleeper_id += 1;
+        Box::pin(ManualSleep {
+            clock: self.0.clone(),
+            id,
+            deadline: clock.elapsed + duration,
+        })
+    }
+}
+
+/// Future returned by [`ManualTimeSource::sleep`]
+struct ManualSleep {
+    clock: Arc<Mutex<ManualClock>>,
+    id: u64,
+    deadline: Duration,
+}
+
+impl Future for ManualSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut clock = self.clock.lock().unwrap();
+        if clock.elapsed >= self.deadline {
+            return Poll::Ready(());
+        }
+        match clock.sleepers.iter_mut().find(|(id, ..)| *id == self.id) {
+            Some((_, _, waker)) => waker.clone_from(cx.waker()),
+            None => clock
+                .sleepers
+                .push((self.id, self.deadline, cx.waker().clone())),
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for ManualSleep {
+    fn drop(&mut self) {
+        if let Ok(mut clock) = self.clock.lock() {
+            clock.sleepers.retain(|(id, ..)| *id != self.id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        task::{Context, Poll, Wake, Waker},
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use crate::{AsyncTime, TimeSource, fakes::ManualTimeSource};
+
+    /// Counts how many times it was woken
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn manual_time_source_clones_share_clock() {
+        let clock = ManualTimeSource::at_time(UNIX_EPOCH);
+        let ts = TimeSource::custom(clock.clone());
+        let start = ts.instant();
+        let start_time = ts.system_time();
+
+        clock.clone().advance(Duration::from_millis(1500));
+        assert_eq!(start.elapsed(), Duration::from_millis(1500));
+        assert_eq!(
+            ts.system_time().duration_since(start_time).unwrap(),
+            Duration::from_millis(1500)
+        );
+
+        clock.set(UNIX_EPOCH + Duration::from_secs(60));
+        assert_eq!(ts.system_time(), UNIX_EPOCH + Duration::from_secs(60));
+        // setting the system time doesn't move instants
+        assert_eq!(start.elapsed(), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn manual_time_source_wakes_sleepers_on_advance() {
+        let clock = ManualTimeSource::at_time(UNIX_EPOCH);
+        let wakes = Arc::new(CountingWaker::default());
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut short = clock.sleep(Duration::from_secs(5));
+        let mut long = clock.sleep(Duration::from_secs(10));
+        assert_eq!(short.as_mut().poll(&mut cx), Poll::Pending);
+        assert_eq!(long.as_mut().poll(&mut cx), Poll::Pending);
+        // polling again doesn't register the sleep twice
+        assert_eq!(short.as_mut().poll(&mut cx), Poll::Pending);
+
+        clock.advance(Duration::from_secs(4));
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+
+        // setting the system time doesn't affect sleeps
+        clock.set(UNIX_EPOCH + Duration::from_secs(3600));
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 0);
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+        assert_eq!(short.as_mut().poll(&mut cx), Poll::Ready(()));
+        assert_eq!(long.as_mut().poll(&mut cx), Poll::Pending);
+
+        // dropped sleeps are never woken
+        drop(long);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(wakes.0.load(Ordering::SeqCst), 1);
+
+        // sleeps that are already due resolve immediately
+        assert_eq!(
+            clock.sleep(Duration::ZERO).as_mut().poll(&mut cx),
+            Poll::Ready(())
+        );
+    }
+}
@@ -29,6 +29,31 @@ pub trait Time: Send + Sync + Debug {
 
     /// Get the current instant
     fn instant(&self) -> StdInstant;
+
+    /// Return this time source as an [`AsyncTime`], if it supports sleeping
+    ///
+    /// This is used by [`TimeSource::sleep`]. Time sources that implement [`AsyncTime`] should
+    /// override it to return `Some(self)`.
+    #[cfg(feature = "custom-timesource")]
+    fn as_async_time(&self) -> Option<&dyn AsyncTime> {
+        None
+    }
+}
+
+/// A boxed, `Send` future, as returned by [`AsyncTime::sleep`]
+#[cfg(feature = "custom-timesource")]
+pub type BoxFuture<T> = std::pin::Pin<Box<dyn std::future::Future<Output = T> + Send + 'static>>;
+
+/// Trait for custom time sources that can also sleep
+///
+/// This allows code that schedules work periodically to be controlled by the time source,
+/// e.g. a [`fakes::ManualTimeSource`] wakes its sleepers when it is advanced past their deadline.
+///
+/// Implementors should also override [`Time::as_async_time`] to return `Some(self)`.
+#[cfg(feature = "custom-timesource")]
+pub trait AsyncTime: Time {
+    /// Return a future that resolves once `duration` has elapsed according to this time source
+    fn sleep(&self, duration: Duration) -> BoxFuture<()>;
 }
 
 /// Tokio-specific time source implementations
@@ -39,11 +64,11 @@ pub trait Time: Send + Sync + Debug {
 /// This requires that the `tokio` feature be enabled.
 #[cfg(feature = "tokio")]
 pub mod tokio {
-    use std::time::SystemTime;
+    use std::time::{Duration, SystemTime};
 
     use tokio::time::Instant as TokioInstant;
 
-    use crate::{Time, TimeSource};
+    use crate::{AsyncTime, BoxFuture, Time, TimeSource};
     use std::time::Instant as StdInstant;
 
     impl TimeSource {
@@ -157,6 +182,50 @@ pub mod tokio {
         fn instant(&self) -> StdInstant {
             TokioInstant::now().into_std()
         }
+
+        fn as_async_time(&self) -> Option<&dyn AsyncTime> {
+            Some(self)
+        }
+    }
+
+    impl AsyncTime for TokioTime {
+        /// Sleep using [`tokio::time::sleep`], which respects tokio's time pause/advance
+        fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+            Box::pin(::tokio::time::sleep(duration))
+        }
+    }
+
+    impl TimeSource {
+        /// Sleep for `duration` according to this time source
+        ///
+        /// Custom time sources that implement [`AsyncTime`] control when the returned future
+        /// resolves. Other time sources use [`tokio::time::sleep`].
+        ///
+        /// This requires that the `tokio` feature be enabled.
+        ///
+        /// # Examples
+        ///
+        /// ```
+        /// # #[tokio::main(flavor = "current_thread")]
+        /// # async fn main() {
+        /// use std::time::{Duration, UNIX_EPOCH};
+        /// use metrique_timesource::TimeSource;
+        ///
+        /// tokio::time::pause();
+        /// let ts = TimeSource::tokio(UNIX_EPOCH);
+        /// ts.sleep(Duration::from_secs(60)).await;
+        /// assert!(ts.system_time() >= UNIX_EPOCH + Duration::from_secs(60));
+        /// # }
+        /// ```
+        pub fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+            match self {
+                TimeSource::Custom(ts) => match ts.as_async_time() {
+                    Some(ts) => ts.sleep(duration),
+                    None => Box::pin(::tokio::time::sleep(duration)),
+                },
+                TimeSource::System => Box::pin(::tokio::time::sleep(duration)),
+            }
+        }
     }
 
     use std::collections::HashMap;
@@ -272,6 +341,28 @@ pub mod tokio {
             assert_eq!(start.elapsed(), Duration::from_secs(1))
         }
 
+        #[tokio::test]
+        async fn sleep_respects_pause() {
+            tokio::time::pause();
+            let ts = TimeSource::tokio(UNIX_EPOCH);
+            let start = ts.instant();
+            ts.sleep(Duration::from_secs(30)).await;
+            // tokio rounds sleeps up to the next millisecond
+            assert!(start.elapsed() >= Duration::from_secs(30));
+        }
+
+        #[tokio::test]
+        async fn sleep_uses_async_custom_time_source() {
+            use crate::fakes::ManualTimeSource;
+            let clock = ManualTimeSource::at_time(UNIX_EPOCH);
+            let ts = TimeSource::custom(clock.clone());
+            let sleep = tokio::spawn(ts.sleep(Duration::from_secs(3600)));
+            tokio::task::yield_now().await;
+            clock.advance(Duration::from_secs(3600));
+            // would take an hour with the real clock
+            sleep.await.unwrap();
+        }
+
         #[tokio::test]
         async fn with_tokio_ts() {
             struct MyMetric {
@@ -526,6 +617,69 @@ pub fn set_time_source(time_source: TimeSource) -> ThreadLocalTimeSourceGuard {
     }
 }
 
+// Process-wide time source override
+#[cfg(feature = "custom-timesource")]
+static GLOBAL_TIME_SOURCE: std::sync::RwLock<Option<TimeSource>> = std::sync::RwLock::new(None);
+
+// Whether `GLOBAL_TIME_SOURCE` is set, so that processes that never set it don't take the lock
+// on every call to `get_time_source`. Only written while holding the write lock.
+#[cfg(feature = "custom-timesource")]
+static GLOBAL_TIME_SOURCE_SET: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Guard for a process-wide time source override
+///
+/// When dropped, the previous process-wide override (if any) is restored.
+#[cfg(feature = "custom-timesource")]
+#[must_use = "if unused the global time source will be immediately removed"]
+pub struct GlobalTimeSourceGuard {
+    previous: Option<TimeSource>,
+}
+
+#[cfg(feature = "custom-timesource")]
+impl Drop for GlobalTimeSourceGuard {
+    fn drop(&mut self) {
+        let mut global = GLOBAL_TIME_SOURCE
+            .write()
+            .unwrap_or_else(|e| e.into_inner());
+        *global = self.previous.take();
+        GLOBAL_TIME_SOURCE_SET.store(global.is_some(), std::sync::atomic::Ordering::Release);
+    }
+}
+
+#[cfg(feature = "custom-timesource")]
+/// Set a process-wide time source override and return a guard
+/// When the guard is dropped, the previous override is restored
+///
+/// Unlike [`set_time_source`], this override also applies to threads you don't control, such as
+/// the thread of a background queue. It has a lower priority than thread-local and
+/// runtime-wide overrides, see [`get_time_source`].
+///
+/// Since the override affects the whole process, tests that use it should not run
+/// concurrently with other tests that depend on the time source.
+///
+/// # Examples
+/// ```standalone_crate
+/// use metrique_timesource::{TimeSource, fakes::StaticTimeSource, time_source, set_global_time_source};
+/// use std::time::UNIX_EPOCH;
+///
+/// let ts = TimeSource::custom(StaticTimeSource::at_time(UNIX_EPOCH));
+/// let _guard = set_global_time_source(ts);
+///
+/// std::thread::spawn(|| {
+///     assert_eq!(time_source().system_time(), UNIX_EPOCH);
+/// }).join().unwrap();
+/// ```
+pub fn set_global_time_source(time_source: TimeSource) -> GlobalTimeSourceGuard {
+    let mut global = GLOBAL_TIME_SOURCE
+        .write()
+        .unwrap_or_else(|e| e.into_inner());
+    GLOBAL_TIME_SOURCE_SET.store(true, std::sync::atomic::Ordering::Release);
+    GlobalTimeSourceGuard {
+        previous: global.replace(time_source),
+    }
+}
+
 #[cfg(feature = "custom-timesource")]
 /// Run a closure with a thread-local time source override
 pub fn with_time_source<F, R>(time_source: TimeSource, f: F) -> R
@@ -538,9 +692,10 @@ where
 
 /// Get the current time source, following the priority order:
 /// 1. Explicitly provided time source
-/// 2. Thread-local override
-/// 3. Tokio task-local override (if `tokio` feature is enabled)
-/// 4. System default
+/// 2. Thread-local override, see [`set_time_source`]
+/// 3. Tokio runtime-wide override (if `tokio` feature is enabled)
+/// 4. Process-wide override, see [`set_global_time_source`]
+/// 5. System default
 #[inline]
 pub fn get_time_source(ts: Option<TimeSource>) -> TimeSource {
     // 1. Explicitly provided time source
@@ -565,7 +720,19 @@ pub fn get_time_source(ts: Option<TimeSource>) -> TimeSource {
         }
     }
 
-    // 4. System default
+    // 4. Process-wide override
+    #[cfg(feature = "custom-timesource")]
+    if GLOBAL_TIME_SOURCE_SET.load(std::sync::atomic::Ordering::Acquire) {
+        let global = GLOBAL_TIME_SOURCE
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        if let Some(ts) = global {
+            return ts;
+        }
+    }
+
+    // 5. System default
     TimeSource::System
 }
 
@@ -645,7 +812,8 @@ impl Instant {
     ///
     /// # Returns
     ///
-    /// The elapsed time as a Duration
+    /// The elapsed time as a Duration. This is zero if the time source's current instant is
+    /// earlier than this instant, which can happen with custom time sources that go backwards.
     ///
     /// # Examples
     ///
@@ -666,7 +834,7 @@ impl Instant {
         #[cfg(feature = "custom-timesource")]
         let ts = &self.time_source;
 
-        ts.instant().as_std() - self.value
+        ts.instant().as_std().saturating_duration_since(self.value)
     }
 
     /// Convert this Instant to a std::time::Instant
@@ -833,12 +1001,29 @@ impl From<SystemTime> for StdSystemTime {
 #[cfg(test)]
 mod tests {
 
-    use std::time::UNIX_EPOCH;
+    use std::{
+        sync::{Arc, Mutex},
+        time::{Duration, Instant as StdInstant, SystemTime as StdSystemTime, UNIX_EPOCH},
+    };
 
     use crate::{
-        TimeSource, fakes, get_time_source, set_time_source, time_source, with_time_source,
+        Time, TimeSource, fakes, get_time_source, set_time_source, time_source, with_time_source,
     };
 
+    /// Time source whose instant is set directly by the test, and can go backwards
+    #[derive(Debug, Clone)]
+    struct SettableInstant(Arc<Mutex<StdInstant>>);
+
+    impl Time for SettableInstant {
+        fn now(&self) -> StdSystemTime {
+            UNIX_EPOCH
+        }
+
+        fn instant(&self) -> StdInstant {
+            *self.0.lock().unwrap()
+        }
+    }
+
     #[test]
     fn test_default_time_source() {
         let ts = time_source();
@@ -898,4 +1083,18 @@ mod tests {
             _ => panic!("Expected default time source after scope"),
         }
     }
+
+    #[test]
+    fn instant_elapsed_saturates_when_time_goes_backwards() {
+        let start = StdInstant::now() + Duration::from_secs(10);
+        let clock = SettableInstant(Arc::new(Mutex::new(start)));
+        let ts = TimeSource::custom(clock.clone());
+        let instant = ts.instant();
+
+        *clock.0.lock().unwrap() = start - Duration::from_secs(5);
+        assert_eq!(instant.elapsed(), Duration::ZERO);
+
+        *clock.0.lock().unwrap() = start + Duration::from_secs(5);
+        assert_eq!(instant.elapsed(), Duration::from_secs(5));
+    }
 }
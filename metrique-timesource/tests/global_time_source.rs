@@ -0,0 +1,61 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! The global time source is process-wide, so it is tested in its own binary, in a single test.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use metrique_timesource::{
+    TimeSource, fakes::StaticTimeSource, set_global_time_source, set_time_source, time_source,
+};
+
+fn static_time(secs: u64) -> TimeSource {
+    TimeSource::custom(StaticTimeSource::at_time(
+        UNIX_EPOCH + Duration::from_secs(secs),
+    ))
+}
+
+#[test]
+fn global_time_source() {
+    assert!(matches!(time_source(), TimeSource::System));
+
+    let guard = set_global_time_source(static_time(1));
+    // applies to other threads
+    std::thread::spawn(|| {
+        assert_eq!(
+            time_source().system_time(),
+            UNIX_EPOCH + Duration::from_secs(1)
+        )
+    })
+    .join()
+    .unwrap();
+
+    // thread-local overrides take priority
+    {
+        let _thread_local = set_time_source(static_time(2));
+        assert_eq!(
+            time_source().system_time(),
+            UNIX_EPOCH + Duration::from_secs(2)
+        );
+    }
+    assert_eq!(
+        time_source().system_time(),
+        UNIX_EPOCH + Duration::from_secs(1)
+    );
+
+    // nested overrides restore the previous one
+    {
+        let _nested = set_global_time_source(static_time(3));
+        assert_eq!(
+            time_source().system_time(),
+            UNIX_EPOCH + Duration::from_secs(3)
+        );
+    }
+    assert_eq!(
+        time_source().system_time(),
+        UNIX_EPOCH + Duration::from_secs(1)
+    );
+
+    drop(guard);
+    assert!(matches!(time_source(), TimeSource::System));
+}
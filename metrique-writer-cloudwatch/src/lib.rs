@@ -3,6 +3,9 @@
 //! This crate provides [`CwLogsStream`], an [`EntryIoStream`] implementation that serializes
 //! metric entries as EMF JSON and submits them directly to CloudWatch Logs via `PutLogEvents`.
 //!
+//! For environments that can't use the EMF logs path, [`PutMetricDataStream`] instead converts
+//! entries into [`MetricDatum`]s, batched for CloudWatch `PutMetricData` requests.
+//!
 //! # Architecture
 //!
 //! ```text
@@ -80,6 +83,13 @@ use metrique_writer_format_emf::Emf;
 use tokio::sync::{mpsc, oneshot};
 use tracing::{debug, warn};
 
+mod metric_data;
+
+pub use metric_data::{
+    Dimension, DistributionMapping, MAX_DATUMS_PER_REQUEST, MAX_VALUES_PER_DATUM, MetricDatum,
+    PutMetricDataHandler, PutMetricDataStream, StatisticSet,
+};
+
 /// Maximum payload size for a single PutLogEvents request (1 MB).
 const MAX_BATCH_BYTES: usize = 1_048_576;
 /// Maximum size of a single log event message (1 MB).
@@ -0,0 +1,725 @@
+//! CloudWatch `PutMetricData` backend.
+//!
+//! [`PutMetricDataStream`] converts metric entries into [`MetricDatum`]s and hands them, in
+//! batches that fit in a single `PutMetricData` request, to a [`PutMetricDataHandler`] that
+//! sends them to CloudWatch. This is meant for environments that can't publish metrics through
+//! EMF logs.
+//!
+//! The datums mirror the `MetricDatum` shape of the CloudWatch API, without depending on a
+//! particular SDK, so the handler converts them to the request type of the client it uses.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::time::SystemTime;
+
+use bon::bon;
+use metrique_writer_core::{
+    Entry, EntryConfig, EntryIoStream, EntryWriter, IoStreamError, MetricFlags, Observation, Unit,
+    ValidationError, Value, ValueWriter,
+};
+use metrique_writer_format_emf::{StorageResolution, metric_storage_resolution};
+
+/// Maximum number of [`MetricDatum`]s in a single `PutMetricData` request.
+pub const MAX_DATUMS_PER_REQUEST: usize = 1000;
+/// Maximum number of distinct values in the `Values` of a single [`MetricDatum`].
+pub const MAX_VALUES_PER_DATUM: usize = 150;
+
+/// A CloudWatch `MetricDatum`, as sent in a `PutMetricData` request.
+///
+/// A datum holds either a single [`value`](Self::value), a distribution as
+/// [`values`](Self::values) and [`counts`](Self::counts), or a summary of a distribution as
+/// [`statistic_values`](Self::statistic_values).
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct MetricDatum {
+    /// The name of the metric
+    pub metric_name: String,
+    /// The dimensions of the metric, from the dimension set and the per-value dimensions
+    pub dimensions: Vec<Dimension>,
+    /// The timestamp of the entry, if it has one. CloudWatch uses the time it receives the
+    /// request otherwise.
+    pub timestamp: Option<SystemTime>,
+    /// The unit of the metric. [`Unit::name`] is the CloudWatch name of the unit.
+    pub unit: Unit,
+    /// The value of a metric with a single observation
+    pub value: Option<f64>,
+    /// The distinct values of a distribution, at most [`MAX_VALUES_PER_DATUM`]
+    pub values: Vec<f64>,
+    /// The number of times each of [`values`](Self::values) was observed
+    pub counts: Vec<f64>,
+    /// A summary of a distribution, see [`DistributionMapping::StatisticValues`]
+    pub statistic_values: Option<StatisticSet>,
+    /// `Some(1)` for metrics with high storage resolution, `None` for the standard resolution
+    pub storage_resolution: Option<i32>,
+}
+
+/// A CloudWatch `Dimension` of a [`MetricDatum`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dimension {
+    /// The name of the dimension
+    pub name: String,
+    /// The value of the dimension
+    pub value: String,
+}
+
+/// A CloudWatch `StatisticSet`, summarizing a distribution
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StatisticSet {
+    /// The number of observations
+    pub sample_count: f64,
+    /// The sum of the observations
+    pub sum: f64,
+    /// The smallest observation
+    pub minimum: f64,
+    /// The largest observation
+    pub maximum: f64,
+}
+
+/// How [`PutMetricDataStream`] reports metrics with more than one observation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DistributionMapping {
+    /// Report every distinct observation in `Values`, with the number of times it was observed in
+    /// `Counts`. Distributions with more than [`MAX_VALUES_PER_DATUM`] observations are split
+    /// into several datums, which CloudWatch aggregates. This preserves percentiles.
+    #[default]
+    ValuesAndCounts,
+    /// Summarize the distribution as `StatisticValues` (count, sum, minimum and maximum), which
+    /// always takes a single datum but loses percentiles.
+    ///
+    /// A repeated observation (see [`Observation::Repeated`]) only carries its total and number
+    /// of occurrences, so its mean is used as both its minimum and maximum.
+    StatisticValues,
+}
+
+/// Sends batches of [`MetricDatum`]s to CloudWatch.
+///
+/// This is implemented for closures taking the namespace and the datums.
+pub trait PutMetricDataHandler {
+    /// Send a single `PutMetricData` request for `namespace`. `data` is never empty and has at
+    /// most [`MAX_DATUMS_PER_REQUEST`] datums.
+    fn put_metric_data(&mut self, namespace: &str, data: Vec<MetricDatum>) -> io::Result<()>;
+}
+
+impl<F: FnMut(&str, Vec<MetricDatum>) -> io::Result<()>> PutMetricDataHandler for F {
+    fn put_metric_data(&mut self, namespace: &str, data: Vec<MetricDatum>) -> io::Result<()> {
+        self(namespace, data)
+    }
+}
+
+/// An [`EntryIoStream`] that converts entries into CloudWatch [`MetricDatum`]s and sends them
+/// through a [`PutMetricDataHandler`].
+///
+/// Each metric of an entry becomes a datum for every dimension set. String fields are only used
+/// as the values of dimensions, since `PutMetricData` has no properties, and an entry that lacks
+/// a dimension of a dimension set is rejected with a validation error. Metrics flagged with
+/// [`NoMetric`](metrique_writer_format_emf::NoMetric) are skipped, and metrics flagged with
+/// [`HighStorageResolution`](metrique_writer_format_emf::HighStorageResolution) get a storage
+/// resolution of 1 second. Non-finite observations are skipped, since CloudWatch rejects them.
+///
+/// Datums are sent once [`MAX_DATUMS_PER_REQUEST`] of them are pending, and when the stream is
+/// flushed. The datums of a request that the handler fails to send are dropped, so retries are
+/// up to the handler.
+///
+/// # Example
+///
+/// ```
+/// use metrique_writer_cloudwatch::{MetricDatum, PutMetricDataStream};
+/// use metrique_writer_core::{Entry, EntryIoStream, EntryWriter};
+///
+/// struct RequestMetrics {
+///     operation: &'static str,
+///     count: u64,
+/// }
+///
+/// impl Entry for RequestMetrics {
+///     fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+///         writer.value("Operation", self.operation);
+///         writer.value("Count", &self.count);
+///     }
+/// }
+///
+/// let mut stream = PutMetricDataStream::builder()
+///     .namespace("MyApp".to_string())
+///     .default_dimensions(vec![vec!["Operation".to_string()]])
+///     .handler(|namespace: &str, data: Vec<MetricDatum>| {
+///         // convert the datums and call `PutMetricData` with your CloudWatch client
+///         assert_eq!(namespace, "MyApp");
+///         assert_eq!(data[0].metric_name, "Count");
+///         Ok(())
+///     })
+///     .build();
+///
+/// stream.next(&RequestMetrics { operation: "Get", count: 1 }).unwrap();
+/// stream.flush().unwrap();
+/// ```
+pub struct PutMetricDataStream<H> {
+    handler: H,
+    namespace: String,
+    default_dimensions: Vec<Vec<String>>,
+    distributions: DistributionMapping,
+    pending: Vec<MetricDatum>,
+}
+
+impl<H> std::fmt::Debug for PutMetricDataStream<H> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PutMetricDataStream")
+            .field("namespace", &self.namespace)
+            .field("default_dimensions", &self.default_dimensions)
+            .field("distributions", &self.distributions)
+            .field("pending_len", &self.pending.len())
+            .finish()
+    }
+}
+
+#[bon]
+impl<H: PutMetricDataHandler> PutMetricDataStream<H> {
+    /// Create a new [`PutMetricDataStream`].
+    ///
+    /// `default_dimensions` are the dimension sets every metric is reported with, and default
+    /// to a single set without dimensions.
+    #[builder]
+    pub fn new(
+        handler: H,
+        namespace: String,
+        #[builder(default = vec![vec![]])] default_dimensions: Vec<Vec<String>>,
+        #[builder(default)] distributions: DistributionMapping,
+    ) -> Self {
+        Self {
+            handler,
+            namespace,
+            default_dimensions,
+            distributions,
+            pending: Vec::new(),
+        }
+    }
+
+    fn send(&mut self, count: usize) -> io::Result<()> {
+        let data: Vec<_> = self.pending.drain(..count).collect();
+        self.handler.put_metric_data(&self.namespace, data)
+    }
+}
+
+impl<H: PutMetricDataHandler> EntryIoStream for PutMetricDataStream<H> {
+    fn next(&mut self, entry: &impl Entry) -> Result<(), IoStreamError> {
+        let mut collector = EntryCollector::default();
+        entry.write(&mut collector);
+        if let Some(error) = collector.error {
+            return Err(error.into());
+        }
+
+        // resolve every dimension set first, so an invalid entry doesn't write anything
+        let dimension_sets = self
+            .default_dimensions
+            .iter()
+            .map(|set| {
+                set.iter()
+                    .map(|name| match collector.strings.get(name.as_str()) {
+                        Some(value) => Ok(Dimension {
+                            name: name.clone(),
+                            value: value.clone(),
+                        }),
+                        None => Err(ValidationError::invalid(format!(
+                            "entry is missing dimension `{name}`"
+                        ))),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for dimensions in &dimension_sets {
+            for metric in &collector.metrics {
+                metric.push_datums(
+                    dimensions,
+                    collector.timestamp,
+                    self.distributions,
+                    &mut self.pending,
+                );
+            }
+        }
+
+        while self.pending.len() >= MAX_DATUMS_PER_REQUEST {
+            self.send(MAX_DATUMS_PER_REQUEST)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            self.send(self.pending.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// The metrics and string fields of an entry
+#[derive(Default)]
+struct EntryCollector<'a> {
+    timestamp: Option<SystemTime>,
+    strings: HashMap<Cow<'a, str>, String>,
+    metrics: Vec<CollectedMetric>,
+    error: Option<ValidationError>,
+}
+
+struct CollectedMetric {
+    name: String,
+    unit: Unit,
+    dimensions: Vec<Dimension>,
+    storage_resolution: Option<i32>,
+    /// `(value, count)` pairs
+    observations: Vec<(f64, f64)>,
+    /// Whether this is a single observation that isn't repeated, reported as a `Value`
+    single: bool,
+}
+
+impl CollectedMetric {
+    fn push_datums(
+        &self,
+        dimensions: &[Dimension],
+        timestamp: Option<SystemTime>,
+        distributions: DistributionMapping,
+        out: &mut Vec<MetricDatum>,
+    ) {
+        if self.observations.is_empty() {
+            return;
+        }
+        let datum = MetricDatum {
+            metric_name: self.name.clone(),
+            dimensions: dimensions.iter().chain(&self.dimensions).cloned().collect(),
+            timestamp,
+            unit: self.unit,
+            value: None,
+            values: vec![],
+            counts: vec![],
+            statistic_values: None,
+            storage_resolution: self.storage_resolution,
+        };
+        if self.single {
+            out.push(MetricDatum {
+                value: Some(self.observations[0].0),
+                ..datum
+            });
+            return;
+        }
+        match distributions {
+            DistributionMapping::ValuesAndCounts => {
+                for chunk in self.observations.chunks(MAX_VALUES_PER_DATUM) {
+                    out.push(MetricDatum {
+                        values: chunk.iter().map(|(value, _)| *value).collect(),
+                        counts: chunk.iter().map(|(_, count)| *count).collect(),
+                        ..datum.clone()
+                    });
+                }
+            }
+            DistributionMapping::StatisticValues => {
+                let mut set = StatisticSet {
+                    sample_count: 0.0,
+                    sum: 0.0,
+                    minimum: f64::INFINITY,
+                    maximum: f64::NEG_INFINITY,
+                };
+                for &(value, count) in &self.observations {
+                    set.sample_count += count;
+                    set.sum += value * count;
+                    set.minimum = set.minimum.min(value);
+                    set.maximum = set.maximum.max(value);
+                }
+                out.push(MetricDatum {
+                    statistic_values: Some(set),
+                    ..datum
+                });
+            }
+        }
+    }
+}
+
+impl<'a> EntryWriter<'a> for EntryCollector<'a> {
+    fn timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp = Some(timestamp);
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        value.write(CollectorValueWriter {
+            name: name.into(),
+            collector: self,
+        });
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {}
+}
+
+struct CollectorValueWriter<'c, 'a> {
+    name: Cow<'a, str>,
+    collector: &'c mut EntryCollector<'a>,
+}
+
+impl ValueWriter for CollectorValueWriter<'_, '_> {
+    fn string(self, value: &str) {
+        self.collector.strings.insert(self.name, value.to_owned());
+    }
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        unit: Unit,
+        dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        flags: MetricFlags<'_>,
+    ) {
+        let storage_resolution = match metric_storage_resolution(&flags) {
+            None => return,
+            Some(StorageResolution::Second) => Some(1),
+            Some(_) => None,
+        };
+        let mut observations = vec![];
+        let mut single = true;
+        for observation in distribution {
+            let (value, count) = match observation {
+                Observation::Unsigned(value) => (value as f64, 1.0),
+                Observation::Floating(value) => (value, 1.0),
+                Observation::Repeated { total, occurrences } => {
+                    single = false;
+                    if occurrences == 0 {
+                        continue;
+                    }
+                    (total / occurrences as f64, occurrences as f64)
+                }
+                _ => continue,
+            };
+            if value.is_finite() {
+                observations.push((value, count));
+            }
+        }
+        self.collector.metrics.push(CollectedMetric {
+            name: self.name.into_owned(),
+            unit,
+            dimensions: dimensions
+                .into_iter()
+                .map(|(name, value)| Dimension {
+                    name: name.to_owned(),
+                    value: value.to_owned(),
+                })
+                .collect(),
+            storage_resolution,
+            single: single && observations.len() == 1,
+            observations,
+        });
+    }
+
+    fn error(self, error: ValidationError) {
+        self.collector.error.get_or_insert(error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use metrique_writer_core::unit::NegativeScale;
+    use metrique_writer_core::value::{ForceFlag, WithDimensions};
+    use metrique_writer_format_emf::{HighStorageResolution, NoMetricCtor};
+
+    use super::*;
+
+    /// Writes its observations as a metric in milliseconds
+    struct Dist(Vec<Observation>);
+
+    impl Value for Dist {
+        fn write(&self, writer: impl ValueWriter) {
+            writer.metric(
+                self.0.iter().copied(),
+                Unit::Second(NegativeScale::Milli),
+                [],
+                MetricFlags::empty(),
+            );
+        }
+    }
+
+    struct RequestMetrics {
+        operation: &'static str,
+        status: &'static str,
+        latency: Duration,
+        count: u64,
+        high_res: HighStorageResolution<u64>,
+        hidden: ForceFlag<u64, NoMetricCtor>,
+        by_host: WithDimensions<u64, 1>,
+        ratio: f64,
+    }
+
+    impl Default for RequestMetrics {
+        fn default() -> Self {
+            Self {
+                operation: "Get",
+                status: "OK",
+                latency: Duration::from_millis(12),
+                count: 3,
+                high_res: 4.into(),
+                hidden: 5.into(),
+                by_host: WithDimensions::new(6, "Host", "host-1"),
+                ratio: f64::NAN,
+            }
+        }
+    }
+
+    impl Entry for RequestMetrics {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(UNIX_EPOCH + Duration::from_secs(10));
+            writer.value("Operation", self.operation);
+            writer.value("Status", self.status);
+            writer.value("Latency", &self.latency);
+            writer.value("Count", &self.count);
+            writer.value("HighRes", &self.high_res);
+            writer.value("Hidden", &self.hidden);
+            writer.value("ByHost", &self.by_host);
+            writer.value("Ratio", &self.ratio);
+        }
+    }
+
+    struct DistEntry(Dist);
+
+    impl Entry for DistEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value("Latency", &self.0);
+        }
+    }
+
+    type Requests = Vec<(String, Vec<MetricDatum>)>;
+
+    fn stream(
+        dimensions: Vec<Vec<&str>>,
+        distributions: DistributionMapping,
+    ) -> (
+        PutMetricDataStream<impl PutMetricDataHandler>,
+        std::sync::Arc<std::sync::Mutex<Requests>>,
+    ) {
+        let requests = std::sync::Arc::<std::sync::Mutex<Requests>>::default();
+        let handler_requests = requests.clone();
+        let stream = PutMetricDataStream::builder()
+            .namespace("TestNS".to_string())
+            .default_dimensions(
+                dimensions
+                    .into_iter()
+                    .map(|set| set.into_iter().map(String::from).collect())
+                    .collect(),
+            )
+            .distributions(distributions)
+            .handler(move |namespace: &str, data: Vec<MetricDatum>| {
+                handler_requests
+                    .lock()
+                    .unwrap()
+                    .push((namespace.to_owned(), data));
+                Ok(())
+            })
+            .build();
+        (stream, requests)
+    }
+
+    fn datum(name: &str, dimensions: &[(&str, &str)]) -> MetricDatum {
+        MetricDatum {
+            metric_name: name.to_owned(),
+            dimensions: dimensions
+                .iter()
+                .map(|(name, value)| Dimension {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            timestamp: None,
+            unit: Unit::None,
+            value: None,
+            values: vec![],
+            counts: vec![],
+            statistic_values: None,
+            storage_resolution: None,
+        }
+    }
+
+    #[test]
+    fn maps_fields_to_datums() {
+        let (mut stream, requests) = stream(
+            vec![vec!["Operation"], vec!["Operation", "Status"]],
+            DistributionMapping::default(),
+        );
+        stream.next(&RequestMetrics::default()).unwrap();
+        assert!(requests.lock().unwrap().is_empty());
+        stream.flush().unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let (namespace, data) = &requests[0];
+        assert_eq!(namespace, "TestNS");
+
+        let timestamp = Some(UNIX_EPOCH + Duration::from_secs(10));
+        let mut expected = vec![];
+        for dimensions in [
+            &[("Operation", "Get")][..],
+            &[("Operation", "Get"), ("Status", "OK")],
+        ] {
+            let with_host: Vec<_> = dimensions
+                .iter()
+                .copied()
+                .chain([("Host", "host-1")])
+                .collect();
+            expected.extend([
+                MetricDatum {
+                    timestamp,
+                    unit: Unit::Second(NegativeScale::Milli),
+                    value: Some(12.0),
+                    ..datum("Latency", dimensions)
+                },
+                MetricDatum {
+                    timestamp,
+                    value: Some(3.0),
+                    ..datum("Count", dimensions)
+                },
+                MetricDatum {
+                    timestamp,
+                    value: Some(4.0),
+                    storage_resolution: Some(1),
+                    ..datum("HighRes", dimensions)
+                },
+                MetricDatum {
+                    timestamp,
+                    value: Some(6.0),
+                    ..datum("ByHost", &with_host)
+                },
+            ]);
+        }
+        assert_eq!(*data, expected);
+    }
+
+    #[test]
+    fn rejects_entries_missing_a_dimension() {
+        let (mut stream, requests) = stream(
+            vec![vec!["Operation"], vec!["Region"]],
+            DistributionMapping::default(),
+        );
+        let err = stream.next(&RequestMetrics::default()).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("entry is missing dimension `Region`"),
+            "{err}"
+        );
+        stream.flush().unwrap();
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn maps_distributions_to_values_and_counts() {
+        let (mut stream, requests) = stream(vec![vec![]], DistributionMapping::default());
+        let entry = DistEntry(Dist(vec![
+            Observation::Unsigned(1),
+            Observation::Floating(2.5),
+            Observation::Repeated {
+                total: 30.0,
+                occurrences: 10,
+            },
+            Observation::Repeated {
+                total: 1.0,
+                occurrences: 0,
+            },
+        ]));
+        stream.next(&entry).unwrap();
+        // a single repeated observation is a distribution, not a value
+        stream
+            .next(&DistEntry(Dist(vec![Observation::Repeated {
+                total: 8.0,
+                occurrences: 2,
+            }])))
+            .unwrap();
+        // a metric without observations writes nothing
+        stream.next(&DistEntry(Dist(vec![]))).unwrap();
+        stream.flush().unwrap();
+
+        let requests = requests.lock().unwrap();
+        let data = &requests[0].1;
+        assert_eq!(data.len(), 2);
+        assert_eq!(data[0].values, [1.0, 2.5, 3.0]);
+        assert_eq!(data[0].counts, [1.0, 1.0, 10.0]);
+        assert_eq!(data[0].value, None);
+        assert_eq!(data[0].unit, Unit::Second(NegativeScale::Milli));
+        assert_eq!(data[1].values, [4.0]);
+        assert_eq!(data[1].counts, [2.0]);
+    }
+
+    #[test]
+    fn splits_distributions_over_the_value_limit() {
+        let (mut stream, requests) = stream(vec![vec![]], DistributionMapping::default());
+        let observations = (0..MAX_VALUES_PER_DATUM as u64 * 2 + 1)
+            .map(Observation::Unsigned)
+            .collect();
+        stream.next(&DistEntry(Dist(observations))).unwrap();
+        stream.flush().unwrap();
+
+        let requests = requests.lock().unwrap();
+        let data = &requests[0].1;
+        let lengths: Vec<_> = data.iter().map(|datum| datum.values.len()).collect();
+        assert_eq!(lengths, [MAX_VALUES_PER_DATUM, MAX_VALUES_PER_DATUM, 1]);
+        assert!(data.iter().all(|datum| datum.metric_name == "Latency"));
+        assert_eq!(data[2].values, [300.0]);
+        assert!(
+            data.iter()
+                .flat_map(|datum| &datum.counts)
+                .all(|count| *count == 1.0)
+        );
+    }
+
+    #[test]
+    fn maps_distributions_to_statistic_values() {
+        let (mut stream, requests) = stream(vec![vec![]], DistributionMapping::StatisticValues);
+        let observations = (1..=MAX_VALUES_PER_DATUM as u64 * 2)
+            .map(Observation::Unsigned)
+            .chain([Observation::Repeated {
+                total: 1000.0,
+                occurrences: 100,
+            }])
+            .collect();
+        stream.next(&DistEntry(Dist(observations))).unwrap();
+        stream.flush().unwrap();
+
+        let requests = requests.lock().unwrap();
+        let data = &requests[0].1;
+        assert_eq!(data.len(), 1);
+        assert!(data[0].values.is_empty());
+        assert_eq!(
+            data[0].statistic_values,
+            Some(StatisticSet {
+                sample_count: 400.0,
+                sum: (300.0 * 301.0 / 2.0) + 1000.0,
+                minimum: 1.0,
+                maximum: 300.0,
+            })
+        );
+    }
+
+    #[test]
+    fn batches_respect_the_datum_limit() {
+        let (mut stream, requests) = stream(vec![vec![]], DistributionMapping::default());
+        let metrics = RequestMetrics::default();
+        // every entry writes 4 datums
+        for _ in 0..600 {
+            stream.next(&metrics).unwrap();
+        }
+        assert_eq!(requests.lock().unwrap().len(), 2);
+        stream.flush().unwrap();
+
+        let requests = requests.lock().unwrap();
+        let lengths: Vec<_> = requests.iter().map(|(_, data)| data.len()).collect();
+        assert_eq!(lengths, [1000, 1000, 400]);
+        // batches keep the order of the entries
+        assert_eq!(requests[1].1[0].metric_name, "Latency");
+    }
+
+    #[test]
+    fn handler_errors_are_io_errors() {
+        let mut stream = PutMetricDataStream::builder()
+            .namespace("TestNS".to_string())
+            .handler(|_: &str, _: Vec<MetricDatum>| Err(io::Error::other("throttled")))
+            .build();
+        stream
+            .next(&DistEntry(Dist(vec![Observation::Unsigned(1)])))
+            .unwrap();
+        let err = stream.flush().unwrap_err();
+        assert_eq!(err.to_string(), "throttled");
+    }
+}
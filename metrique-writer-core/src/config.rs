@@ -5,9 +5,9 @@
 //!
 //! The configurations are in this crate in the interest of interoperability
 
-use std::{borrow::Cow, slice};
+use std::{any::Any, borrow::Cow, slice, time::SystemTime};
 
-use crate::EntryConfig;
+use crate::{Entry, EntryConfig, EntryWriter, Value};
 
 /// This config enables splitting entries in case of multiple dimension values.
 ///
@@ -195,14 +195,109 @@ impl EntryDimensions {
             inner: inner.iter(),
         })
     }
+
+    /// Return the union of the dimension sets of `self` and `other`.
+    ///
+    /// Dimension sets of `self` come first, followed by those of `other` that are not already
+    /// present.
+    pub fn union(&self, other: &EntryDimensions) -> EntryDimensions {
+        let mut dimensions = self.dimensions.to_vec();
+        for dim_set in other.dimensions.iter() {
+            if !dimensions.contains(dim_set) {
+                dimensions.push(dim_set.clone());
+            }
+        }
+        EntryDimensions::new(Cow::Owned(dimensions))
+    }
+}
+
+/// Stacked [EntryDimensions] merge into the [union](EntryDimensions::union) of their dimension
+/// sets.
+impl EntryConfig for EntryDimensions {
+    fn merge(&self, other: &dyn EntryConfig) -> Option<Box<dyn EntryConfig>> {
+        let other = (other as &dyn Any).downcast_ref::<EntryDimensions>()?;
+        Some(Box::new(self.union(other)))
+    }
+}
+
+/// Putting this config on an entry marks it as high priority: samplers always emit it (without
+/// upweighting), and the background queue drops other entries before it when it is full.
+///
+/// This is intended for rare entries that must not be lost, like errors. If every entry is
+/// high priority, the background queue has no choice but to drop some of them when full.
+///
+/// Since `HighPriority` is itself an [`Entry`] that only writes this config, it can be
+/// flattened into an entry, including conditionally via `Option<HighPriority>` (with
+/// `metrique`, use a `#[metrics(flatten_entry)]` field).
+///
+/// ## Example
+///
+/// ```
+/// # use metrique_writer_core::config::HighPriority;
+/// # use metrique_writer_core::{Entry, EntryWriter};
+/// struct MyEntry {
+///     error: Option<String>,
+/// }
+///
+/// impl Entry for MyEntry {
+///     fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+///         if let Some(error) = &self.error {
+///             writer.value("Error", error);
+///             writer.config(const { &HighPriority::new() });
+///         }
+///     }
+/// }
+///
+/// assert!(HighPriority::is_set(&MyEntry { error: Some("timeout".into()) }));
+/// assert!(!HighPriority::is_set(&MyEntry { error: None }));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+#[non_exhaustive]
+pub struct HighPriority(());
+
+impl HighPriority {
+    /// Create a new [HighPriority]
+    pub const fn new() -> Self {
+        Self(())
+    }
+
+    /// Returns whether `entry` writes the [HighPriority] config.
+    ///
+    /// This walks the entry without writing any of its values, so it is cheaper than
+    /// formatting it, but not free. The background queue only calls it for entries it is about to
+    /// evict.
+    pub fn is_set(entry: &impl Entry) -> bool {
+        struct Probe(bool);
+
+        impl<'a> EntryWriter<'a> for Probe {
+            fn timestamp(&mut self, _timestamp: SystemTime) {}
+
+            fn value(&mut self, _name: impl Into<Cow<'a, str>>, _value: &(impl Value + ?Sized)) {}
+
+            fn config(&mut self, config: &'a dyn EntryConfig) {
+                self.0 |= (config as &dyn Any).is::<HighPriority>();
+            }
+        }
+
+        let mut probe = Probe(false);
+        entry.write(&mut probe);
+        probe.0
+    }
 }
 
-impl EntryConfig for EntryDimensions {}
+impl EntryConfig for HighPriority {}
+
+impl Entry for HighPriority {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        writer.config(self);
+    }
+}
 
 #[cfg(test)]
 mod test {
-    use super::EntryDimensions;
-    use std::borrow::Cow;
+    use super::{AllowSplitEntries, EntryDimensions, HighPriority, MetriqueValidationError};
+    use crate::EntryConfig;
+    use std::{any::Any, borrow::Cow};
 
     #[test]
     fn test_misc_coverage() {
@@ -212,4 +307,46 @@ mod test {
         );
         assert!(EntryDimensions::new_static(&[Cow::Borrowed(&[])]).dimensions[0].is_empty());
     }
+
+    #[test]
+    fn high_priority_is_set() {
+        assert!(HighPriority::is_set(&HighPriority::new()));
+        assert!(HighPriority::is_set(&Some(HighPriority::new())));
+        assert!(!HighPriority::is_set(&None::<HighPriority>));
+        assert!(!HighPriority::is_set(&MetriqueValidationError::new(
+            "error"
+        )));
+    }
+
+    fn dim_sets(dimensions: &EntryDimensions) -> Vec<Vec<&str>> {
+        dimensions.dim_sets().map(|set| set.collect()).collect()
+    }
+
+    #[test]
+    fn entry_dimensions_merge_into_union() {
+        let first = EntryDimensions::new_static(&[
+            Cow::Borrowed(&[Cow::Borrowed("Operation")]),
+            Cow::Borrowed(&[Cow::Borrowed("Operation"), Cow::Borrowed("Status")]),
+        ]);
+        let second = EntryDimensions::new_static(&[
+            Cow::Borrowed(&[Cow::Borrowed("Operation")]),
+            Cow::Borrowed(&[Cow::Borrowed("Region")]),
+        ]);
+
+        let merged = first.merge(&second).unwrap();
+        let merged = (&*merged as &dyn Any)
+            .downcast_ref::<EntryDimensions>()
+            .unwrap();
+        assert_eq!(
+            dim_sets(merged),
+            [
+                vec!["Operation"],
+                vec!["Operation", "Status"],
+                vec!["Region"]
+            ]
+        );
+
+        assert!(first.merge(&AllowSplitEntries::new()).is_none());
+        assert!(AllowSplitEntries::new().merge(&first).is_none());
+    }
 }
@@ -50,6 +50,34 @@ impl Entry for BoxEntry {
     }
 }
 
+/// A [`BoxEntry`] for entries that are also [`Sync`], so that the box itself is [`Sync`].
+///
+/// This is useful to store type-erased entries in a struct that has to be shared across threads.
+pub struct SyncBoxEntry(Box<dyn DynEntry + Sync>);
+
+impl SyncBoxEntry {
+    /// Move the entry to the heap and enable dynamic dispatch.
+    pub fn new(entry: impl Entry + Send + Sync + 'static) -> Self {
+        Self(Box::new(entry))
+    }
+}
+
+impl Entry for SyncBoxEntry {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        self.0.write(&mut EntryWriterToDyn(writer))
+    }
+
+    fn sample_group(&self) -> impl Iterator<Item = (Cow<'static, str>, Cow<'static, str>)> {
+        self.0.sample_group().into_iter()
+    }
+}
+
+impl std::fmt::Debug for SyncBoxEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SyncBoxEntry").finish_non_exhaustive()
+    }
+}
+
 // Each Dyn* trait is the object-safe equivalent of its partner
 
 trait DynEntry: Any + Send + 'static {
@@ -271,4 +299,33 @@ mod tests {
             ]
         );
     }
+
+    #[test]
+    fn sync_box_entry() {
+        struct TestEntry;
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.value("StringProp", "some string value");
+                writer.value("BasicIntCount", &1234u64);
+            }
+
+            fn sample_group(&self) -> impl Iterator<Item = (Cow<'static, str>, Cow<'static, str>)> {
+                [(Cow::Borrowed("Operation"), Cow::Borrowed("Get"))].into_iter()
+            }
+        }
+
+        fn assert_sync<T: Sync>(_: &T) {}
+        let boxed = SyncBoxEntry::new(TestEntry);
+        assert_sync(&boxed);
+
+        let mut writer = DummyEntryWriter::default();
+        Entry::write(&boxed, &mut writer);
+        let mut expected = DummyEntryWriter::default();
+        Entry::write(&TestEntry, &mut expected);
+        assert_eq!(writer.0, expected.0);
+        assert_eq!(
+            Entry::sample_group(&boxed).collect::<Vec<_>>(),
+            [(Cow::Borrowed("Operation"), Cow::Borrowed("Get"))]
+        );
+    }
 }
@@ -0,0 +1,205 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{borrow::Cow, time::SystemTime};
+
+use crate::{
+    Entry, EntryConfig, EntryWriter, MetricFlags, Observation, Unit, ValidationError, Value,
+    ValueWriter, value::Distribution,
+};
+
+/// The name and unit of a metric written by an entry, as returned by [`describe_metrics`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MetricDescription {
+    /// The name the metric is written under
+    pub name: String,
+    /// The unit the metric is written with
+    pub unit: Unit,
+    /// Whether the metric is a distribution, i.e. it is flagged with [`Distribution`] or was
+    /// written with more than one observation
+    pub distribution: bool,
+    /// The names of the per-metric dimensions of the metric (e.g. from
+    /// [`WithDimensions`](crate::value::WithDimensions)), in the order they were written
+    pub dimensions: Vec<String>,
+}
+
+/// Describe the metrics that `entry` writes, in the order they are first written.
+///
+/// This writes `entry` to a capturing writer, and returns the names and units of its metric
+/// fields. String fields, timestamps and entry configuration are skipped, and a metric that is
+/// written more than once is only described once. The description is based on what this
+/// particular entry writes, so fields that are skipped when empty (e.g. `None`) are not
+/// described: use a representative entry that has every field set.
+///
+/// This is meant for tooling that needs to enumerate metrics ahead of time, e.g. generating
+/// dashboards or alarms as code.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use metrique_writer::{Entry, Unit};
+/// use metrique_writer_core::entry::describe_metrics;
+///
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     operation: &'static str,
+///     latency: Duration,
+///     retries: u32,
+/// }
+///
+/// let metrics = describe_metrics(&RequestMetrics {
+///     operation: "GetItem",
+///     latency: Duration::from_millis(3),
+///     retries: 0,
+/// });
+/// let metrics: Vec<_> = metrics.iter().map(|m| (m.name.as_str(), m.unit)).collect();
+/// assert_eq!(
+///     metrics,
+///     [
+///         ("latency", Unit::Second(metrique_writer::unit::NegativeScale::Milli)),
+///         ("retries", Unit::None),
+///     ]
+/// );
+/// ```
+pub fn describe_metrics(entry: &impl Entry) -> Vec<MetricDescription> {
+    let mut writer = DescribeWriter(Vec::new());
+    entry.write(&mut writer);
+    writer.0
+}
+
+struct DescribeWriter(Vec<MetricDescription>);
+
+impl<'a> EntryWriter<'a> for DescribeWriter {
+    fn timestamp(&mut self, _timestamp: SystemTime) {}
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        value.write(DescribeValueWriter {
+            name: name.into(),
+            metrics: &mut self.0,
+        });
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {}
+}
+
+struct DescribeValueWriter<'w, 'a> {
+    name: Cow<'a, str>,
+    metrics: &'w mut Vec<MetricDescription>,
+}
+
+impl ValueWriter for DescribeValueWriter<'_, '_> {
+    fn string(self, _value: &str) {}
+
+    fn values<'a, V: Value + 'a>(self, _values: impl IntoIterator<Item = &'a V>) {}
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        unit: Unit,
+        dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        flags: MetricFlags<'_>,
+    ) {
+        if self.metrics.iter().any(|m| m.name == self.name) {
+            return;
+        }
+        let mut observations = 0;
+        for observation in distribution {
+            observations += match observation {
+                Observation::Repeated { occurrences, .. } => occurrences,
+                _ => 1,
+            };
+        }
+        self.metrics.push(MetricDescription {
+            name: self.name.into_owned(),
+            unit,
+            distribution: flags.downcast::<Distribution>().is_some() || observations > 1,
+            dimensions: dimensions.into_iter().map(|(k, _)| k.to_owned()).collect(),
+        });
+    }
+
+    fn error(self, _error: ValidationError) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{MetricDescription, describe_metrics};
+    use crate::{
+        Entry, EntryWriter, MetricFlags, Observation, Unit, Value, ValueWriter,
+        unit::NegativeScale,
+        value::{Distribution, WithDimension},
+    };
+
+    struct Histogram(Vec<u64>);
+    impl Value for Histogram {
+        fn write(&self, writer: impl ValueWriter) {
+            writer.metric(
+                self.0.iter().map(|v| Observation::Unsigned(*v)),
+                Unit::Count,
+                [],
+                MetricFlags::upcast(&Distribution),
+            );
+        }
+    }
+
+    struct Samples;
+    impl Value for Samples {
+        fn write(&self, writer: impl ValueWriter) {
+            writer.metric(
+                [Observation::Floating(1.0), Observation::Floating(2.0)],
+                Unit::None,
+                [],
+                MetricFlags::empty(),
+            );
+        }
+    }
+
+    struct TestEntry;
+    impl Entry for TestEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(std::time::SystemTime::UNIX_EPOCH);
+            writer.value("Operation", "GetItem");
+            writer.value("Latency", &Duration::from_millis(3));
+            // lists are written as properties
+            writer.value("Tags", &[1u64, 2][..]);
+            writer.value("Sizes", &Histogram(vec![1]));
+            writer.value("Samples", &Samples);
+            writer.value("Missing", &None::<u64>);
+            writer.value(
+                "Throttles",
+                &WithDimension::new_with_dimensions(1u64, [("Shard", "1")]),
+            );
+            // only described once
+            writer.value("Latency", &Duration::from_millis(4));
+        }
+    }
+
+    fn metric(
+        name: &str,
+        unit: Unit,
+        distribution: bool,
+        dimensions: &[&str],
+    ) -> MetricDescription {
+        MetricDescription {
+            name: name.into(),
+            unit,
+            distribution,
+            dimensions: dimensions.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn describes_only_metrics() {
+        assert_eq!(
+            describe_metrics(&TestEntry),
+            [
+                metric("Latency", Unit::Second(NegativeScale::Milli), false, &[]),
+                metric("Sizes", Unit::Count, true, &[]),
+                metric("Samples", Unit::None, true, &[]),
+                metric("Throttles", Unit::None, false, &["Shard"]),
+            ]
+        );
+    }
+}
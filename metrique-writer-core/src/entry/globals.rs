@@ -0,0 +1,101 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+
+use crate::{Entry, EntryWriter};
+
+/// An [`Entry`] made of a fixed list of string fields, typically created with [`const_globals!`].
+///
+/// This is meant for globals merged into every entry of a stream (e.g. with `merge_globals`),
+/// for the common case where they are all constant strings and defining a
+/// `#[derive(Entry)]` struct is overkill.
+///
+/// [`const_globals!`]: crate::const_globals
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ConstGlobals<const N: usize> {
+    fields: [(&'static str, Cow<'static, str>); N],
+}
+
+impl<const N: usize> ConstGlobals<N> {
+    /// Create a [`ConstGlobals`] writing each `(name, value)` pair as a string field.
+    pub const fn new(fields: [(&'static str, Cow<'static, str>); N]) -> Self {
+        Self { fields }
+    }
+}
+
+impl<const N: usize> Entry for ConstGlobals<N> {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        for (name, value) in &self.fields {
+            writer.value(*name, &**value);
+        }
+    }
+}
+
+/// Create an [`Entry`] of constant string fields, to be used as globals.
+///
+/// Each value can be anything that converts into a `Cow<'static, str>`, such as a `&'static str`
+/// or a `String` computed at startup.
+///
+/// The resulting [`ConstGlobals`] entry is usually passed to `merge_globals`, so that the fields
+/// are written on every entry of the stream.
+///
+/// # Example
+/// ```
+/// # use metrique_writer::{Entry, EntryIoStream, EntryIoStreamExt, FormatExt};
+/// # use metrique_writer_core::const_globals;
+/// # use metrique_writer_format_emf::Emf;
+/// let globals = const_globals! {
+///     "AvailabilityZone" => "us-east-1a",
+///     "Version" => env!("CARGO_PKG_VERSION"),
+/// };
+///
+/// let mut output = vec![];
+/// let mut stream = Emf::all_validations("MyApp".into(), vec![vec!["AvailabilityZone".into()]])
+///     .output_to(&mut output)
+///     .merge_globals(globals);
+/// # #[derive(Entry)]
+/// # struct RequestMetrics { count: u64 }
+/// stream.next(&RequestMetrics { count: 1 }).unwrap();
+/// ```
+#[macro_export]
+macro_rules! const_globals {
+    ($($name:expr => $value:expr),* $(,)?) => {
+        $crate::entry::ConstGlobals::new([
+            $(($name, ::std::borrow::Cow::<'static, str>::from($value))),*
+        ])
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Entry, test_stream::DummyEntryWriter};
+
+    #[test]
+    fn writes_fields_in_order() {
+        let region = String::from("us-east-1");
+        let globals = crate::const_globals! {
+            "AvailabilityZone" => "us-east-1a",
+            "Region" => region,
+            "Version" => env!("CARGO_PKG_VERSION"),
+        };
+
+        let mut writer = DummyEntryWriter::default();
+        globals.write(&mut writer);
+        assert_eq!(
+            writer.0,
+            [
+                ("AvailabilityZone".to_string(), "us-east-1a".to_string()),
+                ("Region".to_string(), "us-east-1".to_string()),
+                ("Version".to_string(), env!("CARGO_PKG_VERSION").to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty() {
+        let mut writer = DummyEntryWriter::default();
+        crate::const_globals! {}.write(&mut writer);
+        assert!(writer.0.is_empty());
+    }
+}
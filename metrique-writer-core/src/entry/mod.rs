@@ -7,13 +7,31 @@
 use std::{any::Any, borrow::Cow, sync::Arc, time::SystemTime};
 
 mod boxed;
-pub use boxed::BoxEntry;
+pub use boxed::{BoxEntry, SyncBoxEntry};
+
+mod describe;
+pub use describe::{MetricDescription, describe_metrics};
+
+mod globals;
+pub use globals::ConstGlobals;
 
 mod map;
 
 mod merged;
 pub use merged::{Merged, MergedRef};
 
+mod result;
+pub use result::ResultEntry;
+
+mod sample_group;
+pub use sample_group::{SampleGroupMap, SampleGroupMapIntoIter};
+
+mod timestamp;
+pub use timestamp::EntryTimestamp;
+
+mod with_unit;
+pub use with_unit::{DefaultUnitEntryWriter, WithEntryUnit};
+
 use crate::Value;
 
 /// The core trait to be implemented by application data structures holding metric values.
@@ -164,6 +182,9 @@ pub trait Entry {
     /// assert_eq!(&sample_group["Result"], "ValidationError");
     /// ```
     ///
+    /// Formats that need the sample group while formatting should collect it once into a
+    /// [`SampleGroupMap`] rather than calling this repeatedly.
+    ///
     /// [`FixedFractionSample`]: https://docs.rs/metrique-writer/0.1/metrique_writer/sample/struct.FixedFractionSample.html
     /// [`CongressSample`]: https://docs.rs/metrique-writer/0.1/metrique_writer/sample/struct.CongressSample.html
     fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
@@ -210,7 +231,20 @@ impl Entry for EmptyEntry {
 }
 
 /// Trait for format-specific Entry configuration, formats will downcast this to the specific config
-pub trait EntryConfig: Any + std::fmt::Debug {}
+pub trait EntryConfig: Any + std::fmt::Debug {
+    /// Combine this config with `other`, a config passed later to the same entry.
+    ///
+    /// Entry adapters can be stacked, so an entry may be passed the same kind of config more
+    /// than once. Formats that only support one config of a given kind per entry can call this
+    /// to combine them rather than rejecting the later one.
+    ///
+    /// Returns `None` if the configs can't be combined, which is the default. Implementations
+    /// should return a config of the same type as `self`.
+    fn merge(&self, other: &dyn EntryConfig) -> Option<Box<dyn EntryConfig>> {
+        let _ = other;
+        None
+    }
+}
 
 /// Provided by a format for each atomic entry that will be written to the metric destination.
 ///
@@ -0,0 +1,131 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+
+use crate::{Entry, EntryWriter, Value, unit::AsCount};
+
+/// Writes the `Ok` value of a [`Result`] as a metric, or an error count in its place.
+///
+/// A [`Value`] is always written under the name chosen by its entry, so it can't switch to a
+/// different name on error. `ResultEntry` is therefore an [`Entry`] holding its own names, which
+/// is meant to be flattened into the containing entry (`#[entry(flatten)]`):
+/// - `Ok(v)` writes `v` under the value name;
+/// - `Err(_)` writes a count of `1` under the error name, and nothing under the value name.
+///
+/// The error name defaults to the value name followed by `Error` (e.g. `Latency` becomes
+/// `LatencyError`), which suits `PascalCase` names. Use [`ResultEntry::with_error_name`] for
+/// other naming styles (e.g. `latency_error`).
+///
+/// The error itself is not written. To also record the error kind, write it as a separate field.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use metrique_writer::Entry;
+/// # use metrique_writer::test_util::to_test_entry;
+/// use metrique_writer_core::entry::ResultEntry;
+///
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     #[entry(flatten)]
+///     downstream_latency: ResultEntry<Duration, std::io::Error>,
+/// }
+///
+/// let ok = to_test_entry(RequestMetrics {
+///     downstream_latency: ResultEntry::new("DownstreamLatency", Ok(Duration::from_millis(5))),
+/// });
+/// assert_eq!(ok.metrics["DownstreamLatency"], 5);
+/// assert!(!ok.metrics.contains_key("DownstreamLatencyError"));
+///
+/// let err = to_test_entry(RequestMetrics {
+///     downstream_latency: ResultEntry::new(
+///         "DownstreamLatency",
+///         Err(std::io::ErrorKind::TimedOut.into()),
+///     ),
+/// });
+/// assert_eq!(err.metrics["DownstreamLatencyError"], 1);
+/// assert!(!err.metrics.contains_key("DownstreamLatency"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ResultEntry<T, E> {
+    name: Cow<'static, str>,
+    error_name: Option<Cow<'static, str>>,
+    result: Result<T, E>,
+}
+
+impl<T, E> ResultEntry<T, E> {
+    /// Create a [`ResultEntry`] writing the `Ok` value of `result` under `name`, and errors under
+    /// `name` followed by `Error`.
+    pub fn new(name: impl Into<Cow<'static, str>>, result: Result<T, E>) -> Self {
+        Self {
+            name: name.into(),
+            error_name: None,
+            result,
+        }
+    }
+
+    /// Write errors under `error_name` instead of the default derived name
+    pub fn with_error_name(mut self, error_name: impl Into<Cow<'static, str>>) -> Self {
+        self.error_name = Some(error_name.into());
+        self
+    }
+
+    /// Return the wrapped result
+    pub fn into_inner(self) -> Result<T, E> {
+        self.result
+    }
+}
+
+impl<T: Value, E> Entry for ResultEntry<T, E> {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        match &self.result {
+            Ok(value) => writer.value(&*self.name, value),
+            Err(_) => {
+                let error_name = match &self.error_name {
+                    Some(error_name) => Cow::Borrowed(&**error_name),
+                    // only allocated on errors, which are rarer than successes
+                    None => Cow::Owned(format!("{}Error", self.name)),
+                };
+                writer.value(error_name, &AsCount::from(1u64))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Entry, test_stream::DummyEntryWriter};
+
+    use super::ResultEntry;
+
+    fn write(entry: impl Entry) -> Vec<(String, String)> {
+        let mut writer = DummyEntryWriter::default();
+        entry.write(&mut writer);
+        writer.0
+    }
+
+    #[test]
+    fn derives_error_name() {
+        let ok = ResultEntry::<u64, ()>::new("Items", Ok(3));
+        assert_eq!(
+            write(ok),
+            [("Items".into(), "[Unsigned(3)] None []".into())]
+        );
+
+        let err = ResultEntry::<u64, ()>::new("Items", Err(()));
+        assert_eq!(
+            write(err),
+            [("ItemsError".into(), "[Unsigned(1)] Count []".into())]
+        );
+    }
+
+    #[test]
+    fn custom_error_name() {
+        let err = ResultEntry::<u64, ()>::new("items", Err(())).with_error_name("items_failed");
+        assert_eq!(
+            write(err),
+            [("items_failed".into(), "[Unsigned(1)] Count []".into())]
+        );
+    }
+}
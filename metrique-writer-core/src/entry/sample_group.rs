@@ -0,0 +1,250 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use smallvec::SmallVec;
+
+use crate::Entry;
+use crate::entry::SampleGroupElement;
+
+/// The [sample group](Entry::sample_group) of an entry, collected into a small map.
+///
+/// [`Entry::sample_group`] returns an iterator that may recompute its elements every time it is
+/// called. Formats that need the sample group while formatting an entry (e.g. to include it in
+/// diagnostics, or to key per-group state) should collect it once at the start of
+/// [`Format::format`](crate::format::Format::format) and use the collected map from then on.
+///
+/// Sample groups are typically only a couple of elements, so they are stored inline and lookups
+/// are a linear scan. If a key appears more than once, [`SampleGroupMap::get`] returns the value
+/// that was emitted first.
+///
+/// # Example
+/// ```
+/// # use metrique_writer::{Entry, IoStreamError, format::Format};
+/// # use metrique_writer_core::entry::SampleGroupMap;
+/// # use std::io;
+/// /// Writes the sample group of each entry as a `[key=value,...]` line
+/// struct SampleGroupFormat;
+///
+/// impl Format for SampleGroupFormat {
+///     fn format(
+///         &mut self,
+///         entry: &impl Entry,
+///         output: &mut impl io::Write,
+///     ) -> Result<(), IoStreamError> {
+///         let group = SampleGroupMap::from_entry(entry);
+///         let fields: Vec<_> = group.iter().map(|(k, v)| format!("{k}={v}")).collect();
+///         writeln!(output, "[{}]", fields.join(","))?;
+///         Ok(())
+///     }
+/// }
+///
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     #[entry(sample_group)]
+///     operation: &'static str,
+///     latency_ms: u64,
+/// }
+///
+/// let mut output = vec![];
+/// SampleGroupFormat
+///     .format(&RequestMetrics { operation: "Get", latency_ms: 5 }, &mut output)
+///     .unwrap();
+/// assert_eq!(output, b"[operation=Get]\n");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SampleGroupMap {
+    elements: SmallVec<[SampleGroupElement; 2]>,
+}
+
+impl SampleGroupMap {
+    /// Collect the sample group of `entry`
+    pub fn from_entry(entry: &(impl Entry + ?Sized)) -> Self {
+        entry.sample_group().collect()
+    }
+
+    /// Return the value of the sample group element named `key`, if any
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.elements
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| &**v)
+    }
+
+    /// Iterate over the `(key, value)` elements, in the order the entry emitted them
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.elements.iter().map(|(k, v)| (&**k, &**v))
+    }
+
+    /// Return the number of elements in the sample group
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// Return true if the sample group has no elements
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+}
+
+impl FromIterator<SampleGroupElement> for SampleGroupMap {
+    fn from_iter<T: IntoIterator<Item = SampleGroupElement>>(iter: T) -> Self {
+        Self {
+            elements: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl IntoIterator for SampleGroupMap {
+    type Item = SampleGroupElement;
+    type IntoIter = SampleGroupMapIntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        SampleGroupMapIntoIter(self.elements.into_iter())
+    }
+}
+
+/// An owning iterator over the elements of a [`SampleGroupMap`].
+#[derive(Debug)]
+pub struct SampleGroupMapIntoIter(smallvec::IntoIter<[SampleGroupElement; 2]>);
+
+impl Iterator for SampleGroupMapIntoIter {
+    type Item = SampleGroupElement;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for SampleGroupMapIntoIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back()
+    }
+}
+
+impl ExactSizeIterator for SampleGroupMapIntoIter {}
+
+impl std::iter::FusedIterator for SampleGroupMapIntoIter {}
+
+impl<'m> IntoIterator for &'m SampleGroupMap {
+    type Item = (&'m str, &'m str);
+    type IntoIter = std::iter::Map<
+        std::slice::Iter<'m, SampleGroupElement>,
+        fn(&'m SampleGroupElement) -> (&'m str, &'m str),
+    >;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.elements.iter().map(|(k, v)| (k, v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, cell::Cell, io};
+
+    use super::SampleGroupMap;
+    use crate::{
+        Entry, EntryWriter, IoStreamError, entry::SampleGroupElement, format::Format,
+        test_stream::DummyEntryWriter,
+    };
+
+    struct TestEntry {
+        operation: &'static str,
+        result: &'static str,
+        sample_group_calls: Cell<usize>,
+    }
+
+    impl Entry for TestEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value("Operation", self.operation);
+            writer.value("Result", self.result);
+            writer.value("Count", &1u64);
+        }
+
+        fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+            self.sample_group_calls
+                .set(self.sample_group_calls.get() + 1);
+            [
+                (Cow::Borrowed("Operation"), Cow::Borrowed(self.operation)),
+                (Cow::Borrowed("Result"), Cow::Borrowed(self.result)),
+            ]
+            .into_iter()
+        }
+    }
+
+    /// A format that prefixes every field with the operation from the sample group, and writes
+    /// the whole group at the end
+    struct GroupPrefixFormat;
+
+    impl Format for GroupPrefixFormat {
+        fn format(
+            &mut self,
+            entry: &impl Entry,
+            output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            let group = SampleGroupMap::from_entry(entry);
+            let operation = group.get("Operation").unwrap_or("-");
+
+            let mut writer = DummyEntryWriter::default();
+            entry.write(&mut writer);
+            for (name, value) in writer.0 {
+                // looking up the group repeatedly does not call `Entry::sample_group` again
+                let result = group.get("Result").unwrap_or("-");
+                writeln!(output, "{operation}/{result} {name}={value}")?;
+            }
+            for (key, value) in &group {
+                writeln!(output, "group {key}={value}")?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn format_consumes_sample_group() {
+        let entry = TestEntry {
+            operation: "Get",
+            result: "Ok",
+            sample_group_calls: Cell::new(0),
+        };
+        let mut output = vec![];
+        GroupPrefixFormat.format(&entry, &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Get/Ok Operation=Get\n\
+             Get/Ok Result=Ok\n\
+             Get/Ok Count=[Unsigned(1)] None []\n\
+             group Operation=Get\n\
+             group Result=Ok\n"
+        );
+        assert_eq!(entry.sample_group_calls.get(), 1);
+    }
+
+    #[test]
+    fn map_accessors() {
+        let group: SampleGroupMap = [
+            (Cow::Borrowed("A"), Cow::Borrowed("x")),
+            (Cow::Owned("B".to_string()), Cow::Borrowed("y")),
+            (Cow::Borrowed("A"), Cow::Borrowed("z")),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(group.len(), 3);
+        assert!(!group.is_empty());
+        assert_eq!(group.get("A"), Some("x"));
+        assert_eq!(group.get("B"), Some("y"));
+        assert_eq!(group.get("C"), None);
+        assert_eq!(
+            group.iter().collect::<Vec<_>>(),
+            [("A", "x"), ("B", "y"), ("A", "z")]
+        );
+
+        let empty = SampleGroupMap::from_entry(&crate::entry::EmptyEntry);
+        assert!(empty.is_empty());
+        assert_eq!(empty.get("A"), None);
+    }
+}
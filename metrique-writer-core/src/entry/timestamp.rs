@@ -0,0 +1,50 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::SystemTime;
+
+/// A value that can be used as the timestamp of an entry, as marked by `#[entry(timestamp)]` or
+/// `#[metrics(timestamp)]`.
+///
+/// This is implemented for every `Copy` type that converts into a [`SystemTime`], including
+/// [`SystemTime`] itself. Implement it directly for types that carry a time in some other form,
+/// like an event time received from an upstream system.
+///
+/// Returning `None` leaves the timestamp unset, in which case formats are free to use the current
+/// system time (see [`EntryWriter::timestamp`]).
+///
+/// ```
+/// # use std::time::{Duration, SystemTime};
+/// # use metrique_writer::{Entry, EntryTimestamp};
+/// /// Milliseconds since the Unix epoch, or 0 if the event time is unknown
+/// struct EventTime(u64);
+///
+/// impl EntryTimestamp for EventTime {
+///     fn entry_timestamp(&self) -> Option<SystemTime> {
+///         (self.0 != 0).then(|| SystemTime::UNIX_EPOCH + Duration::from_millis(self.0))
+///     }
+/// }
+///
+/// #[derive(Entry)]
+/// struct EventMetrics {
+///     #[entry(timestamp)]
+///     event_time: EventTime,
+///     processed: u64,
+/// }
+/// ```
+///
+/// [`EntryWriter::timestamp`]: crate::EntryWriter::timestamp
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` cannot be used as an entry timestamp",
+    note = "timestamps must implement `EntryTimestamp`, which is implemented for `Copy` types that implement `Into<SystemTime>`"
+)]
+pub trait EntryTimestamp {
+    /// Return the timestamp of the entry, or `None` to leave it unset
+    fn entry_timestamp(&self) -> Option<SystemTime>;
+}
+
+impl<T: Into<SystemTime> + Copy> EntryTimestamp for T {
+    fn entry_timestamp(&self) -> Option<SystemTime> {
+        Some((*self).into())
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    borrow::Cow,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    time::SystemTime,
+};
+
+use crate::{
+    Entry, EntryConfig, EntryWriter, MetricFlags, Observation, Unit, ValidationError, Value,
+    ValueWriter, entry::SampleGroupElement, unit::UnitTag,
+};
+
+/// Wraps an [`Entry`], writing all of its metrics that don't have a unit with the unit `U`.
+///
+/// This is useful for entries where every metric has the same unit, e.g. an entry that only
+/// contains timings that are recorded as plain integers, to avoid attaching the unit to each
+/// value separately.
+///
+/// Metrics that already carry a unit other than [`Unit::None`] keep their own unit, they are not
+/// converted to `U`. This includes [`Duration`](std::time::Duration)s, which are always written
+/// as milliseconds. Since [`unit::None`](crate::unit::None) is written as [`Unit::None`], values
+/// explicitly tagged as unitless also get `U`. When nesting `WithEntryUnit`, the innermost unit
+/// wins. String values are not affected.
+///
+/// ```
+/// # use metrique_writer::Entry;
+/// # use metrique_writer::entry::WithEntryUnit;
+/// # use metrique_writer::unit::{AsBytes, Microsecond};
+/// #[derive(Entry)]
+/// struct Timings {
+///     resolve: u64,
+///     connect: u64,
+///     // keeps its own unit
+///     response_size: AsBytes<u64>,
+/// }
+///
+/// // `resolve` and `connect` are written as microseconds
+/// let timings = WithEntryUnit::<_, Microsecond>::new(Timings {
+///     resolve: 20,
+///     connect: 150,
+///     response_size: 1024.into(),
+/// });
+/// ```
+pub struct WithEntryUnit<E, U> {
+    entry: E,
+    unit: PhantomData<U>,
+}
+
+impl<E, U> WithEntryUnit<E, U> {
+    /// Wrap `entry`
+    pub fn new(entry: E) -> Self {
+        Self {
+            entry,
+            unit: PhantomData,
+        }
+    }
+
+    /// Return the wrapped entry
+    pub fn into_inner(self) -> E {
+        self.entry
+    }
+
+    /// Map the wrapped entry, keeping the unit
+    pub fn map_value<E2>(self, f: impl FnOnce(E) -> E2) -> WithEntryUnit<E2, U> {
+        WithEntryUnit::new(f(self.entry))
+    }
+}
+
+impl<E, U> Deref for WithEntryUnit<E, U> {
+    type Target = E;
+
+    fn deref(&self) -> &Self::Target {
+        &self.entry
+    }
+}
+
+impl<E, U> DerefMut for WithEntryUnit<E, U> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.entry
+    }
+}
+
+impl<E, U> From<E> for WithEntryUnit<E, U> {
+    fn from(entry: E) -> Self {
+        Self::new(entry)
+    }
+}
+
+impl<E: Clone, U> Clone for WithEntryUnit<E, U> {
+    fn clone(&self) -> Self {
+        Self::new(self.entry.clone())
+    }
+}
+
+impl<E: fmt::Debug, U: UnitTag> fmt::Debug for WithEntryUnit<E, U> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WithEntryUnit")
+            .field("entry", &self.entry)
+            .field("unit", &U::UNIT)
+            .finish()
+    }
+}
+
+impl<E: Entry, U: UnitTag> Entry for WithEntryUnit<E, U> {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        self.entry
+            .write(&mut DefaultUnitEntryWriter::<_, U>::new(writer))
+    }
+
+    fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+        self.entry.sample_group()
+    }
+}
+
+/// An [`EntryWriter`] that writes metrics that have no unit with the unit `U`, and forwards
+/// everything else to the wrapped writer unchanged. This is the writer used by
+/// [`WithEntryUnit`].
+pub struct DefaultUnitEntryWriter<'w, W, U> {
+    writer: &'w mut W,
+    unit: PhantomData<U>,
+}
+
+impl<'w, W, U> DefaultUnitEntryWriter<'w, W, U> {
+    /// Wrap `writer`
+    pub fn new(writer: &'w mut W) -> Self {
+        Self {
+            writer,
+            unit: PhantomData,
+        }
+    }
+}
+
+impl<'a, W: EntryWriter<'a>, U: UnitTag> EntryWriter<'a> for DefaultUnitEntryWriter<'_, W, U> {
+    fn timestamp(&mut self, timestamp: SystemTime) {
+        self.writer.timestamp(timestamp)
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        self.writer.value(
+            name,
+            &DefaultUnitValue {
+                value,
+                unit: PhantomData::<U>,
+            },
+        )
+    }
+
+    fn config(&mut self, config: &'a dyn EntryConfig) {
+        self.writer.config(config);
+    }
+}
+
+struct DefaultUnitValue<'v, V: ?Sized, U> {
+    value: &'v V,
+    unit: PhantomData<U>,
+}
+
+impl<V: Value + ?Sized, U: UnitTag> Value for DefaultUnitValue<'_, V, U> {
+    fn write(&self, writer: impl ValueWriter) {
+        self.value.write(DefaultUnitValueWriter {
+            writer,
+            unit: PhantomData::<U>,
+        })
+    }
+}
+
+struct DefaultUnitValueWriter<W, U> {
+    writer: W,
+    unit: PhantomData<U>,
+}
+
+impl<W: ValueWriter, U: UnitTag> ValueWriter for DefaultUnitValueWriter<W, U> {
+    fn string(self, value: &str) {
+        self.writer.string(value)
+    }
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        unit: Unit,
+        dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        flags: MetricFlags<'_>,
+    ) {
+        let unit = match unit {
+            Unit::None => U::UNIT,
+            unit => unit,
+        };
+        self.writer.metric(distribution, unit, dimensions, flags)
+    }
+
+    fn error(self, error: ValidationError) {
+        self.writer.error(error)
+    }
+
+    fn values<'a, V: Value + 'a>(self, values: impl IntoIterator<Item = &'a V>) {
+        // lists of values are not metrics, so there is no unit to default
+        self.writer.values(values)
+    }
+}
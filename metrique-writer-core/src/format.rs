@@ -17,4 +17,135 @@ pub trait Format {
         entry: &impl Entry,
         output: &mut impl io::Write,
     ) -> Result<(), IoStreamError>;
+
+    /// Like [`Format::format`], but also return the number of bytes written to `output`.
+    ///
+    /// The default implementation wraps `output` in a writer that counts the bytes written.
+    /// Formats that know how much they write (e.g. because they assemble the entry in a buffer
+    /// first) should override it to avoid the wrapper, and formats that wrap another format
+    /// should forward to it.
+    ///
+    /// If formatting fails, the bytes written before the failure are not reported.
+    fn format_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<usize, IoStreamError> {
+        let mut output = CountingWrite { output, written: 0 };
+        self.format(entry, &mut output)?;
+        Ok(output.written)
+    }
+
+    /// Like [`Format::format_counted`], but also report whether the entry was written at all or
+    /// dropped, e.g. by a sampler.
+    ///
+    /// The default implementation reports every entry as [`FormatOutcome::Emitted`]. Formats
+    /// that drop entries should override it, and formats that wrap another format should forward
+    /// to it.
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        self.format_counted(entry, output)
+            .map(FormatOutcome::Emitted)
+    }
+}
+
+/// Whether [`Format::format_outcome`] wrote an entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FormatOutcome {
+    /// The entry was written, with the number of bytes written
+    Emitted(usize),
+    /// The entry was dropped without writing anything, e.g. because it was sampled out
+    Dropped,
+}
+
+/// An [`io::Write`] that counts the bytes written to the wrapped output
+pub(crate) struct CountingWrite<'a, O> {
+    pub(crate) output: &'a mut O,
+    pub(crate) written: usize,
+}
+
+impl<O: io::Write> io::Write for CountingWrite<'_, O> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.output.write(buf)?;
+        self.written += written;
+        Ok(written)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let written = self.output.write_vectored(bufs)?;
+        self.written += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::{Format, FormatOutcome};
+    use crate::{Entry, EntryWriter, IoStreamError, ValidationError};
+
+    struct LineFormat;
+
+    impl Format for LineFormat {
+        fn format(
+            &mut self,
+            _entry: &impl Entry,
+            output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            output.write_all(b"line\n")?;
+            let written =
+                output.write_vectored(&[io::IoSlice::new(b"a"), io::IoSlice::new(b"b\n")])?;
+            assert_eq!(written, 3);
+            Ok(())
+        }
+    }
+
+    struct FailingFormat;
+
+    impl Format for FailingFormat {
+        fn format(
+            &mut self,
+            _entry: &impl Entry,
+            output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            output.write_all(b"partial")?;
+            Err(ValidationError::invalid("failed").into())
+        }
+    }
+
+    struct EmptyEntry;
+
+    impl Entry for EmptyEntry {
+        fn write<'a>(&'a self, _writer: &mut impl EntryWriter<'a>) {}
+    }
+
+    #[test]
+    fn format_counted_counts_bytes() {
+        let mut output = vec![];
+        let written = LineFormat.format_counted(&EmptyEntry, &mut output).unwrap();
+        assert_eq!(written, output.len());
+        assert_eq!(output, b"line\nab\n");
+
+        assert!(
+            FailingFormat
+                .format_counted(&EmptyEntry, &mut output)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn format_outcome_defaults_to_emitted() {
+        let mut output = vec![];
+        let outcome = LineFormat.format_outcome(&EmptyEntry, &mut output).unwrap();
+        assert_eq!(outcome, FormatOutcome::Emitted(output.len()));
+    }
 }
@@ -5,13 +5,13 @@
 #![deny(missing_docs)]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
-pub use crate::entry::{BoxEntry, Entry, EntryConfig, EntryWriter};
+pub use crate::entry::{BoxEntry, Entry, EntryConfig, EntryTimestamp, EntryWriter};
 pub use crate::global::GlobalEntrySink;
 pub use crate::sample::SampleGroup;
 pub use crate::sink::{AnyEntrySink, BoxEntrySink, EntrySink};
 pub use crate::stream::{EntryIoStream, IoStreamError};
 pub use crate::unit::{Convert, Unit};
-pub use crate::validate::{ValidationError, ValidationErrorBuilder};
+pub use crate::validate::{NameRules, ValidationError, ValidationErrorBuilder};
 pub use crate::value::{Distribution, MetricFlags, MetricValue, Observation, Value, ValueWriter};
 
 pub(crate) type CowStr = std::borrow::Cow<'static, str>;
@@ -5,7 +5,10 @@
 
 use std::{borrow::Cow, io};
 
-use crate::{Entry, IoStreamError, format::Format};
+use crate::{
+    Entry, IoStreamError,
+    format::{CountingWrite, Format},
+};
 
 /// Allows for sampleable formats, with a "sample rate" that will automatically compensate for entries that
 /// were sampled by that fraction. This allow services to trade a lower-accuracy metric for reduced time emitting and
@@ -21,6 +24,22 @@ pub trait SampledFormat: Format {
         output: &mut impl io::Write,
         rate: f32,
     ) -> Result<(), IoStreamError>;
+
+    /// Like [`SampledFormat::format_with_sample_rate`], but also return the number of bytes
+    /// written to `output`, see [`Format::format_counted`].
+    ///
+    /// Samplers use this to report how much an entry they kept wrote, through
+    /// [`Format::format_outcome`].
+    fn format_with_sample_rate_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+        rate: f32,
+    ) -> Result<usize, IoStreamError> {
+        let mut output = CountingWrite { output, written: 0 };
+        self.format_with_sample_rate(entry, &mut output, rate)?;
+        Ok(output.written)
+    }
 }
 
 /// A type that can be converted to a sample group
@@ -32,6 +51,9 @@ pub trait SampledFormat: Format {
 /// For example, when writing metrics for an API server, it is common to mark the operation (route)
 /// and status code as sample groups, to ensure every (operation, status code) gets a metric.
 ///
+/// `SampleGroup` is implemented for `&'static str` and for the primitive integer types, so a
+/// numeric status code can be used as a sample group directly.
+///
 /// [congress sampling]: https://docs.rs/metrique-writer/0.1/metrique_writer/sample/struct.CongressSample.html
 #[diagnostic::on_unimplemented(
     message = "`{Self}` cannot be used as a sample group",
@@ -48,3 +70,34 @@ impl SampleGroup for &'static str {
         Cow::Borrowed(self)
     }
 }
+
+// Integers, e.g. status codes, are rendered with `itoa`, which avoids going through
+// `fmt::Display`.
+macro_rules! integer_sample_group {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl SampleGroup for $ty {
+                fn as_sample_group(&self) -> Cow<'static, str> {
+                    Cow::Owned(itoa::Buffer::new().format(*self).to_owned())
+                }
+            }
+        )*
+    };
+}
+
+integer_sample_group!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize
+);
+
+#[cfg(test)]
+mod tests {
+    use super::SampleGroup;
+
+    #[test]
+    fn integer_sample_groups() {
+        assert_eq!(200u16.as_sample_group(), "200");
+        assert_eq!(0u8.as_sample_group(), "0");
+        assert_eq!((-1i32).as_sample_group(), "-1");
+        assert_eq!(u64::MAX.as_sample_group(), "18446744073709551615");
+    }
+}
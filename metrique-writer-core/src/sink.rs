@@ -31,6 +31,37 @@ pub trait EntrySink<E: Entry> {
     /// valuable for monitoring service health.
     fn append(&self, entry: E);
 
+    /// Append all of `entries` to the in-memory buffer, in order, with the same contract as
+    /// [`append()`](EntrySink::append).
+    ///
+    /// Entries are ordered as if each of them was passed to `append()` in turn. The default
+    /// implementation does exactly that, but sinks can override it to pay for synchronization
+    /// once per batch rather than once per entry (e.g. `VecEntrySink` locks its buffer once).
+    ///
+    /// # Example
+    /// ```
+    /// # use metrique_writer::{Entry, sink::VecEntrySink, EntrySink};
+    /// #[derive(Entry, PartialEq, Debug)]
+    /// struct MyEntry {
+    ///     counter: u64,
+    /// }
+    ///
+    /// let sink = VecEntrySink::default();
+    /// sink.append_batch((0..3).map(|counter| MyEntry { counter }));
+    /// assert_eq!(
+    ///     sink.drain(),
+    ///     &[MyEntry { counter: 0 }, MyEntry { counter: 1 }, MyEntry { counter: 2 }]
+    /// );
+    /// ```
+    fn append_batch(&self, entries: impl IntoIterator<Item = E>)
+    where
+        Self: Sized,
+    {
+        for entry in entries {
+            self.append(entry);
+        }
+    }
+
     /// Request the sink to flush its contents to some sort of persistent storage. The returned
     /// `FlushWait` can be used to tell when the sink is flushed.
     ///
@@ -69,4 +69,25 @@ pub trait EntryIoStream {
     /// Note that some writers rely on regular flush
     /// calls to interleave IO operations that won't tear across entries.
     fn flush(&mut self) -> io::Result<()>;
+
+    /// The total number of bytes this stream has written to its output so far, or `None` if it
+    /// doesn't track it.
+    ///
+    /// This is used by `BackgroundQueueBuilder::max_buffered_bytes` to flush based on the amount
+    /// of data written, and by `RetryStream` to tell whether a failed entry wrote anything, so it
+    /// should include the output of entries that failed. Streams that wrap another stream should
+    /// forward this.
+    fn bytes_written(&self) -> Option<u64> {
+        None
+    }
+
+    /// The total number of entries this stream accepted but dropped without writing them, e.g.
+    /// because its format [sampled them out](crate::format::FormatOutcome::Dropped), or `None`
+    /// if it doesn't track it.
+    ///
+    /// This is used by the background queue to report how many entries were sampled out.
+    /// Streams that wrap another stream should forward this.
+    fn entries_sampled_out(&self) -> Option<u64> {
+        None
+    }
 }
@@ -1,7 +1,7 @@
 // Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::fmt;
+use std::{borrow::Cow, fmt, fmt::Write as _};
 
 /// An error type that describes why an [`crate::Entry`] isn't valid. This can be because it violated general contracts
 /// (e.g. writing multiple values with the same name) or because it violated a format-specific contract (e.g. using a
@@ -98,9 +98,99 @@ impl ValidationErrorBuilder {
     }
 }
 
+/// Shared rules for validating metric names, which formats can opt into instead of
+/// re-implementing their own checks.
+///
+/// By default, names must be non-empty and must not contain control characters. Formats can
+/// add their own reserved names with [`NameRules::reserved`].
+///
+/// # Example
+/// ```
+/// # use metrique_writer_core::NameRules;
+/// const RULES: NameRules<'static> = NameRules::new().reserved(&["_aws"]);
+///
+/// assert!(RULES.validate("Latency").is_ok());
+/// assert!(RULES.validate("").is_err());
+/// assert!(RULES.validate("_aws").is_err());
+/// assert!(RULES.validate("bad\nname").is_err());
+/// assert_eq!(RULES.escape("bad\nname"), "bad\\u{a}name");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct NameRules<'a> {
+    reserved: &'a [&'a str],
+    allow_control_characters: bool,
+}
+
+impl Default for NameRules<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> NameRules<'a> {
+    /// Rules that reject empty names and names containing control characters.
+    pub const fn new() -> Self {
+        Self {
+            reserved: &[],
+            allow_control_characters: false,
+        }
+    }
+
+    /// Reject names that are exactly equal to one of `reserved`.
+    pub const fn reserved(mut self, reserved: &'a [&'a str]) -> Self {
+        self.reserved = reserved;
+        self
+    }
+
+    /// If `allow` is true, don't reject names containing control characters. This is useful for
+    /// formats that escape them on output.
+    pub const fn allow_control_characters(mut self, allow: bool) -> Self {
+        self.allow_control_characters = allow;
+        self
+    }
+
+    /// Check `name` against these rules, returning a [`ValidationError`] for the offending
+    /// name if it is invalid.
+    ///
+    /// Names containing control characters are reported in their [escaped](Self::escape) form.
+    pub fn validate(&self, name: &str) -> Result<(), ValidationError> {
+        if name.is_empty() {
+            return Err(ValidationError::invalid("name can't be empty").for_field(name));
+        }
+        if let Some(reserved) = self.reserved.iter().find(|reserved| **reserved == name) {
+            return Err(
+                ValidationError::invalid(format!("name can't be `{reserved}`")).for_field(name),
+            );
+        }
+        if !self.allow_control_characters && name.chars().any(char::is_control) {
+            return Err(
+                ValidationError::invalid("name can't contain control characters")
+                    .for_field(&self.escape(name)),
+            );
+        }
+        Ok(())
+    }
+
+    /// Escape any control characters in `name` as `\u{..}`, borrowing `name` if there are none.
+    pub fn escape<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        if !name.chars().any(char::is_control) {
+            return Cow::Borrowed(name);
+        }
+        let mut escaped = String::with_capacity(name.len() + 8);
+        for c in name.chars() {
+            if c.is_control() {
+                let _ = write!(escaped, "{}", c.escape_unicode());
+            } else {
+                escaped.push(c);
+            }
+        }
+        Cow::Owned(escaped)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::validate::ValidationError;
+    use crate::validate::{NameRules, ValidationError};
 
     #[test]
     fn record_invalid() {
@@ -157,4 +247,69 @@ mod tests {
         assert!(format!("{}", error).contains(s));
         assert!(format!("{:?}", error).contains(s));
     }
+
+    #[test]
+    fn name_rules_reject_empty_names() {
+        let err = NameRules::new().validate("").unwrap_err();
+        assert_contains(&err, "for ``: name can't be empty");
+    }
+
+    #[test]
+    fn name_rules_reject_reserved_names() {
+        let rules = NameRules::new().reserved(&["_aws", "Timestamp"]);
+        assert_contains(
+            &rules.validate("_aws").unwrap_err(),
+            "for `_aws`: name can't be `_aws`",
+        );
+        assert_contains(
+            &rules.validate("Timestamp").unwrap_err(),
+            "for `Timestamp`: name can't be `Timestamp`",
+        );
+        // reserved names are matched exactly
+        assert!(rules.validate("_aws_").is_ok());
+        assert!(rules.validate("timestamp").is_ok());
+        assert!(NameRules::new().validate("_aws").is_ok());
+    }
+
+    #[test]
+    fn name_rules_control_characters() {
+        let rules = NameRules::new();
+        for name in ["a\nb", "\t", "a\u{0}", "a\u{7f}", "a\u{85}"] {
+            let err = rules.validate(name).unwrap_err();
+            assert_contains(&err, "name can't contain control characters");
+            assert!(!err.to_string().chars().any(char::is_control), "{err:?}");
+        }
+        assert!(
+            rules
+                .validate("a\nb")
+                .unwrap_err()
+                .to_string()
+                .contains("for `a\\u{a}b`")
+        );
+        assert!(
+            rules
+                .allow_control_characters(true)
+                .validate("a\nb")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn name_rules_accept_unusual_but_valid_names() {
+        let rules = NameRules::new();
+        for name in [" ", "a b", "ünïcödé", "🦆", "a.b/c:d", "\\n", "\""] {
+            assert!(rules.validate(name).is_ok(), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn name_rules_escape() {
+        let rules = NameRules::new();
+        assert!(matches!(
+            rules.escape("plain"),
+            std::borrow::Cow::Borrowed("plain")
+        ));
+        assert_eq!(rules.escape("a\tb\r\n"), "a\\u{9}b\\u{d}\\u{a}");
+        assert_eq!(rules.escape("🦆\u{1b}"), "🦆\\u{1b}");
+    }
 }
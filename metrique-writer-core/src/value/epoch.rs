@@ -0,0 +1,125 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::{Duration, SystemTime};
+
+use super::{MetricValue, Observation, Value, ValueWriter};
+use crate::{Unit, unit, value::MetricFlags};
+
+/// Writes a [`SystemTime`] as a metric containing the number of milliseconds since the Unix
+/// epoch.
+///
+/// This is meant for event times that are used in math downstream (e.g. to compute a lag), as
+/// opposed to the entry timestamp (`#[entry(timestamp)]`), or a timestamp written as a string
+/// property. The value is written as an unsigned observation without a unit.
+///
+/// Times before the Unix epoch are clamped to 0.
+///
+/// Anything that converts into a [`SystemTime`] can be wrapped, including the `SystemTime` of
+/// `metrique-timesource`.
+///
+/// # Example
+/// ```
+/// # use std::time::{Duration, SystemTime};
+/// # use metrique_writer::Entry;
+/// # use metrique_writer::test_util::to_test_entry;
+/// use metrique_writer_core::value::AsEpochMillis;
+///
+/// #[derive(Entry)]
+/// struct EventMetrics {
+///     event_time: AsEpochMillis,
+/// }
+///
+/// let entry = to_test_entry(EventMetrics {
+///     event_time: AsEpochMillis::new(SystemTime::UNIX_EPOCH + Duration::from_secs(2)),
+/// });
+/// assert_eq!(entry.metrics["event_time"], 2000);
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AsEpochMillis(SystemTime);
+
+impl AsEpochMillis {
+    /// Create an [`AsEpochMillis`] writing `time`
+    pub fn new(time: impl Into<SystemTime>) -> Self {
+        Self(time.into())
+    }
+
+    /// Returns the wrapped time
+    pub fn time(&self) -> SystemTime {
+        self.0
+    }
+
+    /// Returns the number of milliseconds since the Unix epoch, or 0 for times before it
+    pub fn epoch_millis(&self) -> u64 {
+        let since_epoch = self
+            .0
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO);
+        u64::try_from(since_epoch.as_millis()).unwrap_or(u64::MAX)
+    }
+}
+
+impl From<SystemTime> for AsEpochMillis {
+    fn from(time: SystemTime) -> Self {
+        Self(time)
+    }
+}
+
+impl Value for AsEpochMillis {
+    fn write(&self, writer: impl ValueWriter) {
+        writer.metric(
+            [Observation::Unsigned(self.epoch_millis())],
+            Unit::None,
+            [],
+            MetricFlags::empty(),
+        )
+    }
+}
+
+impl MetricValue for AsEpochMillis {
+    type Unit = unit::None;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::AsEpochMillis;
+    use crate::{EntryWriter, test_stream::DummyEntryWriter};
+
+    fn written(value: AsEpochMillis) -> Vec<(String, String)> {
+        let mut writer = DummyEntryWriter::default();
+        writer.value("EventTime", &value);
+        writer.0
+    }
+
+    #[test]
+    fn writes_epoch_millis() {
+        // 2023-11-14T22:13:20.123Z
+        let time = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        assert_eq!(AsEpochMillis::new(time).epoch_millis(), 1_700_000_000_123);
+        assert_eq!(
+            written(time.into()),
+            [(
+                "EventTime".to_string(),
+                "[Unsigned(1700000000123)] None []".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn truncates_sub_millisecond_precision() {
+        let time = SystemTime::UNIX_EPOCH + Duration::from_micros(1_999);
+        assert_eq!(AsEpochMillis::new(time).epoch_millis(), 1);
+    }
+
+    #[test]
+    fn pre_epoch_times_clamp_to_zero() {
+        let time = SystemTime::UNIX_EPOCH - Duration::from_secs(60);
+        assert_eq!(AsEpochMillis::new(time).epoch_millis(), 0);
+        assert_eq!(
+            written(time.into()),
+            [("EventTime".to_string(), "[Unsigned(0)] None []".to_string())]
+        );
+    }
+}
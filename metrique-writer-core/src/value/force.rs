@@ -210,4 +210,12 @@ impl<S: EntryIoStream, FLAGS: FlagConstructor> EntryIoStream for ForceFlag<S, FL
     fn flush(&mut self) -> io::Result<()> {
         self.0.flush()
     }
+
+    fn bytes_written(&self) -> Option<u64> {
+        self.0.bytes_written()
+    }
+
+    fn entries_sampled_out(&self) -> Option<u64> {
+        self.0.entries_sampled_out()
+    }
 }
@@ -169,6 +169,122 @@ where
     }
 }
 
+/// A formatter that carries runtime configuration, such as a precision, and formats values
+/// using `&self`. Used for `#[metrics]`'s `#[metrics(format = EXPR)]` when `EXPR` is a value
+/// rather than a type, for example `#[metrics(format = Precision::new(3))]`.
+///
+/// Stateless formatters should implement [`ValueFormatter`] instead, which does not need to
+/// store a formatter next to the value. Lifting over types such as [`Arc`] and [`Option`]
+/// works the same way as for [`ValueFormatter`].
+///
+/// ```
+/// # use metrique_writer::ValueWriter;
+/// # use metrique_writer::value::ConfiguredValueFormatter;
+/// struct Precision(usize);
+///
+/// impl ConfiguredValueFormatter<f64> for Precision {
+///     fn format_value(&self, writer: impl ValueWriter, value: &f64) {
+///         writer.string(&format!("{value:.*}", self.0));
+///     }
+/// }
+/// ```
+pub trait ConfiguredValueFormatter<V: ?Sized, L: Liftability = Lifted> {
+    /// Write `value` to `writer`
+    fn format_value(&self, writer: impl ValueWriter, value: &V);
+}
+
+impl<V: ?Sized, F: ?Sized> ConfiguredValueFormatter<&V> for F
+where
+    F: ConfiguredValueFormatter<V>,
+{
+    fn format_value(&self, writer: impl ValueWriter, value: &&V) {
+        <Self as ConfiguredValueFormatter<V>>::format_value(self, writer, value)
+    }
+}
+
+impl<V, F: ?Sized> ConfiguredValueFormatter<Option<V>> for F
+where
+    F: ConfiguredValueFormatter<V>,
+{
+    fn format_value(&self, writer: impl ValueWriter, value: &Option<V>) {
+        if let Some(value) = value {
+            <Self as ConfiguredValueFormatter<V>>::format_value(self, writer, value)
+        }
+    }
+}
+
+impl<V: ?Sized, F: ?Sized> ConfiguredValueFormatter<Box<V>> for F
+where
+    F: ConfiguredValueFormatter<V>,
+{
+    fn format_value(&self, writer: impl ValueWriter, value: &Box<V>) {
+        <Self as ConfiguredValueFormatter<V>>::format_value(self, writer, value)
+    }
+}
+
+impl<V: ?Sized, F: ?Sized> ConfiguredValueFormatter<Arc<V>> for F
+where
+    F: ConfiguredValueFormatter<V>,
+{
+    fn format_value(&self, writer: impl ValueWriter, value: &Arc<V>) {
+        <Self as ConfiguredValueFormatter<V>>::format_value(self, writer, value)
+    }
+}
+
+impl<V: ToOwned + ?Sized, F: ?Sized> ConfiguredValueFormatter<Cow<'_, V>> for F
+where
+    F: ConfiguredValueFormatter<V>,
+{
+    fn format_value(&self, writer: impl ValueWriter, value: &Cow<V>) {
+        <Self as ConfiguredValueFormatter<V>>::format_value(self, writer, value)
+    }
+}
+
+#[doc(hidden)]
+/// A value stored together with the [ConfiguredValueFormatter] used to format it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WithFormatter<V, VF> {
+    value: V,
+    formatter: VF,
+}
+
+impl<V, VF> WithFormatter<V, VF> {
+    #[doc(hidden)]
+    pub fn new(value: V, formatter: VF) -> Self {
+        Self { value, formatter }
+    }
+
+    #[doc(hidden)]
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+}
+
+#[doc(hidden)]
+/// A wrapper for a [WithFormatter] that formats the value using its formatter
+#[derive(Debug)]
+pub struct ConfiguredFormattedValue<'a, V, VF, L = Lifted>(
+    PhantomData<L>,
+    &'a WithFormatter<V, VF>,
+);
+
+impl<'a, V, VF, L> ConfiguredFormattedValue<'a, V, VF, L> {
+    #[doc(hidden)]
+    pub fn new(value: &'a WithFormatter<V, VF>) -> Self {
+        Self(PhantomData, value)
+    }
+}
+
+#[diagnostic::do_not_recommend]
+impl<V, VF, L: Liftability> super::Value for ConfiguredFormattedValue<'_, V, VF, L>
+where
+    VF: ConfiguredValueFormatter<V, L>,
+{
+    fn write(&self, writer: impl ValueWriter) {
+        self.1.formatter.format_value(writer, &self.1.value);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use serde_json::json;
@@ -7,15 +7,24 @@
 //! as well as string properties.
 
 mod dimensions;
+mod epoch;
 mod flags;
 mod force;
 mod formatter;
 mod primitive;
+mod ratio;
+mod top_k;
 
 pub use dimensions::{WithDimension, WithDimensions, WithVecDimensions};
+pub use epoch::AsEpochMillis;
 pub use force::{FlagConstructor, ForceFlag};
-pub use formatter::{FormattedValue, Lifted, NotLifted, ToString, ValueFormatter};
+pub use formatter::{
+    ConfiguredFormattedValue, ConfiguredValueFormatter, FormattedValue, Lifted, NotLifted,
+    ToString, ValueFormatter, WithFormatter,
+};
+pub use ratio::{OnZeroDenominator, Ratio};
 use std::{borrow::Cow, fmt::Write, sync::Arc};
+pub use top_k::TopK;
 
 pub use flags::{Distribution, MetricFlags, MetricOptions};
 
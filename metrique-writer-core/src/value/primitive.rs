@@ -1,7 +1,10 @@
 // Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::time::Duration;
+use std::{
+    num::{NonZeroU8, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroUsize},
+    time::Duration,
+};
 
 use super::{MetricValue, Observation, Value, ValueWriter};
 use crate::{
@@ -74,6 +77,25 @@ impl MetricValue for usize {
     type Unit = unit::None;
 }
 
+macro_rules! non_zero {
+    ($($t:ty),+) => {
+        $(
+            impl Value for $t {
+                #[inline]
+                fn write(&self, writer: impl ValueWriter) {
+                    self.get().write(writer)
+                }
+            }
+
+            impl MetricValue for $t {
+                type Unit = unit::None;
+            }
+        )+
+    };
+}
+
+non_zero!(NonZeroU64, NonZeroU32, NonZeroU16, NonZeroU8, NonZeroUsize);
+
 macro_rules! float {
     ($t:ty) => {
         impl Value for $t {
@@ -134,3 +156,62 @@ impl<V: Value> Value for [V] {
         writer.values(self.iter());
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io,
+        num::{NonZeroU32, NonZeroU64},
+        time::SystemTime,
+    };
+
+    use metrique_writer::{Entry, format::Format};
+    use metrique_writer_format_emf::Emf;
+    use serde_json::json;
+
+    #[derive(Entry)]
+    struct NonZeroMetric {
+        #[entry(timestamp)]
+        timestamp: SystemTime,
+        request_id: NonZeroU64,
+        retries: NonZeroU32,
+        missing: Option<NonZeroU32>,
+    }
+
+    #[test]
+    fn non_zero_writes_underlying_integer() {
+        let mut emf = Emf::no_validations("MyNS".into(), vec![vec![]]);
+        let mut output = io::Cursor::new(vec![]);
+        emf.format(
+            &NonZeroMetric {
+                timestamp: SystemTime::UNIX_EPOCH,
+                request_id: NonZeroU64::new(u64::MAX).unwrap(),
+                retries: NonZeroU32::new(3).unwrap(),
+                missing: None,
+            },
+            &mut output,
+        )
+        .unwrap();
+        let output: serde_json::Value = String::from_utf8(output.into_inner())
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_json_diff::assert_json_eq!(
+            output,
+            json!({
+                "_aws": {
+                    "CloudWatchMetrics": [
+                        {
+                            "Namespace": "MyNS",
+                            "Dimensions": [[]],
+                            "Metrics": [{"Name": "request_id"}, {"Name": "retries"}]
+                        }
+                    ],
+                    "Timestamp": 0
+                },
+                "request_id": u64::MAX,
+                "retries": 3,
+            })
+        );
+    }
+}
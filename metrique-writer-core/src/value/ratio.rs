@@ -0,0 +1,206 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::marker::PhantomData;
+
+use crate::{
+    MetricValue, Observation, Unit, Value, ValueWriter,
+    unit::{self, UnitTag},
+    value::MetricFlags,
+};
+
+/// What a [`Ratio`] writes when its denominator is zero.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OnZeroDenominator {
+    /// Write a ratio of `0`.
+    #[default]
+    Zero,
+    /// Write nothing, so the metric is absent from the entry.
+    Skip,
+}
+
+/// A [`Value`] that writes the ratio of a numerator to a denominator, e.g. an error rate
+/// computed as `errors / requests`.
+///
+/// A zero denominator doesn't write `NaN` or infinity (which most formats reject). Instead, the
+/// ratio is written as `0` or skipped, depending on [`Ratio::on_zero_denominator`].
+///
+/// The ratio is written as a floating point number with the unit `U`, which is [`unit::None`]
+/// by default. When `U` is [`unit::Percent`] (see [`Ratio::percent`]), the ratio is multiplied
+/// by 100.
+///
+/// # Example
+/// ```
+/// # use metrique_writer::Entry;
+/// # use metrique_writer::test_util::to_test_entry;
+/// use metrique_writer_core::value::{OnZeroDenominator, Ratio};
+///
+/// #[derive(Entry)]
+/// struct Metrics {
+///     error_rate: Ratio,
+///     error_percent: Ratio<metrique_writer_core::unit::Percent>,
+///     throttle_rate: Ratio,
+/// }
+///
+/// let entry = to_test_entry(Metrics {
+///     error_rate: Ratio::from_counts(1, 4),
+///     error_percent: Ratio::from_counts(1, 4).percent(),
+///     throttle_rate: Ratio::from_counts(0, 0).on_zero_denominator(OnZeroDenominator::Skip),
+/// });
+/// assert_eq!(entry.metrics["error_rate"], 0.25);
+/// assert_eq!(entry.metrics["error_percent"], 25.0);
+/// assert!(!entry.metrics.contains_key("throttle_rate"));
+/// ```
+pub struct Ratio<U = unit::None> {
+    numerator: f64,
+    denominator: f64,
+    on_zero: OnZeroDenominator,
+    _unit: PhantomData<U>,
+}
+
+impl Ratio {
+    /// Create a [`Ratio`] of `numerator` to `denominator`, writing `0` if `denominator` is zero.
+    pub fn new(numerator: f64, denominator: f64) -> Self {
+        Self {
+            numerator,
+            denominator,
+            on_zero: OnZeroDenominator::default(),
+            _unit: PhantomData,
+        }
+    }
+
+    /// Create a [`Ratio`] of two counts, writing `0` if `denominator` is zero.
+    pub fn from_counts(numerator: u64, denominator: u64) -> Self {
+        Self::new(numerator as f64, denominator as f64)
+    }
+}
+
+impl<U> Ratio<U> {
+    /// Set what is written when the denominator is zero.
+    pub fn on_zero_denominator(mut self, on_zero: OnZeroDenominator) -> Self {
+        self.on_zero = on_zero;
+        self
+    }
+
+    /// Write the ratio as a [`unit::Percent`], multiplied by 100.
+    pub fn percent(self) -> Ratio<unit::Percent> {
+        Ratio {
+            numerator: self.numerator,
+            denominator: self.denominator,
+            on_zero: self.on_zero,
+            _unit: PhantomData,
+        }
+    }
+
+    /// Return the ratio that is written, or `None` if nothing is written.
+    ///
+    /// This is not scaled by the unit.
+    pub fn value(&self) -> Option<f64> {
+        if self.denominator == 0.0 {
+            match self.on_zero {
+                OnZeroDenominator::Zero => Some(0.0),
+                OnZeroDenominator::Skip => None,
+            }
+        } else {
+            Some(self.numerator / self.denominator)
+        }
+    }
+}
+
+impl<U> Clone for Ratio<U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<U> Copy for Ratio<U> {}
+
+impl<U: UnitTag> std::fmt::Debug for Ratio<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Ratio")
+            .field("numerator", &self.numerator)
+            .field("denominator", &self.denominator)
+            .field("on_zero", &self.on_zero)
+            .field("unit", &U::UNIT)
+            .finish()
+    }
+}
+
+impl<U: UnitTag> Value for Ratio<U> {
+    fn write(&self, writer: impl ValueWriter) {
+        let Some(ratio) = self.value() else {
+            return;
+        };
+        let scale = if U::UNIT == Unit::Percent { 100.0 } else { 1.0 };
+        writer.metric(
+            [Observation::Floating(ratio * scale)],
+            U::UNIT,
+            [],
+            MetricFlags::empty(),
+        );
+    }
+}
+
+impl<U: UnitTag> MetricValue for Ratio<U> {
+    type Unit = U;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OnZeroDenominator, Ratio};
+    use crate::{Observation, Unit, ValidationError, Value, ValueWriter, value::MetricFlags};
+
+    struct Capture<'a>(&'a mut Option<(Vec<Observation>, Unit)>);
+    impl ValueWriter for Capture<'_> {
+        fn string(self, value: &str) {
+            panic!("unexpected string {value}");
+        }
+        fn metric<'a>(
+            self,
+            distribution: impl IntoIterator<Item = Observation>,
+            unit: Unit,
+            _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+            _flags: MetricFlags<'_>,
+        ) {
+            *self.0 = Some((distribution.into_iter().collect(), unit));
+        }
+        fn error(self, error: ValidationError) {
+            panic!("unexpected error {error}");
+        }
+    }
+
+    fn written(value: &impl Value) -> Option<(Vec<Observation>, Unit)> {
+        let mut out = None;
+        value.write(Capture(&mut out));
+        out
+    }
+
+    #[test]
+    fn writes_ratio() {
+        assert_eq!(
+            written(&Ratio::from_counts(3, 4)),
+            Some((vec![Observation::Floating(0.75)], Unit::None))
+        );
+        assert_eq!(
+            written(&Ratio::new(1.0, 8.0).percent()),
+            Some((vec![Observation::Floating(12.5)], Unit::Percent))
+        );
+    }
+
+    #[test]
+    fn zero_denominator() {
+        assert_eq!(
+            written(&Ratio::from_counts(0, 0)),
+            Some((vec![Observation::Floating(0.0)], Unit::None))
+        );
+        assert_eq!(
+            written(&Ratio::from_counts(5, 0).percent()),
+            Some((vec![Observation::Floating(0.0)], Unit::Percent))
+        );
+        let skipped = Ratio::from_counts(5, 0).on_zero_denominator(OnZeroDenominator::Skip);
+        assert_eq!(skipped.value(), None);
+        assert_eq!(written(&skipped), None);
+        assert_eq!(written(&skipped.percent()), None);
+    }
+}
@@ -0,0 +1,274 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{Value, ValueWriter};
+
+/// A [`Value`] that tracks the most frequent string values observed over its lifetime, and
+/// writes the top `k` of them as a property.
+///
+/// This is useful to diagnose dimension cardinality, for example to find which operations or
+/// customers dominate a high-cardinality dimension.
+///
+/// Frequencies are estimated using the [Space-Saving] algorithm with a bounded number of
+/// counters (by default, `4 * k`), so memory use does not grow with the number of distinct
+/// values. Counts are exact as long as no more distinct values than counters have been
+/// observed. Afterwards, a reported count can overestimate the true count by at most the
+/// smallest count being tracked, but any value whose true frequency is larger than that is
+/// guaranteed to be tracked.
+///
+/// The top values are written as a list (see [`ValueWriter::values`]) of `value=count`
+/// strings, ordered from most to least frequent. Nothing is written if no values were observed.
+///
+/// # Thread safety
+///
+/// [`TopK::observe`] takes `&self`: the counters are protected by an internal [`Mutex`], so a
+/// `TopK` can be shared between threads (e.g. in an [`Arc`](std::sync::Arc)) and observed into
+/// concurrently while also being held in a metric entry. Writing the value takes a snapshot
+/// under the same lock, so an entry reports the state at the time it is written, not the time it
+/// was closed. Observing a value is O(1) when it is already tracked, and O(counters) when it
+/// needs to evict one.
+///
+/// # Example
+/// ```
+/// # use std::sync::Arc;
+/// # use metrique::unit_of_work::metrics;
+/// # use metrique_writer::test_util::test_metric;
+/// use metrique_writer_core::value::TopK;
+///
+/// #[metrics]
+/// struct PeriodicMetrics {
+///     #[metrics(no_close)]
+///     top_operations: Arc<TopK>,
+/// }
+///
+/// let top_operations = Arc::new(TopK::new(2));
+/// for operation in ["GetItem", "PutItem", "GetItem", "Query", "GetItem", "PutItem"] {
+///     top_operations.observe(operation);
+/// }
+///
+/// let entry = test_metric(PeriodicMetrics { top_operations });
+/// assert_eq!(entry.values["top_operations"], "GetItem=3,PutItem=2");
+/// ```
+///
+/// [Space-Saving]: https://doi.org/10.1007/978-3-540-30570-5_27
+#[derive(Debug)]
+pub struct TopK {
+    k: usize,
+    capacity: usize,
+    counters: Mutex<HashMap<String, u64>>,
+}
+
+impl TopK {
+    /// Create a new [`TopK`] reporting the `k` most frequent values, with `4 * k` counters.
+    pub fn new(k: usize) -> Self {
+        Self::with_counters(k, k.saturating_mul(4))
+    }
+
+    /// Create a new [`TopK`] reporting the `k` most frequent values, using `counters` counters
+    /// to estimate frequencies.
+    ///
+    /// More counters give more accurate estimates at the cost of memory and slower eviction.
+    /// `counters` is raised to `k` if it is smaller.
+    pub fn with_counters(k: usize, counters: usize) -> Self {
+        let capacity = counters.max(k);
+        Self {
+            k,
+            capacity,
+            counters: Mutex::new(HashMap::with_capacity(capacity)),
+        }
+    }
+
+    /// Record an occurrence of `value`.
+    pub fn observe(&self, value: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(count) = counters.get_mut(value) {
+            *count += 1;
+            return;
+        }
+        if counters.len() < self.capacity {
+            counters.insert(value.to_owned(), 1);
+            return;
+        }
+        // Space-Saving: replace the least frequent value, inheriting its count
+        let (min_value, min_count) = counters
+            .iter()
+            .min_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(value, count)| (value.clone(), *count))
+            .expect("capacity is non-zero and the map is full");
+        counters.remove(&min_value);
+        counters.insert(value.to_owned(), min_count + 1);
+    }
+
+    /// Return the (at most) `k` most frequent values with their estimated counts, from most to
+    /// least frequent. Ties are ordered by value.
+    pub fn top(&self) -> Vec<(String, u64)> {
+        let counters = self.counters.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut top = counters
+            .iter()
+            .map(|(value, count)| (value.clone(), *count))
+            .collect::<Vec<_>>();
+        drop(counters);
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        top.truncate(self.k);
+        top
+    }
+
+    /// Reset all counters.
+    pub fn clear(&self) {
+        self.counters
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+impl Value for TopK {
+    fn write(&self, writer: impl ValueWriter) {
+        let top = self.top();
+        if top.is_empty() {
+            return;
+        }
+        let entries = top
+            .into_iter()
+            .map(|(value, count)| {
+                let mut entry = value;
+                let _ = write!(entry, "={count}");
+                entry
+            })
+            .collect::<Vec<_>>();
+        writer.values(&entries);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::TopK;
+    use crate::{Observation, Unit, ValidationError, Value, ValueWriter, value::MetricFlags};
+
+    fn observe_all(top_k: &TopK, values: &[(&str, usize)]) {
+        for (value, count) in values {
+            for _ in 0..*count {
+                top_k.observe(value);
+            }
+        }
+    }
+
+    fn owned(top: &[(&str, u64)]) -> Vec<(String, u64)> {
+        top.iter().map(|(v, c)| (v.to_string(), *c)).collect()
+    }
+
+    #[test]
+    fn exact_counts_within_capacity() {
+        let top_k = TopK::new(3);
+        observe_all(&top_k, &[("a", 5), ("b", 2), ("c", 7), ("d", 1), ("e", 2)]);
+        assert_eq!(top_k.top(), owned(&[("c", 7), ("a", 5), ("b", 2)]));
+    }
+
+    #[test]
+    fn ties_are_ordered_by_value() {
+        let top_k = TopK::new(2);
+        observe_all(&top_k, &[("z", 1), ("y", 1), ("x", 1)]);
+        assert_eq!(top_k.top(), owned(&[("x", 1), ("y", 1)]));
+    }
+
+    #[test]
+    fn heavy_hitters_survive_eviction() {
+        let top_k = TopK::with_counters(2, 4);
+        // a long tail of distinct values interleaved with two heavy hitters
+        for i in 0..1000 {
+            top_k.observe("heavy1");
+            if i % 2 == 0 {
+                top_k.observe("heavy2");
+            }
+            top_k.observe(&format!("tail{i}"));
+        }
+        let top = top_k.top();
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].0, "heavy1");
+        assert_eq!(top[1].0, "heavy2");
+        // Space-Saving counts never underestimate
+        assert!(top[0].1 >= 1000);
+        assert!(top[1].1 >= 500);
+        assert!(top_k.counters.lock().unwrap().len() <= 4);
+    }
+
+    #[test]
+    fn empty_and_zero() {
+        assert!(TopK::new(3).top().is_empty());
+
+        let zero = TopK::new(0);
+        zero.observe("a");
+        assert!(zero.top().is_empty());
+
+        let top_k = TopK::with_counters(3, 1);
+        observe_all(&top_k, &[("a", 1), ("b", 1), ("c", 1), ("d", 1)]);
+        assert_eq!(top_k.counters.lock().unwrap().len(), 3);
+
+        top_k.clear();
+        assert!(top_k.top().is_empty());
+    }
+
+    #[test]
+    fn concurrent_observations() {
+        let top_k = Arc::new(TopK::new(2));
+        let threads = (0..4)
+            .map(|_| {
+                let top_k = top_k.clone();
+                std::thread::spawn(move || observe_all(&top_k, &[("a", 100), ("b", 50)]))
+            })
+            .collect::<Vec<_>>();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(top_k.top(), owned(&[("a", 400), ("b", 200)]));
+    }
+
+    struct CaptureString<'a>(&'a mut Option<String>);
+    impl ValueWriter for CaptureString<'_> {
+        fn string(self, value: &str) {
+            *self.0 = Some(value.to_owned());
+        }
+        fn metric<'a>(
+            self,
+            _distribution: impl IntoIterator<Item = Observation>,
+            _unit: Unit,
+            _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+            _flags: MetricFlags<'_>,
+        ) {
+            panic!("unexpected metric");
+        }
+        fn error(self, error: ValidationError) {
+            panic!("unexpected error {error}");
+        }
+    }
+
+    fn written(value: &impl Value) -> Option<String> {
+        let mut out = None;
+        value.write(CaptureString(&mut out));
+        out
+    }
+
+    #[test]
+    fn writes_values_as_property() {
+        let top_k = TopK::new(2);
+        assert_eq!(written(&top_k), None);
+
+        observe_all(&top_k, &[("GetItem", 3), ("PutItem", 2), ("Query", 1)]);
+        assert_eq!(written(&top_k).as_deref(), Some("GetItem=3,PutItem=2"));
+
+        // the value reflects the state at write time
+        observe_all(&top_k, &[("Query", 4)]);
+        assert_eq!(written(&top_k).as_deref(), Some("Query=5,GetItem=3"));
+    }
+}
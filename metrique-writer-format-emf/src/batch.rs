@@ -0,0 +1,632 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::time::{Duration, SystemTime};
+
+use metrique_writer::format::Format;
+use metrique_writer_core::{
+    Entry, EntryConfig, EntryIoStream, IoStreamError, MetricFlags, Observation, Unit,
+    ValidationError, Value, ValueWriter,
+};
+
+use crate::emf::{Emf, StorageMode, storage_mode, storage_mode_flags};
+use crate::rate_limit::rate_limited;
+
+/// The default maximum number of entries combined into a single log event.
+///
+/// EMF allows at most 100 values per metric in a log event.
+pub const DEFAULT_MAX_ENTRIES_PER_EVENT: usize = 100;
+
+// PutLogEvents accepts at most 10,000 log events per call, so there is no point in holding more
+// entries than that before writing them out
+const MAX_PENDING_ENTRIES: usize = 10_000;
+
+/// An [`EntryIoStream`] that formats entries with [`Emf`], combining entries that share a
+/// timestamp into a single log event.
+///
+/// Each EMF log event has a single timestamp and a single value for every property, and
+/// dimensions are properties. Entries can therefore only be combined when they have the same
+/// timestamp (to the millisecond) and exactly the same string properties. The metrics of
+/// combined entries are written as a single distribution per metric (e.g.
+/// `"Latency": {"Values": [3, 5], "Counts": [1, 1]}`), which CloudWatch Metrics aggregates
+/// the same way as separate log events. This mostly helps entries that only carry dimensions
+/// and metrics, e.g. periodically emitted or aggregated entries. Wide events with per-request
+/// properties (such as request IDs) are never combined, and are written as usual.
+///
+/// Entries are also written on their own if they can't be represented in a combined event:
+/// - entries that set an [`EntryConfig`] (e.g. [`EntryDimensions`](crate::EntryDimensions)),
+/// - entries that write lists of values, duplicate fields, or validation errors,
+/// - entries with a metric whose unit, per-metric dimensions or EMF flags differ from the same
+///   metric in the pending event.
+///
+/// Every entry is fully formatted when it is appended, so validation errors are still returned
+/// from [`next`](EntryIoStream::next). Combined events are written when the stream is flushed,
+/// so a background queue with a flush interval bounds how long entries are held. Entries keep
+/// the order of the first entry of their event. Note that this does more work per entry than
+/// an unbatched [`Emf`] stream, in exchange for writing fewer log events.
+///
+/// Formatting options that [`Emf`] applies to entries as a whole (namespaces, directives,
+/// always-present metrics, ...) apply to each combined event. Metric flags that [`Emf`]
+/// ignores (e.g. [`Distribution`](metrique_writer_core::value::Distribution)) are dropped
+/// from combined entries.
+///
+/// # Example
+/// ```
+/// # use std::time::SystemTime;
+/// # use metrique_writer::{Entry, EntryIoStream};
+/// # use metrique_writer_format_emf::{BatchedEmfStream, Emf};
+/// #[derive(Entry)]
+/// struct QueueMetrics {
+///     #[entry(timestamp)]
+///     timestamp: SystemTime,
+///     queue: &'static str,
+///     depth: u64,
+/// }
+///
+/// let emf = Emf::builder("MyApp".into(), vec![vec!["queue".into()]]).build();
+/// let mut output = Vec::new();
+/// let mut stream = BatchedEmfStream::new(emf, &mut output);
+/// for depth in [3, 5, 8] {
+///     stream
+///         .next(&QueueMetrics {
+///             timestamp: SystemTime::UNIX_EPOCH,
+///             queue: "orders",
+///             depth,
+///         })
+///         .unwrap();
+/// }
+/// stream.flush().unwrap();
+/// drop(stream);
+///
+/// // a single log event
+/// let output = String::from_utf8(output).unwrap();
+/// assert_eq!(output.lines().count(), 1);
+/// assert!(output.contains(r#""depth":{"Values":[3,5,8],"Counts":[1,1,1]}"#));
+/// ```
+pub struct BatchedEmfStream<O> {
+    emf: Emf,
+    output: O,
+    max_entries_per_event: usize,
+    pending: Vec<Pending>,
+    pending_entries: usize,
+    // indexes of the events that entries can still be added to, keyed by timestamp millis and
+    // properties
+    open: HashMap<EventKey, Vec<usize>>,
+    scratch: Vec<u8>,
+    bytes_written: u64,
+}
+
+type EventKey = (u128, Vec<(String, String)>);
+
+enum Pending {
+    Event(BatchedEvent),
+    Line(Vec<u8>),
+}
+
+impl<O: io::Write> BatchedEmfStream<O> {
+    /// Create a [`BatchedEmfStream`] that formats entries with `emf` and writes them to
+    /// `output`, combining up to [`DEFAULT_MAX_ENTRIES_PER_EVENT`] entries per log event.
+    pub fn new(emf: Emf, output: O) -> Self {
+        Self {
+            emf,
+            output,
+            max_entries_per_event: DEFAULT_MAX_ENTRIES_PER_EVENT,
+            pending: Vec::new(),
+            pending_entries: 0,
+            open: HashMap::new(),
+            scratch: Vec::new(),
+            bytes_written: 0,
+        }
+    }
+
+    /// Combine at most `max_entries_per_event` entries into a single log event.
+    ///
+    /// EMF allows at most 100 values per metric, so this should not be raised above
+    /// [`DEFAULT_MAX_ENTRIES_PER_EVENT`] unless entries write few distinct values. A value of
+    /// 0 or 1 disables batching.
+    pub fn max_entries_per_event(mut self, max_entries_per_event: usize) -> Self {
+        self.max_entries_per_event = max_entries_per_event;
+        self
+    }
+
+    /// Return the `Emf` format and output of this stream, dropping any entries that were not
+    /// written yet.
+    pub fn into_inner(self) -> (Emf, O) {
+        (self.emf, self.output)
+    }
+
+    fn add(&mut self, entry: CapturedEntry) {
+        let key = (entry.timestamp_millis(), entry.properties);
+        if let Some(open) = self.open.get_mut(&key) {
+            for (i, &index) in open.iter().enumerate() {
+                if let Pending::Event(event) = &mut self.pending[index]
+                    && event.try_add(&entry.metrics)
+                {
+                    if event.entries >= self.max_entries_per_event {
+                        open.swap_remove(i);
+                    }
+                    return;
+                }
+            }
+        }
+        // no open event can take the entry, start a new one
+        self.pending.push(Pending::Event(BatchedEvent {
+            timestamp: entry.timestamp,
+            properties: key.1.clone(),
+            metrics: entry.metrics,
+            entries: 1,
+        }));
+        self.open
+            .entry(key)
+            .or_default()
+            .push(self.pending.len() - 1);
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        self.open.clear();
+        self.pending_entries = 0;
+        for pending in std::mem::take(&mut self.pending) {
+            let result = match pending {
+                Pending::Line(line) => self.output.write_all(&line).map(|()| line.len()),
+                Pending::Event(event) => {
+                    self.scratch.clear();
+                    match self.emf.format(&event, &mut self.scratch) {
+                        Ok(()) => self
+                            .output
+                            .write_all(&self.scratch)
+                            .map(|()| self.scratch.len()),
+                        Err(IoStreamError::Io(err)) => Err(err),
+                        Err(IoStreamError::Validation(err)) => {
+                            // every entry was validated on its own, so this is unexpected
+                            rate_limited!(
+                                Duration::from_secs(1),
+                                tracing::error!(
+                                    message = "dropping combined EMF event that failed validation",
+                                    error = %err,
+                                )
+                            );
+                            Ok(0)
+                        }
+                    }
+                }
+            };
+            self.bytes_written += result? as u64;
+        }
+        Ok(())
+    }
+}
+
+impl<O: io::Write> EntryIoStream for BatchedEmfStream<O> {
+    fn next(&mut self, entry: &impl Entry) -> Result<(), IoStreamError> {
+        // format the entry on its own to return validation errors now, and to have its output
+        // ready in case it can't be combined
+        self.scratch.clear();
+        self.emf.format(entry, &mut self.scratch)?;
+
+        let mut capture = CapturedEntry::default();
+        entry.write(&mut capture);
+        if capture.batchable && self.max_entries_per_event > 1 {
+            self.add(capture);
+        } else {
+            self.pending.push(Pending::Line(self.scratch.clone()));
+        }
+
+        self.pending_entries += 1;
+        if self.pending_entries >= MAX_PENDING_ENTRIES {
+            self.write_pending()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.output.flush()
+    }
+
+    fn bytes_written(&self) -> Option<u64> {
+        Some(self.bytes_written)
+    }
+}
+
+/// A metric written by an entry, with the observations of every entry combined into it
+struct BatchedMetric {
+    name: String,
+    unit: Unit,
+    dimensions: Vec<(String, String)>,
+    storage_mode: Option<StorageMode>,
+    observations: Vec<Observation>,
+}
+
+impl BatchedMetric {
+    fn is_compatible(&self, other: &BatchedMetric) -> bool {
+        self.unit == other.unit
+            && self.dimensions == other.dimensions
+            && self.storage_mode == other.storage_mode
+    }
+}
+
+impl Value for BatchedMetric {
+    fn write(&self, writer: impl ValueWriter) {
+        writer.metric(
+            self.observations.iter().copied(),
+            self.unit,
+            self.dimensions.iter().map(|(k, v)| (&**k, &**v)),
+            storage_mode_flags(self.storage_mode),
+        );
+    }
+}
+
+/// Entries combined into a single log event
+struct BatchedEvent {
+    timestamp: SystemTime,
+    properties: Vec<(String, String)>,
+    metrics: Vec<BatchedMetric>,
+    entries: usize,
+}
+
+impl BatchedEvent {
+    /// Add the metrics of an entry to this event, returning false (and leaving the event
+    /// unchanged) if any of them is incompatible with the event's metrics.
+    fn try_add(&mut self, metrics: &[BatchedMetric]) -> bool {
+        let indexes = metrics
+            .iter()
+            .map(
+                |metric| match self.metrics.iter().position(|m| m.name == metric.name) {
+                    Some(index) if self.metrics[index].is_compatible(metric) => Ok(Some(index)),
+                    Some(_) => Err(()),
+                    None => Ok(None),
+                },
+            )
+            .collect::<Result<Vec<_>, ()>>();
+        let Ok(indexes) = indexes else {
+            return false;
+        };
+        for (metric, index) in metrics.iter().zip(indexes) {
+            match index {
+                Some(index) => self.metrics[index]
+                    .observations
+                    .extend_from_slice(&metric.observations),
+                None => self.metrics.push(BatchedMetric {
+                    name: metric.name.clone(),
+                    unit: metric.unit,
+                    dimensions: metric.dimensions.clone(),
+                    storage_mode: metric.storage_mode,
+                    observations: metric.observations.clone(),
+                }),
+            }
+        }
+        self.entries += 1;
+        true
+    }
+}
+
+impl Entry for BatchedEvent {
+    fn write<'a>(&'a self, writer: &mut impl metrique_writer_core::EntryWriter<'a>) {
+        writer.timestamp(self.timestamp);
+        for (name, value) in &self.properties {
+            writer.value(&**name, &**value);
+        }
+        for metric in &self.metrics {
+            writer.value(&*metric.name, metric);
+        }
+    }
+}
+
+/// The fields of an entry, captured to be combined with other entries
+struct CapturedEntry {
+    timestamp: SystemTime,
+    properties: Vec<(String, String)>,
+    metrics: Vec<BatchedMetric>,
+    batchable: bool,
+    has_timestamp: bool,
+}
+
+impl Default for CapturedEntry {
+    fn default() -> Self {
+        Self {
+            // like Emf, use the current time for entries without a timestamp
+            timestamp: SystemTime::now(),
+            properties: Vec::new(),
+            metrics: Vec::new(),
+            batchable: true,
+            has_timestamp: false,
+        }
+    }
+}
+
+impl CapturedEntry {
+    fn timestamp_millis(&self) -> u128 {
+        self.timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+    }
+
+    fn has_field(&self, name: &str) -> bool {
+        self.properties.iter().any(|(n, _)| n == name)
+            || self.metrics.iter().any(|m| m.name == name)
+    }
+}
+
+impl<'a> metrique_writer_core::EntryWriter<'a> for CapturedEntry {
+    fn timestamp(&mut self, timestamp: SystemTime) {
+        if std::mem::replace(&mut self.has_timestamp, true) {
+            self.batchable = false;
+        }
+        self.timestamp = timestamp;
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        let name = name.into();
+        if !self.batchable {
+            return;
+        }
+        if self.has_field(&name) {
+            self.batchable = false;
+            return;
+        }
+        value.write(CapturedValue {
+            name: &name,
+            entry: self,
+        });
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {
+        self.batchable = false;
+    }
+}
+
+struct CapturedValue<'e> {
+    name: &'e str,
+    entry: &'e mut CapturedEntry,
+}
+
+impl ValueWriter for CapturedValue<'_> {
+    fn string(self, value: &str) {
+        self.entry
+            .properties
+            .push((self.name.to_owned(), value.to_owned()));
+    }
+
+    fn values<'a, V: Value + 'a>(self, _values: impl IntoIterator<Item = &'a V>) {
+        // Emf writes lists as arrays, which can't be captured as a string
+        self.entry.batchable = false;
+    }
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        unit: Unit,
+        dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        flags: MetricFlags<'_>,
+    ) {
+        self.entry.metrics.push(BatchedMetric {
+            name: self.name.to_owned(),
+            unit,
+            dimensions: dimensions
+                .into_iter()
+                .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                .collect(),
+            storage_mode: storage_mode(&flags),
+            observations: distribution.into_iter().collect(),
+        });
+    }
+
+    fn error(self, _error: ValidationError) {
+        self.entry.batchable = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::time::{Duration, SystemTime};
+
+    use metrique_writer_core::value::WithDimension;
+    use metrique_writer_core::{Entry, EntryIoStream, EntryWriter};
+
+    use super::BatchedEmfStream;
+    use crate::{Emf, EntryDimensions, HighStorageResolution};
+
+    #[derive(Clone, Copy, Default)]
+    enum Variant {
+        #[default]
+        Plain,
+        HighResolution,
+        Config,
+        PerMetricDimension,
+        DuplicateField,
+    }
+
+    #[derive(Default)]
+    struct QueueEntry {
+        millis: u64,
+        queue: &'static str,
+        depth: u64,
+        variant: Variant,
+    }
+
+    impl Entry for QueueEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(SystemTime::UNIX_EPOCH + Duration::from_millis(self.millis));
+            writer.value("queue", self.queue);
+            match self.variant {
+                Variant::Plain => writer.value("depth", &self.depth),
+                Variant::HighResolution => {
+                    writer.value("depth", &HighStorageResolution::from(self.depth))
+                }
+                Variant::Config => {
+                    writer.config(
+                        const {
+                            &EntryDimensions::new(Cow::Borrowed(&[Cow::Borrowed(&[
+                                Cow::Borrowed("queue"),
+                            ])]))
+                        },
+                    );
+                    writer.value("depth", &self.depth)
+                }
+                Variant::PerMetricDimension => writer.value(
+                    "depth",
+                    &WithDimension::new_with_dimensions(self.depth, [("Shard", "1")]),
+                ),
+                Variant::DuplicateField => {
+                    writer.value("depth", &self.depth);
+                    writer.value("depth", &self.depth);
+                }
+            }
+            writer.value("wait", &Duration::from_millis(self.depth * 10));
+        }
+    }
+
+    fn queue(millis: u64, queue: &'static str, depth: u64) -> QueueEntry {
+        QueueEntry {
+            millis,
+            queue,
+            depth,
+            ..Default::default()
+        }
+    }
+
+    fn emf() -> Emf {
+        Emf::builder("Ns".into(), vec![vec!["queue".into()]])
+            .allow_ignored_dimensions(true)
+            .build()
+    }
+
+    fn write(
+        mut stream: BatchedEmfStream<Vec<u8>>,
+        entries: impl IntoIterator<Item = QueueEntry>,
+    ) -> Vec<serde_json::Value> {
+        for entry in entries {
+            stream.next(&entry).unwrap();
+        }
+        stream.flush().unwrap();
+        let written = stream.bytes_written().unwrap();
+        let (_, output) = stream.into_inner();
+        assert_eq!(written, output.len() as u64);
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    fn depths(events: &[serde_json::Value]) -> Vec<serde_json::Value> {
+        events.iter().map(|e| e["depth"].clone()).collect()
+    }
+
+    #[test]
+    fn combines_entries_sharing_timestamp_and_properties() {
+        let events = write(
+            BatchedEmfStream::new(emf(), vec![]),
+            [
+                queue(1, "orders", 3),
+                queue(1, "payments", 4),
+                queue(1, "orders", 5),
+                queue(2, "orders", 6),
+                queue(1, "orders", 7),
+            ],
+        );
+        let directive = serde_json::json!({
+            "Namespace": "Ns",
+            "Dimensions": [["queue"]],
+            "Metrics": [{"Name": "depth"}, {"Name": "wait", "Unit": "Milliseconds"}],
+        });
+        assert_json_diff::assert_json_eq!(
+            events,
+            serde_json::json!([
+                {
+                    "_aws": {"CloudWatchMetrics": [directive], "Timestamp": 1},
+                    "queue": "orders",
+                    "depth": {"Values": [3, 5, 7], "Counts": [1, 1, 1]},
+                    "wait": {"Values": [30, 50, 70], "Counts": [1, 1, 1]},
+                },
+                {
+                    "_aws": {"CloudWatchMetrics": [directive], "Timestamp": 1},
+                    "queue": "payments",
+                    "depth": 4,
+                    "wait": 40,
+                },
+                {
+                    "_aws": {"CloudWatchMetrics": [directive], "Timestamp": 2},
+                    "queue": "orders",
+                    "depth": 6,
+                    "wait": 60,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn limits_entries_per_event() {
+        let events = write(
+            BatchedEmfStream::new(emf(), vec![]).max_entries_per_event(2),
+            (0..5).map(|depth| queue(1, "orders", depth)),
+        );
+        assert_eq!(
+            depths(&events),
+            [
+                serde_json::json!({"Values": [0, 1], "Counts": [1, 1]}),
+                serde_json::json!({"Values": [2, 3], "Counts": [1, 1]}),
+                serde_json::json!(4),
+            ]
+        );
+
+        let events = write(
+            BatchedEmfStream::new(emf(), vec![]).max_entries_per_event(1),
+            (0..3).map(|depth| queue(1, "orders", depth)),
+        );
+        assert_eq!(events.len(), 3);
+    }
+
+    #[test]
+    fn incompatible_entries_are_written_on_their_own() {
+        let variant = |depth, variant| QueueEntry {
+            variant,
+            ..queue(1, "orders", depth)
+        };
+        let events = write(
+            BatchedEmfStream::new(emf(), vec![]),
+            [
+                queue(1, "orders", 1),
+                // a different storage resolution for `depth`
+                variant(2, Variant::HighResolution),
+                // entry config
+                variant(3, Variant::Config),
+                // different per-metric dimensions for `depth`
+                variant(4, Variant::PerMetricDimension),
+                // compatible with the first entry
+                queue(1, "orders", 5),
+            ],
+        );
+        assert_eq!(
+            depths(&events),
+            [
+                serde_json::json!({"Values": [1, 5], "Counts": [1, 1]}),
+                serde_json::json!(2),
+                serde_json::json!(3),
+                serde_json::json!(4),
+            ]
+        );
+        assert_eq!(
+            events[1]["_aws"]["CloudWatchMetrics"][0]["Metrics"][0]["StorageResolution"],
+            1
+        );
+    }
+
+    #[test]
+    fn validation_errors_are_returned_on_append() {
+        let mut stream = BatchedEmfStream::new(emf(), vec![]);
+        let invalid = QueueEntry {
+            variant: Variant::DuplicateField,
+            ..queue(1, "orders", 1)
+        };
+        let error = stream.next(&invalid).unwrap_err();
+        assert!(error.to_string().contains("duplicate field"), "{error}");
+        stream.flush().unwrap();
+        assert!(stream.into_inner().1.is_empty());
+    }
+}
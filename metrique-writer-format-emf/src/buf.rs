@@ -62,6 +62,10 @@ impl PrefixedStringBuf {
         self
     }
 
+    pub fn prefix(&self) -> &str {
+        &self.buf[..self.prefix_len]
+    }
+
     pub fn as_str(&self) -> &str {
         &self.buf
     }
@@ -93,16 +97,18 @@ impl AsRef<[u8]> for PrefixedStringBuf {
 // Note that this is mostly copied as is from the std library. We need this until
 // https://doc.rust-lang.org/nightly/src/std/io/mod.rs.html#1732 is stable. No dark magic is occurring, just the current
 // IoVec type doesn't expose the underying &[u8] with the right lifetime through a fn yet.
-
+//
+// Returns the total number of bytes written, which is the combined length of `bufs`.
 pub(crate) fn write_all_vectored<V: AsRef<[u8]>, const N: usize>(
     bufs: SmallVec<[V; N]>,
     output: &mut impl io::Write,
-) -> io::Result<()> {
+) -> io::Result<usize> {
     // Only a debug assert because this will still work with bufs.len() > N, but to avoid heap allocations, we should
     // avoid it.
     debug_assert!(!bufs.is_empty() && bufs.len() <= N);
 
     let mut slices: SmallVec<[_; N]> = bufs.iter().map(AsRef::as_ref).collect();
+    let len = slices.iter().map(|s| s.len()).sum();
     let mut slices = &mut slices[..];
 
     // Until the IoSlice APIs are expanded, there's no way to get back a &'a [u8]. We'll reconstruct the slices on
@@ -121,7 +127,7 @@ pub(crate) fn write_all_vectored<V: AsRef<[u8]>, const N: usize>(
         io_slices.clear();
     }
 
-    Ok(())
+    Ok(len)
 }
 
 // Also copied out of std. This "advances" the slices forward by count bytes. If all of the bytes were written
@@ -11,17 +11,18 @@ use metrique_writer_core::format::Format;
 use metrique_writer_core::sample::SampledFormat;
 use metrique_writer_core::stream::IoStreamError;
 use metrique_writer_core::{
-    Entry, EntryConfig, MetricFlags, Observation, Unit, ValidationError, ValidationErrorBuilder,
-    Value,
+    Entry, EntryConfig, MetricFlags, NameRules, Observation, Unit, ValidationError,
+    ValidationErrorBuilder, Value,
 };
 use rand::rngs::ThreadRng;
 use rand::{Rng, RngCore};
 use std::any::Any;
+use std::collections::BTreeMap;
 use std::fmt::{Display, Write};
 use std::iter;
 use std::mem;
 use std::num::NonZero;
-use std::ops::Deref;
+use std::ops::{Deref, Range};
 use std::time::Duration;
 use std::{borrow::Cow, io, time::SystemTime};
 
@@ -112,6 +113,14 @@ struct Validation {
 ///
 /// All observations other than the ones containing the NaN will be emitted as usual.
 ///
+/// ## Combining entries into fewer log events
+///
+/// Every entry is formatted as (at least) its own log event. To combine entries that share a
+/// timestamp and properties into a single log event, write them through a
+/// [`BatchedEmfStream`](crate::BatchedEmfStream) instead of [`output_to`].
+///
+/// [`output_to`]: metrique_writer::FormatExt::output_to
+///
 /// ## Examples
 ///
 /// Here is an example of using [`Emf`] to format an [`Entry`] as a string:
@@ -161,6 +170,9 @@ pub struct Emf {
     state: State,
     validation: Validation,
     validation_map_base: hashbrown::HashMap<SCow<'static>, LineData>,
+    // the constant fields, used as the validation map when dimensions are not validated
+    validation_map_constant_fields: hashbrown::HashMap<SCow<'static>, LineData>,
+    always_present_metrics: Vec<(String, Unit)>,
 }
 
 #[derive(Clone)]
@@ -168,6 +180,7 @@ struct State {
     namespaces: Vec<JsonEncodedString>,
     each_dimensions_str: Vec<JsonEncodedArray>,
     log_group_and_timestamp: LogGroupNameAndTimestampString,
+    timestamp_unit: TimestampUnit,
     dimension_set_map: hashbrown::HashMap<DimensionSet, MetricsForDimensionSet>,
 
     // buf that string fields can be added to
@@ -176,6 +189,8 @@ struct State {
     fields_buf: PrefixedStringBuf,
     // buf that metrics can be added to
     metrics_buf: PrefixedStringBuf,
+    metrics_splits: DirectiveSplits,
+    max_metrics_per_directive: usize,
     // buf that dimensions are added to. Used internally in `finish` and reset, not accumulator.
     dimensions_buf: PrefixedStringBuf,
     // index after the namespace in dimensions_buf
@@ -184,7 +199,25 @@ struct State {
     counts_buf: PrefixedStringBuf,
     // buf of extra declarations
     decl_buf: PrefixedStringBuf,
+    // buf of extra declarations with `directive_upgrades` applied, used instead of `decl_buf`
+    // when `any_directive_upgraded` is set
+    upgraded_decl_buf: PrefixedStringBuf,
+    directive_upgrades: Vec<DirectiveUpgrade>,
+    any_directive_upgraded: bool,
     allow_ignored_dimensions: bool,
+    skip_zero_metrics: bool,
+    nan_policy: NanPolicy,
+    buffer_sizes: BufferSizes,
+}
+
+/// A metric definition in an extra directive that is not declared at high storage resolution,
+/// but is emitted at high storage resolution if its value asks for it.
+#[derive(Clone)]
+struct DirectiveUpgrade {
+    name: String,
+    // the range of the extra directives that is replaced by `,"StorageResolution":1`
+    range: Range<usize>,
+    upgraded: bool,
 }
 
 /// Serde declaration of EMF's MetricDirective type
@@ -229,6 +262,59 @@ pub enum StorageResolution {
     Minute = 60,
 }
 
+/// The unit the `Timestamp` of EMF entries is written in, see [`EmfBuilder::timestamp_unit`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimestampUnit {
+    /// Milliseconds since the Unix epoch, as specified by EMF. This is the default.
+    #[default]
+    Millis,
+    /// Whole seconds since the Unix epoch, rounded down.
+    Seconds,
+}
+
+/// What [`Emf`] does with `NaN` metric observations, see [`EmfBuilder::nan_policy`].
+///
+/// Infinite observations are not affected, they are always clamped to `±f64::MAX`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NanPolicy {
+    /// Skip `NaN` observations, logging a (rate-limited) error. A metric with only `NaN`
+    /// observations is not emitted. This is the default.
+    #[default]
+    Skip,
+    /// Fail formatting the entry with a validation error for the metric.
+    Error,
+    /// Emit `NaN` observations as `0`.
+    Zero,
+}
+
+impl NanPolicy {
+    fn apply(self, observation: Observation, saw_nan: &mut bool) -> Observation {
+        let is_nan = match observation {
+            Observation::Floating(value) => value.is_nan(),
+            Observation::Repeated { total, .. } => total.is_nan(),
+            _ => false,
+        };
+        if !is_nan {
+            return observation;
+        }
+        *saw_nan = true;
+        match (self, observation) {
+            (NanPolicy::Zero, Observation::Floating(_)) => Observation::Floating(0.0),
+            (NanPolicy::Zero, Observation::Repeated { occurrences, .. }) => Observation::Repeated {
+                total: 0.0,
+                occurrences,
+            },
+            _ => observation,
+        }
+    }
+}
+
+fn to_json(value: &(impl serde::Serialize + ?Sized)) -> String {
+    serde_json::to_string(value).expect("nothing that can fail here")
+}
+
 /// Contains a JSON string that has been JSON-encoded
 #[derive(Clone)]
 struct JsonEncodedString {
@@ -359,8 +445,17 @@ impl Emf {
             namespaces: vec![namespace],
             default_dimensions,
             allow_ignored_dimensions: false,
+            skip_zero_metrics: false,
+            nan_policy: NanPolicy::default(),
             extra_directives: String::new(),
+            directive_upgrades: Vec::new(),
             log_group_name: None,
+            timestamp_unit: TimestampUnit::default(),
+            max_metrics_per_directive: MAX_METRICS_PER_DIRECTIVE,
+            always_present_metrics: Vec::new(),
+            constant_fields: Vec::new(),
+            resource_attributes: None,
+            buffer_sizes: BufferSizes::default(),
             #[cfg(debug_assertions)]
             validation: Validation::default(),
             #[cfg(not(debug_assertions))]
@@ -402,21 +497,28 @@ impl Emf {
         entry: &impl Entry,
         output: &mut impl io::Write,
         multiplicity: Option<u64>,
-    ) -> Result<(), IoStreamError> {
+    ) -> Result<usize, IoStreamError> {
         self.state.string_fields_buf.clear();
         self.state.fields_buf.clear();
         self.state.metrics_buf.clear();
+        self.state.metrics_splits.clear();
         self.state.decl_buf.clear();
         self.state.dimension_set_map.clear();
+        if mem::take(&mut self.state.any_directive_upgraded) {
+            for upgrade in &mut self.state.directive_upgrades {
+                upgrade.upgraded = false;
+            }
+        }
 
         // counts_buf is cleared when returning
         let mut writer = EntryWriter {
             validation_map: if self.validation.skip_validate_dimensions_exist {
-                hashbrown::HashMap::new()
+                self.validation_map_constant_fields.clone()
             } else {
                 self.validation_map_base.clone()
             },
             entry_dimensions: None,
+            entry_dimensions_config: None,
             state: &mut self.state,
             multiplicity,
             timestamp: None,
@@ -424,9 +526,12 @@ impl Emf {
             error: ValidationErrorBuilder::default(),
             allow_split_entries: false,
             is_allow_unroutable_entries: false,
+            always_present_metrics: &self.always_present_metrics,
+            always_present_seen: bit_set::BitSet::default(),
         };
 
         entry.write(&mut writer);
+        writer.write_always_present_metrics();
         writer.finish(output)
     }
 
@@ -511,10 +616,19 @@ impl Emf {
 pub struct EmfBuilder {
     default_dimensions: Vec<Vec<String>>,
     extra_directives: String,
+    directive_upgrades: Vec<DirectiveUpgrade>,
     namespaces: Vec<String>,
     validation: Validation,
     allow_ignored_dimensions: bool,
+    skip_zero_metrics: bool,
+    nan_policy: NanPolicy,
     log_group_name: Option<String>,
+    timestamp_unit: TimestampUnit,
+    max_metrics_per_directive: usize,
+    always_present_metrics: Vec<(String, Unit)>,
+    constant_fields: Vec<(String, String)>,
+    resource_attributes: Option<BTreeMap<String, String>>,
+    buffer_sizes: BufferSizes,
 }
 
 impl EmfBuilder {
@@ -576,7 +690,44 @@ impl EmfBuilder {
     /// );
     /// ```
     pub fn build(self) -> Emf {
-        let mut validation_map = hashbrown::HashMap::new();
+        let mut validation_map_constant_fields = hashbrown::HashMap::new();
+        let mut constant_fields = String::new();
+        for (name, value) in &self.constant_fields {
+            validation_map_constant_fields
+                .entry_ref(name)
+                .or_insert(LineData {
+                    kind: LineKind::String,
+                });
+            constant_fields.push(',');
+            constant_fields.json_string(name).push(':');
+            constant_fields.json_string(value);
+        }
+        if let Some(attributes) = &self.resource_attributes {
+            assert!(
+                !self
+                    .constant_fields
+                    .iter()
+                    .any(|(n, _)| n == RESOURCE_FIELD),
+                "constant field `{RESOURCE_FIELD}` conflicts with the resource attributes"
+            );
+            validation_map_constant_fields
+                .entry_ref(RESOURCE_FIELD)
+                .or_insert(LineData {
+                    kind: LineKind::String,
+                });
+            constant_fields.push(',');
+            constant_fields.json_string(RESOURCE_FIELD).push_str(":{");
+            for (i, (name, value)) in attributes.iter().enumerate() {
+                if i > 0 {
+                    constant_fields.push(',');
+                }
+                constant_fields.json_string(name).push(':');
+                constant_fields.json_string(value);
+            }
+            constant_fields.push('}');
+        }
+        // a constant field also provides the value of a dimension with its name
+        let mut validation_map = validation_map_constant_fields.clone();
         for dimension_set in &self.default_dimensions {
             for dimension in dimension_set {
                 validation_map.entry_ref(dimension).or_insert(LineData {
@@ -610,17 +761,38 @@ impl EmfBuilder {
                 each_dimensions_str,
                 dimension_set_map: hashbrown::HashMap::new(),
                 after_namespace_index: dimensions_prefix.len() - dimensions_after_ns.len(),
-                dimensions_buf: PrefixedStringBuf::new(dimensions_prefix, 256),
-                fields_buf: PrefixedStringBuf::new("}", 2048),
-                string_fields_buf: PrefixedStringBuf::new("", 2048),
-                counts_buf: PrefixedStringBuf::new(r#"],"Counts":["#, 256),
-                metrics_buf: PrefixedStringBuf::new(r#"],"Metrics":["#, 2048),
-                decl_buf: PrefixedStringBuf::new(&self.extra_directives, 256),
+                dimensions_buf: PrefixedStringBuf::new(
+                    dimensions_prefix,
+                    self.buffer_sizes.dimensions,
+                ),
+                fields_buf: PrefixedStringBuf::new("}", self.buffer_sizes.fields),
+                // string fields are cleared back to the constant fields
+                string_fields_buf: PrefixedStringBuf::new(
+                    &constant_fields,
+                    self.buffer_sizes.string_fields,
+                ),
+                counts_buf: PrefixedStringBuf::new(r#"],"Counts":["#, self.buffer_sizes.counts),
+                metrics_buf: PrefixedStringBuf::new(r#"],"Metrics":["#, self.buffer_sizes.metrics),
+                metrics_splits: DirectiveSplits::default(),
+                max_metrics_per_directive: self.max_metrics_per_directive,
+                decl_buf: PrefixedStringBuf::new(
+                    &self.extra_directives,
+                    self.buffer_sizes.declarations,
+                ),
+                buffer_sizes: self.buffer_sizes,
+                upgraded_decl_buf: PrefixedStringBuf::new("", 0),
+                directive_upgrades: self.directive_upgrades,
+                any_directive_upgraded: false,
                 allow_ignored_dimensions: self.allow_ignored_dimensions,
+                skip_zero_metrics: self.skip_zero_metrics,
+                nan_policy: self.nan_policy,
                 log_group_and_timestamp: LogGroupNameAndTimestampString::new(self.log_group_name),
+                timestamp_unit: self.timestamp_unit,
             },
             validation_map_base: validation_map,
+            validation_map_constant_fields,
             validation: self.validation,
+            always_present_metrics: self.always_present_metrics,
         }
     }
 
@@ -632,6 +804,18 @@ impl EmfBuilder {
     /// Note that EMF skips metric definitions that refer to metrics that don't exist, so if
     /// you have a metric that appears only in some entries it is OK to emit a directive for it.
     ///
+    /// ## Storage resolution
+    ///
+    /// Set [`MetricDefinition::storage_resolution`] to [`StorageResolution::Second`] to emit a
+    /// metric at high storage resolution under this directive, without wrapping its values in
+    /// [`HighStorageResolution`]. This only affects this directive: the metric keeps its own
+    /// resolution in the default directive and in other directives.
+    ///
+    /// If a value asks for high storage resolution (e.g. it is wrapped in
+    /// [`HighStorageResolution`]), the highest resolution wins, and the metric is emitted at high
+    /// storage resolution in every directive it appears in. [`NoMetric`] values are still
+    /// emitted under directives that explicitly list them.
+    ///
     /// ## Example
     ///
     /// This will publish all metrics with dimensions `["Operation"]`, and will also publish
@@ -707,10 +891,43 @@ impl EmfBuilder {
     /// );
     /// ```
     pub fn directive(mut self, directive: MetricDirective) -> Self {
-        self.extra_directives.push(',');
-        // injection-safe because this is pushing directly after serialization
-        self.extra_directives
-            .push_str(&serde_json::to_string(&directive).expect("nothing that can fail here"));
+        // injection-safe because this is only pushing serialized JSON
+        let directives = &mut self.extra_directives;
+        directives.push_str(r#",{"Dimensions":"#);
+        directives.push_str(&to_json(&directive.dimensions));
+        directives.push_str(r#","Metrics":["#);
+        for (i, metric) in directive.metrics.iter().enumerate() {
+            if i > 0 {
+                directives.push(',');
+            }
+            let mut definition = to_json(&MetricDefinition {
+                storage_resolution: None,
+                ..*metric
+            });
+            // drop the closing `}`, to append the storage resolution
+            definition.pop();
+            directives.push_str(&definition);
+            match metric.storage_resolution {
+                Some(StorageResolution::Second) => {
+                    directives.push_str(r#","StorageResolution":1"#);
+                }
+                resolution => {
+                    let start = directives.len();
+                    if let Some(StorageResolution::Minute) = resolution {
+                        directives.push_str(r#","StorageResolution":60"#);
+                    }
+                    self.directive_upgrades.push(DirectiveUpgrade {
+                        name: metric.name.to_owned(),
+                        range: start..directives.len(),
+                        upgraded: false,
+                    });
+                }
+            }
+            directives.push('}');
+        }
+        directives.push_str(r#"],"Namespace":"#);
+        directives.push_str(&to_json(directive.namespace));
+        directives.push('}');
         self
     }
 
@@ -738,6 +955,51 @@ impl EmfBuilder {
         self
     }
 
+    /// Controls whether metrics whose value is zero are skipped.
+    ///
+    /// When this is set to `true`, a metric written with a single observation of `0` (or `0.0`)
+    /// is skipped entirely: it has no metric definition and no field in the output, as if the
+    /// entry didn't write it. This reduces log volume for sparse counters that are mostly `0`.
+    /// Distributions with more than one observation are never skipped, even if every
+    /// observation is zero, and neither are [`always_present_metrics`](Self::always_present_metrics).
+    ///
+    /// Note that this changes the statistics of the skipped metrics in CloudWatch: the zeros
+    /// are not counted as datapoints, so e.g. the `Average` of a metric is the average of its
+    /// non-zero values, and its `SampleCount` only counts entries with a non-zero value.
+    ///
+    /// Skipped metrics are still validated like any other metric, e.g. writing the same metric
+    /// twice is still an error.
+    pub fn skip_zero_metrics(mut self, skip: bool) -> Self {
+        self.skip_zero_metrics = skip;
+        self
+    }
+
+    /// Set what is done with `NaN` metric observations. Defaults to [`NanPolicy::Skip`].
+    ///
+    /// [`NanPolicy::Error`] makes [`format`](metrique_writer::format::Format::format) fail with
+    /// a validation error naming the metric, which is useful to surface bugs in tests.
+    ///
+    /// ## Example
+    /// ```
+    /// # use metrique_writer::{Entry, format::Format as _};
+    /// # use metrique_writer_format_emf::{Emf, NanPolicy};
+    /// #[derive(Entry)]
+    /// struct MyMetrics {
+    ///     ratio: f64,
+    /// }
+    ///
+    /// let mut emf = Emf::builder("MyApp".to_string(), vec![vec![]])
+    ///     .nan_policy(NanPolicy::Error)
+    ///     .build();
+    ///
+    /// let err = emf.format(&MyMetrics { ratio: f64::NAN }, &mut vec![]).unwrap_err();
+    /// assert!(err.to_string().contains("ratio"));
+    /// ```
+    pub fn nan_policy(mut self, nan_policy: NanPolicy) -> Self {
+        self.nan_policy = nan_policy;
+        self
+    }
+
     /// Skips validation that all dimensions referenced in dimension sets exist in the entry.
     ///
     /// When `skip` is true, dimensions referenced in dimension sets that are not present in the
@@ -1021,8 +1283,336 @@ impl EmfBuilder {
         self.log_group_name = Some(log_group_name.into());
         self
     }
+
+    /// Set the maximum number of metrics in a single metric directive. Defaults to 100, which is
+    /// the limit of the EMF specification.
+    ///
+    /// When an entry has more metrics than this (under a single dimension set), its metrics are
+    /// split into several directives with the same namespace and dimensions, in every namespace.
+    /// The metric values themselves are not affected. Directives added with
+    /// [`directive`](Self::directive) are never split.
+    ///
+    /// CloudWatch rejects directives with more than 100 metrics, so only set this to a lower
+    /// value.
+    ///
+    /// # Panics
+    /// Panics if `max_metrics_per_directive` is 0.
+    pub fn max_metrics_per_directive(mut self, max_metrics_per_directive: usize) -> Self {
+        assert!(
+            max_metrics_per_directive > 0,
+            "max_metrics_per_directive must be at least 1"
+        );
+        self.max_metrics_per_directive = max_metrics_per_directive;
+        self
+    }
+
+    /// Set the unit the `Timestamp` of every entry is written in.
+    ///
+    /// EMF specifies the timestamp in milliseconds since the Unix epoch, which is the default
+    /// ([`TimestampUnit::Millis`]). [`TimestampUnit::Seconds`] writes whole seconds instead, for
+    /// log processors that don't accept millisecond timestamps. CloudWatch itself expects
+    /// milliseconds, so only use this if your entries are not sent to CloudWatch directly.
+    ///
+    /// ## Example
+    /// ```
+    /// # use metrique_writer::{Entry, format::Format as _};
+    /// # use metrique_writer_format_emf::{Emf, TimestampUnit};
+    /// # use std::time::{Duration, SystemTime};
+    /// #[derive(Entry)]
+    /// struct MyMetrics {
+    ///     #[entry(timestamp)]
+    ///     start: SystemTime,
+    ///     my_field: u32,
+    /// }
+    ///
+    /// let mut emf = Emf::builder("MyApp".to_string(), vec![vec![]])
+    ///     .timestamp_unit(TimestampUnit::Seconds)
+    ///     .build();
+    /// let mut output = Vec::new();
+    ///
+    /// emf.format(&MyMetrics {
+    ///     start: SystemTime::UNIX_EPOCH + Duration::from_millis(1_500),
+    ///     my_field: 4,
+    /// }, &mut output).unwrap();
+    ///
+    /// let output: serde_json::Value = serde_json::from_slice(&output).unwrap();
+    /// assert_eq!(output["_aws"]["Timestamp"], 1);
+    /// ```
+    pub fn timestamp_unit(mut self, timestamp_unit: TimestampUnit) -> Self {
+        self.timestamp_unit = timestamp_unit;
+        self
+    }
+
+    /// Emit the given metrics with a value of `0` in every entry that doesn't write them.
+    ///
+    /// CloudWatch alarms treat a metric with no datapoints as missing data, which is often
+    /// not what you want for metrics like `Fault` or `Error` that are only written when
+    /// something goes wrong. Metrics configured here are always present in the output: if an
+    /// entry doesn't write a value with that name, a zero-valued metric with the given unit
+    /// is emitted instead. Entries that do write the name keep their own value.
+    ///
+    /// The names are the final metric names, after any renaming done by the entry.
+    /// Calling this multiple times adds to the set of metrics.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use metrique_writer::{
+    /// #    Entry, EntryWriter,
+    /// #    format::{Format as _},
+    /// #    unit::Unit,
+    /// # };
+    /// # use metrique_writer_format_emf::Emf;
+    /// # use std::time::SystemTime;
+    ///
+    /// #[derive(Entry)]
+    /// #[entry(rename_all = "PascalCase")]
+    /// struct MyMetrics {
+    ///     #[entry(timestamp)]
+    ///     start: SystemTime,
+    ///     my_field: u32,
+    /// }
+    ///
+    /// let mut emf = Emf::builder("MyApp".to_string(), vec![vec![]])
+    ///     .always_present_metrics(&[("Fault", Unit::Count)])
+    ///     .build();
+    /// let mut output = Vec::new();
+    ///
+    /// emf.format(&MyMetrics {
+    ///     start: SystemTime::UNIX_EPOCH, // use SystemTime::now() in the real world
+    ///     my_field: 4,
+    /// }, &mut output).unwrap();
+    ///
+    /// let output = String::from_utf8(output).unwrap();
+    /// assert_json_diff::assert_json_eq!(serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+    ///     serde_json::json!({
+    ///         "_aws": {
+    ///             "CloudWatchMetrics": [
+    ///                  {"Namespace": "MyApp", "Dimensions": [[]], "Metrics": [
+    ///                      {"Name": "MyField"},
+    ///                      {"Name": "Fault", "Unit": "Count"}
+    ///                  ]},
+    ///             ],
+    ///             "Timestamp": 0,
+    ///         },
+    ///         "MyField": 4,
+    ///         "Fault": 0,
+    ///     })
+    /// );
+    /// ```
+    pub fn always_present_metrics(mut self, metrics: &[(&str, Unit)]) -> Self {
+        self.always_present_metrics
+            .extend(metrics.iter().map(|&(name, unit)| (name.to_owned(), unit)));
+        self
+    }
+
+    /// Add a string field with a fixed `value` to every line.
+    ///
+    /// This is useful to tag every log line with e.g. the host it was emitted from, without
+    /// changing the entries. The field is a plain string property, not a metric, so it is
+    /// searchable in CloudWatch Logs but doesn't publish anything to CloudWatch Metrics
+    /// (unless a dimension set refers to it, in which case it provides that dimension's value).
+    /// The name and value are JSON-escaped.
+    ///
+    /// Entries that write a field with the same name fail validation with a duplicate field
+    /// error, like any other duplicate field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is not a valid EMF field name (e.g. `_aws`), or if a constant field
+    /// named `name` was already added.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use metrique_writer::{
+    /// #    Entry, EntryWriter,
+    /// #    format::{Format as _},
+    /// # };
+    /// # use metrique_writer_format_emf::Emf;
+    /// # use std::time::SystemTime;
+    ///
+    /// #[derive(Entry)]
+    /// #[entry(rename_all = "PascalCase")]
+    /// struct MyMetrics {
+    ///     #[entry(timestamp)]
+    ///     start: SystemTime,
+    ///     my_field: u32,
+    /// }
+    ///
+    /// let mut emf = Emf::builder("MyApp".to_string(), vec![vec![]])
+    ///     .add_constant_field("Hostname", "host-1.example.com")
+    ///     .build();
+    /// let mut output = Vec::new();
+    ///
+    /// emf.format(&MyMetrics {
+    ///     start: SystemTime::UNIX_EPOCH, // use SystemTime::now() in the real world
+    ///     my_field: 4,
+    /// }, &mut output).unwrap();
+    ///
+    /// let output = String::from_utf8(output).unwrap();
+    /// assert_json_diff::assert_json_eq!(serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+    ///     serde_json::json!({
+    ///         "_aws": {
+    ///             "CloudWatchMetrics": [
+    ///                  {"Namespace": "MyApp", "Dimensions": [[]], "Metrics": [{"Name": "MyField"}]},
+    ///             ],
+    ///             "Timestamp": 0,
+    ///         },
+    ///         "MyField": 4,
+    ///         "Hostname": "host-1.example.com",
+    ///     })
+    /// );
+    /// ```
+    pub fn add_constant_field(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let name = name.into();
+        if let Err(err) = EMF_NAME_RULES.validate(&name) {
+            panic!("invalid constant field name: {err}");
+        }
+        assert!(
+            !self.constant_fields.iter().any(|(n, _)| *n == name),
+            "duplicate constant field `{name}`"
+        );
+        self.constant_fields.push((name, value.into()));
+        self
+    }
+
+    /// Set the initial capacities of the buffers entries are formatted into.
+    ///
+    /// This is purely a performance knob: services that emit very large entries can avoid
+    /// reallocating the buffers on every entry. Buffers are shrunk back to 1 MiB after
+    /// formatting an entry, so larger capacities don't help. The output is not affected. The
+    /// `fields` and
+    /// `metrics` sizes also apply to the buffers of entries that are
+    /// [split](metrique_writer_core::config::AllowSplitEntries) by per-metric dimensions.
+    ///
+    /// ## Example
+    /// ```
+    /// # use metrique_writer_format_emf::{BufferSizes, Emf};
+    /// let mut sizes = BufferSizes::default();
+    /// sizes.fields = 64 * 1024;
+    /// sizes.metrics = 16 * 1024;
+    /// let emf = Emf::builder("MyApp".to_string(), vec![vec![]])
+    ///     .with_buffer_capacity(sizes)
+    ///     .build();
+    /// ```
+    pub fn with_buffer_capacity(mut self, sizes: BufferSizes) -> Self {
+        self.buffer_sizes = sizes;
+        self
+    }
+
+    /// Add a `resource` object with the given attributes to every line, next to the `_aws`
+    /// metadata.
+    ///
+    /// This is meant for pipelines that read EMF with an OpenTelemetry collector, which expects
+    /// the resource attributes (e.g. `service.name`) in a top-level `resource` object. The
+    /// attributes are written in sorted order, and their names and values are JSON-escaped.
+    /// Calling this again replaces the attributes.
+    ///
+    /// Entries that write a field named `resource` fail validation with a duplicate field
+    /// error, like any other duplicate field.
+    ///
+    /// # Panics
+    ///
+    /// [`build`](Self::build) panics if a [constant field](Self::add_constant_field) is also
+    /// named `resource`.
+    ///
+    /// ## Example
+    ///
+    /// ```
+    /// # use metrique_writer::{Entry, format::Format as _};
+    /// # use metrique_writer_format_emf::Emf;
+    /// # use std::{collections::BTreeMap, time::SystemTime};
+    /// #[derive(Entry)]
+    /// #[entry(rename_all = "PascalCase")]
+    /// struct MyMetrics {
+    ///     #[entry(timestamp)]
+    ///     start: SystemTime,
+    ///     my_field: u32,
+    /// }
+    ///
+    /// let mut emf = Emf::builder("MyApp".to_string(), vec![vec![]])
+    ///     .with_resource_attributes([
+    ///         ("service.name", "my-app"),
+    ///         ("deployment.environment", "prod"),
+    ///     ])
+    ///     .build();
+    /// let mut output = Vec::new();
+    ///
+    /// emf.format(&MyMetrics {
+    ///     start: SystemTime::UNIX_EPOCH, // use SystemTime::now() in the real world
+    ///     my_field: 4,
+    /// }, &mut output).unwrap();
+    ///
+    /// let output = String::from_utf8(output).unwrap();
+    /// assert_json_diff::assert_json_eq!(serde_json::from_str::<serde_json::Value>(&output).unwrap(),
+    ///     serde_json::json!({
+    ///         "_aws": {
+    ///             "CloudWatchMetrics": [
+    ///                  {"Namespace": "MyApp", "Dimensions": [[]], "Metrics": [{"Name": "MyField"}]},
+    ///             ],
+    ///             "Timestamp": 0,
+    ///         },
+    ///         "MyField": 4,
+    ///         "resource": {
+    ///             "deployment.environment": "prod",
+    ///             "service.name": "my-app",
+    ///         },
+    ///     })
+    /// );
+    /// ```
+    pub fn with_resource_attributes(
+        mut self,
+        attributes: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        self.resource_attributes = Some(
+            attributes
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect(),
+        );
+        self
+    }
+}
+
+/// The initial capacities, in bytes, of the buffers [`Emf`] formats entries into, see
+/// [`EmfBuilder::with_buffer_capacity`].
+///
+/// The buffers grow as needed, so these only avoid reallocations when formatting large
+/// entries. The defaults fit typical entries.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BufferSizes {
+    /// The buffer for metric values. Defaults to 2048.
+    pub fields: usize,
+    /// The buffer for string fields, including constant fields. Defaults to 2048.
+    pub string_fields: usize,
+    /// The buffer for metric definitions. Defaults to 2048.
+    pub metrics: usize,
+    /// The buffer for dimension sets. Defaults to 256.
+    pub dimensions: usize,
+    /// The buffer for the counts of a single distribution metric. Defaults to 256.
+    pub counts: usize,
+    /// The buffer for the directives added with [`EmfBuilder::directive`]. Defaults to 256.
+    pub declarations: usize,
+}
+
+impl Default for BufferSizes {
+    fn default() -> Self {
+        Self {
+            fields: 2048,
+            string_fields: 2048,
+            metrics: 2048,
+            dimensions: 256,
+            counts: 256,
+            declarations: 256,
+        }
+    }
 }
 
+/// The name of the field that [`EmfBuilder::with_resource_attributes`] are written to
+const RESOURCE_FIELD: &str = "resource";
+
 #[derive(Clone)]
 enum LineKind {
     // this is a string
@@ -1136,6 +1726,7 @@ impl From<&'_ DimensionSetKey<'_>> for DimensionSet {
 struct MetricsForDimensionSet {
     fields_buf: PrefixedStringBuf,
     metrics_buf: PrefixedStringBuf,
+    metrics_splits: DirectiveSplits,
     // an index into "metrics_buf" after the end of the namespace
     after_namespace_index: usize,
     index: NonZero<usize>,
@@ -1147,6 +1738,7 @@ impl MetricsForDimensionSet {
         each_dimensions_str: &[JsonEncodedArray],
         variable_dimensions: &DimensionSetKey<'_>,
         index: NonZero<usize>,
+        buffer_sizes: &BufferSizes,
     ) -> Self {
         let dimensions_str = each_dimensions_str
             .iter()
@@ -1156,7 +1748,7 @@ impl MetricsForDimensionSet {
                 )
             })
             .join(",");
-        let mut metrics_buf = String::with_capacity(2048);
+        let mut metrics_buf = String::with_capacity(buffer_sizes.metrics);
         write!(
             metrics_buf,
             r#"{{"_aws":{{"CloudWatchMetrics":[{{"Namespace":{namespace_str}"#
@@ -1168,7 +1760,7 @@ impl MetricsForDimensionSet {
             r#","Dimensions":[{dimensions_str}],"Metrics":["#
         )
         .ok();
-        let mut fields_buf = String::with_capacity(2048);
+        let mut fields_buf = String::with_capacity(buffer_sizes.fields);
         fields_buf.push('}');
         // push the strings for the variable dimensions
         for (name, value) in &variable_dimensions.entry {
@@ -1180,24 +1772,99 @@ impl MetricsForDimensionSet {
         Self {
             fields_buf: PrefixedStringBuf::from_prefix(fields_buf),
             metrics_buf: PrefixedStringBuf::from_prefix(metrics_buf),
+            metrics_splits: DirectiveSplits::default(),
             after_namespace_index,
             index,
         }
     }
 }
 
+/// EMF's limit on the number of metrics in a directive
+const MAX_METRICS_PER_DIRECTIVE: usize = 100;
+
+/// Tracks where the metrics of a directive have to be split into several directives, to
+/// respect [`EmfBuilder::max_metrics_per_directive`].
+#[derive(Clone, Default)]
+struct DirectiveSplits {
+    metrics: usize,
+    // the offsets in the metrics buf of the `,` before the first metric of each extra directive
+    offsets: Vec<usize>,
+}
+
+impl DirectiveSplits {
+    fn clear(&mut self) {
+        self.metrics = 0;
+        self.offsets.clear();
+    }
+
+    fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    // record a metric definition written at `start` of the metrics buf
+    fn record(&mut self, start: usize, max_metrics_per_directive: usize) {
+        if self.metrics > 0 && self.metrics.is_multiple_of(max_metrics_per_directive) {
+            self.offsets.push(start);
+        }
+        self.metrics += 1;
+    }
+
+    /// Close the directives in `metrics_buf`, splitting its metrics into directives of at most
+    /// `max_metrics_per_directive` metrics, and repeat them for every namespace.
+    ///
+    /// `metrics_buf` must start with the first directive, up to its first metric, and `header`
+    /// is what follows the namespace of every directive, up to its first metric.
+    fn split_directives(
+        &self,
+        metrics_buf: &mut PrefixedStringBuf,
+        header: &str,
+        namespaces: &[JsonEncodedString],
+    ) {
+        let prefix_len = metrics_buf.prefix().len();
+        let metrics = metrics_buf.as_str()[prefix_len..].to_owned();
+        metrics_buf.truncate(prefix_len);
+        let mut chunks = Vec::with_capacity(self.offsets.len() + 1);
+        let mut start = 0;
+        for offset in &self.offsets {
+            chunks.push(&metrics[start..offset - prefix_len]);
+            // skip the `,` before the first metric
+            start = offset - prefix_len + 1;
+        }
+        chunks.push(&metrics[start..]);
+        for (i, namespace) in namespaces.iter().enumerate() {
+            for (j, chunk) in chunks.iter().enumerate() {
+                if i > 0 || j > 0 {
+                    metrics_buf
+                        .push_raw_str(r#"]},{"Namespace":"#)
+                        .push_json_safe_string(namespace)
+                        // safe because the header is valid JSON
+                        .push_raw_str(header);
+                }
+                // safe because this is valid JSON
+                metrics_buf.push_raw_str(chunk);
+            }
+        }
+        metrics_buf.push_raw_str("]}");
+    }
+}
+
 pub use metrique_writer_core::config::{AllowSplitEntries, EntryDimensions};
 
 struct EntryWriter<'a> {
     validation_map: hashbrown::HashMap<SCow<'a>, LineData>,
     state: &'a mut State,
     entry_dimensions: Option<Vec<JsonEncodedArray>>,
+    // the `EntryDimensions` `entry_dimensions` was computed from, merged if set more than once
+    entry_dimensions_config: Option<Cow<'a, EntryDimensions>>,
     validations: &'a Validation,
     timestamp: Option<SystemTime>,
     multiplicity: Option<u64>,
     error: ValidationErrorBuilder,
     allow_split_entries: bool,
     is_allow_unroutable_entries: bool,
+    always_present_metrics: &'a [(String, Unit)],
+    // indexes into `always_present_metrics` of the metrics written by the entry
+    always_present_seen: bit_set::BitSet<u32>,
 }
 
 impl<'a> metrique_writer_core::EntryWriter<'a> for EntryWriter<'a> {
@@ -1209,6 +1876,13 @@ impl<'a> metrique_writer_core::EntryWriter<'a> for EntryWriter<'a> {
 
     fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
         let name = name.into();
+        if let Some(index) = self
+            .always_present_metrics
+            .iter()
+            .position(|(n, _)| *n == name)
+        {
+            self.always_present_seen.insert(index);
+        }
         if self.validate_name(&name) {
             value.write(ValueWriter {
                 name: SCow(name),
@@ -1223,11 +1897,6 @@ impl<'a> metrique_writer_core::EntryWriter<'a> for EntryWriter<'a> {
                 self.error.invalid_mut("entry dimensions must be configured before emitting a metric with custom dimensions");
                 return;
             }
-            if self.entry_dimensions.is_some() {
-                self.error
-                    .invalid_mut("entry dimensions cannot be set twice");
-                return;
-            }
             if dimensions.is_empty() {
                 self.error.invalid_mut("entry dimensions cannot be empty");
                 return;
@@ -1262,10 +1931,27 @@ impl<'a> metrique_writer_core::EntryWriter<'a> for EntryWriter<'a> {
                     }
                 }
             }
+            // stacked entry adapters can each set entry dimensions, in which case they are merged
+            // into the union of their dimension sets
+            let dimensions = match self.entry_dimensions_config.take() {
+                None => Cow::Borrowed(dimensions),
+                Some(previous) => {
+                    match previous.merge(dimensions).and_then(|merged| {
+                        (merged as Box<dyn Any>).downcast::<EntryDimensions>().ok()
+                    }) {
+                        Some(merged) => Cow::Owned(*merged),
+                        None => {
+                            self.error
+                                .invalid_mut("entry dimensions could not be merged");
+                            return;
+                        }
+                    }
+                }
+            };
             // FIXME: this does a bunch of allocations. If there are performance problems here, it's probably
             // good to do some caching since there are probably not many EntryDimensions values (one for
             // every metric "shape", of which I expect to be O(1)).
-            let dimensions: Vec<JsonEncodedArray> = self
+            let entry_dimensions: Vec<JsonEncodedArray> = self
                 .state
                 .each_dimensions_str
                 .iter()
@@ -1275,7 +1961,8 @@ impl<'a> metrique_writer_core::EntryWriter<'a> for EntryWriter<'a> {
                         .map(|e| d.clone().to_owned().extend_with_strings(e))
                 })
                 .collect();
-            self.entry_dimensions = Some(dimensions);
+            self.entry_dimensions = Some(entry_dimensions);
+            self.entry_dimensions_config = Some(dimensions);
         }
         if (config as &dyn Any)
             .downcast_ref::<AllowSplitEntries>()
@@ -1292,8 +1979,18 @@ impl<'a> metrique_writer_core::EntryWriter<'a> for EntryWriter<'a> {
     }
 }
 
-impl EntryWriter<'_> {
-    fn finish(mut self, output: &mut impl io::Write) -> Result<(), IoStreamError> {
+impl<'a> EntryWriter<'a> {
+    fn write_always_present_metrics(&mut self) {
+        let always_present_metrics = self.always_present_metrics;
+        for (index, (name, unit)) in always_present_metrics.iter().enumerate() {
+            if !self.always_present_seen.contains(index) {
+                metrique_writer_core::EntryWriter::value(self, name, &ZeroMetric(*unit));
+            }
+        }
+    }
+
+    /// Write the lines of the entry to `output`, returning the number of bytes written
+    fn finish(mut self, output: &mut impl io::Write) -> Result<usize, IoStreamError> {
         if !self.validations.skip_validate_dimensions_exist && !self.is_allow_unroutable_entries {
             for (dim, value) in self.validation_map.iter_mut() {
                 if let LineData {
@@ -1311,8 +2008,12 @@ impl EntryWriter<'_> {
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap_or_default();
         let mut timestamp_buf = itoa::Buffer::new();
-        let timestamp_str = timestamp_buf.format(unix.as_millis());
+        let timestamp_str = match self.state.timestamp_unit {
+            TimestampUnit::Millis => timestamp_buf.format(unix.as_millis()),
+            TimestampUnit::Seconds => timestamp_buf.format(unix.as_secs()),
+        };
         self.error.build()?;
+        let mut written = 0;
         self.state
             .decl_buf
             // safe because timestamp is a number
@@ -1320,19 +2021,47 @@ impl EntryWriter<'_> {
                 &self.state.log_group_and_timestamp,
                 timestamp_str,
             );
+        if self.state.any_directive_upgraded {
+            // max wins: a metric that asks for high storage resolution is emitted at high
+            // storage resolution in every extra directive
+            let state = &mut *self.state;
+            let directives = state.decl_buf.prefix();
+            state.upgraded_decl_buf.clear();
+            let mut copied = 0;
+            for upgrade in state.directive_upgrades.iter().filter(|u| u.upgraded) {
+                state
+                    .upgraded_decl_buf
+                    .push_raw_str(&directives[copied..upgrade.range.start])
+                    .push_raw_str(r#","StorageResolution":1"#);
+                copied = upgrade.range.end;
+            }
+            state
+                .upgraded_decl_buf
+                .push_raw_str(&directives[copied..])
+                .push_raw_str(&state.decl_buf.as_str()[directives.len()..]);
+        }
         self.state.string_fields_buf.push_raw_str("}\n");
 
         let mut emitted_any_dimension_metrics = false;
 
         for entry in self.state.dimension_set_map.values_mut() {
-            entry.metrics_buf.push_raw_str("]}");
-            let metrics_len = entry.metrics_buf.as_str().len();
-            for namespace in &self.state.namespaces[1..] {
-                entry
-                    .metrics_buf
-                    .push_raw_str(r#",{"Namespace":"#)
-                    .push_json_safe_string(namespace)
-                    .extend_from_within_range(entry.after_namespace_index, metrics_len);
+            if entry.metrics_splits.is_empty() {
+                entry.metrics_buf.push_raw_str("]}");
+                let metrics_len = entry.metrics_buf.as_str().len();
+                for namespace in &self.state.namespaces[1..] {
+                    entry
+                        .metrics_buf
+                        .push_raw_str(r#",{"Namespace":"#)
+                        .push_json_safe_string(namespace)
+                        .extend_from_within_range(entry.after_namespace_index, metrics_len);
+                }
+            } else {
+                let header = entry.metrics_buf.prefix()[entry.after_namespace_index..].to_owned();
+                entry.metrics_splits.split_directives(
+                    &mut entry.metrics_buf,
+                    &header,
+                    &self.state.namespaces,
+                );
             }
             entry
                 .metrics_buf
@@ -1351,7 +2080,7 @@ impl EntryWriter<'_> {
                 continue;
             }
             emitted_any_dimension_metrics = true;
-            write_all_vectored(buf, output)?;
+            written += write_all_vectored(buf, output)?;
         }
 
         // if we emitted any dimensioned line and there are no fields with no dimensions,
@@ -1370,52 +2099,77 @@ impl EntryWriter<'_> {
                 }
                 self.state.dimensions_buf.push_json_safe_array(dimension);
             }
-            self.state.metrics_buf.push_raw_str("]}");
-            let metrics_len = self.state.metrics_buf.as_str().len();
-            for namespace in &self.state.namespaces[1..] {
-                self.state
-                    .metrics_buf
-                    .push_raw_str(r#",{"Namespace":"#)
-                    .push_json_safe_string(namespace)
-                    // safe because dimensions_buf[after_namespace_index..]
-                    // contains valid dimensions
-                    .push_raw_str(
-                        &self.state.dimensions_buf.as_str()[self.state.after_namespace_index..],
-                    )
-                    // safe because this is valid JSON
-                    .extend_from_within_range(0, metrics_len);
+            if self.state.metrics_splits.is_empty() {
+                self.state.metrics_buf.push_raw_str("]}");
+                let metrics_len = self.state.metrics_buf.as_str().len();
+                for namespace in &self.state.namespaces[1..] {
+                    self.state
+                        .metrics_buf
+                        .push_raw_str(r#",{"Namespace":"#)
+                        .push_json_safe_string(namespace)
+                        // safe because dimensions_buf[after_namespace_index..]
+                        // contains valid dimensions
+                        .push_raw_str(
+                            &self.state.dimensions_buf.as_str()[self.state.after_namespace_index..],
+                        )
+                        // safe because this is valid JSON
+                        .extend_from_within_range(0, metrics_len);
+                }
+            } else {
+                let header = format!(
+                    "{}{}",
+                    &self.state.dimensions_buf.as_str()[self.state.after_namespace_index..],
+                    self.state.metrics_buf.prefix()
+                );
+                self.state.metrics_splits.split_directives(
+                    &mut self.state.metrics_buf,
+                    &header,
+                    &self.state.namespaces,
+                );
             }
             // it's OK to write each line with a separate call to `write_all_vectored`,
             // since nothing bad occurs if lines are split.
             let buf: SmallVec<[_; 5]> = smallvec![
                 self.state.dimensions_buf.as_ref(),
                 self.state.metrics_buf.as_ref(),
-                self.state.decl_buf.as_ref(),
+                if self.state.any_directive_upgraded {
+                    self.state.upgraded_decl_buf.as_ref()
+                } else {
+                    self.state.decl_buf.as_ref()
+                },
                 self.state.fields_buf.as_ref(),
                 self.state.string_fields_buf.as_ref(),
             ];
-            write_all_vectored(buf, output)?;
+            written += write_all_vectored(buf, output)?;
         }
-        Ok(())
+        Ok(written)
     }
 
     fn validate_name(&mut self, name: &str) -> bool {
-        if !self.validations.skip_validate_names {
-            if name.is_empty() {
-                self.error
-                    .extend_mut(ValidationError::invalid("name can't be empty").for_field(""));
-                return false;
-            }
-            if name == "_aws" {
-                self.error
-                    .extend_mut(ValidationError::invalid("name can't be `_aws`").for_field("_aws"));
-                return false;
-            }
+        if !self.validations.skip_validate_names
+            && let Err(err) = EMF_NAME_RULES.validate(name)
+        {
+            self.error.extend_mut(err);
+            return false;
         }
         true
     }
 }
 
+/// The value written for [`EmfBuilder::always_present_metrics`] that are missing from an entry
+struct ZeroMetric(Unit);
+
+impl Value for ZeroMetric {
+    fn write(&self, writer: impl metrique_writer_core::ValueWriter) {
+        writer.metric([Observation::Unsigned(0)], self.0, [], MetricFlags::empty());
+    }
+}
+
+/// Control characters are allowed since they are escaped in the JSON output.
+const EMF_NAME_RULES: NameRules<'static> = NameRules::new()
+    .reserved(&["_aws"])
+    .allow_control_characters(true);
+
 struct FiniteFloat(f64);
 
 fn clamp_to_finite(float: f64, name_for_log: &str) -> Option<FiniteFloat> {
@@ -1577,11 +2331,25 @@ impl ValueWriter<'_, '_> {
         unit: Unit,
         flags: MetricFlags<'_>,
         multiplicity: Option<u64>,
+        skip_zero: bool,
+        nan_policy: NanPolicy,
     ) -> Result<(), ValidationError> {
-        let mut distribution = distribution.into_iter();
+        let mut saw_nan = false;
+        let mut distribution = distribution
+            .into_iter()
+            .map(|observation| nan_policy.apply(observation, &mut saw_nan))
+            .peekable();
         let Some(first) = distribution.next() else {
             return Ok(()); // skip metric with no observations
         };
+        let is_zero = match first {
+            Observation::Unsigned(value) => value == 0,
+            Observation::Floating(value) => value == 0.0,
+            _ => false,
+        };
+        if skip_zero && is_zero && distribution.peek().is_none() {
+            return Ok(()); // skip metric with a single zero observation
+        }
 
         // If write_metric_value skips a NaN metric, it will have already
         // written the metric name, so the buffer looks like
@@ -1592,14 +2360,19 @@ impl ValueWriter<'_, '_> {
         // There is always a comma, since `fields_buf` always contains at least the `}`
         // that closes the `_aws` block (and possibly other fields).
         let fields_buf_index = fields_buf.as_str().len();
-        if let Err(MetricSkipped) = Self::write_metric_value(
+        let result = Self::write_metric_value(
             name,
             fields_buf,
             counts_buf,
             first,
             distribution,
             multiplicity,
-        ) {
+        );
+        if saw_nan && nan_policy == NanPolicy::Error {
+            fields_buf.truncate(fields_buf_index);
+            return Err(ValidationError::invalid("metric has a NaN value"));
+        }
+        if let Err(MetricSkipped) = result {
             // skipping this metric, truncate the metric name
             fields_buf.truncate(fields_buf_index);
             return Ok(()); // skip metric with only NaN observations
@@ -1797,9 +2570,10 @@ impl metrique_writer_core::ValueWriter for ValueWriter<'_, '_> {
                     .for_field(&self.name),
             );
         }
-        let (metrics_buf, fields_buf, index) = if is_global {
+        let (metrics_buf, metrics_splits, fields_buf, index) = if is_global {
             (
                 &mut self.entry.state.metrics_buf,
+                &mut self.entry.state.metrics_splits,
                 &mut self.entry.state.fields_buf,
                 0,
             )
@@ -1822,9 +2596,15 @@ impl metrique_writer_core::ValueWriter for ValueWriter<'_, '_> {
                         each_dimensions_str,
                         &key,
                         index,
+                        &self.entry.state.buffer_sizes,
                     )
                 });
-            (&mut val.metrics_buf, &mut val.fields_buf, val.index.into())
+            (
+                &mut val.metrics_buf,
+                &mut val.metrics_splits,
+                &mut val.fields_buf,
+                val.index.into(),
+            )
         };
         if !self.entry.validations.skip_validate_unique && !self.entry.is_allow_unroutable_entries {
             // either the field is a true duplicate, or the field is an UnfoundDimension that is referred to as a metric
@@ -1860,7 +2640,26 @@ impl metrique_writer_core::ValueWriter for ValueWriter<'_, '_> {
             }
         }
 
-        if let Err(err) = Self::write_metric(
+        if !self.entry.state.directive_upgrades.is_empty()
+            && storage_mode(&flags) == Some(StorageMode::HighStorageResolution)
+        {
+            for upgrade in &mut self.entry.state.directive_upgrades {
+                if upgrade.name == *self.name {
+                    upgrade.upgraded = true;
+                    self.entry.state.any_directive_upgraded = true;
+                }
+            }
+        }
+
+        // always-present metrics are written even if they are zero
+        let skip_zero = self.entry.state.skip_zero_metrics
+            && !self
+                .entry
+                .always_present_metrics
+                .iter()
+                .any(|(name, _)| *name == *self.name);
+        let metric_start = metrics_buf.as_str().len();
+        let result = Self::write_metric(
             &self.name,
             fields_buf,
             metrics_buf,
@@ -1869,7 +2668,13 @@ impl metrique_writer_core::ValueWriter for ValueWriter<'_, '_> {
             unit,
             flags,
             self.entry.multiplicity,
-        ) {
+            skip_zero,
+            self.entry.state.nan_policy,
+        );
+        if metrics_buf.as_str().len() > metric_start {
+            metrics_splits.record(metric_start, self.entry.state.max_metrics_per_directive);
+        }
+        if let Err(err) = result {
             self.error(err);
         }
     }
@@ -1885,13 +2690,22 @@ impl Format for Emf {
         entry: &impl Entry,
         output: &mut impl io::Write,
     ) -> Result<(), IoStreamError> {
+        self.format_with_multiplicity(entry, output, None).map(drop)
+    }
+
+    // the lines are assembled in buffers before being written, so the count is free
+    fn format_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<usize, IoStreamError> {
         self.format_with_multiplicity(entry, output, None)
     }
 }
 
 // ordering is "who wins"
 #[derive(Clone, Copy, Debug, PartialOrd, Ord, PartialEq, Eq)]
-enum StorageMode {
+pub(crate) enum StorageMode {
     HighStorageResolution,
     NoMetric,
 }
@@ -1917,6 +2731,43 @@ impl MetricOptions for EmfOptions {
     }
 }
 
+/// The storage mode that the EMF flags of a metric ask for, if any
+pub(crate) fn storage_mode(flags: &MetricFlags<'_>) -> Option<StorageMode> {
+    flags
+        .downcast::<EmfOptions>()
+        .map(|options| options.storage_mode)
+}
+
+/// The EMF flags asking for `storage_mode`, the inverse of [`storage_mode`]
+pub(crate) fn storage_mode_flags(storage_mode: Option<StorageMode>) -> MetricFlags<'static> {
+    match storage_mode {
+        None => MetricFlags::empty(),
+        Some(StorageMode::HighStorageResolution) => MetricFlags::upcast(&EmfOptions {
+            storage_mode: StorageMode::HighStorageResolution,
+        }),
+        Some(StorageMode::NoMetric) => MetricFlags::upcast(&EmfOptions {
+            storage_mode: StorageMode::NoMetric,
+        }),
+    }
+}
+
+/// The storage resolution that the EMF flags of a metric ask for, or `None` if they ask for the
+/// value not to be reported as a metric (see [`NoMetric`]).
+///
+/// This lets formats that report to CloudWatch without going through EMF honor
+/// [`HighStorageResolution`] and [`NoMetric`].
+pub fn metric_storage_resolution(flags: &MetricFlags<'_>) -> Option<StorageResolution> {
+    match flags.downcast::<EmfOptions>() {
+        Some(EmfOptions {
+            storage_mode: StorageMode::NoMetric,
+        }) => None,
+        Some(EmfOptions {
+            storage_mode: StorageMode::HighStorageResolution,
+        }) => Some(StorageResolution::Second),
+        None => Some(StorageResolution::Minute),
+    }
+}
+
 /// Creates options for high storage resolution
 pub struct HighStorageResolutionCtor;
 
@@ -1987,6 +2838,14 @@ impl<R> Format for SampledEmf<R> {
     ) -> Result<(), IoStreamError> {
         self.emf.format(entry, output)
     }
+
+    fn format_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<usize, IoStreamError> {
+        self.emf.format_counted(entry, output)
+    }
 }
 
 /// return an (n, alpha) such that
@@ -2027,7 +2886,9 @@ impl<R: RngCore> SampledFormat for SampledEmf<R> {
             )));
         }
         let n = rate_to_n(rate, &mut self.rng);
-        self.emf.format_with_multiplicity(entry, output, Some(n))
+        self.emf
+            .format_with_multiplicity(entry, output, Some(n))
+            .map(drop)
     }
 }
 
@@ -2228,23 +3089,70 @@ mod tests {
                 vec!["MyDimension".to_string(), "MyOtherDimension".to_string()],
             ],
         )
-        .skip_all_validations(true) // emitted even with no validations
+        .skip_all_validations(true)
+        .build();
+        // stacked entry dimensions are merged rather than rejected
+        emf.format(&TestEntry, &mut vec![]).unwrap();
+
+        let mut emf: Emf = Emf::builder(
+            "TestNS".to_string(),
+            vec![
+                vec![],
+                vec!["MyDimension".to_string(), "MyOtherDimension".to_string()],
+            ],
+        )
+        .skip_all_validations(false) // emitted even with
         .build();
         let errors = format!("{}", emf.format(&TestEntry, &mut vec![]).unwrap_err());
-        assert!(errors.contains("entry dimensions cannot be set twice"));
+        assert!(errors.contains("for `Metric`: duplicate field")); // with validations, duplicate field is emitted
+    }
+
+    #[test]
+    fn test_stacked_entry_dimensions_are_merged() {
+        struct TestEntry;
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.config(
+                    const {
+                        &EntryDimensions::new(Cow::Borrowed(&[
+                            Cow::Borrowed(&[Cow::Borrowed("Operation")]),
+                            Cow::Borrowed(&[Cow::Borrowed("Operation"), Cow::Borrowed("Status")]),
+                        ]))
+                    },
+                );
+                writer.config(
+                    const {
+                        &EntryDimensions::new(Cow::Borrowed(&[
+                            Cow::Borrowed(&[Cow::Borrowed("Operation")]),
+                            Cow::Borrowed(&[Cow::Borrowed("Region")]),
+                        ]))
+                    },
+                );
+                writer.timestamp(SystemTime::UNIX_EPOCH);
+                writer.value("Operation", "Get");
+                writer.value("Status", "Ok");
+                writer.value("Region", "us-east-1");
+                writer.value("Az", "us-east-1a");
+                writer.value("Count", &1u64);
+            }
+        }
 
-        let mut emf: Emf = Emf::builder(
-            "TestNS".to_string(),
-            vec![
-                vec![],
-                vec!["MyDimension".to_string(), "MyOtherDimension".to_string()],
-            ],
-        )
-        .skip_all_validations(false) // emitted even with
-        .build();
-        let errors = format!("{}", emf.format(&TestEntry, &mut vec![]).unwrap_err());
-        assert!(errors.contains("for `Metric`: duplicate field")); // with validations, duplicate field is emitted as well
-        assert!(errors.contains("entry dimensions cannot be set twice"));
+        let mut emf =
+            Emf::all_validations("TestNS".to_string(), vec![vec![], vec!["Az".to_string()]]);
+        let mut output = vec![];
+        emf.format(&TestEntry, &mut output).unwrap();
+        let output: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            output["_aws"]["CloudWatchMetrics"][0]["Dimensions"],
+            serde_json::json!([
+                ["Operation"],
+                ["Operation", "Status"],
+                ["Region"],
+                ["Az", "Operation"],
+                ["Az", "Operation", "Status"],
+                ["Az", "Region"],
+            ])
+        );
     }
 
     #[test]
@@ -3769,6 +4677,106 @@ mod tests {
         );
     }
 
+    #[test]
+    fn directive_storage_resolution_merges_with_flags() {
+        struct TestEntry {
+            high_res: bool,
+        }
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.timestamp(SystemTime::UNIX_EPOCH);
+                writer.value("Operation", "Foo");
+                if self.high_res {
+                    writer.value("Latency", &HighStorageResolution::from(5u64));
+                } else {
+                    writer.value("Latency", &5u64);
+                }
+                writer.value("Count", &1u64);
+            }
+        }
+
+        let definition = |name, storage_resolution| MetricDefinition {
+            name,
+            unit: Unit::Count,
+            storage_resolution,
+        };
+        let mut emf = Emf::builder("TestNS".to_string(), vec![vec![]])
+            .directive(MetricDirective {
+                dimensions: vec![vec!["Operation"]],
+                metrics: vec![
+                    definition("Latency", Some(StorageResolution::Second)),
+                    definition("Count", None),
+                ],
+                namespace: "TestNS",
+            })
+            .directive(MetricDirective {
+                dimensions: vec![vec![]],
+                metrics: vec![
+                    definition("Count", Some(StorageResolution::Minute)),
+                    definition("Latency", Some(StorageResolution::Minute)),
+                ],
+                namespace: "TestNS",
+            })
+            .directive(MetricDirective {
+                dimensions: vec![vec!["Operation"]],
+                metrics: vec![definition("Latency", None)],
+                namespace: "OtherNS",
+            })
+            .build();
+
+        let mut format = |high_res| {
+            let mut output = vec![];
+            emf.format(&TestEntry { high_res }, &mut output).unwrap();
+            serde_json::from_slice::<serde_json::Value>(&output).unwrap()["_aws"]
+                ["CloudWatchMetrics"]
+                .clone()
+        };
+
+        // a high resolution directive doesn't affect the other directives
+        assert_json_diff::assert_json_eq!(
+            format(false),
+            serde_json::json!([
+                {"Namespace": "TestNS", "Dimensions": [[]], "Metrics": [
+                    {"Name": "Latency"}, {"Name": "Count"},
+                ]},
+                {"Dimensions": [["Operation"]], "Namespace": "TestNS", "Metrics": [
+                    {"Name": "Latency", "Unit": "Count", "StorageResolution": 1}, {"Name": "Count", "Unit": "Count"},
+                ]},
+                {"Dimensions": [[]], "Namespace": "TestNS", "Metrics": [
+                    {"Name": "Count", "Unit": "Count", "StorageResolution": 60},
+                    {"Name": "Latency", "Unit": "Count", "StorageResolution": 60},
+                ]},
+                {"Dimensions": [["Operation"]], "Namespace": "OtherNS", "Metrics": [
+                    {"Name": "Latency", "Unit": "Count"},
+                ]},
+            ])
+        );
+        // a high resolution value is emitted at high resolution in every directive
+        assert_json_diff::assert_json_eq!(
+            format(true),
+            serde_json::json!([
+                {"Namespace": "TestNS", "Dimensions": [[]], "Metrics": [
+                    {"Name": "Latency", "StorageResolution": 1}, {"Name": "Count"},
+                ]},
+                {"Dimensions": [["Operation"]], "Namespace": "TestNS", "Metrics": [
+                    {"Name": "Latency", "Unit": "Count", "StorageResolution": 1}, {"Name": "Count", "Unit": "Count"},
+                ]},
+                {"Dimensions": [[]], "Namespace": "TestNS", "Metrics": [
+                    {"Name": "Count", "Unit": "Count", "StorageResolution": 60},
+                    {"Name": "Latency", "Unit": "Count", "StorageResolution": 1},
+                ]},
+                {"Dimensions": [["Operation"]], "Namespace": "OtherNS", "Metrics": [
+                    {"Name": "Latency", "Unit": "Count", "StorageResolution": 1},
+                ]},
+            ])
+        );
+        // the upgrade doesn't leak into the next entry
+        assert_eq!(
+            format(false)[3]["Metrics"][0],
+            serde_json::json!({"Name": "Latency", "Unit": "Count"})
+        );
+    }
+
     #[rstest]
     #[case("Foo", "Region", true)]
     // merging property "_aws" is illegal
@@ -3852,4 +4860,503 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn metric_storage_resolution_follows_flags() {
+        assert!(matches!(
+            metric_storage_resolution(&MetricFlags::empty()),
+            Some(StorageResolution::Minute)
+        ));
+        assert!(matches!(
+            metric_storage_resolution(&HighStorageResolutionCtor::construct()),
+            Some(StorageResolution::Second)
+        ));
+        assert!(metric_storage_resolution(&NoMetricCtor::construct()).is_none());
+    }
+
+    #[test]
+    fn splits_directives_with_too_many_metrics() {
+        struct TestEntry(Vec<String>);
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.config(const { &AllowSplitEntries::new() });
+                writer.timestamp(SystemTime::UNIX_EPOCH);
+                writer.value("Operation", "Foo");
+                for (i, name) in self.0.iter().enumerate() {
+                    writer.value(name.as_str(), &(i as u64));
+                }
+                // skipped metrics don't count towards the limit
+                writer.value("Nan", &f64::NAN);
+                writer.value("Hidden", &NoMetric::from(1u64));
+                writer.value(
+                    "PerShard",
+                    &WithDimension::new_with_dimensions(1u64, [("Shard", "1")]),
+                );
+                writer.value(
+                    "PerShard2",
+                    &WithDimension::new_with_dimensions(2u64, [("Shard", "1")]),
+                );
+            }
+        }
+
+        fn directives(line: &serde_json::Value) -> Vec<(String, serde_json::Value, Vec<String>)> {
+            line["_aws"]["CloudWatchMetrics"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|d| {
+                    let names = d["Metrics"].as_array().unwrap().iter();
+                    (
+                        d["Namespace"].as_str().unwrap().to_owned(),
+                        d["Dimensions"].clone(),
+                        names
+                            .map(|m| m["Name"].as_str().unwrap().to_owned())
+                            .collect(),
+                    )
+                })
+                .collect()
+        }
+
+        let names: Vec<_> = (0..250).map(|i| format!("Metric{i}")).collect();
+        let mut emf = Emf::builder("NS1".to_string(), vec![vec!["Operation".to_string()]])
+            .add_namespace("NS2".to_string())
+            .build();
+        let mut output = vec![];
+        emf.format(&TestEntry(names.clone()), &mut output).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_slice(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+
+        let dims = serde_json::json!([["Operation"]]);
+        let expected: Vec<_> = ["NS1", "NS2"]
+            .into_iter()
+            .flat_map(|ns| {
+                names
+                    .chunks(100)
+                    .map(|chunk| (ns.to_owned(), dims.clone(), chunk.to_vec()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        // the entry with per-metric dimensions is written first
+        assert_eq!(directives(&lines[1]), expected);
+        for (i, name) in names.iter().enumerate() {
+            assert_eq!(lines[1][name], i);
+        }
+        assert_eq!(lines[1]["Hidden"], 1);
+        assert_eq!(lines[1]["Operation"], "Foo");
+
+        // the limit is configurable, and applies to split entries as well
+        let mut emf = Emf::builder("NS1".to_string(), vec![vec!["Operation".to_string()]])
+            .add_namespace("NS2".to_string())
+            .max_metrics_per_directive(1)
+            .build();
+        let mut output = vec![];
+        emf.format(&TestEntry(vec!["A".into(), "B".into()]), &mut output)
+            .unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty())
+            .map(|l| serde_json::from_slice(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        let shard_dims = serde_json::json!([["Operation", "Shard"]]);
+        let directive = |ns: &str, dims: &serde_json::Value, name: &str| {
+            (ns.to_owned(), dims.clone(), vec![name.to_owned()])
+        };
+        assert_eq!(
+            directives(&lines[0]),
+            [
+                directive("NS1", &shard_dims, "PerShard"),
+                directive("NS1", &shard_dims, "PerShard2"),
+                directive("NS2", &shard_dims, "PerShard"),
+                directive("NS2", &shard_dims, "PerShard2"),
+            ]
+        );
+        assert_eq!(lines[0]["PerShard2"], 2);
+        assert_eq!(
+            directives(&lines[1]),
+            [
+                directive("NS1", &dims, "A"),
+                directive("NS1", &dims, "B"),
+                directive("NS2", &dims, "A"),
+                directive("NS2", &dims, "B"),
+            ]
+        );
+        assert_eq!(lines[1]["B"], 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "max_metrics_per_directive must be at least 1")]
+    fn max_metrics_per_directive_must_be_positive() {
+        Emf::builder("NS".to_string(), vec![vec![]]).max_metrics_per_directive(0);
+    }
+
+    #[test]
+    fn skip_zero_metrics() {
+        struct TestEntry {
+            duplicate: bool,
+        }
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.timestamp(SystemTime::UNIX_EPOCH);
+                writer.value("Operation", "Foo");
+                writer.value("Zero", &0u64);
+                writer.value("ZeroFloat", &0.0f64);
+                writer.value("ZeroDuration", &Duration::ZERO);
+                writer.value("One", &1u64);
+                writer.value(
+                    "ZeroDistribution",
+                    &Distribution::<u64, 2>::from_iter([0, 0]),
+                );
+                writer.value("ZeroMean", &Mean::<Millisecond>::from_iter([0u32, 0]));
+                writer.value("Fault", &0u64);
+                if self.duplicate {
+                    writer.value("Zero", &0u64);
+                }
+            }
+        }
+
+        let mut emf = Emf::builder("TestNS".to_string(), vec![vec!["Operation".to_string()]])
+            .always_present_metrics(&[("Fault", Unit::Count), ("Error", Unit::Count)])
+            .skip_zero_metrics(true)
+            .build();
+        let mut output = vec![];
+        emf.format(&TestEntry { duplicate: false }, &mut output)
+            .unwrap();
+        assert_json_eq!(
+            serde_json::from_slice::<serde_json::Value>(&output).unwrap(),
+            serde_json::json!({
+                "_aws": {
+                    "CloudWatchMetrics": [{"Namespace": "TestNS", "Dimensions": [["Operation"]], "Metrics": [
+                        {"Name": "One"},
+                        {"Name": "ZeroDistribution"},
+                        {"Name": "ZeroMean", "Unit": "Milliseconds"},
+                        {"Name": "Fault"},
+                        {"Name": "Error", "Unit": "Count"},
+                    ]}],
+                    "Timestamp": 0,
+                },
+                "Operation": "Foo",
+                "One": 1,
+                "ZeroDistribution": {"Values": [0, 0], "Counts": [1, 1]},
+                "ZeroMean": {"Values": [0], "Counts": [2]},
+                "Fault": 0,
+                "Error": 0,
+            })
+        );
+
+        // skipped metrics are still validated
+        let err = emf
+            .format(&TestEntry { duplicate: true }, &mut vec![])
+            .unwrap_err();
+        assert!(err.to_string().contains("duplicate field"), "{err}");
+    }
+
+    #[test]
+    fn nan_policy() {
+        struct TestEntry;
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.timestamp(SystemTime::UNIX_EPOCH);
+                writer.value("Ok", &1.5f64);
+                writer.value("Nan", &f64::NAN);
+                writer.value("Infinite", &f64::INFINITY);
+                writer.value(
+                    "NanDistribution",
+                    &Distribution::<f64, 2>::from_iter([f64::NAN, 2.0]),
+                );
+                writer.value("NanMean", &Mean::<Second>::from_iter([f64::NAN, 1.0]));
+            }
+        }
+
+        let format = |policy| {
+            let mut emf = Emf::builder("TestNS".to_string(), vec![vec![]])
+                .nan_policy(policy)
+                .build();
+            let mut output = vec![];
+            emf.format(&TestEntry, &mut output)?;
+            let mut output = serde_json::from_slice::<serde_json::Value>(&output).unwrap();
+            output.as_object_mut().unwrap().remove("_aws");
+            Ok::<_, IoStreamError>(output)
+        };
+
+        assert_json_eq!(
+            format(NanPolicy::Skip).unwrap(),
+            serde_json::json!({
+                "Ok": 1.5,
+                "Infinite": f64::MAX,
+                "NanDistribution": {"Values": [2], "Counts": [1]},
+            })
+        );
+        assert_json_eq!(
+            format(NanPolicy::Zero).unwrap(),
+            serde_json::json!({
+                "Ok": 1.5,
+                "Nan": 0,
+                "Infinite": f64::MAX,
+                "NanDistribution": {"Values": [0, 2], "Counts": [1, 1]},
+                "NanMean": {"Values": [0], "Counts": [2]},
+            })
+        );
+        let err = format(NanPolicy::Error).unwrap_err().to_string();
+        for field in ["Nan", "NanDistribution", "NanMean"] {
+            assert!(
+                err.contains(&format!("for `{field}`: metric has a NaN value")),
+                "{err}"
+            );
+        }
+        assert!(!err.contains("`Ok`"), "{err}");
+    }
+
+    #[test]
+    fn buffer_capacity_does_not_change_output() {
+        struct TestEntry;
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.config(const { &AllowSplitEntries::new() });
+                writer.timestamp(SystemTime::UNIX_EPOCH);
+                for i in 0..300u64 {
+                    writer.value(format!("Metric{i}"), &i);
+                    writer.value(format!("String{i}"), "some string value");
+                }
+                writer.value(
+                    "Distribution",
+                    &Distribution::<u64, 1000>::from_iter(0..1000),
+                );
+                writer.value(
+                    "PerShard",
+                    &WithDimension::new_with_dimensions(1u64, [("Shard", "1")]),
+                );
+            }
+        }
+
+        let builder = || {
+            Emf::builder("TestNS".to_string(), vec![vec![]])
+                .add_namespace("OtherNS".to_string())
+                .add_constant_field("Host", "h1")
+        };
+        let large = BufferSizes {
+            fields: 256 * 1024,
+            string_fields: 64 * 1024,
+            metrics: 64 * 1024,
+            dimensions: 4096,
+            counts: 16 * 1024,
+            declarations: 4096,
+        };
+        let tiny = BufferSizes {
+            fields: 0,
+            string_fields: 0,
+            metrics: 0,
+            dimensions: 0,
+            counts: 0,
+            declarations: 0,
+        };
+
+        let mut expected = vec![];
+        builder().build().format(&TestEntry, &mut expected).unwrap();
+        for sizes in [large, tiny] {
+            let mut emf = builder().with_buffer_capacity(sizes).build();
+            // formatting twice also checks the buffers are reset correctly
+            for _ in 0..2 {
+                let mut output = vec![];
+                emf.format(&TestEntry, &mut output).unwrap();
+                assert_eq!(output, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn timestamp_unit() {
+        struct TestEntry;
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.timestamp(SystemTime::UNIX_EPOCH + Duration::from_millis(1_749_475_336_015));
+                writer.value("Count", &1u64);
+            }
+        }
+
+        let timestamp = |emf: EmfBuilder| {
+            let mut output = vec![];
+            emf.build().format(&TestEntry, &mut output).unwrap();
+            let output = String::from_utf8(output).unwrap();
+            let timestamp =
+                serde_json::from_str::<serde_json::Value>(&output).unwrap()["_aws"]["Timestamp"]
+                    .clone();
+            (output, timestamp)
+        };
+        let builder = Emf::builder("TestNS".to_string(), vec![vec![]]);
+
+        let (default, millis) = timestamp(builder.clone());
+        assert_eq!(millis, serde_json::json!(1_749_475_336_015u64));
+        assert_eq!(
+            default,
+            timestamp(builder.clone().timestamp_unit(TimestampUnit::Millis)).0
+        );
+        assert_eq!(
+            timestamp(builder.timestamp_unit(TimestampUnit::Seconds)).1,
+            serde_json::json!(1_749_475_336u64)
+        );
+    }
+
+    #[test]
+    fn format_counted_returns_bytes_written() {
+        struct TestEntry;
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.config(const { &AllowSplitEntries::new() });
+                writer.timestamp(SystemTime::UNIX_EPOCH);
+                writer.value("Operation", "Get");
+                writer.value("Latency", &1u64);
+                writer.value("Throttles", &WithDimension::new(1u64, "Operation", "Get"));
+            }
+        }
+
+        let mut emf = Emf::builder("TestNS".to_string(), vec![vec![]])
+            .add_namespace("OtherNS".to_string())
+            .build();
+        let mut output = vec![];
+        let written = emf.format_counted(&TestEntry, &mut output).unwrap();
+        assert_eq!(String::from_utf8_lossy(&output).lines().count(), 2);
+        assert_eq!(written, output.len());
+
+        let mut sampled = emf.with_sampling();
+        let mut output = vec![];
+        let written = sampled.format_counted(&TestEntry, &mut output).unwrap();
+        assert_eq!(written, output.len());
+    }
+
+    #[test]
+    fn constant_fields_are_added_to_every_line() {
+        struct TestEntry(Option<&'static str>);
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.config(const { &AllowSplitEntries::new() });
+                writer.timestamp(SystemTime::UNIX_EPOCH);
+                writer.value("Operation", "Get");
+                writer.value("Latency", &1u64);
+                writer.value(
+                    "Throttles",
+                    &WithDimension::new_with_dimensions(2u64, [("Shard", "1")]),
+                );
+                if let Some(host) = self.0 {
+                    writer.value("Host", host);
+                }
+            }
+        }
+
+        let builder = || {
+            Emf::builder(
+                "TestNS".to_string(),
+                vec![vec!["Operation".to_string(), "Region".to_string()]],
+            )
+            .add_constant_field("Host", "h\"1")
+            .add_constant_field("Region", "us-east-1")
+        };
+
+        for mut emf in [
+            builder().skip_all_validations(false).build(),
+            builder()
+                .skip_all_validations(false)
+                .allow_dimensions_with_no_data(true)
+                .build(),
+        ] {
+            let mut output = Vec::new();
+            emf.format(&TestEntry(None), &mut output).unwrap();
+            let lines = String::from_utf8(output).unwrap();
+            let lines: Vec<serde_json::Value> = lines
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect();
+            // one line for the split metric, one for the others
+            assert_eq!(lines.len(), 2);
+            for line in &lines {
+                assert_eq!(line["Host"], "h\"1");
+                // constant fields can provide a dimension
+                assert_eq!(line["Region"], "us-east-1");
+            }
+
+            let errors = emf
+                .format(&TestEntry(Some("other")), &mut vec![])
+                .unwrap_err()
+                .to_string();
+            assert!(errors.contains("for `Host`: duplicate field"), "{errors}");
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid constant field name")]
+    fn constant_field_name_is_validated() {
+        let _ = Emf::builder("TestNS".to_string(), vec![vec![]]).add_constant_field("_aws", "x");
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate constant field `Host`")]
+    fn constant_field_names_are_unique() {
+        let _ = Emf::builder("TestNS".to_string(), vec![vec![]])
+            .add_constant_field("Host", "a")
+            .add_constant_field("Host", "b");
+    }
+
+    #[test]
+    fn resource_attributes_are_added_to_every_line() {
+        struct TestEntry(bool);
+        impl Entry for TestEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.config(const { &AllowSplitEntries::new() });
+                writer.timestamp(SystemTime::UNIX_EPOCH);
+                writer.value("Latency", &1u64);
+                writer.value(
+                    "Throttles",
+                    &WithDimension::new_with_dimensions(2u64, [("Shard", "1")]),
+                );
+                if self.0 {
+                    writer.value("resource", "other");
+                }
+            }
+        }
+
+        let mut emf = Emf::builder("TestNS".to_string(), vec![vec![]])
+            .with_resource_attributes([("ignored", "replaced")])
+            .with_resource_attributes([("service.name", "a\"b"), ("host.name", "h1")])
+            .add_constant_field("Host", "h1")
+            .build();
+        let mut output = Vec::new();
+        emf.format(&TestEntry(false), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<serde_json::Value> = output
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        for line in &lines {
+            assert_eq!(
+                line["resource"],
+                serde_json::json!({"host.name": "h1", "service.name": "a\"b"})
+            );
+            assert_eq!(line["Host"], "h1");
+        }
+        // attributes are written in a stable order
+        assert!(output.contains(r#""resource":{"host.name":"h1","service.name":"a\"b"}"#));
+
+        let errors = emf
+            .format(&TestEntry(true), &mut vec![])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            errors.contains("for `resource`: duplicate field"),
+            "{errors}"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "constant field `resource` conflicts with the resource attributes")]
+    fn resource_attributes_conflict_with_constant_field() {
+        let _ = Emf::builder("TestNS".to_string(), vec![vec![]])
+            .add_constant_field("resource", "a")
+            .with_resource_attributes([("service.name", "b")])
+            .build();
+    }
 }
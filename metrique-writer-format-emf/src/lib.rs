@@ -5,15 +5,17 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+mod batch;
 mod buf;
 mod emf;
 mod json_string;
 mod rate_limit;
 
+pub use batch::{BatchedEmfStream, DEFAULT_MAX_ENTRIES_PER_EVENT};
 pub use emf::{
-    AllowSplitEntries, Emf, EmfBuilder, EntryDimensions, HighStorageResolution,
-    HighStorageResolutionCtor, MetricDefinition, MetricDirective, NoMetric, NoMetricCtor,
-    SampledEmf, StorageResolution,
+    AllowSplitEntries, BufferSizes, Emf, EmfBuilder, EntryDimensions, HighStorageResolution,
+    HighStorageResolutionCtor, MetricDefinition, MetricDirective, NanPolicy, NoMetric,
+    NoMetricCtor, SampledEmf, StorageResolution, TimestampUnit, metric_storage_resolution,
 };
 
 /// Re-exports of `FlagConstructor` types for use in `#[metrics(flags(...))]` attributes.
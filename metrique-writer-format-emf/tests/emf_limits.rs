@@ -4,8 +4,8 @@
 //! Comprehensive tests for EMF (Embedded Metric Format) limits validation.
 //!
 //! This module tests the behavior of the metrique library when EMF limits are approached
-//! or exceeded. In the future, this behavior will be changed to truncation, however, for the moment
-//! this test serves to document the current behavior.
+//! or exceeded. Directives with too many metrics are split, other limits are not enforced yet
+//! and these tests serve to document the current behavior.
 
 use metrique_writer::{
     Entry, EntryWriter, MetricFlags, Observation, Unit, Value, ValueWriter, format::Format,
@@ -347,17 +347,13 @@ fn test_boundary_metrics_101() {
     let entry = MetricCountTestEntry::new(101);
     let emf_output = format_entry_to_emf(&entry);
 
-    // Document current behavior - this will likely be > 100 until truncation is implemented
-    let total_metrics = emf_output.count_total_metrics();
-    let max_per_directive = emf_output.max_metrics_per_directive();
-    println!(
-        "Current behavior with 101 metrics: {} total metrics, {} max per directive",
-        total_metrics, max_per_directive
-    );
-    // Current behavior: no truncation implemented, so 101 metrics are all in one directive
+    // metrics beyond the limit are split into another directive
+    assert_eq!(emf_output.count_total_metrics(), 101);
+    assert_eq!(emf_output.aws.cloudwatch_metrics.len(), 2);
     assert_eq!(
-        max_per_directive, 101,
-        "Current behavior: no truncation, 101 metrics in single directive"
+        emf_output.max_metrics_per_directive(),
+        100,
+        "Should have max 100 metrics per directive"
     );
 }
 
@@ -7,7 +7,8 @@ use std::{
 };
 
 use metrique_writer::{
-    Entry, EntryIoStream, EntrySink, EntryWriter, FormatExt, sink::BackgroundQueue,
+    Entry, EntryIoStream, EntryIoStreamExt, EntrySink, EntryWriter, FormatExt,
+    sink::BackgroundQueue,
 };
 use metrique_writer_core::test_stream::TestSink;
 use metrique_writer_format_emf::Emf;
@@ -304,3 +305,209 @@ fn test_vec_single_observation_stays_scalar_in_emf() {
     let output: serde_json::Value = serde_json::from_str(&sink.dump()).unwrap();
     assert_json_diff::assert_json_eq!(output["Data"], serde_json::json!([10, 20]));
 }
+
+#[test]
+fn test_const_globals_merged_into_stream() {
+    let mut output = Vec::new();
+    let mut stream = Emf::all_validations("MyApp".into(), vec![vec!["AZ".into()]])
+        .output_to(&mut output)
+        .merge_globals(metrique_writer_core::const_globals! {
+            "AZ" => "us-east-1a",
+            "Version" => String::from("1.0.0"),
+        });
+    stream.next(&TestEntry { count: 3 }).unwrap();
+    stream.flush().unwrap();
+
+    let output: serde_json::Value =
+        serde_json::from_str(String::from_utf8(output).unwrap().trim()).unwrap();
+    assert_eq!(output["AZ"], "us-east-1a");
+    assert_eq!(output["Version"], "1.0.0");
+    assert_eq!(output["BasicIntCount"], 3);
+    assert_eq!(
+        output["_aws"]["CloudWatchMetrics"][0]["Dimensions"],
+        serde_json::json!([["AZ"]])
+    );
+}
+
+struct LatencyEntry {
+    latency: metrique_writer_core::entry::ResultEntry<Duration, &'static str>,
+}
+
+impl Entry for LatencyEntry {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        writer.timestamp(SystemTime::UNIX_EPOCH);
+        self.latency.write(writer);
+    }
+}
+
+fn format_result_entry(latency: Result<Duration, &'static str>) -> serde_json::Value {
+    let mut output = Vec::new();
+    let mut stream = Emf::all_validations("MyApp".into(), vec![vec![]]).output_to(&mut output);
+    stream
+        .next(&LatencyEntry {
+            latency: metrique_writer_core::entry::ResultEntry::new("Latency", latency),
+        })
+        .unwrap();
+    stream.flush().unwrap();
+    serde_json::from_str(String::from_utf8(output).unwrap().trim()).unwrap()
+}
+
+#[test]
+fn test_result_entry_ok() {
+    let output = format_result_entry(Ok(Duration::from_millis(42)));
+    assert_eq!(output["Latency"], 42);
+    assert!(output.get("LatencyError").is_none());
+    assert_eq!(
+        output["_aws"]["CloudWatchMetrics"][0]["Metrics"],
+        serde_json::json!([{"Name": "Latency", "Unit": "Milliseconds"}])
+    );
+}
+
+#[test]
+fn test_result_entry_err() {
+    let output = format_result_entry(Err("timed out"));
+    assert_eq!(output["LatencyError"], 1);
+    assert!(output.get("Latency").is_none());
+    assert_eq!(
+        output["_aws"]["CloudWatchMetrics"][0]["Metrics"],
+        serde_json::json!([{"Name": "LatencyError", "Unit": "Count"}])
+    );
+}
+
+struct TimingsEntry {
+    resolve: u64,
+    connect: u64,
+    request_size: metrique_writer::unit::AsBytes<u64>,
+    latency: Duration,
+}
+
+impl Entry for TimingsEntry {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        writer.timestamp(SystemTime::UNIX_EPOCH);
+        writer.value("Resolve", &self.resolve);
+        writer.value("Connect", &self.connect);
+        writer.value("RequestSize", &self.request_size);
+        writer.value("Latency", &self.latency);
+        writer.value("Operation", "Foo");
+    }
+}
+
+fn format_entry(entry: &impl Entry) -> serde_json::Value {
+    let mut output = Vec::new();
+    let mut stream = Emf::all_validations("MyApp".into(), vec![vec![]]).output_to(&mut output);
+    stream.next(entry).unwrap();
+    stream.flush().unwrap();
+    serde_json::from_str(String::from_utf8(output).unwrap().trim()).unwrap()
+}
+
+#[test]
+fn test_with_entry_unit() {
+    use metrique_writer::entry::WithEntryUnit;
+    use metrique_writer::unit::{Microsecond, Second};
+
+    let timings = || TimingsEntry {
+        resolve: 20,
+        connect: 150,
+        request_size: 1024.into(),
+        latency: Duration::from_millis(3),
+    };
+
+    let output = format_entry(&WithEntryUnit::<_, Microsecond>::new(timings()));
+    assert_eq!(output["Resolve"], 20);
+    assert_eq!(output["Connect"], 150);
+    assert_eq!(output["Operation"], "Foo");
+    // values that already have a unit keep it
+    assert_eq!(
+        output["_aws"]["CloudWatchMetrics"][0]["Metrics"],
+        serde_json::json!([
+            {"Name": "Resolve", "Unit": "Microseconds"},
+            {"Name": "Connect", "Unit": "Microseconds"},
+            {"Name": "RequestSize", "Unit": "Bytes"},
+            {"Name": "Latency", "Unit": "Milliseconds"},
+        ])
+    );
+
+    // the innermost unit wins
+    let output = format_entry(&WithEntryUnit::<_, Second>::new(WithEntryUnit::<
+        _,
+        Microsecond,
+    >::new(timings())));
+    assert_eq!(
+        output["_aws"]["CloudWatchMetrics"][0]["Metrics"][0],
+        serde_json::json!({"Name": "Resolve", "Unit": "Microseconds"})
+    );
+}
+
+#[test]
+fn test_without_entry_unit() {
+    let output = format_entry(&TimingsEntry {
+        resolve: 20,
+        connect: 150,
+        request_size: 1024.into(),
+        latency: Duration::from_millis(3),
+    });
+    assert_eq!(
+        output["_aws"]["CloudWatchMetrics"][0]["Metrics"][0],
+        serde_json::json!({"Name": "Resolve"})
+    );
+}
+
+struct RequestEntry {
+    fault: Option<u64>,
+}
+
+impl Entry for RequestEntry {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        writer.timestamp(SystemTime::UNIX_EPOCH);
+        writer.value("Operation", "Foo");
+        writer.value("Latency", &Duration::from_millis(3));
+        if let Some(fault) = &self.fault {
+            writer.value("Fault", fault);
+        }
+    }
+}
+
+#[test]
+fn test_always_present_metrics() {
+    use metrique_writer::unit::Unit;
+
+    let format = |entry: &RequestEntry| {
+        let mut output = Vec::new();
+        let mut stream = Emf::builder("MyApp".into(), vec![vec!["Operation".into()]])
+            .always_present_metrics(&[("Fault", Unit::Count), ("Error", Unit::Count)])
+            .always_present_metrics(&[("Latency", Unit::None)])
+            .build()
+            .output_to(&mut output);
+        stream.next(entry).unwrap();
+        stream.flush().unwrap();
+        serde_json::from_str::<serde_json::Value>(String::from_utf8(output).unwrap().trim())
+            .unwrap()
+    };
+
+    // absent metrics are emitted as zero
+    let output = format(&RequestEntry { fault: None });
+    assert_eq!(output["Fault"], 0);
+    assert_eq!(output["Error"], 0);
+    assert_eq!(output["Latency"], 3);
+    assert_eq!(
+        output["_aws"]["CloudWatchMetrics"][0]["Metrics"],
+        serde_json::json!([
+            {"Name": "Latency", "Unit": "Milliseconds"},
+            {"Name": "Fault", "Unit": "Count"},
+            {"Name": "Error", "Unit": "Count"},
+        ])
+    );
+
+    // present metrics keep their value
+    let output = format(&RequestEntry { fault: Some(1) });
+    assert_eq!(output["Fault"], 1);
+    assert_eq!(output["Error"], 0);
+    assert_eq!(
+        output["_aws"]["CloudWatchMetrics"][0]["Metrics"],
+        serde_json::json!([
+            {"Name": "Latency", "Unit": "Milliseconds"},
+            {"Name": "Fault"},
+            {"Name": "Error", "Unit": "Count"},
+        ])
+    );
+}
@@ -17,7 +17,7 @@ use rand::{Rng, RngCore};
 
 // Maximum buffer size before shrinking on clear. Prevents one large entry from
 // permanently bloating memory.
-const MAX_BUF_RETAIN: usize = 1024 * 1024;
+pub(crate) const MAX_BUF_RETAIN: usize = 1024 * 1024;
 
 /// A pure JSON formatter for metrique metrics.
 ///
@@ -219,7 +219,7 @@ struct JsonValueWriter<'b, 'c> {
 ///
 /// String values are JSON-escaped. Metric values write their observations as
 /// numeric scalars (single observation) or nested sub-arrays (multiple observations).
-struct JsonArrayElementWriter<'a>(&'a mut String);
+pub(crate) struct JsonArrayElementWriter<'a>(pub(crate) &'a mut String);
 
 impl ValueWriter for JsonArrayElementWriter<'_> {
     fn string(self, value: &str) {
@@ -341,7 +341,7 @@ fn push_observation_comma(buf: &mut String, obs: Observation, multiplicity: Opti
 }
 
 /// Push a scalar observation value into the buffer.
-fn push_observation(buf: &mut String, obs: Observation, multiplicity: Option<u64>) {
+pub(crate) fn push_observation(buf: &mut String, obs: Observation, multiplicity: Option<u64>) {
     match obs {
         Observation::Unsigned(v) => {
             buf.push_str(itoa::Buffer::new().format(v));
@@ -380,7 +380,7 @@ fn push_float(buf: &mut String, v: f64) {
 }
 
 /// Push a JSON-escaped string with surrounding quotes into the buffer.
-fn push_json_string(buf: &mut String, s: &str) {
+pub(crate) fn push_json_string(buf: &mut String, s: &str) {
     buf.push('"');
     let bytes = s.as_bytes();
     let mut start = 0;
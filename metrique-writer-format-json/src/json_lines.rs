@@ -0,0 +1,474 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+use std::io;
+use std::time::SystemTime;
+
+use metrique_writer_core::entry::EntryConfig;
+use metrique_writer_core::format::Format;
+use metrique_writer_core::sample::SampledFormat;
+use metrique_writer_core::stream::IoStreamError;
+use metrique_writer_core::value::{MetricFlags, Observation, Value, ValueWriter};
+use metrique_writer_core::{
+    Entry, EntryWriter, NameRules, Unit, ValidationError, ValidationErrorBuilder,
+};
+
+use crate::json::{JsonArrayElementWriter, MAX_BUF_RETAIN, push_json_string, push_observation};
+
+/// `timestamp` is reserved for the entry timestamp. Control characters are escaped in the output.
+const NAME_RULES: NameRules<'static> = NameRules::new()
+    .reserved(&["timestamp"])
+    .allow_control_characters(true);
+
+/// A flat JSON formatter for metrique metrics, for generic log aggregators.
+///
+/// Outputs one flat JSON object per entry as a single line, followed by a newline. Unlike
+/// [`Json`](crate::Json), metrics and string properties are written side by side, and units,
+/// dimensions and flags are dropped:
+/// ```json
+/// {"timestamp":1705312800000,"Latency":42.5,"Count":10,"ResponseTimes":[1,2,3],"Operation":"GetItem"}
+/// ```
+///
+/// - The timestamp is written as milliseconds since the Unix epoch, or as an ISO-8601 string
+///   with [`JsonLines::timestamp_format`]. If the entry has no timestamp, the time of
+///   formatting is used. The `timestamp` name is reserved.
+/// - Single observations are written as numbers, multiple observations as arrays. Repeated
+///   observations are written as `{"total": f64, "count": u64}`.
+/// - Metrics with no observations are skipped.
+/// - Non-finite floating-point values are handled like in [`Json`](crate::Json).
+///
+/// This is meant as a debugging and interop format. It implements [`SampledFormat`] so it can
+/// be used with the samplers, but the sample rate is not written.
+///
+/// ```
+/// use metrique_writer_format_json::{JsonLines, TimestampFormat};
+///
+/// let format = JsonLines::new().timestamp_format(TimestampFormat::Iso8601);
+/// ```
+#[derive(Debug)]
+pub struct JsonLines {
+    // Reusable buffer, cleared between entries. Each value writes a ,"key":value fragment.
+    buf: String,
+    timestamp_format: TimestampFormat,
+}
+
+/// How [`JsonLines`] writes the entry timestamp, see [`JsonLines::timestamp_format`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum TimestampFormat {
+    /// Milliseconds since the Unix epoch, as a number, e.g. `1705312800000`
+    #[default]
+    UnixMillis,
+    /// An ISO-8601 (RFC 3339) UTC string, e.g. `"2024-01-15T10:00:00Z"`. Fractional seconds
+    /// are only written when they are not zero.
+    Iso8601,
+}
+
+impl JsonLines {
+    /// Create a new flat JSON formatter.
+    pub fn new() -> Self {
+        Self {
+            buf: String::with_capacity(2048),
+            timestamp_format: TimestampFormat::default(),
+        }
+    }
+
+    /// Set how the `timestamp` field is written. Defaults to [`TimestampFormat::UnixMillis`].
+    pub fn timestamp_format(mut self, timestamp_format: TimestampFormat) -> Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+}
+
+impl Default for JsonLines {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Format for JsonLines {
+    fn format(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<(), IoStreamError> {
+        self.buf.truncate(0);
+        self.buf.shrink_to(MAX_BUF_RETAIN);
+
+        let mut writer = JsonLinesEntryWriter {
+            timestamp: None,
+            buf: &mut self.buf,
+            error: ValidationErrorBuilder::default(),
+        };
+        entry.write(&mut writer);
+
+        let timestamp = writer.timestamp;
+        writer.error.build()?;
+
+        let timestamp = timestamp.unwrap_or_else(SystemTime::now);
+
+        output.write_all(b"{\"timestamp\":")?;
+        match self.timestamp_format {
+            TimestampFormat::UnixMillis => {
+                let millis = timestamp
+                    .duration_since(SystemTime::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+                output.write_all(itoa::Buffer::new().format(millis).as_bytes())?;
+            }
+            TimestampFormat::Iso8601 => {
+                // timestamps outside of jiff's range (years -9999 to 9999) are clamped
+                let timestamp = jiff::Timestamp::try_from(timestamp).unwrap_or(
+                    if timestamp < SystemTime::UNIX_EPOCH {
+                        jiff::Timestamp::MIN
+                    } else {
+                        jiff::Timestamp::MAX
+                    },
+                );
+                write!(output, "\"{timestamp}\"")?;
+            }
+        }
+        output.write_all(self.buf.as_bytes())?;
+        output.write_all(b"}\n")?;
+        Ok(())
+    }
+}
+
+impl SampledFormat for JsonLines {
+    /// Format `entry` like [`Format::format`], ignoring the sample rate.
+    fn format_with_sample_rate(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+        _rate: f32,
+    ) -> Result<(), IoStreamError> {
+        self.format(entry, output)
+    }
+}
+
+struct JsonLinesEntryWriter<'b> {
+    timestamp: Option<SystemTime>,
+    buf: &'b mut String,
+    error: ValidationErrorBuilder,
+}
+
+impl<'a> EntryWriter<'a> for JsonLinesEntryWriter<'_> {
+    fn timestamp(&mut self, timestamp: SystemTime) {
+        if self.timestamp.is_some() {
+            self.error.invalid_mut("timestamp set more than once");
+        }
+        self.timestamp = Some(timestamp);
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        let name = name.into();
+        if let Err(err) = NAME_RULES.validate(&name) {
+            self.error.extend_mut(err);
+            return;
+        }
+        value.write(JsonLinesValueWriter {
+            name: &name,
+            buf: self.buf,
+            error: &mut self.error,
+        });
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {}
+}
+
+struct JsonLinesValueWriter<'b> {
+    name: &'b str,
+    buf: &'b mut String,
+    error: &'b mut ValidationErrorBuilder,
+}
+
+impl JsonLinesValueWriter<'_> {
+    fn push_name(&mut self) {
+        self.buf.push(',');
+        push_json_string(self.buf, self.name);
+        self.buf.push(':');
+    }
+}
+
+impl ValueWriter for JsonLinesValueWriter<'_> {
+    fn string(mut self, value: &str) {
+        self.push_name();
+        push_json_string(self.buf, value);
+    }
+
+    fn values<'a, V: Value + 'a>(mut self, values: impl IntoIterator<Item = &'a V>) {
+        self.push_name();
+        let buf = self.buf;
+        buf.push('[');
+        let mut wrote_any = false;
+        for value in values {
+            let before = buf.len();
+            if wrote_any {
+                buf.push(',');
+            }
+            let after_sep = buf.len();
+            value.write(JsonArrayElementWriter(buf));
+            if buf.len() > after_sep {
+                wrote_any = true;
+            } else {
+                buf.truncate(before);
+            }
+        }
+        buf.push(']');
+    }
+
+    fn metric<'a>(
+        mut self,
+        distribution: impl IntoIterator<Item = Observation>,
+        _unit: Unit,
+        _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        _flags: MetricFlags<'_>,
+    ) {
+        let mut obs = distribution.into_iter();
+        let Some(first) = obs.next() else {
+            return; // no observations, skip metric
+        };
+
+        self.push_name();
+        let buf = self.buf;
+        if let Some(second) = obs.next() {
+            buf.push('[');
+            push_observation(buf, first, None);
+            buf.push(',');
+            push_observation(buf, second, None);
+            for ob in obs {
+                buf.push(',');
+                push_observation(buf, ob, None);
+            }
+            buf.push(']');
+        } else {
+            push_observation(buf, first, None);
+        }
+    }
+
+    fn error(self, error: ValidationError) {
+        self.error.extend_mut(error.for_field(self.name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrique_writer::value::WithDimension;
+    use std::time::Duration;
+
+    const TIMESTAMP: u64 = 1705312800;
+
+    fn format(entry: &impl Entry) -> String {
+        let mut output = Vec::new();
+        JsonLines::new().format(entry, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    fn parse(output: &str) -> serde_json::Value {
+        serde_json::from_str(output).unwrap()
+    }
+
+    struct ScalarEntry;
+    impl Entry for ScalarEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(TIMESTAMP));
+            writer.value("Latency", &42.5f64);
+            writer.value("Count", &10u64);
+            writer.value("Success", &true);
+            writer.value("Duration", &Duration::from_millis(3));
+            writer.value("Missing", &None::<u64>);
+            writer.value("WithDimension", &WithDimension::new(1u64, "Region", "x"));
+        }
+    }
+
+    #[test]
+    fn test_scalars() {
+        let output = format(&ScalarEntry);
+        assert!(output.ends_with("}\n"));
+        assert_eq!(output.lines().count(), 1);
+        assert_eq!(
+            parse(&output),
+            serde_json::json!({
+                "timestamp": 1705312800000u64,
+                "Latency": 42.5,
+                "Count": 10,
+                "Success": 1,
+                "Duration": 3,
+                "WithDimension": 1,
+            })
+        );
+    }
+
+    struct StringEntry;
+    impl Entry for StringEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(TIMESTAMP));
+            writer.value("Operation", &"GetItem");
+            writer.value("Escaped", &"a \"quoted\"\nline");
+            writer.value("List", &["a", "b"][..]);
+        }
+    }
+
+    #[test]
+    fn test_strings() {
+        assert_eq!(
+            parse(&format(&StringEntry)),
+            serde_json::json!({
+                "timestamp": 1705312800000u64,
+                "Operation": "GetItem",
+                "Escaped": "a \"quoted\"\nline",
+                "List": ["a", "b"],
+            })
+        );
+    }
+
+    struct Distribution(Vec<Observation>);
+    impl Value for Distribution {
+        fn write(&self, writer: impl ValueWriter) {
+            writer.metric(self.0.iter().copied(), Unit::None, [], MetricFlags::empty());
+        }
+    }
+
+    struct DistributionEntry;
+    impl Entry for DistributionEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(TIMESTAMP));
+            writer.value(
+                "ResponseTimes",
+                &Distribution(vec![
+                    Observation::Unsigned(1),
+                    Observation::Floating(2.5),
+                    Observation::Repeated {
+                        total: 150.0,
+                        occurrences: 3,
+                    },
+                ]),
+            );
+            writer.value(
+                "Single",
+                &Distribution(vec![Observation::Repeated {
+                    total: 10.0,
+                    occurrences: 2,
+                }]),
+            );
+            writer.value("Empty", &Distribution(vec![]));
+            writer.value("NaN", &f64::NAN);
+            writer.value("Infinity", &f64::INFINITY);
+        }
+    }
+
+    #[test]
+    fn test_distributions() {
+        assert_eq!(
+            parse(&format(&DistributionEntry)),
+            serde_json::json!({
+                "timestamp": 1705312800000u64,
+                "ResponseTimes": [1, 2.5, {"total": 150, "count": 3}],
+                "Single": {"total": 10, "count": 2},
+                "NaN": null,
+                "Infinity": f64::MAX,
+            })
+        );
+    }
+
+    struct NoTimestampEntry;
+    impl Entry for NoTimestampEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value("Count", &1u64);
+        }
+    }
+
+    #[test]
+    fn test_iso8601_timestamp() {
+        let mut format = JsonLines::new().timestamp_format(TimestampFormat::Iso8601);
+        let mut output = Vec::new();
+        format.format(&ScalarEntry, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("{\"timestamp\":\"2024-01-15T10:00:00Z\","));
+        assert_eq!(parse(&output)["Count"], 10);
+
+        struct MillisEntry;
+        impl Entry for MillisEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.timestamp(SystemTime::UNIX_EPOCH + Duration::from_millis(1500));
+            }
+        }
+        let mut output = Vec::new();
+        format.format(&MillisEntry, &mut output).unwrap();
+        assert_eq!(output, b"{\"timestamp\":\"1970-01-01T00:00:01.5Z\"}\n");
+    }
+
+    #[test]
+    fn test_sample_rate_is_ignored() {
+        let mut output = Vec::new();
+        JsonLines::new()
+            .format_with_sample_rate(&ScalarEntry, &mut output, 0.1)
+            .unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), format(&ScalarEntry));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let output = format(&ScalarEntry);
+        assert!(output.starts_with("{\"timestamp\":1705312800000,"));
+
+        let before = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64;
+        let output = parse(&format(&NoTimestampEntry));
+        assert!(output["timestamp"].as_u64().unwrap() >= before);
+        assert_eq!(output["Count"], 1);
+    }
+
+    struct DoubleTimestampEntry;
+    impl Entry for DoubleTimestampEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(SystemTime::UNIX_EPOCH);
+            writer.timestamp(SystemTime::UNIX_EPOCH);
+        }
+    }
+
+    struct InvalidNamesEntry;
+    impl Entry for InvalidNamesEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value("", &1u64);
+            writer.value("timestamp", &2u64);
+        }
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let mut output = Vec::new();
+        let err = JsonLines::new()
+            .format(&DoubleTimestampEntry, &mut output)
+            .unwrap_err();
+        assert!(err.to_string().contains("timestamp set more than once"));
+
+        let err = JsonLines::new()
+            .format(&InvalidNamesEntry, &mut output)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("for ``: name can't be empty"), "{err}");
+        assert!(
+            err.contains("for `timestamp`: name can't be `timestamp`"),
+            "{err}"
+        );
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_buffer_reuse() {
+        let mut format = JsonLines::new();
+        let mut output = Vec::new();
+        format.format(&StringEntry, &mut output).unwrap();
+        format.format(&NoTimestampEntry, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(parse(lines[0])["Operation"], "GetItem");
+        assert!(parse(lines[1]).get("Operation").is_none());
+    }
+}
@@ -6,5 +6,7 @@
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 mod json;
+mod json_lines;
 
 pub use json::{Json, SampledJson};
+pub use json_lines::{JsonLines, TimestampFormat};
@@ -0,0 +1,662 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::time::SystemTime;
+
+use metrique_writer_core::entry::EntryConfig;
+use metrique_writer_core::format::Format;
+use metrique_writer_core::stream::IoStreamError;
+use metrique_writer_core::value::{Distribution, MetricFlags, Observation, Value, ValueWriter};
+use metrique_writer_core::{Entry, EntryWriter, Unit, ValidationError, ValidationErrorBuilder};
+
+/// The Prometheus type a metric is written as, see [`Prometheus::metric_type_for`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MetricType {
+    /// A monotonic counter, written as the sum of its observations. The name gets a `_total`
+    /// suffix.
+    Counter,
+    /// A gauge, written as the mean of its observations.
+    Gauge,
+    /// A histogram, written as cumulative `_bucket` samples, plus `_sum` and `_count`.
+    Histogram,
+}
+
+impl MetricType {
+    fn name(self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+            MetricType::Histogram => "histogram",
+        }
+    }
+}
+
+/// A [Prometheus text exposition] formatter for metrique metrics.
+///
+/// This is meant for entries that are already aggregated, e.g. by `metrique-aggregation`, and
+/// served from a `/metrics` endpoint. Since Prometheus pulls metrics, [`Prometheus::render`]
+/// formats an entry into a [`String`] on demand. [`Prometheus`] also implements [`Format`], but
+/// each formatted entry is a complete exposition, so only write one entry per scrape.
+///
+/// - Every metric is preceded by its `# HELP` and `# TYPE` lines. The help text is the name of
+///   the field unless set with [`Prometheus::help`].
+/// - Metrics flagged as a [`Distribution`], or with more than one observation, are written as
+///   [histograms](MetricType::Histogram). Other metrics are [gauges](MetricType::Gauge). Use
+///   [`Prometheus::metric_type_for`] to write a metric as a [counter](MetricType::Counter).
+/// - Histogram buckets are the observations of the metric, so the buckets of an aggregated
+///   histogram (e.g. `metrique_aggregation::histogram::Histogram`) are kept as-is. Use
+///   [`Prometheus::with_buckets`] to use fixed buckets instead.
+/// - Values are converted to Prometheus base units, and the unit is added to the name:
+///   seconds (`_seconds`), bytes (`_bytes`, bits are converted to bytes), bytes per second
+///   (`_bytes_per_second`) and ratios (`_ratio`, percentages are divided by 100).
+///   [`Unit::Count`] and [`Unit::None`] have no suffix, and [`Unit::Custom`] values are
+///   written as-is.
+/// - Per-metric dimensions (e.g. from
+///   [`WithDimension`](metrique_writer_core::value::WithDimension)) are written as labels, and
+///   so are the string fields listed in [`Prometheus::label_fields`]. Other string fields and
+///   lists are not written.
+/// - Timestamps are not written, Prometheus uses the time of the scrape.
+///
+/// Characters that are not allowed in metric and label names are replaced by `_`, and label
+/// values and help texts are escaped. `NaN` observations are skipped, and metrics with no
+/// observations are not written.
+///
+/// ## Example
+/// ```
+/// use metrique_writer::Entry;
+/// use metrique_writer_format_prometheus::{MetricType, Prometheus};
+/// use std::time::Duration;
+///
+/// #[derive(Entry)]
+/// struct ServiceMetrics {
+///     operation: &'static str,
+///     latency: Duration,
+///     requests: u64,
+/// }
+///
+/// let format = Prometheus::new()
+///     .with_namespace("my_app")
+///     .label_fields(["operation"])
+///     .metric_type_for("requests", MetricType::Counter)
+///     .help("requests", "Requests served");
+/// let output = format.render(&ServiceMetrics {
+///     operation: "GetItem",
+///     latency: Duration::from_millis(250),
+///     requests: 10,
+/// }).unwrap();
+///
+/// assert_eq!(output, "\
+/// ## HELP my_app_latency_seconds latency
+/// ## TYPE my_app_latency_seconds gauge
+/// my_app_latency_seconds{operation=\"GetItem\"} 0.25
+/// ## HELP my_app_requests_total Requests served
+/// ## TYPE my_app_requests_total counter
+/// my_app_requests_total{operation=\"GetItem\"} 10
+/// ");
+/// ```
+///
+/// [Prometheus text exposition]: https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
+#[derive(Clone, Debug, Default)]
+pub struct Prometheus {
+    namespace: String,
+    metric_types: HashMap<String, MetricType>,
+    help: HashMap<String, String>,
+    buckets: Option<Vec<f64>>,
+    label_fields: Vec<String>,
+}
+
+impl Prometheus {
+    /// Create a new Prometheus formatter, without a namespace or label fields.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Prepend `namespace` and a `_` to the name of every metric, e.g. `my_app`.
+    pub fn with_namespace(mut self, namespace: impl Into<String>) -> Self {
+        let mut prefix = String::new();
+        push_metric_name(&mut prefix, &namespace.into());
+        prefix.push('_');
+        self.namespace = prefix;
+        self
+    }
+
+    /// Set the type the metric named `name` is written as, instead of inferring it from its
+    /// observations.
+    ///
+    /// `name` is the name the entry writes, e.g. before the namespace and unit are added.
+    pub fn metric_type_for(mut self, name: impl Into<String>, metric_type: MetricType) -> Self {
+        self.metric_types.insert(name.into(), metric_type);
+        self
+    }
+
+    /// Set the `# HELP` text of the metric named `name`.
+    ///
+    /// `name` is the name the entry writes, e.g. before the namespace and unit are added.
+    pub fn help(mut self, name: impl Into<String>, help: impl Into<String>) -> Self {
+        self.help.insert(name.into(), help.into());
+        self
+    }
+
+    /// Write histograms with fixed bucket upper bounds, in base units (e.g. seconds), instead
+    /// of one bucket per observation. A `+Inf` bucket is always written.
+    ///
+    /// Fixed buckets are needed to compute quantiles across scrapes or instances with
+    /// `histogram_quantile`, since the buckets of aggregated histograms depend on the
+    /// observations.
+    ///
+    /// # Panics
+    /// Panics if a bound is `NaN`.
+    pub fn with_buckets(mut self, buckets: impl IntoIterator<Item = f64>) -> Self {
+        let mut buckets: Vec<f64> = buckets.into_iter().collect();
+        assert!(
+            !buckets.iter().any(|b| b.is_nan()),
+            "histogram bucket bounds can't be NaN"
+        );
+        buckets.sort_by(f64::total_cmp);
+        buckets.dedup();
+        self.buckets = Some(buckets);
+        self
+    }
+
+    /// Write the string fields named `fields` as labels on every metric of the entry.
+    ///
+    /// Only list fields with a small number of distinct values (e.g. the operation or status),
+    /// since every distinct combination of labels is a separate time series.
+    pub fn label_fields(mut self, fields: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.label_fields.extend(fields.into_iter().map(Into::into));
+        self
+    }
+
+    /// Format `entry` as a Prometheus text exposition.
+    pub fn render(&self, entry: &impl Entry) -> Result<String, ValidationError> {
+        let mut writer = PrometheusEntryWriter {
+            prometheus: self,
+            families: Vec::new(),
+            samples: Vec::new(),
+            labels: Vec::new(),
+            error: ValidationErrorBuilder::default(),
+        };
+        entry.write(&mut writer);
+        writer.error.build()?;
+
+        let mut out = String::with_capacity(1024);
+        for (index, family) in writer.families.iter().enumerate() {
+            out.push_str("# HELP ");
+            out.push_str(&family.name);
+            out.push(' ');
+            let help = self.help.get(&family.source).unwrap_or(&family.source);
+            push_escaped(&mut out, help, false);
+            out.push_str("\n# TYPE ");
+            out.push_str(&family.name);
+            out.push(' ');
+            out.push_str(family.metric_type.name());
+            out.push('\n');
+            for sample in writer.samples.iter().filter(|s| s.family == index) {
+                self.push_sample(&mut out, family, sample, &writer.labels);
+            }
+        }
+        Ok(out)
+    }
+
+    fn push_sample(
+        &self,
+        out: &mut String,
+        family: &Family,
+        sample: &Sample,
+        entry_labels: &[(String, String)],
+    ) {
+        // per-metric dimensions take precedence over entry labels with the same name
+        let labels: Vec<_> = entry_labels
+            .iter()
+            .filter(|(name, _)| !sample.labels.iter().any(|(n, _)| n == name))
+            .chain(&sample.labels)
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect();
+        let sum: f64 = sample.observations.iter().map(|(v, n)| v * *n as f64).sum();
+        let count: u64 = sample.observations.iter().map(|(_, n)| n).sum();
+
+        match family.metric_type {
+            MetricType::Counter => push_line(out, &family.name, "", &labels, None, |out| {
+                push_float(out, sum)
+            }),
+            MetricType::Gauge => push_line(out, &family.name, "", &labels, None, |out| {
+                push_float(out, sum / count as f64)
+            }),
+            MetricType::Histogram => {
+                let bounds = match &self.buckets {
+                    Some(buckets) => Cow::Borrowed(buckets),
+                    None => {
+                        let mut bounds: Vec<f64> =
+                            sample.observations.iter().map(|(v, _)| *v).collect();
+                        bounds.sort_by(f64::total_cmp);
+                        bounds.dedup();
+                        Cow::Owned(bounds)
+                    }
+                };
+                for bound in bounds.iter().filter(|b| **b != f64::INFINITY) {
+                    let cumulative: u64 = sample
+                        .observations
+                        .iter()
+                        .filter(|(v, _)| v <= bound)
+                        .map(|(_, n)| n)
+                        .sum();
+                    push_line(out, &family.name, "_bucket", &labels, Some(*bound), |out| {
+                        out.push_str(itoa::Buffer::new().format(cumulative))
+                    });
+                }
+                push_line(
+                    out,
+                    &family.name,
+                    "_bucket",
+                    &labels,
+                    Some(f64::INFINITY),
+                    |out| out.push_str(itoa::Buffer::new().format(count)),
+                );
+                push_line(out, &family.name, "_sum", &labels, None, |out| {
+                    push_float(out, sum)
+                });
+                push_line(out, &family.name, "_count", &labels, None, |out| {
+                    out.push_str(itoa::Buffer::new().format(count))
+                });
+            }
+        }
+    }
+}
+
+impl Format for Prometheus {
+    fn format(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<(), IoStreamError> {
+        let rendered = self.render(entry)?;
+        output.write_all(rendered.as_bytes())?;
+        Ok(())
+    }
+}
+
+struct Family {
+    name: String,
+    // the name written by the entry, used to look up the help text
+    source: String,
+    metric_type: MetricType,
+}
+
+struct Sample {
+    family: usize,
+    labels: Vec<(String, String)>,
+    // (value in base units, occurrences)
+    observations: Vec<(f64, u64)>,
+}
+
+struct PrometheusEntryWriter<'p> {
+    prometheus: &'p Prometheus,
+    families: Vec<Family>,
+    samples: Vec<Sample>,
+    labels: Vec<(String, String)>,
+    error: ValidationErrorBuilder,
+}
+
+impl<'a> EntryWriter<'a> for PrometheusEntryWriter<'_> {
+    fn timestamp(&mut self, _timestamp: SystemTime) {
+        // Prometheus uses the time of the scrape
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        let name = name.into();
+        value.write(PrometheusValueWriter {
+            name: &name,
+            writer: self,
+        });
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {
+        // Currently there's no EntryConfig that is relevant to the Prometheus format.
+    }
+}
+
+struct PrometheusValueWriter<'w, 'p> {
+    name: &'w str,
+    writer: &'w mut PrometheusEntryWriter<'p>,
+}
+
+impl ValueWriter for PrometheusValueWriter<'_, '_> {
+    fn string(self, value: &str) {
+        let prometheus = self.writer.prometheus;
+        if prometheus
+            .label_fields
+            .iter()
+            .any(|field| field == self.name)
+        {
+            self.writer
+                .labels
+                .push((label_name(self.name), value.to_owned()));
+        }
+    }
+
+    fn values<'a, V: Value + 'a>(self, _values: impl IntoIterator<Item = &'a V>) {
+        // Prometheus has no representation for lists
+    }
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        unit: Unit,
+        dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        flags: MetricFlags<'_>,
+    ) {
+        if self.name.is_empty() {
+            self.writer
+                .error
+                .extend_mut(ValidationError::invalid("name can't be empty").for_field(""));
+            return;
+        }
+        let (suffix, factor) = base_unit(unit);
+        let observations: Vec<(f64, u64)> = distribution
+            .into_iter()
+            .filter_map(|observation| match observation {
+                Observation::Unsigned(value) => Some((value as f64, 1)),
+                Observation::Floating(value) => Some((value, 1)),
+                Observation::Repeated { total, occurrences } if occurrences > 0 => {
+                    Some((total / occurrences as f64, occurrences))
+                }
+                _ => None,
+            })
+            .map(|(value, occurrences)| (value * factor, occurrences))
+            .filter(|(value, _)| !value.is_nan())
+            .collect();
+        if observations.is_empty() {
+            return;
+        }
+
+        let prometheus = self.writer.prometheus;
+        let metric_type = match prometheus.metric_types.get(self.name) {
+            Some(metric_type) => *metric_type,
+            None if flags.downcast::<Distribution>().is_some()
+                || observations.len() > 1
+                || observations[0].1 > 1 =>
+            {
+                MetricType::Histogram
+            }
+            None => MetricType::Gauge,
+        };
+
+        let mut name = prometheus.namespace.clone();
+        push_metric_name(&mut name, self.name);
+        if let Some(suffix) = suffix
+            && !name.ends_with(suffix)
+        {
+            name.push_str(suffix);
+        }
+        if metric_type == MetricType::Counter && !name.ends_with("_total") {
+            name.push_str("_total");
+        }
+
+        let families = &mut self.writer.families;
+        let family = match families.iter().position(|f| f.name == name) {
+            Some(family) if families[family].metric_type != metric_type => {
+                self.writer.error.extend_mut(
+                    ValidationError::invalid(format!(
+                        "`{name}` is written as both a {} and a {}",
+                        families[family].metric_type.name(),
+                        metric_type.name()
+                    ))
+                    .for_field(self.name),
+                );
+                return;
+            }
+            Some(family) => family,
+            None => {
+                families.push(Family {
+                    name,
+                    source: self.name.to_owned(),
+                    metric_type,
+                });
+                families.len() - 1
+            }
+        };
+        self.writer.samples.push(Sample {
+            family,
+            labels: dimensions
+                .into_iter()
+                .map(|(name, value)| (label_name(name), value.to_owned()))
+                .collect(),
+            observations,
+        });
+    }
+
+    fn error(self, error: ValidationError) {
+        self.writer.error.extend_mut(error.for_field(self.name));
+    }
+}
+
+/// The name suffix of the Prometheus base unit of `unit`, and the factor to convert to it.
+fn base_unit(unit: Unit) -> (Option<&'static str>, f64) {
+    match unit {
+        Unit::Second(scale) => (Some("_seconds"), 1.0 / scale.reduction_factor() as f64),
+        Unit::Byte(scale) => (Some("_bytes"), scale.expansion_factor() as f64),
+        Unit::Bit(scale) => (Some("_bytes"), scale.expansion_factor() as f64 / 8.0),
+        Unit::BytePerSecond(scale) => (Some("_bytes_per_second"), scale.expansion_factor() as f64),
+        Unit::BitPerSecond(scale) => (
+            Some("_bytes_per_second"),
+            scale.expansion_factor() as f64 / 8.0,
+        ),
+        Unit::Percent => (Some("_ratio"), 0.01),
+        _ => (None, 1.0),
+    }
+}
+
+/// Push `name`, replacing characters that are not allowed in metric names.
+fn push_metric_name(buf: &mut String, name: &str) {
+    if buf.is_empty() && name.starts_with(|c: char| c.is_ascii_digit()) {
+        buf.push('_');
+    }
+    buf.extend(name.chars().map(|c| match c {
+        'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | ':' => c,
+        _ => '_',
+    }));
+}
+
+/// Return `name`, replacing characters that are not allowed in label names.
+fn label_name(name: &str) -> String {
+    let mut label = String::with_capacity(name.len());
+    push_metric_name(&mut label, name);
+    label.replace(':', "_")
+}
+
+/// Push `s`, escaping backslashes and newlines, and double quotes if `quotes` is set.
+fn push_escaped(buf: &mut String, s: &str, quotes: bool) {
+    for c in s.chars() {
+        match c {
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '"' if quotes => buf.push_str("\\\""),
+            c => buf.push(c),
+        }
+    }
+}
+
+fn push_line(
+    out: &mut String,
+    name: &str,
+    suffix: &str,
+    labels: &[(&str, &str)],
+    le: Option<f64>,
+    value: impl FnOnce(&mut String),
+) {
+    out.push_str(name);
+    out.push_str(suffix);
+    if !labels.is_empty() || le.is_some() {
+        out.push('{');
+        for (i, (name, value)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str(name);
+            out.push_str("=\"");
+            push_escaped(out, value, true);
+            out.push('"');
+        }
+        if let Some(le) = le {
+            if !labels.is_empty() {
+                out.push(',');
+            }
+            out.push_str("le=\"");
+            push_float(out, le);
+            out.push('"');
+        }
+        out.push('}');
+    }
+    out.push(' ');
+    value(out);
+    out.push('\n');
+}
+
+fn push_float(buf: &mut String, v: f64) {
+    if v == f64::INFINITY {
+        buf.push_str("+Inf");
+    } else if v == f64::NEG_INFINITY {
+        buf.push_str("-Inf");
+    } else if v.is_nan() {
+        buf.push_str("NaN");
+    } else {
+        let mut buffer = dtoa::Buffer::new();
+        let s = buffer.format_finite(v);
+        buf.push_str(s.strip_suffix(".0").unwrap_or(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrique_aggregation::histogram::{Histogram, SortAndMerge};
+    use metrique_core::CloseValue;
+    use metrique_writer::value::{Mean, WithDimension};
+    use metrique_writer_core::unit::{AsKilobits, AsPercent, Millisecond, Second};
+    use std::time::Duration;
+
+    struct TestEntry;
+    impl Entry for TestEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(SystemTime::UNIX_EPOCH);
+            writer.value("Latency", &Duration::from_millis(1500));
+            writer.value("Errors", &3u64);
+            writer.value("CpuUsage", &AsPercent::from(50.0f64));
+            writer.value("Throughput", &AsKilobits::from(16u64));
+            writer.value("Nan", &f64::NAN);
+            writer.value(
+                "Throttles",
+                &WithDimension::new_with_dimensions(1u64, [("shard.id", "a\"b\\c\nd")]),
+            );
+            writer.value(
+                "Throttles",
+                &WithDimension::new_with_dimensions(2u64, [("shard.id", "e")]),
+            );
+            // written after the metrics, but still on every metric
+            writer.value("Operation", "GetItem");
+            writer.value("RequestId", "1234");
+        }
+    }
+
+    #[test]
+    fn formats_units_and_labels() {
+        let format = Prometheus::new()
+            .label_fields(["Operation"])
+            .metric_type_for("Errors", MetricType::Counter)
+            .help("Errors", "Errors\\failures\nseen");
+        assert_eq!(
+            format.render(&TestEntry).unwrap(),
+            r#"# HELP Latency_seconds Latency
+# TYPE Latency_seconds gauge
+Latency_seconds{Operation="GetItem"} 1.5
+# HELP Errors_total Errors\\failures\nseen
+# TYPE Errors_total counter
+Errors_total{Operation="GetItem"} 3
+# HELP CpuUsage_ratio CpuUsage
+# TYPE CpuUsage_ratio gauge
+CpuUsage_ratio{Operation="GetItem"} 0.5
+# HELP Throughput_bytes Throughput
+# TYPE Throughput_bytes gauge
+Throughput_bytes{Operation="GetItem"} 2000
+# HELP Throttles Throttles
+# TYPE Throttles gauge
+Throttles{Operation="GetItem",shard_id="a\"b\\c\nd"} 1
+Throttles{Operation="GetItem",shard_id="e"} 2
+"#
+        );
+    }
+
+    struct HistogramEntry;
+    impl Entry for HistogramEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            let mut histogram = Histogram::<Duration, SortAndMerge>::new(SortAndMerge::new());
+            for ms in [100, 100, 300, 2000] {
+                histogram.add_value(Duration::from_millis(ms));
+            }
+            writer.value("latency", &histogram.close());
+            writer.value("mean", &Mean::<Second>::from_iter([1u32, 2, 6]));
+        }
+    }
+
+    #[test]
+    fn formats_histograms() {
+        assert_eq!(
+            Prometheus::new()
+                .with_namespace("my-app")
+                .render(&HistogramEntry)
+                .unwrap(),
+            r#"# HELP my_app_latency_seconds latency
+# TYPE my_app_latency_seconds histogram
+my_app_latency_seconds_bucket{le="0.1"} 2
+my_app_latency_seconds_bucket{le="0.3"} 3
+my_app_latency_seconds_bucket{le="2"} 4
+my_app_latency_seconds_bucket{le="+Inf"} 4
+my_app_latency_seconds_sum 2.5
+my_app_latency_seconds_count 4
+# HELP my_app_mean_seconds mean
+# TYPE my_app_mean_seconds histogram
+my_app_mean_seconds_bucket{le="3"} 3
+my_app_mean_seconds_bucket{le="+Inf"} 3
+my_app_mean_seconds_sum 9
+my_app_mean_seconds_count 3
+"#
+        );
+
+        let output = Prometheus::new()
+            .with_buckets([1.0, 0.25, f64::INFINITY])
+            .metric_type_for("mean", MetricType::Gauge)
+            .render(&HistogramEntry)
+            .unwrap();
+        assert!(output.contains(
+            "latency_seconds_bucket{le=\"0.25\"} 2\n\
+             latency_seconds_bucket{le=\"1\"} 3\n\
+             latency_seconds_bucket{le=\"+Inf\"} 4\n"
+        ));
+        assert!(output.contains("mean_seconds 3\n"));
+    }
+
+    #[test]
+    fn rejects_conflicting_types() {
+        struct Conflicting;
+        impl Entry for Conflicting {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.value("Latency", &Duration::from_millis(1));
+                writer.value("Latency", &Mean::<Millisecond>::from_iter([1u32, 2]));
+            }
+        }
+        let err = Prometheus::new().render(&Conflicting).unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`Latency_seconds` is written as both a gauge and a histogram"),
+            "{err}"
+        );
+        // the format writes nothing for invalid entries
+        let mut output = vec![];
+        assert!(Prometheus::new().format(&Conflicting, &mut output).is_err());
+        assert!(output.is_empty());
+    }
+}
@@ -0,0 +1,554 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::ops::Range;
+use std::time::SystemTime;
+
+use metrique_writer_core::entry::EntryConfig;
+use metrique_writer_core::format::Format;
+use metrique_writer_core::sample::SampledFormat;
+use metrique_writer_core::stream::IoStreamError;
+use metrique_writer_core::value::{MetricFlags, Observation, Value, ValueWriter};
+use metrique_writer_core::{Entry, EntryWriter, Unit, ValidationError, ValidationErrorBuilder};
+
+// Maximum buffer size before shrinking on clear. Prevents one large entry from
+// permanently bloating memory.
+const MAX_BUF_RETAIN: usize = 1024 * 1024;
+
+/// The StatsD type a metric is written as, see [`Statsd::metric_type`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MetricType {
+    /// `c`: every observation is added to the metric's count.
+    Counter,
+    /// `g`: the metric is set to the last observation.
+    Gauge,
+    /// `h`: every observation is added to a histogram, aggregated by the StatsD server.
+    Histogram,
+    /// `d`: every observation is added to a DogStatsD distribution, aggregated globally by
+    /// Datadog rather than by the agent.
+    Distribution,
+    /// `ms`: every observation is added to a timer. Observations are written as they are, so
+    /// they should be in milliseconds, which is how `Duration` metrics are written.
+    Timer,
+}
+
+impl MetricType {
+    fn suffix(self) -> &'static str {
+        match self {
+            MetricType::Counter => "c",
+            MetricType::Gauge => "g",
+            MetricType::Histogram => "h",
+            MetricType::Distribution => "d",
+            MetricType::Timer => "ms",
+        }
+    }
+}
+
+/// A [StatsD] formatter for metrique metrics, using [DogStatsD] tags.
+///
+/// Every metric of an entry is written as one `name:value|type|@rate|#tags` line per
+/// observation, and all the lines of an entry are written with a single call to
+/// [`io::Write::write_all`], so writing each entry to a UDP socket sends it as a single packet.
+///
+/// - The type of every metric is [`MetricType::Distribution`] by default. This can be changed
+///   for all metrics with [`Statsd::metric_type`], or for specific metrics with
+///   [`Statsd::metric_type_for`].
+/// - Per-metric dimensions (e.g. from
+///   [`WithDimension`](metrique_writer_core::value::WithDimension)) are written as tags, and so
+///   is the unit of the metric, as `unit:<unit>`.
+/// - String fields listed in [`Statsd::tag_fields`] are written as tags on every line of the
+///   entry. Other string fields and lists are not written.
+/// - The timestamp of the entry is not written, StatsD servers use the time they receive
+///   metrics at.
+///
+/// Tag names and values are written with the characters StatsD reserves (`|`, `,`, `#` and
+/// newlines) replaced by `_`. Metric names containing reserved characters (`:`, `|`, `@`, `#`
+/// and newlines) fail validation instead.
+///
+/// ## Observations
+///
+/// `NaN` observations are skipped, and infinities are clamped to `f64::MAX` / `-f64::MAX`.
+/// A repeated observation (e.g. from [`Mean`](metrique_writer::value::Mean)) is written as a
+/// single line with the mean of its occurrences and a sample rate of `1/occurrences`, so the
+/// server counts it as `occurrences` observations. [Counters](MetricType::Counter) are written
+/// with the total instead.
+///
+/// ## Sampling
+///
+/// [`Statsd`] implements [`SampledFormat`] by writing the sample rate of the entry as the
+/// `|@rate` suffix of every line, so it composes with the samplers from
+/// [`metrique_writer::sample`], and the StatsD server extrapolates the dropped entries.
+///
+/// ## Example
+/// ```
+/// use metrique_writer::{Entry, format::Format};
+/// use metrique_writer_format_statsd::{MetricType, Statsd};
+/// use std::time::Duration;
+///
+/// #[derive(Entry)]
+/// #[entry(rename_all = "PascalCase")]
+/// struct RequestMetrics {
+///     operation: &'static str,
+///     request_id: &'static str,
+///     latency: Duration,
+///     errors: u64,
+/// }
+///
+/// let mut format = Statsd::new()
+///     .with_prefix("my_app.")
+///     .tag_fields(["Operation"])
+///     .metric_type_for("Errors", MetricType::Counter);
+/// let mut output = vec![];
+/// format.format(&RequestMetrics {
+///     operation: "GetItem",
+///     request_id: "1234",
+///     latency: Duration::from_millis(42),
+///     errors: 1,
+/// }, &mut output).unwrap();
+///
+/// assert_eq!(
+///     String::from_utf8(output).unwrap(),
+///     "my_app.Latency:42|d|#Operation:GetItem,unit:Milliseconds\n\
+///      my_app.Errors:1|c|#Operation:GetItem\n",
+/// );
+/// ```
+///
+/// [StatsD]: https://github.com/statsd/statsd/blob/master/docs/metric_types.md
+/// [DogStatsD]: https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/
+#[derive(Clone, Debug)]
+pub struct Statsd {
+    prefix: String,
+    metric_type: MetricType,
+    metric_types: HashMap<String, MetricType>,
+    tag_fields: Vec<String>,
+    // The lines of the current entry, without the entry tags, which are only known once the
+    // whole entry is written. `lines` holds the body and the metric tags of every line.
+    lines: String,
+    line_ranges: Vec<LineRanges>,
+    entry_tags: String,
+    output_buf: String,
+}
+
+#[derive(Clone, Debug)]
+struct LineRanges {
+    body: Range<usize>,
+    tags: Range<usize>,
+}
+
+impl Statsd {
+    /// Create a new StatsD formatter, writing every metric as a
+    /// [distribution](MetricType::Distribution), without a prefix or tag fields.
+    pub fn new() -> Self {
+        Self {
+            prefix: String::new(),
+            metric_type: MetricType::Distribution,
+            metric_types: HashMap::new(),
+            tag_fields: Vec::new(),
+            lines: String::with_capacity(2048),
+            line_ranges: Vec::new(),
+            entry_tags: String::with_capacity(256),
+            output_buf: String::with_capacity(2048),
+        }
+    }
+
+    /// Prepend `prefix` to the name of every metric, e.g. `my_app.`.
+    ///
+    /// # Panics
+    /// Panics if `prefix` contains a character StatsD reserves in metric names.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        let prefix = prefix.into();
+        assert!(
+            !prefix.contains(is_reserved_in_name),
+            "invalid metric name prefix `{prefix}`"
+        );
+        self.prefix = prefix;
+        self
+    }
+
+    /// Set the type metrics are written as, unless set for a specific metric with
+    /// [`Statsd::metric_type_for`].
+    pub fn metric_type(mut self, metric_type: MetricType) -> Self {
+        self.metric_type = metric_type;
+        self
+    }
+
+    /// Set the type the metric named `name` is written as.
+    ///
+    /// `name` is the name the entry writes, without the [prefix](Statsd::with_prefix).
+    pub fn metric_type_for(mut self, name: impl Into<String>, metric_type: MetricType) -> Self {
+        self.metric_types.insert(name.into(), metric_type);
+        self
+    }
+
+    /// Write the string fields named `fields` as tags on every line of the entry.
+    ///
+    /// Only list fields with a small number of distinct values (e.g. the operation or status),
+    /// since every distinct combination of tags is a separate metric to the StatsD server.
+    pub fn tag_fields(mut self, fields: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.tag_fields.extend(fields.into_iter().map(Into::into));
+        self
+    }
+
+    fn format_with_rate(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+        rate: Option<f64>,
+    ) -> Result<(), IoStreamError> {
+        self.clear_buffers();
+
+        let mut writer = StatsdEntryWriter {
+            statsd: self,
+            rate,
+            error: ValidationErrorBuilder::default(),
+        };
+        entry.write(&mut writer);
+        writer.error.build()?;
+
+        let out = &mut self.output_buf;
+        for line in &self.line_ranges {
+            out.push_str(&self.lines[line.body.clone()]);
+            let metric_tags = &self.lines[line.tags.clone()];
+            if !self.entry_tags.is_empty() || !metric_tags.is_empty() {
+                out.push_str("|#");
+                out.push_str(&self.entry_tags);
+                if !self.entry_tags.is_empty() && !metric_tags.is_empty() {
+                    out.push(',');
+                }
+                out.push_str(metric_tags);
+            }
+            out.push('\n');
+        }
+        if !out.is_empty() {
+            output.write_all(out.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Clear buffers and shrink overly large retained capacity.
+    fn clear_buffers(&mut self) {
+        for buf in [&mut self.lines, &mut self.entry_tags, &mut self.output_buf] {
+            buf.clear();
+            buf.shrink_to(MAX_BUF_RETAIN);
+        }
+        self.line_ranges.clear();
+    }
+}
+
+impl Default for Statsd {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Format for Statsd {
+    fn format(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<(), IoStreamError> {
+        self.format_with_rate(entry, output, None)
+    }
+}
+
+impl SampledFormat for Statsd {
+    fn format_with_sample_rate(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+        rate: f32,
+    ) -> Result<(), IoStreamError> {
+        if rate <= 0.0 || rate.is_nan() {
+            return Err(IoStreamError::Validation(ValidationError::invalid(
+                "format with non-positive sample rate",
+            )));
+        }
+        self.format_with_rate(entry, output, Some(rate as f64))
+    }
+}
+
+struct StatsdEntryWriter<'s> {
+    statsd: &'s mut Statsd,
+    rate: Option<f64>,
+    error: ValidationErrorBuilder,
+}
+
+impl<'a> EntryWriter<'a> for StatsdEntryWriter<'_> {
+    fn timestamp(&mut self, _timestamp: SystemTime) {
+        // StatsD servers timestamp metrics when they receive them
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        let name = name.into();
+        value.write(StatsdValueWriter {
+            name: &name,
+            writer: self,
+        });
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {
+        // Currently there's no EntryConfig that is relevant to the StatsD format.
+    }
+}
+
+struct StatsdValueWriter<'w, 's> {
+    name: &'w str,
+    writer: &'w mut StatsdEntryWriter<'s>,
+}
+
+impl ValueWriter for StatsdValueWriter<'_, '_> {
+    fn string(self, value: &str) {
+        let statsd = &mut *self.writer.statsd;
+        if !statsd.tag_fields.iter().any(|field| field == self.name) {
+            return;
+        }
+        if !statsd.entry_tags.is_empty() {
+            statsd.entry_tags.push(',');
+        }
+        push_tag(&mut statsd.entry_tags, self.name, value);
+    }
+
+    fn values<'a, V: Value + 'a>(self, _values: impl IntoIterator<Item = &'a V>) {
+        // StatsD has no representation for lists
+    }
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        unit: Unit,
+        dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        _flags: MetricFlags<'_>,
+    ) {
+        if self.name.is_empty() || self.name.contains(is_reserved_in_name) {
+            self.writer.error.extend_mut(
+                ValidationError::invalid("metric name contains a character StatsD reserves")
+                    .for_field(self.name),
+            );
+            return;
+        }
+        let rate = self.writer.rate;
+        let statsd = &mut *self.writer.statsd;
+        let metric_type = statsd
+            .metric_types
+            .get(self.name)
+            .copied()
+            .unwrap_or(statsd.metric_type);
+
+        let lines = &mut statsd.lines;
+        let tags_start = lines.len();
+        for (name, value) in dimensions {
+            if lines.len() > tags_start {
+                lines.push(',');
+            }
+            push_tag(lines, name, value);
+        }
+        if unit != Unit::None {
+            if lines.len() > tags_start {
+                lines.push(',');
+            }
+            push_tag(lines, "unit", unit.name());
+        }
+        let tags = tags_start..lines.len();
+
+        for observation in distribution {
+            let (value, occurrences) = match observation {
+                Observation::Unsigned(value) => (value as f64, 1),
+                Observation::Floating(value) if !value.is_nan() => (value, 1),
+                Observation::Repeated { total, occurrences }
+                    if occurrences > 0 && !total.is_nan() =>
+                {
+                    if metric_type == MetricType::Counter {
+                        (total, 1)
+                    } else {
+                        (total / occurrences as f64, occurrences)
+                    }
+                }
+                // skip NaN and empty observations
+                _ => continue,
+            };
+            let body_start = lines.len();
+            lines.push_str(&statsd.prefix);
+            lines.push_str(self.name);
+            lines.push(':');
+            match observation {
+                Observation::Unsigned(value) => lines.push_str(itoa::Buffer::new().format(value)),
+                _ => push_float(lines, value),
+            }
+            lines.push('|');
+            lines.push_str(metric_type.suffix());
+            let rate = rate.unwrap_or(1.0) / occurrences as f64;
+            if rate < 1.0 {
+                lines.push_str("|@");
+                push_float(lines, rate);
+            }
+            statsd.line_ranges.push(LineRanges {
+                body: body_start..lines.len(),
+                tags: tags.clone(),
+            });
+        }
+    }
+
+    fn error(self, error: ValidationError) {
+        self.writer.error.extend_mut(error.for_field(self.name));
+    }
+}
+
+fn is_reserved_in_name(c: char) -> bool {
+    matches!(c, ':' | '|' | '@' | '#' | '\n' | '\r')
+}
+
+/// Push a `name:value` tag, replacing the characters StatsD reserves in tags.
+fn push_tag(buf: &mut String, name: &str, value: &str) {
+    let sanitize = |s: &str, buf: &mut String| {
+        buf.extend(s.chars().map(|c| match c {
+            '|' | ',' | '#' | '\n' | '\r' => '_',
+            c => c,
+        }))
+    };
+    sanitize(name, buf);
+    buf.push(':');
+    sanitize(value, buf);
+}
+
+/// Push a float value, clamping infinities.
+fn push_float(buf: &mut String, v: f64) {
+    let v = v.clamp(-f64::MAX, f64::MAX);
+    // We use `dtoa` over `ryu` because `dtoa` always emits decimal notation
+    // (no scientific notation), which StatsD servers don't all accept.
+    let mut buffer = dtoa::Buffer::new();
+    let s = buffer.format_finite(v);
+    buf.push_str(s.strip_suffix(".0").unwrap_or(s));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use metrique_writer::sample::FixedFractionSample;
+    use metrique_writer::value::{Distribution, Mean, WithDimension};
+    use metrique_writer_core::unit::Millisecond;
+    use rand::SeedableRng;
+    use std::time::Duration;
+
+    struct TestEntry;
+    impl Entry for TestEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(SystemTime::UNIX_EPOCH);
+            writer.value("Latency", &Duration::from_micros(1500));
+            writer.value("Count", &10u64);
+            writer.value("Nan", &f64::NAN);
+            writer.value("Sizes", &Distribution::<u64, 2>::from_iter([1, 2]));
+            writer.value("Mean", &Mean::<Millisecond>::from_iter([1u32, 2, 6]));
+            writer.value(
+                "Throttles",
+                &WithDimension::new_with_dimensions(1u64, [("Shard", "a|b,c")]),
+            );
+            // written after the metrics, but still on every line
+            writer.value("Operation", "GetItem");
+            writer.value("RequestId", "1234");
+            writer.value("Tags", &[1u64, 2][..]);
+        }
+    }
+
+    fn format(format: &mut impl SampledFormat, rate: Option<f32>) -> String {
+        let mut output = vec![];
+        match rate {
+            None => format.format(&TestEntry, &mut output).unwrap(),
+            Some(rate) => format
+                .format_with_sample_rate(&TestEntry, &mut output, rate)
+                .unwrap(),
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn formats_lines() {
+        let mut statsd = Statsd::new()
+            .tag_fields(["Operation"])
+            .metric_type_for("Count", MetricType::Counter)
+            .metric_type_for("Mean", MetricType::Timer);
+        let expected = "\
+Latency:1.5|d|#Operation:GetItem,unit:Milliseconds
+Count:10|c|#Operation:GetItem
+Sizes:1|d|#Operation:GetItem
+Sizes:2|d|#Operation:GetItem
+Mean:3|ms|@0.3333333333333333|#Operation:GetItem,unit:Milliseconds
+Throttles:1|d|#Operation:GetItem,Shard:a_b_c
+";
+        assert_eq!(format(&mut statsd, None), expected);
+        // buffers are reset between entries
+        assert_eq!(format(&mut statsd, None), expected);
+    }
+
+    #[test]
+    fn writes_sample_rate() {
+        let mut statsd = Statsd::new()
+            .with_prefix("app.")
+            .metric_type(MetricType::Histogram)
+            .metric_type_for("Mean", MetricType::Counter);
+        assert_eq!(
+            format(&mut statsd, Some(0.5)),
+            "\
+app.Latency:1.5|h|@0.5|#unit:Milliseconds
+app.Count:10|h|@0.5
+app.Sizes:1|h|@0.5
+app.Sizes:2|h|@0.5
+app.Mean:9|c|@0.5|#unit:Milliseconds
+app.Throttles:1|h|@0.5|#Shard:a_b_c
+"
+        );
+        // a rate of 1 is not written
+        assert!(!format(&mut statsd, Some(1.0)).contains('@'));
+        assert!(
+            statsd
+                .format_with_sample_rate(&TestEntry, &mut vec![], 0.0)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn composes_with_samplers() {
+        let mut sampled = FixedFractionSample::with_rng(
+            Statsd::new(),
+            0.25,
+            rand_chacha::ChaChaRng::seed_from_u64(0),
+        );
+        let mut output = vec![];
+        for _ in 0..100 {
+            sampled.format(&TestEntry, &mut output).unwrap();
+        }
+        let output = String::from_utf8(output).unwrap();
+        let counts: Vec<_> = output.lines().filter(|l| l.starts_with("Count:")).collect();
+        assert!(!counts.is_empty() && counts.len() < 100);
+        assert!(counts.iter().all(|l| *l == "Count:10|d|@0.25"));
+    }
+
+    #[test]
+    fn validates_metric_names() {
+        struct BadName;
+        impl Entry for BadName {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.value("Latency|ms", &1u64);
+                // reserved characters are fine in string fields
+                writer.value("Name:With|Reserved", "value");
+            }
+        }
+        let err = Statsd::new()
+            .format(&BadName, &mut vec![])
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("for `Latency|ms`: metric name contains a character StatsD reserves"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid metric name prefix `app:`")]
+    fn validates_prefix() {
+        let _ = Statsd::new().with_prefix("app:");
+    }
+}
@@ -61,10 +61,10 @@ synstructure::decl_derive!([$name, attributes(entry)] =>
     ///  * `#[entry(flatten)]` to treat the field as a sub-entry whose contents will be merged with the current entry.
     ///    Note that any `sample_group` will be concatenated to this entry's!
     ///  * `#[entry(timestamp)]` to treat the field as the entry's timestamp. Note that it must impl
-    ///    `Into<SystemTime>`!
+    ///    `EntryTimestamp`, which is implemented for `Copy` types that impl `Into<SystemTime>`!
     ///  * `#[entry(sample_group)]` to treat the field as part of the entry's `sample_group`. The field's name (
-    ///     optionally overwritten by the `name` attribute) will be used as the key. Note that the field value must be
-    ///     cloneable and impl `Into<Cow<'static, str>>`!
+    ///     optionally overwritten by the `name` attribute) will be used as the key. Note that the field value must
+    ///     implement `SampleGroup`, e.g. `&'static str` or an integer such as a status code!
     ///  * `#[entry(format = FORMATTER)]` to format the field using a custom format, which should be a type
     ///    implementing `ValueFormatter`.
     ///
@@ -449,12 +449,9 @@ impl FieldSet {
                     ));
                 } else {
                     self.has_timestamp = true;
-                    // Note we have an explicit clippy allow so that if the timestamp is already a SystemTime, it
-                    // doesn't generate code with a warning!
                     self.writes.push(quote_spanned! {field.binding.span()=>
-                        #[allow(clippy::useless_conversion)]
-                        {
-                            #krate::core::entry::EntryWriter::timestamp(writer, (*#field).into());
+                        if let ::std::option::Option::Some(timestamp) = #krate::core::entry::EntryTimestamp::entry_timestamp(#field) {
+                            #krate::core::entry::EntryWriter::timestamp(writer, timestamp);
                         }
                     });
                 }
@@ -607,9 +604,8 @@ mod tests {
                                     sub_entry: ref __binding_6,
                                     custom_format: ref __binding_7,
                                 } => {
-                                    #[allow(clippy::useless_conversion)]
-                                    {
-                                        ::metrique_writer::core::entry::EntryWriter::timestamp(writer, (*__binding_0).into());
+                                    if let ::std::option::Option::Some(timestamp) = ::metrique_writer::core::entry::EntryTimestamp::entry_timestamp(__binding_0) {
+                                        ::metrique_writer::core::entry::EntryWriter::timestamp(writer, timestamp);
                                     }
                                     ::metrique_writer::core::entry::EntryWriter::value(writer, "Foo", __binding_1);
                                     ::metrique_writer::core::entry::EntryWriter::value(writer, "Bar", __binding_2);
@@ -669,9 +665,8 @@ mod tests {
                                 TestEntry {
                                     start: ref __binding_0,
                                 } => {
-                                    #[allow(clippy::useless_conversion)]
-                                    {
-                                        ::metrique::writer::core::entry::EntryWriter::timestamp(writer, (*__binding_0).into());
+                                    if let ::std::option::Option::Some(timestamp) = ::metrique::writer::core::entry::EntryTimestamp::entry_timestamp(__binding_0) {
+                                        ::metrique::writer::core::entry::EntryWriter::timestamp(writer, timestamp);
                                     }
                                 }
                             }
@@ -753,9 +748,8 @@ mod tests {
                                 }
                                 TestEntry::Second { test: ref __binding_0, time: ref __binding_1, some_counter: ref __binding_2, } => {
                                     ::metrique_writer::core::entry::EntryWriter::value(writer, "Test", __binding_0);
-                                    #[allow(clippy::useless_conversion)]
-                                    {
-                                        ::metrique_writer::core::entry::EntryWriter::timestamp(writer, (*__binding_1).into());
+                                    if let ::std::option::Option::Some(timestamp) = ::metrique_writer::core::entry::EntryTimestamp::entry_timestamp(__binding_1) {
+                                        ::metrique_writer::core::entry::EntryWriter::timestamp(writer, timestamp);
                                     }
                                     ::metrique_writer::core::entry::EntryWriter::value(writer, "SomeCounter", __binding_2);
                                 }
@@ -0,0 +1,50 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+
+use metrique_writer_core::{Entry, EntryWriter, Value, entry::SampleGroupElement};
+
+/// An entry with one extra field appended, as written by sinks that annotate entries such as
+/// [`WithComputedField`](crate::sink::WithComputedField).
+///
+/// The field is written after the fields of the wrapped entry. Its name is written as-is: name
+/// styles like `rename_all` apply to the fields of the wrapped entry, not to the extra field.
+#[derive(Clone, Debug)]
+pub struct WithField<E, V> {
+    entry: E,
+    name: Cow<'static, str>,
+    value: V,
+}
+
+impl<E, V> WithField<E, V> {
+    /// Append the field `name` with `value` to `entry`
+    pub fn new(entry: E, name: impl Into<Cow<'static, str>>, value: V) -> Self {
+        Self {
+            entry,
+            name: name.into(),
+            value,
+        }
+    }
+
+    /// Return the value of the extra field
+    pub fn value(&self) -> &V {
+        &self.value
+    }
+
+    /// Return the wrapped entry, discarding the extra field
+    pub fn into_inner(self) -> E {
+        self.entry
+    }
+}
+
+impl<E: Entry, V: Value> Entry for WithField<E, V> {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        self.entry.write(writer);
+        writer.value(&*self.name, &self.value);
+    }
+
+    fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+        self.entry.sample_group()
+    }
+}
@@ -4,6 +4,9 @@
 //! Contains various utilities for [Entry](crate::Entry)
 
 mod dimensions;
+mod field;
 mod map;
 pub use dimensions::WithGlobalDimensions;
+pub use field::WithField;
 pub use map::EnumMapEntry;
+pub use metrique_writer_core::entry::{MetricDescription, WithEntryUnit, describe_metrics};
@@ -10,13 +10,16 @@ use metrique_writer_core::{
     stream::{EntryIoStream, IoStreamError},
 };
 
-pub use metrique_writer_core::format::Format;
+pub use metrique_writer_core::format::{Format, FormatOutcome};
+use metrique_writer_core::sample::SampledFormat;
 use smallvec::SmallVec;
 
 use crate::{
     CowStr,
     entry::WithGlobalDimensions,
-    stream::{MergeGlobalDimensions, MergeGlobals},
+    stream::{
+        MergeGlobalDimensions, MergeGlobals, SampleGroupDimensions, with_sample_group_dimensions,
+    },
 };
 
 /// Extension trait for [`Format`]. This adds methods that use types not
@@ -103,6 +106,8 @@ pub trait FormatExt: Format {
         FormattedEntryIoStream {
             format: self,
             output,
+            bytes_written: 0,
+            entries_sampled_out: 0,
         }
     }
 
@@ -157,6 +162,8 @@ pub trait FormatExt: Format {
         FormattedMakeWriterEntryIoStream {
             format: self,
             output,
+            bytes_written: 0,
+            entries_sampled_out: 0,
         }
     }
 
@@ -247,6 +254,96 @@ pub trait FormatExt: Format {
             global_dimensions_denylist: global_dimensions_denylist.unwrap_or_default(),
         }
     }
+
+    /// Add dimension sets to every entry from the keys of its [sample group](Entry::sample_group),
+    /// so a field that is already in the sample group (like the operation name) doesn't also need
+    /// to be declared as a dimension on the entry.
+    ///
+    /// Each entry gets the given `dimension_sets` as an [`EntryDimensions`] config, which the EMF
+    /// format cartesian-products with its own dimension sets and merges with any other
+    /// `EntryDimensions` the entry sets. Keys that are missing from the sample group of an entry
+    /// are skipped for that entry, and sets left empty by that are dropped, so an entry with none
+    /// of the keys is written with only the format's dimension sets.
+    ///
+    /// The sample group values must also be written as string fields of the entry, which
+    /// `#[entry(sample_group)]` fields are.
+    ///
+    /// There is intentionally both a [`EntryIoStreamExt::sample_group_dimensions`] and a
+    /// [`FormatExt::sample_group_dimensions`], which implement exactly the same functionality,
+    /// to allow using in interfaces that accept an [`EntryIoStream`] as well as interfaces
+    /// that accept a [`Format`].
+    ///
+    /// ```
+    /// # use metrique_writer::{Entry, EntryIoStream, FormatExt as _};
+    /// # use metrique_writer_format_emf::Emf;
+    /// #[derive(Entry)]
+    /// #[entry(rename_all = "PascalCase")]
+    /// struct RequestMetrics {
+    ///     #[entry(sample_group)]
+    ///     operation: &'static str,
+    ///     count: u64,
+    /// }
+    ///
+    /// let mut output = vec![];
+    /// let mut stream = Emf::all_validations("MyApp".into(), vec![vec![]])
+    ///     .sample_group_dimensions([["Operation"]])
+    ///     .output_to(&mut output);
+    /// stream.next(&RequestMetrics { operation: "Get", count: 1 }).unwrap();
+    /// drop(stream);
+    /// assert!(String::from_utf8(output).unwrap().contains(r#""Dimensions":[["Operation"]]"#));
+    /// ```
+    ///
+    /// [`EntryDimensions`]: metrique_writer_core::config::EntryDimensions
+    /// [`EntryIoStreamExt::sample_group_dimensions`]: crate::stream::EntryIoStreamExt::sample_group_dimensions
+    /// [`FormatExt::sample_group_dimensions`]: crate::format::FormatExt::sample_group_dimensions
+    fn sample_group_dimensions<K: Into<CowStr>>(
+        self,
+        dimension_sets: impl IntoIterator<Item = impl IntoIterator<Item = K>>,
+    ) -> SampleGroupDimensions<Self>
+    where
+        Self: Sized,
+    {
+        SampleGroupDimensions::new(self, dimension_sets)
+    }
+
+    /// Add a fixed `prefix` and `suffix` to every line written by this format.
+    ///
+    /// This is useful for ingestion systems that need framing on each metric line, such as a
+    /// syslog priority. Entries that are written as multiple lines (e.g. EMF entries that are split
+    /// because they have too many metrics) get the prefix and suffix on each line. The suffix is
+    /// written before the line's newline.
+    ///
+    /// ```
+    /// # use metrique_writer::{Entry, EntryIoStream, format::FormatExt as _};
+    /// # use metrique_writer_format_emf::Emf;
+    /// #[derive(Entry)]
+    /// struct RequestMetrics {
+    ///     count: u64,
+    /// }
+    ///
+    /// let mut output = vec![];
+    /// let mut stream = Emf::all_validations("MyApp".into(), vec![vec![]])
+    ///     // syslog priority for local0.info
+    ///     .with_line_framing("<134>", "")
+    ///     .output_to(&mut output);
+    /// stream.next(&RequestMetrics { count: 1 }).unwrap();
+    /// assert!(output.starts_with(b"<134>{"));
+    /// ```
+    fn with_line_framing(
+        self,
+        prefix: impl Into<Vec<u8>>,
+        suffix: impl Into<Vec<u8>>,
+    ) -> WithLineFraming<Self>
+    where
+        Self: Sized,
+    {
+        WithLineFraming {
+            format: self,
+            prefix: prefix.into(),
+            suffix: suffix.into(),
+            buffer: Vec::new(),
+        }
+    }
 }
 impl<T: Format + ?Sized> FormatExt for T {}
 
@@ -256,11 +353,57 @@ impl<T: Format + ?Sized> FormatExt for T {}
 pub struct FormattedEntryIoStream<F, O> {
     format: F,
     output: O,
+    bytes_written: u64,
+    entries_sampled_out: u64,
 }
 
 impl<F: Format, O: io::Write> EntryIoStream for FormattedEntryIoStream<F, O> {
     fn next(&mut self, entry: &impl Entry) -> Result<(), IoStreamError> {
-        self.format.format(entry, &mut self.output)
+        // count with a wrapper rather than the count in the outcome, so the partial output of
+        // entries that fail to format is counted too
+        let outcome = self.format.format_outcome(
+            entry,
+            &mut CountingWriter {
+                output: &mut self.output,
+                bytes_written: &mut self.bytes_written,
+            },
+        )?;
+        if outcome == FormatOutcome::Dropped {
+            self.entries_sampled_out += 1;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+
+    fn bytes_written(&self) -> Option<u64> {
+        Some(self.bytes_written)
+    }
+
+    fn entries_sampled_out(&self) -> Option<u64> {
+        Some(self.entries_sampled_out)
+    }
+}
+
+/// An [`io::Write`] that counts the bytes written to the wrapped output
+struct CountingWriter<'a, O> {
+    output: &'a mut O,
+    bytes_written: &'a mut u64,
+}
+
+impl<O: io::Write> io::Write for CountingWriter<'_, O> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.output.write(buf)?;
+        *self.bytes_written += written as u64;
+        Ok(written)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let written = self.output.write_vectored(bufs)?;
+        *self.bytes_written += written as u64;
+        Ok(written)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -277,6 +420,24 @@ impl<F: Format, G: Entry> Format for MergeGlobals<F, G> {
         self.stream
             .format(&self.globals.merge_by_ref(entry), output)
     }
+
+    fn format_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<usize, IoStreamError> {
+        self.stream
+            .format_counted(&self.globals.merge_by_ref(entry), output)
+    }
+
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        self.stream
+            .format_outcome(&self.globals.merge_by_ref(entry), output)
+    }
 }
 
 impl<F: Format, const N: usize> Format for MergeGlobalDimensions<F, N> {
@@ -296,6 +457,189 @@ impl<F: Format, const N: usize> Format for MergeGlobalDimensions<F, N> {
             self.stream.format(&entry_with_global_dimensions, output)
         }
     }
+
+    fn format_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<usize, IoStreamError> {
+        if self.global_dimensions.is_empty() {
+            self.stream.format_counted(&entry, output)
+        } else {
+            let entry_with_global_dimensions = WithGlobalDimensions::new(
+                entry,
+                self.global_dimensions.clone(),
+                self.global_dimensions_denylist.clone(),
+            );
+            self.stream
+                .format_counted(&entry_with_global_dimensions, output)
+        }
+    }
+
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        if self.global_dimensions.is_empty() {
+            self.stream.format_outcome(&entry, output)
+        } else {
+            let entry_with_global_dimensions = WithGlobalDimensions::new(
+                entry,
+                self.global_dimensions.clone(),
+                self.global_dimensions_denylist.clone(),
+            );
+            self.stream
+                .format_outcome(&entry_with_global_dimensions, output)
+        }
+    }
+}
+
+impl<F: Format> Format for SampleGroupDimensions<F> {
+    fn format(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<(), IoStreamError> {
+        self.stream.format(
+            &with_sample_group_dimensions(&self.dimensions, entry),
+            output,
+        )
+    }
+
+    fn format_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<usize, IoStreamError> {
+        self.stream.format_counted(
+            &with_sample_group_dimensions(&self.dimensions, entry),
+            output,
+        )
+    }
+
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        self.stream.format_outcome(
+            &with_sample_group_dimensions(&self.dimensions, entry),
+            output,
+        )
+    }
+}
+
+impl<F: SampledFormat> SampledFormat for SampleGroupDimensions<F> {
+    fn format_with_sample_rate(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+        rate: f32,
+    ) -> Result<(), IoStreamError> {
+        self.stream.format_with_sample_rate(
+            &with_sample_group_dimensions(&self.dimensions, entry),
+            output,
+            rate,
+        )
+    }
+}
+
+/// See [`FormatExt::with_line_framing`].
+#[derive(Debug, Clone)]
+pub struct WithLineFraming<F> {
+    format: F,
+    prefix: Vec<u8>,
+    suffix: Vec<u8>,
+    // reused between entries, the inner format writes here before the lines are framed
+    buffer: Vec<u8>,
+}
+
+impl<F> WithLineFraming<F> {
+    /// Write the framed lines of `buffer` to `output`, returning the number of bytes written
+    fn write_framed(&mut self, output: &mut impl io::Write) -> io::Result<usize> {
+        let mut written = 0;
+        let mut rest = &self.buffer[..];
+        while !rest.is_empty() {
+            let (line, newline) = match rest.iter().position(|&b| b == b'\n') {
+                Some(end) => (&rest[..end], true),
+                None => (rest, false),
+            };
+            output.write_all(&self.prefix)?;
+            output.write_all(line)?;
+            output.write_all(&self.suffix)?;
+            written += self.prefix.len() + line.len() + self.suffix.len();
+            if newline {
+                output.write_all(b"\n")?;
+                written += 1;
+                rest = &rest[line.len() + 1..];
+            } else {
+                rest = &[];
+            }
+        }
+        Ok(written)
+    }
+
+    /// Format to the buffer with `format`, then write the framed lines, returning the result of
+    /// `format` and the number of bytes written
+    fn frame<T>(
+        &mut self,
+        output: &mut impl io::Write,
+        format: impl FnOnce(&mut F, &mut Vec<u8>) -> Result<T, IoStreamError>,
+    ) -> Result<(T, usize), IoStreamError> {
+        self.buffer.clear();
+        let result = format(&mut self.format, &mut self.buffer);
+        // like an unframed format, write whatever was written even if formatting failed
+        let written = self.write_framed(output)?;
+        result.map(|result| (result, written))
+    }
+}
+
+impl<F: Format> Format for WithLineFraming<F> {
+    fn format(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<(), IoStreamError> {
+        self.format_counted(entry, output).map(drop)
+    }
+
+    fn format_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<usize, IoStreamError> {
+        self.frame(output, |format, buffer| format.format(entry, buffer))
+            .map(|((), written)| written)
+    }
+
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        let (outcome, written) = self.frame(output, |format, buffer| {
+            format.format_outcome(entry, buffer)
+        })?;
+        Ok(match outcome {
+            FormatOutcome::Dropped => FormatOutcome::Dropped,
+            _ => FormatOutcome::Emitted(written),
+        })
+    }
+}
+
+impl<F: SampledFormat> SampledFormat for WithLineFraming<F> {
+    fn format_with_sample_rate(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+        rate: f32,
+    ) -> Result<(), IoStreamError> {
+        self.frame(output, |format, buffer| {
+            format.format_with_sample_rate(entry, buffer, rate)
+        })
+        .map(drop)
+    }
 }
 
 #[derive(Debug)]
@@ -305,6 +649,8 @@ impl<F: Format, const N: usize> Format for MergeGlobalDimensions<F, N> {
 pub struct FormattedMakeWriterEntryIoStream<F, O> {
     format: F,
     output: O,
+    bytes_written: u64,
+    entries_sampled_out: u64,
 }
 
 #[cfg(feature = "tracing-subscriber-03")]
@@ -312,11 +658,202 @@ impl<F: Format, O: for<'a> tracing_subscriber::fmt::MakeWriter<'a>> EntryIoStrea
     for FormattedMakeWriterEntryIoStream<F, O>
 {
     fn next(&mut self, entry: &impl Entry) -> Result<(), IoStreamError> {
-        self.format.format(entry, &mut self.output.make_writer())
+        let outcome = self.format.format_outcome(
+            entry,
+            &mut CountingWriter {
+                output: &mut self.output.make_writer(),
+                bytes_written: &mut self.bytes_written,
+            },
+        )?;
+        if outcome == FormatOutcome::Dropped {
+            self.entries_sampled_out += 1;
+        }
+        Ok(())
     }
 
     fn flush(&mut self) -> io::Result<()> {
         // tracing-subscriber formatters do not need or support flushing
         Ok(())
     }
+
+    fn bytes_written(&self) -> Option<u64> {
+        Some(self.bytes_written)
+    }
+
+    fn entries_sampled_out(&self) -> Option<u64> {
+        Some(self.entries_sampled_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use metrique_writer_core::{Entry, EntryWriter, IoStreamError, ValidationError};
+
+    use super::{Format, FormatExt as _, FormatOutcome};
+    use crate::EntryIoStream;
+
+    /// Writes each entry's string fields as lines, and optionally fails after writing them
+    struct LinesFormat {
+        fail: bool,
+    }
+
+    struct Lines(&'static [&'static str]);
+
+    impl Entry for Lines {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            for line in self.0 {
+                writer.value("line", *line);
+            }
+        }
+    }
+
+    impl Format for LinesFormat {
+        fn format(
+            &mut self,
+            entry: &impl Entry,
+            output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            let mut writer = metrique_writer_core::test_stream::DummyEntryWriter::default();
+            entry.write(&mut writer);
+            for (_, line) in writer.0 {
+                writeln!(output, "{line}")?;
+            }
+            if self.fail {
+                return Err(ValidationError::invalid("failed").into());
+            }
+            Ok(())
+        }
+    }
+
+    fn framed(entries: &[Lines], prefix: &str, suffix: &str) -> String {
+        let mut output = vec![];
+        let mut stream = LinesFormat { fail: false }
+            .with_line_framing(prefix, suffix)
+            .output_to(&mut output);
+        for entry in entries {
+            stream.next(entry).unwrap();
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn single_line_entries() {
+        assert_eq!(
+            framed(
+                &[Lines(&["{\"a\":1}"]), Lines(&["{\"a\":2}"])],
+                "<134>",
+                " END"
+            ),
+            "<134>{\"a\":1} END\n<134>{\"a\":2} END\n"
+        );
+    }
+
+    #[test]
+    fn multi_line_entries() {
+        assert_eq!(
+            framed(&[Lines(&["one", "two", "three"])], "> ", ""),
+            "> one\n> two\n> three\n"
+        );
+        // empty lines are framed too, but an entry that writes nothing is not
+        assert_eq!(
+            framed(&[Lines(&[]), Lines(&["", "x"])], "[", "]"),
+            "[]\n[x]\n"
+        );
+    }
+
+    #[test]
+    fn missing_trailing_newline() {
+        struct NoNewline;
+        impl Format for NoNewline {
+            fn format(
+                &mut self,
+                _entry: &impl Entry,
+                output: &mut impl io::Write,
+            ) -> Result<(), IoStreamError> {
+                output.write_all(b"a\nb")?;
+                Ok(())
+            }
+        }
+
+        let mut output = vec![];
+        NoNewline
+            .with_line_framing("<", ">")
+            .format(&Lines(&[]), &mut output)
+            .unwrap();
+        assert_eq!(output, b"<a>\n<b>");
+    }
+
+    #[test]
+    fn counts_framed_bytes() {
+        let mut format = LinesFormat { fail: false }.with_line_framing("<134>", " END");
+        let mut output = vec![];
+        let written = format
+            .format_counted(&Lines(&["one", "two"]), &mut output)
+            .unwrap();
+        assert_eq!(written, output.len());
+
+        let mut output = vec![];
+        let mut stream = format.output_to(&mut output);
+        stream.next(&Lines(&["one"])).unwrap();
+        stream.next(&Lines(&["two", "three"])).unwrap();
+        let written = stream.bytes_written().unwrap();
+        drop(stream);
+        assert_eq!(written, output.len() as u64);
+    }
+
+    #[test]
+    fn output_is_written_on_error() {
+        let mut output = vec![];
+        let result = LinesFormat { fail: true }
+            .with_line_framing("# ", "")
+            .format(&Lines(&["partial"]), &mut output);
+        assert!(matches!(result, Err(IoStreamError::Validation(_))));
+        assert_eq!(output, b"# partial\n");
+    }
+
+    /// Like [`LinesFormat`], but reports entries without lines as sampled out
+    struct SkipEmptyFormat;
+
+    impl Format for SkipEmptyFormat {
+        fn format(
+            &mut self,
+            entry: &impl Entry,
+            output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            self.format_outcome(entry, output).map(|_| ())
+        }
+
+        fn format_outcome(
+            &mut self,
+            entry: &impl Entry,
+            output: &mut impl io::Write,
+        ) -> Result<FormatOutcome, IoStreamError> {
+            let mut writer = metrique_writer_core::test_stream::DummyEntryWriter::default();
+            entry.write(&mut writer);
+            if writer.0.is_empty() {
+                return Ok(FormatOutcome::Dropped);
+            }
+            LinesFormat { fail: false }
+                .format_counted(entry, output)
+                .map(FormatOutcome::Emitted)
+        }
+    }
+
+    #[test]
+    fn counts_entries_sampled_out() {
+        let mut output = vec![];
+        let mut stream = SkipEmptyFormat
+            .with_line_framing("<", ">")
+            .output_to(&mut output);
+        assert_eq!(stream.entries_sampled_out(), Some(0));
+        stream.next(&Lines(&["one"])).unwrap();
+        stream.next(&Lines(&[])).unwrap();
+        stream.next(&Lines(&[])).unwrap();
+        assert_eq!(stream.entries_sampled_out(), Some(2));
+        assert_eq!(stream.bytes_written(), Some(6));
+        drop(stream);
+        assert_eq!(output, b"<one>\n");
+    }
 }
@@ -5,7 +5,7 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
-pub use metrique_writer_core::entry::{BoxEntry, Entry, EntryConfig, EntryWriter};
+pub use metrique_writer_core::entry::{BoxEntry, Entry, EntryConfig, EntryTimestamp, EntryWriter};
 pub use metrique_writer_core::global::GlobalEntrySink;
 pub use metrique_writer_core::sink::{AnyEntrySink, BoxEntrySink, EntrySink};
 pub use metrique_writer_core::stream::{EntryIoStream, IoStreamError};
@@ -32,6 +32,7 @@ pub mod value;
 pub use metrique_writer_core as core;
 
 pub use format::FormatExt;
+pub use metrique_writer_core::const_globals;
 pub use metrique_writer_core::global::{AttachGlobalEntrySink, ShutdownFn};
 pub use metrique_writer_core::unit;
 pub use stream::EntryIoStreamExt;
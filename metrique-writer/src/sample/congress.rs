@@ -3,11 +3,17 @@
 
 use std::{
     io, mem,
+    sync::{Arc, Mutex, PoisonError},
     time::{Duration, Instant},
 };
 
 use ahash::HashMap;
-use metrique_writer_core::{Entry, IoStreamError, entry::SampleGroupElement, format::Format};
+use metrique_writer_core::{
+    Entry, IoStreamError,
+    config::HighPriority,
+    entry::{SampleGroupElement, SampleGroupMap},
+    format::{Format, FormatOutcome},
+};
 use rand::{Rng, RngCore, rngs::ThreadRng};
 use smallvec::SmallVec;
 
@@ -52,6 +58,7 @@ impl CongressSampleBuilder {
             next_interval_start: Instant::now(),
             current_observed: 0,
             groups: Default::default(),
+            published_rates: None,
         }
     }
 
@@ -99,16 +106,20 @@ pub struct CongressSample<F, R = DefaultRng<ThreadRng>> {
     next_interval_start: Instant,
     current_observed: u32,
     groups: HashMap<Group, GroupState>,
+    // only set once a `SampleRatesHandle` was requested, updated once per interval
+    published_rates: Option<Arc<Mutex<SampleRates>>>,
 }
 
 type Group = SmallVec<[SampleGroupElement; 2]>;
+type SampleRates = Vec<(SampleGroupMap, f32)>;
+
+impl<F, R: RngCore> CongressSample<F, R> {
+    /// Return the sample rate to write `entry` with, or `None` to drop it
+    fn sample(&mut self, entry: &impl Entry) -> Option<f32> {
+        if HighPriority::is_set(entry) {
+            return Some(1.0);
+        }
 
-impl<F: SampledFormat, R: RngCore> Format for CongressSample<F, R> {
-    fn format(
-        &mut self,
-        entry: &impl Entry,
-        output: &mut impl io::Write,
-    ) -> Result<(), IoStreamError> {
         let mut group: Group = entry.sample_group().collect();
         group.sort_unstable();
 
@@ -123,10 +134,33 @@ impl<F: SampledFormat, R: RngCore> Format for CongressSample<F, R> {
         }
 
         let rate = self.sample_rate(group);
-        if rate == 1.0 || self.rng.random::<f32>() <= rate {
-            self.format.format_with_sample_rate(entry, output, rate)
-        } else {
-            Ok(())
+        (rate == 1.0 || self.rng.random::<f32>() <= rate).then_some(rate)
+    }
+}
+
+impl<F: SampledFormat, R: RngCore> Format for CongressSample<F, R> {
+    fn format(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<(), IoStreamError> {
+        match self.sample(entry) {
+            Some(rate) => self.format.format_with_sample_rate(entry, output, rate),
+            None => Ok(()),
+        }
+    }
+
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        match self.sample(entry) {
+            Some(rate) => self
+                .format
+                .format_with_sample_rate_counted(entry, output, rate)
+                .map(FormatOutcome::Emitted),
+            None => Ok(FormatOutcome::Dropped),
         }
     }
 }
@@ -217,6 +251,59 @@ impl<F, R> CongressSample<F, R> {
         &mut self.format
     }
 
+    /// Return the sample rate currently used for each sample group, for diagnostics like tuning
+    /// the target number of entries.
+    ///
+    /// A group that was seen for the first time during the current interval has a rate of 1.0
+    /// until the end of the interval. Groups are listed in no particular order. Calling this
+    /// doesn't affect sampling.
+    ///
+    /// Once the format is moved into an [`EntryIoStream`] or a background queue, use
+    /// [`CongressSample::sample_rates_handle`] instead.
+    ///
+    /// [`EntryIoStream`]: crate::EntryIoStream
+    pub fn sample_rates(&self) -> SampleRates {
+        self.groups
+            .iter()
+            .map(|(group, state)| (group.iter().cloned().collect(), state.sample_rate))
+            .collect()
+    }
+
+    /// Return a handle that can be used from other threads to look at the sample rate of each
+    /// sample group, for diagnostics like tuning the target number of entries.
+    ///
+    /// Unlike [`CongressSample::sample_rates`], the rates seen through the handle are only
+    /// updated at the end of every interval, which is also when they change the most. Until
+    /// then, the handle returns the rates computed at the end of the previous interval, or no
+    /// rates before the first interval ends.
+    ///
+    /// Publishing the rates costs a copy of the rates once per interval, and is only done once
+    /// a handle was requested.
+    ///
+    /// ```
+    /// # use metrique_writer::{Entry, EntryIoStream, FormatExt as _};
+    /// # use metrique_writer::sample::SampledFormatExt as _;
+    /// # use metrique_writer_format_emf::Emf;
+    /// # use std::io;
+    /// let mut format = Emf::all_validations("MyApp".into(), vec![vec![]])
+    ///     .with_sampling()
+    ///     .sample_by_congress_at_fixed_entries_per_second(100);
+    /// let rates = format.sample_rates_handle();
+    /// let stream = format.output_to(io::sink());
+    /// // ... later, e.g. from a diagnostics endpoint
+    /// for (group, rate) in rates.snapshot() {
+    ///     let group: Vec<_> = group.iter().map(|(k, v)| format!("{k}={v}")).collect();
+    ///     println!("{}: {rate}", group.join(","));
+    /// }
+    /// ```
+    pub fn sample_rates_handle(&mut self) -> SampleRatesHandle {
+        let rates = self
+            .published_rates
+            .get_or_insert_with(Default::default)
+            .clone();
+        SampleRatesHandle { rates }
+    }
+
     fn sample_rate(&mut self, group: Group) -> f32 {
         let now = Instant::now();
         if now > self.next_interval_start {
@@ -271,6 +358,34 @@ impl<F, R> CongressSample<F, R> {
                 };
             }
         }
+
+        if let Some(published_rates) = &self.published_rates {
+            let rates = self.sample_rates();
+            *published_rates
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner) = rates;
+        }
+    }
+}
+
+/// A handle to look at the sample rates of a [`CongressSample`] from other threads, see
+/// [`CongressSample::sample_rates_handle`].
+///
+/// This is meant for diagnostics. The rates are the ones computed at the end of the last
+/// sampling interval.
+#[derive(Clone, Debug)]
+pub struct SampleRatesHandle {
+    rates: Arc<Mutex<SampleRates>>,
+}
+
+impl SampleRatesHandle {
+    /// Return the sample rate of each sample group as of the end of the last interval, in no
+    /// particular order.
+    pub fn snapshot(&self) -> SampleRates {
+        self.rates
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
     }
 }
 
@@ -446,6 +561,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn exposes_sample_rates() {
+        let mut congress = CongressSampleBuilder::default()
+            .target_entries_per_interval(200)
+            .interval(Duration::from_secs(86400)) // trigger manually
+            .build(TestFormat::default());
+        let handle = congress.sample_rates_handle();
+        assert!(handle.snapshot().is_empty());
+
+        for _ in 0..100 {
+            for (count, operation) in [(800, "A"), (50, "B")] {
+                for _ in 0..count {
+                    congress
+                        .format(&TestEntry { operation }, &mut io::sink())
+                        .unwrap();
+                }
+            }
+            congress.update_rates();
+        }
+
+        let sorted = |mut rates: Vec<(SampleGroupMap, f32)>| {
+            rates.sort_by(|(a, _), (b, _)| a.get("operation").cmp(&b.get("operation")));
+            rates
+        };
+        let rates = sorted(congress.sample_rates());
+        assert_eq!(rates.len(), 2);
+        assert_eq!(rates[0].0.get("operation"), Some("A"));
+        assert_approx_eq!(rates[0].1, 0.1975, 0.001);
+        assert_eq!(rates[1].0.get("operation"), Some("B"));
+        assert_approx_eq!(rates[1].1, 0.8395, 0.001);
+        assert_eq!(sorted(handle.snapshot()), rates);
+
+        // new groups show up immediately, but only in the handle after the interval ends
+        congress
+            .format(&TestEntry { operation: "C" }, &mut io::sink())
+            .unwrap();
+        assert_eq!(congress.sample_rates().len(), 3);
+        assert_eq!(handle.snapshot().len(), 2);
+    }
+
     // | SET | Unsampled | House | Senate | Congress | Final |
     // | A   | 72000     | 7488  | 7800   | 7800     | 7647  |
     // | B   | 78000     | 8112  | 7800   | 8112     | 7953  |
@@ -508,6 +663,46 @@ mod tests {
         }
     }
 
+    #[test]
+    fn high_priority_entries_are_never_sampled() {
+        let mut congress = CongressSampleBuilder::default()
+            .target_entries_per_interval(10)
+            .interval(Duration::from_secs(86400)) // trigger manually
+            .build(TestFormat::default());
+
+        for _ in 0..100 {
+            congress.format.entries.clear();
+            for _ in 0..1000 {
+                congress
+                    .format(&TestEntry { operation: "A" }, &mut io::sink())
+                    .unwrap();
+            }
+            congress.update_rates();
+        }
+
+        congress.format.entries.clear();
+        for _ in 0..100 {
+            congress
+                .format(
+                    &PriorityEntry(TestEntry { operation: "B" }),
+                    &mut io::sink(),
+                )
+                .unwrap();
+        }
+        let in_interval = mem::take(&mut congress.format.entries);
+        assert_eq!(in_interval.len(), 100);
+        for (entry, rate) in in_interval {
+            assert_eq!(entry, "B");
+            assert_eq!(rate, 1.0);
+        }
+        // priority entries do not count against their group's budget
+        assert!(
+            !congress
+                .groups
+                .contains_key(&[("operation".into(), "B".into())][..])
+        );
+    }
+
     #[derive(Clone, Copy, Debug)]
     struct TestEntry {
         operation: &'static str,
@@ -523,6 +718,19 @@ mod tests {
         }
     }
 
+    struct PriorityEntry(TestEntry);
+
+    impl Entry for PriorityEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            self.0.write(writer);
+            writer.config(const { &HighPriority::new() });
+        }
+
+        fn sample_group(&self) -> impl Iterator<Item = (Cow<'static, str>, Cow<'static, str>)> {
+            self.0.sample_group()
+        }
+    }
+
     #[derive(Default)]
     struct TestFormat {
         entries: Vec<(String, f32)>,
@@ -0,0 +1,309 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io;
+
+use metrique_writer_core::{
+    Entry, IoStreamError,
+    config::HighPriority,
+    entry::SampleGroupMap,
+    format::{Format, FormatOutcome},
+};
+use rand::{Rng, RngCore, rngs::ThreadRng};
+
+use super::{DefaultRng, SampledFormat};
+
+/// See [`SampledFormatExt::sample_by_consistent_hash`].
+///
+/// Whether an entry is written is a deterministic function of its key, as returned by the key
+/// extractor from the entry's [sample group](Entry::sample_group). All entries with the same
+/// key are either written or discarded together, even across processes, which keeps e.g. all
+/// the entries of a trace together. Written entries are upweighted by the sample rate exactly
+/// like with [`FixedFractionSample`].
+///
+/// The hash function is fixed and doesn't depend on the platform, the process, or the version
+/// of this crate. Entries whose key extractor returns `None` are sampled at random at the same
+/// rate.
+///
+/// [`SampledFormatExt::sample_by_consistent_hash`]: super::SampledFormatExt::sample_by_consistent_hash
+/// [`FixedFractionSample`]: super::FixedFractionSample
+pub struct ConsistentHashSample<F, K, R = DefaultRng<ThreadRng>> {
+    format: F,
+    key: K,
+    rate: f32,
+    // entries are kept if the hash of their key is at most this
+    threshold: u64,
+    rng: R,
+}
+
+impl<F, K> ConsistentHashSample<F, K> {
+    /// Create a new [`SampledFormat`] from `format` that will emit the entries for which `key`
+    /// returns a key whose hash falls within `rate`.
+    ///
+    /// Uses the default [`ThreadRng`] for entries without a key.
+    pub fn new(format: F, key: K, rate: f32) -> Self
+    where
+        K: FnMut(&SampleGroupMap) -> Option<&str>,
+    {
+        Self::with_rng(format, key, rate, Default::default())
+    }
+}
+
+impl<F, K, R> ConsistentHashSample<F, K, R> {
+    /// Like [`ConsistentHashSample::new`], but also specify the random number generator used for
+    /// entries without a key.
+    pub fn with_rng(format: F, key: K, rate: f32, rng: R) -> Self
+    where
+        K: FnMut(&SampleGroupMap) -> Option<&str>,
+    {
+        assert!(rate.is_finite() && 0.0 < rate && rate <= 1.0);
+        let threshold = if rate == 1.0 {
+            u64::MAX
+        } else {
+            (rate as f64 * u64::MAX as f64) as u64
+        };
+        Self {
+            format,
+            key,
+            rate,
+            threshold,
+            rng,
+        }
+    }
+
+    /// Return a mutable reference to the inner [`Format`].
+    ///
+    /// [`Format`]: crate::format::Format
+    pub fn format_mut(&mut self) -> &mut F {
+        &mut self.format
+    }
+}
+
+impl<F, K, R> ConsistentHashSample<F, K, R>
+where
+    K: FnMut(&SampleGroupMap) -> Option<&str>,
+    R: RngCore,
+{
+    /// Return the sample rate to write `entry` with, or `None` to drop it
+    fn sample(&mut self, entry: &impl Entry) -> Option<f32> {
+        if HighPriority::is_set(entry) {
+            return Some(1.0);
+        }
+
+        let group = SampleGroupMap::from_entry(entry);
+        let keep = match (self.key)(&group) {
+            Some(key) => hash(key.as_bytes()) <= self.threshold,
+            None => self.rng.random::<f32>() <= self.rate,
+        };
+        keep.then_some(self.rate)
+    }
+}
+
+impl<F, K, R> Format for ConsistentHashSample<F, K, R>
+where
+    F: SampledFormat,
+    K: FnMut(&SampleGroupMap) -> Option<&str>,
+    R: RngCore,
+{
+    fn format(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<(), IoStreamError> {
+        match self.sample(entry) {
+            Some(rate) => self.format.format_with_sample_rate(entry, output, rate),
+            None => Ok(()),
+        }
+    }
+
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        match self.sample(entry) {
+            Some(rate) => self
+                .format
+                .format_with_sample_rate_counted(entry, output, rate)
+                .map(FormatOutcome::Emitted),
+            None => Ok(FormatOutcome::Dropped),
+        }
+    }
+}
+
+/// 64-bit FNV-1a, followed by the murmur3 finalizer to spread short keys over the whole range.
+///
+/// This must never change, since sampling decisions are meant to agree across versions.
+fn hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &byte in bytes {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51afd7ed558ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ceb9fe1a85ec53);
+    hash ^ (hash >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, io};
+
+    use metrique_writer_core::{
+        Entry, EntryWriter, IoStreamError,
+        config::HighPriority,
+        entry::{SampleGroupElement, SampleGroupMap},
+        format::Format,
+    };
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::{ConsistentHashSample, SampledFormat, hash};
+
+    /// Records the trace id and sample rate of every written entry
+    #[derive(Default)]
+    struct TracesFormat {
+        written: Vec<(Option<String>, f32)>,
+    }
+
+    impl Format for TracesFormat {
+        fn format(
+            &mut self,
+            _entry: &impl Entry,
+            _output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            unreachable!("should be using sampled format fns")
+        }
+    }
+
+    impl SampledFormat for TracesFormat {
+        fn format_with_sample_rate(
+            &mut self,
+            entry: &impl Entry,
+            _output: &mut impl io::Write,
+            rate: f32,
+        ) -> Result<(), IoStreamError> {
+            let trace_id = entry
+                .sample_group()
+                .find(|(key, _)| key == "TraceId")
+                .map(|(_, value)| value.into_owned());
+            self.written.push((trace_id, rate));
+            Ok(())
+        }
+    }
+
+    struct Span {
+        trace_id: Option<String>,
+    }
+
+    impl Entry for Span {
+        fn write<'a>(&'a self, _writer: &mut impl EntryWriter<'a>) {}
+
+        fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+            self.trace_id
+                .iter()
+                .map(|id| (Cow::Borrowed("TraceId"), Cow::Owned(id.clone())))
+        }
+    }
+
+    fn span(trace_id: usize) -> Span {
+        Span {
+            trace_id: Some(format!("trace-{trace_id}")),
+        }
+    }
+
+    fn sampler(
+        rate: f32,
+    ) -> ConsistentHashSample<TracesFormat, impl FnMut(&SampleGroupMap) -> Option<&str>, StdRng>
+    {
+        ConsistentHashSample::with_rng(
+            TracesFormat::default(),
+            |group| group.get("TraceId"),
+            rate,
+            StdRng::seed_from_u64(0),
+        )
+    }
+
+    #[test]
+    fn hash_is_stable() {
+        assert_eq!(hash(b""), 0xefd01f60ba992926);
+        assert_eq!(hash(b"trace-1"), 0xdad57d1ab89e1bd4);
+    }
+
+    #[test]
+    fn samples_whole_traces() {
+        let mut sample = sampler(0.25);
+        for trace_id in 0..1000 {
+            for _ in 0..3 {
+                sample.format(&span(trace_id), &mut io::sink()).unwrap();
+            }
+        }
+        let written = &sample.format_mut().written;
+        // every trace is written either 3 times or not at all
+        assert_eq!(written.len() % 3, 0);
+        for spans in written.chunks(3) {
+            assert!(spans.iter().all(|span| *span == spans[0]));
+            assert_eq!(spans[0].1, 0.25);
+        }
+        let traces = written.len() / 3;
+        assert!((200..300).contains(&traces), "{traces}");
+
+        // another sampler makes the same decisions
+        let mut other = sampler(0.25);
+        for trace_id in 0..1000 {
+            for _ in 0..3 {
+                other.format(&span(trace_id), &mut io::sink()).unwrap();
+            }
+        }
+        assert_eq!(other.format_mut().written, sample.format_mut().written);
+    }
+
+    #[test]
+    fn higher_rates_keep_a_superset() {
+        let mut low = sampler(0.1);
+        let mut high = sampler(0.5);
+        for trace_id in 0..1000 {
+            low.format(&span(trace_id), &mut io::sink()).unwrap();
+            high.format(&span(trace_id), &mut io::sink()).unwrap();
+        }
+        let high_traces: Vec<_> = high.format_mut().written.iter().map(|w| &w.0).collect();
+        for (trace_id, _) in &low.format_mut().written {
+            assert!(high_traces.contains(&trace_id));
+        }
+    }
+
+    #[test]
+    fn samples_entries_without_key_at_random() {
+        let mut sample = sampler(0.5);
+        for _ in 0..1000 {
+            sample
+                .format(&Span { trace_id: None }, &mut io::sink())
+                .unwrap();
+        }
+        let written = sample.format_mut().written.len();
+        assert!((400..600).contains(&written), "{written}");
+    }
+
+    #[test]
+    fn never_samples_high_priority_entries() {
+        let mut sample = sampler(0.0001);
+        for _ in 0..100 {
+            sample
+                .format(&HighPriority::new(), &mut io::sink())
+                .unwrap();
+        }
+        let written = &sample.format_mut().written;
+        assert_eq!(written.len(), 100);
+        assert!(written.iter().all(|(_, rate)| *rate == 1.0));
+    }
+
+    #[test]
+    fn full_rate_keeps_everything() {
+        let mut sample = sampler(1.0);
+        for trace_id in 0..100 {
+            sample.format(&span(trace_id), &mut io::sink()).unwrap();
+        }
+        assert_eq!(sample.format_mut().written.len(), 100);
+    }
+}
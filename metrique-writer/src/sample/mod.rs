@@ -16,6 +16,14 @@
 //! 2. [CongressSample], which maintains a bounded rate of metric emission,
 //!    and also tries to ensure that a reasonable amount of entries for
 //!    every [sample group] is sampled.
+//! 3. [ConsistentHashSample], which samples metrics by a fixed fraction, deciding by the hash
+//!    of a key from the [sample group] so that related entries (e.g. of the same trace) are
+//!    sampled together.
+//! 4. [TailSample], which picks the sample rate of each complete entry by rules on its values,
+//!    e.g. to keep every failed request and a fraction of the rest.
+//!
+//! Entries marked [`HighPriority`] are never sampled out by these samplers, and are
+//! emitted with a sample rate of 1.
 //!
 //! [sample group]: Entry::sample_group
 //!
@@ -23,13 +31,25 @@
 
 use std::{io, marker::PhantomData, time::Duration};
 
-use metrique_writer_core::{Entry, IoStreamError, format::Format};
-use rand::{Rng, RngCore, rngs::ThreadRng};
+use metrique_writer_core::{
+    Entry, IoStreamError,
+    config::HighPriority,
+    entry::SampleGroupMap,
+    format::{Format, FormatOutcome},
+};
+use rand::{
+    Rng, RngCore, SeedableRng,
+    rngs::{StdRng, ThreadRng},
+};
 
 pub use metrique_writer_core::sample::SampledFormat;
 
 mod congress;
-pub use congress::{CongressSample, CongressSampleBuilder};
+pub use congress::{CongressSample, CongressSampleBuilder, SampleRatesHandle};
+mod consistent_hash;
+pub use consistent_hash::ConsistentHashSample;
+mod tail;
+pub use tail::{TailSample, TailSampleBuilder};
 
 /// Utility wrapper to impl [`RngCore`] from a stateless random number generator that impls [`Default`], like
 /// [`ThreadRng`].
@@ -67,6 +87,40 @@ pub trait SampledFormatExt: SampledFormat {
         FixedFractionSample::new(self, sample_rate)
     }
 
+    /// Discard all but `sample_rate` fraction of entries, deciding by the hash of a key that
+    /// `key` extracts from the entry's [sample group](Entry::sample_group) rather than at random.
+    ///
+    /// Entries with the same key are all written or all discarded, including in other processes
+    /// using the same rate, so e.g. keying on a trace id keeps whole traces. A key that is kept
+    /// at some rate is also kept at every higher rate. Entries without a key are sampled at
+    /// random. See [`ConsistentHashSample`].
+    ///
+    /// ```
+    /// # use metrique_writer::{Entry, FormatExt as _};
+    /// # use metrique_writer::sample::SampledFormatExt as _;
+    /// # use metrique_writer_format_emf::Emf;
+    /// # use std::io;
+    /// #[derive(Entry)]
+    /// #[entry(rename_all = "PascalCase")]
+    /// struct SpanMetrics {
+    ///     #[entry(sample_group)]
+    ///     trace_id: u64,
+    ///     latency_ms: u64,
+    /// }
+    ///
+    /// let stream = Emf::all_validations("MyApp".into(), vec![vec![]])
+    ///     .with_sampling()
+    ///     .sample_by_consistent_hash(|group| group.get("TraceId"), 0.1)
+    ///     .output_to(io::sink());
+    /// ```
+    fn sample_by_consistent_hash<K>(self, key: K, sample_rate: f32) -> ConsistentHashSample<Self, K>
+    where
+        Self: Sized,
+        K: FnMut(&SampleGroupMap) -> Option<&str>,
+    {
+        ConsistentHashSample::new(self, key, sample_rate)
+    }
+
     /// Tries to write at most *n* entries per second and uses a
     /// [congressional sampling strategy](https://dl.acm.org/doi/abs/10.1145/335191.335450) to boost the accuracy of
     /// low-frequency events.
@@ -106,6 +160,52 @@ impl<F> FixedFractionSample<F> {
     pub fn new(format: F, rate: f32) -> Self {
         Self::with_rng(format, rate, Default::default())
     }
+}
+
+impl<F> FixedFractionSample<F, StdRng> {
+    /// Like [`FixedFractionSample::new`], but uses a random number generator seeded with `seed`,
+    /// so the same entries are sampled every time. This is mostly useful for tests that assert on
+    /// sampled output.
+    ///
+    /// The RNG is currently ChaCha-based. Which entries a given seed samples is only reproducible
+    /// with the same version of this crate, and may change in any release.
+    ///
+    /// ```
+    /// # use metrique_writer::{Entry, EntryIoStream, FormatExt as _};
+    /// # use metrique_writer::sample::FixedFractionSample;
+    /// # use metrique_writer_format_emf::Emf;
+    /// # use std::time::SystemTime;
+    /// #[derive(Entry)]
+    /// struct MyMetrics {
+    ///     #[entry(timestamp)]
+    ///     start: SystemTime,
+    ///     count: u64,
+    /// }
+    ///
+    /// let sampled_output = |seed| {
+    ///     let mut output = vec![];
+    ///     let format = Emf::all_validations("MyApp".into(), vec![vec![]]).with_sampling();
+    ///     let mut stream = FixedFractionSample::with_seed(format, 0.5, seed).output_to(&mut output);
+    ///     for count in 0..10 {
+    ///         let start = SystemTime::UNIX_EPOCH; // use SystemTime::now() in the real world
+    ///         stream.next(&MyMetrics { start, count }).unwrap();
+    ///     }
+    ///     drop(stream);
+    ///     output
+    /// };
+    /// assert_eq!(sampled_output(42), sampled_output(42));
+    /// ```
+    pub fn with_seed(format: F, rate: f32, seed: u64) -> Self {
+        Self::with_rng(format, rate, StdRng::seed_from_u64(seed))
+    }
+}
+
+impl<F, R> FixedFractionSample<F, R> {
+    /// Like [`FixedFractionSample::new`], but also specify the random number generator.
+    pub fn with_rng(format: F, rate: f32, rng: R) -> Self {
+        assert!(rate.is_finite() && 0.0 < rate && rate <= 1.0);
+        Self { format, rate, rng }
+    }
 
     /// Return a mutable reference to the inner [`Format`].
     ///
@@ -194,11 +294,16 @@ impl<F> FixedFractionSample<F> {
     }
 }
 
-impl<F, R> FixedFractionSample<F, R> {
-    /// Like [`FixedFractionSample::new`], but also specify the random number generator.
-    pub fn with_rng(format: F, rate: f32, rng: R) -> Self {
-        assert!(rate.is_finite() && 0.0 < rate && rate <= 1.0);
-        Self { format, rate, rng }
+impl<F, R: RngCore> FixedFractionSample<F, R> {
+    /// Return the sample rate to write `entry` with, or `None` to drop it
+    fn sample(&mut self, entry: &impl Entry) -> Option<f32> {
+        if HighPriority::is_set(entry) {
+            Some(1.0)
+        } else if self.rng.random::<f32>() <= self.rate {
+            Some(self.rate)
+        } else {
+            None
+        }
     }
 }
 
@@ -208,11 +313,157 @@ impl<F: SampledFormat, R: RngCore> Format for FixedFractionSample<F, R> {
         entry: &impl Entry,
         output: &mut impl io::Write,
     ) -> Result<(), IoStreamError> {
-        if self.rng.random::<f32>() <= self.rate {
-            self.format
-                .format_with_sample_rate(entry, output, self.rate)
-        } else {
+        match self.sample(entry) {
+            Some(rate) => self.format.format_with_sample_rate(entry, output, rate),
+            None => Ok(()),
+        }
+    }
+
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        match self.sample(entry) {
+            Some(rate) => self
+                .format
+                .format_with_sample_rate_counted(entry, output, rate)
+                .map(FormatOutcome::Emitted),
+            None => Ok(FormatOutcome::Dropped),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, mem};
+
+    use metrique_writer_core::{
+        Entry, EntryWriter, IoStreamError,
+        config::HighPriority,
+        format::{Format, FormatOutcome},
+        test_stream::DummyEntryWriter,
+    };
+
+    use super::{FixedFractionSample, SampledFormat};
+
+    /// Records the index of every written [`IndexEntry`]
+    #[derive(Default)]
+    struct IndexFormat {
+        indices: Vec<u32>,
+    }
+
+    impl Format for IndexFormat {
+        fn format(
+            &mut self,
+            _entry: &impl Entry,
+            _output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            unreachable!("should be using sampled format fns")
+        }
+    }
+
+    impl SampledFormat for IndexFormat {
+        fn format_with_sample_rate(
+            &mut self,
+            entry: &impl Entry,
+            _output: &mut impl io::Write,
+            _rate: f32,
+        ) -> Result<(), IoStreamError> {
+            let mut writer = DummyEntryWriter::default();
+            entry.write(&mut writer);
+            self.indices.push(writer.0[0].1.parse().unwrap());
+            Ok(())
+        }
+    }
+
+    struct IndexEntry(u32);
+
+    impl Entry for IndexEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value("Index", &self.0.to_string());
+        }
+    }
+
+    #[derive(Default)]
+    struct RatesFormat {
+        rates: Vec<f32>,
+    }
+
+    impl Format for RatesFormat {
+        fn format(
+            &mut self,
+            _entry: &impl Entry,
+            _output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            unreachable!("should be using sampled format fns")
+        }
+    }
+
+    impl SampledFormat for RatesFormat {
+        fn format_with_sample_rate(
+            &mut self,
+            _entry: &impl Entry,
+            _output: &mut impl io::Write,
+            rate: f32,
+        ) -> Result<(), IoStreamError> {
+            self.rates.push(rate);
             Ok(())
         }
     }
+
+    struct PlainEntry;
+
+    impl Entry for PlainEntry {
+        fn write<'a>(&'a self, _writer: &mut impl EntryWriter<'a>) {}
+    }
+
+    #[test]
+    fn fixed_fraction_never_samples_high_priority_entries() {
+        let mut sample = FixedFractionSample::new(RatesFormat::default(), 0.0001);
+        for _ in 0..100 {
+            sample.format(&PlainEntry, &mut io::sink()).unwrap();
+            sample
+                .format(&HighPriority::new(), &mut io::sink())
+                .unwrap();
+        }
+        let rates = &sample.format_mut().rates;
+        // all 100 priority entries, plus an expected 0.01 plain entries
+        assert!((100..=101).contains(&rates.len()));
+        assert_eq!(rates.iter().filter(|&&rate| rate == 1.0).count(), 100);
+    }
+
+    #[test]
+    fn fixed_fraction_with_seed_is_reproducible() {
+        let sampled = |seed| {
+            let mut sample = FixedFractionSample::with_seed(IndexFormat::default(), 0.5, seed);
+            for index in 0..1000 {
+                sample.format(&IndexEntry(index), &mut io::sink()).unwrap();
+            }
+            mem::take(&mut sample.format_mut().indices)
+        };
+        let first = sampled(7);
+        assert!((400..600).contains(&first.len()), "{}", first.len());
+        assert_eq!(sampled(7), first);
+        assert_ne!(sampled(8), first);
+    }
+
+    #[test]
+    fn fixed_fraction_reports_dropped_entries() {
+        let mut sample = FixedFractionSample::with_seed(IndexFormat::default(), 0.5, 7);
+        let mut dropped = vec![];
+        for index in 0..1000 {
+            match sample
+                .format_outcome(&IndexEntry(index), &mut io::sink())
+                .unwrap()
+            {
+                FormatOutcome::Emitted(_) => {}
+                FormatOutcome::Dropped => dropped.push(index),
+                _ => unreachable!(),
+            }
+        }
+        let emitted = &sample.format_mut().indices;
+        assert_eq!(emitted.len() + dropped.len(), 1000);
+        assert!(emitted.iter().all(|index| !dropped.contains(index)));
+    }
 }
@@ -0,0 +1,460 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{any::Any, borrow::Cow, io, time::SystemTime};
+
+use metrique_writer_core::{
+    Entry, EntryConfig, EntryWriter, IoStreamError, MetricFlags, Observation, Unit,
+    ValidationError, Value, ValueWriter,
+    config::HighPriority,
+    format::{Format, FormatOutcome},
+};
+use rand::{Rng, RngCore, rngs::ThreadRng};
+use smallvec::SmallVec;
+
+use super::{DefaultRng, SampledFormat};
+
+/// A builder for [TailSample]
+///
+/// Rules are evaluated in the order they were added, and the first rule that matches an entry
+/// decides its sample rate. Entries that match no rule are sampled at the default rate.
+///
+/// ```
+/// # use metrique_writer::{Entry, EntryIoStream, FormatExt as _};
+/// # use metrique_writer::sample::TailSampleBuilder;
+/// # use metrique_writer_format_emf::Emf;
+/// # use std::io;
+/// #[derive(Entry)]
+/// #[entry(rename_all = "PascalCase")]
+/// struct RequestMetrics {
+///     error: bool,
+///     latency_ms: u64,
+/// }
+///
+/// let format = TailSampleBuilder::new(0.01)
+///     .when_truthy("Error", 1.0)
+///     .when_above("LatencyMs", 500.0, 0.5)
+///     .build(Emf::all_validations("MyApp".into(), vec![vec![]]).with_sampling());
+/// let mut stream = format.output_to(io::sink());
+/// stream.next(&RequestMetrics { error: true, latency_ms: 12 }).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct TailSampleBuilder {
+    rules: Vec<Rule>,
+    default_rate: f32,
+}
+
+impl TailSampleBuilder {
+    /// Create a builder that samples the entries that match no rule at `default_rate`.
+    ///
+    /// # Panics
+    /// Panics if `default_rate` is not in `(0, 1]`.
+    pub fn new(default_rate: f32) -> Self {
+        Self {
+            rules: vec![],
+            default_rate: check_rate(default_rate),
+        }
+    }
+
+    /// Sample entries at `rate` if their `field` is truthy: a metric with a non-zero observation
+    /// (including a `true` bool), or a string other than `""`, `"false"`, or `"0"`.
+    ///
+    /// # Panics
+    /// Panics if `rate` is not in `(0, 1]`.
+    pub fn when_truthy(self, field: impl Into<Cow<'static, str>>, rate: f32) -> Self {
+        self.rule(field, Condition::Truthy, rate)
+    }
+
+    /// Sample entries at `rate` if their `field` is a metric with an observation greater than
+    /// `threshold`. Repeated observations count as their mean. The threshold is in the unit the
+    /// metric is written in, e.g. milliseconds for a `Duration` field.
+    ///
+    /// # Panics
+    /// Panics if `rate` is not in `(0, 1]`.
+    pub fn when_above(
+        self,
+        field: impl Into<Cow<'static, str>>,
+        threshold: f64,
+        rate: f32,
+    ) -> Self {
+        self.rule(field, Condition::Above(threshold), rate)
+    }
+
+    /// Sample entries at `rate` if their `field` is the string `value`.
+    ///
+    /// # Panics
+    /// Panics if `rate` is not in `(0, 1]`.
+    pub fn when_equals(
+        self,
+        field: impl Into<Cow<'static, str>>,
+        value: impl Into<Cow<'static, str>>,
+        rate: f32,
+    ) -> Self {
+        self.rule(field, Condition::Equals(value.into()), rate)
+    }
+
+    fn rule(
+        mut self,
+        field: impl Into<Cow<'static, str>>,
+        condition: Condition,
+        rate: f32,
+    ) -> Self {
+        self.rules.push(Rule {
+            field: field.into(),
+            condition,
+            rate: check_rate(rate),
+        });
+        self
+    }
+
+    /// Wrap the given [`Format`] that supports sampling with the rule-based sampling behavior,
+    /// using the [DefaultRng].
+    ///
+    /// [`Format`]: crate::format::Format
+    pub fn build<F>(self, format: F) -> TailSample<F> {
+        self.build_with_rng(format, Default::default())
+    }
+
+    /// Like [`TailSampleBuilder::build`], but also specify the random number generator.
+    pub fn build_with_rng<F, R>(self, format: F, rng: R) -> TailSample<F, R> {
+        TailSample {
+            format,
+            rng,
+            rules: self.rules,
+            default_rate: self.default_rate,
+        }
+    }
+}
+
+fn check_rate(rate: f32) -> f32 {
+    assert!(
+        rate.is_finite() && 0.0 < rate && rate <= 1.0,
+        "sample rate must be in (0, 1], got {rate}"
+    );
+    rate
+}
+
+#[derive(Clone, Debug)]
+struct Rule {
+    field: Cow<'static, str>,
+    condition: Condition,
+    rate: f32,
+}
+
+#[derive(Clone, Debug)]
+enum Condition {
+    Truthy,
+    Above(f64),
+    Equals(Cow<'static, str>),
+}
+
+impl Condition {
+    fn matches_string(&self, value: &str) -> bool {
+        match self {
+            Self::Truthy => !matches!(value, "" | "false" | "0"),
+            Self::Above(_) => false,
+            Self::Equals(expected) => value == expected,
+        }
+    }
+
+    fn matches_observation(&self, observation: Observation) -> bool {
+        let value = match observation {
+            Observation::Unsigned(value) => value as f64,
+            Observation::Floating(value) => value,
+            Observation::Repeated { occurrences: 0, .. } => return false,
+            Observation::Repeated { total, occurrences } => total / occurrences as f64,
+            _ => return false,
+        };
+        match self {
+            Self::Truthy => value != 0.0,
+            Self::Above(threshold) => value > *threshold,
+            Self::Equals(_) => false,
+        }
+    }
+}
+
+/// Samples entries at a rate picked by rules on the values of the complete entry, e.g. to keep
+/// every failed or slow request and a small fraction of the rest. See [`TailSampleBuilder`].
+///
+/// Before formatting, every entry is written once to a lightweight [`EntryWriter`] that only
+/// looks at the fields named by rules, so the cost of deciding is a walk over the entry plus a
+/// name comparison per field and rule. Entries marked [`HighPriority`] are always written with a
+/// sample rate of 1.
+pub struct TailSample<F, R = DefaultRng<ThreadRng>> {
+    format: F,
+    rng: R,
+    rules: Vec<Rule>,
+    default_rate: f32,
+}
+
+impl<F, R> TailSample<F, R> {
+    /// Return a mutable reference to the inner [`Format`].
+    ///
+    /// [`Format`]: crate::format::Format
+    pub fn format_mut(&mut self) -> &mut F {
+        &mut self.format
+    }
+
+    /// Return the sample rate of `entry`, per the first rule it matches
+    fn sample_rate(&self, entry: &impl Entry) -> f32 {
+        let mut matcher = RuleMatcher {
+            rules: &self.rules,
+            matched: SmallVec::from_elem(false, self.rules.len()),
+            high_priority: false,
+        };
+        entry.write(&mut matcher);
+        if matcher.high_priority {
+            return 1.0;
+        }
+        matcher
+            .matched
+            .iter()
+            .position(|&matched| matched)
+            .map_or(self.default_rate, |index| self.rules[index].rate)
+    }
+}
+
+impl<F, R: RngCore> TailSample<F, R> {
+    /// Return the sample rate to write `entry` with, or `None` to drop it
+    fn sample(&mut self, entry: &impl Entry) -> Option<f32> {
+        let rate = self.sample_rate(entry);
+        (rate == 1.0 || self.rng.random::<f32>() <= rate).then_some(rate)
+    }
+}
+
+impl<F: SampledFormat, R: RngCore> Format for TailSample<F, R> {
+    fn format(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<(), IoStreamError> {
+        match self.sample(entry) {
+            Some(rate) => self.format.format_with_sample_rate(entry, output, rate),
+            None => Ok(()),
+        }
+    }
+
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        match self.sample(entry) {
+            Some(rate) => self
+                .format
+                .format_with_sample_rate_counted(entry, output, rate)
+                .map(FormatOutcome::Emitted),
+            None => Ok(FormatOutcome::Dropped),
+        }
+    }
+}
+
+/// Records which rules an entry matches, without keeping any of its values
+struct RuleMatcher<'r> {
+    rules: &'r [Rule],
+    matched: SmallVec<[bool; 4]>,
+    high_priority: bool,
+}
+
+impl<'a> EntryWriter<'a> for RuleMatcher<'_> {
+    fn timestamp(&mut self, _timestamp: SystemTime) {}
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        let name = name.into();
+        for (rule, matched) in self.rules.iter().zip(&mut self.matched) {
+            if !*matched && rule.field == name {
+                value.write(ConditionWriter {
+                    condition: &rule.condition,
+                    matched,
+                });
+            }
+        }
+    }
+
+    fn config(&mut self, config: &'a dyn EntryConfig) {
+        self.high_priority |= (config as &dyn Any).is::<HighPriority>();
+    }
+}
+
+struct ConditionWriter<'m> {
+    condition: &'m Condition,
+    matched: &'m mut bool,
+}
+
+impl ValueWriter for ConditionWriter<'_> {
+    fn string(self, value: &str) {
+        *self.matched = self.condition.matches_string(value);
+    }
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        _unit: Unit,
+        _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        _flags: MetricFlags<'_>,
+    ) {
+        *self.matched = distribution
+            .into_iter()
+            .any(|observation| self.condition.matches_observation(observation));
+    }
+
+    fn error(self, _error: ValidationError) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io, time::Duration};
+
+    use metrique_writer_core::{
+        Entry, EntryWriter, IoStreamError, config::HighPriority, format::Format,
+    };
+    use rand::{SeedableRng, rngs::StdRng};
+
+    use super::{SampledFormat, TailSample, TailSampleBuilder};
+
+    /// Records the sample rate of every written entry
+    #[derive(Default)]
+    struct RatesFormat {
+        rates: Vec<f32>,
+    }
+
+    impl Format for RatesFormat {
+        fn format(
+            &mut self,
+            _entry: &impl Entry,
+            _output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            unreachable!("should be using sampled format fns")
+        }
+    }
+
+    impl SampledFormat for RatesFormat {
+        fn format_with_sample_rate(
+            &mut self,
+            _entry: &impl Entry,
+            _output: &mut impl io::Write,
+            rate: f32,
+        ) -> Result<(), IoStreamError> {
+            self.rates.push(rate);
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Request {
+        operation: &'static str,
+        error: bool,
+        latency: Duration,
+        retries: Option<&'static str>,
+        high_priority: bool,
+    }
+
+    impl Entry for Request {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value("Operation", self.operation);
+            writer.value("Error", &self.error);
+            writer.value("Latency", &self.latency);
+            writer.value("Retries", &self.retries);
+            if self.high_priority {
+                writer.config(const { &HighPriority::new() });
+            }
+        }
+    }
+
+    fn sampler() -> TailSample<RatesFormat, StdRng> {
+        TailSampleBuilder::new(0.01)
+            .when_truthy("Error", 1.0)
+            .when_equals("Operation", "Delete", 1.0)
+            .when_above("Latency", 500.0, 0.5)
+            .when_truthy("Retries", 0.25)
+            .build_with_rng(RatesFormat::default(), StdRng::seed_from_u64(0))
+    }
+
+    #[test]
+    fn first_matching_rule_decides() {
+        let sample = sampler();
+        let rate = |request: Request| sample.sample_rate(&request);
+
+        assert_eq!(rate(Request::default()), 0.01);
+        assert_eq!(
+            rate(Request {
+                error: true,
+                ..Default::default()
+            }),
+            1.0
+        );
+        assert_eq!(
+            rate(Request {
+                operation: "Delete",
+                ..Default::default()
+            }),
+            1.0
+        );
+        assert_eq!(
+            rate(Request {
+                latency: Duration::from_millis(501),
+                ..Default::default()
+            }),
+            0.5
+        );
+        assert_eq!(
+            rate(Request {
+                latency: Duration::from_millis(500),
+                ..Default::default()
+            }),
+            0.01
+        );
+        // the error rule comes first
+        assert_eq!(
+            rate(Request {
+                error: true,
+                latency: Duration::from_secs(1),
+                ..Default::default()
+            }),
+            1.0
+        );
+        assert_eq!(
+            rate(Request {
+                retries: Some("2"),
+                ..Default::default()
+            }),
+            0.25
+        );
+        assert_eq!(
+            rate(Request {
+                retries: Some("0"),
+                ..Default::default()
+            }),
+            0.01
+        );
+        assert_eq!(
+            rate(Request {
+                high_priority: true,
+                ..Default::default()
+            }),
+            1.0
+        );
+    }
+
+    #[test]
+    fn writes_with_the_chosen_rate() {
+        let mut sample = sampler();
+        for i in 0..1000 {
+            let request = Request {
+                error: i % 10 == 0,
+                ..Default::default()
+            };
+            sample.format(&request, &mut io::sink()).unwrap();
+        }
+        let rates = &sample.format_mut().rates;
+        assert_eq!(rates.iter().filter(|&&rate| rate == 1.0).count(), 100);
+        let sampled = rates.iter().filter(|&&rate| rate == 0.01).count();
+        assert!(sampled < 30, "{sampled}");
+    }
+
+    #[test]
+    #[should_panic(expected = "sample rate must be in (0, 1], got 1.5")]
+    fn rejects_invalid_rates() {
+        let _ = TailSampleBuilder::new(0.01).when_truthy("Error", 1.5);
+    }
+}
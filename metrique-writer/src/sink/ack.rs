@@ -0,0 +1,423 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    borrow::Cow,
+    collections::VecDeque,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use metrique_writer_core::{Entry, EntrySink, sink::FlushWait};
+
+/// A destination that acknowledges every entry delivered to it, used by [`AckSink`].
+///
+/// Unlike an [`EntrySink`], delivery takes the entry by reference and reports whether the
+/// downstream accepted it, so that [`AckSink`] can retry it.
+pub trait AckEntrySink<E> {
+    /// Deliver `entry`, returning `Ok(())` once the downstream acknowledged it.
+    ///
+    /// A [transient](AckError::transient) error causes the entry to be retried, while a
+    /// [permanent](AckError::permanent) error causes it to be given up on.
+    fn try_append(&self, entry: &E) -> Result<(), AckError>;
+
+    /// Request the downstream to flush. Defaults to a [`FlushWait`] that is ready immediately.
+    fn flush_async(&self) -> FlushWait {
+        FlushWait::ready()
+    }
+}
+
+/// The error returned by [`AckEntrySink::try_append`] when the downstream did not acknowledge
+/// an entry.
+#[derive(Clone, Debug)]
+pub struct AckError {
+    message: Cow<'static, str>,
+    transient: bool,
+}
+
+impl AckError {
+    /// An error that may go away on retry, e.g. a timeout or throttling
+    pub fn transient(message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            message: message.into(),
+            transient: true,
+        }
+    }
+
+    /// An error that won't go away on retry, e.g. an entry the downstream can't accept
+    pub fn permanent(message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            message: message.into(),
+            transient: false,
+        }
+    }
+
+    /// Returns whether the entry should be retried
+    pub fn is_transient(&self) -> bool {
+        self.transient
+    }
+}
+
+impl fmt::Display for AckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = if self.transient {
+            "transient"
+        } else {
+            "permanent"
+        };
+        write!(f, "{kind} delivery error: {}", self.message)
+    }
+}
+
+impl std::error::Error for AckError {}
+
+/// Why [`AckSink`] gave up on an entry
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum AckFailure {
+    /// The downstream returned a [permanent](AckError::permanent) error
+    Rejected(AckError),
+    /// The entry was dropped to keep the number of unacknowledged entries within bounds. The
+    /// error is the last transient error returned for the entry.
+    BufferFull(AckError),
+}
+
+type FailureCallback<E> = dyn Fn(E, AckFailure) + Send + Sync;
+
+/// An [`EntrySink`] that delivers entries to an [`AckEntrySink`] at least once, retrying
+/// entries until the downstream acknowledges them.
+///
+/// Entries are delivered in order. When the downstream returns a transient error, the entry and
+/// all entries appended after it are kept in memory, and delivery is retried on the next
+/// [`append`](EntrySink::append) or [`flush_async`](EntrySink::flush_async). At most
+/// `max_pending` entries are kept: when there are more, the oldest ones are given up on. This
+/// gives durability against transient downstream failures, but not against process crashes.
+///
+/// Entries that are given up on, either because the downstream returned a permanent error or
+/// because the buffer was full, are passed to the callback set with
+/// [`on_failure`](AckSink::on_failure), e.g. to log them or write them to a dead-letter
+/// destination. By default, they are dropped with a `tracing` error.
+///
+/// Delivery happens on the appending thread, while holding a lock that serializes appends, so
+/// this is meant for low volume, critical entries.
+///
+/// # Example
+/// ```
+/// # use std::sync::{Arc, Mutex};
+/// # use metrique_writer::{Entry, EntrySink};
+/// use metrique_writer::sink::{AckEntrySink, AckError, AckSink};
+///
+/// #[derive(Entry)]
+/// struct Breadcrumb {
+///     step: u64,
+/// }
+///
+/// // a downstream that is unavailable until told otherwise
+/// #[derive(Default)]
+/// struct Downstream {
+///     available: Mutex<bool>,
+///     received: Mutex<Vec<u64>>,
+/// }
+///
+/// impl AckEntrySink<Breadcrumb> for Arc<Downstream> {
+///     fn try_append(&self, entry: &Breadcrumb) -> Result<(), AckError> {
+///         if !*self.available.lock().unwrap() {
+///             return Err(AckError::transient("unavailable"));
+///         }
+///         self.received.lock().unwrap().push(entry.step);
+///         Ok(())
+///     }
+/// }
+///
+/// let downstream = Arc::new(Downstream::default());
+/// let sink = AckSink::new(downstream.clone(), 100);
+/// sink.append(Breadcrumb { step: 1 });
+/// assert_eq!(sink.pending(), 1);
+///
+/// *downstream.available.lock().unwrap() = true;
+/// futures::executor::block_on(sink.flush_async());
+/// assert_eq!(*downstream.received.lock().unwrap(), [1]);
+/// assert_eq!(sink.pending(), 0);
+/// ```
+pub struct AckSink<S, E> {
+    inner: Arc<AckSinkInner<S, E>>,
+}
+
+struct AckSinkInner<S, E> {
+    sink: S,
+    max_pending: usize,
+    // entries that were not acknowledged yet, along with the last error returned for them
+    pending: Mutex<VecDeque<(E, Option<AckError>)>>,
+    on_failure: Box<FailureCallback<E>>,
+}
+
+impl<S, E> AckSink<S, E> {
+    /// Wrap `sink`, keeping at most `max_pending` unacknowledged entries in memory.
+    ///
+    /// # Panics
+    /// Panics if `max_pending` is 0.
+    pub fn new(sink: S, max_pending: usize) -> Self {
+        Self::with_failure_callback(
+            sink,
+            max_pending,
+            Box::new(|_, failure| {
+                tracing::error!(?failure, "giving up on delivering a metric entry");
+            }),
+        )
+    }
+
+    /// Set the callback called with the entries that are given up on.
+    ///
+    /// The callback is called on the appending thread, after the lock serializing appends has
+    /// been released, so it may append to this sink.
+    ///
+    /// # Panics
+    /// Panics if this sink has already been cloned.
+    pub fn on_failure(self, on_failure: impl Fn(E, AckFailure) + Send + Sync + 'static) -> Self {
+        let inner = Arc::into_inner(self.inner)
+            .expect("on_failure must be called before the AckSink is cloned");
+        Self::with_failure_callback(inner.sink, inner.max_pending, Box::new(on_failure))
+    }
+
+    fn with_failure_callback(
+        sink: S,
+        max_pending: usize,
+        on_failure: Box<FailureCallback<E>>,
+    ) -> Self {
+        assert!(max_pending > 0, "max_pending must be positive");
+        Self {
+            inner: Arc::new(AckSinkInner {
+                sink,
+                max_pending,
+                pending: Mutex::new(VecDeque::new()),
+                on_failure,
+            }),
+        }
+    }
+
+    /// Returns the number of entries that were not acknowledged yet
+    pub fn pending(&self) -> usize {
+        self.inner.pending.lock().unwrap().len()
+    }
+}
+
+impl<S: AckEntrySink<E>, E> AckSink<S, E> {
+    /// Deliver pending entries in order, stopping at the first transient error. Entries that are
+    /// given up on are returned, so the callback is called without holding the lock.
+    fn deliver(&self, pending: &mut VecDeque<(E, Option<AckError>)>) -> Vec<(E, AckFailure)> {
+        let mut failed = vec![];
+        while let Some((entry, last_error)) = pending.front_mut() {
+            match self.inner.sink.try_append(entry) {
+                Ok(()) => {
+                    pending.pop_front();
+                }
+                Err(error) if error.is_transient() => {
+                    *last_error = Some(error);
+                    break;
+                }
+                Err(error) => {
+                    let (entry, _) = pending.pop_front().unwrap();
+                    failed.push((entry, AckFailure::Rejected(error)));
+                }
+            }
+        }
+        while pending.len() > self.inner.max_pending {
+            let (entry, last_error) = pending.pop_front().unwrap();
+            let error = last_error.unwrap_or_else(|| AckError::transient("not delivered yet"));
+            failed.push((entry, AckFailure::BufferFull(error)));
+        }
+        failed
+    }
+
+    fn retry_pending(&self) {
+        let failed = {
+            let mut pending = self.inner.pending.lock().unwrap();
+            self.deliver(&mut pending)
+        };
+        for (entry, failure) in failed {
+            (self.inner.on_failure)(entry, failure);
+        }
+    }
+}
+
+impl<S, E> Clone for AckSink<S, E> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<S, E> fmt::Debug for AckSink<S, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AckSink")
+            .field("max_pending", &self.inner.max_pending)
+            .field("pending", &self.pending())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<E: Entry, S: AckEntrySink<E>> EntrySink<E> for AckSink<S, E> {
+    fn append(&self, entry: E) {
+        let failed = {
+            let mut pending = self.inner.pending.lock().unwrap();
+            pending.push_back((entry, None));
+            self.deliver(&mut pending)
+        };
+        for (entry, failure) in failed {
+            (self.inner.on_failure)(entry, failure);
+        }
+    }
+
+    /// Retries delivering pending entries, then flushes the downstream.
+    ///
+    /// Entries that are still not acknowledged remain pending.
+    fn flush_async(&self) -> FlushWait {
+        self.retry_pending();
+        self.inner.sink.flush_async()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::VecDeque,
+        sync::{Arc, Mutex},
+    };
+
+    use metrique_writer_core::EntrySink;
+
+    use super::{AckEntrySink, AckError, AckFailure, AckSink};
+    use crate::Entry;
+
+    #[derive(Entry, Debug, PartialEq)]
+    struct TestEntry {
+        value: u64,
+    }
+
+    /// A downstream that returns scripted results, then acknowledges everything
+    #[derive(Clone, Default)]
+    struct ScriptedSink {
+        results: Arc<Mutex<VecDeque<Result<(), AckError>>>>,
+        received: Arc<Mutex<Vec<u64>>>,
+    }
+
+    impl ScriptedSink {
+        fn fail_next(&self, errors: impl IntoIterator<Item = AckError>) {
+            self.results
+                .lock()
+                .unwrap()
+                .extend(errors.into_iter().map(Err));
+        }
+
+        fn received(&self) -> Vec<u64> {
+            self.received.lock().unwrap().clone()
+        }
+    }
+
+    impl AckEntrySink<TestEntry> for ScriptedSink {
+        fn try_append(&self, entry: &TestEntry) -> Result<(), AckError> {
+            self.results.lock().unwrap().pop_front().unwrap_or(Ok(()))?;
+            self.received.lock().unwrap().push(entry.value);
+            Ok(())
+        }
+    }
+
+    type Failures = Arc<Mutex<Vec<(u64, AckFailure)>>>;
+
+    fn ack_sink(max_pending: usize) -> (AckSink<ScriptedSink, TestEntry>, ScriptedSink, Failures) {
+        let downstream = ScriptedSink::default();
+        let failures = Failures::default();
+        let sink = AckSink::new(downstream.clone(), max_pending).on_failure({
+            let failures = failures.clone();
+            move |entry: TestEntry, failure| failures.lock().unwrap().push((entry.value, failure))
+        });
+        (sink, downstream, failures)
+    }
+
+    #[test]
+    fn retries_transient_failures_in_order() {
+        let (sink, downstream, failures) = ack_sink(10);
+        downstream.fail_next([
+            AckError::transient("timeout"),
+            AckError::transient("timeout"),
+        ]);
+
+        sink.append(TestEntry { value: 1 });
+        assert_eq!(sink.pending(), 1);
+        // later entries wait behind the pending one, to keep the order
+        sink.append(TestEntry { value: 2 });
+        assert_eq!(sink.pending(), 2);
+        assert!(downstream.received().is_empty());
+
+        // the downstream recovered
+        futures::executor::block_on(sink.flush_async());
+        assert_eq!(sink.pending(), 0);
+        assert_eq!(downstream.received(), [1, 2]);
+        sink.append(TestEntry { value: 3 });
+        assert_eq!(downstream.received(), [1, 2, 3]);
+        assert!(failures.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn permanent_failures_are_reported() {
+        let (sink, downstream, failures) = ack_sink(10);
+        downstream.fail_next([AckError::permanent("too large")]);
+
+        sink.append(TestEntry { value: 1 });
+        sink.append(TestEntry { value: 2 });
+        assert_eq!(sink.pending(), 0);
+        assert_eq!(downstream.received(), [2]);
+
+        let failures = failures.lock().unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, 1);
+        assert!(matches!(&failures[0].1, AckFailure::Rejected(e) if !e.is_transient()));
+    }
+
+    #[test]
+    fn oldest_entries_are_dropped_when_buffer_is_full() {
+        let (sink, downstream, failures) = ack_sink(2);
+        downstream.fail_next((0..3).map(|_| AckError::transient("unavailable")));
+
+        for value in 1..=3 {
+            sink.append(TestEntry { value });
+        }
+        assert_eq!(sink.pending(), 2);
+        {
+            let failures = failures.lock().unwrap();
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].0, 1);
+            assert!(
+                matches!(&failures[0].1, AckFailure::BufferFull(e) if e.to_string() == "transient delivery error: unavailable")
+            );
+        }
+
+        futures::executor::block_on(sink.flush_async());
+        assert_eq!(downstream.received(), [2, 3]);
+        assert_eq!(sink.pending(), 0);
+    }
+
+    #[test]
+    fn failure_callback_can_append() {
+        let downstream = ScriptedSink::default();
+        downstream.fail_next([AckError::permanent("rejected")]);
+        let sink = Arc::new(Mutex::new(None::<AckSink<ScriptedSink, TestEntry>>));
+        let ack_sink = AckSink::new(downstream.clone(), 10).on_failure({
+            let sink = sink.clone();
+            move |entry: TestEntry, _| {
+                // retry once with a marker value, as a dead-letter destination would
+                if let Some(sink) = sink.lock().unwrap().as_ref() {
+                    sink.append(TestEntry {
+                        value: entry.value + 100,
+                    });
+                }
+            }
+        });
+        *sink.lock().unwrap() = Some(ack_sink.clone());
+
+        ack_sink.append(TestEntry { value: 1 });
+        assert_eq!(downstream.received(), [101]);
+        sink.lock().unwrap().take();
+    }
+}
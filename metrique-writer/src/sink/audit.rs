@@ -0,0 +1,213 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use metrique_writer_core::{
+    Entry, EntryConfig, EntrySink, EntryWriter, MetricFlags, Observation, Unit, ValidationError,
+    Value, ValueWriter, sink::FlushWait,
+};
+
+/// An [`EntrySink`] that counts how many times each field name is emitted, then appends the entry
+/// to the wrapped sink.
+///
+/// The counts can be read at runtime with [`AuditSink::snapshot`], e.g. from a debug endpoint, to
+/// find metrics that are never (or rarely) emitted and are candidates for removal.
+///
+/// A name is counted when its value actually writes something, so e.g. an `Option` field that is
+/// `None` is not counted. Timestamps are not counted. Names are counted as written by the entry,
+/// before any format-specific changes.
+///
+/// Counting requires writing every entry an extra time, so this has a cost similar to formatting
+/// the entry.
+///
+/// # Example
+/// ```
+/// # use metrique_writer::{Entry, EntrySink, sink::AuditSink, test_util::test_entry_sink};
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     operation: &'static str,
+///     retries: Option<u64>,
+/// }
+///
+/// let sink = AuditSink::new(test_entry_sink().sink);
+/// sink.append(RequestMetrics { operation: "Get", retries: None });
+/// sink.append(RequestMetrics { operation: "Put", retries: Some(1) });
+///
+/// let counts = sink.snapshot();
+/// assert_eq!(counts["operation"], 2);
+/// assert_eq!(counts["retries"], 1);
+/// ```
+#[derive(Clone, Debug)]
+pub struct AuditSink<S> {
+    sink: S,
+    counts: Arc<Mutex<HashMap<Cow<'static, str>, u64>>>,
+}
+
+impl<S> AuditSink<S> {
+    /// Wrap `sink`, counting the field names of every appended entry.
+    ///
+    /// Clones of the returned sink share the same counts.
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            counts: Default::default(),
+        }
+    }
+
+    /// Return the number of times each field name was emitted so far
+    pub fn snapshot(&self) -> HashMap<Cow<'static, str>, u64> {
+        self.counts.lock().unwrap().clone()
+    }
+
+    /// Reset all counts to zero, e.g. to start a new audit period
+    pub fn reset(&self) {
+        self.counts.lock().unwrap().clear();
+    }
+
+    fn record(&self, entry: &impl Entry) {
+        let mut counts = self.counts.lock().unwrap();
+        entry.write(&mut RecordingEntryWriter {
+            counts: &mut counts,
+        });
+    }
+}
+
+impl<E: Entry, S: EntrySink<E>> EntrySink<E> for AuditSink<S> {
+    fn append(&self, entry: E) {
+        self.record(&entry);
+        self.sink.append(entry);
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        self.sink.flush_async()
+    }
+}
+
+/// An [`EntryWriter`] that only records which names were written
+struct RecordingEntryWriter<'c> {
+    counts: &'c mut HashMap<Cow<'static, str>, u64>,
+}
+
+impl<'a> EntryWriter<'a> for RecordingEntryWriter<'_> {
+    fn timestamp(&mut self, _timestamp: SystemTime) {}
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        let mut written = false;
+        value.write(WrittenProbe(&mut written));
+        if !written {
+            return;
+        }
+        let name = name.into();
+        match self.counts.get_mut(&*name) {
+            Some(count) => *count += 1,
+            // only allocate the first time a borrowed name is seen
+            None => {
+                self.counts.insert(Cow::Owned(name.into_owned()), 1);
+            }
+        }
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {}
+}
+
+/// A [`ValueWriter`] that records whether the value wrote anything
+struct WrittenProbe<'w>(&'w mut bool);
+
+impl ValueWriter for WrittenProbe<'_> {
+    fn string(self, _value: &str) {
+        *self.0 = true;
+    }
+
+    fn metric<'a>(
+        self,
+        _distribution: impl IntoIterator<Item = Observation>,
+        _unit: Unit,
+        _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        _flags: MetricFlags<'_>,
+    ) {
+        *self.0 = true;
+    }
+
+    fn error(self, _error: ValidationError) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use metrique_writer_core::EntrySink;
+
+    use super::AuditSink;
+    use crate::{Entry, test_util::test_entry_sink};
+
+    #[derive(Entry)]
+    #[entry(rename_all = "PascalCase")]
+    struct TestEntry {
+        operation: &'static str,
+        item_count: u64,
+        error: Option<&'static str>,
+    }
+
+    #[derive(Entry)]
+    struct OtherEntry {
+        #[entry(name = "Operation")]
+        op: &'static str,
+        #[entry(name = "CacheHit")]
+        cache_hit: bool,
+    }
+
+    #[test]
+    fn counts_emitted_names_across_entries() {
+        let test_sink = test_entry_sink();
+        let audit = AuditSink::new(test_sink.sink);
+
+        for i in 0..3 {
+            audit.append(TestEntry {
+                operation: "Get",
+                item_count: i,
+                error: (i == 1).then_some("Throttled"),
+            });
+        }
+        audit.append(OtherEntry {
+            op: "Put",
+            cache_hit: true,
+        });
+
+        let counts = audit.snapshot();
+        assert_eq!(counts.len(), 4);
+        assert_eq!(counts["Operation"], 4);
+        assert_eq!(counts["ItemCount"], 3);
+        assert_eq!(counts["Error"], 1);
+        assert_eq!(counts["CacheHit"], 1);
+
+        // entries are still appended to the inner sink
+        assert_eq!(test_sink.inspector.entries().len(), 4);
+    }
+
+    #[test]
+    fn clones_share_counts_and_reset() {
+        let audit = AuditSink::new(test_entry_sink().sink);
+        let clone = audit.clone();
+
+        let entry = |operation| TestEntry {
+            operation,
+            item_count: 1,
+            error: None,
+        };
+        audit.append(entry("Get"));
+        clone.append(entry("Put"));
+        assert_eq!(audit.snapshot()[&Cow::Borrowed("Operation")], 2);
+        assert!(!audit.snapshot().contains_key("Error"));
+
+        clone.reset();
+        assert!(audit.snapshot().is_empty());
+        audit.append(entry("Get"));
+        assert_eq!(clone.snapshot()["ItemCount"], 1);
+    }
+}
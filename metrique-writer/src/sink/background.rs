@@ -13,7 +13,8 @@ use std::{
 use crossbeam_queue::ArrayQueue;
 use crossbeam_utils::sync::{Parker, Unparker};
 use metrique_writer_core::{
-    BoxEntrySink, EntryIoStream, IoStreamError, ValidationError, sink::FlushWait,
+    BoxEntrySink, EntryIoStream, IoStreamError, ValidationError, config::HighPriority,
+    sink::FlushWait,
 };
 
 use crate::{Entry, EntryIoStreamExt, EntrySink, rate_limit::rate_limited};
@@ -31,6 +32,7 @@ pub struct BackgroundQueueBuilder {
     metric_name: Option<String>,
     observer: Option<Box<dyn BackgroundQueueObserver>>,
     flush_interval: Duration,
+    max_buffered_bytes: Option<u64>,
     shutdown_timeout: Duration,
 }
 
@@ -42,6 +44,7 @@ impl Default for BackgroundQueueBuilder {
             metric_name: None,
             observer: None,
             flush_interval: Duration::from_secs(1),
+            max_buffered_bytes: None,
             shutdown_timeout: Duration::from_secs(30),
         }
     }
@@ -58,6 +61,9 @@ impl Default for BackgroundQueueBuilder {
 /// 4. `metrique_io_errors` - the amount of IO errors encountered emitting metrics.
 /// 5. `metrique_validation_errors` - the amount of validation errors encountered emitting metrics.
 /// 6. `metrique_queue_overflows` - the count of metrics being lost due to a full queue.
+/// 7. `metrique_metrics_sampled_out` - the count of metrics dropped by a sampling format, as
+///    reported by [`EntryIoStream::entries_sampled_out`]. These are also counted in
+///    `metrique_metrics_emitted`.
 pub const BACKGROUND_QUEUE_METRICS: &[DescribedMetric] = &[
     DescribedMetric {
         name: "metrique_idle_percent",
@@ -95,6 +101,12 @@ pub const BACKGROUND_QUEUE_METRICS: &[DescribedMetric] = &[
         r#type: MetricsRsType::Counter,
         description: "Number of metrics lost due to the queue being full",
     },
+    DescribedMetric {
+        name: "metrique_metrics_sampled_out",
+        unit: MetricsRsUnit::Count,
+        r#type: MetricsRsType::Counter,
+        description: "Number of metrics dropped by sampling in this queue",
+    },
 ];
 
 impl BackgroundQueueBuilder {
@@ -114,6 +126,10 @@ impl BackgroundQueueBuilder {
     /// Note that we deliberately drop the oldest entries on hitting capacity. We almost always care more about the most
     /// recent metrics as they're more reflective of the system state. See the [`crate`] documentation.
     ///
+    /// Entries marked [`HighPriority`] are kept in preference to other entries when the queue is full.
+    /// Only the entries that would be evicted are checked for the mark, so this costs nothing until
+    /// the queue fills up.
+    ///
     /// A [`tracing`] error will be emitted periodically if metrics are being dropped.
     pub fn capacity(mut self, capacity: usize) -> Self {
         assert!(capacity > 0);
@@ -370,6 +386,24 @@ impl BackgroundQueueBuilder {
         self
     }
 
+    /// Additionally flush the writer once `max_buffered_bytes` or more have been written to it
+    /// since the last flush.
+    ///
+    /// By default, the writer is only flushed periodically (see
+    /// [`flush_interval`](Self::flush_interval)) and when requested. Setting a limit bounds the
+    /// amount of data sitting in the writer's buffers. An entry is always written in full, even if
+    /// it is larger than the limit on its own, and the writer is then flushed right after it.
+    ///
+    /// This requires a stream that reports how many bytes it has written through
+    /// [`EntryIoStream::bytes_written`], like the streams created with
+    /// [`FormatExt::output_to`](crate::format::FormatExt::output_to). For other streams, the limit
+    /// has no effect.
+    pub fn max_buffered_bytes(mut self, max_buffered_bytes: u64) -> Self {
+        assert!(max_buffered_bytes > 0);
+        self.max_buffered_bytes = Some(max_buffered_bytes);
+        self
+    }
+
     /// Sets how long the background thread will try to drain remaining metric entries once starting to shut down.
     ///
     /// Defaults to 30 seconds.
@@ -414,6 +448,20 @@ impl BackgroundQueueBuilder {
         (BoxEntrySink::new(queue), handle)
     }
 
+    /// Build a [`WaitableBackgroundQueue`] for writing metric entries of type `T`, which also
+    /// supports waiting for a single entry to be written with
+    /// [`append_and_wait`](WaitableBackgroundQueue::append_and_wait).
+    ///
+    /// Queues built with [`BackgroundQueueBuilder::build`] don't carry the bookkeeping needed for
+    /// this, so use this only when you need it.
+    pub fn build_waitable<T: Entry + Send + 'static>(
+        self,
+        stream: impl EntryIoStream + Send + 'static,
+    ) -> (WaitableBackgroundQueue<T>, BackgroundQueueJoinHandle) {
+        let (queue, handle) = self.build(stream);
+        (WaitableBackgroundQueue(queue), handle)
+    }
+
     fn do_build<S: EntryIoStream + Send + 'static, E: Entry + Send + 'static>(
         self,
         stream: S,
@@ -437,6 +485,9 @@ impl BackgroundQueueBuilder {
             stream,
             inner: Arc::clone(&inner),
             flush_interval: self.flush_interval,
+            max_buffered_bytes: self.max_buffered_bytes,
+            bytes_written_at_flush: 0,
+            entries_sampled_out_at_flush: 0,
             shutdown_timeout: self.shutdown_timeout,
             shutdown_signal: Arc::clone(&shutdown_signal),
             parker,
@@ -513,11 +564,117 @@ impl<T: Entry + Send + 'static> EntrySink<T> for BackgroundQueue<T> {
         self.0.push(entry)
     }
 
+    /// Pushes all of `entries`, then wakes up the background thread once.
+    ///
+    /// The queue is lock-free, so entries are still pushed one at a time and may be interleaved
+    /// with concurrent appends, but the wakeup and overflow reporting are paid once per batch.
+    fn append_batch(&self, entries: impl IntoIterator<Item = T>) {
+        self.0.push_batch(entries)
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        self.0.flush_async()
+    }
+}
+
+/// A [`BackgroundQueue`] that can also wait for individual entries to be written.
+///
+/// Built with [`BackgroundQueueBuilder::build_waitable`]. Entries appended with
+/// [`EntrySink::append`] behave exactly like in a [`BackgroundQueue`], while
+/// [`append_and_wait`](Self::append_and_wait) returns a [`FlushWait`] that resolves once that
+/// particular entry has been written and the output stream flushed, without waiting for the rest
+/// of the queue.
+///
+/// # Example
+/// ```
+/// # use std::sync::{Arc, Mutex};
+/// # use metrique_writer::{Entry, EntrySink};
+/// # use metrique_writer::sink::BackgroundQueueBuilder;
+/// # let output: Arc<Mutex<metrique_writer_core::test_stream::TestStream>> = Default::default();
+/// # #[derive(Entry)]
+/// # struct Breadcrumb { value: u64 }
+/// let (queue, _handle) = BackgroundQueueBuilder::new().build_waitable(output.clone());
+/// futures::executor::block_on(queue.append_and_wait(Breadcrumb { value: 1 }));
+/// assert_eq!(output.lock().unwrap().values_flushed, 1);
+/// ```
+pub struct WaitableBackgroundQueue<T>(BackgroundQueue<WaitableEntry<T>>);
+
+impl<T: Entry + Send + 'static> WaitableBackgroundQueue<T> {
+    /// Append `entry`, returning a [`FlushWait`] that resolves once it has been written to the
+    /// output stream and the stream has been flushed.
+    ///
+    /// If the entry is dropped without being written, for example because it was evicted from a
+    /// full queue, the [`FlushWait`] still resolves, at the latest after the next flush. Entries
+    /// appended after the [`BackgroundQueueJoinHandle`] shut the queue down are never written, and
+    /// their [`FlushWait`] only resolves once every clone of the queue has been dropped.
+    pub fn append_and_wait(&self, entry: T) -> FlushWait {
+        let (channel, receiver) = tokio::sync::oneshot::channel();
+        self.0.0.push(WaitableEntry {
+            entry,
+            waiter: Some((FlushSignal { channel }, self.0.0.flush_queue_sender.clone())),
+        });
+        FlushWait::from_future(async move {
+            let _ = receiver.await;
+        })
+    }
+}
+
+impl<T> Clone for WaitableBackgroundQueue<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Entry + Send + 'static> EntrySink<T> for WaitableBackgroundQueue<T> {
+    fn append(&self, entry: T) {
+        self.0.append(WaitableEntry {
+            entry,
+            waiter: None,
+        })
+    }
+
+    fn append_batch(&self, entries: impl IntoIterator<Item = T>) {
+        self.0
+            .append_batch(entries.into_iter().map(|entry| WaitableEntry {
+                entry,
+                waiter: None,
+            }))
+    }
+
     fn flush_async(&self) -> FlushWait {
         self.0.flush_async()
     }
 }
 
+// An entry of a `WaitableBackgroundQueue`. The background thread is unaware of waiters: when a
+// waited-for entry is dropped after being written, its signal is handed to the flush queue, so
+// it is woken by the next flush of the stream like a `flush_async` waker.
+struct WaitableEntry<T> {
+    entry: T,
+    waiter: Option<(FlushSignal, std::sync::mpsc::Sender<FlushSignal>)>,
+}
+
+impl<T: Entry> Entry for WaitableEntry<T> {
+    fn write<'a>(&'a self, writer: &mut impl crate::EntryWriter<'a>) {
+        self.entry.write(writer)
+    }
+
+    fn sample_group(
+        &self,
+    ) -> impl Iterator<Item = metrique_writer_core::entry::SampleGroupElement> {
+        self.entry.sample_group()
+    }
+}
+
+impl<T> Drop for WaitableEntry<T> {
+    fn drop(&mut self) {
+        if let Some((signal, flush_queue_sender)) = self.waiter.take() {
+            // if the background thread has shut down, dropping the signal wakes the waiter
+            flush_queue_sender.send(signal).ok();
+        }
+    }
+}
+
 impl BackgroundQueueJoinHandle {
     /// Drop the handle but also let the background thread keep running until no [`BackgroundQueue`]s exist.
     pub fn forget(mut self) {
@@ -541,14 +698,24 @@ impl Drop for BackgroundQueueJoinHandle {
     }
 }
 
-impl<E> Inner<E> {
+impl<E: Entry> Inner<E> {
     fn push(&self, entry: E) {
+        self.push_batch([entry])
+    }
+
+    fn push_batch(&self, entries: impl IntoIterator<Item = E>) {
         // force_push causes the oldest entry to be dropped if the queue is full. We want this since the more recent
         // metrics are more valuable when describing the state of the service!
-        if self.queue.force_push(entry).is_some() {
-            if let Some(observer) = self.observer.as_ref() {
-                observer.on_event(&self.name, BackgroundQueueEvent::QueueOverflow);
+        let mut overflowed = false;
+        for entry in entries {
+            if self.force_push_keeping_priority(entry) {
+                overflowed = true;
+                if let Some(observer) = self.observer.as_ref() {
+                    observer.on_event(&self.name, BackgroundQueueEvent::QueueOverflow);
+                }
             }
+        }
+        if overflowed {
             rate_limited!(
                 Duration::from_secs(1),
                 tracing::error!(
@@ -561,6 +728,22 @@ impl<E> Inner<E> {
         self.unparker.unpark();
     }
 
+    /// Push `entry`, returning whether an entry was dropped.
+    ///
+    /// If the evicted entry is [`HighPriority`], it is pushed again (to the back of the queue),
+    /// evicting the next oldest entry instead. This gives up after a full pass over the queue, so
+    /// an entry is still dropped if the queue is full of high priority entries.
+    fn force_push_keeping_priority(&self, mut entry: E) -> bool {
+        for _ in 0..=self.queue.capacity() {
+            match self.queue.force_push(entry) {
+                None => return false,
+                Some(evicted) if HighPriority::is_set(&evicted) => entry = evicted,
+                Some(_) => return true,
+            }
+        }
+        true
+    }
+
     fn flush_async(&self) -> FlushWait {
         let (channel, receiver) = tokio::sync::oneshot::channel();
         self.flush_queue_sender.send(FlushSignal { channel }).ok();
@@ -579,6 +762,12 @@ struct Receiver<S, E> {
     stream: S,
     inner: Arc<Inner<E>>,
     flush_interval: Duration,
+    // flush once this many bytes were written since the last flush
+    max_buffered_bytes: Option<u64>,
+    // the stream's `bytes_written` as of the last flush
+    bytes_written_at_flush: u64,
+    // the stream's `entries_sampled_out` as of the last flush
+    entries_sampled_out_at_flush: u64,
     shutdown_timeout: Duration,
     shutdown_signal: Arc<AtomicBool>,
     // Utility to notice wakeup events when an appender thread has appended something to the queue.
@@ -806,6 +995,13 @@ impl<S: EntryIoStream, E: Entry> Receiver<S, E> {
                 )
             }
         }
+
+        if let Some(max_buffered_bytes) = self.max_buffered_bytes
+            && let Some(bytes_written) = self.stream.bytes_written()
+            && bytes_written.saturating_sub(self.bytes_written_at_flush) >= max_buffered_bytes
+        {
+            self.flush_stream();
+        }
     }
 
     fn flush_stream(&mut self) {
@@ -816,6 +1012,12 @@ impl<S: EntryIoStream, E: Entry> Receiver<S, E> {
                 tracing::warn!(?err, "couldn't flush metric stream")
             )
         }
+        self.bytes_written_at_flush = self.stream.bytes_written().unwrap_or(0);
+        let entries_sampled_out = self.stream.entries_sampled_out().unwrap_or(0);
+        let sampled_out = entries_sampled_out.saturating_sub(std::mem::replace(
+            &mut self.entries_sampled_out_at_flush,
+            entries_sampled_out,
+        ));
 
         if let Some(observer) = &self.inner.observer {
             // intentionally route through the observer here, so if a new global recorder is
@@ -840,6 +1042,10 @@ impl<S: EntryIoStream, E: Entry> Receiver<S, E> {
                     count: std::mem::take(&mut self.metric_validation_errors),
                 },
             );
+            observer.on_event(
+                &self.inner.name,
+                BackgroundQueueEvent::MetricsSampledOut { count: sampled_out },
+            );
         }
     }
 
@@ -927,6 +1133,21 @@ mod tests {
         }
     }
 
+    #[test]
+    fn writes_batches_in_fifo_order() {
+        test_all_queues! {
+            |builder| builder.capacity(1_000),
+            |output, queue, handle| {
+                queue.append(TestEntry(0));
+                for batch in 0..9 {
+                    queue.append_batch((1..=111).map(|i| TestEntry(batch * 111 + i)));
+                }
+                handle.shut_down();
+                assert_eq!(output.lock().unwrap().values, (0..1_000).collect::<Vec<_>>());
+            }
+        }
+    }
+
     #[test]
     fn drops_older_entries_when_full() {
         test_all_queues! {
@@ -951,6 +1172,66 @@ mod tests {
         }
     }
 
+    struct MaybePriority(u64, bool);
+
+    impl Entry for MaybePriority {
+        fn write<'a>(&'a self, writer: &mut impl crate::EntryWriter<'a>) {
+            writer.value("value", &self.0);
+            if self.1 {
+                writer.config(const { &HighPriority::new() });
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_high_priority_entries_when_full() {
+        test_all_queues! {
+            |builder| builder.capacity(10),
+            |output, queue, handle| {
+                // hold lock so writer can't make progress
+                {
+                    let _locked = output.lock().unwrap();
+                    queue.append(MaybePriority(1000, true));
+                    for i in 0..20 {
+                        queue.append(MaybePriority(i, false));
+                    }
+                    queue.append(MaybePriority(1001, true));
+                    for i in 20..40 {
+                        queue.append(MaybePriority(i, false));
+                    }
+                }
+                // lock released, should drain now
+                handle.shut_down();
+
+                // as above, the background queue can pick up one entry before getting blocked on the mutex
+                let output = output.lock().unwrap();
+                assert!((10..=11).contains(&output.values.len()));
+                assert!(output.values.contains(&1000));
+                assert!(output.values.contains(&1001));
+                assert!((32..40).all(|i| output.values.contains(&i)));
+            }
+        }
+    }
+
+    #[test]
+    fn drops_high_priority_entries_when_full_of_them() {
+        test_all_queues! {
+            |builder| builder.capacity(10),
+            |output, queue, handle| {
+                {
+                    let _locked = output.lock().unwrap();
+                    for i in 0..20 {
+                        queue.append(MaybePriority(i, true));
+                    }
+                }
+                handle.shut_down();
+
+                let output = output.lock().unwrap();
+                assert!((10..=11).contains(&output.values.len()));
+            }
+        }
+    }
+
     #[test]
     fn writes_all_entries_from_multiple_threads() {
         test_all_queues! {
@@ -1102,6 +1383,127 @@ mod tests {
         }
     }
 
+    #[test]
+    fn append_and_wait_resolves_once_entry_is_flushed() {
+        // a long flush interval, so only the wait can cause a flush
+        let output: Arc<Mutex<TestStream>> = Default::default();
+        let (queue, handle) = BackgroundQueueBuilder::new()
+            .flush_interval(Duration::from_secs(59))
+            .build_waitable(Arc::clone(&output));
+
+        queue.append(TestEntry(0));
+        futures::executor::block_on(queue.append_and_wait(TestEntry(1)));
+        {
+            let output = output.lock().unwrap();
+            assert_eq!(output.values, [0, 1]);
+            assert_eq!(output.values_flushed, 2);
+        }
+
+        queue.append_batch([TestEntry(2), TestEntry(3)]);
+        futures::executor::block_on(queue.append_and_wait(TestEntry(4)));
+        assert_eq!(output.lock().unwrap().values_flushed, 5);
+
+        handle.shut_down();
+    }
+
+    #[test]
+    fn append_and_wait_resolves_when_entry_is_evicted() {
+        let output: Arc<Mutex<TestStream>> = Default::default();
+        let (queue, handle) = BackgroundQueueBuilder::new()
+            .capacity(1)
+            .flush_interval(Duration::from_secs(59))
+            .build_waitable(Arc::clone(&output));
+
+        let wait = {
+            // hold lock so writer can't make progress
+            let _locked = output.lock().unwrap();
+            queue.append(TestEntry(0));
+            let wait = queue.append_and_wait(TestEntry(1));
+            // evicts the waited-for entry
+            queue.append(TestEntry(2));
+            wait
+        };
+        futures::executor::block_on(wait);
+        assert!(!output.lock().unwrap().values.contains(&1));
+
+        handle.shut_down();
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum OutputEvent {
+        Write(usize),
+        Flush,
+    }
+
+    #[derive(Clone, Default)]
+    struct RecordingOutput(Arc<Mutex<Vec<OutputEvent>>>);
+
+    impl std::io::Write for RecordingOutput {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().push(OutputEvent::Write(buf.len()));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.0.lock().unwrap().push(OutputEvent::Flush);
+            Ok(())
+        }
+    }
+
+    /// Writes as many bytes as the value of the [`TestEntry`]
+    struct SizedFormat;
+
+    impl crate::format::Format for SizedFormat {
+        fn format(
+            &mut self,
+            entry: &impl Entry,
+            output: &mut impl std::io::Write,
+        ) -> Result<(), IoStreamError> {
+            let stream: Arc<Mutex<TestStream>> = Default::default();
+            entry.write(&mut stream.clone());
+            let size = stream.lock().unwrap().values[0];
+            output.write_all(&vec![b'x'; size as usize])?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flushes_when_max_buffered_bytes_is_reached() {
+        use crate::format::FormatExt as _;
+        use OutputEvent::{Flush, Write};
+
+        let output = RecordingOutput::default();
+        let (queue, handle) = BackgroundQueueBuilder::new()
+            .flush_interval(Duration::from_secs(59))
+            .max_buffered_bytes(10)
+            .build(SizedFormat.output_to(output.clone()));
+
+        // an entry larger than the limit is still written, and the count restarts after it
+        for size in [4, 4, 4, 25, 3, 6, 1] {
+            queue.append(TestEntry(size));
+        }
+        handle.shut_down();
+
+        let events = output.0.lock().unwrap();
+        assert_eq!(
+            events[..10],
+            [
+                Write(4),
+                Write(4),
+                Write(4),
+                Flush,
+                Write(25),
+                Flush,
+                Write(3),
+                Write(6),
+                Write(1),
+                Flush,
+            ]
+        );
+        // the rest are shutdown flushes
+        assert!(events[10..].iter().all(|event| *event == Flush));
+    }
+
     // Implement a simple waker to avoid taking a dependency on tokio rt
     #[derive(Default)]
     struct SimpleWaker(AtomicBool);
@@ -1396,6 +1798,7 @@ mod tests {
         emitted: u64,
         io_errors: u64,
         validation_errors: u64,
+        sampled_out: u64,
         flush_completes: u64,
         last_queue: Option<String>,
     }
@@ -1417,6 +1820,7 @@ mod tests {
                 }
                 BackgroundQueueEvent::IoErrors { count } => e.io_errors += count,
                 BackgroundQueueEvent::ValidationErrors { count } => e.validation_errors += count,
+                BackgroundQueueEvent::MetricsSampledOut { count } => e.sampled_out += count,
                 BackgroundQueueEvent::FlushComplete { .. } => e.flush_completes += 1,
             }
         }
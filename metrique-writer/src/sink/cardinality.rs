@@ -0,0 +1,381 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    borrow::Cow,
+    fmt,
+    hash::{BuildHasher, BuildHasherDefault, DefaultHasher},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use metrique_writer_core::{
+    Entry, EntryConfig, EntrySink, EntryWriter, MetricFlags, Observation, Unit, ValidationError,
+    Value, ValueWriter, entry::SampleGroupElement, sink::FlushWait,
+};
+
+/// An [`EntrySink`] that estimates the number of distinct values of some dimensions, and
+/// periodically appends the estimates to the inner sink as [`CardinalityEstimate`] entries.
+///
+/// This catches cardinality explosions (e.g. a request id accidentally used as a dimension)
+/// before they show up on the bill. Every entry is appended to the inner sink unchanged. The
+/// string values written for the configured dimension names are fed into a
+/// [HyperLogLog](https://en.wikipedia.org/wiki/HyperLogLog) sketch per dimension, which uses a
+/// few KiB of memory regardless of the number of distinct values, with a typical error of
+/// about 1.6%.
+///
+/// Once `interval` has passed since the previous report, the next appended entry triggers a
+/// [`CardinalityEstimate`] entry for every dimension, after which the sketches are reset. The
+/// estimates are therefore the number of distinct values seen during each interval. Dimension
+/// names are matched against the names as they are written, i.e. after inflection.
+///
+/// The state is shared between clones of the sink.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use metrique_writer::{Entry, EntrySink, sink::CardinalityMonitorSink, test_util::test_entry_sink};
+/// #[derive(Entry)]
+/// #[entry(rename_all = "PascalCase")]
+/// struct RequestMetrics {
+///     operation: &'static str,
+///     customer: String,
+/// }
+///
+/// let test_sink = test_entry_sink();
+/// let sink = CardinalityMonitorSink::new(
+///     test_sink.sink,
+///     ["Operation", "Customer"],
+///     Duration::from_secs(60),
+/// );
+///
+/// for customer in 0..100 {
+///     sink.append(RequestMetrics { operation: "Get", customer: customer.to_string() });
+/// }
+/// assert_eq!(sink.estimate("Operation"), Some(1));
+/// assert_eq!(sink.estimate("Customer"), Some(100));
+/// ```
+pub struct CardinalityMonitorSink<S> {
+    sink: S,
+    interval: Duration,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    dimensions: Vec<(Cow<'static, str>, HyperLogLog)>,
+    last_report: Instant,
+}
+
+impl<S: Clone> Clone for CardinalityMonitorSink<S> {
+    fn clone(&self) -> Self {
+        Self {
+            sink: self.sink.clone(),
+            interval: self.interval,
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for CardinalityMonitorSink<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CardinalityMonitorSink")
+            .field("sink", &self.sink)
+            .field("interval", &self.interval)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S> CardinalityMonitorSink<S> {
+    /// Wrap `sink`, estimating the cardinality of each of `dimensions` and reporting the
+    /// estimates every `interval`.
+    pub fn new(
+        sink: S,
+        dimensions: impl IntoIterator<Item = impl Into<Cow<'static, str>>>,
+        interval: Duration,
+    ) -> Self {
+        let dimensions = dimensions
+            .into_iter()
+            .map(|name| (name.into(), HyperLogLog::new()))
+            .collect();
+        Self {
+            sink,
+            interval,
+            state: Arc::new(Mutex::new(State {
+                dimensions,
+                last_report: Instant::now(),
+            })),
+        }
+    }
+
+    /// Return the estimated number of distinct values of `dimension` in the current interval,
+    /// or `None` if `dimension` is not monitored.
+    pub fn estimate(&self, dimension: &str) -> Option<u64> {
+        let state = self.state.lock().unwrap();
+        state
+            .dimensions
+            .iter()
+            .find(|(name, _)| name == dimension)
+            .map(|(_, sketch)| sketch.estimate())
+    }
+}
+
+impl<E, S> EntrySink<E> for CardinalityMonitorSink<S>
+where
+    E: Entry,
+    S: EntrySink<E> + EntrySink<CardinalityEstimate>,
+{
+    fn append(&self, entry: E) {
+        let estimates = {
+            let mut state = self.state.lock().unwrap();
+            entry.write(&mut DimensionCollector {
+                dimensions: &mut state.dimensions,
+            });
+            if state.last_report.elapsed() >= self.interval {
+                state.last_report = Instant::now();
+                state
+                    .dimensions
+                    .iter_mut()
+                    .map(|(name, sketch)| {
+                        let estimate = sketch.estimate();
+                        sketch.clear();
+                        CardinalityEstimate {
+                            dimension: name.clone(),
+                            estimate,
+                        }
+                    })
+                    .collect()
+            } else {
+                vec![]
+            }
+        };
+        self.sink.append(entry);
+        // append outside the lock, since the inner sink might block
+        for estimate in estimates {
+            self.sink.append(estimate);
+        }
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        EntrySink::<E>::flush_async(&self.sink)
+    }
+}
+
+/// The estimated number of distinct values of a dimension, as written by
+/// [`CardinalityMonitorSink`].
+///
+/// This is written as an entry with a `Dimension` property containing the dimension name, and an
+/// `EstimatedCardinality` metric.
+#[derive(Clone, Debug)]
+pub struct CardinalityEstimate {
+    dimension: Cow<'static, str>,
+    estimate: u64,
+}
+
+impl CardinalityEstimate {
+    /// Return the name of the dimension
+    pub fn dimension(&self) -> &str {
+        &self.dimension
+    }
+
+    /// Return the estimated number of distinct values
+    pub fn estimate(&self) -> u64 {
+        self.estimate
+    }
+}
+
+impl Entry for CardinalityEstimate {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        writer.value("Dimension", &*self.dimension);
+        writer.value("EstimatedCardinality", &self.estimate);
+    }
+
+    fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+        [(Cow::Borrowed("Dimension"), self.dimension.clone())].into_iter()
+    }
+}
+
+/// Feeds the string values of the monitored dimensions into their sketches
+struct DimensionCollector<'s> {
+    dimensions: &'s mut [(Cow<'static, str>, HyperLogLog)],
+}
+
+impl<'a> EntryWriter<'a> for DimensionCollector<'_> {
+    fn timestamp(&mut self, _timestamp: SystemTime) {}
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        let name = name.into();
+        if let Some((_, sketch)) = self.dimensions.iter_mut().find(|(n, _)| *n == name) {
+            value.write(SketchValueWriter(sketch));
+        }
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {}
+}
+
+struct SketchValueWriter<'s>(&'s mut HyperLogLog);
+
+impl ValueWriter for SketchValueWriter<'_> {
+    fn string(self, value: &str) {
+        self.0.insert(value);
+    }
+
+    fn metric<'a>(
+        self,
+        _distribution: impl IntoIterator<Item = Observation>,
+        _unit: Unit,
+        _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        _flags: MetricFlags<'_>,
+    ) {
+        // only string values can be dimensions
+    }
+
+    fn error(self, _error: ValidationError) {}
+}
+
+// 2^12 registers, for a standard error of 1.04 / sqrt(2^12) ~= 1.6%
+const PRECISION: u32 = 12;
+const REGISTERS: usize = 1 << PRECISION;
+
+/// A HyperLogLog sketch, as described by Flajolet et al. with the small range correction
+struct HyperLogLog {
+    registers: Box<[u8; REGISTERS]>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self {
+            registers: Box::new([0; REGISTERS]),
+        }
+    }
+
+    fn insert(&mut self, value: &str) {
+        // SipHash with fixed keys, so estimates are reproducible
+        let hash = BuildHasherDefault::<DefaultHasher>::default().hash_one(value);
+        let index = (hash >> (64 - PRECISION)) as usize;
+        // the remaining bits, with a sentinel bit so the rank is at most 64 - PRECISION + 1
+        let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&rank| 2f64.powi(-i32::from(rank)))
+            .sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&rank| rank == 0).count();
+        let estimate = if raw <= 2.5 * m && zeros > 0 {
+            // linear counting is more accurate for small cardinalities
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        };
+        estimate.round() as u64
+    }
+
+    fn clear(&mut self) {
+        self.registers.fill(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use metrique_writer_core::EntrySink;
+
+    use super::{CardinalityMonitorSink, HyperLogLog};
+    use crate::{Entry, test_util::test_entry_sink};
+
+    #[derive(Entry)]
+    #[entry(rename_all = "PascalCase")]
+    struct TestEntry {
+        operation: &'static str,
+        request_id: String,
+        latency: u64,
+    }
+
+    fn entry(operation: &'static str, request_id: usize) -> TestEntry {
+        TestEntry {
+            operation,
+            request_id: format!("request-{request_id}"),
+            latency: 1,
+        }
+    }
+
+    #[test]
+    fn estimates_are_within_tolerance() {
+        for distinct in [1, 10, 1_000, 10_000, 100_000] {
+            let mut sketch = HyperLogLog::new();
+            for i in 0..distinct {
+                // insert every value twice, duplicates must not be counted
+                sketch.insert(&format!("value-{i}"));
+                sketch.insert(&format!("value-{i}"));
+            }
+            let estimate = sketch.estimate() as f64;
+            // 5% is about 3 standard errors
+            let error = (estimate - distinct as f64).abs() / distinct as f64;
+            assert!(error < 0.05, "estimated {estimate} for {distinct}");
+        }
+    }
+
+    #[test]
+    fn monitors_configured_dimensions() {
+        let test_sink = test_entry_sink();
+        let sink = CardinalityMonitorSink::new(
+            test_sink.sink,
+            ["Operation", "RequestId", "Latency", "Missing"],
+            Duration::from_secs(3600),
+        );
+        for request_id in 0..5000 {
+            let operation = ["Get", "Put", "Delete"][request_id % 3];
+            sink.append(entry(operation, request_id));
+        }
+
+        assert_eq!(sink.estimate("Operation"), Some(3));
+        let request_ids = sink.estimate("RequestId").unwrap() as f64;
+        assert!((4750.0..5250.0).contains(&request_ids), "{request_ids}");
+        // metrics are not dimensions
+        assert_eq!(sink.estimate("Latency"), Some(0));
+        assert_eq!(sink.estimate("Missing"), Some(0));
+        assert_eq!(sink.estimate("NotMonitored"), None);
+        // entries are passed through, and the interval hasn't passed yet
+        assert_eq!(test_sink.inspector.entries().len(), 5000);
+    }
+
+    #[test]
+    fn reports_estimates_every_interval() {
+        let test_sink = test_entry_sink();
+        let sink =
+            CardinalityMonitorSink::new(test_sink.sink, ["Operation", "RequestId"], Duration::ZERO);
+        sink.append(entry("Get", 1));
+        sink.append(entry("Put", 2));
+
+        let entries = test_sink.inspector.entries();
+        assert_eq!(entries.len(), 6);
+        assert_eq!(entries[0].values["Operation"], "Get");
+        assert_eq!(entries[1].values["Dimension"], "Operation");
+        assert_eq!(entries[1].metrics["EstimatedCardinality"], 1);
+        assert_eq!(entries[2].values["Dimension"], "RequestId");
+        assert_eq!(entries[2].metrics["EstimatedCardinality"], 1);
+        // sketches are reset after each report
+        assert_eq!(entries[3].values["Operation"], "Put");
+        assert_eq!(entries[4].metrics["EstimatedCardinality"], 1);
+        assert_eq!(sink.estimate("Operation"), Some(0));
+    }
+
+    #[test]
+    fn clones_share_state() {
+        let test_sink = test_entry_sink();
+        let sink =
+            CardinalityMonitorSink::new(test_sink.sink, ["Operation"], Duration::from_secs(3600));
+        let clone = sink.clone();
+        sink.append(entry("Get", 1));
+        clone.append(entry("Put", 2));
+        assert_eq!(sink.estimate("Operation"), Some(2));
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+
+use metrique_writer_core::{Entry, EntrySink, Value, sink::FlushWait};
+
+use crate::entry::WithField;
+
+/// An [`EntrySink`] that adds a field computed from each entry when it is appended.
+///
+/// The closure is called with every appended entry and returns the name and [`Value`] of the
+/// field to add. This is useful for fields derived from several other fields of the entry, such
+/// as a cost-allocation tag computed from the resources a request used, that don't belong in the
+/// entry type itself.
+///
+/// The inner sink receives a [`WithField`], so it must either accept any entry (e.g. a
+/// [`BoxEntrySink`](crate::BoxEntrySink)) or be typed for the wrapped entry.
+///
+/// # Example
+/// ```
+/// # use metrique_writer::{Entry, EntrySink, sink::WithComputedField, test_util::test_entry_sink};
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     cpu_millis: u64,
+///     bytes_out: u64,
+/// }
+///
+/// let test_sink = test_entry_sink();
+/// let sink = WithComputedField::new(test_sink.sink, |entry: &RequestMetrics| {
+///     ("ComputeUnits", entry.cpu_millis / 10 + entry.bytes_out / 1024)
+/// });
+///
+/// sink.append(RequestMetrics { cpu_millis: 250, bytes_out: 4096 });
+/// assert_eq!(test_sink.inspector.get(0).metrics["ComputeUnits"], 29);
+/// ```
+#[derive(Clone, Debug)]
+pub struct WithComputedField<S, F> {
+    sink: S,
+    compute: F,
+}
+
+impl<S, F> WithComputedField<S, F> {
+    /// Wrap `sink`, adding the field returned by `compute` to every entry.
+    pub fn new(sink: S, compute: F) -> Self {
+        Self { sink, compute }
+    }
+}
+
+impl<E, S, F, N, V> EntrySink<E> for WithComputedField<S, F>
+where
+    E: Entry,
+    S: EntrySink<WithField<E, V>>,
+    F: Fn(&E) -> (N, V),
+    N: Into<Cow<'static, str>>,
+    V: Value,
+{
+    fn append(&self, entry: E) {
+        let (name, value) = (self.compute)(&entry);
+        self.sink.append(WithField::new(entry, name, value));
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        self.sink.flush_async()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrique_writer_core::EntrySink;
+
+    use super::WithComputedField;
+    use crate::{Entry, sink::VecEntrySink, test_util::test_entry_sink};
+
+    #[derive(Entry)]
+    #[entry(rename_all = "PascalCase")]
+    struct TestEntry {
+        operation: &'static str,
+        cpu_millis: u64,
+        memory_mb: u64,
+    }
+
+    fn cost(entry: &TestEntry) -> (&'static str, f64) {
+        let gb_seconds = entry.cpu_millis as f64 / 1000.0 * entry.memory_mb as f64 / 1024.0;
+        ("CostUnits", gb_seconds)
+    }
+
+    #[test]
+    fn derived_cost_field_is_added() {
+        let test_sink = test_entry_sink();
+        let sink = WithComputedField::new(test_sink.sink, cost);
+
+        sink.append(TestEntry {
+            operation: "A",
+            cpu_millis: 2000,
+            memory_mb: 512,
+        });
+        sink.append(TestEntry {
+            operation: "B",
+            cpu_millis: 500,
+            memory_mb: 4096,
+        });
+
+        let entries = test_sink.inspector.entries();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].values["Operation"], "A");
+        assert_eq!(entries[0].metrics["CostUnits"], 1.0);
+        assert_eq!(entries[0].metrics["CpuMillis"], 2000);
+        assert_eq!(entries[1].values["Operation"], "B");
+        assert_eq!(entries[1].metrics["CostUnits"], 2.0);
+    }
+
+    #[test]
+    fn computed_name_and_string_value() {
+        let sink = VecEntrySink::new();
+        let computed = WithComputedField::new(sink.clone(), |entry: &TestEntry| {
+            let tier = if entry.memory_mb > 1024 {
+                "large"
+            } else {
+                "small"
+            };
+            (format!("{}Tier", entry.operation), tier)
+        });
+        computed.append(TestEntry {
+            operation: "Get",
+            cpu_millis: 1,
+            memory_mb: 2048,
+        });
+
+        let entries = sink.drain();
+        assert_eq!(entries.len(), 1);
+        let entry = crate::test_util::to_test_entry(&entries[0]);
+        assert_eq!(entry.values["GetTier"], "large");
+        assert_eq!(
+            entries.into_iter().next().unwrap().into_inner().cpu_millis,
+            1
+        );
+    }
+}
@@ -0,0 +1,202 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    borrow::Cow,
+    fmt,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use ahash::HashMap;
+use metrique_writer_core::{Entry, EntrySink, sink::FlushWait};
+
+use crate::entry::WithField;
+
+/// An [`EntrySink`] that converts an absolute, monotonically increasing counter carried by each
+/// entry into the delta since the previous entry with the same key, and adds it as a field.
+///
+/// Some sources only expose cumulative counters (e.g. bytes sent since boot), while metric
+/// backends like CloudWatch expect the amount for each interval. The closure passed to
+/// [`CounterDeltaSink::new`] returns a key and the absolute counter value for an entry. The sink
+/// remembers the last value for every key, and appends the entry with a field containing the
+/// difference from that value:
+/// - the first entry for a key has no previous value, so no delta field is written;
+/// - if the counter went down, the counter is assumed to have been reset (e.g. the process
+///   restarted) and the delta is the new absolute value.
+///
+/// The state is shared between clones of the sink. The key set is never pruned, so keys should
+/// come from a bounded set (e.g. a network interface or a downstream host).
+///
+/// The inner sink receives a [`WithField`] holding the delta, if any.
+///
+/// # Example
+/// ```
+/// # use metrique_writer::{Entry, EntrySink, sink::CounterDeltaSink, test_util::test_entry_sink};
+/// #[derive(Entry)]
+/// struct InterfaceMetrics {
+///     interface: &'static str,
+///     total_bytes_sent: u64,
+/// }
+///
+/// let test_sink = test_entry_sink();
+/// let sink = CounterDeltaSink::new(test_sink.sink, "BytesSent", |entry: &InterfaceMetrics| {
+///     (entry.interface, entry.total_bytes_sent)
+/// });
+///
+/// sink.append(InterfaceMetrics { interface: "eth0", total_bytes_sent: 1000 });
+/// sink.append(InterfaceMetrics { interface: "eth0", total_bytes_sent: 1500 });
+///
+/// let entries = test_sink.inspector.entries();
+/// assert!(!entries[0].metrics.contains_key("BytesSent"));
+/// assert_eq!(entries[1].metrics["BytesSent"], 500);
+/// ```
+pub struct CounterDeltaSink<S, F, K> {
+    sink: S,
+    name: Cow<'static, str>,
+    extract: F,
+    last: Arc<Mutex<HashMap<K, u64>>>,
+}
+
+impl<S: Clone, F: Clone, K> Clone for CounterDeltaSink<S, F, K> {
+    fn clone(&self) -> Self {
+        Self {
+            sink: self.sink.clone(),
+            name: self.name.clone(),
+            extract: self.extract.clone(),
+            last: self.last.clone(),
+        }
+    }
+}
+
+impl<S: fmt::Debug, F, K> fmt::Debug for CounterDeltaSink<S, F, K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CounterDeltaSink")
+            .field("sink", &self.sink)
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S, F, K> CounterDeltaSink<S, F, K> {
+    /// Wrap `sink`, adding a field called `name` with the delta of the counter returned by
+    /// `extract`.
+    pub fn new(sink: S, name: impl Into<Cow<'static, str>>, extract: F) -> Self {
+        Self {
+            sink,
+            name: name.into(),
+            extract,
+            last: Default::default(),
+        }
+    }
+}
+
+impl<E, S, F, K> EntrySink<E> for CounterDeltaSink<S, F, K>
+where
+    E: Entry,
+    S: EntrySink<WithField<E, Option<u64>>>,
+    F: Fn(&E) -> (K, u64),
+    K: Hash + Eq,
+{
+    fn append(&self, entry: E) {
+        let (key, value) = (self.extract)(&entry);
+        let previous = self.last.lock().unwrap().insert(key, value);
+        let delta = previous.map(|previous| {
+            if value >= previous {
+                value - previous
+            } else {
+                // the counter was reset, so it counted `value` since the reset
+                value
+            }
+        });
+        self.sink
+            .append(WithField::new(entry, self.name.clone(), delta));
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        self.sink.flush_async()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrique_writer_core::EntrySink;
+
+    use super::CounterDeltaSink;
+    use crate::{Entry, sink::VecEntrySink, test_util::test_entry_sink};
+
+    #[derive(Entry, Clone)]
+    #[entry(rename_all = "PascalCase")]
+    struct TestEntry {
+        host: &'static str,
+        total_requests: u64,
+    }
+
+    fn total_requests(entry: &TestEntry) -> (&'static str, u64) {
+        (entry.host, entry.total_requests)
+    }
+
+    #[test]
+    fn emits_deltas_per_key() {
+        let test_sink = test_entry_sink();
+        let sink = CounterDeltaSink::new(test_sink.sink, "Requests", total_requests);
+
+        for (host, total_requests) in [("a", 10), ("b", 100), ("a", 15), ("a", 15), ("b", 130)] {
+            sink.append(TestEntry {
+                host,
+                total_requests,
+            });
+        }
+
+        let entries = test_sink.inspector.entries();
+        assert_eq!(entries.len(), 5);
+        // first entry for each key has no delta
+        assert!(!entries[0].metrics.contains_key("Requests"));
+        assert!(!entries[1].metrics.contains_key("Requests"));
+        assert_eq!(entries[2].metrics["Requests"], 5);
+        assert_eq!(entries[3].metrics["Requests"], 0);
+        assert_eq!(entries[4].values["Host"], "b");
+        assert_eq!(entries[4].metrics["Requests"], 30);
+        // the absolute value is still written
+        assert_eq!(entries[4].metrics["TotalRequests"], 130);
+    }
+
+    #[test]
+    fn counter_reset_uses_new_value() {
+        let sink = VecEntrySink::new();
+        let deltas = CounterDeltaSink::new(sink.clone(), "Requests", total_requests);
+
+        for total_requests in [1000, 1200, 30, 50] {
+            deltas.append(TestEntry {
+                host: "a",
+                total_requests,
+            });
+        }
+
+        let entries = sink.drain();
+        assert_eq!(
+            entries.iter().map(|e| *e.value()).collect::<Vec<_>>(),
+            [None, Some(200), Some(30), Some(20)]
+        );
+        let entry = crate::test_util::to_test_entry(&entries[2]);
+        assert_eq!(entry.metrics["Requests"], 30);
+    }
+
+    #[test]
+    fn clones_share_state() {
+        let test_sink = test_entry_sink();
+        let sink = CounterDeltaSink::new(test_sink.sink, "Requests", total_requests);
+        let clone = sink.clone();
+
+        sink.append(TestEntry {
+            host: "a",
+            total_requests: 1,
+        });
+        clone.append(TestEntry {
+            host: "a",
+            total_requests: 4,
+        });
+
+        assert_eq!(test_sink.inspector.get(1).metrics["Requests"], 3);
+    }
+}
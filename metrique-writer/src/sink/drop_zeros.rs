@@ -0,0 +1,254 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{borrow::Cow, collections::HashSet, sync::Arc, time::SystemTime};
+
+use metrique_writer_core::{
+    Entry, EntryConfig, EntrySink, EntryWriter, MetricFlags, Observation, Unit, ValidationError,
+    Value, ValueWriter, entry::SampleGroupElement, sink::FlushWait,
+};
+
+/// An [`EntrySink`] that leaves out metric values that are exactly zero when entries are written.
+///
+/// Entries built from large, fixed schemas often have many fields that are zero for most
+/// requests, which cost bytes in the output and, for formats like EMF, a metric datapoint each.
+/// A value is dropped if it is a metric whose observations are all zero (an empty distribution is
+/// not considered zero). String values and metrics with any non-zero observation are written
+/// unchanged.
+///
+/// Fields that must always be present (e.g. an error count that dashboards average over) can be
+/// kept with [`DropZerosSink::keep`]. Names are matched against the names as written, after any
+/// inflection.
+///
+/// # Example
+/// ```
+/// # use metrique_writer::{Entry, EntrySink, sink::DropZerosSink, test_util::test_entry_sink};
+/// #[derive(Entry)]
+/// #[entry(rename_all = "PascalCase")]
+/// struct RequestMetrics {
+///     retries: u64,
+///     throttles: u64,
+///     errors: u64,
+/// }
+///
+/// let test_sink = test_entry_sink();
+/// let sink = DropZerosSink::new(test_sink.sink).keep("Errors");
+///
+/// sink.append(RequestMetrics { retries: 2, throttles: 0, errors: 0 });
+///
+/// let entry = test_sink.inspector.get(0);
+/// assert_eq!(entry.metrics["Retries"], 2);
+/// assert!(!entry.metrics.contains_key("Throttles"));
+/// assert_eq!(entry.metrics["Errors"], 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DropZerosSink<S> {
+    sink: S,
+    keep: Arc<HashSet<Cow<'static, str>>>,
+}
+
+impl<S> DropZerosSink<S> {
+    /// Wrap `sink`, dropping every zero metric value.
+    pub fn new(sink: S) -> Self {
+        Self {
+            sink,
+            keep: Default::default(),
+        }
+    }
+
+    /// Always write the field called `name`, even if it is zero.
+    pub fn keep(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        Arc::make_mut(&mut self.keep).insert(name.into());
+        self
+    }
+}
+
+impl<E, S> EntrySink<E> for DropZerosSink<S>
+where
+    E: Entry,
+    S: EntrySink<DropZerosEntry<E>>,
+{
+    fn append(&self, entry: E) {
+        self.sink.append(DropZerosEntry {
+            entry,
+            keep: self.keep.clone(),
+        });
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        self.sink.flush_async()
+    }
+}
+
+/// An entry whose zero metric values are left out when written, as written by [`DropZerosSink`].
+#[derive(Clone, Debug)]
+pub struct DropZerosEntry<E> {
+    entry: E,
+    keep: Arc<HashSet<Cow<'static, str>>>,
+}
+
+impl<E> DropZerosEntry<E> {
+    /// Return the wrapped entry
+    pub fn into_inner(self) -> E {
+        self.entry
+    }
+}
+
+impl<E: Entry> Entry for DropZerosEntry<E> {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        self.entry.write(&mut DropZerosEntryWriter {
+            writer,
+            keep: &self.keep,
+        });
+    }
+
+    fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+        self.entry.sample_group()
+    }
+}
+
+struct DropZerosEntryWriter<'w, W> {
+    writer: &'w mut W,
+    keep: &'w HashSet<Cow<'static, str>>,
+}
+
+impl<'a, W: EntryWriter<'a>> EntryWriter<'a> for DropZerosEntryWriter<'_, W> {
+    fn timestamp(&mut self, timestamp: SystemTime) {
+        self.writer.timestamp(timestamp);
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        let name = name.into();
+        if !self.keep.contains(&*name) && is_zero(value) {
+            return;
+        }
+        self.writer.value(name, value);
+    }
+
+    fn config(&mut self, config: &'a dyn EntryConfig) {
+        self.writer.config(config);
+    }
+}
+
+fn is_zero(value: &(impl Value + ?Sized)) -> bool {
+    let mut zero = false;
+    value.write(ZeroCheckValueWriter { zero: &mut zero });
+    zero
+}
+
+/// A [`ValueWriter`] that records whether a value is a metric with only zero observations
+struct ZeroCheckValueWriter<'z> {
+    zero: &'z mut bool,
+}
+
+impl ValueWriter for ZeroCheckValueWriter<'_> {
+    fn string(self, _value: &str) {}
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        _unit: Unit,
+        _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        _flags: MetricFlags<'_>,
+    ) {
+        let mut any = false;
+        for observation in distribution {
+            let zero = match observation {
+                Observation::Unsigned(value) => value == 0,
+                Observation::Floating(value) => value == 0.0,
+                Observation::Repeated { total, .. } => total == 0.0,
+                _ => false,
+            };
+            if !zero {
+                return;
+            }
+            any = true;
+        }
+        *self.zero = any;
+    }
+
+    fn error(self, _error: ValidationError) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use metrique_writer_core::EntrySink;
+
+    use super::DropZerosSink;
+    use crate::{Entry, test_util::test_entry_sink, value::Distribution};
+
+    #[derive(Entry)]
+    #[entry(rename_all = "PascalCase")]
+    struct TestEntry {
+        operation: &'static str,
+        requests: u64,
+        retries: u64,
+        errors: u64,
+        latency: Duration,
+        ratio: f64,
+        samples: Distribution<u64, 2>,
+    }
+
+    fn entry(requests: u64, retries: u64, errors: u64) -> TestEntry {
+        TestEntry {
+            operation: "Get",
+            requests,
+            retries,
+            errors,
+            latency: Duration::ZERO,
+            ratio: 0.0,
+            samples: Distribution::from_iter([0, 0]),
+        }
+    }
+
+    #[test]
+    fn drops_zero_metrics() {
+        let test_sink = test_entry_sink();
+        let sink = DropZerosSink::new(test_sink.sink);
+
+        sink.append(entry(0, 0, 0));
+
+        let entry = test_sink.inspector.get(0);
+        assert_eq!(entry.values["Operation"], "Get");
+        assert!(entry.metrics.is_empty(), "{:?}", entry.metrics);
+    }
+
+    #[test]
+    fn keeps_allowlisted_zero_metrics() {
+        let test_sink = test_entry_sink();
+        let sink = DropZerosSink::new(test_sink.sink)
+            .keep("Errors")
+            .keep("Latency");
+
+        sink.append(entry(0, 0, 0));
+
+        let entry = test_sink.inspector.get(0);
+        assert_eq!(entry.metrics["Errors"], 0);
+        assert_eq!(entry.metrics["Latency"], 0);
+        assert!(!entry.metrics.contains_key("Requests"));
+        assert!(!entry.metrics.contains_key("Retries"));
+    }
+
+    #[test]
+    fn non_zero_metrics_pass_through() {
+        let test_sink = test_entry_sink();
+        let sink = DropZerosSink::new(test_sink.sink).keep("Errors");
+
+        sink.append(TestEntry {
+            latency: Duration::from_millis(5),
+            ratio: 0.5,
+            samples: Distribution::from_iter([0, 3]),
+            ..entry(1, 2, 0)
+        });
+
+        let entry = test_sink.inspector.get(0);
+        assert_eq!(entry.metrics["Requests"], 1);
+        assert_eq!(entry.metrics["Retries"], 2);
+        assert_eq!(entry.metrics["Errors"], 0);
+        assert_eq!(entry.metrics["Latency"], 5);
+        assert_eq!(entry.metrics["Ratio"], 0.5);
+        assert_eq!(entry.metrics["Samples"].distribution.len(), 2);
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::marker::PhantomData;
+
+use metrique_writer_core::{Entry, EntrySink, sink::FlushWait};
+
+/// An [`EntrySink`] that splits every entry appended to it into any number of sub-entries, and
+/// appends each of them to the wrapped sink. The original entry itself is not appended.
+///
+/// This is useful when one logical unit of work covers several operations, e.g. a gateway that
+/// proxies multiple operations in a single request, but each operation should be emitted as its
+/// own entry, with its own dimensions (see [`EntryDimensions`]). It is the runtime equivalent of
+/// an enum with one entry per variant. To send the sub-entries to different destinations (e.g.
+/// streams that publish to separate namespaces), make the sub-entry an enum and dispatch on it
+/// in the wrapped sink.
+///
+/// The split closure needs the concrete entry type, so this is a sink rather than an
+/// [`EntryIoStream`](crate::EntryIoStream) adapter, since streams accept entries of any type.
+///
+/// [`EntryDimensions`]: crate::config::EntryDimensions
+///
+/// # Example
+/// ```
+/// # use metrique_writer::{Entry, EntrySink, sink::FanOutSink, test_util::test_entry_sink};
+/// #[derive(Entry)]
+/// struct GatewayRequest {
+///     #[entry(ignore)]
+///     operations: Vec<(&'static str, u64)>,
+/// }
+///
+/// #[derive(Entry)]
+/// struct OperationMetrics {
+///     operation: &'static str,
+///     latency_ms: u64,
+/// }
+///
+/// let test_sink = test_entry_sink();
+/// let sink = FanOutSink::new(test_sink.sink, |request: &GatewayRequest| {
+///     request
+///         .operations
+///         .iter()
+///         .map(|&(operation, latency_ms)| OperationMetrics { operation, latency_ms })
+///         .collect()
+/// });
+///
+/// sink.append(GatewayRequest {
+///     operations: vec![("GetItem", 3), ("PutItem", 7)],
+/// });
+///
+/// let entries = test_sink.inspector.entries();
+/// assert_eq!(entries.len(), 2);
+/// assert_eq!(entries[0].values["operation"], "GetItem");
+/// assert_eq!(entries[1].metrics["latency_ms"], 7);
+/// ```
+pub struct FanOutSink<S, F, Sub> {
+    sink: S,
+    split: F,
+    sub: PhantomData<fn() -> Sub>,
+}
+
+impl<S, F, Sub> FanOutSink<S, F, Sub> {
+    /// Wrap `sink`, appending the sub-entries returned by `split` for every entry
+    pub fn new<E>(sink: S, split: F) -> Self
+    where
+        F: Fn(&E) -> Vec<Sub>,
+    {
+        Self {
+            sink,
+            split,
+            sub: PhantomData,
+        }
+    }
+
+    /// Return the wrapped sink
+    pub fn into_inner(self) -> S {
+        self.sink
+    }
+}
+
+impl<S: Clone, F: Clone, Sub> Clone for FanOutSink<S, F, Sub> {
+    fn clone(&self) -> Self {
+        Self {
+            sink: self.sink.clone(),
+            split: self.split.clone(),
+            sub: PhantomData,
+        }
+    }
+}
+
+impl<S: std::fmt::Debug, F, Sub> std::fmt::Debug for FanOutSink<S, F, Sub> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FanOutSink")
+            .field("sink", &self.sink)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<E, S, F, Sub> EntrySink<E> for FanOutSink<S, F, Sub>
+where
+    E: Entry,
+    Sub: Entry,
+    S: EntrySink<Sub>,
+    F: Fn(&E) -> Vec<Sub>,
+{
+    fn append(&self, entry: E) {
+        for sub in (self.split)(&entry) {
+            self.sink.append(sub);
+        }
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        self.sink.flush_async()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrique_writer_core::EntrySink;
+
+    use super::FanOutSink;
+    use crate::{Entry, test_util::test_entry_sink};
+
+    #[derive(Entry)]
+    struct Request {
+        operations: usize,
+    }
+
+    #[derive(Entry)]
+    struct OperationEntry {
+        index: usize,
+    }
+
+    #[test]
+    fn splits_into_n_entries() {
+        let test_sink = test_entry_sink();
+        let sink = FanOutSink::new(test_sink.sink, |request: &Request| {
+            (0..request.operations)
+                .map(|index| OperationEntry { index })
+                .collect()
+        });
+
+        sink.append(Request { operations: 3 });
+        sink.append(Request { operations: 0 });
+        sink.append(Request { operations: 2 });
+        futures::executor::block_on(EntrySink::<Request>::flush_async(&sink));
+
+        let entries = test_sink.inspector.entries();
+        let indexes: Vec<_> = entries
+            .iter()
+            .map(|e| e.metrics["index"].as_u64())
+            .collect();
+        assert_eq!(indexes, [0, 1, 2, 0, 1]);
+    }
+}
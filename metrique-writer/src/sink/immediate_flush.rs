@@ -1,7 +1,14 @@
 // Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{marker::PhantomData, sync::Arc, time::Instant};
+use std::{
+    marker::PhantomData,
+    sync::{
+        Arc, Condvar, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
 
 use metrique_writer_core::entry::BoxEntry;
 
@@ -23,6 +30,7 @@ use super::{
 pub struct FlushImmediatelyBuilder {
     metric_name: Option<String>,
     observer: Option<Box<dyn FlushImmediatelyObserver>>,
+    coalesce_window: Option<Duration>,
 }
 
 impl FlushImmediatelyBuilder {
@@ -39,6 +47,29 @@ impl FlushImmediatelyBuilder {
         self
     }
 
+    /// Coalesce the flushes of entries appended within `window` of each other.
+    ///
+    /// By default, every [`append`](EntrySink::append) writes the entry and flushes the stream
+    /// before returning. With a coalescing window, an `append` that runs while other appends
+    /// are in progress writes its entry and waits for `window` to pass, and entries appended in
+    /// the meantime are written to the stream and wait for that same flush. An `append` with no
+    /// other append in progress flushes right away, since there is nothing to coalesce it with.
+    /// Every `append` still returns only once its entry has been flushed, so the durability
+    /// guarantee is unchanged.
+    ///
+    /// This trades latency for throughput: under concurrent load each `append` can take up to
+    /// `window` longer, but a burst of entries costs a single flush.
+    ///
+    /// Only the flushes are coalesced. Each entry is still formatted and written to the stream
+    /// on its own as it is appended.
+    ///
+    /// [`flush_async`](EntrySink::flush_async) flushes the entries whose window is still open
+    /// before returning, so the returned [`FlushWait`] is always ready.
+    pub fn coalesce_window(mut self, window: Duration) -> Self {
+        self.coalesce_window = Some(window);
+        self
+    }
+
     /// Send lifecycle events from the sink to a user-provided observer.
     ///
     /// Use this to capture per-flush timing from any observability backend, not just
@@ -171,13 +202,7 @@ impl FlushImmediatelyBuilder {
     /// Build a [`FlushImmediately`] for writing metric entries of type `T` to the given stream.
     pub fn build<T: Entry, S: EntryIoStream>(self, stream: S) -> FlushImmediately<T, S> {
         FlushImmediately {
-            stream: Arc::new(std::sync::Mutex::new(SinkState {
-                stream,
-                name: self
-                    .metric_name
-                    .unwrap_or_else(|| "immediate-flush".to_string()),
-                observer: self.observer,
-            })),
+            stream: Arc::new(self.shared(stream)),
             _phantom: PhantomData,
         }
     }
@@ -194,25 +219,118 @@ impl FlushImmediatelyBuilder {
     /// Build an [`AnyFlushImmediately`] for writing metric entries of any type that impls [`Entry`].
     pub fn build_any<S: EntryIoStream>(self, stream: S) -> AnyFlushImmediately<S> {
         AnyFlushImmediately {
-            stream: Arc::new(std::sync::Mutex::new(SinkState {
+            stream: Arc::new(self.shared(stream)),
+        }
+    }
+
+    fn shared<S>(self, stream: S) -> SharedState<S> {
+        SharedState {
+            state: Mutex::new(SinkState {
                 stream,
                 name: self
                     .metric_name
                     .unwrap_or_else(|| "immediate-flush".to_string()),
                 observer: self.observer,
-            })),
+                written: 0,
+                flushed: 0,
+                coalescing: false,
+            }),
+            flushed: Condvar::new(),
+            coalesce_window: self.coalesce_window,
+            appending: AtomicUsize::new(0),
         }
     }
 }
 
+struct SharedState<S> {
+    state: Mutex<SinkState<S>>,
+    /// Notified when a coalesced flush completes
+    flushed: Condvar,
+    coalesce_window: Option<Duration>,
+    /// Number of appends in progress, when coalescing
+    appending: AtomicUsize,
+}
+
+impl<S: EntryIoStream> SharedState<S> {
+    fn append<E: Entry>(&self, entry: &E) {
+        let Some(window) = self.coalesce_window else {
+            let mut state = self.state.lock().unwrap();
+            state.write(entry);
+            // Flush after each write to ensure entries are written immediately
+            state.flush();
+            return;
+        };
+
+        let _appending = AppendingGuard::new(&self.appending);
+        let mut state = self.state.lock().unwrap();
+        state.write(entry);
+        state.written += 1;
+        let position = state.written;
+        if state.coalescing {
+            // the append that opened the window flushes this entry once the window closes
+            let _state = self
+                .flushed
+                .wait_while(state, |state| state.flushed < position)
+                .unwrap();
+            return;
+        }
+        if self.appending.load(Ordering::Relaxed) == 1 {
+            // no other append is in progress, so there is nothing to wait for
+            state.flush_coalesced();
+            return;
+        }
+
+        state.coalescing = true;
+        drop(state);
+        std::thread::sleep(window);
+        let mut state = self.state.lock().unwrap();
+        state.flush_coalesced();
+        state.coalescing = false;
+        drop(state);
+        self.flushed.notify_all();
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        if self.coalesce_window.is_some() {
+            // flush the entries whose window is still open rather than waiting for it to close
+            self.state.lock().unwrap().flush_coalesced();
+            self.flushed.notify_all();
+        }
+        // Otherwise, since we flush after each append, this is a no-op
+        FlushWait::ready()
+    }
+}
+
+/// Counts an append as in progress until dropped
+struct AppendingGuard<'a>(&'a AtomicUsize);
+
+impl<'a> AppendingGuard<'a> {
+    fn new(appending: &'a AtomicUsize) -> Self {
+        appending.fetch_add(1, Ordering::Relaxed);
+        Self(appending)
+    }
+}
+
+impl Drop for AppendingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 struct SinkState<S> {
     stream: S,
     name: String,
     observer: Option<Box<dyn FlushImmediatelyObserver>>,
+    /// Number of entries written to the stream, when coalescing
+    written: u64,
+    /// Number of entries flushed, when coalescing
+    flushed: u64,
+    /// Whether an append is waiting for the coalescing window to close
+    coalescing: bool,
 }
 
 impl<S: EntryIoStream> SinkState<S> {
-    fn append<E: Entry>(&mut self, entry: &E) {
+    fn write<E: Entry>(&mut self, entry: &E) {
         match self.stream.next(entry) {
             Ok(()) => {}
             Err(IoStreamError::Validation(err)) => {
@@ -231,9 +349,14 @@ impl<S: EntryIoStream> SinkState<S> {
                 }
             }
         }
+    }
 
-        // Flush after each write to ensure entries are written immediately
-        self.flush();
+    /// Flush the entries written since the last coalesced flush, if any
+    fn flush_coalesced(&mut self) {
+        if self.flushed < self.written {
+            self.flush();
+            self.flushed = self.written;
+        }
     }
 
     fn flush(&mut self) {
@@ -269,7 +392,8 @@ impl<S: EntryIoStream> SinkState<S> {
 ///
 /// This sink is designed for simplicity and reliability, not high performance. Each call to
 /// [`append`](EntrySink::append) will block while the entry is written to the output stream.
-/// For high-throughput applications, consider using [`BackgroundQueue`](super::BackgroundQueue) instead.
+/// For high-throughput applications, consider using [`BackgroundQueue`](super::BackgroundQueue) instead,
+/// or [coalescing](FlushImmediatelyBuilder::coalesce_window) the flushes of bursts of entries.
 ///
 /// # Example
 ///
@@ -297,7 +421,7 @@ impl<S: EntryIoStream> SinkState<S> {
 /// ```
 #[derive(Clone)]
 pub struct FlushImmediately<T, S> {
-    stream: Arc<std::sync::Mutex<SinkState<S>>>,
+    stream: Arc<SharedState<S>>,
     _phantom: PhantomData<fn(T)>,
 }
 
@@ -325,13 +449,11 @@ impl FlushImmediately<(), ()> {
 
 impl<T: Entry, S: EntryIoStream> EntrySink<T> for FlushImmediately<T, S> {
     fn append(&self, entry: T) {
-        let mut state = self.stream.lock().unwrap();
-        state.append(&entry);
+        self.stream.append(&entry);
     }
 
     fn flush_async(&self) -> FlushWait {
-        // Since we flush after each append, this is a no-op
-        FlushWait::ready()
+        self.stream.flush_async()
     }
 }
 
@@ -341,7 +463,7 @@ impl<T: Entry, S: EntryIoStream> EntrySink<T> for FlushImmediately<T, S> {
 /// known, prefer [`FlushImmediately`] instead.
 #[derive(Clone)]
 pub struct AnyFlushImmediately<S> {
-    stream: Arc<std::sync::Mutex<SinkState<S>>>,
+    stream: Arc<SharedState<S>>,
 }
 
 impl<S: EntryIoStream> AnyFlushImmediately<S> {
@@ -358,13 +480,11 @@ impl<S: EntryIoStream> AnyFlushImmediately<S> {
 
 impl<S: EntryIoStream> AnyEntrySink for AnyFlushImmediately<S> {
     fn append_any(&self, entry: impl Entry + Send + 'static) {
-        let mut state = self.stream.lock().unwrap();
-        state.append(&entry);
+        self.stream.append(&entry);
     }
 
     fn flush_async(&self) -> FlushWait {
-        // Since we flush after each append, this is a no-op
-        FlushWait::ready()
+        self.stream.flush_async()
     }
 }
 
@@ -516,6 +636,81 @@ mod tests {
         assert_eq!(output.lock().unwrap().values, vec![1]);
     }
 
+    #[test]
+    fn coalesces_flushes_of_concurrent_appends() {
+        let output: Arc<Mutex<TestStream>> = Default::default();
+        let sink = FlushImmediatelyBuilder::new()
+            .coalesce_window(Duration::from_millis(200))
+            .build::<TestEntry, _>(Arc::clone(&output));
+
+        std::thread::scope(|s| {
+            // hold up the writes until every append is in progress
+            let blocked = output.lock().unwrap();
+            for i in 0..8 {
+                let (sink, output) = (&sink, &output);
+                s.spawn(move || {
+                    sink.append(TestEntry(i));
+                    // the entry is flushed by the time append returns
+                    let output = output.lock().unwrap();
+                    let position = output.values.iter().position(|v| *v == i).unwrap();
+                    assert!(position < output.values_flushed);
+                });
+            }
+            while sink.stream.appending.load(Ordering::Relaxed) < 8 {
+                std::thread::yield_now();
+            }
+            drop(blocked);
+        });
+
+        let output = output.lock().unwrap();
+        assert_eq!(output.values.len(), 8);
+        assert_eq!(output.values_flushed, 8);
+        assert!(output.flushes < 8, "{} flushes", output.flushes);
+    }
+
+    #[test]
+    fn flush_async_flushes_coalesced_entries() {
+        let output: Arc<Mutex<TestStream>> = Default::default();
+        let sink = FlushImmediatelyBuilder::new()
+            .coalesce_window(Duration::from_millis(500))
+            .build_any(Arc::clone(&output));
+
+        // simulate another append in progress, so that the window is opened
+        let _other = AppendingGuard::new(&sink.stream.appending);
+        std::thread::scope(|s| {
+            let appender = s.spawn(|| sink.append_any(TestEntry(1)));
+            while output.lock().unwrap().values.is_empty() {
+                std::thread::yield_now();
+            }
+            assert_eq!(output.lock().unwrap().values_flushed, 0);
+
+            futures::executor::block_on(AnyEntrySink::flush_async(&sink));
+            assert_eq!(output.lock().unwrap().values_flushed, 1);
+            assert_eq!(output.lock().unwrap().flushes, 1);
+
+            // the window closing doesn't flush again, since there is nothing new to flush
+            appender.join().unwrap();
+            assert_eq!(output.lock().unwrap().flushes, 1);
+        });
+    }
+
+    #[test]
+    fn does_not_wait_for_the_window_without_concurrent_appends() {
+        let output: Arc<Mutex<TestStream>> = Default::default();
+        let sink = FlushImmediatelyBuilder::new()
+            .coalesce_window(Duration::from_secs(60))
+            .build::<TestEntry, _>(Arc::clone(&output));
+
+        let start = Instant::now();
+        sink.append(TestEntry(1));
+        sink.append(TestEntry(2));
+        assert!(start.elapsed() < Duration::from_secs(30));
+
+        let output = output.lock().unwrap();
+        assert_eq!(output.values_flushed, 2);
+        assert_eq!(output.flushes, 2);
+    }
+
     #[cfg(feature = "metrics-rs-024")]
     #[test]
     fn metrics_recorder_works() {
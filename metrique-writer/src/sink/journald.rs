@@ -0,0 +1,498 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    borrow::Cow,
+    fmt::Write as _,
+    io,
+    os::unix::net::UnixDatagram,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use metrique_writer_core::{
+    Entry, EntryConfig, EntryWriter, MetricFlags, Observation, Unit, ValidationError, Value,
+    ValueWriter, sink::FlushWait,
+};
+
+use crate::{AnyEntrySink, rate_limit::rate_limited};
+
+const JOURNALD_SOCKET: &str = "/run/systemd/journal/socket";
+// journald silently truncates longer field names
+const MAX_FIELD_NAME_LEN: usize = 64;
+
+/// An [`AnyEntrySink`] that writes each entry to the systemd journal as a journal entry with
+/// structured fields.
+///
+/// Entries are sent using journald's [native protocol], the same one used by
+/// `sd_journal_send`, over its datagram socket. Each appended entry is sent immediately from
+/// the appending thread, which is cheap for a local datagram socket.
+///
+/// Fields are mapped as follows:
+/// - Field names are converted to valid journal field names: they are upper-cased, characters
+///   other than `A-Z`, `0-9` and `_` are replaced with `_`, and leading underscores (which are
+///   reserved for trusted fields) are removed.
+/// - String values are written as-is.
+/// - Metric values write their observations, separated by `,`. A repeated observation is written
+///   as its total. If the metric has a unit, it is written in a `<NAME>_UNIT` field, and each of
+///   its dimensions is written in a `<NAME>_<DIMENSION>` field.
+/// - The timestamp, if any, is written in the `TIMESTAMP` field as milliseconds since the epoch.
+///
+/// Every entry also gets a `MESSAGE` field (see [`JournaldSink::with_message`]), and, if set,
+/// a `SYSLOG_IDENTIFIER` field.
+///
+/// Entries that fail validation are dropped, and a [`tracing`] error is emitted periodically.
+/// Entries that are too large for a single datagram (usually around 200KiB) are also dropped.
+///
+/// # Example
+/// ```no_run
+/// # use metrique_writer::{Entry, EntrySink, sink::JournaldSink};
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     operation: &'static str,
+///     latency_ms: u64,
+/// }
+///
+/// let sink = JournaldSink::connect()
+///     .unwrap()
+///     .with_syslog_identifier("my-service");
+/// // written as OPERATION=Get, LATENCY_MS=12
+/// sink.append(RequestMetrics { operation: "Get", latency_ms: 12 });
+/// ```
+///
+/// [native protocol]: https://systemd.io/JOURNAL_NATIVE_PROTOCOL/
+#[derive(Clone)]
+pub struct JournaldSink {
+    socket: Arc<UnixDatagram>,
+    path: Arc<PathBuf>,
+    common_fields: Arc<Vec<u8>>,
+    message: Cow<'static, str>,
+    syslog_identifier: Option<Cow<'static, str>>,
+}
+
+impl std::fmt::Debug for JournaldSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JournaldSink")
+            .field("path", &self.path)
+            .field("message", &self.message)
+            .field("syslog_identifier", &self.syslog_identifier)
+            .finish()
+    }
+}
+
+impl JournaldSink {
+    /// Create a sink writing to the journal at its default socket, `/run/systemd/journal/socket`.
+    ///
+    /// This does not check that journald is running. If it isn't, appended entries are dropped
+    /// and a [`tracing`] error is emitted periodically.
+    pub fn connect() -> io::Result<Self> {
+        Self::connect_to(JOURNALD_SOCKET)
+    }
+
+    /// Create a sink writing to a journal listening on the datagram socket at `path`.
+    pub fn connect_to(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut sink = Self {
+            socket: Arc::new(UnixDatagram::unbound()?),
+            path: Arc::new(path.as_ref().to_owned()),
+            common_fields: Arc::default(),
+            message: Cow::Borrowed("metrics"),
+            syslog_identifier: None,
+        };
+        sink.update_common_fields();
+        Ok(sink)
+    }
+
+    /// Set the `MESSAGE` field written on every entry, `"metrics"` by default.
+    ///
+    /// This is what `journalctl` displays for the entry by default.
+    pub fn with_message(mut self, message: impl Into<Cow<'static, str>>) -> Self {
+        self.message = message.into();
+        self.update_common_fields();
+        self
+    }
+
+    /// Set the `SYSLOG_IDENTIFIER` field written on every entry, which `journalctl -t` filters on.
+    pub fn with_syslog_identifier(mut self, identifier: impl Into<Cow<'static, str>>) -> Self {
+        self.syslog_identifier = Some(identifier.into());
+        self.update_common_fields();
+        self
+    }
+
+    fn update_common_fields(&mut self) {
+        let mut fields = Vec::new();
+        push_field(&mut fields, "MESSAGE", &self.message);
+        // informational
+        push_field(&mut fields, "PRIORITY", "6");
+        if let Some(identifier) = &self.syslog_identifier {
+            push_field(&mut fields, "SYSLOG_IDENTIFIER", identifier);
+        }
+        self.common_fields = Arc::new(fields);
+    }
+
+    fn encode(&self, entry: &impl Entry) -> Result<Vec<u8>, ValidationError> {
+        let mut writer = JournalEntryWriter {
+            buf: (*self.common_fields).clone(),
+            name: String::new(),
+            error: None,
+        };
+        entry.write(&mut writer);
+        match writer.error {
+            Some(error) => Err(error),
+            None => Ok(writer.buf),
+        }
+    }
+}
+
+impl AnyEntrySink for JournaldSink {
+    fn append_any(&self, entry: impl Entry + Send + 'static) {
+        let payload = match self.encode(&entry) {
+            Ok(payload) => payload,
+            Err(error) => {
+                rate_limited!(
+                    Duration::from_secs(1),
+                    tracing::error!(?error, "dropping invalid metric entry for journald")
+                );
+                return;
+            }
+        };
+        if let Err(error) = self.socket.send_to(&payload, &*self.path) {
+            rate_limited!(
+                Duration::from_secs(1),
+                tracing::error!(?error, path = ?self.path, "failed to send metric entry to journald")
+            );
+        }
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        // entries are sent as they are appended
+        FlushWait::ready()
+    }
+}
+
+/// Convert `name` into a valid journal field name, appending it to `out`
+fn push_field_name(out: &mut String, name: &str) {
+    let start = out.len();
+    for c in name.trim_start_matches('_').chars() {
+        if out.len() - start >= MAX_FIELD_NAME_LEN {
+            break;
+        }
+        out.push(match c {
+            'a'..='z' => c.to_ascii_uppercase(),
+            'A'..='Z' | '0'..='9' | '_' => c,
+            _ => '_',
+        });
+    }
+    // field names can't be empty or start with a digit
+    if out[start..]
+        .chars()
+        .next()
+        .is_none_or(|c| c.is_ascii_digit())
+    {
+        out.insert(start, 'M');
+        out.truncate(start + MAX_FIELD_NAME_LEN);
+    }
+}
+
+/// Append a field in the journal native protocol format. `name` must already be a valid field name.
+fn push_field(buf: &mut Vec<u8>, name: &str, value: &str) {
+    buf.extend_from_slice(name.as_bytes());
+    if value.contains('\n') {
+        // values containing newlines use the binary format: the value is preceded by its length
+        buf.push(b'\n');
+        buf.extend_from_slice(&(value.len() as u64).to_le_bytes());
+    } else {
+        buf.push(b'=');
+    }
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(b'\n');
+}
+
+struct JournalEntryWriter {
+    buf: Vec<u8>,
+    // reused buffer for the current field name
+    name: String,
+    error: Option<ValidationError>,
+}
+
+impl<'a> EntryWriter<'a> for JournalEntryWriter {
+    fn timestamp(&mut self, timestamp: SystemTime) {
+        let millis = timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        push_field(&mut self.buf, "TIMESTAMP", &millis.to_string());
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        let name = name.into();
+        self.name.clear();
+        push_field_name(&mut self.name, &name);
+        value.write(JournalValueWriter {
+            entry: self,
+            original_name: &name,
+        });
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {}
+}
+
+struct JournalValueWriter<'w> {
+    entry: &'w mut JournalEntryWriter,
+    original_name: &'w str,
+}
+
+impl ValueWriter for JournalValueWriter<'_> {
+    fn string(self, value: &str) {
+        push_field(&mut self.entry.buf, &self.entry.name, value);
+    }
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        unit: Unit,
+        dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        _flags: MetricFlags<'_>,
+    ) {
+        let entry = self.entry;
+        let mut value = String::new();
+        for observation in distribution {
+            let start = value.len();
+            if start > 0 {
+                value.push(',');
+            }
+            let _ = match observation {
+                Observation::Unsigned(v) => write!(value, "{v}"),
+                Observation::Floating(v) => write!(value, "{v}"),
+                Observation::Repeated { total, .. } => write!(value, "{total}"),
+                _ => {
+                    // skip observation kinds this sink doesn't know how to write
+                    value.truncate(start);
+                    Ok(())
+                }
+            };
+        }
+        push_field(&mut entry.buf, &entry.name, &value);
+
+        let base_len = entry.name.len();
+        if unit != Unit::None {
+            entry.name.push_str("_UNIT");
+            push_field(&mut entry.buf, &entry.name, unit.name());
+            entry.name.truncate(base_len);
+        }
+        for (dimension, dimension_value) in dimensions {
+            entry.name.push('_');
+            push_field_name(&mut entry.name, dimension);
+            entry.name.truncate(MAX_FIELD_NAME_LEN);
+            push_field(&mut entry.buf, &entry.name, dimension_value);
+            entry.name.truncate(base_len);
+        }
+    }
+
+    fn error(self, error: ValidationError) {
+        if self.entry.error.is_none() {
+            self.entry.error = Some(error.for_field(self.original_name));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, os::unix::net::UnixDatagram, time::Duration};
+
+    use metrique_writer_core::{EntrySink, EntryWriter, Unit, ValueWriter, unit::NegativeScale};
+
+    use super::{JournaldSink, push_field_name};
+    use crate::{Entry, value::MetricFlags};
+
+    /// Parse a payload in the journal native protocol format
+    fn parse(mut payload: &[u8]) -> Vec<(String, String)> {
+        let mut fields = vec![];
+        while !payload.is_empty() {
+            let line_end = payload.iter().position(|&b| b == b'\n').unwrap();
+            let line = std::str::from_utf8(&payload[..line_end]).unwrap();
+            payload = &payload[line_end + 1..];
+            if let Some((name, value)) = line.split_once('=') {
+                fields.push((name.to_owned(), value.to_owned()));
+            } else {
+                let len = u64::from_le_bytes(payload[..8].try_into().unwrap()) as usize;
+                let value = std::str::from_utf8(&payload[8..8 + len]).unwrap();
+                assert_eq!(payload[8 + len], b'\n');
+                payload = &payload[8 + len + 1..];
+                fields.push((line.to_owned(), value.to_owned()));
+            }
+        }
+        fields
+    }
+
+    struct TestJournal {
+        _dir: tempfile::TempDir,
+        socket: UnixDatagram,
+        sink: JournaldSink,
+    }
+
+    impl TestJournal {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let path = dir.path().join("journal.socket");
+            let socket = UnixDatagram::bind(&path).unwrap();
+            socket
+                .set_read_timeout(Some(Duration::from_secs(5)))
+                .unwrap();
+            let sink = JournaldSink::connect_to(&path).unwrap();
+            Self {
+                _dir: dir,
+                socket,
+                sink,
+            }
+        }
+
+        fn recv(&self) -> Vec<(String, String)> {
+            let mut buf = vec![0; 64 * 1024];
+            let len = self.socket.recv(&mut buf).unwrap();
+            parse(&buf[..len])
+        }
+    }
+
+    fn field<'a>(fields: &'a [(String, String)], name: &str) -> Option<&'a str> {
+        fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    #[derive(Entry)]
+    struct RequestMetrics {
+        #[entry(timestamp)]
+        timestamp: std::time::SystemTime,
+        operation: &'static str,
+        #[entry(name = "Latency")]
+        latency: Duration,
+        request_count: u64,
+        error: Option<String>,
+    }
+
+    #[test]
+    fn writes_structured_fields() {
+        let journal = TestJournal::new();
+        let sink = journal.sink.clone().with_syslog_identifier("my-service");
+
+        sink.append(RequestMetrics {
+            timestamp: std::time::UNIX_EPOCH + Duration::from_millis(1234),
+            operation: "Get",
+            latency: Duration::from_millis(12),
+            request_count: 3,
+            error: Some("line 1\nline 2".into()),
+        });
+
+        let fields = journal.recv();
+        assert_eq!(field(&fields, "MESSAGE"), Some("metrics"));
+        assert_eq!(field(&fields, "PRIORITY"), Some("6"));
+        assert_eq!(field(&fields, "SYSLOG_IDENTIFIER"), Some("my-service"));
+        assert_eq!(field(&fields, "TIMESTAMP"), Some("1234"));
+        assert_eq!(field(&fields, "OPERATION"), Some("Get"));
+        assert_eq!(field(&fields, "LATENCY"), Some("12"));
+        assert_eq!(field(&fields, "LATENCY_UNIT"), Some("Milliseconds"));
+        assert_eq!(field(&fields, "REQUEST_COUNT"), Some("3"));
+        assert_eq!(field(&fields, "REQUEST_COUNT_UNIT"), None);
+        assert_eq!(field(&fields, "ERROR"), Some("line 1\nline 2"));
+    }
+
+    struct Distribution;
+
+    impl metrique_writer_core::Value for Distribution {
+        fn write(&self, writer: impl ValueWriter) {
+            writer.metric(
+                [
+                    metrique_writer_core::Observation::Unsigned(1),
+                    metrique_writer_core::Observation::Floating(2.5),
+                    metrique_writer_core::Observation::Repeated {
+                        total: 10.0,
+                        occurrences: 4,
+                    },
+                ],
+                Unit::Second(NegativeScale::Micro),
+                [("Availability Zone", "use1-az1"), ("host", "a")],
+                MetricFlags::empty(),
+            )
+        }
+    }
+
+    struct DistributionEntry;
+
+    impl Entry for DistributionEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value(Cow::Borrowed("my.metric"), &Distribution);
+        }
+    }
+
+    #[test]
+    fn writes_observations_units_and_dimensions() {
+        let journal = TestJournal::new();
+        journal
+            .sink
+            .clone()
+            .with_message("request metrics")
+            .append(DistributionEntry);
+
+        let fields = journal.recv();
+        assert_eq!(field(&fields, "MESSAGE"), Some("request metrics"));
+        assert_eq!(field(&fields, "SYSLOG_IDENTIFIER"), None);
+        assert_eq!(field(&fields, "MY_METRIC"), Some("1,2.5,10"));
+        assert_eq!(field(&fields, "MY_METRIC_UNIT"), Some("Microseconds"));
+        assert_eq!(
+            field(&fields, "MY_METRIC_AVAILABILITY_ZONE"),
+            Some("use1-az1")
+        );
+        assert_eq!(field(&fields, "MY_METRIC_HOST"), Some("a"));
+    }
+
+    struct InvalidEntry;
+
+    impl Entry for InvalidEntry {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value(
+                "Bad",
+                &metrique_writer_core::Observation::Floating(f64::NAN),
+            );
+            writer.value("Invalid", &InvalidValue);
+        }
+    }
+
+    struct InvalidValue;
+
+    impl metrique_writer_core::Value for InvalidValue {
+        fn write(&self, writer: impl ValueWriter) {
+            writer.invalid("nope");
+        }
+    }
+
+    #[test]
+    fn drops_invalid_entries() {
+        let journal = TestJournal::new();
+        journal.sink.append(InvalidEntry);
+        journal.sink.append(DistributionEntry);
+
+        // only the valid entry is received
+        let fields = journal.recv();
+        assert_eq!(field(&fields, "MY_METRIC"), Some("1,2.5,10"));
+        assert!(field(&fields, "INVALID").is_none());
+    }
+
+    #[test]
+    fn field_names_are_sanitized() {
+        let sanitize = |name: &str| {
+            let mut out = String::new();
+            push_field_name(&mut out, name);
+            out
+        };
+        assert_eq!(sanitize("RequestCount"), "REQUESTCOUNT");
+        assert_eq!(sanitize("request-count"), "REQUEST_COUNT");
+        assert_eq!(sanitize("__private"), "PRIVATE");
+        assert_eq!(sanitize("2xx"), "M2XX");
+        assert_eq!(sanitize(""), "M");
+        assert_eq!(sanitize("ünïcode"), "_N_CODE");
+        assert_eq!(sanitize(&"a".repeat(100)), "A".repeat(64));
+        assert_eq!(sanitize(&"1".repeat(100)).len(), 64);
+    }
+}
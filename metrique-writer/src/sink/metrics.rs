@@ -246,6 +246,10 @@ impl<R: MetricRecorder> BackgroundQueueObserver for BackgroundQueueMetricsRsObse
                 self.0
                     .increment_counter("metrique_validation_errors", queue, count);
             }
+            BackgroundQueueEvent::MetricsSampledOut { count } => {
+                self.0
+                    .increment_counter("metrique_metrics_sampled_out", queue, count);
+            }
             BackgroundQueueEvent::FlushComplete {
                 idle_percent,
                 queue_len,
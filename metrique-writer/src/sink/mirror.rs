@@ -0,0 +1,140 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use metrique_writer_core::{Entry, EntrySink, sink::FlushWait};
+use rand::Rng;
+
+/// An [`EntrySink`] that appends every entry to a primary sink, and additionally appends a
+/// clone of a random fraction of entries to a secondary sink.
+///
+/// This is useful to validate a new pipeline (e.g. a staging destination) against a sample of
+/// production traffic, while production still receives every entry.
+///
+/// Note that, unlike the [samplers](crate::sample), mirrored entries are *not* upweighted, so
+/// aggregate statistics computed from the secondary sink only reflect the mirrored fraction.
+///
+/// # Example
+/// ```
+/// # use metrique_writer::{Entry, EntrySink, sink::MirrorSink, test_util::test_entry_sink};
+/// #[derive(Entry, Clone)]
+/// struct RequestMetrics {
+///     operation: &'static str,
+/// }
+///
+/// let production = test_entry_sink();
+/// let staging = test_entry_sink();
+/// // mirror 1% of entries to staging
+/// let sink = MirrorSink::new(production.sink, staging.sink, 0.01);
+///
+/// for _ in 0..100 {
+///     sink.append(RequestMetrics { operation: "Foo" });
+/// }
+/// assert_eq!(production.inspector.entries().len(), 100);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MirrorSink<P, S> {
+    primary: P,
+    secondary: S,
+    rate: f32,
+}
+
+impl<P, S> MirrorSink<P, S> {
+    /// Create a new [`MirrorSink`] that appends every entry to `primary`, and a clone of each
+    /// entry to `secondary` with probability `rate`.
+    ///
+    /// A `rate` of 0 never mirrors and a `rate` of 1 mirrors every entry.
+    ///
+    /// # Panics
+    /// Panics if `rate` is not within `[0, 1]`.
+    pub fn new(primary: P, secondary: S, rate: f32) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&rate),
+            "mirror rate must be within [0, 1], got {rate}"
+        );
+        Self {
+            primary,
+            secondary,
+            rate,
+        }
+    }
+}
+
+impl<E, P, S> EntrySink<E> for MirrorSink<P, S>
+where
+    E: Entry + Clone,
+    P: EntrySink<E>,
+    S: EntrySink<E>,
+{
+    fn append(&self, entry: E) {
+        // random() is in [0, 1), so a rate of 1 always mirrors and a rate of 0 never does
+        if self.rate > 0.0 && rand::rng().random::<f32>() < self.rate {
+            self.secondary.append(entry.clone());
+        }
+        self.primary.append(entry);
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        let primary = self.primary.flush_async();
+        let secondary = self.secondary.flush_async();
+        FlushWait::from_future(async move {
+            primary.await;
+            secondary.await;
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrique_writer_core::EntrySink;
+
+    use super::MirrorSink;
+    use crate::{Entry, test_util::test_entry_sink};
+
+    #[derive(Entry, Clone)]
+    struct TestEntry {
+        id: u64,
+    }
+
+    fn run(rate: f32, count: u64) -> (usize, usize) {
+        let primary = test_entry_sink();
+        let secondary = test_entry_sink();
+        let sink = MirrorSink::new(primary.sink, secondary.sink, rate);
+        for id in 0..count {
+            sink.append(TestEntry { id });
+        }
+        futures::executor::block_on(EntrySink::<TestEntry>::flush_async(&sink));
+
+        let primary = primary.inspector.entries();
+        // the primary receives everything, in order
+        assert!(
+            primary
+                .iter()
+                .map(|e| e.metrics["id"].as_u64())
+                .eq(0..count)
+        );
+        (primary.len(), secondary.inspector.entries().len())
+    }
+
+    #[test]
+    fn mirrors_a_fraction_of_entries() {
+        let (primary, secondary) = run(0.1, 20_000);
+        assert_eq!(primary, 20_000);
+        // expected 2000, with a standard deviation of ~42
+        assert!(
+            (1700..=2300).contains(&secondary),
+            "mirrored {secondary} entries"
+        );
+    }
+
+    #[test]
+    fn rate_bounds() {
+        assert_eq!(run(0.0, 1000), (1000, 0));
+        assert_eq!(run(1.0, 1000), (1000, 1000));
+    }
+
+    #[test]
+    #[should_panic(expected = "mirror rate must be within [0, 1]")]
+    fn invalid_rate_panics() {
+        let _ = MirrorSink::new((), (), 1.5);
+    }
+}
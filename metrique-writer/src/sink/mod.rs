@@ -3,32 +3,65 @@
 
 //! Contains various utilities for working with [EntrySink]
 
-use std::sync::{Arc, Mutex};
+use std::sync::{
+    Arc, Mutex,
+    atomic::{AtomicU64, Ordering},
+};
 
 use crate::Entry;
 
+mod ack;
+mod audit;
 #[cfg(feature = "background-queue")]
 mod background;
+mod cardinality;
+mod computed;
+mod counter_delta;
+mod drop_zeros;
+mod fan_out;
 mod immediate_flush;
+#[cfg(all(feature = "journald", unix))]
+mod journald;
 mod metrics;
+mod mirror;
 mod observer;
+mod routing;
+mod tee;
+#[cfg(feature = "version-sink")]
+mod version;
 
+pub use ack::{AckEntrySink, AckError, AckFailure, AckSink};
+pub use audit::AuditSink;
 #[cfg(feature = "background-queue")]
 pub use background::{BACKGROUND_QUEUE_METRICS, describe_sink_metrics};
 #[cfg(feature = "background-queue")]
-pub use background::{BackgroundQueue, BackgroundQueueBuilder, BackgroundQueueJoinHandle};
+pub use background::{
+    BackgroundQueue, BackgroundQueueBuilder, BackgroundQueueJoinHandle, WaitableBackgroundQueue,
+};
+pub use cardinality::{CardinalityEstimate, CardinalityMonitorSink};
+pub use computed::WithComputedField;
+pub use counter_delta::CounterDeltaSink;
+pub use drop_zeros::{DropZerosEntry, DropZerosSink};
+pub use fan_out::FanOutSink;
 pub use immediate_flush::{
     AnyFlushImmediately, FlushImmediately, FlushImmediatelyBuilder,
     describe_immediate_flush_metrics,
 };
+#[cfg(all(feature = "journald", unix))]
+pub use journald::JournaldSink;
 pub use metrique_writer_core::sink::{AnyEntrySink, AppendOnDrop, FlushWait};
 use metrique_writer_core::{BoxEntrySink, EntryIoStream, EntrySink};
 pub use metrique_writer_core::{
     global::AttachGlobalEntrySink, global::AttachHandle, global::ShutdownFn, global_entry_sink,
 };
+pub use mirror::MirrorSink;
 #[cfg(feature = "background-queue")]
 pub use observer::{BackgroundQueueEvent, BackgroundQueueObserver};
 pub use observer::{FlushImmediatelyEvent, FlushImmediatelyObserver};
+pub use routing::RoutingSink;
+pub use tee::TeeSink;
+#[cfg(feature = "version-sink")]
+pub use version::WithVersionSink;
 
 /// Extension trait for `AttachGlobalEntrySink`, containing functions that use
 /// types that are not present in [`metrique_writer_core`].
@@ -81,6 +114,14 @@ impl<E: Entry> EntrySink<E> for VecEntrySink<E> {
         self.0.lock().unwrap().push(entry);
     }
 
+    /// Appends all of `entries` while holding the lock once, so they are not interleaved with
+    /// entries appended concurrently.
+    fn append_batch(&self, entries: impl IntoIterator<Item = E>) {
+        // collect before locking, so the iterator can't deadlock by appending to this sink
+        let entries: Vec<E> = entries.into_iter().collect();
+        self.0.lock().unwrap().extend(entries);
+    }
+
     fn flush_async(&self) -> FlushWait {
         FlushWait::ready()
     }
@@ -119,6 +160,24 @@ impl<E> VecEntrySink<E> {
         let entries = self.0.lock().unwrap();
         entries.iter().any(predicate)
     }
+
+    /// Returns the number of entries currently appended to the sink, without draining them.
+    pub fn len(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+
+    /// Returns true if no entries are currently appended to the sink.
+    pub fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+
+    /// Returns a clone of the most recently appended entry, without draining it.
+    pub fn peek_last(&self) -> Option<E>
+    where
+        E: Clone,
+    {
+        self.0.lock().unwrap().last().cloned()
+    }
 }
 
 /// An [EntrySink] that drops all entries.
@@ -138,6 +197,11 @@ impl DevNullSink {
     pub fn boxed() -> BoxEntrySink {
         Self::new().boxed()
     }
+
+    /// Return a new [`CountingDevNullSink`], which drops all entries but counts them
+    pub fn counting() -> CountingDevNullSink {
+        CountingDevNullSink::default()
+    }
 }
 
 impl AnyEntrySink for DevNullSink {
@@ -148,6 +212,40 @@ impl AnyEntrySink for DevNullSink {
     }
 }
 
+/// An [EntrySink] that drops all entries, like [`DevNullSink`], but counts how many it dropped.
+///
+/// Useful to confirm that entries are flowing while debugging. Clones share the same count.
+///
+/// ```
+/// # use metrique_writer::{Entry, EntrySink, sink::DevNullSink};
+/// #[derive(Entry)]
+/// struct MyEntry { counter: u64 }
+///
+/// let sink = DevNullSink::counting();
+/// sink.append(MyEntry { counter: 1 });
+/// sink.clone().append(MyEntry { counter: 2 });
+/// assert_eq!(sink.count(), 2);
+/// ```
+#[derive(Clone, Default, Debug)]
+pub struct CountingDevNullSink(Arc<AtomicU64>);
+
+impl CountingDevNullSink {
+    /// Return the number of entries dropped by this sink and its clones
+    pub fn count(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl AnyEntrySink for CountingDevNullSink {
+    fn append_any(&self, _entry: impl Entry + Send + 'static) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        FlushWait::ready()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::time::SystemTime;
@@ -206,6 +304,94 @@ mod tests {
         assert!(!sink.contains_entry(|_| true));
     }
 
+    #[test]
+    fn counting_dev_null_sink_counts_across_threads() {
+        let sink = DevNullSink::counting();
+        assert_eq!(sink.count(), 0);
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                let sink = sink.clone();
+                s.spawn(move || {
+                    for counter in 0..100 {
+                        sink.append(TestEntry {
+                            timestamp: SystemTime::now(),
+                            counter,
+                            status: "OK".into(),
+                        });
+                    }
+                });
+            }
+        });
+        assert_eq!(sink.count(), 400);
+    }
+
+    #[test]
+    fn vec_entry_sink_len_and_peek_last() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Counter(u32);
+        impl Entry for Counter {
+            fn write<'a>(&'a self, writer: &mut impl crate::EntryWriter<'a>) {
+                writer.value("counter", &self.0);
+            }
+        }
+
+        let sink = VecEntrySink::new();
+        assert!(sink.is_empty());
+        assert_eq!(sink.len(), 0);
+        assert_eq!(sink.peek_last(), None);
+
+        sink.append(Counter(1));
+        sink.append(Counter(2));
+        // reading doesn't drain the sink
+        for _ in 0..2 {
+            assert!(!sink.is_empty());
+            assert_eq!(sink.len(), 2);
+            assert_eq!(sink.peek_last(), Some(Counter(2)));
+        }
+
+        assert_eq!(sink.drain(), [Counter(1), Counter(2)]);
+        assert!(sink.is_empty());
+        assert_eq!(sink.peek_last(), None);
+    }
+
+    #[test]
+    fn vec_entry_sink_append_batch_takes_lock_once() {
+        let sink = VecEntrySink::<TestEntry>::new();
+        let entry = |counter, status: &str| TestEntry {
+            timestamp: SystemTime::now(),
+            counter,
+            status: status.into(),
+        };
+
+        // a concurrent appender can't interleave with a batch, since the batch holds the lock
+        // for all of its entries
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                for counter in 0..1000 {
+                    sink.append(entry(counter, "single"));
+                }
+            });
+            for _ in 0..10 {
+                sink.append_batch((0..100).map(|counter| entry(counter, "batch")));
+            }
+        });
+
+        let entries = sink.drain();
+        assert_eq!(entries.len(), 2000);
+        let batched = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.status == "batch")
+            .collect::<Vec<_>>();
+        for batch in batched.chunks(100) {
+            let (first_index, _) = batch[0];
+            for (offset, (index, e)) in batch.iter().enumerate() {
+                assert_eq!(*index, first_index + offset);
+                assert_eq!(e.counter as usize, offset);
+            }
+        }
+    }
+
     #[test]
     fn test_null_entry_sink() {
         let sink = DevNullSink::new();
@@ -45,6 +45,13 @@ pub enum BackgroundQueueEvent {
         /// Number of validation errors since the previous flush.
         count: u64,
     },
+    /// Entries were dropped by a sampling format since the previous flush. These entries are
+    /// also counted in [`BackgroundQueueEvent::MetricsEmitted`], since the stream accepted them.
+    #[non_exhaustive]
+    MetricsSampledOut {
+        /// Number of entries sampled out since the previous flush.
+        count: u64,
+    },
     /// A flush cycle has completed.
     #[non_exhaustive]
     FlushComplete {
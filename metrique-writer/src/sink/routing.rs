@@ -0,0 +1,260 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use metrique_writer_core::{
+    AnyEntrySink, BoxEntrySink, Entry, entry::SampleGroupElement, sink::FlushWait,
+};
+
+type KeyFn =
+    dyn Fn(&mut dyn Iterator<Item = SampleGroupElement>) -> Option<Cow<'static, str>> + Send + Sync;
+
+/// An [`EntrySink`](crate::EntrySink) that routes every entry to one of several sinks, based on
+/// the entry's [sample group](Entry::sample_group).
+///
+/// A key is derived from the sample group of each entry, and the entry is appended to the sink
+/// registered for that key. Entries without a key, or whose key has no registered sink, are
+/// appended to the default sink. This is useful e.g. to write the entries of each tenant of a
+/// multi-tenant service to their own output.
+///
+/// The routes are fixed when the sink is created, so appending doesn't take any lock and
+/// concurrent appends only contend in the destination sinks. Cloning the sink is cheap and
+/// shares the destinations.
+///
+/// # Example
+/// ```
+/// # use std::collections::HashMap;
+/// # use metrique_writer::{Entry, EntrySink, sink::RoutingSink, test_util::test_entry_sink};
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     #[entry(sample_group)]
+///     tenant: &'static str,
+///     latency_ms: u64,
+/// }
+///
+/// let tenant_a = test_entry_sink();
+/// let other = test_entry_sink();
+/// let sink = RoutingSink::by_group(
+///     "tenant",
+///     HashMap::from([("a".to_string(), tenant_a.sink)]),
+///     other.sink,
+/// );
+///
+/// sink.append(RequestMetrics { tenant: "a", latency_ms: 3 });
+/// sink.append(RequestMetrics { tenant: "b", latency_ms: 7 });
+///
+/// assert_eq!(tenant_a.inspector.entries()[0].metrics["latency_ms"], 3);
+/// assert_eq!(other.inspector.entries()[0].metrics["latency_ms"], 7);
+/// ```
+#[derive(Clone)]
+pub struct RoutingSink(Arc<RoutingSinkInner>);
+
+struct RoutingSinkInner {
+    key: Box<KeyFn>,
+    routes: HashMap<String, BoxEntrySink>,
+    default: BoxEntrySink,
+}
+
+impl RoutingSink {
+    /// Create a [`RoutingSink`] that routes entries by the key that `key` returns for the
+    /// elements of their sample group.
+    ///
+    /// Entries for which `key` returns `None` go to `default`, as do entries whose key is
+    /// not in `routes`.
+    pub fn new(
+        key: impl Fn(&mut dyn Iterator<Item = SampleGroupElement>) -> Option<Cow<'static, str>>
+        + Send
+        + Sync
+        + 'static,
+        routes: HashMap<String, BoxEntrySink>,
+        default: BoxEntrySink,
+    ) -> Self {
+        Self(Arc::new(RoutingSinkInner {
+            key: Box::new(key),
+            routes,
+            default,
+        }))
+    }
+
+    /// Create a [`RoutingSink`] that routes entries by the value of the sample group element
+    /// named `group`.
+    ///
+    /// Entries without that element go to `default`, as do entries whose value is not in
+    /// `routes`.
+    pub fn by_group(
+        group: impl Into<Cow<'static, str>>,
+        routes: HashMap<String, BoxEntrySink>,
+        default: BoxEntrySink,
+    ) -> Self {
+        let group = group.into();
+        Self::new(
+            move |elements| {
+                elements
+                    .filter(|(name, _)| *name == group)
+                    .map(|(_, value)| value)
+                    .next()
+            },
+            routes,
+            default,
+        )
+    }
+
+    fn route(&self, entry: &impl Entry) -> &BoxEntrySink {
+        let inner = &*self.0;
+        (inner.key)(&mut entry.sample_group())
+            .and_then(|key| inner.routes.get(&*key))
+            .unwrap_or(&inner.default)
+    }
+}
+
+impl std::fmt::Debug for RoutingSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut routes: Vec<_> = self.0.routes.keys().collect();
+        routes.sort();
+        f.debug_struct("RoutingSink")
+            .field("routes", &routes)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AnyEntrySink for RoutingSink {
+    fn append_any(&self, entry: impl Entry + Send + 'static) {
+        self.route(&entry).append_any(entry);
+    }
+
+    /// Flushes every destination, including the default.
+    fn flush_async(&self) -> FlushWait {
+        let waits: Vec<_> = self
+            .0
+            .routes
+            .values()
+            .chain([&self.0.default])
+            .map(|sink| sink.flush_async())
+            .collect();
+        FlushWait::from_future(async move {
+            for wait in waits {
+                wait.await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+
+    use metrique_writer_core::EntrySink;
+
+    use super::RoutingSink;
+    use crate::{Entry, test_util::test_entry_sink};
+
+    #[derive(Entry)]
+    struct TenantEntry {
+        #[entry(sample_group)]
+        region: &'static str,
+        #[entry(sample_group)]
+        tenant: &'static str,
+        id: u64,
+    }
+
+    #[derive(Entry)]
+    struct UntenantedEntry {
+        #[entry(sample_group)]
+        region: &'static str,
+        id: u64,
+    }
+
+    fn entry(tenant: &'static str, id: u64) -> TenantEntry {
+        TenantEntry {
+            region: "us-east-1",
+            tenant,
+            id,
+        }
+    }
+
+    fn ids(inspector: &crate::test_util::Inspector) -> Vec<u64> {
+        inspector
+            .entries()
+            .iter()
+            .map(|e| e.metrics["id"].as_u64())
+            .collect()
+    }
+
+    #[test]
+    fn routes_by_named_group_with_fallback() {
+        let (a, b, default) = (test_entry_sink(), test_entry_sink(), test_entry_sink());
+        let sink = RoutingSink::by_group(
+            "tenant",
+            HashMap::from([("a".to_string(), a.sink), ("b".to_string(), b.sink)]),
+            default.sink,
+        );
+
+        sink.append(entry("a", 1));
+        sink.append(entry("b", 2));
+        // unknown tenant
+        sink.append(entry("c", 3));
+        // no tenant element in the sample group
+        sink.append(UntenantedEntry {
+            region: "us-east-1",
+            id: 4,
+        });
+        sink.append(entry("a", 5));
+        futures::executor::block_on(EntrySink::<TenantEntry>::flush_async(&sink));
+
+        assert_eq!(ids(&a.inspector), [1, 5]);
+        assert_eq!(ids(&b.inspector), [2]);
+        assert_eq!(ids(&default.inspector), [3, 4]);
+    }
+
+    #[test]
+    fn routes_by_key_function() {
+        let (east, default) = (test_entry_sink(), test_entry_sink());
+        let sink = RoutingSink::new(
+            |elements| {
+                elements
+                    .filter(|(name, _)| name == "region")
+                    .find_map(|(_, region)| {
+                        let (area, _) = region.split_once('-')?;
+                        Some(Cow::Owned(area.to_owned()))
+                    })
+            },
+            HashMap::from([("us".to_string(), east.sink)]),
+            default.sink,
+        );
+
+        sink.append(entry("a", 1));
+        assert_eq!(ids(&east.inspector), [1]);
+        assert!(default.inspector.entries().is_empty());
+    }
+
+    #[test]
+    fn concurrent_appends_reach_their_route() {
+        let (a, b, default) = (test_entry_sink(), test_entry_sink(), test_entry_sink());
+        let sink = RoutingSink::by_group(
+            "tenant",
+            HashMap::from([("a".to_string(), a.sink), ("b".to_string(), b.sink)]),
+            default.sink,
+        );
+
+        std::thread::scope(|s| {
+            for tenant in ["a", "b"] {
+                for _ in 0..4 {
+                    let sink = sink.clone();
+                    s.spawn(move || {
+                        for id in 0..100 {
+                            sink.append(entry(tenant, id));
+                        }
+                    });
+                }
+            }
+        });
+
+        assert_eq!(a.inspector.entries().len(), 400);
+        assert_eq!(b.inspector.entries().len(), 400);
+        assert!(default.inspector.entries().is_empty());
+    }
+}
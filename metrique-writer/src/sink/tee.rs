@@ -0,0 +1,250 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use metrique_writer_core::{
+    AnyEntrySink, BoxEntrySink, Entry, EntryWriter, entry::SampleGroupElement, sink::FlushWait,
+};
+
+/// An [`EntrySink`](metrique_writer_core::EntrySink) that appends every entry to each of several
+/// sinks.
+///
+/// This is useful when migrating between destinations, e.g. to write the same entries to both a
+/// legacy EMF file and a new exporter until the new pipeline is trusted. Unlike
+/// [`MirrorSink`](super::MirrorSink), every sink receives every entry.
+///
+/// The sinks are type-erased [`BoxEntrySink`]s, so a `TeeSink` is an [`AnyEntrySink`] that
+/// accepts entries of any type.
+///
+/// # Clone cost
+///
+/// Entries don't need to be [`Clone`]. The entry is never copied: it is appended to the first
+/// sink, and once that sink drops it (usually right after writing it), it is appended to the
+/// next one, and so on. This means a sink that holds on to entries, like
+/// [`VecEntrySink`](super::VecEntrySink), delays the sinks after it until it releases them, and
+/// the sinks should not append to the `TeeSink` itself.
+///
+/// For entries that are cheap to clone, [`TeeSink::append_cloned`] instead appends a clone of the
+/// entry to every sink but the last, so the sinks don't wait for each other. An
+/// [`Arc`] of the entry also implements [`Entry`] and is cloned by bumping a reference count.
+///
+/// # Example
+/// ```
+/// # use metrique_writer::{Entry, EntrySink, sink::TeeSink, test_util::test_entry_sink};
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     operation: &'static str,
+/// }
+///
+/// let legacy = test_entry_sink();
+/// let new = test_entry_sink();
+/// let sink = TeeSink::new(vec![legacy.sink, new.sink]);
+///
+/// sink.append(RequestMetrics { operation: "Foo" });
+/// assert_eq!(legacy.inspector.entries()[0].values["operation"], "Foo");
+/// assert_eq!(new.inspector.entries()[0].values["operation"], "Foo");
+/// ```
+#[derive(Clone, Debug)]
+pub struct TeeSink {
+    sinks: Arc<[BoxEntrySink]>,
+}
+
+impl TeeSink {
+    /// Create a new [`TeeSink`] that appends every entry to each of `sinks`, in order.
+    ///
+    /// If `sinks` is empty, entries are dropped.
+    pub fn new(sinks: Vec<BoxEntrySink>) -> Self {
+        Self {
+            sinks: sinks.into(),
+        }
+    }
+
+    /// Return the sinks entries are appended to
+    pub fn into_inner(self) -> Vec<BoxEntrySink> {
+        self.sinks.to_vec()
+    }
+
+    /// Append a clone of `entry` to every sink but the last, which receives `entry` itself.
+    ///
+    /// Unlike [`append`](metrique_writer_core::EntrySink::append), the sinks don't wait for each
+    /// other to drop the entry. See [Clone cost](TeeSink#clone-cost).
+    pub fn append_cloned<E: Entry + Clone + Send + 'static>(&self, entry: E) {
+        let Some((last, rest)) = self.sinks.split_last() else {
+            return;
+        };
+        for sink in rest {
+            sink.append_any(entry.clone());
+        }
+        last.append_any(entry);
+    }
+}
+
+impl AnyEntrySink for TeeSink {
+    fn append_any(&self, entry: impl Entry + Send + 'static) {
+        relay(&self.sinks, 0, entry);
+    }
+
+    /// Returns a [`FlushWait`] that completes once every sink has flushed.
+    ///
+    /// Each sink is flushed after the ones before it, so that it has received the entries they
+    /// released while flushing.
+    fn flush_async(&self) -> FlushWait {
+        let sinks = Arc::clone(&self.sinks);
+        FlushWait::from_future(async move {
+            for sink in sinks.iter() {
+                sink.flush_async().await;
+            }
+        })
+    }
+}
+
+/// Append `entry` to `sinks[index]`, and to the sinks after it once that sink drops it
+fn relay<E: Entry + Send + 'static>(sinks: &Arc<[BoxEntrySink]>, index: usize, entry: E) {
+    match sinks.get(index..) {
+        None | Some([]) => {}
+        Some([last]) => last.append_any(entry),
+        Some([sink, ..]) => sink.append_any(Relay {
+            entry: Some(entry),
+            sinks: Arc::clone(sinks),
+            next: index + 1,
+        }),
+    }
+}
+
+/// An entry that is appended to `sinks[next]` when dropped
+struct Relay<E: Entry + Send + 'static> {
+    // only `None` while dropping
+    entry: Option<E>,
+    sinks: Arc<[BoxEntrySink]>,
+    next: usize,
+}
+
+impl<E: Entry + Send + 'static> Entry for Relay<E> {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        if let Some(entry) = &self.entry {
+            entry.write(writer);
+        }
+    }
+
+    fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+        self.entry.iter().flat_map(Entry::sample_group)
+    }
+}
+
+impl<E: Entry + Send + 'static> Drop for Relay<E> {
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry.take() {
+            relay(&self.sinks, self.next, entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+    };
+
+    use metrique_writer_core::{BoxEntrySink, EntrySink};
+
+    use super::TeeSink;
+    use crate::{Entry, EntryWriter, sink::VecEntrySink, test_util::test_entry_sink};
+
+    #[derive(Entry, Clone)]
+    struct TestEntry {
+        id: u64,
+    }
+
+    #[test]
+    fn appends_every_entry_to_every_sink() {
+        let (a, b, c) = (test_entry_sink(), test_entry_sink(), test_entry_sink());
+        let sink = TeeSink::new(vec![a.sink, b.sink, c.sink]);
+        for id in 0..3 {
+            sink.append(TestEntry { id });
+        }
+        futures::executor::block_on(EntrySink::<TestEntry>::flush_async(&sink));
+
+        for inspector in [a.inspector, b.inspector, c.inspector] {
+            let ids: Vec<_> = inspector
+                .entries()
+                .iter()
+                .map(|e| e.metrics["id"].as_u64())
+                .collect();
+            assert_eq!(ids, [0, 1, 2]);
+        }
+    }
+
+    #[test]
+    fn clones_once_per_extra_sink() {
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        struct CountingEntry;
+        impl Clone for CountingEntry {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, Ordering::Relaxed);
+                CountingEntry
+            }
+        }
+        impl Entry for CountingEntry {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.value("value", &1u64);
+            }
+        }
+
+        let (a, b) = (test_entry_sink(), test_entry_sink());
+        let sink = TeeSink::new(vec![a.sink, b.sink]);
+        sink.append_cloned(CountingEntry);
+        assert_eq!(CLONES.load(Ordering::Relaxed), 1);
+
+        // an Arc'd entry is cloned by reference
+        sink.append_cloned(Arc::new(CountingEntry));
+        assert_eq!(CLONES.load(Ordering::Relaxed), 1);
+
+        // and append never clones
+        sink.append(CountingEntry);
+        assert_eq!(CLONES.load(Ordering::Relaxed), 1);
+        assert_eq!(a.inspector.entries().len(), 3);
+        assert_eq!(b.inspector.entries().len(), 3);
+    }
+
+    #[test]
+    fn appends_entries_that_are_not_clone() {
+        struct NotClone(Cell<u64>);
+        impl Entry for NotClone {
+            fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+                writer.value("value", &self.0.get());
+            }
+        }
+
+        let (a, b, c) = (test_entry_sink(), test_entry_sink(), test_entry_sink());
+        let sink = TeeSink::new(vec![a.sink, b.sink, c.sink]);
+        sink.append(NotClone(Cell::new(7)));
+        for inspector in [a.inspector, b.inspector, c.inspector] {
+            assert_eq!(inspector.entries()[0].metrics["value"], 7);
+        }
+    }
+
+    #[test]
+    fn later_sinks_receive_entries_once_released() {
+        let held = VecEntrySink::new();
+        let after = test_entry_sink();
+        let sink = TeeSink::new(vec![BoxEntrySink::new(held.clone()), after.sink]);
+        sink.append(TestEntry { id: 1 });
+        assert!(after.inspector.entries().is_empty());
+
+        drop(held.drain());
+        assert_eq!(after.inspector.entries()[0].metrics["id"], 1);
+    }
+
+    #[test]
+    fn empty_tee_drops_entries() {
+        let sink = TeeSink::new(vec![]);
+        sink.append(TestEntry { id: 0 });
+        futures::executor::block_on(EntrySink::<TestEntry>::flush_async(&sink));
+    }
+}
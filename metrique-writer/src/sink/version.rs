@@ -0,0 +1,126 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{borrow::Cow, sync::Arc};
+
+use arc_swap::ArcSwap;
+use metrique_writer_core::{AnyEntrySink, Entry, sink::FlushWait};
+
+use crate::entry::WithField;
+
+/// An [`AnyEntrySink`] that adds the current version (e.g. of the deployment or of the
+/// loaded configuration) as a string field to every entry appended to it.
+///
+/// The version is read from a shared [`ArcSwap`] at the time each entry is appended, so
+/// storing a new version (for example, when the configuration is reloaded) is reflected on
+/// all subsequent entries without having to rebuild the sink.
+///
+/// # Example
+/// ```
+/// # use std::sync::Arc;
+/// # use arc_swap::ArcSwap;
+/// # use metrique_writer::{Entry, EntrySink, sink::WithVersionSink, test_util::test_entry_sink};
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     operation: &'static str,
+/// }
+///
+/// let version = Arc::new(ArcSwap::from_pointee("v1".to_string()));
+/// let test_sink = test_entry_sink();
+/// let sink = WithVersionSink::new(test_sink.sink, "ConfigVersion", version.clone());
+///
+/// sink.append(RequestMetrics { operation: "Foo" });
+/// // e.g. on config reload
+/// version.store(Arc::new("v2".to_string()));
+/// sink.append(RequestMetrics { operation: "Foo" });
+///
+/// let entries = test_sink.inspector.entries();
+/// assert_eq!(entries[0].values["ConfigVersion"], "v1");
+/// assert_eq!(entries[1].values["ConfigVersion"], "v2");
+/// ```
+#[derive(Clone, Debug)]
+pub struct WithVersionSink<S> {
+    sink: S,
+    name: Cow<'static, str>,
+    version: Arc<ArcSwap<String>>,
+}
+
+impl<S> WithVersionSink<S> {
+    /// Wrap `sink`, writing the current value of `version` under the field `name` on every entry.
+    pub fn new(sink: S, name: impl Into<Cow<'static, str>>, version: Arc<ArcSwap<String>>) -> Self {
+        Self {
+            sink,
+            name: name.into(),
+            version,
+        }
+    }
+}
+
+impl<S: AnyEntrySink> AnyEntrySink for WithVersionSink<S> {
+    fn append_any(&self, entry: impl Entry + Send + 'static) {
+        self.sink.append_any(WithField::new(
+            entry,
+            self.name.clone(),
+            self.version.load_full(),
+        ));
+    }
+
+    fn flush_async(&self) -> FlushWait {
+        self.sink.flush_async()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use arc_swap::ArcSwap;
+    use metrique_writer_core::{AnyEntrySink, EntrySink};
+
+    use super::WithVersionSink;
+    use crate::{Entry, test_util::test_entry_sink};
+
+    #[derive(Entry)]
+    #[entry(rename_all = "PascalCase")]
+    struct TestEntry {
+        operation: &'static str,
+    }
+
+    #[test]
+    fn version_change_is_reflected_on_subsequent_entries() {
+        let version = Arc::new(ArcSwap::from_pointee("1".to_string()));
+        let test_sink = test_entry_sink();
+        let sink = WithVersionSink::new(test_sink.sink, "ConfigVersion", version.clone());
+
+        sink.append(TestEntry { operation: "A" });
+        sink.append(TestEntry { operation: "B" });
+        version.store(Arc::new("2".to_string()));
+        sink.append(TestEntry { operation: "C" });
+
+        let entries = test_sink.inspector.entries();
+        assert_eq!(entries.len(), 3);
+        let versions = entries
+            .iter()
+            .map(|e| {
+                (
+                    e.values["Operation"].as_str(),
+                    e.values["ConfigVersion"].as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(versions, [("A", "1"), ("B", "1"), ("C", "2")]);
+    }
+
+    #[test]
+    fn boxed_sink_is_versioned() {
+        let version = Arc::new(ArcSwap::from_pointee("1".to_string()));
+        let test_sink = test_entry_sink();
+        let sink = WithVersionSink::new(test_sink.sink, "ConfigVersion", version).boxed();
+
+        sink.append(TestEntry { operation: "A" });
+        assert_eq!(
+            test_sink.inspector.get(0).values["ConfigVersion"],
+            "1".to_string()
+        );
+    }
+}
@@ -3,7 +3,7 @@
 
 //! Contains various utilities for working with [EntryIoStream]
 
-use std::{collections::HashSet, io};
+use std::{collections::HashSet, io, time::Duration};
 
 use metrique_writer_core::{Entry, config::MetriqueValidationError};
 use smallvec::SmallVec;
@@ -12,6 +12,22 @@ use crate::{CowStr, entry::WithGlobalDimensions};
 
 pub use metrique_writer_core::{EntryIoStream, IoStreamError};
 
+#[cfg(feature = "pii-guard")]
+mod pii_guard;
+#[cfg(feature = "pii-guard")]
+pub use pii_guard::{PiiAction, PiiGuardStream, REDACTED};
+mod filter;
+pub use filter::{EntryFilter, FilteredStream};
+mod retry;
+pub use retry::RetryStream;
+mod sample_group_dimensions;
+pub use sample_group_dimensions::SampleGroupDimensions;
+pub(crate) use sample_group_dimensions::with_sample_group_dimensions;
+#[cfg(unix)]
+mod unix_socket;
+#[cfg(unix)]
+pub use unix_socket::{DroppedEntries, UnixSocketStream};
+
 /// Extension trait for [`EntryIoStream`]. This adds methods that use types not
 /// present within [`metrique_writer_core`].
 pub trait EntryIoStreamExt: EntryIoStream {
@@ -105,6 +121,120 @@ pub trait EntryIoStreamExt: EntryIoStream {
         }
     }
 
+    /// Add dimension sets to every entry from the keys of its [sample group](Entry::sample_group),
+    /// so a field that is already in the sample group (like the operation name) doesn't also need
+    /// to be declared as a dimension on the entry.
+    ///
+    /// Each entry gets the given `dimension_sets` as an [`EntryDimensions`] config, which the EMF
+    /// format cartesian-products with its own dimension sets and merges with any other
+    /// `EntryDimensions` the entry sets. Keys that are missing from the sample group of an entry
+    /// are skipped for that entry, and sets left empty by that are dropped, so an entry with none
+    /// of the keys is written with only the format's dimension sets.
+    ///
+    /// The sample group values must also be written as string fields of the entry, which
+    /// `#[entry(sample_group)]` fields are.
+    ///
+    /// There is intentionally both a [`EntryIoStreamExt::sample_group_dimensions`] and a
+    /// [`FormatExt::sample_group_dimensions`], which implement exactly the same functionality,
+    /// to allow using in interfaces that accept an [`EntryIoStream`] as well as interfaces
+    /// that accept a [`Format`].
+    ///
+    /// ```
+    /// # use metrique_writer::{EntryIoStream, EntryIoStreamExt as _, FormatExt as _};
+    /// # use metrique_writer_format_emf::Emf;
+    /// # use std::io;
+    /// fn set_up_emf(out: impl io::Write) -> impl EntryIoStream {
+    ///     Emf::all_validations("MyApp".into(), vec![vec![]])
+    ///         .output_to(out)
+    ///         .sample_group_dimensions([["Operation"]])
+    /// }
+    /// ```
+    ///
+    /// [`EntryDimensions`]: metrique_writer_core::config::EntryDimensions
+    /// [`Format`]: crate::format::Format
+    /// [`FormatExt::sample_group_dimensions`]: crate::format::FormatExt::sample_group_dimensions
+    fn sample_group_dimensions<K: Into<CowStr>>(
+        self,
+        dimension_sets: impl IntoIterator<Item = impl IntoIterator<Item = K>>,
+    ) -> SampleGroupDimensions<Self>
+    where
+        Self: Sized,
+    {
+        SampleGroupDimensions::new(self, dimension_sets)
+    }
+
+    /// Retry writing entries and flushing when the stream fails with a transient IO error
+    /// (`WouldBlock` or `Interrupted`), e.g. when writing to a non-blocking pipe or socket.
+    ///
+    /// Each operation is attempted up to `max_attempts` times, waiting `backoff` before the first
+    /// retry and doubling the wait after each one. An entry is only retried if the failed
+    /// attempt wrote nothing, so output is never duplicated. Every attempt formats the entry
+    /// again, so this must not wrap a format that samples at random. See [`RetryStream`] for
+    /// details.
+    ///
+    /// ```
+    /// # use metrique_writer::{EntryIoStream, EntryIoStreamExt as _, FormatExt as _};
+    /// # use metrique_writer_format_emf::Emf;
+    /// # use std::{io, time::Duration};
+    /// fn set_up_emf(out: impl io::Write) -> impl EntryIoStream {
+    ///     Emf::all_validations("MyApp".into(), vec![vec![]])
+    ///         .output_to(out)
+    ///         .with_retry(5, Duration::from_millis(1))
+    /// }
+    /// ```
+    ///
+    /// # Panics
+    /// Panics if `max_attempts` is 0.
+    fn with_retry(self, max_attempts: u32, backoff: Duration) -> RetryStream<Self>
+    where
+        Self: Sized,
+    {
+        RetryStream::new(self, max_attempts, backoff)
+    }
+
+    /// Only write the entries that `filter` keeps, dropping the others before they are
+    /// formatted.
+    ///
+    /// `filter` is either a closure taking the entry's [`SampleGroupMap`], or any
+    /// [`EntryFilter`] for filters that need to inspect the whole entry. Dropped entries are
+    /// not an error.
+    ///
+    /// Combinators apply from the outside in: a filter added after [`merge_globals`] sees
+    /// entries before the globals are merged, and a filter in front of a
+    /// [sampled](crate::sample) format drops entries before the sampler sees them, so they don't
+    /// count toward its rates.
+    ///
+    /// ```
+    /// # use metrique_writer::{Entry, EntryIoStream, EntryIoStreamExt as _, FormatExt as _};
+    /// # use metrique_writer_core::entry::SampleGroupMap;
+    /// # use metrique_writer_format_emf::Emf;
+    /// # use std::io;
+    /// #[derive(Entry)]
+    /// #[entry(rename_all = "PascalCase")]
+    /// struct RequestMetrics {
+    ///     #[entry(sample_group)]
+    ///     operation: &'static str,
+    ///     count: u64,
+    /// }
+    ///
+    /// let mut output = vec![];
+    /// let mut stream = Emf::all_validations("MyApp".into(), vec![vec![]])
+    ///     .output_to(&mut output)
+    ///     .filter(|group: &SampleGroupMap| group.get("Operation") != Some("Ping"));
+    /// stream.next(&RequestMetrics { operation: "Ping", count: 1 }).unwrap();
+    /// drop(stream);
+    /// assert!(output.is_empty());
+    /// ```
+    ///
+    /// [`merge_globals`]: EntryIoStreamExt::merge_globals
+    /// [`SampleGroupMap`]: metrique_writer_core::entry::SampleGroupMap
+    fn filter<F: EntryFilter>(self, filter: F) -> FilteredStream<Self, F>
+    where
+        Self: Sized,
+    {
+        FilteredStream::new(self, filter)
+    }
+
     /// See [`tee()`].
     fn tee<S>(self, other: S) -> Tee<Self, S>
     where
@@ -176,6 +306,20 @@ impl<S1: EntryIoStream, S2: EntryIoStream> EntryIoStream for Tee<S1, S2> {
         let r2 = self.s2.flush();
         r1.and(r2)
     }
+
+    fn bytes_written(&self) -> Option<u64> {
+        match (self.s1.bytes_written(), self.s2.bytes_written()) {
+            (Some(b1), Some(b2)) => Some(b1 + b2),
+            (b1, b2) => b1.or(b2),
+        }
+    }
+
+    fn entries_sampled_out(&self) -> Option<u64> {
+        match (self.s1.entries_sampled_out(), self.s2.entries_sampled_out()) {
+            (Some(n1), Some(n2)) => Some(n1 + n2),
+            (n1, n2) => n1.or(n2),
+        }
+    }
 }
 
 /// See [`EntryIoStreamExt::merge_globals`] or [`FormatExt::merge_globals`].
@@ -195,6 +339,14 @@ impl<S: EntryIoStream, G: Entry> EntryIoStream for MergeGlobals<S, G> {
     fn flush(&mut self) -> io::Result<()> {
         self.stream.flush()
     }
+
+    fn bytes_written(&self) -> Option<u64> {
+        self.stream.bytes_written()
+    }
+
+    fn entries_sampled_out(&self) -> Option<u64> {
+        self.stream.entries_sampled_out()
+    }
 }
 
 /// See [`EntryIoStreamExt::merge_global_dimensions`] or [`FormatExt::merge_global_dimensions`].
@@ -225,6 +377,14 @@ impl<S: EntryIoStream, const N: usize> EntryIoStream for MergeGlobalDimensions<S
     fn flush(&mut self) -> io::Result<()> {
         self.stream.flush()
     }
+
+    fn bytes_written(&self) -> Option<u64> {
+        self.stream.bytes_written()
+    }
+
+    fn entries_sampled_out(&self) -> Option<u64> {
+        self.stream.entries_sampled_out()
+    }
 }
 
 /// An EntryIoStream that drops all entries sent to it
@@ -0,0 +1,258 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io;
+
+use metrique_writer_core::{Entry, EntryIoStream, IoStreamError, entry::SampleGroupMap};
+
+/// Decides which entries a [`FilteredStream`] writes, see [`EntryIoStreamExt::filter`].
+///
+/// Closures taking the [`SampleGroupMap`] of the entry implement this trait. Since closures
+/// can't be generic over the entry type, implement this trait directly to inspect the rest of the
+/// entry, e.g. by [writing](Entry::write) it to a custom [`EntryWriter`].
+///
+/// ```
+/// # use metrique_writer::{Entry, EntryWriter, stream::EntryFilter};
+/// # use metrique_writer_core::{MetricFlags, Observation, Unit, ValidationError, Value, ValueWriter};
+/// # use std::{borrow::Cow, time::SystemTime};
+/// /// Drops entries that have a `Healthcheck` field set to "true"
+/// struct DropHealthchecks;
+///
+/// impl EntryFilter for DropHealthchecks {
+///     fn keep(&mut self, entry: &impl Entry) -> bool {
+///         let mut finder = FindHealthcheck(false);
+///         entry.write(&mut finder);
+///         !finder.0
+///     }
+/// }
+///
+/// struct FindHealthcheck(bool);
+///
+/// impl<'a> EntryWriter<'a> for FindHealthcheck {
+///     fn timestamp(&mut self, _timestamp: SystemTime) {}
+///
+///     fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+///         if name.into() == "Healthcheck" {
+///             value.write(IsTrue(&mut self.0));
+///         }
+///     }
+///
+///     fn config(&mut self, _config: &'a dyn metrique_writer::EntryConfig) {}
+/// }
+///
+/// struct IsTrue<'a>(&'a mut bool);
+///
+/// impl ValueWriter for IsTrue<'_> {
+///     fn string(self, value: &str) {
+///         *self.0 = value == "true";
+///     }
+///
+///     fn metric<'a>(
+///         self,
+///         _distribution: impl IntoIterator<Item = Observation>,
+///         _unit: Unit,
+///         _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+///         _flags: MetricFlags<'_>,
+///     ) {
+///     }
+///
+///     fn error(self, _error: ValidationError) {}
+/// }
+/// ```
+///
+/// [`EntryIoStreamExt::filter`]: crate::stream::EntryIoStreamExt::filter
+/// [`EntryWriter`]: metrique_writer_core::EntryWriter
+pub trait EntryFilter {
+    /// Return true if `entry` should be written, or false to drop it
+    fn keep(&mut self, entry: &impl Entry) -> bool;
+}
+
+impl<F: FnMut(&SampleGroupMap) -> bool> EntryFilter for F {
+    fn keep(&mut self, entry: &impl Entry) -> bool {
+        self(&SampleGroupMap::from_entry(entry))
+    }
+}
+
+/// An [`EntryIoStream`] that only writes the entries its [`EntryFilter`] keeps, see
+/// [`EntryIoStreamExt::filter`].
+///
+/// [`EntryIoStreamExt::filter`]: crate::stream::EntryIoStreamExt::filter
+#[derive(Clone, Debug)]
+pub struct FilteredStream<S, F> {
+    stream: S,
+    filter: F,
+}
+
+impl<S, F> FilteredStream<S, F> {
+    pub(crate) fn new(stream: S, filter: F) -> Self {
+        Self { stream, filter }
+    }
+
+    /// Return the wrapped stream
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: EntryIoStream, F: EntryFilter> EntryIoStream for FilteredStream<S, F> {
+    fn next(&mut self, entry: &impl Entry) -> Result<(), IoStreamError> {
+        if self.filter.keep(entry) {
+            self.stream.next(entry)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+
+    fn bytes_written(&self) -> Option<u64> {
+        self.stream.bytes_written()
+    }
+
+    fn entries_sampled_out(&self) -> Option<u64> {
+        self.stream.entries_sampled_out()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, io};
+
+    use metrique_writer_core::{
+        Entry, EntryIoStream, EntryWriter, IoStreamError,
+        entry::{SampleGroupElement, SampleGroupMap},
+        format::Format,
+        sample::SampledFormat,
+        test_stream::DummyEntryWriter,
+    };
+
+    use super::EntryFilter;
+    use crate::{
+        format::FormatExt as _, sample::SampledFormatExt as _, stream::EntryIoStreamExt as _,
+    };
+
+    struct Request {
+        operation: &'static str,
+        latency: u64,
+    }
+
+    impl Entry for Request {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value("Operation", self.operation);
+            writer.value("Latency", &self.latency);
+        }
+
+        fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+            [(Cow::Borrowed("Operation"), Cow::Borrowed(self.operation))].into_iter()
+        }
+    }
+
+    struct Globals;
+
+    impl Entry for Globals {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.value("Host", "a");
+        }
+    }
+
+    /// Writes the string values of each entry as a line, followed by the sample rate if any
+    struct LineFormat;
+
+    impl LineFormat {
+        fn write_line(
+            entry: &impl Entry,
+            output: &mut impl io::Write,
+            rate: Option<f32>,
+        ) -> Result<(), IoStreamError> {
+            let mut writer = DummyEntryWriter::default();
+            entry.write(&mut writer);
+            let values: Vec<_> = writer
+                .0
+                .iter()
+                .filter(|(name, _)| name != "Latency")
+                .map(|(_, value)| value.as_str())
+                .collect();
+            write!(output, "{}", values.join(" "))?;
+            if let Some(rate) = rate {
+                write!(output, " @{rate}")?;
+            }
+            writeln!(output)?;
+            Ok(())
+        }
+    }
+
+    impl Format for LineFormat {
+        fn format(
+            &mut self,
+            entry: &impl Entry,
+            output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            Self::write_line(entry, output, None)
+        }
+    }
+
+    impl SampledFormat for LineFormat {
+        fn format_with_sample_rate(
+            &mut self,
+            entry: &impl Entry,
+            output: &mut impl io::Write,
+            rate: f32,
+        ) -> Result<(), IoStreamError> {
+            Self::write_line(entry, output, Some(rate))
+        }
+    }
+
+    fn requests() -> [Request; 3] {
+        [("Get", 1), ("Ping", 1), ("Put", 100)]
+            .map(|(operation, latency)| Request { operation, latency })
+    }
+
+    #[test]
+    fn drops_filtered_entries() {
+        let mut output = vec![];
+        {
+            let mut stream = LineFormat
+                .output_to(&mut output)
+                .filter(|group: &SampleGroupMap| group.get("Operation") != Some("Ping"))
+                // merged before the entry reaches the filter
+                .merge_globals(Globals);
+            for request in requests() {
+                stream.next(&request).unwrap();
+            }
+            assert_eq!(stream.bytes_written(), Some(12));
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), "a Get\na Put\n");
+    }
+
+    /// Keeps entries with a latency over 10, by inspecting the whole entry
+    struct SlowRequests;
+
+    impl EntryFilter for SlowRequests {
+        fn keep(&mut self, entry: &impl Entry) -> bool {
+            let mut writer = DummyEntryWriter::default();
+            entry.write(&mut writer);
+            writer
+                .0
+                .iter()
+                .any(|(name, value)| name == "Latency" && value != "[Unsigned(1)] None []")
+        }
+    }
+
+    #[test]
+    fn composes_with_globals_and_sampling() {
+        let mut output = vec![];
+        {
+            let mut stream = LineFormat
+                .sample_by_fixed_fraction(1.0)
+                .merge_globals(Globals)
+                .output_to(&mut output)
+                .filter(SlowRequests);
+            for request in requests() {
+                stream.next(&request).unwrap();
+            }
+        }
+        assert_eq!(String::from_utf8(output).unwrap(), "a Put @1\n");
+    }
+}
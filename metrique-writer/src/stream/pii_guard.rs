@@ -0,0 +1,458 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{borrow::Cow, io, time::Duration, time::SystemTime};
+
+use metrique_writer_core::{
+    Entry, EntryConfig, EntryIoStream, EntryWriter, IoStreamError, MetricFlags, Observation, Unit,
+    ValidationError, Value, ValueWriter, entry::SampleGroupElement,
+};
+use regex_lite::Regex;
+
+use crate::rate_limited;
+
+/// The string written in place of a value that matched a pattern, with [`PiiAction::RedactField`]
+pub const REDACTED: &str = "[REDACTED]";
+
+/// What a [`PiiGuardStream`] does with an entry that has a string value matching one of its
+/// patterns.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PiiAction {
+    /// Replace every matching value with [`REDACTED`], and write the rest of the entry as usual
+    RedactField,
+    /// Don't write the entry. A [`ValidationError`] naming the matching fields (but not their
+    /// values) is returned instead, which e.g. a [`BackgroundQueue`] counts and reports like any
+    /// other invalid entry.
+    ///
+    /// [`BackgroundQueue`]: crate::sink::BackgroundQueue
+    DropEntry,
+    /// Write the entry unchanged, and log a `tracing` warning naming the matching fields (but not
+    /// their values). Warnings are rate-limited to one per second.
+    ///
+    /// This is meant to find out which fields are affected before enforcing one of the other
+    /// actions.
+    Log,
+}
+
+/// An [`EntryIoStream`] that scans the string values of every entry for patterns (e.g. email
+/// addresses or credit card numbers), and redacts or drops entries that match before they reach
+/// the wrapped stream.
+///
+/// This is meant as a last line of defense for compliance, in front of the stream that actually
+/// writes entries out. Patterns are [`regex_lite`] regular expressions, added with
+/// [`PiiGuardStream::with_pattern`]. What happens on a match is controlled by [`PiiAction`].
+///
+/// Only string values are scanned (including lists of values, which are scanned as their
+/// comma-joined string representation). Field names, metric values, and dimensions are not
+/// scanned. Every string value is matched against every pattern, so this adds a cost
+/// proportional to the amount of text written and the number of patterns.
+///
+/// Requires the `pii-guard` feature.
+///
+/// # Example
+/// ```
+/// # use metrique_writer::{Entry, EntryIoStream, FormatExt};
+/// # use metrique_writer::stream::{PiiAction, PiiGuardStream};
+/// # use metrique_writer_format_emf::Emf;
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     operation: &'static str,
+///     error_message: String,
+/// }
+///
+/// let mut output = vec![];
+/// let mut stream = PiiGuardStream::new(
+///     Emf::all_validations("MyApp".into(), vec![vec![]]).output_to(&mut output),
+///     PiiAction::RedactField,
+/// )
+/// .with_pattern(r"[\w.+-]+@[\w-]+\.[\w.]+")
+/// .unwrap();
+///
+/// stream
+///     .next(&RequestMetrics {
+///         operation: "Register",
+///         error_message: "user someone@example.com already exists".into(),
+///     })
+///     .unwrap();
+/// drop(stream);
+///
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.contains(r#""error_message":"[REDACTED]""#));
+/// assert!(!output.contains("someone@example.com"));
+/// ```
+#[derive(Debug)]
+pub struct PiiGuardStream<S> {
+    stream: S,
+    action: PiiAction,
+    patterns: Vec<Regex>,
+}
+
+impl<S> PiiGuardStream<S> {
+    /// Wrap `stream`, applying `action` to entries matching any pattern.
+    ///
+    /// No patterns are configured initially, add them with [`PiiGuardStream::with_pattern`].
+    pub fn new(stream: S, action: PiiAction) -> Self {
+        Self {
+            stream,
+            action,
+            patterns: vec![],
+        }
+    }
+
+    /// Add a regular expression that string values are matched against.
+    ///
+    /// A value matches if the pattern matches any part of it. Returns an error if `pattern` isn't
+    /// a valid regular expression.
+    pub fn with_pattern(mut self, pattern: &str) -> Result<Self, regex_lite::Error> {
+        self.patterns.push(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    /// Return the wrapped stream
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    fn matching_fields(&self, entry: &impl Entry) -> Vec<String> {
+        let mut writer = ScanningEntryWriter {
+            patterns: &self.patterns,
+            matching: vec![],
+        };
+        entry.write(&mut writer);
+        writer.matching
+    }
+}
+
+impl<S: EntryIoStream> EntryIoStream for PiiGuardStream<S> {
+    fn next(&mut self, entry: &impl Entry) -> Result<(), IoStreamError> {
+        if self.patterns.is_empty() {
+            return self.stream.next(entry);
+        }
+        match self.action {
+            // redacting rewrites values as they are written, so there is no need for a separate
+            // scan
+            PiiAction::RedactField => self.stream.next(&RedactedEntry {
+                entry,
+                patterns: &self.patterns,
+            }),
+            PiiAction::DropEntry => {
+                let matching = self.matching_fields(entry);
+                if matching.is_empty() {
+                    return self.stream.next(entry);
+                }
+                let mut error = ValidationError::builder();
+                for name in matching {
+                    error.extend_mut(
+                        ValidationError::invalid("value matched a PII pattern, entry dropped")
+                            .for_field(&name),
+                    );
+                }
+                Err(IoStreamError::Validation(error.build().unwrap_err()))
+            }
+            PiiAction::Log => {
+                let matching = self.matching_fields(entry);
+                if !matching.is_empty() {
+                    rate_limited!(
+                        Duration::from_secs(1),
+                        tracing::warn!(fields = ?matching, "metric entry matched a PII pattern")
+                    );
+                }
+                self.stream.next(entry)
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+
+    fn bytes_written(&self) -> Option<u64> {
+        self.stream.bytes_written()
+    }
+
+    fn entries_sampled_out(&self) -> Option<u64> {
+        self.stream.entries_sampled_out()
+    }
+}
+
+fn matches_any(patterns: &[Regex], value: &str) -> bool {
+    patterns.iter().any(|pattern| pattern.is_match(value))
+}
+
+fn value_matches(patterns: &[Regex], value: &(impl Value + ?Sized)) -> bool {
+    let mut matched = false;
+    value.write(ScanningValueWriter {
+        patterns,
+        matched: &mut matched,
+    });
+    matched
+}
+
+/// An [`EntryWriter`] that only records the names of values matching a pattern
+struct ScanningEntryWriter<'p> {
+    patterns: &'p [Regex],
+    matching: Vec<String>,
+}
+
+impl<'a> EntryWriter<'a> for ScanningEntryWriter<'_> {
+    fn timestamp(&mut self, _timestamp: SystemTime) {}
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        if value_matches(self.patterns, value) {
+            self.matching.push(name.into().into_owned());
+        }
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {}
+}
+
+/// A [`ValueWriter`] that records whether a string value matched a pattern
+struct ScanningValueWriter<'p, 'm> {
+    patterns: &'p [Regex],
+    matched: &'m mut bool,
+}
+
+impl ValueWriter for ScanningValueWriter<'_, '_> {
+    fn string(self, value: &str) {
+        *self.matched = matches_any(self.patterns, value);
+    }
+
+    fn metric<'a>(
+        self,
+        _distribution: impl IntoIterator<Item = Observation>,
+        _unit: Unit,
+        _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        _flags: MetricFlags<'_>,
+    ) {
+    }
+
+    fn error(self, _error: ValidationError) {}
+}
+
+/// An entry whose matching string values are replaced with [`REDACTED`] as it is written
+struct RedactedEntry<'e, E> {
+    entry: &'e E,
+    patterns: &'e [Regex],
+}
+
+impl<E: Entry> Entry for RedactedEntry<'_, E> {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        self.entry.write(&mut RedactingEntryWriter {
+            writer,
+            patterns: self.patterns,
+        });
+    }
+
+    fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+        self.entry.sample_group()
+    }
+}
+
+struct RedactingEntryWriter<'w, W> {
+    writer: &'w mut W,
+    patterns: &'w [Regex],
+}
+
+impl<'a, W: EntryWriter<'a>> EntryWriter<'a> for RedactingEntryWriter<'_, W> {
+    fn timestamp(&mut self, timestamp: SystemTime) {
+        self.writer.timestamp(timestamp);
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        self.writer.value(
+            name,
+            &RedactedValue {
+                value,
+                patterns: self.patterns,
+            },
+        );
+    }
+
+    fn config(&mut self, config: &'a dyn EntryConfig) {
+        self.writer.config(config);
+    }
+}
+
+struct RedactedValue<'v, V: ?Sized> {
+    value: &'v V,
+    patterns: &'v [Regex],
+}
+
+impl<V: Value + ?Sized> Value for RedactedValue<'_, V> {
+    fn write(&self, writer: impl ValueWriter) {
+        self.value.write(RedactingValueWriter {
+            writer,
+            patterns: self.patterns,
+        });
+    }
+}
+
+struct RedactingValueWriter<'p, W> {
+    writer: W,
+    patterns: &'p [Regex],
+}
+
+impl<W: ValueWriter> ValueWriter for RedactingValueWriter<'_, W> {
+    fn string(self, value: &str) {
+        if matches_any(self.patterns, value) {
+            self.writer.string(REDACTED);
+        } else {
+            self.writer.string(value);
+        }
+    }
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        unit: Unit,
+        dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        flags: MetricFlags<'_>,
+    ) {
+        self.writer.metric(distribution, unit, dimensions, flags);
+    }
+
+    fn error(self, error: ValidationError) {
+        self.writer.error(error);
+    }
+
+    fn values<'a, V: Value + 'a>(self, values: impl IntoIterator<Item = &'a V>) {
+        let values: Vec<&V> = values.into_iter().collect();
+        if values
+            .iter()
+            .any(|value| value_matches(self.patterns, *value))
+        {
+            self.writer.string(REDACTED);
+        } else {
+            self.writer.values(values);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use metrique_writer_core::{
+        Entry, EntryIoStream, IoStreamError, test_stream::DummyEntryWriter,
+    };
+
+    use super::{PiiAction, PiiGuardStream};
+
+    const EMAIL: &str = r"[\w.+-]+@[\w-]+\.[\w.]+";
+    const CARD_NUMBER: &str = r"\b(?:\d[ -]?){13,16}\b";
+
+    #[derive(Default)]
+    struct CaptureStream(Vec<Vec<(String, String)>>);
+
+    impl EntryIoStream for CaptureStream {
+        fn next(&mut self, entry: &impl Entry) -> Result<(), IoStreamError> {
+            let mut writer = DummyEntryWriter::default();
+            entry.write(&mut writer);
+            self.0.push(writer.0);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(crate::Entry)]
+    struct TestEntry {
+        operation: &'static str,
+        message: &'static str,
+        tags: Vec<&'static str>,
+        count: u64,
+    }
+
+    fn clean_entry() -> TestEntry {
+        TestEntry {
+            operation: "Checkout",
+            message: "order placed",
+            tags: vec!["web", "eu"],
+            count: 1,
+        }
+    }
+
+    fn pii_entry() -> TestEntry {
+        TestEntry {
+            operation: "Checkout",
+            message: "payment failed for card 4111 1111 1111 1111",
+            tags: vec!["web", "someone@example.com"],
+            count: 1,
+        }
+    }
+
+    fn guard(action: PiiAction) -> PiiGuardStream<CaptureStream> {
+        PiiGuardStream::new(CaptureStream::default(), action)
+            .with_pattern(EMAIL)
+            .unwrap()
+            .with_pattern(CARD_NUMBER)
+            .unwrap()
+    }
+
+    fn written(entry: &TestEntry) -> Vec<(String, String)> {
+        let mut writer = DummyEntryWriter::default();
+        entry.write(&mut writer);
+        writer.0
+    }
+
+    fn field<'a>(fields: &'a [(String, String)], name: &str) -> &'a str {
+        &fields.iter().find(|(n, _)| n == name).unwrap().1
+    }
+
+    #[test]
+    fn non_matching_entries_are_unchanged() {
+        for action in [PiiAction::RedactField, PiiAction::DropEntry, PiiAction::Log] {
+            let mut stream = guard(action);
+            stream.next(&clean_entry()).unwrap();
+            assert_eq!(stream.into_inner().0, [written(&clean_entry())]);
+        }
+    }
+
+    #[test]
+    fn redacts_matching_fields() {
+        let mut stream = guard(PiiAction::RedactField);
+        stream.next(&pii_entry()).unwrap();
+
+        let entries = stream.into_inner().0;
+        assert_eq!(entries.len(), 1);
+        let fields = &entries[0];
+        assert_eq!(field(fields, "operation"), "Checkout");
+        assert_eq!(field(fields, "message"), "[REDACTED]");
+        assert_eq!(field(fields, "tags"), "[REDACTED]");
+        assert_eq!(field(fields, "count"), "[Unsigned(1)] None []");
+    }
+
+    #[test]
+    fn drops_matching_entries() {
+        let mut stream = guard(PiiAction::DropEntry);
+        let Err(IoStreamError::Validation(err)) = stream.next(&pii_entry()) else {
+            panic!("expected a validation error");
+        };
+        let err = err.to_string();
+        assert!(err.contains("for `message`"), "{err}");
+        assert!(err.contains("for `tags`"), "{err}");
+        assert!(!err.contains("4111"), "{err}");
+
+        stream.next(&clean_entry()).unwrap();
+        assert_eq!(stream.into_inner().0, [written(&clean_entry())]);
+    }
+
+    #[test]
+    fn log_writes_matching_entries_unchanged() {
+        let mut stream = guard(PiiAction::Log);
+        stream.next(&pii_entry()).unwrap();
+        assert_eq!(stream.into_inner().0, [written(&pii_entry())]);
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        assert!(
+            PiiGuardStream::new(CaptureStream::default(), PiiAction::Log)
+                .with_pattern("(unclosed")
+                .is_err()
+        );
+    }
+}
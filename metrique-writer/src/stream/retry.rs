@@ -0,0 +1,332 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{cell::Cell, io, thread, time::Duration};
+
+use metrique_writer_core::{Entry, EntryIoStream, IoStreamError};
+
+/// An [`EntryIoStream`] that retries writing and flushing the wrapped stream when it fails with
+/// a transient [`io::Error`], see [`EntryIoStreamExt::with_retry`].
+///
+/// Only the [`WouldBlock`] and [`Interrupted`] error kinds are retried. Other errors, including
+/// [validation errors](IoStreamError::Validation), are returned immediately.
+///
+/// Entries are retried by writing them to the wrapped stream again, which formats them again.
+/// To avoid writing the same output twice, a failed entry is only retried if the wrapped stream
+/// reports (through [`EntryIoStream::bytes_written`]) that the failed attempt wrote nothing. If
+/// the failed attempt wrote partial output, or the wrapped stream doesn't track the bytes it
+/// writes, the error is returned immediately. Flushes are always retried.
+///
+/// Because every attempt formats the entry again, `RetryStream` must not wrap a format that
+/// samples at random, such as [`sample_by_fixed_fraction`] or
+/// [`sample_by_congress_at_fixed_entries_per_second`]. Each attempt would draw again, so an entry
+/// that was sampled in could be sampled out on retry, and the sample rates written with retried
+/// entries would no longer match the entries that were kept. [`sample_by_consistent_hash`] makes
+/// the same decision on every attempt, so it can be retried.
+///
+/// Attempts are separated by a delay that starts at the `backoff` passed to
+/// [`EntryIoStreamExt::with_retry`] and doubles after every attempt. The delay blocks the
+/// calling thread, which is normally the thread of a [`BackgroundQueue`].
+///
+/// When every attempt fails, the error of the last attempt is returned, with the number of
+/// attempts added to its message.
+///
+/// [`EntryIoStreamExt::with_retry`]: crate::stream::EntryIoStreamExt::with_retry
+/// [`WouldBlock`]: io::ErrorKind::WouldBlock
+/// [`Interrupted`]: io::ErrorKind::Interrupted
+/// [`BackgroundQueue`]: crate::sink::BackgroundQueue
+/// [`sample_by_fixed_fraction`]: crate::sample::SampledFormatExt::sample_by_fixed_fraction
+/// [`sample_by_congress_at_fixed_entries_per_second`]: crate::sample::SampledFormatExt::sample_by_congress_at_fixed_entries_per_second
+/// [`sample_by_consistent_hash`]: crate::sample::SampledFormatExt::sample_by_consistent_hash
+#[derive(Debug)]
+pub struct RetryStream<S> {
+    stream: S,
+    max_attempts: u32,
+    backoff: Duration,
+}
+
+impl<S> RetryStream<S> {
+    pub(crate) fn new(stream: S, max_attempts: u32, backoff: Duration) -> Self {
+        assert!(max_attempts > 0, "max_attempts must be at least 1");
+        Self {
+            stream,
+            max_attempts,
+            backoff,
+        }
+    }
+
+    /// Return the wrapped stream
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Call `attempt` until it succeeds, fails with an error `retry` rejects, or runs out of
+    /// attempts.
+    fn retry<T>(
+        &mut self,
+        mut attempt: impl FnMut(&mut S) -> Result<T, IoStreamError>,
+        mut retry: impl FnMut(&S) -> bool,
+    ) -> Result<T, IoStreamError> {
+        let mut backoff = self.backoff;
+        for attempts in 1.. {
+            let err = match attempt(&mut self.stream) {
+                Err(IoStreamError::Io(err)) if is_retryable(&err) && retry(&self.stream) => err,
+                result => return result,
+            };
+            if attempts >= self.max_attempts {
+                return Err(IoStreamError::Io(io::Error::new(
+                    err.kind(),
+                    format!("giving up after {attempts} attempts: {err}"),
+                )));
+            }
+            if !backoff.is_zero() {
+                thread::sleep(backoff);
+            }
+            backoff = backoff.saturating_mul(2);
+        }
+        unreachable!("the loop only ends by returning")
+    }
+}
+
+fn is_retryable(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+    )
+}
+
+impl<S: EntryIoStream> EntryIoStream for RetryStream<S> {
+    fn next(&mut self, entry: &impl Entry) -> Result<(), IoStreamError> {
+        let before = Cell::new(None);
+        self.retry(
+            |stream| {
+                before.set(stream.bytes_written());
+                stream.next(entry)
+            },
+            // only retry if the failed attempt didn't write anything
+            |stream| before.get().is_some() && stream.bytes_written() == before.get(),
+        )
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.retry(
+            |stream| stream.flush().map_err(IoStreamError::Io),
+            |_stream| true,
+        )
+        .map_err(|err| match err {
+            IoStreamError::Io(err) => err,
+            // flushes only return IO errors
+            IoStreamError::Validation(err) => io::Error::other(err),
+        })
+    }
+
+    fn bytes_written(&self) -> Option<u64> {
+        self.stream.bytes_written()
+    }
+
+    fn entries_sampled_out(&self) -> Option<u64> {
+        self.stream.entries_sampled_out()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::VecDeque, io, time::Duration};
+
+    use metrique_writer_core::{
+        Entry, EntryIoStream, EntryWriter, IoStreamError, ValidationError, format::Format,
+    };
+
+    use crate::{format::FormatExt as _, stream::EntryIoStreamExt as _};
+
+    /// What the next call to [`ScriptedWriter::write`] does
+    enum Step {
+        Fail(io::ErrorKind),
+        Accept(usize),
+    }
+
+    /// A writer that follows a script, then accepts everything
+    #[derive(Default)]
+    struct ScriptedWriter {
+        script: VecDeque<Step>,
+        output: Vec<u8>,
+        flush_failures: Vec<io::ErrorKind>,
+    }
+
+    impl ScriptedWriter {
+        fn new(script: impl IntoIterator<Item = Step>) -> Self {
+            Self {
+                script: script.into_iter().collect(),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl io::Write for &mut ScriptedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let len = match self.script.pop_front() {
+                Some(Step::Fail(kind)) => return Err(kind.into()),
+                Some(Step::Accept(len)) => len.min(buf.len()),
+                None => buf.len(),
+            };
+            self.output.extend_from_slice(&buf[..len]);
+            Ok(len)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            match self.flush_failures.pop() {
+                Some(kind) => Err(kind.into()),
+                None => Ok(()),
+            }
+        }
+    }
+
+    /// Writes a single line, without retrying like `write_all` does
+    struct LineFormat;
+
+    impl Format for LineFormat {
+        fn format(
+            &mut self,
+            _entry: &impl Entry,
+            output: &mut impl io::Write,
+        ) -> Result<(), IoStreamError> {
+            let line = b"line\n";
+            let written = output.write(line)?;
+            if written < line.len() {
+                let rest = output.write(&line[written..])?;
+                assert_eq!(written + rest, line.len());
+            }
+            Ok(())
+        }
+    }
+
+    struct TestEntry;
+
+    impl Entry for TestEntry {
+        fn write<'a>(&'a self, _writer: &mut impl EntryWriter<'a>) {}
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        let mut output = ScriptedWriter::new([
+            Step::Fail(io::ErrorKind::WouldBlock),
+            Step::Fail(io::ErrorKind::Interrupted),
+        ]);
+        {
+            let mut stream = LineFormat
+                .output_to(&mut output)
+                .with_retry(3, Duration::ZERO);
+            stream.next(&TestEntry).unwrap();
+            assert_eq!(stream.bytes_written(), Some(5));
+        }
+        assert_eq!(output.output, b"line\n");
+    }
+
+    #[test]
+    fn reports_final_failure() {
+        let mut output = ScriptedWriter::new([
+            Step::Fail(io::ErrorKind::WouldBlock),
+            Step::Fail(io::ErrorKind::WouldBlock),
+            Step::Fail(io::ErrorKind::WouldBlock),
+        ]);
+        {
+            let mut stream = LineFormat
+                .output_to(&mut output)
+                .with_retry(3, Duration::from_millis(1));
+            let Err(IoStreamError::Io(err)) = stream.next(&TestEntry) else {
+                panic!("expected an IO error");
+            };
+            assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+            assert!(
+                err.to_string().contains("giving up after 3 attempts"),
+                "{err}"
+            );
+            // the next entry is written
+            stream.next(&TestEntry).unwrap();
+        }
+        assert_eq!(output.output, b"line\n");
+    }
+
+    #[test]
+    fn does_not_retry_other_errors() {
+        let mut output = ScriptedWriter::new([Step::Fail(io::ErrorKind::BrokenPipe)]);
+        {
+            let mut stream = LineFormat
+                .output_to(&mut output)
+                .with_retry(3, Duration::ZERO);
+            let Err(IoStreamError::Io(err)) = stream.next(&TestEntry) else {
+                panic!("expected an IO error");
+            };
+            assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+            assert!(!err.to_string().contains("giving up"));
+        }
+        assert!(output.output.is_empty());
+
+        struct InvalidFormat;
+        impl Format for InvalidFormat {
+            fn format(
+                &mut self,
+                _entry: &impl Entry,
+                _output: &mut impl io::Write,
+            ) -> Result<(), IoStreamError> {
+                Err(ValidationError::invalid("invalid").into())
+            }
+        }
+        let mut stream = InvalidFormat
+            .output_to(io::sink())
+            .with_retry(3, Duration::ZERO);
+        assert!(matches!(
+            stream.next(&TestEntry),
+            Err(IoStreamError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn does_not_retry_partial_output() {
+        let mut output =
+            ScriptedWriter::new([Step::Accept(2), Step::Fail(io::ErrorKind::WouldBlock)]);
+        {
+            let mut stream = LineFormat
+                .output_to(&mut output)
+                .with_retry(3, Duration::ZERO);
+            let Err(IoStreamError::Io(err)) = stream.next(&TestEntry) else {
+                panic!("expected an IO error");
+            };
+            assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        }
+        // the line is not written twice
+        assert_eq!(output.output, b"li");
+    }
+
+    #[test]
+    fn retries_flush() {
+        let mut output = ScriptedWriter {
+            flush_failures: vec![io::ErrorKind::Interrupted, io::ErrorKind::WouldBlock],
+            ..ScriptedWriter::default()
+        };
+        LineFormat
+            .output_to(&mut output)
+            .with_retry(3, Duration::ZERO)
+            .flush()
+            .unwrap();
+
+        output.flush_failures = vec![io::ErrorKind::WouldBlock; 2];
+        let mut stream = LineFormat
+            .output_to(&mut output)
+            .with_retry(2, Duration::ZERO);
+        let err = stream.flush().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WouldBlock);
+        assert!(
+            err.to_string().contains("giving up after 2 attempts"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "max_attempts must be at least 1")]
+    fn max_attempts_must_be_positive() {
+        let _ = LineFormat
+            .output_to(io::sink())
+            .with_retry(0, Duration::ZERO);
+    }
+}
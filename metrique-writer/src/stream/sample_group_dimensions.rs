@@ -0,0 +1,234 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{borrow::Cow, io};
+
+use metrique_writer_core::{
+    Entry, EntryIoStream, EntryWriter, IoStreamError,
+    config::EntryDimensions,
+    entry::{SampleGroupElement, SampleGroupMap},
+};
+
+use crate::CowStr;
+
+/// See [`EntryIoStreamExt::sample_group_dimensions`] or [`FormatExt::sample_group_dimensions`].
+///
+/// [`EntryIoStreamExt::sample_group_dimensions`]: crate::stream::EntryIoStreamExt::sample_group_dimensions
+/// [`FormatExt::sample_group_dimensions`]: crate::format::FormatExt::sample_group_dimensions
+#[derive(Clone, Debug)]
+pub struct SampleGroupDimensions<S> {
+    pub(crate) stream: S,
+    // used as-is for entries whose sample group has every key
+    pub(crate) dimensions: EntryDimensions,
+}
+
+impl<S> SampleGroupDimensions<S> {
+    pub(crate) fn new<K: Into<CowStr>>(
+        stream: S,
+        dimension_sets: impl IntoIterator<Item = impl IntoIterator<Item = K>>,
+    ) -> Self {
+        let dimension_sets: Vec<Cow<'static, [CowStr]>> = dimension_sets
+            .into_iter()
+            .map(|set| set.into_iter().map(Into::into).collect::<Vec<_>>())
+            .filter(|set| !set.is_empty())
+            .map(Cow::Owned)
+            .collect();
+        Self {
+            stream,
+            dimensions: EntryDimensions::new(Cow::Owned(dimension_sets)),
+        }
+    }
+}
+
+/// Wrap `entry` so that it sets the sets of `dimensions` whose keys are in its sample group
+pub(crate) fn with_sample_group_dimensions<E: Entry>(
+    dimensions: &EntryDimensions,
+    entry: E,
+) -> WithEntryDimensions<'_, E> {
+    let dimensions = if dimensions.is_empty() {
+        None
+    } else {
+        resolve(dimensions, &SampleGroupMap::from_entry(&entry))
+    };
+    WithEntryDimensions { entry, dimensions }
+}
+
+fn resolve<'d>(
+    dimensions: &'d EntryDimensions,
+    group: &SampleGroupMap,
+) -> Option<Cow<'d, EntryDimensions>> {
+    let present = |dimension: &str| group.get(dimension).is_some();
+    if dimensions.dim_sets().flatten().all(present) {
+        return Some(Cow::Borrowed(dimensions));
+    }
+
+    // skip the dimensions the sample group doesn't have, dropping the sets that end up empty or
+    // the same as an earlier set
+    let mut dimension_sets: Vec<Cow<'static, [CowStr]>> = vec![];
+    for set in dimensions.dim_sets() {
+        let set: Vec<CowStr> = set
+            .filter(|dimension| present(dimension))
+            .map(|dimension| Cow::Owned(dimension.to_owned()))
+            .collect();
+        if !set.is_empty() && !dimension_sets.iter().any(|other| **other == *set) {
+            dimension_sets.push(Cow::Owned(set));
+        }
+    }
+    (!dimension_sets.is_empty())
+        .then(|| Cow::Owned(EntryDimensions::new(Cow::Owned(dimension_sets))))
+}
+
+impl<S: EntryIoStream> EntryIoStream for SampleGroupDimensions<S> {
+    fn next(&mut self, entry: &impl Entry) -> Result<(), IoStreamError> {
+        self.stream
+            .next(&with_sample_group_dimensions(&self.dimensions, entry))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+
+    fn bytes_written(&self) -> Option<u64> {
+        self.stream.bytes_written()
+    }
+
+    fn entries_sampled_out(&self) -> Option<u64> {
+        self.stream.entries_sampled_out()
+    }
+}
+
+/// An entry that additionally sets an [`EntryDimensions`] config, if any
+pub(crate) struct WithEntryDimensions<'d, E> {
+    entry: E,
+    dimensions: Option<Cow<'d, EntryDimensions>>,
+}
+
+impl<E: Entry> Entry for WithEntryDimensions<'_, E> {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        self.entry.write(writer);
+        if let Some(dimensions) = &self.dimensions {
+            writer.config(&**dimensions);
+        }
+    }
+
+    fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+        self.entry.sample_group()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use metrique_writer_core::{
+        Entry, EntryIoStream, EntryWriter, config::EntryDimensions, entry::SampleGroupElement,
+    };
+    use metrique_writer_format_emf::Emf;
+
+    use crate::{format::FormatExt as _, stream::EntryIoStreamExt as _};
+
+    struct Request {
+        operation: Option<&'static str>,
+        status: Option<&'static str>,
+        // entry dimensions set by the entry itself
+        dimensions: Option<EntryDimensions>,
+    }
+
+    impl Entry for Request {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(std::time::SystemTime::UNIX_EPOCH);
+            writer.value("Operation", &self.operation);
+            writer.value("Status", &self.status);
+            writer.value("Count", &1u64);
+            if let Some(dimensions) = &self.dimensions {
+                writer.config(dimensions);
+            }
+        }
+
+        fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+            [("Operation", self.operation), ("Status", self.status)]
+                .into_iter()
+                .filter_map(|(key, value)| Some((Cow::Borrowed(key), Cow::Borrowed(value?))))
+        }
+    }
+
+    fn request(operation: Option<&'static str>, status: Option<&'static str>) -> Request {
+        Request {
+            operation,
+            status,
+            dimensions: None,
+        }
+    }
+
+    fn dimensions(line: &str) -> serde_json::Value {
+        let line: serde_json::Value = serde_json::from_str(line).unwrap();
+        line["_aws"]["CloudWatchMetrics"][0]["Dimensions"].clone()
+    }
+
+    fn write_all(requests: &[Request]) -> Vec<serde_json::Value> {
+        let mut output = vec![];
+        let mut stream = Emf::all_validations("Ns".into(), vec![vec![]])
+            .output_to(&mut output)
+            .sample_group_dimensions([
+                vec!["Operation"],
+                vec!["Operation", "Status"],
+                vec!["Status"],
+            ]);
+        for request in requests {
+            stream.next(request).unwrap();
+        }
+        drop(stream);
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(dimensions)
+            .collect()
+    }
+
+    #[test]
+    fn adds_sample_group_dimensions() {
+        assert_eq!(
+            write_all(&[request(Some("Get"), Some("OK"))]),
+            [serde_json::json!([
+                ["Operation"],
+                ["Operation", "Status"],
+                ["Status"]
+            ])]
+        );
+    }
+
+    #[test]
+    fn skips_missing_dimensions() {
+        assert_eq!(
+            write_all(&[
+                request(Some("Get"), None),
+                request(None, Some("OK")),
+                request(None, None),
+            ]),
+            [
+                serde_json::json!([["Operation"]]),
+                serde_json::json!([["Status"]]),
+                serde_json::json!([[]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn merges_with_entry_dimensions() {
+        let mut request = request(Some("Get"), Some("OK"));
+        request.dimensions = Some(EntryDimensions::new_static(&[Cow::Borrowed(&[
+            Cow::Borrowed("Status"),
+        ])]));
+
+        let mut output = vec![];
+        let mut stream = Emf::all_validations("Ns".into(), vec![vec![]])
+            .sample_group_dimensions([["Operation"]])
+            .output_to(&mut output);
+        stream.next(&request).unwrap();
+        drop(stream);
+        assert_eq!(
+            dimensions(String::from_utf8(output).unwrap().trim()),
+            serde_json::json!([["Status"], ["Operation"]])
+        );
+    }
+}
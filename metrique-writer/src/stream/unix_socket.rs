@@ -0,0 +1,301 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{
+    io::{self, Write as _},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use metrique_writer_core::{
+    Entry, EntryIoStream, IoStreamError,
+    format::{Format, FormatOutcome},
+};
+
+/// An [`EntryIoStream`] that sends each formatted entry over a Unix domain socket, prefixed by
+/// its length.
+///
+/// This is meant for local sidecars that consume metrics over a stream socket. Each entry is sent
+/// as one frame: its length in bytes as a big-endian `u32`, followed by the bytes written by the
+/// [`Format`] for that entry.
+///
+/// The socket is connected when the first entry is written. If connecting or writing fails, the
+/// connection is dropped and a new connection is attempted for a later entry, at most once per
+/// reconnect interval (see [`UnixSocketStream::with_reconnect_interval`]). While disconnected,
+/// entries are dropped and counted, see [`UnixSocketStream::dropped_entries`]. Dropped entries
+/// are not reported as errors, so a sidecar that is down does not flood the application logs.
+///
+/// Writes block until the sidecar reads them, so this should normally be used behind a
+/// [`BackgroundQueue`](crate::sink::BackgroundQueue).
+///
+/// # Example
+/// ```no_run
+/// # use metrique_writer::stream::UnixSocketStream;
+/// # use metrique_writer::{AttachGlobalEntrySinkExt, GlobalEntrySink, sink::global_entry_sink};
+/// # use metrique_writer_format_emf::Emf;
+/// global_entry_sink! { ServiceMetrics }
+///
+/// let stream = UnixSocketStream::new(
+///     Emf::all_validations("MyApp".into(), vec![vec![]]),
+///     "/run/metrics-sidecar.sock",
+/// );
+/// let dropped = stream.dropped_entries();
+/// let _handle = ServiceMetrics::attach_to_stream(stream);
+/// // `dropped.get()` can be reported periodically
+/// ```
+#[derive(Debug)]
+pub struct UnixSocketStream<F> {
+    format: F,
+    path: PathBuf,
+    connection: Option<UnixStream>,
+    reconnect_interval: Duration,
+    next_connect: Option<Instant>,
+    dropped: DroppedEntries,
+    entries_sampled_out: u64,
+    // reused between entries, holds the frame being written
+    buffer: Vec<u8>,
+}
+
+/// A handle to the number of entries dropped by a [`UnixSocketStream`], that can be read after
+/// the stream has been moved into a sink.
+#[derive(Debug, Clone, Default)]
+pub struct DroppedEntries(Arc<AtomicU64>);
+
+impl DroppedEntries {
+    /// Return the number of entries dropped so far
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn increment(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+const LENGTH_PREFIX_LEN: usize = size_of::<u32>();
+
+impl<F> UnixSocketStream<F> {
+    /// Create a stream sending entries formatted with `format` to the Unix domain socket at
+    /// `path`.
+    ///
+    /// This does not connect to the socket until the first entry is written.
+    pub fn new(format: F, path: impl AsRef<Path>) -> Self {
+        Self {
+            format,
+            path: path.as_ref().to_owned(),
+            connection: None,
+            reconnect_interval: Duration::from_secs(1),
+            next_connect: None,
+            dropped: DroppedEntries::default(),
+            entries_sampled_out: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Set the minimum time between two connection attempts after a failure, 1 second by
+    /// default.
+    pub fn with_reconnect_interval(mut self, interval: Duration) -> Self {
+        self.reconnect_interval = interval;
+        self
+    }
+
+    /// Return a handle to the number of entries dropped because the socket was disconnected
+    pub fn dropped_entries(&self) -> DroppedEntries {
+        self.dropped.clone()
+    }
+
+    /// Return true if the stream is currently connected
+    pub fn is_connected(&self) -> bool {
+        self.connection.is_some()
+    }
+
+    /// Connect if disconnected and the reconnect interval has passed, return true if connected
+    fn try_connect(&mut self) -> bool {
+        if self.connection.is_none() {
+            let now = Instant::now();
+            if self.next_connect.is_some_and(|next| now < next) {
+                return false;
+            }
+            match UnixStream::connect(&self.path) {
+                Ok(connection) => {
+                    self.connection = Some(connection);
+                    self.next_connect = None;
+                }
+                Err(_) => self.next_connect = Some(now + self.reconnect_interval),
+            }
+        }
+        self.connection.is_some()
+    }
+
+    fn disconnect(&mut self) {
+        self.connection = None;
+        self.next_connect = Some(Instant::now() + self.reconnect_interval);
+    }
+}
+
+impl<F: Format> EntryIoStream for UnixSocketStream<F> {
+    fn next(&mut self, entry: &impl Entry) -> Result<(), IoStreamError> {
+        self.buffer.clear();
+        self.buffer.extend_from_slice(&[0; LENGTH_PREFIX_LEN]);
+        let result = match self.format.format_outcome(entry, &mut self.buffer) {
+            Ok(FormatOutcome::Dropped) => {
+                self.entries_sampled_out += 1;
+                Ok(())
+            }
+            result => result.map(drop),
+        };
+
+        let len = self.buffer.len() - LENGTH_PREFIX_LEN;
+        if len > 0 {
+            let len = u32::try_from(len).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("entry of {len} bytes is too large for a frame"),
+                )
+            })?;
+            self.buffer[..LENGTH_PREFIX_LEN].copy_from_slice(&len.to_be_bytes());
+
+            // like an unframed stream, send whatever was written even if formatting failed
+            let written = self.try_connect()
+                && self
+                    .connection
+                    .as_mut()
+                    .is_some_and(|connection| connection.write_all(&self.buffer).is_ok());
+            if !written {
+                if self.connection.is_some() {
+                    // a partial frame may have been written, so the connection can't be reused
+                    self.disconnect();
+                }
+                self.dropped.increment();
+            }
+        }
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let Some(connection) = &mut self.connection else {
+            return Ok(());
+        };
+        let result = connection.flush();
+        if result.is_err() {
+            self.disconnect();
+        }
+        result
+    }
+    fn entries_sampled_out(&self) -> Option<u64> {
+        Some(self.entries_sampled_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Read,
+        os::unix::net::{UnixListener, UnixStream},
+        time::Duration,
+    };
+
+    use metrique_writer_core::{
+        EntryIoStream,
+        test_stream::{DummyFormat, TestEntry},
+    };
+
+    use super::UnixSocketStream;
+
+    fn read_frame(connection: &mut UnixStream) -> String {
+        let mut len = [0; 4];
+        connection.read_exact(&mut len).unwrap();
+        let mut frame = vec![0; u32::from_be_bytes(len) as usize];
+        connection.read_exact(&mut frame).unwrap();
+        String::from_utf8(frame).unwrap()
+    }
+
+    #[test]
+    fn writes_length_prefixed_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        let mut stream = UnixSocketStream::new(DummyFormat, &path);
+        stream.next(&TestEntry(1)).unwrap();
+        stream.next(&TestEntry(22)).unwrap();
+        stream.flush().unwrap();
+        assert!(stream.is_connected());
+
+        let (mut connection, _) = listener.accept().unwrap();
+        assert_eq!(
+            read_frame(&mut connection),
+            r#"[("value", "[Unsigned(1)] None []")]"#
+        );
+        assert_eq!(
+            read_frame(&mut connection),
+            r#"[("value", "[Unsigned(22)] None []")]"#
+        );
+        assert_eq!(stream.dropped_entries().get(), 0);
+    }
+
+    #[test]
+    fn drops_while_disconnected_and_reconnects() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.sock");
+
+        let mut stream =
+            UnixSocketStream::new(DummyFormat, &path).with_reconnect_interval(Duration::ZERO);
+        let dropped = stream.dropped_entries();
+
+        // nothing is listening yet
+        stream.next(&TestEntry(1)).unwrap();
+        stream.next(&TestEntry(2)).unwrap();
+        assert!(!stream.is_connected());
+        assert_eq!(dropped.get(), 2);
+
+        let listener = UnixListener::bind(&path).unwrap();
+        stream.next(&TestEntry(3)).unwrap();
+        let (mut connection, _) = listener.accept().unwrap();
+        assert_eq!(
+            read_frame(&mut connection),
+            r#"[("value", "[Unsigned(3)] None []")]"#
+        );
+
+        // the reader goes away, so the connection breaks and entries are dropped until the
+        // socket accepts connections again
+        drop(connection);
+        drop(listener);
+        std::fs::remove_file(&path).unwrap();
+        while stream.is_connected() {
+            stream.next(&TestEntry(4)).unwrap();
+        }
+        let dropped_while_broken = dropped.get();
+        assert!(dropped_while_broken > 2);
+
+        let listener = UnixListener::bind(&path).unwrap();
+        stream.next(&TestEntry(5)).unwrap();
+        let (mut connection, _) = listener.accept().unwrap();
+        assert_eq!(
+            read_frame(&mut connection),
+            r#"[("value", "[Unsigned(5)] None []")]"#
+        );
+        assert_eq!(dropped.get(), dropped_while_broken);
+    }
+
+    #[test]
+    fn waits_for_reconnect_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.sock");
+
+        let mut stream = UnixSocketStream::new(DummyFormat, &path)
+            .with_reconnect_interval(Duration::from_secs(3600));
+        stream.next(&TestEntry(1)).unwrap();
+
+        // the socket is now available, but the stream doesn't retry until the interval passed
+        let _listener = UnixListener::bind(&path).unwrap();
+        stream.next(&TestEntry(2)).unwrap();
+        assert!(!stream.is_connected());
+        assert_eq!(stream.dropped_entries().get(), 2);
+    }
+}
@@ -8,9 +8,12 @@
 //! For usage examples, see [`test_entry_sink`] and `examples/testing.rs`
 
 use std::{
-    collections::HashMap,
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
     sync::{Arc, Mutex},
-    time::SystemTime,
+    time::{Duration, SystemTime},
 };
 
 use metrique_core::{CloseEntry, InflectableEntry};
@@ -26,6 +29,12 @@ use crate::{
     sink::FlushWait,
 };
 
+#[cfg(feature = "json-schema")]
+mod json_schema;
+
+#[cfg(feature = "json-schema")]
+pub use json_schema::JsonSchemaFormat;
+
 /// Test flag. This is merely reflected in [TestEntry] to allow seeing that flags are set.
 #[derive(Debug)]
 pub struct TestFlagOpt;
@@ -330,6 +339,231 @@ pub fn to_test_entry(e: impl Entry) -> TestEntry {
     entry
 }
 
+/// Assert that an entry contains exactly the expected fields.
+///
+/// The entry is converted into a [`TestEntry`] (so it can be anything that implements
+/// [`Entry`], or a [`TestEntry`] read from an [`Inspector`]), and each of its properties and
+/// metrics is compared against the expected map. Fields that are missing, unexpected, or have a
+/// different value are all listed in the panic message. Expected values can be anything that
+/// converts into an [`ExpectedField`], e.g. strings for properties and numbers for metrics.
+///
+/// The timestamp of the entry is ignored, unless an expected `timestamp` (an
+/// `Option<SystemTime>`) is passed after the map.
+///
+/// # Example
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use metrique_writer::{Entry, assert_entry_eq};
+///
+/// #[derive(Entry)]
+/// #[entry(rename_all = "PascalCase")]
+/// struct RequestMetrics {
+///     #[entry(timestamp)]
+///     timestamp: SystemTime,
+///     operation: &'static str,
+///     count: u64,
+///     latency: Duration,
+/// }
+///
+/// let entry = RequestMetrics {
+///     timestamp: SystemTime::UNIX_EPOCH,
+///     operation: "Foo",
+///     count: 1,
+///     latency: Duration::from_millis(5),
+/// };
+/// assert_entry_eq!(&entry, {
+///     "Operation" => "Foo",
+///     "Count" => 1u64,
+///     "Latency" => Duration::from_millis(5),
+/// });
+/// assert_entry_eq!(&entry, {
+///     "Operation" => "Foo",
+///     "Count" => 1u64,
+///     "Latency" => 5.0,
+/// }, timestamp = Some(SystemTime::UNIX_EPOCH));
+/// ```
+#[macro_export]
+macro_rules! assert_entry_eq {
+    ($entry:expr, { $($name:expr => $value:expr),* $(,)? } $(,)?) => {
+        $crate::test_util::__assert_entry_eq(
+            $crate::test_util::TestEntry::from($entry),
+            ::std::vec![$(($name, $crate::test_util::ExpectedField::from($value))),*],
+            ::std::option::Option::None,
+        )
+    };
+    ($entry:expr, { $($name:expr => $value:expr),* $(,)? }, timestamp = $timestamp:expr $(,)?) => {
+        $crate::test_util::__assert_entry_eq(
+            $crate::test_util::TestEntry::from($entry),
+            ::std::vec![$(($name, $crate::test_util::ExpectedField::from($value))),*],
+            ::std::option::Option::Some($timestamp),
+        )
+    };
+}
+
+/// The expected value of a field in [`assert_entry_eq!`](crate::assert_entry_eq).
+///
+/// Numbers are compared against metrics with a single observation. Integers and floats compare
+/// by value (so `1u64` matches a metric written as `1.0`), and durations are compared in
+/// milliseconds, the unit they are written in.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum ExpectedField {
+    /// A string property
+    Property(String),
+    /// A metric with a single integer observation
+    Unsigned(u64),
+    /// A metric with a single floating point observation
+    Floating(f64),
+}
+
+impl ExpectedField {
+    fn matches(&self, entry: &TestEntry, name: &str) -> bool {
+        match self {
+            ExpectedField::Property(expected) => entry.values.get(name) == Some(expected),
+            ExpectedField::Unsigned(expected) => {
+                single_observation(entry, name).is_some_and(|obs| match obs {
+                    Observation::Unsigned(v) => v == *expected,
+                    Observation::Floating(v) => v == *expected as f64,
+                    _ => false,
+                })
+            }
+            ExpectedField::Floating(expected) => {
+                single_observation(entry, name).is_some_and(|obs| match obs {
+                    Observation::Unsigned(v) => v as f64 == *expected,
+                    Observation::Floating(v) => v == *expected,
+                    _ => false,
+                })
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ExpectedField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpectedField::Property(value) => write!(f, "{value:?}"),
+            ExpectedField::Unsigned(value) => write!(f, "{value}"),
+            ExpectedField::Floating(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+impl From<&str> for ExpectedField {
+    fn from(value: &str) -> Self {
+        ExpectedField::Property(value.to_owned())
+    }
+}
+
+impl From<String> for ExpectedField {
+    fn from(value: String) -> Self {
+        ExpectedField::Property(value)
+    }
+}
+
+macro_rules! expected_unsigned {
+    ($($t:ty),+) => {
+        $(
+            impl From<$t> for ExpectedField {
+                fn from(value: $t) -> Self {
+                    ExpectedField::Unsigned(value.into())
+                }
+            }
+        )+
+    };
+}
+
+expected_unsigned!(u8, u16, u32, u64, bool);
+
+impl From<usize> for ExpectedField {
+    fn from(value: usize) -> Self {
+        ExpectedField::Unsigned(value as u64)
+    }
+}
+
+impl From<f32> for ExpectedField {
+    fn from(value: f32) -> Self {
+        ExpectedField::Floating(value.into())
+    }
+}
+
+impl From<f64> for ExpectedField {
+    fn from(value: f64) -> Self {
+        ExpectedField::Floating(value)
+    }
+}
+
+impl From<Duration> for ExpectedField {
+    fn from(value: Duration) -> Self {
+        ExpectedField::Floating(value.as_secs_f64() * 1000.0)
+    }
+}
+
+fn single_observation(entry: &TestEntry, name: &str) -> Option<Observation> {
+    match entry.metrics.get(name)?.distribution.as_slice() {
+        [obs] => Some(*obs),
+        _ => None,
+    }
+}
+
+fn describe_metric(metric: &Metric) -> String {
+    match metric.distribution.as_slice() {
+        [Observation::Unsigned(v)] => v.to_string(),
+        [Observation::Floating(v)] => v.to_string(),
+        distribution => format!("{distribution:?}"),
+    }
+}
+
+fn describe_field(entry: &TestEntry, name: &str) -> Option<String> {
+    match (entry.values.get(name), entry.metrics.get(name)) {
+        (Some(value), _) => Some(format!("{value:?}")),
+        (None, Some(metric)) => Some(describe_metric(metric)),
+        (None, None) => None,
+    }
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_entry_eq(
+    entry: TestEntry,
+    expected: Vec<(&str, ExpectedField)>,
+    timestamp: Option<Option<SystemTime>>,
+) {
+    let mut mismatches = String::new();
+    if let Some(timestamp) = timestamp
+        && entry.timestamp != timestamp
+    {
+        let _ = writeln!(
+            mismatches,
+            "  timestamp: expected {timestamp:?}, found {:?}",
+            entry.timestamp
+        );
+    }
+
+    let expected: BTreeMap<&str, ExpectedField> = expected.into_iter().collect();
+    for (name, value) in &expected {
+        if !value.matches(&entry, name) {
+            let found = describe_field(&entry, name).unwrap_or_else(|| "no field".to_owned());
+            let _ = writeln!(mismatches, "  {name:?}: expected {value}, found {found}");
+        }
+    }
+    let mut unexpected: Vec<&String> = entry
+        .values
+        .keys()
+        .chain(entry.metrics.keys())
+        .filter(|name| !expected.contains_key(name.as_str()))
+        .collect();
+    unexpected.sort();
+    unexpected.dedup();
+    for name in unexpected {
+        let found = describe_field(&entry, name).unwrap_or_default();
+        let _ = writeln!(mismatches, "  {name:?}: unexpected field {found}");
+    }
+
+    if !mismatches.is_empty() {
+        panic!("entry does not match the expected fields:\n{mismatches}");
+    }
+}
+
 /// Convert a `#[metric]` directly to `TestEntry`
 ///
 /// # Example
@@ -518,6 +752,135 @@ pub fn render_entry_sink<F: Format + Send + 'static>(format: F) -> (RenderQueue<
     (queue, sink)
 }
 
+/// A [`GlobalAlloc`] that forwards to [`System`] and counts allocations made while inside
+/// [`count_allocations`], so tests can guard the hot append path against allocation regressions.
+///
+/// Counting is per-thread, so allocations made by other tests (or background threads) running
+/// concurrently are not counted.
+///
+/// Install it as the global allocator of your test binary (e.g. at the top of a file in `tests/`):
+///
+/// ```
+/// use metrique_writer::test_util::{CountingAllocator, assert_no_allocations};
+/// use metrique_writer::{Entry, EntrySink, sink::DevNullSink};
+///
+/// #[global_allocator]
+/// static ALLOCATOR: CountingAllocator = CountingAllocator::new();
+///
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     operation: &'static str,
+///     number_of_ducks: usize,
+/// }
+///
+/// # fn main() {
+/// let sink = DevNullSink::new();
+/// assert_no_allocations(|| {
+///     sink.append(RequestMetrics {
+///         operation: "SayHello",
+///         number_of_ducks: 10,
+///     })
+/// });
+/// # }
+/// ```
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct CountingAllocator;
+
+impl CountingAllocator {
+    /// Return a new [`CountingAllocator`]
+    pub const fn new() -> Self {
+        CountingAllocator
+    }
+}
+
+thread_local! {
+    // `const` initialized, without destructors, so they can be used from within the allocator
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn record_allocation() {
+    // `try_with` since the allocator can be called while thread locals are being torn down
+    let _ = COUNTING.try_with(|counting| {
+        if counting.get() {
+            let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        }
+    });
+}
+
+// SAFETY: forwards to `System`, only additionally updating thread-local counters
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        record_allocation();
+        // SAFETY: forwarded from the caller
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        record_allocation();
+        // SAFETY: forwarded from the caller
+        unsafe { System.alloc_zeroed(layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        record_allocation();
+        // SAFETY: forwarded from the caller
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: forwarded from the caller
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+/// Run `f`, returning its result and the number of heap allocations (including reallocations)
+/// it made on the current thread.
+///
+/// # Panics
+/// Panics if [`CountingAllocator`] is not installed as the global allocator.
+pub fn count_allocations<R>(f: impl FnOnce() -> R) -> (R, usize) {
+    struct Reset(bool);
+    impl Drop for Reset {
+        fn drop(&mut self) {
+            COUNTING.with(|counting| counting.set(self.0));
+        }
+    }
+
+    let previous = COUNTING.with(|counting| counting.replace(true));
+    let _reset = Reset(previous);
+    let before = ALLOCATIONS.with(Cell::get);
+
+    // make sure allocations are actually being counted
+    drop(std::hint::black_box(Box::new(0u8)));
+    let after_probe = ALLOCATIONS.with(Cell::get);
+    assert!(
+        after_probe > before,
+        "CountingAllocator is not installed as the #[global_allocator]"
+    );
+
+    let result = f();
+    let allocations = ALLOCATIONS.with(Cell::get) - after_probe;
+    (result, allocations)
+}
+
+/// Run `f` and assert that it makes no heap allocations on the current thread.
+///
+/// See [`CountingAllocator`] for an example.
+///
+/// # Panics
+/// Panics if `f` allocates, or if [`CountingAllocator`] is not installed as the global allocator.
+#[track_caller]
+pub fn assert_no_allocations<R>(f: impl FnOnce() -> R) -> R {
+    let (result, allocations) = count_allocations(f);
+    assert_eq!(
+        allocations, 0,
+        "expected no allocations, but {allocations} allocation(s) occurred"
+    );
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -554,4 +917,94 @@ mod tests {
         let entries = sink.inspector.entries();
         let _ = &entries[0].values["wrong_name"];
     }
+
+    #[derive(Entry)]
+    #[entry(rename_all = "PascalCase")]
+    struct RequestMetrics {
+        #[entry(timestamp)]
+        timestamp: SystemTime,
+        operation: &'static str,
+        count: u64,
+        latency: Duration,
+        extra: Option<u32>,
+    }
+
+    fn request_metrics(extra: Option<u32>) -> RequestMetrics {
+        RequestMetrics {
+            timestamp: SystemTime::UNIX_EPOCH,
+            operation: "Foo",
+            count: 1,
+            latency: Duration::from_millis(5),
+            extra,
+        }
+    }
+
+    #[test]
+    fn assert_entry_eq_matches() {
+        let entry = request_metrics(None);
+        crate::assert_entry_eq!(&entry, {
+            "Operation" => "Foo",
+            "Count" => 1u64,
+            "Latency" => Duration::from_millis(5),
+        });
+        // numbers compare by value, and the timestamp can be checked
+        crate::assert_entry_eq!(to_test_entry(&entry), {
+            "Operation" => String::from("Foo"),
+            "Count" => 1.0,
+            "Latency" => 5u64,
+        }, timestamp = Some(SystemTime::UNIX_EPOCH));
+    }
+
+    #[test]
+    fn assert_entry_eq_lists_mismatches() {
+        let panic = std::panic::catch_unwind(|| {
+            crate::assert_entry_eq!(request_metrics(Some(3)), {
+                "Operation" => "Bar",
+                "Count" => 1u64,
+                "Latency" => 5u64,
+                "Missing" => 2u64,
+            });
+        })
+        .unwrap_err();
+        let message = panic.downcast_ref::<String>().unwrap();
+        assert_eq!(
+            message,
+            "entry does not match the expected fields:\n\
+             \x20 \"Missing\": expected 2, found no field\n\
+             \x20 \"Operation\": expected \"Bar\", found \"Foo\"\n\
+             \x20 \"Extra\": unexpected field 3\n"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "timestamp: expected None, found Some(")]
+    fn assert_entry_eq_checks_timestamp() {
+        crate::assert_entry_eq!(request_metrics(None), {
+            "Operation" => "Foo",
+            "Count" => 1u64,
+            "Latency" => 5u64,
+        }, timestamp = None);
+    }
+
+    #[test]
+    #[should_panic(expected = "\"Latency\": expected 5, found 5.5")]
+    fn assert_entry_eq_does_not_truncate_floats() {
+        let mut entry = request_metrics(None);
+        entry.latency = Duration::from_micros(5500);
+        crate::assert_entry_eq!(entry, {
+            "Operation" => "Foo",
+            "Count" => 1u64,
+            "Latency" => 5u64,
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "\"Count\": expected \"1\", found 1")]
+    fn assert_entry_eq_distinguishes_properties_and_metrics() {
+        crate::assert_entry_eq!(request_metrics(None), {
+            "Operation" => "Foo",
+            "Count" => "1",
+            "Latency" => 5u64,
+        });
+    }
 }
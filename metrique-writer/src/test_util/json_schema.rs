@@ -0,0 +1,314 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt, io};
+
+use jsonschema::Validator;
+use metrique_writer_core::{Entry, IoStreamError};
+
+use crate::{
+    format::{Format, FormatOutcome},
+    sample::SampledFormat,
+};
+
+/// A [`Format`] decorator that checks every line written by the inner format against a
+/// [JSON Schema](https://json-schema.org/), and panics if a line doesn't conform.
+///
+/// This is meant for tests, to catch changes to the output that would break its consumers
+/// (e.g. an EMF agent or a log processor). Each non-empty output line is parsed as JSON and
+/// validated, so it works with any newline-delimited JSON format such as EMF. The output is
+/// written unchanged once validated.
+///
+/// This requires that the `json-schema` feature be enabled.
+///
+/// # Example
+/// ```
+/// use metrique_writer::{Entry, EntryIoStream, FormatExt, test_util::JsonSchemaFormat};
+/// use metrique_writer_format_emf::Emf;
+///
+/// #[derive(Entry)]
+/// struct RequestMetrics {
+///     operation: &'static str,
+///     latency: u64,
+/// }
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "required": ["_aws", "latency"],
+///     "properties": { "latency": { "type": "number" } },
+/// });
+/// let format = JsonSchemaFormat::new(Emf::all_validations("MyApp".into(), vec![vec![]]), &schema);
+///
+/// let mut output = vec![];
+/// let mut stream = format.output_to(&mut output);
+/// // panics if the EMF output doesn't match the schema
+/// stream.next(&RequestMetrics { operation: "Get", latency: 10 }).unwrap();
+/// ```
+pub struct JsonSchemaFormat<F> {
+    format: F,
+    validator: Validator,
+    buffer: Vec<u8>,
+}
+
+impl<F: fmt::Debug> fmt::Debug for JsonSchemaFormat<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JsonSchemaFormat")
+            .field("format", &self.format)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F> JsonSchemaFormat<F> {
+    /// Wrap `format`, checking its output against `schema`.
+    ///
+    /// # Panics
+    /// Panics if `schema` is not a valid JSON Schema.
+    pub fn new(format: F, schema: &serde_json::Value) -> Self {
+        let validator = jsonschema::validator_for(schema)
+            .unwrap_or_else(|e| panic!("JsonSchemaFormat: invalid schema: {e}"));
+        Self {
+            format,
+            validator,
+            buffer: vec![],
+        }
+    }
+
+    /// Return a mutable reference to the inner [`Format`].
+    pub fn format_mut(&mut self) -> &mut F {
+        &mut self.format
+    }
+
+    /// Validate the buffered output, and then write it to `output`
+    fn validate_and_write(&mut self, output: &mut impl io::Write) -> io::Result<()> {
+        for line in self.buffer.split(|&b| b == b'\n') {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            let line = String::from_utf8_lossy(line);
+            let instance: serde_json::Value = serde_json::from_str(&line)
+                .unwrap_or_else(|e| panic!("JsonSchemaFormat: output is not JSON ({e}): {line}"));
+            let errors: Vec<String> = self
+                .validator
+                .iter_errors(&instance)
+                .map(|error| format!("{} at `{}`", error, error.instance_path))
+                .collect();
+            if !errors.is_empty() {
+                panic!(
+                    "JsonSchemaFormat: output does not conform to the schema:\n- {}\noutput: {line}",
+                    errors.join("\n- ")
+                );
+            }
+        }
+        output.write_all(&self.buffer)
+    }
+}
+
+impl<F: Format> Format for JsonSchemaFormat<F> {
+    fn format(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<(), IoStreamError> {
+        self.buffer.clear();
+        let result = self.format.format(entry, &mut self.buffer);
+        // validation errors may still have written output, which must conform too
+        self.validate_and_write(output)?;
+        result
+    }
+
+    fn format_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<usize, IoStreamError> {
+        self.buffer.clear();
+        let result = self.format.format_counted(entry, &mut self.buffer);
+        self.validate_and_write(output)?;
+        result
+    }
+
+    fn format_outcome(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+    ) -> Result<FormatOutcome, IoStreamError> {
+        self.buffer.clear();
+        let result = self.format.format_outcome(entry, &mut self.buffer);
+        self.validate_and_write(output)?;
+        result
+    }
+}
+
+impl<F: SampledFormat> SampledFormat for JsonSchemaFormat<F> {
+    fn format_with_sample_rate(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+        rate: f32,
+    ) -> Result<(), IoStreamError> {
+        self.buffer.clear();
+        let result = self
+            .format
+            .format_with_sample_rate(entry, &mut self.buffer, rate);
+        self.validate_and_write(output)?;
+        result
+    }
+
+    fn format_with_sample_rate_counted(
+        &mut self,
+        entry: &impl Entry,
+        output: &mut impl io::Write,
+        rate: f32,
+    ) -> Result<usize, IoStreamError> {
+        self.buffer.clear();
+        let result = self
+            .format
+            .format_with_sample_rate_counted(entry, &mut self.buffer, rate);
+        self.validate_and_write(output)?;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrique_writer_core::{Entry, EntryIoStream, EntryWriter};
+    use metrique_writer_format_emf::Emf;
+
+    use super::JsonSchemaFormat;
+    use crate::{
+        format::{Format as _, FormatExt as _, FormatOutcome},
+        sample::SampledFormatExt as _,
+    };
+
+    struct Request {
+        operation: &'static str,
+        latency: Option<u64>,
+    }
+
+    impl Entry for Request {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            writer.timestamp(std::time::SystemTime::UNIX_EPOCH);
+            writer.value("Operation", self.operation);
+            writer.value("Latency", &self.latency);
+        }
+    }
+
+    fn schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "required": ["_aws", "Operation", "Latency"],
+            "properties": {
+                "_aws": {
+                    "type": "object",
+                    "required": ["Timestamp", "CloudWatchMetrics"],
+                },
+                "Operation": { "enum": ["Get", "Put"] },
+                "Latency": { "type": "number" },
+            },
+        })
+    }
+
+    fn write(request: Request) -> String {
+        let format =
+            JsonSchemaFormat::new(Emf::all_validations("Ns".into(), vec![vec![]]), &schema());
+        let mut output = vec![];
+        let mut stream = format.output_to(&mut output);
+        stream.next(&request).unwrap();
+        drop(stream);
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn accepts_conforming_output() {
+        let output = write(Request {
+            operation: "Get",
+            latency: Some(10),
+        });
+        // the output is passed through unchanged
+        let expected = {
+            let mut output = vec![];
+            Emf::all_validations("Ns".into(), vec![vec![]])
+                .output_to(&mut output)
+                .next(&Request {
+                    operation: "Get",
+                    latency: Some(10),
+                })
+                .unwrap();
+            String::from_utf8(output).unwrap()
+        };
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not conform to the schema")]
+    fn rejects_missing_field() {
+        write(Request {
+            operation: "Get",
+            latency: None,
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "at `/Operation`")]
+    fn rejects_wrong_value() {
+        write(Request {
+            operation: "Delete",
+            latency: Some(10),
+        });
+    }
+
+    #[test]
+    fn validates_sampled_output() {
+        // sampled EMF writes metrics as values and counts
+        let mut schema = schema();
+        schema["properties"]["Latency"] = serde_json::json!({
+            "type": "object",
+            "required": ["Values", "Counts"],
+        });
+        let format = JsonSchemaFormat::new(
+            Emf::all_validations("Ns".into(), vec![vec![]]).with_sampling(),
+            &schema,
+        );
+        let mut output = vec![];
+        let mut stream = format.sample_by_fixed_fraction(1.0).output_to(&mut output);
+        stream
+            .next(&Request {
+                operation: "Put",
+                latency: Some(1),
+            })
+            .unwrap();
+        drop(stream);
+        assert!(!output.is_empty());
+    }
+
+    #[test]
+    fn forwards_dropped_outcome() {
+        let mut format = JsonSchemaFormat::new(
+            Emf::all_validations("Ns".into(), vec![vec![]])
+                .with_sampling()
+                .sample_by_fixed_fraction(f32::MIN_POSITIVE),
+            &schema(),
+        );
+        let mut output = vec![];
+        let outcome = format
+            .format_outcome(
+                &Request {
+                    operation: "Put",
+                    latency: Some(1),
+                },
+                &mut output,
+            )
+            .unwrap();
+        assert_eq!(outcome, FormatOutcome::Dropped);
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid schema")]
+    fn rejects_invalid_schema() {
+        JsonSchemaFormat::new(
+            Emf::all_validations("Ns".into(), vec![vec![]]),
+            &serde_json::json!({ "type": 12 }),
+        );
+    }
+}
@@ -0,0 +1,62 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use metrique_writer::{
+    Entry, EntrySink,
+    sink::DevNullSink,
+    test_util::{CountingAllocator, assert_no_allocations, count_allocations},
+};
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator::new();
+
+#[derive(Entry)]
+struct RequestMetrics {
+    operation: &'static str,
+    number_of_ducks: usize,
+    success: bool,
+}
+
+#[test]
+fn append_to_dev_null_does_not_allocate() {
+    let sink = DevNullSink::new();
+    assert_no_allocations(|| {
+        sink.append(RequestMetrics {
+            operation: "SayHello",
+            number_of_ducks: 10,
+            success: true,
+        })
+    });
+}
+
+#[test]
+fn allocations_are_counted() {
+    let (vec, allocations) = count_allocations(|| {
+        let mut vec = vec![1u64];
+        vec.push(2u64);
+        vec
+    });
+    assert_eq!(vec, [1, 2]);
+    // the initial allocation and the reallocation on growth
+    assert_eq!(allocations, 2);
+
+    let ((), allocations) = count_allocations(|| {});
+    assert_eq!(allocations, 0);
+}
+
+#[test]
+fn nested_counting() {
+    let (inner, outer) = count_allocations(|| {
+        let (_, inner) = count_allocations(|| Box::new(1u32));
+        inner
+    });
+    assert_eq!(inner, 1);
+    // the outer count also includes the inner closure's allocation and its probe
+    assert!(outer >= 2, "{outer}");
+}
+
+#[test]
+#[should_panic(expected = "expected no allocations, but 1 allocation(s) occurred")]
+fn detects_allocation() {
+    assert_no_allocations(|| std::hint::black_box(String::from("allocates")));
+}
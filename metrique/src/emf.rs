@@ -17,6 +17,29 @@ pub use metrique_writer_format_emf::{
 #[cfg(feature = "emf")]
 pub use metrique_writer_format_emf::flags;
 
+// used by `#[metrics(emf::high_storage_resolution)]`
+#[cfg(feature = "emf")]
+#[doc(hidden)]
+pub use metrique_writer_format_emf::HighStorageResolution as __HighStorageResolution;
+
+// without the `emf` feature, `#[metrics(emf::high_storage_resolution)]` still compiles, but
+// doesn't set any flag, since there is no format to read it
+#[cfg(not(feature = "emf"))]
+#[doc(hidden)]
+pub type __HighStorageResolution<T> =
+    metrique_writer_core::value::ForceFlag<T, __NoHighStorageResolutionCtor>;
+
+#[cfg(not(feature = "emf"))]
+#[doc(hidden)]
+pub struct __NoHighStorageResolutionCtor;
+
+#[cfg(not(feature = "emf"))]
+impl metrique_writer_core::value::FlagConstructor for __NoHighStorageResolutionCtor {
+    fn construct() -> metrique_writer_core::MetricFlags<'static> {
+        metrique_writer_core::MetricFlags::empty()
+    }
+}
+
 /// Add EMF Entry-specific dimensions
 ///
 /// Generally, you will not use this directly. Instead, use the `#[metrics(emf::dimension_sets)]` attribute. See the
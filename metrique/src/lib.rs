@@ -15,6 +15,9 @@ pub mod json;
 mod keep_alive;
 #[cfg(feature = "local-format")]
 pub mod local;
+mod merge;
+#[cfg(feature = "serde")]
+pub mod serialize;
 
 /// Provides timing utilities for metrics, including timestamps and duration measurements.
 ///
@@ -79,10 +82,12 @@ pub mod _guide {
     pub mod extending {}
 }
 
+pub use merge::{MergeTarget, Merged};
 use metrique_core::CloseEntry;
 use metrique_writer_core::Entry;
 use metrique_writer_core::EntryWriter;
 use metrique_writer_core::entry::SampleGroupElement;
+use metrique_writer_core::entry::SyncBoxEntry;
 pub use slot::{FlushGuard, ForceFlushGuard, LazySlot, OnParentDrop, Slot, SlotGuard};
 
 pub use flex::Flex;
@@ -97,8 +102,8 @@ use std::fmt::Debug;
 use std::sync::Arc;
 
 pub use metrique_core::{
-    CloseValue, CloseValueRef, Counter, CounterGuard, InflectableEntry, NameStyle,
-    OwnedCounterGuard,
+    CloseValue, CloseValueRef, Counter, CounterGuard, Gated, Gauge, InflectableEntry, NameStyle,
+    OwnedCounterGuard, Reset,
 };
 
 /// Unit types and utilities for metrics.
@@ -115,6 +120,10 @@ pub mod unit {
         Second, Terabit, TerabitPerSecond, Terabyte, TerabytePerSecond,
     };
     use metrique_writer_core::{MetricValue, unit::WithUnit};
+    // used by `#[metrics(flatten, unit = ...)]`
+    #[doc(hidden)]
+    pub use metrique_writer_core::entry::DefaultUnitEntryWriter;
+
     /// Internal trait to attach units when closing values
     #[doc(hidden)]
     pub trait AttachUnit: Sized {
@@ -133,15 +142,19 @@ pub mod unit {
 
 #[doc(hidden)]
 pub mod format {
-    pub use metrique_writer_core::value::FormattedValue;
+    pub use metrique_writer_core::value::{
+        ConfiguredFormattedValue, FormattedValue, WithFormatter,
+    };
 }
 
 /// Test utilities for metrique
 #[cfg(feature = "test-util")]
 pub mod test_util {
     pub use crate::writer::test_util::{
-        Inspector, Metric, TestEntry, TestEntrySink, test_entry_sink, test_metric, to_test_entry,
+        ExpectedField, Inspector, Metric, TestEntry, TestEntrySink, test_entry_sink, test_metric,
+        to_test_entry,
     };
+    pub use metrique_writer::assert_entry_eq;
 }
 
 /// Wide event macros and utilities.
@@ -322,6 +335,92 @@ impl<E: CloseEntry + Send + Sync + 'static, S: EntrySink<RootMetric<E>> + Send +
         ForceFlushGuard::new(self.inner.force_drop_guard())
     }
 
+    /// Create a [`FlushGuard`] that delays flushing like [`Self::flush_guard`], but for at
+    /// most `timeout`.
+    ///
+    /// This spawns a task on `runtime` that waits for `timeout` and then drops a
+    /// [`ForceFlushGuard`], so an entry extended by background work can't be held back forever.
+    /// Once the timeout has elapsed, the entry is flushed as soon as this struct is dropped (or
+    /// immediately, if it has been dropped already), even if the returned [`FlushGuard`] (or any
+    /// other) is still alive. Metrics written by the background work after that point are
+    /// not emitted.
+    ///
+    /// The spawned task does not keep the entry alive, and exits as soon as the entry is
+    /// flushed, so an entry that flushes before the timeout doesn't leave a timer behind. The
+    /// timer must be enabled on `runtime`.
+    ///
+    /// This requires the `flush-timeout` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use metrique::{OnParentDrop, ServiceMetrics, Slot, SlotGuard};
+    /// use metrique::unit_of_work::metrics;
+    /// use metrique::writer::GlobalEntrySink;
+    ///
+    /// #[metrics(rename_all = "PascalCase")]
+    /// struct RequestMetrics {
+    ///     operation: &'static str,
+    ///     #[metrics(flatten)]
+    ///     background_metrics: Slot<BackgroundMetrics>,
+    /// }
+    ///
+    /// #[metrics(subfield)]
+    /// #[derive(Default)]
+    /// struct BackgroundMetrics {
+    ///     items_processed: usize,
+    /// }
+    ///
+    /// async fn handle_request() {
+    ///     let mut metrics = RequestMetrics {
+    ///         operation: "abc",
+    ///         background_metrics: Default::default(),
+    ///     }
+    ///     .append_on_drop(ServiceMetrics::sink());
+    ///
+    ///     // wait for the background work, but for no more than 5 seconds
+    ///     let flush_guard = metrics.flush_guard_with_timeout(
+    ///         Duration::from_secs(5),
+    ///         &tokio::runtime::Handle::current(),
+    ///     );
+    ///     let background_metrics = metrics
+    ///         .background_metrics
+    ///         .open(OnParentDrop::Wait(flush_guard))
+    ///         .unwrap();
+    ///
+    ///     tokio::task::spawn(do_background_work(background_metrics));
+    /// }
+    ///
+    /// async fn do_background_work(mut metrics: SlotGuard<BackgroundMetrics>) {
+    ///     tokio::time::sleep(Duration::from_secs(1)).await;
+    ///     metrics.items_processed += 1;
+    /// }
+    /// ```
+    #[cfg(feature = "flush-timeout")]
+    pub fn flush_guard_with_timeout(
+        &self,
+        timeout: std::time::Duration,
+        runtime: &tokio::runtime::Handle,
+    ) -> FlushGuard {
+        let force_flush_guard = self.force_flush_guard();
+        let mut flushed = self
+            .inner
+            .flushed
+            .get_or_init(|| tokio::sync::watch::Sender::new(()))
+            .subscribe();
+        runtime.spawn(async move {
+            // nothing is ever sent, so `changed` only completes once the entry is flushed
+            if tokio::time::timeout(timeout, flushed.changed())
+                .await
+                .is_err()
+            {
+                drop(force_flush_guard);
+            }
+        });
+        self.flush_guard()
+    }
+
     /// Return a cloneable handle to the contents. The handle allows for cloneable,
     /// shared access to the contents.
     ///
@@ -379,6 +478,11 @@ impl<E: CloseEntry + Send + Sync + 'static, S: EntrySink<RootMetric<E>> + Send +
 struct AppendAndCloseOnDropInner<E: CloseEntry, S: EntrySink<RootMetric<E>>> {
     entry: Option<E>,
     sink: S,
+    /// Entries written alongside this one, added by [`Merged::new`]
+    merged: Vec<SyncBoxEntry>,
+    /// Closed when the entry is flushed, to stop the timers of `flush_guard_with_timeout`
+    #[cfg(feature = "flush-timeout")]
+    flushed: std::sync::OnceLock<tokio::sync::watch::Sender<()>>,
 }
 
 impl<E: CloseEntry, S: EntrySink<RootMetric<E>>> Deref for AppendAndCloseOnDrop<E, S> {
@@ -413,7 +517,10 @@ impl<E: CloseEntry, S: EntrySink<RootMetric<E>>> Drop for AppendAndCloseOnDropIn
     fn drop(&mut self) {
         let entry = self.entry.take().expect("only drop calls this");
         let entry = entry.close();
-        self.sink.append(RootEntry::new(entry));
+        self.sink.append(RootEntry {
+            metric: entry,
+            merged: std::mem::take(&mut self.merged),
+        });
     }
 }
 
@@ -503,6 +610,9 @@ pub fn append_and_close<
         inner: Parent::new(AppendAndCloseOnDropInner {
             entry: Some(base),
             sink,
+            merged: Vec::new(),
+            #[cfg(feature = "flush-timeout")]
+            flushed: std::sync::OnceLock::new(),
         }),
     }
 }
@@ -645,22 +755,31 @@ pub type RootMetric<E> = RootEntry<<E as CloseValue>::Closed>;
 /// [`metrics`]: crate::unit_of_work::metrics
 pub struct RootEntry<M: InflectableEntry> {
     metric: M,
+    merged: Vec<SyncBoxEntry>,
 }
 
 impl<M: InflectableEntry> RootEntry<M> {
     /// create a new [`RootEntry`]
     pub fn new(metric: M) -> Self {
-        Self { metric }
+        Self {
+            metric,
+            merged: Vec::new(),
+        }
     }
 }
 
 impl<M: InflectableEntry> Entry for RootEntry<M> {
     fn write<'a>(&'a self, w: &mut impl EntryWriter<'a>) {
         self.metric.write(w);
+        for merged in &self.merged {
+            merged.write(w);
+        }
     }
 
     fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
-        self.metric.sample_group()
+        self.metric
+            .sample_group()
+            .chain(self.merged.iter().flat_map(|merged| merged.sample_group()))
     }
 }
 
@@ -672,11 +791,53 @@ pub use metrique_metricsrs as metrics_rs;
 
 pub use metrique_core::concat;
 
+// used by `#[metrics(flatten, index)]`
+#[doc(hidden)]
+pub use metrique_core::indexed;
+
+// used by the `FIELD_NAMES` constant generated by `#[metrics]`
+#[doc(hidden)]
+pub use metrique_core::field_names;
+
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub use serde as __serde;
+
+/// Internal macro used by the `#[metrics]` macro to implement `Serialize` for entries
+/// when the `serde` feature is enabled.
+#[cfg(feature = "serde")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __plumbing_serialize_entry {
+    ([$($impl_generics:tt)*] [$($ty:tt)*] [$($where_clause:tt)*]) => {
+        impl $($impl_generics)* $crate::__serde::Serialize for $($ty)* $($where_clause)* {
+            fn serialize<__S: $crate::__serde::Serializer>(
+                &self,
+                serializer: __S,
+            ) -> ::std::result::Result<__S::Ok, __S::Error> {
+                $crate::__serde::Serialize::serialize(
+                    &$crate::serialize::SerializeEntry::new(self),
+                    serializer,
+                )
+            }
+        }
+    };
+}
+
+/// Internal macro used by the `#[metrics]` macro to implement `Serialize` for entries
+/// when the `serde` feature is enabled.
+#[cfg(not(feature = "serde"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __plumbing_serialize_entry {
+    ($($tt:tt)*) => {};
+}
+
 /// Re-exports of [metrique_writer]
 pub mod writer {
     pub use metrique_writer::GlobalEntrySink;
     pub use metrique_writer::{AnyEntrySink, BoxEntrySink, EntrySink};
-    pub use metrique_writer::{BoxEntry, EntryConfig, EntryWriter, core::Entry};
+    pub use metrique_writer::{BoxEntry, EntryConfig, EntryTimestamp, EntryWriter, core::Entry};
     pub use metrique_writer::{Convert, Unit};
     pub use metrique_writer::{EntryIoStream, IoStreamError};
     pub use metrique_writer::{MetricFlags, MetricValue, Observation, Value, ValueWriter};
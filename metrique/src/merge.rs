@@ -0,0 +1,191 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Merging closed subfields into an open entry, see [`Merged`]
+
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use std::fmt::Debug;
+
+use metrique_core::{CloseEntry, InflectableEntry, field_names};
+use metrique_writer_core::{EntrySink, entry::SyncBoxEntry};
+
+use crate::{AppendAndCloseOnDrop, RootEntry, RootMetric};
+
+/// An open entry that closed entries of type `M` can be merged into with [`Merged::new`].
+///
+/// This is implemented for [`AppendAndCloseOnDrop`], and for [`Merged`] itself so that several
+/// entries can be merged into the same parent.
+pub trait MergeTarget<M> {
+    /// Whether the names `M` writes are different from every name this target already writes,
+    /// including the names of the entries merged into it so far.
+    const DISJOINT: bool;
+
+    /// Write `entry` alongside this target when it is flushed
+    #[doc(hidden)]
+    fn __merge(&mut self, entry: M);
+}
+
+impl<E, S, M> MergeTarget<M> for AppendAndCloseOnDrop<E, S>
+where
+    E: CloseEntry<Closed: field_names::InflectableFieldNames>,
+    S: EntrySink<RootMetric<E>>,
+    M: InflectableEntry + field_names::InflectableFieldNames + Send + Sync + 'static,
+{
+    const DISJOINT: bool = field_names::is_disjoint(
+        <E::Closed as field_names::InflectableFieldNames>::FIELD_NAMES,
+        M::FIELD_NAMES,
+    );
+
+    fn __merge(&mut self, entry: M) {
+        self.inner
+            .merged
+            .push(SyncBoxEntry::new(RootEntry::new(entry)));
+    }
+}
+
+impl<G, M, N> MergeTarget<N> for Merged<G, M>
+where
+    G: MergeTarget<N>,
+    M: field_names::InflectableFieldNames,
+    N: field_names::InflectableFieldNames,
+{
+    const DISJOINT: bool = G::DISJOINT && field_names::is_disjoint(M::FIELD_NAMES, N::FIELD_NAMES);
+
+    fn __merge(&mut self, entry: N) {
+        self.target.__merge(entry)
+    }
+}
+
+/// An open entry `G` that the closed entry `M` has been merged into.
+///
+/// This is returned by the `merge_into` method that [`metrics`] generates for subfields, and
+/// derefs to `G`, so the parent entry can still be modified. When `G` is flushed, the metrics of
+/// `M` are written after those of `G`, in the name style of `M`, since `M` is not flattened into
+/// `G`.
+///
+/// The type records every entry merged so far, so merging an entry that writes a name that the
+/// parent or any other merged entry also writes fails to compile, rather than writing the name
+/// twice. This includes merging the same subfield type twice. Names are compared using
+/// `FIELD_NAMES`, so entries with names that are only known at runtime can't be merged.
+///
+/// # Example
+///
+/// ```
+/// # use metrique::unit_of_work::metrics;
+/// # use metrique::writer::sink::VecEntrySink;
+/// #[metrics]
+/// struct RequestMetrics {
+///     operation: &'static str,
+/// }
+///
+/// #[metrics(subfield)]
+/// struct DownstreamMetrics {
+///     downstream_calls: usize,
+/// }
+///
+/// let sink = VecEntrySink::new();
+/// let metrics = RequestMetrics { operation: "Get" }.append_on_drop(sink.clone());
+/// // e.g. collected in a spawned task
+/// let downstream = DownstreamMetrics { downstream_calls: 3 };
+/// let mut metrics = downstream.merge_into(metrics);
+/// metrics.operation = "Put";
+/// drop(metrics);
+///
+/// let entries = sink.drain();
+/// let entry = metrique::test_util::to_test_entry(&entries[0]);
+/// assert_eq!(entry.values["operation"], "Put");
+/// assert_eq!(entry.metrics["downstream_calls"], 3);
+/// ```
+///
+/// Merging two subfields that write the same name fails to compile:
+///
+/// ```compile_fail
+/// # use metrique::unit_of_work::metrics;
+/// # use metrique::writer::sink::VecEntrySink;
+/// #[metrics]
+/// struct RequestMetrics {
+///     operation: &'static str,
+/// }
+///
+/// #[metrics(subfield)]
+/// struct DownstreamMetrics {
+///     calls: usize,
+/// }
+///
+/// #[metrics(subfield)]
+/// struct CacheMetrics {
+///     calls: usize,
+/// }
+///
+/// let metrics = RequestMetrics { operation: "Get" }.append_on_drop(VecEntrySink::new());
+/// let metrics = DownstreamMetrics { calls: 3 }.merge_into(metrics);
+/// CacheMetrics { calls: 1 }.merge_into(metrics);
+/// ```
+///
+/// And so does merging the same subfield twice:
+///
+/// ```compile_fail
+/// # use metrique::unit_of_work::metrics;
+/// # use metrique::writer::sink::VecEntrySink;
+/// #[metrics]
+/// struct RequestMetrics {
+///     operation: &'static str,
+/// }
+///
+/// #[metrics(subfield)]
+/// struct DownstreamMetrics {
+///     downstream_calls: usize,
+/// }
+///
+/// let metrics = RequestMetrics { operation: "Get" }.append_on_drop(VecEntrySink::new());
+/// let metrics = DownstreamMetrics { downstream_calls: 3 }.merge_into(metrics);
+/// DownstreamMetrics { downstream_calls: 1 }.merge_into(metrics);
+/// ```
+///
+/// [`metrics`]: crate::unit_of_work::metrics
+pub struct Merged<G, M> {
+    target: G,
+    _merged: PhantomData<fn() -> M>,
+}
+
+impl<G: MergeTarget<M>, M> Merged<G, M> {
+    /// Merge the closed entry `entry` into `target`.
+    ///
+    /// This fails to compile if `entry` writes any name that `target` already writes.
+    pub fn new(mut target: G, entry: M) -> Self {
+        const {
+            assert!(
+                G::DISJOINT,
+                "the merged entries write some of the same metric names"
+            )
+        };
+        target.__merge(entry);
+        Self {
+            target,
+            _merged: PhantomData,
+        }
+    }
+}
+
+impl<G, M> Deref for Merged<G, M> {
+    type Target = G;
+
+    fn deref(&self) -> &Self::Target {
+        &self.target
+    }
+}
+
+impl<G, M> DerefMut for Merged<G, M> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.target
+    }
+}
+
+impl<G: Debug, M> Debug for Merged<G, M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Merged")
+            .field("target", &self.target)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,253 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Serialize closed metric entries directly with [`serde`].
+//!
+//! With the `serde` feature enabled, the [`metrics`] macro implements [`serde::Serialize`]
+//! for the generated `<Name>Entry` type. This is useful for custom pipelines that want to
+//! turn a closed entry into, for example, a `serde_json::Value` without going through a
+//! [`Format`].
+//!
+//! The entry is serialized as a map from metric name to value, using the same (inflected)
+//! names that a format would see:
+//!
+//! - Flattened subfields are inlined into the parent map.
+//! - String values are serialized as strings.
+//! - A metric with a single observation is serialized as a number. A metric with several
+//!   observations (for example, a histogram) is serialized as a sequence. Repeated
+//!   observations are serialized as `{"total": .., "count": ..}`.
+//! - Metrics with no observations (and values that write nothing, like `None`) are skipped.
+//! - Units are dropped by default. Use [`SerializeEntry::with_units`] to serialize every
+//!   metric as `{"value": .., "unit": ..}` instead.
+//! - Per-value dimensions and format-specific configuration (such as EMF dimension sets)
+//!   are not serialized.
+//! - The entry timestamp, if any, is serialized as milliseconds since the Unix epoch
+//!   under the `timestamp` key.
+//!
+//! A value that reports a [`ValidationError`] fails serialization with a custom error.
+//!
+//! ```
+//! use metrique::serialize::SerializeEntry;
+//! use metrique::unit::Millisecond;
+//! use metrique::unit_of_work::metrics;
+//! use metrique::CloseValue;
+//! use std::time::Duration;
+//!
+//! #[metrics(rename_all = "PascalCase")]
+//! struct RequestMetrics {
+//!     operation: &'static str,
+//!     #[metrics(unit = Millisecond)]
+//!     latency: Duration,
+//! }
+//!
+//! let entry = RequestMetrics {
+//!     operation: "Foo",
+//!     latency: Duration::from_millis(5),
+//! }
+//! .close();
+//!
+//! assert_eq!(
+//!     serde_json::to_value(&entry).unwrap(),
+//!     serde_json::json!({"Operation": "Foo", "Latency": 5.0}),
+//! );
+//! assert_eq!(
+//!     serde_json::to_value(SerializeEntry::new(&entry).with_units(true)).unwrap(),
+//!     serde_json::json!({
+//!         "Operation": "Foo",
+//!         "Latency": {"value": 5.0, "unit": "Milliseconds"},
+//!     }),
+//! );
+//! ```
+//!
+//! [`metrics`]: crate::unit_of_work::metrics
+//! [`Format`]: crate::writer::format::Format
+
+use std::{borrow::Cow, time::SystemTime};
+
+use metrique_core::InflectableEntry;
+use metrique_writer_core::{
+    EntryConfig, EntryWriter, MetricFlags, Observation, Unit, ValidationError, Value, ValueWriter,
+};
+use serde::{
+    Serialize, Serializer,
+    ser::{Error as _, SerializeMap, SerializeSeq},
+};
+
+/// Serializes an [`InflectableEntry`] as a map from metric name to value.
+///
+/// See the [module documentation](self) for how values are represented.
+#[derive(Debug, Clone, Copy)]
+pub struct SerializeEntry<'a, E> {
+    entry: &'a E,
+    units: bool,
+}
+
+impl<'a, E: InflectableEntry> SerializeEntry<'a, E> {
+    /// Serialize `entry`, dropping units.
+    pub fn new(entry: &'a E) -> Self {
+        Self {
+            entry,
+            units: false,
+        }
+    }
+
+    /// If `units` is true, serialize every metric as `{"value": .., "unit": ..}`
+    /// rather than as a bare number.
+    pub fn with_units(mut self, units: bool) -> Self {
+        self.units = units;
+        self
+    }
+}
+
+impl<E: InflectableEntry> Serialize for SerializeEntry<'_, E> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut writer = MapWriter {
+            map: serializer.serialize_map(None)?,
+            units: self.units,
+            error: None,
+        };
+        self.entry.write(&mut writer);
+        match writer.error {
+            Some(error) => Err(error),
+            None => writer.map.end(),
+        }
+    }
+}
+
+struct MapWriter<M: SerializeMap> {
+    map: M,
+    units: bool,
+    error: Option<M::Error>,
+}
+
+impl<M: SerializeMap> MapWriter<M> {
+    fn entry(&mut self, name: &str, value: &impl Serialize) {
+        if self.error.is_none() {
+            if let Err(error) = self.map.serialize_entry(name, value) {
+                self.error = Some(error);
+            }
+        }
+    }
+}
+
+impl<'a, M: SerializeMap> EntryWriter<'a> for MapWriter<M> {
+    fn timestamp(&mut self, timestamp: SystemTime) {
+        let millis = timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        self.entry("timestamp", &millis);
+    }
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, value: &(impl Value + ?Sized)) {
+        if self.error.is_some() {
+            return;
+        }
+        let name = name.into();
+        let mut captured = None;
+        value.write(CaptureWriter(&mut captured));
+        match captured {
+            None => {}
+            Some(Captured::String(value)) => self.entry(&name, &value),
+            Some(Captured::Metric(observations, _)) if observations.is_empty() => {}
+            Some(Captured::Metric(observations, unit)) => {
+                let observations = SerializeObservations(&observations);
+                if self.units {
+                    self.entry(
+                        &name,
+                        &WithUnit {
+                            value: observations,
+                            unit,
+                        },
+                    )
+                } else {
+                    self.entry(&name, &observations)
+                }
+            }
+            Some(Captured::Error(error)) => {
+                self.error = Some(M::Error::custom(format!(
+                    "invalid value for metric `{name}`: {error}"
+                )));
+            }
+        }
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {}
+}
+
+enum Captured {
+    String(String),
+    Metric(Vec<Observation>, Unit),
+    Error(ValidationError),
+}
+
+struct CaptureWriter<'a>(&'a mut Option<Captured>);
+
+impl ValueWriter for CaptureWriter<'_> {
+    fn string(self, value: &str) {
+        *self.0 = Some(Captured::String(value.to_owned()));
+    }
+
+    fn metric<'a>(
+        self,
+        distribution: impl IntoIterator<Item = Observation>,
+        unit: Unit,
+        _dimensions: impl IntoIterator<Item = (&'a str, &'a str)>,
+        _flags: MetricFlags<'_>,
+    ) {
+        *self.0 = Some(Captured::Metric(distribution.into_iter().collect(), unit));
+    }
+
+    fn error(self, error: ValidationError) {
+        *self.0 = Some(Captured::Error(error));
+    }
+}
+
+struct WithUnit<V> {
+    value: V,
+    unit: Unit,
+}
+
+impl<V: Serialize> Serialize for WithUnit<V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("value", &self.value)?;
+        map.serialize_entry("unit", &self.unit)?;
+        map.end()
+    }
+}
+
+struct SerializeObservations<'a>(&'a [Observation]);
+
+impl Serialize for SerializeObservations<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            [Observation::Unsigned(value)] => serializer.serialize_u64(*value),
+            [Observation::Floating(value)] => serializer.serialize_f64(*value),
+            observations => {
+                let mut seq = serializer.serialize_seq(Some(observations.len()))?;
+                for observation in observations {
+                    seq.serialize_element(&SerializeObservation(observation))?;
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
+struct SerializeObservation<'a>(&'a Observation);
+
+impl Serialize for SerializeObservation<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self.0 {
+            Observation::Unsigned(value) => serializer.serialize_u64(value),
+            Observation::Floating(value) => serializer.serialize_f64(value),
+            Observation::Repeated { total, occurrences } => {
+                let mut map = serializer.serialize_map(Some(2))?;
+                map.serialize_entry("total", &total)?;
+                map.serialize_entry("count", &occurrences)?;
+                map.end()
+            }
+            _ => serializer.serialize_none(),
+        }
+    }
+}
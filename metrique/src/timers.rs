@@ -2,16 +2,18 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::{
+    borrow::Cow,
     marker::PhantomData,
-    ops::AddAssign,
+    ops::{AddAssign, Deref, DerefMut},
     sync::{Arc, Mutex},
     time::{Duration, UNIX_EPOCH},
 };
 
-use metrique_core::CloseValue;
+use metrique_core::{CloseValue, Identity, InflectableEntry, NameStyle, Reset};
 use metrique_timesource::{Instant, SystemTime, TimeSource, time_source};
 use metrique_writer_core::{
-    Value,
+    Entry, EntrySink, EntryWriter, Value,
+    entry::SampleGroupElement,
     unit::{Millisecond, Second},
 };
 use metrique_writer_core::{unit::Microsecond, value::ValueFormatter};
@@ -84,6 +86,13 @@ impl Default for Timestamp {
     }
 }
 
+/// Sets the timestamp to the current time, loaded from [`metrique_timesource::time_source`]
+impl Reset for Timestamp {
+    fn reset(&mut self) {
+        *self = Self::now();
+    }
+}
+
 /// A `Timestamp` which records the time when the record is closed
 ///
 /// When combined with [`Timestamp`], this is a useful tool for to record when
@@ -121,6 +130,11 @@ impl CloseValue for TimestampOnClose {
     }
 }
 
+/// The time is only recorded on close, so there is nothing to reset
+impl Reset for TimestampOnClose {
+    fn reset(&mut self) {}
+}
+
 /// Formats a timestamp in `EpochSeconds` format
 pub type EpochSeconds = TimestampFormat<Second>;
 
@@ -322,6 +336,14 @@ impl CloseValue for Timer {
         <&Self>::close(&self)
     }
 }
+
+/// Restarts the timer, using the time source from [`metrique_timesource::time_source`]
+impl Reset for Timer {
+    fn reset(&mut self) {
+        *self = Self::start_now();
+    }
+}
+
 /// A guard that stops a timer when dropped.
 ///
 /// This guard is returned by [`Stopwatch::start()`] and will add the elapsed time
@@ -851,14 +873,550 @@ impl CloseValue for Stopwatch {
     }
 }
 
+/// Stops the stopwatch and discards the recorded time
+impl Reset for Stopwatch {
+    fn reset(&mut self) {
+        *self = Self::new_from_timesource(self.time_source.clone());
+    }
+}
+
+/// A timer for operations with several phases, that records the time spent in each phase
+/// as well as the total time
+///
+/// The timer starts immediately, with no active phase. [`PhasedTimer::switch_phase`] ends the
+/// active phase (if any) and starts a new one. Time spent in a phase that is entered several
+/// times is added up. Time spent outside of any phase still counts towards the total.
+///
+/// When closed, a `PhasedTimer` stops (like [`Timer`]) and must be used with
+/// `#[metrics(flatten)]`. It writes one duration per phase, and the total under the name passed
+/// to [`PhasedTimer::start_now`]. These names are inflected and prefixed like field names.
+///
+/// # Example
+/// ```
+/// use metrique::timers::PhasedTimer;
+/// use metrique::unit_of_work::metrics;
+///
+/// #[metrics(rename_all = "PascalCase")]
+/// struct RequestMetrics {
+///     #[metrics(flatten)]
+///     latency: PhasedTimer,
+/// }
+///
+/// let mut metrics = RequestMetrics {
+///     latency: PhasedTimer::start_now("total_time"),
+/// };
+/// metrics.latency.switch_phase("auth_time");
+/// // authenticate...
+/// metrics.latency.switch_phase("query_time");
+/// // query...
+/// metrics.latency.end_phase();
+/// // the entry contains AuthTime, QueryTime and TotalTime
+/// ```
+#[derive(Debug)]
+pub struct PhasedTimer {
+    time_source: TimeSource,
+    total_name: Cow<'static, str>,
+    start: Instant,
+    total: Option<Duration>,
+    active: Option<(usize, Instant)>,
+    phases: Vec<(Cow<'static, str>, Duration)>,
+}
+
+impl PhasedTimer {
+    /// Creates a new phased timer that starts immediately using the default time source.
+    ///
+    /// The total time is written under `total_name`.
+    pub fn start_now(total_name: impl Into<Cow<'static, str>>) -> Self {
+        Self::start_now_with_timesource(total_name, time_source())
+    }
+
+    /// Creates a new phased timer that starts immediately using the specified time source.
+    ///
+    /// This is useful for testing with a mock time source.
+    pub fn start_now_with_timesource(
+        total_name: impl Into<Cow<'static, str>>,
+        time_source: TimeSource,
+    ) -> Self {
+        Self {
+            start: time_source.instant(),
+            time_source,
+            total_name: total_name.into(),
+            total: None,
+            active: None,
+            phases: Vec::new(),
+        }
+    }
+
+    /// Ends the active phase (if any) and starts the phase `name`.
+    ///
+    /// If `name` was already active before, the time spent in it is added to its existing
+    /// duration. This does nothing if the timer is stopped.
+    pub fn switch_phase(&mut self, name: impl Into<Cow<'static, str>>) {
+        if self.total.is_some() {
+            return;
+        }
+        let now = self.time_source.instant();
+        self.end_phase_at(&now);
+
+        let name = name.into();
+        let index = match self.phases.iter().position(|(phase, _)| *phase == name) {
+            Some(index) => index,
+            None => {
+                self.phases.push((name, Duration::ZERO));
+                self.phases.len() - 1
+            }
+        };
+        self.active = Some((index, now));
+    }
+
+    /// Ends the active phase (if any), without starting a new one.
+    ///
+    /// Time spent until the next [`PhasedTimer::switch_phase`] only counts towards the total.
+    pub fn end_phase(&mut self) {
+        let now = self.time_source.instant();
+        self.end_phase_at(&now);
+    }
+
+    /// Stops the timer, ending the active phase, and returns the total elapsed duration.
+    ///
+    /// Calling `stop` on a stopped timer is idempotent, and returns the
+    /// timer's stopped duration.
+    pub fn stop(&mut self) -> Duration {
+        if let Some(total) = self.total {
+            return total;
+        }
+        self.end_phase();
+        let total = self.start.elapsed();
+        self.total = Some(total);
+        total
+    }
+
+    /// Returns the name of the active phase, if any.
+    pub fn active_phase(&self) -> Option<&str> {
+        self.active
+            .as_ref()
+            .map(|(index, _)| &*self.phases[*index].0)
+    }
+
+    fn end_phase_at(&mut self, now: &Instant) {
+        if let Some((index, started)) = self.active.take() {
+            self.phases[index].1 += now.as_std().saturating_duration_since(started.as_std());
+        }
+    }
+}
+
+impl CloseValue for PhasedTimer {
+    type Closed = PhasedDurations;
+
+    fn close(mut self) -> Self::Closed {
+        let total = self.stop();
+        PhasedDurations {
+            total_name: self.total_name,
+            total,
+            phases: self.phases,
+        }
+    }
+}
+
+/// The closed value of a [`PhasedTimer`]
+///
+/// Writes the duration of each phase, followed by the total duration.
+#[derive(Debug, Clone)]
+pub struct PhasedDurations {
+    total_name: Cow<'static, str>,
+    total: Duration,
+    phases: Vec<(Cow<'static, str>, Duration)>,
+}
+
+impl PhasedDurations {
+    /// Returns the total duration
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+
+    /// Returns the duration of the phase `name`, if it was ever active
+    pub fn phase(&self, name: &str) -> Option<Duration> {
+        self.phases
+            .iter()
+            .find(|(phase, _)| phase == name)
+            .map(|(_, duration)| *duration)
+    }
+}
+
+impl<NS: NameStyle> InflectableEntry<NS> for PhasedDurations {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        for (phase, duration) in &self.phases {
+            writer.value(NS::inflect_name(phase), duration);
+        }
+        writer.value(NS::inflect_name(&self.total_name), &self.total);
+    }
+}
+
+/// Wraps a metrics subtree, and records how long closing it takes
+///
+/// Closing a value can be expensive, for example when a field's `close` reads contended state
+/// behind a lock. `TimedClose` measures the inner `close` using a [`TimeSource`], and writes the
+/// measured duration under the name passed to [`TimedClose::new`], in addition to the fields
+/// of the inner entry.
+///
+/// The inner value is accessible through [`Deref`] and [`DerefMut`]. `TimedClose` must be used
+/// with `#[metrics(flatten)]`.
+///
+/// # Example
+/// ```
+/// use metrique::timers::TimedClose;
+/// use metrique::unit_of_work::metrics;
+///
+/// #[metrics(subfield)]
+/// struct CacheMetrics {
+///     hits: usize,
+/// }
+///
+/// #[metrics(rename_all = "PascalCase")]
+/// struct RequestMetrics {
+///     #[metrics(flatten)]
+///     cache: TimedClose<CacheMetrics>,
+/// }
+///
+/// let mut metrics = RequestMetrics {
+///     cache: TimedClose::new(CacheMetrics { hits: 0 }, "cache_close_time"),
+/// };
+/// metrics.cache.hits += 1;
+/// // the entry contains Hits and CacheCloseTime
+/// ```
+#[derive(Debug)]
+pub struct TimedClose<T> {
+    inner: T,
+    name: Cow<'static, str>,
+    time_source: TimeSource,
+}
+
+impl<T> TimedClose<T> {
+    /// Wraps `inner`, writing the time taken to close it under `name`, using the default time
+    /// source.
+    pub fn new(inner: T, name: impl Into<Cow<'static, str>>) -> Self {
+        Self::new_with_timesource(inner, name, time_source())
+    }
+
+    /// Wraps `inner`, writing the time taken to close it under `name`, using the specified time
+    /// source.
+    ///
+    /// This is useful for testing with a mock time source.
+    pub fn new_with_timesource(
+        inner: T,
+        name: impl Into<Cow<'static, str>>,
+        time_source: TimeSource,
+    ) -> Self {
+        Self {
+            inner,
+            name: name.into(),
+            time_source,
+        }
+    }
+
+    /// Returns the wrapped value
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl<T> Deref for TimedClose<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for TimedClose<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T: CloseValue> CloseValue for TimedClose<T> {
+    type Closed = TimedCloseEntry<T::Closed>;
+
+    fn close(self) -> Self::Closed {
+        let start = self.time_source.instant();
+        let inner = self.inner.close();
+        TimedCloseEntry {
+            inner,
+            name: self.name,
+            duration: start.elapsed(),
+        }
+    }
+}
+
+/// The closed value of a [`TimedClose`]
+///
+/// Writes the fields of the inner entry, followed by the close duration.
+#[derive(Debug, Clone)]
+pub struct TimedCloseEntry<E> {
+    inner: E,
+    name: Cow<'static, str>,
+    duration: Duration,
+}
+
+impl<E> TimedCloseEntry<E> {
+    /// Returns the time it took to close the inner value
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns the closed inner value
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+}
+
+impl<NS: NameStyle, E: InflectableEntry<NS>> InflectableEntry<NS> for TimedCloseEntry<E> {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        self.inner.write(writer);
+        writer.value(NS::inflect_name(&self.name), &self.duration);
+    }
+
+    fn sample_group(&self) -> impl Iterator<Item = SampleGroupElement> {
+        self.inner.sample_group()
+    }
+}
+
+/// Records the time a value was closed, along with how long it lived
+///
+/// `LifetimeTimer` is like [`TimestampOnClose`], but also measures the time elapsed between
+/// its creation and its close. When closed, it writes the system time at close under
+/// `timestamp_name` and the elapsed duration under `duration_name`. Both are read from the
+/// same [`TimeSource`], so tests that override the time source see consistent values.
+///
+/// `LifetimeTimer` must be used with `#[metrics(flatten)]`.
+///
+/// # Example
+/// ```
+/// use metrique::timers::LifetimeTimer;
+/// use metrique::unit_of_work::metrics;
+///
+/// #[metrics(rename_all = "PascalCase")]
+/// struct ConnectionMetrics {
+///     #[metrics(flatten)]
+///     lifetime: LifetimeTimer,
+/// }
+///
+/// let metrics = ConnectionMetrics {
+///     lifetime: LifetimeTimer::start_now("closed_at", "connection_lifetime"),
+/// };
+/// // the entry contains ClosedAt and ConnectionLifetime
+/// ```
+#[derive(Debug)]
+pub struct LifetimeTimer {
+    time_source: TimeSource,
+    start: Instant,
+    timestamp_name: Cow<'static, str>,
+    duration_name: Cow<'static, str>,
+}
+
+impl LifetimeTimer {
+    /// Starts a lifetime timer using the default time source, writing the close time under
+    /// `timestamp_name` and the lifetime under `duration_name`.
+    pub fn start_now(
+        timestamp_name: impl Into<Cow<'static, str>>,
+        duration_name: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self::start_now_with_timesource(timestamp_name, duration_name, time_source())
+    }
+
+    /// Starts a lifetime timer using the specified time source, writing the close time under
+    /// `timestamp_name` and the lifetime under `duration_name`.
+    ///
+    /// This is useful for testing with a mock time source.
+    pub fn start_now_with_timesource(
+        timestamp_name: impl Into<Cow<'static, str>>,
+        duration_name: impl Into<Cow<'static, str>>,
+        time_source: TimeSource,
+    ) -> Self {
+        Self {
+            start: time_source.instant(),
+            time_source,
+            timestamp_name: timestamp_name.into(),
+            duration_name: duration_name.into(),
+        }
+    }
+
+    /// Returns the time elapsed since the timer was started
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl CloseValue for LifetimeTimer {
+    type Closed = LifetimeValue;
+
+    fn close(self) -> Self::Closed {
+        LifetimeValue {
+            closed_at: TimestampValue::new(&self.time_source.system_time()),
+            lifetime: self.start.elapsed(),
+            timestamp_name: self.timestamp_name,
+            duration_name: self.duration_name,
+        }
+    }
+}
+
+/// Restarts the timer from now
+impl Reset for LifetimeTimer {
+    fn reset(&mut self) {
+        self.start = self.time_source.instant();
+    }
+}
+
+/// The closed value of a [`LifetimeTimer`]
+///
+/// Writes the time of the close, followed by the time elapsed since the timer was started.
+#[derive(Debug, Clone)]
+pub struct LifetimeValue {
+    closed_at: TimestampValue,
+    lifetime: Duration,
+    timestamp_name: Cow<'static, str>,
+    duration_name: Cow<'static, str>,
+}
+
+impl LifetimeValue {
+    /// Returns the time at which the timer was closed
+    pub fn closed_at(&self) -> TimestampValue {
+        self.closed_at
+    }
+
+    /// Returns the time elapsed between starting and closing the timer
+    pub fn lifetime(&self) -> Duration {
+        self.lifetime
+    }
+}
+
+impl<NS: NameStyle> InflectableEntry<NS> for LifetimeValue {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        writer.value(NS::inflect_name(&self.timestamp_name), &self.closed_at);
+        writer.value(NS::inflect_name(&self.duration_name), &self.lifetime);
+    }
+}
+
+/// A guard that appends an entry with the time spent in a scope to a sink when dropped
+///
+/// This is meant for quick instrumentation, where a metrics struct with a [`Timer`] field would
+/// be more than is needed. The start time is taken from a [`TimeSource`] when the guard is
+/// created. When it is dropped, a [`ScopeDuration`] entry is appended to the sink, containing the
+/// elapsed time under the name passed to [`ScopeTiming::start`] and the start time as the entry
+/// timestamp.
+///
+/// # Example
+/// ```
+/// use metrique::timers::ScopeTiming;
+/// use metrique::test_util::test_entry_sink;
+///
+/// let test_sink = test_entry_sink();
+/// {
+///     let _timing = ScopeTiming::start(test_sink.sink.clone(), "LoadConfigTime");
+///     // load the configuration
+/// }
+/// assert!(test_sink.inspector.get(0).metrics.contains_key("LoadConfigTime"));
+/// ```
+#[must_use = "the duration is appended when the guard is dropped"]
+pub struct ScopeTiming<S: EntrySink<ScopeDuration>> {
+    sink: S,
+    name: Cow<'static, str>,
+    timestamp: SystemTime,
+    start: Instant,
+}
+
+impl<S: EntrySink<ScopeDuration>> ScopeTiming<S> {
+    /// Starts timing a scope using the default time source, appending the duration to `sink`
+    /// under `name` when the returned guard is dropped.
+    pub fn start(sink: S, name: impl Into<Cow<'static, str>>) -> Self {
+        Self::start_with_timesource(sink, name, time_source())
+    }
+
+    /// Starts timing a scope using the specified time source, appending the duration to `sink`
+    /// under `name` when the returned guard is dropped.
+    ///
+    /// This is useful for testing with a mock time source.
+    pub fn start_with_timesource(
+        sink: S,
+        name: impl Into<Cow<'static, str>>,
+        time_source: TimeSource,
+    ) -> Self {
+        Self {
+            sink,
+            name: name.into(),
+            timestamp: time_source.system_time(),
+            start: time_source.instant(),
+        }
+    }
+
+    /// Returns the time elapsed since the guard was created
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl<S: EntrySink<ScopeDuration> + std::fmt::Debug> std::fmt::Debug for ScopeTiming<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ScopeTiming")
+            .field("sink", &self.sink)
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: EntrySink<ScopeDuration>> Drop for ScopeTiming<S> {
+    fn drop(&mut self) {
+        self.sink.append(ScopeDuration {
+            name: std::mem::take(&mut self.name),
+            timestamp: self.timestamp.as_std(),
+            duration: self.start.elapsed(),
+        });
+    }
+}
+
+/// The entry appended by a [`ScopeTiming`]
+///
+/// Writes the start of the scope as the entry timestamp, and the time spent in the scope. When
+/// appended on its own, the name is written as passed to [`ScopeTiming::start`], like the fields
+/// of a root entry without `rename_all`. When flattened into another entry, the name is inflected
+/// and prefixed like a field name.
+#[derive(Debug, Clone)]
+pub struct ScopeDuration {
+    name: Cow<'static, str>,
+    timestamp: std::time::SystemTime,
+    duration: Duration,
+}
+
+impl ScopeDuration {
+    /// Returns the time spent in the scope
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+impl<NS: NameStyle> InflectableEntry<NS> for ScopeDuration {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        writer.timestamp(self.timestamp);
+        writer.value(NS::inflect_name(&self.name), &self.duration);
+    }
+}
+
+impl Entry for ScopeDuration {
+    fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+        InflectableEntry::<Identity>::write(self, writer)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::time::{Duration, UNIX_EPOCH};
 
-    use metrique_core::CloseValue;
-    use metrique_timesource::{TimeSource, set_time_source};
+    use metrique_core::{CloseValue, InflectableEntry, PascalCase, concat::ConstStr};
+    use metrique_timesource::{TimeSource, fakes::ManuallyAdvancedTimeSource, set_time_source};
+    use metrique_writer::sink::VecEntrySink;
+    use metrique_writer_core::{Entry, EntryWriter};
 
-    use crate::timers::{Stopwatch, Timer};
+    use crate::timers::{LifetimeTimer, PhasedTimer, ScopeTiming, Stopwatch, TimedClose, Timer};
 
     #[tokio::test(start_paused = true)]
     async fn timer_stop_is_idempotent() {
@@ -1006,4 +1564,188 @@ mod test {
         guard.discard();
         assert_eq!(stopwatch.duration, Some(Duration::from_secs(1)));
     }
+
+    #[test]
+    fn phased_timer_accounts_phases_and_total() {
+        let clock = ManuallyAdvancedTimeSource::at_time(UNIX_EPOCH);
+        let mut timer =
+            PhasedTimer::start_now_with_timesource("Total", TimeSource::custom(clock.clone()));
+
+        // time before the first phase only counts towards the total
+        clock.update_instant(Duration::from_secs(1));
+        timer.switch_phase("Auth");
+        assert_eq!(timer.active_phase(), Some("Auth"));
+        clock.update_instant(Duration::from_secs(2));
+        timer.switch_phase("Query");
+        clock.update_instant(Duration::from_secs(4));
+        // re-entering a phase adds to its duration
+        timer.switch_phase("Auth");
+        clock.update_instant(Duration::from_secs(8));
+        timer.end_phase();
+        assert_eq!(timer.active_phase(), None);
+        clock.update_instant(Duration::from_secs(16));
+
+        let closed = timer.close();
+        assert_eq!(closed.phase("Auth"), Some(Duration::from_secs(10)));
+        assert_eq!(closed.phase("Query"), Some(Duration::from_secs(4)));
+        assert_eq!(closed.phase("Other"), None);
+        assert_eq!(closed.total(), Duration::from_secs(31));
+    }
+
+    #[test]
+    fn phased_timer_stop_is_idempotent() {
+        let clock = ManuallyAdvancedTimeSource::at_time(UNIX_EPOCH);
+        let mut timer =
+            PhasedTimer::start_now_with_timesource("Total", TimeSource::custom(clock.clone()));
+
+        timer.switch_phase("Work");
+        clock.update_instant(Duration::from_secs(1));
+        assert_eq!(timer.stop(), Duration::from_secs(1));
+
+        // neither time nor phase switches after stopping are recorded
+        clock.update_instant(Duration::from_secs(1));
+        timer.switch_phase("Late");
+        assert_eq!(timer.stop(), Duration::from_secs(1));
+
+        let closed = timer.close();
+        assert_eq!(closed.phase("Work"), Some(Duration::from_secs(1)));
+        assert_eq!(closed.phase("Late"), None);
+        assert_eq!(closed.total(), Duration::from_secs(1));
+    }
+
+    struct SlowClose {
+        clock: ManuallyAdvancedTimeSource,
+        cost: Duration,
+    }
+
+    impl CloseValue for SlowClose {
+        type Closed = u32;
+
+        fn close(self) -> u32 {
+            self.clock.update_instant(self.cost);
+            7
+        }
+    }
+
+    #[test]
+    fn timed_close_measures_inner_close() {
+        let clock = ManuallyAdvancedTimeSource::at_time(UNIX_EPOCH);
+        let mut timed = TimedClose::new_with_timesource(
+            SlowClose {
+                clock: clock.clone(),
+                cost: Duration::ZERO,
+            },
+            "CloseTime",
+            TimeSource::custom(clock.clone()),
+        );
+        timed.cost = Duration::from_millis(3);
+
+        // time before closing is not measured
+        clock.update_instant(Duration::from_secs(1));
+        let closed = timed.close();
+        assert_eq!(closed.duration(), Duration::from_millis(3));
+        assert_eq!(closed.into_inner(), 7);
+    }
+
+    #[test]
+    fn scope_timing_appends_duration_on_drop() {
+        let clock = ManuallyAdvancedTimeSource::at_time(UNIX_EPOCH + Duration::from_secs(10));
+        let test_sink = metrique_writer::test_util::test_entry_sink();
+
+        let timing = ScopeTiming::start_with_timesource(
+            test_sink.sink.clone(),
+            "ScopeTime",
+            TimeSource::custom(clock.clone()),
+        );
+        clock.update_instant(Duration::from_millis(250));
+        assert_eq!(timing.elapsed(), Duration::from_millis(250));
+        // nothing is appended until the guard is dropped
+        assert!(test_sink.inspector.entries().is_empty());
+
+        clock.update_instant(Duration::from_millis(50));
+        drop(timing);
+
+        let entries = test_sink.inspector.entries();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].metrics["ScopeTime"], 300);
+        assert_eq!(
+            entries[0].timestamp,
+            Some(UNIX_EPOCH + Duration::from_secs(10))
+        );
+    }
+
+    struct RetryPrefix;
+    impl ConstStr for RetryPrefix {
+        const VAL: &'static str = "Retry";
+    }
+
+    /// Writes an entry as if it was flattened with `prefix = "retry_"` into a PascalCase entry
+    struct FlattenedRetry<E>(E);
+    impl<E: InflectableEntry<PascalCase<RetryPrefix>>> Entry for FlattenedRetry<E> {
+        fn write<'a>(&'a self, writer: &mut impl EntryWriter<'a>) {
+            self.0.write(writer)
+        }
+    }
+
+    #[test]
+    fn scope_duration_name_is_inflected_when_flattened() {
+        let clock = ManuallyAdvancedTimeSource::at_time(UNIX_EPOCH);
+        let sink = VecEntrySink::default();
+        let timing = ScopeTiming::start_with_timesource(
+            sink.clone(),
+            "scope_time",
+            TimeSource::custom(clock.clone()),
+        );
+        clock.update_instant(Duration::from_millis(5));
+        drop(timing);
+        let duration = sink.drain().pop().unwrap();
+
+        // appended on its own, the name is written as passed
+        let entry = metrique_writer::test_util::to_test_entry(&duration);
+        assert_eq!(entry.metrics["scope_time"], 5);
+
+        let entry = metrique_writer::test_util::to_test_entry(FlattenedRetry(duration));
+        assert_eq!(entry.metrics["RetryScopeTime"], 5);
+    }
+
+    #[test]
+    fn lifetime_timer_records_close_time_and_lifetime() {
+        let clock = ManuallyAdvancedTimeSource::at_time(UNIX_EPOCH + Duration::from_secs(10));
+        // the default time source respects thread-local overrides
+        let _ts = set_time_source(TimeSource::custom(clock.clone()));
+        let timer = LifetimeTimer::start_now("ClosedAt", "Lifetime");
+
+        clock.update_instant(Duration::from_millis(250));
+        clock.update_time(UNIX_EPOCH + Duration::from_secs(20));
+        assert_eq!(timer.elapsed(), Duration::from_millis(250));
+
+        let closed = timer.close();
+        assert_eq!(closed.lifetime(), Duration::from_millis(250));
+        assert_eq!(
+            closed.closed_at().duration_since_epoch(),
+            Duration::from_secs(20)
+        );
+
+        let entry = metrique_writer::test_util::to_test_entry(crate::RootEntry::new(closed));
+        assert_eq!(entry.values["ClosedAt"], "20000.0");
+        assert_eq!(entry.metrics["Lifetime"], 250);
+    }
+
+    #[crate::unit_of_work::metrics(rename_all = "snake_case")]
+    struct ConnectionMetrics {
+        #[metrics(flatten, prefix = "conn_")]
+        lifetime: LifetimeTimer,
+    }
+
+    #[test]
+    fn lifetime_timer_names_are_inflected() {
+        let metrics = ConnectionMetrics {
+            lifetime: LifetimeTimer::start_now("ClosedAt", "Lifetime"),
+        };
+
+        let entry =
+            metrique_writer::test_util::to_test_entry(crate::RootEntry::new(metrics.close()));
+        assert!(entry.values.contains_key("conn_closed_at"));
+        assert!(entry.metrics.contains_key("conn_lifetime"));
+    }
 }
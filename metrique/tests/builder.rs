@@ -0,0 +1,59 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::{Duration, SystemTime};
+
+use metrique::unit::Millisecond;
+use metrique::writer::test_util;
+use metrique::{CloseValue, RootEntry, unit_of_work::metrics};
+
+#[metrics(subfield)]
+#[derive(Default)]
+struct CacheMetrics {
+    hits: usize,
+    misses: usize,
+}
+
+#[metrics(rename_all = "PascalCase")]
+struct RequestMetrics {
+    #[metrics(timestamp)]
+    timestamp: SystemTime,
+    operation: &'static str,
+    #[metrics(unit = Millisecond)]
+    latency: Duration,
+    #[metrics(default)]
+    retries: usize,
+    #[metrics(default, flatten, prefix = "cache_")]
+    cache: CacheMetrics,
+    #[metrics(default, ignore)]
+    notes: Vec<String>,
+}
+
+#[test]
+fn builder_defaults_unset_fields() {
+    let metrics =
+        RequestMetrics::builder(SystemTime::UNIX_EPOCH, "Get", Duration::from_millis(5)).build();
+    let entry = test_util::to_test_entry(RootEntry::new(metrics.close()));
+
+    assert_eq!(entry.timestamp, Some(SystemTime::UNIX_EPOCH));
+    assert_eq!(entry.values["Operation"], "Get");
+    assert_eq!(entry.metrics["Latency"], 5);
+    assert_eq!(entry.metrics["Retries"], 0);
+    assert_eq!(entry.metrics["CacheHits"], 0);
+    assert_eq!(entry.metrics["CacheMisses"], 0);
+}
+
+#[test]
+fn builder_sets_default_fields() {
+    let metrics = RequestMetrics::builder(SystemTime::UNIX_EPOCH, "Put", Duration::ZERO)
+        .retries(2)
+        .cache(CacheMetrics { hits: 3, misses: 1 })
+        .notes(vec!["slow".to_string()])
+        .build();
+    assert_eq!(metrics.notes, ["slow"]);
+    let entry = test_util::to_test_entry(RootEntry::new(metrics.close()));
+
+    assert_eq!(entry.metrics["Retries"], 2);
+    assert_eq!(entry.metrics["CacheHits"], 3);
+    assert_eq!(entry.metrics["CacheMisses"], 1);
+}
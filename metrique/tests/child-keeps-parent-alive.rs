@@ -104,3 +104,69 @@ async fn force_flush() {
     assert!(entry.metrics.get("Child").is_none());
     assert_eq!(entry.metrics["Duration"], 5000);
 }
+
+#[tokio::test(start_paused = true)]
+async fn flush_guard_with_timeout_forces_flush() {
+    let q = VecEntrySink::new();
+    let mut metrics = ParentMetrics::default().append_on_drop(q.clone());
+
+    let flush_guard = metrics
+        .flush_guard_with_timeout(Duration::from_secs(5), &tokio::runtime::Handle::current());
+    let mut child = metrics.child.open(OnParentDrop::Wait(flush_guard)).unwrap();
+    child.a = 5000;
+    // this task won't complete before the timeout
+    task::spawn(async move {
+        sleep(Duration::from_secs(100000)).await;
+        child.b = 10;
+    });
+
+    metrics.duration = Some(Duration::from_secs(1));
+    drop(metrics);
+
+    // the child is still holding the flush guard
+    sleep(Duration::from_secs(4)).await;
+    assert_eq!(q.drain().len(), 0);
+
+    // after the timeout, the entry is flushed without the child's data
+    sleep(Duration::from_secs(2)).await;
+    let result = q.drain();
+    assert_eq!(result.len(), 1);
+    let entry = test_util::to_test_entry(&result[0]);
+    assert!(entry.metrics.get("A").is_none());
+    assert_eq!(entry.metrics["Duration"], 1000);
+}
+
+#[tokio::test(start_paused = true)]
+async fn flush_guard_with_timeout_flushes_early_when_released() {
+    let q = VecEntrySink::new();
+    let mut metrics = ParentMetrics::default().append_on_drop(q.clone());
+
+    let flush_guard = metrics
+        .flush_guard_with_timeout(Duration::from_secs(5), &tokio::runtime::Handle::current());
+    let mut child = metrics.child.open(OnParentDrop::Wait(flush_guard)).unwrap();
+    drop(metrics);
+
+    task::spawn(async move {
+        sleep(Duration::from_secs(1)).await;
+        child.a = 7;
+    })
+    .await
+    .unwrap();
+
+    // the entry is flushed as soon as the child is done, without waiting for the timeout
+    let result = q.drain();
+    assert_eq!(result.len(), 1);
+    let entry = test_util::to_test_entry(&result[0]);
+    assert_eq!(entry.metrics["A"], 7);
+
+    // the timer task stops once the entry is flushed, rather than waiting for the timeout
+    task::yield_now().await;
+    assert_eq!(
+        tokio::runtime::Handle::current()
+            .metrics()
+            .num_alive_tasks(),
+        0
+    );
+    sleep(Duration::from_secs(10)).await;
+    assert_eq!(q.drain().len(), 0);
+}
@@ -0,0 +1,77 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use metrique::unit_of_work::metrics;
+use metrique::writer::test_util::test_metric;
+
+mod prefixes {
+    pub const API: &str = "Api:";
+    pub const RETRY: &str = "retry_";
+}
+
+#[metrics(subfield)]
+struct RetryMetrics {
+    attempts: usize,
+}
+
+#[metrics(prefix = prefixes::API, rename_all = "PascalCase")]
+struct RequestMetrics {
+    request_count: usize,
+    // `name` is not prefixed, like with a literal prefix
+    #[metrics(name = "Errors")]
+    error_count: usize,
+    #[metrics(flatten, prefix = prefixes::RETRY)]
+    retry: RetryMetrics,
+}
+
+#[test]
+fn const_prefix_is_not_inflected() {
+    let entry = test_metric(RequestMetrics {
+        request_count: 1,
+        error_count: 2,
+        retry: RetryMetrics { attempts: 3 },
+    });
+    // the rest of the name is still inflected
+    assert_eq!(entry.metrics["Api:RequestCount"], 1);
+    assert_eq!(entry.metrics["Errors"], 2);
+    assert_eq!(entry.metrics["retry_Attempts"], 3);
+}
+
+#[test]
+fn const_prefix_field_names() {
+    assert_eq!(
+        RequestMetrics::FIELD_NAMES,
+        ["Api:RequestCount", "Errors", "retry_Attempts"]
+    );
+}
+
+#[metrics(subfield, prefix = prefixes::RETRY, rename_all = "kebab-case")]
+struct Nested {
+    backoff_count: usize,
+}
+
+#[metrics(rename_all = "PascalCase")]
+struct Parent {
+    #[metrics(flatten)]
+    nested: Nested,
+}
+
+#[test]
+fn const_prefix_in_subfield() {
+    let entry = test_metric(Parent {
+        nested: Nested { backoff_count: 4 },
+    });
+    assert_eq!(entry.metrics["retry_backoff-count"], 4);
+}
+
+#[metrics(tag(name = "op"), prefix = prefixes::API, rename_all = "snake_case")]
+enum Operation {
+    ReadData { count: u32 },
+}
+
+#[test]
+fn const_prefix_on_enum_tag() {
+    let entry = test_metric(Operation::ReadData { count: 5 });
+    assert_eq!(entry.values["Api:op"], "read_data");
+    assert_eq!(entry.metrics["Api:count"], 5);
+}
@@ -0,0 +1,28 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use metrique::RootEntry;
+use metrique::unit_of_work::metrics;
+use metrique::writer::sink::VecEntrySink;
+
+type RequestSink = VecEntrySink<RootEntry<RequestMetricsEntry>>;
+
+#[metrics(default_sink = RequestSink)]
+struct RequestMetrics {
+    operation: &'static str,
+}
+
+// the guard is named without a sink type, so this only compiles if the default sink is used
+fn start_request(sink: RequestSink) -> RequestMetricsGuard {
+    RequestMetrics { operation: "Get" }.append_on_drop(sink)
+}
+
+#[test]
+fn guard_uses_default_sink() {
+    let sink = RequestSink::default();
+    let guard = start_request(sink.clone());
+    let handle: RequestMetricsHandle = guard.handle();
+    drop(handle);
+
+    assert_eq!(sink.drain().len(), 1);
+}
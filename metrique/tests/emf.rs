@@ -131,3 +131,95 @@ fn test_vec_property_emits_json_array_in_emf() {
     assert_eq!(json["Plugins"], serde_json::json!(["auth", "cache"]));
     assert_eq!(json["RequestCount"], 5);
 }
+
+#[metrics(rename_all = "PascalCase")]
+struct RenamedMetrics {
+    #[metrics(timestamp)]
+    timestamp: SystemTime,
+    #[metrics(name = "ItemCount", alias = "NumItems")]
+    items: usize,
+    #[metrics(alias = "Op")]
+    operation: &'static str,
+}
+
+#[test]
+fn test_alias_emits_both_names_in_emf() {
+    let mut emf = Emf::all_validations("App".to_string(), vec![vec![]]);
+    let mut output = vec![];
+
+    emf.format(
+        &RootEntry::new(
+            RenamedMetrics {
+                timestamp: UNIX_EPOCH,
+                items: 3,
+                operation: "Get",
+            }
+            .close(),
+        ),
+        &mut output,
+    )
+    .unwrap();
+
+    let json: Value = serde_json::from_slice(&output).unwrap();
+    assert_eq!(json["ItemCount"], 3);
+    assert_eq!(json["NumItems"], 3);
+    assert_eq!(json["Operation"], "Get");
+    assert_eq!(json["Op"], "Get");
+    assert_eq!(
+        json["_aws"]["CloudWatchMetrics"][0]["Metrics"],
+        serde_json::json!([{"Name": "ItemCount"}, {"Name": "NumItems"}])
+    );
+}
+
+#[metrics(subfield, emf::dimension_sets = [["Operation"], ["Region"]], rename_all = "PascalCase")]
+struct RegionMetrics {
+    region: &'static str,
+    replicas: usize,
+}
+
+#[metrics(emf::dimension_sets = [["Status", "Operation"], ["Operation"]], rename_all = "PascalCase")]
+struct StackedDimensionsMetrics {
+    operation: &'static str,
+    #[metrics(timestamp)]
+    timestamp: SystemTime,
+    status: &'static str,
+    #[metrics(flatten)]
+    region: RegionMetrics,
+}
+
+#[test]
+fn test_stacked_dimension_sets_are_merged() {
+    let mut emf = Emf::all_validations("MyApp".to_string(), vec![vec![]]);
+    let mut output = vec![];
+
+    emf.format(
+        &RootEntry::new(
+            StackedDimensionsMetrics {
+                operation: "operation",
+                timestamp: UNIX_EPOCH,
+                status: "status",
+                region: RegionMetrics {
+                    region: "us-east-1",
+                    replicas: 3,
+                },
+            }
+            .close(),
+        ),
+        &mut output,
+    )
+    .unwrap();
+
+    let json: Value = serde_json::from_slice(&output).unwrap();
+    let dimensions: Vec<Vec<String>> =
+        serde_json::from_value(json["_aws"]["CloudWatchMetrics"][0]["Dimensions"].clone()).unwrap();
+    assert_eq!(
+        dimensions,
+        [
+            vec!["Status", "Operation"],
+            vec!["Operation"],
+            // `["Operation"]` is only included once
+            vec!["Region"],
+        ]
+    );
+    assert_eq!(json["Replicas"], 3);
+}
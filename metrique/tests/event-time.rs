@@ -0,0 +1,95 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::{Duration, SystemTime};
+
+use metrique::{
+    CloseValue, RootEntry,
+    test_util::to_test_entry,
+    unit_of_work::metrics,
+    writer::{Entry, EntryTimestamp},
+};
+
+/// An event time as received from an upstream system, in milliseconds since the Unix epoch.
+/// Not every event carries one.
+#[derive(Clone, Debug)]
+struct EventTime {
+    epoch_millis: Option<u64>,
+}
+
+impl EventTime {
+    fn at(epoch_millis: u64) -> Self {
+        Self {
+            epoch_millis: Some(epoch_millis),
+        }
+    }
+}
+
+impl EntryTimestamp for EventTime {
+    fn entry_timestamp(&self) -> Option<SystemTime> {
+        self.epoch_millis
+            .map(|millis| SystemTime::UNIX_EPOCH + Duration::from_millis(millis))
+    }
+}
+
+impl CloseValue for EventTime {
+    type Closed = Self;
+
+    fn close(self) -> Self::Closed {
+        self
+    }
+}
+
+#[metrics(rename_all = "PascalCase")]
+struct EventMetrics {
+    #[metrics(timestamp)]
+    event_time: EventTime,
+    processed: usize,
+}
+
+#[test]
+fn metrics_timestamp_from_event_time() {
+    let entry = to_test_entry(RootEntry::new(
+        EventMetrics {
+            event_time: EventTime::at(1_700_000_000_000),
+            processed: 3,
+        }
+        .close(),
+    ));
+    assert_eq!(
+        entry.timestamp,
+        Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000))
+    );
+    assert_eq!(entry.metrics["Processed"], 3);
+}
+
+#[test]
+fn missing_event_time_leaves_timestamp_unset() {
+    let entry = to_test_entry(RootEntry::new(
+        EventMetrics {
+            event_time: EventTime { epoch_millis: None },
+            processed: 3,
+        }
+        .close(),
+    ));
+    assert_eq!(entry.timestamp, None);
+}
+
+#[derive(Entry)]
+struct DerivedEventMetrics {
+    #[entry(timestamp)]
+    event_time: EventTime,
+    processed: usize,
+}
+
+#[test]
+fn derived_entry_timestamp_from_event_time() {
+    let entry = to_test_entry(DerivedEventMetrics {
+        event_time: EventTime::at(1_000),
+        processed: 1,
+    });
+    assert_eq!(
+        entry.timestamp,
+        Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1))
+    );
+}
@@ -0,0 +1,136 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::borrow::Cow;
+use std::time::{Duration, SystemTime};
+
+use metrique::writer::{Entry, EntryConfig, EntryWriter, Value};
+use metrique::{CloseValue, RootEntry, unit_of_work::metrics};
+
+/// Collects the names an entry writes, in order
+#[derive(Default)]
+struct NameCollector(Vec<String>);
+
+impl<'a> EntryWriter<'a> for NameCollector {
+    fn timestamp(&mut self, _timestamp: SystemTime) {}
+
+    fn value(&mut self, name: impl Into<Cow<'a, str>>, _value: &(impl Value + ?Sized)) {
+        self.0.push(name.into().into_owned());
+    }
+
+    fn config(&mut self, _config: &'a dyn EntryConfig) {}
+}
+
+fn written_names(entry: impl Entry) -> Vec<String> {
+    let mut collector = NameCollector::default();
+    entry.write(&mut collector);
+    collector.0
+}
+
+#[metrics(subfield)]
+#[derive(Default)]
+struct RetryMetrics {
+    attempts: usize,
+    #[metrics(name = "LastError")]
+    last_error: Option<&'static str>,
+}
+
+#[metrics(subfield, rename_all = "kebab-case")]
+#[derive(Default)]
+struct CacheMetrics {
+    hit_count: usize,
+    #[metrics(flatten, exact_prefix = "Inner:")]
+    retry: RetryMetrics,
+}
+
+#[metrics(rename_all = "PascalCase", prefix = "api_")]
+struct RequestMetrics {
+    #[metrics(timestamp)]
+    timestamp: SystemTime,
+    operation: &'static str,
+    #[metrics(name = "request_count", alias = "OldRequestCount")]
+    requests: usize,
+    latency: Duration,
+    #[metrics(flatten, prefix = "retry_")]
+    retry: RetryMetrics,
+    #[metrics(flatten)]
+    cache: Option<CacheMetrics>,
+    #[metrics(ignore)]
+    #[allow(dead_code)]
+    ignored: usize,
+}
+
+#[test]
+fn field_names_match_written_names() {
+    assert_eq!(
+        RequestMetrics::FIELD_NAMES,
+        [
+            "ApiOperation",
+            "request_count",
+            "OldRequestCount",
+            "ApiLatency",
+            "RetryAttempts",
+            "RetryLastError",
+            // the subfield's own `rename_all` takes precedence
+            "hit-count",
+            "Inner:attempts",
+            "Inner:LastError",
+        ]
+    );
+
+    let retry = || RetryMetrics {
+        attempts: 1,
+        last_error: Some("Throttled"),
+    };
+    let metrics = RequestMetrics {
+        timestamp: SystemTime::UNIX_EPOCH,
+        operation: "Get",
+        requests: 1,
+        latency: Duration::from_millis(3),
+        retry: retry(),
+        cache: Some(CacheMetrics {
+            hit_count: 2,
+            retry: retry(),
+        }),
+        ignored: 0,
+    };
+    assert_eq!(
+        written_names(RootEntry::new(metrics.close())),
+        RequestMetrics::FIELD_NAMES
+    );
+}
+
+#[metrics(value)]
+struct RequestCount(usize);
+
+#[test]
+fn field_names_of_value_are_empty() {
+    assert!(RequestCount::FIELD_NAMES.is_empty());
+}
+
+#[derive(metrique::writer::Entry)]
+struct CustomEntry {
+    custom: usize,
+}
+
+// names only known at runtime don't stop the struct from compiling, `FIELD_NAMES` is just not
+// available
+#[metrics]
+struct WithRuntimeNames {
+    #[metrics(flatten_entry, no_close)]
+    custom: CustomEntry,
+    #[metrics(flatten, prefix = "retry_", index)]
+    retries: Vec<RetryMetrics>,
+}
+
+#[test]
+fn runtime_names_still_compile() {
+    let names = written_names(RootEntry::new(
+        WithRuntimeNames {
+            custom: CustomEntry { custom: 1 },
+            retries: vec![RetryMetrics::default()],
+        }
+        .close(),
+    ));
+    assert_eq!(names, ["custom", "retry_0_attempts", "retry_0_LastError"]);
+}
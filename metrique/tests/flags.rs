@@ -275,3 +275,53 @@ fn force_flag_type_wrapper_still_works() {
     let hr_metric = metrics.iter().find(|m| m["Name"] == "HighRes").unwrap();
     assert_eq!(hr_metric["StorageResolution"], 1);
 }
+
+// --- EMF: emf::high_storage_resolution attribute ---
+
+#[metrics(
+    rename_all = "PascalCase",
+    emf::dimension_sets = [["Operation"]],
+)]
+struct HighResAttribute {
+    #[metrics(timestamp)]
+    timestamp: SystemTime,
+    operation: String,
+    #[metrics(emf::high_storage_resolution)]
+    queue_depth: u64,
+    #[metrics(emf::high_storage_resolution, unit = metrique::unit::Millisecond)]
+    latency: std::time::Duration,
+    normal_count: u64,
+}
+
+#[test]
+fn high_storage_resolution_attribute() {
+    let m = HighResAttribute {
+        timestamp: UNIX_EPOCH,
+        operation: "test".into(),
+        queue_depth: 7,
+        latency: std::time::Duration::from_millis(12),
+        normal_count: 3,
+    };
+    let closed = CloseValue::close(m);
+    let entry = RootEntry::new(closed);
+
+    let mut emf = Emf::all_validations("Test".to_string(), vec![vec!["Operation".to_string()]]);
+    let mut output = vec![];
+    emf.format(&entry, &mut output).unwrap();
+    let json: Value = serde_json::from_slice(&output).unwrap();
+
+    let metrics = json["_aws"]["CloudWatchMetrics"][0]["Metrics"]
+        .as_array()
+        .unwrap();
+    let queue_depth = metrics.iter().find(|m| m["Name"] == "QueueDepth").unwrap();
+    assert_eq!(queue_depth["StorageResolution"], 1);
+    let latency = metrics.iter().find(|m| m["Name"] == "Latency").unwrap();
+    assert_eq!(latency["StorageResolution"], 1);
+    assert_eq!(latency["Unit"], "Milliseconds");
+    let normal = metrics.iter().find(|m| m["Name"] == "NormalCount").unwrap();
+    assert!(normal.get("StorageResolution").is_none());
+
+    assert_eq!(json["QueueDepth"], 7);
+    assert_eq!(json["Latency"], 12);
+    assert_eq!(json["NormalCount"], 3);
+}
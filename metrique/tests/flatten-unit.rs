@@ -0,0 +1,84 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use metrique::unit::{Byte, Count, Millisecond};
+use metrique::writer::unit::{NegativeScale, PositiveScale};
+use metrique::writer::{Unit, test_util};
+use metrique::{CloseValue, RootEntry, unit_of_work::metrics};
+
+#[metrics(subfield)]
+struct Timings {
+    connect: u64,
+    first_byte: u64,
+    // already has a unit, which is kept
+    #[metrics(unit = Count)]
+    attempts: u64,
+    host: &'static str,
+}
+
+#[metrics(rename_all = "PascalCase")]
+struct RequestMetrics {
+    #[metrics(flatten, unit = Millisecond)]
+    timings: Timings,
+    #[metrics(flatten, prefix = "retry", index, unit = Byte)]
+    retries: Vec<Timings>,
+}
+
+fn timings(connect: u64) -> Timings {
+    Timings {
+        connect,
+        first_byte: connect + 10,
+        attempts: 1,
+        host: "example.com",
+    }
+}
+
+#[test]
+fn flatten_unit_applies_to_values_without_unit() {
+    let metrics = RequestMetrics {
+        timings: timings(5),
+        retries: vec![timings(7)],
+    };
+    let entry = test_util::to_test_entry(RootEntry::new(metrics.close()));
+
+    assert_eq!(entry.metrics["Connect"], 5);
+    assert_eq!(
+        entry.metrics["Connect"].unit,
+        Unit::Second(NegativeScale::Milli)
+    );
+    assert_eq!(entry.metrics["FirstByte"], 15);
+    assert_eq!(
+        entry.metrics["FirstByte"].unit,
+        Unit::Second(NegativeScale::Milli)
+    );
+    assert_eq!(entry.metrics["Attempts"].unit, Unit::Count);
+    assert_eq!(entry.values["Host"], "example.com");
+
+    assert_eq!(entry.metrics["Retry0Connect"], 7);
+    assert_eq!(
+        entry.metrics["Retry0Connect"].unit,
+        Unit::Byte(PositiveScale::One)
+    );
+    assert_eq!(entry.metrics["Retry0Attempts"].unit, Unit::Count);
+}
+
+#[metrics]
+struct WrappedMetrics {
+    #[metrics(flatten)]
+    timings: metrique::writer::entry::WithEntryUnit<Timings, Millisecond>,
+}
+
+#[test]
+fn with_entry_unit_subfield() {
+    let metrics = WrappedMetrics {
+        timings: timings(5).into(),
+    };
+    let entry = test_util::to_test_entry(RootEntry::new(metrics.close()));
+
+    assert_eq!(entry.metrics["connect"], 5);
+    assert_eq!(
+        entry.metrics["connect"].unit,
+        Unit::Second(NegativeScale::Milli)
+    );
+    assert_eq!(entry.metrics["attempts"].unit, Unit::Count);
+}
@@ -0,0 +1,123 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+
+use metrique::writer::value::{ConfiguredValueFormatter, ToString};
+use metrique::writer::{Entry, ValueWriter, test_util};
+use metrique::{CloseValue, RootEntry, unit_of_work::metrics};
+
+#[derive(Debug)]
+struct Precision {
+    digits: usize,
+}
+
+impl Precision {
+    fn new(digits: usize) -> Self {
+        Self { digits }
+    }
+}
+
+impl ConfiguredValueFormatter<f64> for Precision {
+    fn format_value(&self, writer: impl ValueWriter, value: &f64) {
+        writer.string(&format!("{value:.*}", self.digits));
+    }
+}
+
+/// A configured formatter with no configuration
+#[derive(Debug)]
+struct Percent;
+
+impl ConfiguredValueFormatter<f64> for Percent {
+    fn format_value(&self, writer: impl ValueWriter, value: &f64) {
+        writer.string(&format!("{}%", value * 100.0));
+    }
+}
+
+/// Writes strings with a prefix
+#[derive(Debug)]
+struct Prefixed {
+    prefix: &'static str,
+}
+
+impl ConfiguredValueFormatter<str> for Prefixed {
+    fn format_value(&self, writer: impl ValueWriter, value: &str) {
+        writer.string(&format!("{}{value}", self.prefix));
+    }
+}
+
+#[metrics(rename_all = "PascalCase")]
+#[derive(Debug)]
+struct RequestMetrics {
+    #[metrics(sample_group, format = Prefixed { prefix: "Api" })]
+    operation: &'static str,
+    #[metrics(format = Precision::new(3))]
+    ratio: f64,
+    #[metrics(format = Precision { digits: 1 })]
+    load: Option<Arc<f64>>,
+    #[metrics(format = Percent {})]
+    hit_rate: f64,
+    #[metrics(format = ToString)]
+    success: bool,
+}
+
+#[metrics(value)]
+struct Ratio(#[metrics(format = Precision::new(2))] f64);
+
+#[test]
+fn format_expression() {
+    let metrics = RequestMetrics {
+        operation: "Get",
+        ratio: 0.123456,
+        load: Some(Arc::new(1.25)),
+        hit_rate: 0.5,
+        success: true,
+    };
+    let entry = RootEntry::new(metrics.close());
+    // sample groups use the value, not the formatted value
+    assert_eq!(
+        Entry::sample_group(&entry).collect::<Vec<_>>(),
+        [("Operation".into(), "Get".into())]
+    );
+    let entry = test_util::to_test_entry(entry);
+    assert_eq!(entry.values["Operation"], "ApiGet");
+    assert_eq!(entry.values["Ratio"], "0.123");
+    assert_eq!(entry.values["Load"], "1.2");
+    assert_eq!(entry.values["HitRate"], "50%");
+    assert_eq!(entry.values["Success"], "true");
+
+    let metrics = RequestMetrics {
+        operation: "Put",
+        ratio: 1.0,
+        load: None,
+        hit_rate: 0.0,
+        success: false,
+    };
+    let entry = test_util::to_test_entry(RootEntry::new(metrics.close()));
+    assert!(!entry.values.contains_key("Load"));
+}
+
+#[test]
+fn format_expression_value() {
+    #[metrics]
+    struct Metrics {
+        ratio: Ratio,
+    }
+
+    let entry = test_util::to_test_entry(RootEntry::new(
+        Metrics {
+            ratio: Ratio(2.0 / 3.0),
+        }
+        .close(),
+    ));
+    assert_eq!(entry.values["ratio"], "0.67");
+}
+
+#[test]
+fn format_expression_is_zero_cost_for_unit_structs() {
+    // formatters with no configuration take no space in the entry
+    assert_eq!(
+        std::mem::size_of::<metrique::format::WithFormatter<f64, Percent>>(),
+        std::mem::size_of::<f64>()
+    );
+}
@@ -0,0 +1,59 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::{fmt::Debug, marker::PhantomData, time::Duration};
+
+use metrique::{CloseValue, RootEntry, unit_of_work::metrics, writer::test_util};
+
+trait Stage: Send + 'static {}
+
+struct Connect;
+impl Stage for Connect {}
+
+struct Transfer;
+impl Stage for Transfer {}
+
+/// A latency tagged with the stage it measures
+#[metrics(value)]
+struct Latency<S: Stage>(Duration, PhantomData<S>);
+
+impl<S: Stage> Latency<S> {
+    fn new(duration: Duration) -> Self {
+        Self(duration, PhantomData)
+    }
+}
+
+#[metrics(value)]
+struct Label<'a, T>(&'a str, PhantomData<T>)
+where
+    T: Debug;
+
+/// The lifetime is only used by an ignored field
+#[metrics(value)]
+struct Borrowed<'a>(usize, PhantomData<&'a ()>);
+
+#[metrics]
+struct Metrics<'a> {
+    connect: Latency<Connect>,
+    transfer: Latency<Transfer>,
+    label: Label<'a, u8>,
+    borrowed: Borrowed<'a>,
+}
+
+#[test]
+fn generic_value_newtypes() {
+    let label = String::from("download");
+    let entry = test_util::to_test_entry(RootEntry::new(
+        Metrics {
+            connect: Latency::new(Duration::from_millis(4)),
+            transfer: Latency::new(Duration::from_millis(20)),
+            label: Label(&label, PhantomData),
+            borrowed: Borrowed(3, PhantomData),
+        }
+        .close(),
+    ));
+    assert_eq!(entry.metrics["connect"], 4);
+    assert_eq!(entry.metrics["transfer"], 20);
+    assert_eq!(entry.values["label"], "download");
+    assert_eq!(entry.metrics["borrowed"], 3);
+}
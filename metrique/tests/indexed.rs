@@ -0,0 +1,96 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::Duration;
+
+use metrique::unit::Millisecond;
+use metrique::writer::test_util;
+use metrique::{CloseValue, RootEntry, unit_of_work::metrics};
+
+#[metrics(subfield)]
+struct Attempt {
+    #[metrics(unit = Millisecond)]
+    latency: Duration,
+    status: &'static str,
+}
+
+fn attempt(millis: u64, status: &'static str) -> Attempt {
+    Attempt {
+        latency: Duration::from_millis(millis),
+        status,
+    }
+}
+
+#[metrics(rename_all = "PascalCase")]
+struct RequestMetrics {
+    #[metrics(flatten, index, prefix = "retry")]
+    retries: Vec<Attempt>,
+    #[metrics(flatten, index, exact_prefix = "Shard")]
+    shards: [Attempt; 2],
+    operation: &'static str,
+}
+
+#[test]
+fn writes_elements_with_indexed_prefix() {
+    let metrics = RequestMetrics {
+        retries: vec![attempt(5, "Throttled"), attempt(7, "Ok")],
+        shards: [attempt(1, "Ok"), attempt(2, "Ok")],
+        operation: "Get",
+    };
+    let entry = test_util::to_test_entry(RootEntry::new(metrics.close()));
+
+    assert_eq!(entry.metrics["Retry0Latency"], 5);
+    assert_eq!(entry.values["Retry0Status"], "Throttled");
+    assert_eq!(entry.metrics["Retry1Latency"], 7);
+    assert_eq!(entry.values["Retry1Status"], "Ok");
+    assert_eq!(entry.metrics["Shard0Latency"], 1);
+    assert_eq!(entry.metrics["Shard1Latency"], 2);
+    assert_eq!(entry.values["Operation"], "Get");
+}
+
+#[test]
+fn empty_collection_writes_nothing() {
+    let metrics = RequestMetrics {
+        retries: vec![],
+        shards: [attempt(1, "Ok"), attempt(2, "Ok")],
+        operation: "Get",
+    };
+    let entry = test_util::to_test_entry(RootEntry::new(metrics.close()));
+
+    assert!(!entry.metrics.keys().any(|name| name.starts_with("Retry")));
+    assert!(!entry.values.keys().any(|name| name.starts_with("Retry")));
+    assert_eq!(entry.metrics["Shard0Latency"], 1);
+}
+
+#[metrics(rename_all = "snake_case")]
+struct SnakeMetrics {
+    #[metrics(flatten, index, prefix = "Retry")]
+    retries: Vec<Attempt>,
+}
+
+#[metrics(rename_all = "kebab-case")]
+struct KebabMetrics {
+    #[metrics(flatten, index, prefix = "retry")]
+    retries: Vec<Attempt>,
+}
+
+#[test]
+fn index_follows_rename_all() {
+    let entry = test_util::to_test_entry(RootEntry::new(
+        SnakeMetrics {
+            retries: vec![attempt(5, "Throttled")],
+        }
+        .close(),
+    ));
+    assert_eq!(entry.metrics["retry_0_latency"], 5);
+    assert_eq!(entry.values["retry_0_status"], "Throttled");
+
+    let entry = test_util::to_test_entry(RootEntry::new(
+        KebabMetrics {
+            retries: vec![attempt(5, "Throttled")],
+        }
+        .close(),
+    ));
+    assert_eq!(entry.metrics["retry-0-latency"], 5);
+    assert_eq!(entry.values["retry-0-status"], "Throttled");
+}
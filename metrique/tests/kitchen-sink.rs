@@ -380,6 +380,36 @@ fn arc_atomics_flush_as_expected() {
     assert_eq!(entry.metrics["F"], 1);
 }
 
+#[metrics(rename_all = "PascalCase")]
+struct SharedAtomic {
+    requests: Arc<AtomicU64>,
+}
+
+#[test]
+fn shared_atomic_reports_running_value() {
+    let vec_sink = VecEntrySink::new();
+    let requests = Arc::new(AtomicU64::new(0));
+
+    let first = SharedAtomic {
+        requests: requests.clone(),
+    }
+    .append_on_drop(vec_sink.clone());
+    let second = SharedAtomic {
+        requests: requests.clone(),
+    }
+    .append_on_drop(vec_sink.clone());
+    requests.fetch_add(2, Ordering::Relaxed);
+    drop(first);
+    requests.fetch_add(3, Ordering::Relaxed);
+    drop(second);
+
+    let entries = vec_sink.drain();
+    assert_eq!(test_util::to_test_entry(&entries[0]).metrics["Requests"], 2);
+    // closing the first entry did not reset the shared value
+    assert_eq!(test_util::to_test_entry(&entries[1]).metrics["Requests"], 5);
+    assert_eq!(requests.load(Ordering::Relaxed), 5);
+}
+
 #[test]
 fn multiple_sinks() {
     global_entry_sink! { MetricsA };
@@ -0,0 +1,84 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use metrique::unit_of_work::metrics;
+use metrique::writer::sink::VecEntrySink;
+use metrique::writer::test_util;
+
+#[metrics(rename_all = "PascalCase")]
+#[derive(Default)]
+struct RequestMetrics {
+    operation: &'static str,
+    status: usize,
+}
+
+#[metrics(subfield)]
+#[derive(Default)]
+struct DownstreamMetrics {
+    downstream_calls: usize,
+    downstream_errors: usize,
+}
+
+#[metrics(subfield_owned)]
+struct CacheMetrics {
+    cache_hits: usize,
+}
+
+#[test]
+fn merge_into_appends_subfield_to_entry() {
+    let q = VecEntrySink::new();
+    let mut metrics = RequestMetrics::default().append_on_drop(q.clone());
+    metrics.operation = "Get";
+
+    let downstream = DownstreamMetrics {
+        downstream_calls: 3,
+        downstream_errors: 1,
+    };
+    let mut metrics = downstream.merge_into(metrics);
+    // the parent can still be modified after merging
+    metrics.status = 200;
+
+    // nothing is written until the entry is dropped
+    assert_eq!(q.drain().len(), 0);
+    drop(metrics);
+
+    let result = q.drain();
+    assert_eq!(result.len(), 1);
+    let entry = test_util::to_test_entry(&result[0]);
+    assert_eq!(entry.values["Operation"], "Get");
+    assert_eq!(entry.metrics["Status"], 200);
+    assert_eq!(entry.metrics["downstream_calls"], 3);
+    assert_eq!(entry.metrics["downstream_errors"], 1);
+}
+
+#[test]
+fn merge_into_multiple_subfields() {
+    let q = VecEntrySink::new();
+    let metrics = RequestMetrics::default().append_on_drop(q.clone());
+    let metrics = DownstreamMetrics::default().merge_into(metrics);
+    let metrics = CacheMetrics { cache_hits: 7 }.merge_into(metrics);
+    drop(metrics);
+
+    let result = q.drain();
+    assert_eq!(result.len(), 1);
+    let entry = test_util::to_test_entry(&result[0]);
+    assert_eq!(entry.metrics["Status"], 0);
+    assert_eq!(entry.metrics["downstream_calls"], 0);
+    assert_eq!(entry.metrics["cache_hits"], 7);
+}
+
+#[test]
+fn merge_into_waits_for_flush_guards() {
+    let q = VecEntrySink::new();
+    let metrics = RequestMetrics::default().append_on_drop(q.clone());
+    let guard = metrics.flush_guard();
+    let metrics = CacheMetrics { cache_hits: 1 }.merge_into(metrics);
+    drop(metrics);
+    assert_eq!(q.drain().len(), 0);
+
+    drop(guard);
+    let result = q.drain();
+    assert_eq!(result.len(), 1);
+    let entry = test_util::to_test_entry(&result[0]);
+    assert_eq!(entry.metrics["cache_hits"], 1);
+}
@@ -0,0 +1,85 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::marker::PhantomData;
+
+use metrique::test_util::{TestEntrySink, test_entry_sink};
+use metrique::{CloseValue, RootEntry, unit_of_work::metrics, writer::test_util};
+
+struct Pending;
+struct Sent;
+
+#[metrics(subfield)]
+struct Attempt<S> {
+    count: usize,
+    state: PhantomData<S>,
+}
+
+/// A request whose type tracks whether it was sent
+#[metrics(rename_all = "PascalCase")]
+struct Request<S> {
+    operation: &'static str,
+    #[metrics(flatten, prefix = "attempt_")]
+    attempt: Attempt<S>,
+    state: PhantomData<S>,
+    // concrete markers are skipped too
+    marker: PhantomData<Sent>,
+}
+
+impl Request<Pending> {
+    fn new(operation: &'static str) -> Self {
+        Request {
+            operation,
+            attempt: Attempt {
+                count: 0,
+                state: PhantomData,
+            },
+            state: PhantomData,
+            marker: PhantomData,
+        }
+    }
+
+    fn send(self) -> Request<Sent> {
+        Request {
+            operation: self.operation,
+            attempt: Attempt {
+                count: self.attempt.count + 1,
+                state: PhantomData,
+            },
+            state: PhantomData,
+            marker: PhantomData,
+        }
+    }
+}
+
+#[metrics(value)]
+struct Tagged<S>(usize, PhantomData<S>);
+
+#[test]
+fn phantom_data_fields_are_skipped() {
+    let TestEntrySink { inspector, sink } = test_entry_sink();
+    let request = Request::new("Get").send().append_on_drop(sink);
+    drop(request);
+
+    let entry = inspector.get(0);
+    assert_eq!(entry.values["Operation"], "Get");
+    assert_eq!(entry.metrics["AttemptCount"], 1);
+    assert_eq!(entry.metrics.len(), 1);
+    assert_eq!(entry.values.len(), 1);
+}
+
+#[test]
+fn phantom_data_in_value() {
+    #[metrics]
+    struct Metrics {
+        tagged: Tagged<Pending>,
+    }
+
+    let entry = test_util::to_test_entry(RootEntry::new(
+        Metrics {
+            tagged: Tagged(3, PhantomData),
+        }
+        .close(),
+    ));
+    assert_eq!(entry.metrics["tagged"], 3);
+}
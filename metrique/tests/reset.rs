@@ -0,0 +1,91 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use metrique::timers::{Stopwatch, Timer, Timestamp};
+use metrique::unit::Millisecond;
+use metrique::writer::test_util::to_test_entry;
+use metrique::{CloseValue, Counter, RootEntry, unit_of_work::metrics};
+use metrique_timesource::{TimeSource, fakes::ManuallyAdvancedTimeSource, set_time_source};
+
+#[metrics(subfield, reset)]
+#[derive(Default)]
+struct CacheMetrics {
+    hits: usize,
+    misses: Counter,
+}
+
+// closed by reference, so a single struct can be closed once per iteration
+#[metrics(subfield, reset, rename_all = "PascalCase")]
+struct IterationMetrics {
+    #[metrics(timestamp)]
+    timestamp: Timestamp,
+    #[metrics(no_reset)]
+    operation: &'static str,
+    #[metrics(unit = Millisecond)]
+    latency: Timer,
+    #[metrics(unit = Millisecond)]
+    backoff: Stopwatch,
+    #[metrics(flatten, prefix = "cache_")]
+    cache: CacheMetrics,
+    #[metrics(default)]
+    retries: Option<usize>,
+    #[metrics(ignore)]
+    iteration: usize,
+}
+
+#[test]
+fn reset_reuses_struct_across_iterations() {
+    let clock = ManuallyAdvancedTimeSource::at_time(UNIX_EPOCH);
+    let _guard = set_time_source(TimeSource::custom(clock.clone()));
+
+    let mut metrics = IterationMetrics {
+        timestamp: Timestamp::now(),
+        operation: "Poll",
+        latency: Timer::start_now(),
+        backoff: Stopwatch::new(),
+        cache: CacheMetrics::default(),
+        retries: None,
+        iteration: 0,
+    };
+
+    let mut entries = vec![];
+    for iteration in 1..=3u64 {
+        clock.update_time(UNIX_EPOCH + Duration::from_secs(iteration));
+        metrics.reset();
+        metrics.iteration += 1;
+        metrics.cache.hits += 1;
+        metrics.cache.misses.increment();
+        if iteration == 2 {
+            metrics.retries = Some(1);
+            let _backoff = metrics.backoff.start();
+            clock.update_instant(Duration::from_millis(5));
+        }
+        clock.update_instant(Duration::from_millis(10));
+        entries.push(to_test_entry(RootEntry::new((&metrics).close())));
+        // time between iterations is not recorded, since the timer restarts on reset
+        clock.update_instant(Duration::from_millis(50));
+    }
+
+    // `ignore` fields are not reset
+    assert_eq!(metrics.iteration, 3);
+    for (iteration, entry) in (1..=3u64).zip(&entries) {
+        assert_eq!(
+            entry.timestamp,
+            Some(UNIX_EPOCH + Duration::from_secs(iteration))
+        );
+        assert_eq!(entry.values["Operation"], "Poll");
+        assert_eq!(entry.metrics["CacheHits"].as_u64(), 1);
+        assert_eq!(entry.metrics["CacheMisses"].as_u64(), 1);
+    }
+    assert_eq!(entries[0].metrics["Latency"].as_u64(), 10);
+    assert_eq!(entries[1].metrics["Latency"].as_u64(), 15);
+    assert_eq!(entries[2].metrics["Latency"].as_u64(), 10);
+    assert_eq!(entries[1].metrics["Backoff"].as_u64(), 5);
+    assert_eq!(entries[1].metrics["Retries"].as_u64(), 1);
+    for entry in [&entries[0], &entries[2]] {
+        assert!(!entry.metrics.contains_key("Backoff"));
+        assert!(!entry.metrics.contains_key("Retries"));
+    }
+}
@@ -98,3 +98,85 @@ fn test_sample_group_flatten_entry() {
     assert_eq!(inspector.get(0).values["Operation"], "CountGeese");
     assert_eq!(inspector.get(0).values["status"], "FAILURE");
 }
+
+#[metrics(rename_all = "PascalCase")]
+struct OptionalMetric {
+    bird_species: &'static str,
+    #[metrics(flatten)]
+    general: Option<GeneralMetrics>,
+}
+
+#[test]
+fn test_sample_group_optional_flatten() {
+    let metric = OptionalMetric {
+        bird_species: "Mallard",
+        general: Some(GeneralMetrics {
+            operation: Operation::CountDucks,
+            api_status: Status { status: "SUCCESS" },
+        }),
+    };
+    let entry = RootEntry::new(metric.close());
+    let sample_group = entry
+        .sample_group()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sample_group,
+        vec![
+            ("Operation".to_string(), "CountDucks".to_string()),
+            ("APIStatus".to_string(), "SUCCESS".to_string())
+        ]
+    );
+
+    let metric = OptionalMetric {
+        bird_species: "Mallard",
+        general: None,
+    };
+    let entry = RootEntry::new(metric.close());
+    assert_eq!(entry.sample_group().count(), 0);
+    let TestEntrySink { inspector, sink } = test_entry_sink();
+    sink.append(entry);
+    let entry = inspector.get(0);
+    assert_eq!(entry.values["BirdSpecies"], "Mallard");
+    assert!(!entry.values.contains_key("Operation"));
+    assert!(!entry.values.contains_key("APIStatus"));
+}
+
+#[derive(Entry)]
+pub struct HttpStatusEntry {
+    #[entry(sample_group)]
+    status_code: u16,
+}
+
+#[metrics(rename_all = "PascalCase")]
+struct NumericMetric {
+    #[metrics(sample_group)]
+    status_code: u16,
+    #[metrics(flatten_entry, no_close)]
+    upstream: HttpStatusEntry,
+}
+
+#[test]
+fn test_sample_group_numeric() {
+    let metric = NumericMetric {
+        status_code: 200,
+        upstream: HttpStatusEntry { status_code: 503 },
+    };
+    let entry = RootEntry::new(metric.close());
+    let sample_group = entry
+        .sample_group()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sample_group,
+        vec![
+            ("StatusCode".to_string(), "200".to_string()),
+            ("status_code".to_string(), "503".to_string())
+        ]
+    );
+    // the field is still written as a metric
+    let TestEntrySink { inspector, sink } = test_entry_sink();
+    sink.append(entry);
+    assert_eq!(inspector.get(0).metrics["StatusCode"], 200);
+    assert_eq!(inspector.get(0).metrics["status_code"], 503);
+}
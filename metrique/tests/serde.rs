@@ -0,0 +1,145 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Tests for the `Serialize` impl generated for entries when the `serde` feature is enabled.
+
+use std::time::{Duration, SystemTime};
+
+use metrique::CloseValue;
+use metrique::serialize::SerializeEntry;
+use metrique::unit::{Byte, Millisecond};
+use metrique::unit_of_work::metrics;
+use metrique::writer::value::WithDimensions;
+use metrique::writer::{MetricFlags, Observation, Unit, Value, ValueWriter};
+
+#[metrics(value(string), rename_all = "snake_case")]
+enum Operation {
+    CountDucks,
+}
+
+#[metrics(subfield)]
+pub struct Nested {
+    #[metrics(unit = Byte)]
+    bytes_read: usize,
+    #[metrics(name = "CustomName")]
+    custom: u32,
+}
+
+#[metrics(rename_all = "PascalCase", emf::dimension_sets = [["Operation"]])]
+struct RequestMetrics {
+    #[metrics(timestamp)]
+    timestamp: SystemTime,
+    operation: Operation,
+    #[metrics(unit = Millisecond)]
+    latency: Duration,
+    success: bool,
+    missing: Option<u32>,
+    #[metrics(flatten, prefix = "nested_")]
+    nested: Nested,
+    #[metrics(no_close)]
+    with_dimensions: WithDimensions<u32, 1>,
+    #[metrics(no_close)]
+    histogram: Histogram,
+}
+
+struct Histogram(Vec<Observation>);
+
+impl Value for Histogram {
+    fn write(&self, writer: impl ValueWriter) {
+        writer.metric(self.0.iter().copied(), Unit::None, [], MetricFlags::empty())
+    }
+}
+
+fn request_metrics() -> RequestMetricsEntry {
+    RequestMetrics {
+        timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000),
+        operation: Operation::CountDucks,
+        latency: Duration::from_micros(1500),
+        success: true,
+        missing: None,
+        nested: Nested {
+            bytes_read: 1024,
+            custom: 7,
+        },
+        with_dimensions: WithDimensions::new(3, "Region", "us-east-1"),
+        histogram: Histogram(vec![
+            Observation::Unsigned(1),
+            Observation::Floating(2.5),
+            Observation::Repeated {
+                total: 10.0,
+                occurrences: 4,
+            },
+        ]),
+    }
+    .close()
+}
+
+#[test]
+fn serialize_entry() {
+    let json = serde_json::to_string_pretty(&request_metrics()).unwrap();
+    insta::assert_snapshot!(json);
+}
+
+#[test]
+fn serialize_entry_with_units() {
+    let entry = request_metrics();
+    let json = serde_json::to_string_pretty(&SerializeEntry::new(&entry).with_units(true)).unwrap();
+    insta::assert_snapshot!(json);
+}
+
+#[metrics(tag(name = "Operation"), rename_all = "kebab-case")]
+enum EntryEnum {
+    Read { bytes_read: usize },
+    Write(#[metrics(flatten)] Nested),
+}
+
+#[test]
+fn serialize_entry_enum() {
+    let read = serde_json::to_value(EntryEnum::Read { bytes_read: 5 }.close()).unwrap();
+    assert_eq!(
+        read,
+        serde_json::json!({"operation": "read", "bytes-read": 5})
+    );
+
+    let write = serde_json::to_value(
+        EntryEnum::Write(Nested {
+            bytes_read: 6,
+            custom: 1,
+        })
+        .close(),
+    )
+    .unwrap();
+    assert_eq!(
+        write,
+        serde_json::json!({"operation": "write", "bytes-read": 6, "CustomName": 1})
+    );
+}
+
+#[metrics]
+struct Invalid {
+    #[metrics(no_close)]
+    value: InvalidValue,
+}
+
+struct InvalidValue;
+
+impl Value for InvalidValue {
+    fn write(&self, writer: impl ValueWriter) {
+        writer.invalid("not a number")
+    }
+}
+
+#[test]
+fn serialize_invalid_value_fails() {
+    let err = serde_json::to_value(
+        Invalid {
+            value: InvalidValue,
+        }
+        .close(),
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("invalid value for metric `value`"),
+        "{err}"
+    );
+}
@@ -0,0 +1,93 @@
+// Copyright Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::time::Duration;
+
+use metrique::unit::Microsecond;
+use metrique::writer::test_util;
+use metrique::{CloseValue, RootEntry, unit_of_work::metrics};
+
+fn is_zero(duration: &Duration) -> bool {
+    duration.is_zero()
+}
+
+fn no_retries(retries: &RetryMetrics) -> bool {
+    retries.attempts == 0
+}
+
+#[metrics(subfield)]
+struct RetryMetrics {
+    attempts: usize,
+    #[metrics(skip_if = is_zero)]
+    backoff: Duration,
+}
+
+#[metrics(rename_all = "PascalCase")]
+struct RequestMetrics {
+    operation: &'static str,
+    #[metrics(skip_if = is_zero, unit = Microsecond)]
+    cache_miss_latency: Duration,
+    #[metrics(flatten, prefix = "retry_", skip_if = no_retries)]
+    retry: RetryMetrics,
+}
+
+#[metrics(rename_all = "PascalCase")]
+enum OperationMetrics {
+    Read {
+        #[metrics(skip_if = is_zero)]
+        cache_miss_latency: Duration,
+    },
+}
+
+#[test]
+fn skip_if_skips_fields() {
+    let metrics = RequestMetrics {
+        operation: "Get",
+        cache_miss_latency: Duration::ZERO,
+        retry: RetryMetrics {
+            attempts: 0,
+            backoff: Duration::from_millis(5),
+        },
+    };
+    let entry = test_util::to_test_entry(RootEntry::new(metrics.close()));
+    assert_eq!(entry.values["Operation"], "Get");
+    assert!(!entry.metrics.contains_key("CacheMissLatency"));
+    assert!(!entry.metrics.contains_key("RetryAttempts"));
+    assert!(!entry.metrics.contains_key("RetryBackoff"));
+}
+
+#[test]
+fn skip_if_keeps_fields() {
+    let metrics = RequestMetrics {
+        operation: "Get",
+        cache_miss_latency: Duration::from_micros(300),
+        retry: RetryMetrics {
+            attempts: 2,
+            backoff: Duration::ZERO,
+        },
+    };
+    let entry = test_util::to_test_entry(RootEntry::new(metrics.close()));
+    assert_eq!(entry.metrics["CacheMissLatency"], 300);
+    assert_eq!(entry.metrics["RetryAttempts"], 2);
+    // skipped inside the subfield
+    assert!(!entry.metrics.contains_key("RetryBackoff"));
+}
+
+#[test]
+fn skip_if_in_enum_variant() {
+    let entry = test_util::to_test_entry(RootEntry::new(
+        OperationMetrics::Read {
+            cache_miss_latency: Duration::ZERO,
+        }
+        .close(),
+    ));
+    assert!(!entry.metrics.contains_key("CacheMissLatency"));
+
+    let entry = test_util::to_test_entry(RootEntry::new(
+        OperationMetrics::Read {
+            cache_miss_latency: Duration::from_millis(2),
+        }
+        .close(),
+    ));
+    assert_eq!(entry.metrics["CacheMissLatency"], 2);
+}
@@ -139,3 +139,56 @@ fn value_string_user_derives_after_metrics_preserved() {
     let closed = metrique::CloseValue::close(p);
     assert_eq!(format!("{:?}", closed), "Low");
 }
+
+#[metrics(value(string), derive_eq)]
+enum Status {
+    Ok,
+    Error,
+}
+
+#[metrics(value, derive_eq)]
+#[derive(Debug)]
+struct Latency {
+    #[metrics(unit = metrique::unit::Millisecond)]
+    millis: u64,
+}
+
+#[test]
+fn derive_eq_value_types() {
+    assert_eq!(Status::Ok.close(), Status::Ok.close());
+    assert_ne!(Status::Ok.close(), Status::Error.close());
+
+    let latency = |millis| Latency { millis };
+    assert_eq!(latency(5).close(), latency(5).close());
+    assert_ne!(latency(5).close(), latency(6).close());
+}
+
+#[metrics(value(string), rename_all = "SCREAMING_SNAKE_CASE")]
+enum RequestState {
+    InFlight,
+    TimedOut,
+    #[metrics(name = "done")]
+    Completed,
+}
+
+#[metrics(rename_all = "PascalCase")]
+struct StateMetrics {
+    first_state: RequestState,
+    second_state: RequestState,
+    last_state: RequestState,
+}
+
+#[test]
+fn screaming_snake_case_variants() {
+    let metrics = StateMetrics {
+        first_state: RequestState::InFlight,
+        second_state: RequestState::TimedOut,
+        last_state: RequestState::Completed,
+    };
+    let entry = test_util::to_test_entry(RootEntry::new(metrics.close()));
+    assert_eq!(entry.values["FirstState"], "IN_FLIGHT");
+    assert_eq!(entry.values["SecondState"], "TIMED_OUT");
+    // an explicit name takes precedence over rename_all
+    assert_eq!(entry.values["LastState"], "done");
+    assert_eq!(<&str>::from(RequestState::TimedOut), "TIMED_OUT");
+}
@@ -3,18 +3,22 @@
 
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-use metrique::writer::BoxEntrySink;
 use metrique::writer::test_util::{Inspector, TestEntrySink, test_entry_sink, to_test_entry};
+use metrique::writer::unit::NegativeScale;
+use metrique::writer::{BoxEntrySink, Unit};
 use metrique::{
     CloseValue, LazySlot, OnParentDrop, RootEntry,
     timers::{
-        EpochMicros, EpochMillis, EpochSeconds, Stopwatch, Timer, Timestamp, TimestampOnClose,
+        EpochMicros, EpochMillis, EpochSeconds, PhasedTimer, Stopwatch, TimedClose, Timer,
+        Timestamp, TimestampOnClose,
     },
     unit::{Millisecond, Second},
     unit_of_work::metrics,
 };
 use metrique_timesource::{
-    ThreadLocalTimeSourceGuard, TimeSource, fakes::StaticTimeSource, set_time_source,
+    ThreadLocalTimeSourceGuard, TimeSource,
+    fakes::{ManuallyAdvancedTimeSource, StaticTimeSource},
+    set_time_source,
 };
 
 #[metrics(rename_all = "PascalCase")]
@@ -176,6 +180,83 @@ fn timestamp_format_test() {
     assert_eq!(entry.values["micros"], "1001001");
 }
 
+#[metrics(rename_all = "PascalCase")]
+struct PhasedMetrics {
+    operation: &'static str,
+    #[metrics(flatten)]
+    latency: PhasedTimer,
+    #[metrics(flatten, prefix = "retry_")]
+    retry_latency: PhasedTimer,
+}
+
+#[test]
+fn phased_timer_writes_phases_and_total() {
+    let clock = ManuallyAdvancedTimeSource::at_time(UNIX_EPOCH);
+    let _guard = set_time_source(TimeSource::custom(clock.clone()));
+    let mut metrics = PhasedMetrics {
+        operation: "Query",
+        latency: PhasedTimer::start_now("total_time"),
+        retry_latency: PhasedTimer::start_now("total_time"),
+    };
+    metrics.latency.switch_phase("auth_time");
+    metrics.retry_latency.switch_phase("backoff");
+    clock.update_instant(Duration::from_millis(5));
+    metrics.latency.switch_phase("query_time");
+    clock.update_instant(Duration::from_millis(20));
+
+    let entry = to_test_entry(RootEntry::new(metrics.close()));
+    assert_eq!(entry.values["Operation"], "Query");
+    // phase and total names are inflected like field names
+    assert_eq!(entry.metrics["AuthTime"].as_u64(), 5);
+    assert_eq!(entry.metrics["QueryTime"].as_u64(), 20);
+    assert_eq!(entry.metrics["TotalTime"].as_u64(), 25);
+    assert_eq!(
+        entry.metrics["TotalTime"].unit,
+        Unit::Second(NegativeScale::Milli)
+    );
+    // and get the prefix of the field they are flattened into
+    assert_eq!(entry.metrics["RetryBackoff"].as_u64(), 25);
+    assert_eq!(entry.metrics["RetryTotalTime"].as_u64(), 25);
+}
+
+#[metrics(subfield)]
+struct CacheMetrics {
+    hits: usize,
+    #[metrics(unit = Millisecond)]
+    lookup: Timer,
+}
+
+#[metrics(rename_all = "PascalCase")]
+struct TimedCloseMetrics {
+    operation: &'static str,
+    #[metrics(flatten)]
+    cache: TimedClose<CacheMetrics>,
+}
+
+#[test]
+fn timed_close_writes_close_duration() {
+    let mut metrics = TimedCloseMetrics {
+        operation: "Get",
+        cache: TimedClose::new(
+            CacheMetrics {
+                hits: 0,
+                lookup: Timer::start_now(),
+            },
+            "cache_close_time",
+        ),
+    };
+    metrics.cache.hits += 2;
+
+    let entry = to_test_entry(RootEntry::new(metrics.close()));
+    assert_eq!(entry.values["Operation"], "Get");
+    // fields of the inner entry and the close duration are inflected as usual
+    assert_eq!(entry.metrics["Hits"], 2);
+    assert!(entry.metrics.contains_key("Lookup"));
+    let close_time = &entry.metrics["CacheCloseTime"];
+    assert!(close_time.as_f64() >= 0.0);
+    assert_eq!(close_time.unit, Unit::Second(NegativeScale::Milli));
+}
+
 fn to_micros(ts: SystemTime) -> String {
     ts.duration_since(UNIX_EPOCH)
         .unwrap()
@@ -78,7 +78,7 @@ enum EntryEnumTupleFieldWithTimestamp {
 // Entry enum tuple field with incompatible attributes should error
 #[metrics]
 enum EntryEnumTupleIncompatibleAttrs {
-    Variant(#[metrics(flatten, unit = metrique::writer::unit::Millisecond)] u32),
+    Variant(#[metrics(flatten_entry, unit = metrique::writer::unit::Millisecond)] u32),
 }
 
 // Nested enum/struct scenarios with fields that don't implement CloseValue by ref: